@@ -11,9 +11,12 @@ use std::{
 };
 
 use rut::{
-    add, cli, commit, diff, init, log,
-    output::{Color, OutputWriter, Style},
-    restore, rm, status,
+    add, cat_file, cli, commit, diff, difftool, fast_export, fast_import, hash_object, init, log,
+    merge,
+    output::{Color, NoProgress, OutputWriter, Style},
+    prompt::LinePrompt,
+    refs::{RefHandler, Revision},
+    restore, rev_walk, rm, status,
     workspace::Repository,
 };
 
@@ -23,6 +26,23 @@ pub fn run_command<S: Into<OsString> + Clone + From<&'static str>>(
     args: Vec<S>,
     repository: &Repository,
 ) -> rut::Result<String> {
+    run_command_in_directory(args, repository.worktree().root())
+}
+
+/// Run the command as if the user was working in the given directory.
+pub fn run_command_in_directory<S: Into<OsString> + Clone + From<&'static str>>(
+    args: Vec<S>,
+    directory: &Path,
+) -> rut::Result<String> {
+    let (_, output) = run_command_with_exit_status(args, directory)?;
+    Ok(output)
+}
+
+/// Run the command in the given directory, and return its exit status along with its output.
+pub fn run_command_with_exit_status<S: Into<OsString> + Clone + From<&'static str>>(
+    args: Vec<S>,
+    directory: &Path,
+) -> rut::Result<(i32, String)> {
     let mut writer = CapturingOutputWriter {
         output: String::new(),
     };
@@ -39,8 +59,8 @@ pub fn run_command<S: Into<OsString> + Clone + From<&'static str>>(
         complete_args
     };
 
-    cli::run_command(complete_args, repository.worktree().root(), &mut writer)?;
-    Ok(writer.output)
+    let exit_status = cli::run_command(complete_args, directory, &mut writer)?;
+    Ok((exit_status, writer.output))
 }
 
 pub fn run_command_string<S: AsRef<str>>(args: S, repository: &Repository) -> rut::Result<String> {
@@ -50,6 +70,24 @@ pub fn run_command_string<S: AsRef<str>>(args: S, repository: &Repository) -> ru
     Ok(result)
 }
 
+pub fn run_command_string_with_exit_status<S: AsRef<str>>(
+    args: S,
+    repository: &Repository,
+) -> rut::Result<(i32, String)> {
+    let args = shlex::split(args.as_ref())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Failed to split arguments"))?;
+    run_command_with_exit_status(args, repository.worktree().root())
+}
+
+pub fn run_command_string_in_directory<S: AsRef<str>>(
+    args: S,
+    directory: &Path,
+) -> rut::Result<String> {
+    let args = shlex::split(args.as_ref())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Failed to split arguments"))?;
+    run_command_in_directory(args, directory)
+}
+
 pub fn rut_commit_with_output_capture(
     commit_message: &str,
     repository: &Repository,
@@ -76,16 +114,7 @@ pub fn rut_commit(commit_message: &str, repository: &Repository) -> rut::Result<
     // sleep a little to ensure that we get a reasonably strict "happens-after" relationship the
     // commit and anything that follows it
     thread::sleep(std::time::Duration::from_millis(50));
-    Ok(get_head_commit(&repository.git_dir()))
-}
-
-fn get_head_commit(git_dir: &PathBuf) -> String {
-    let git_dir_arg = git_dir.as_os_str().to_str().unwrap();
-    let output = Command::new("git")
-        .args(["--git-dir", git_dir_arg, "rev-parse", "HEAD"])
-        .output()
-        .expect("Failed running 'git rev-parse HEAD'");
-    get_stdout(&output)
+    Ok(RefHandler::new(repository).head()?.to_string())
 }
 
 fn get_stdout(output: &Output) -> String {
@@ -96,14 +125,35 @@ fn get_stdout(output: &Output) -> String {
     )
 }
 
-pub fn git_cat_file(git_dir: &PathBuf, reference: &str) -> String {
-    let git_dir_arg = git_dir.as_os_str().to_str().unwrap();
-    let output = Command::new("git")
-        .args(["--git-dir", git_dir_arg, "cat-file", "-p", reference])
-        .output()
-        .expect("Failed running 'git cat-file -p HEAD'");
-    assert_eq!(output.status.code().unwrap(), 0);
-    get_stdout(&output)
+pub fn rut_cat_file(reference: &str, repository: &Repository) -> rut::Result<String> {
+    let output = run_command_string(format!("cat-file -p {}", reference), repository)?;
+    Ok(output.trim_end_matches('\n').to_owned())
+}
+
+pub fn rut_cat_file_batch(input: &str, repository: &Repository) -> rut::Result<String> {
+    let mut output_writer = CapturingOutputWriter {
+        output: String::new(),
+    };
+    cat_file::cat_file_batch(&mut input.as_bytes(), repository, &mut output_writer)?;
+    Ok(output_writer.output)
+}
+
+pub fn rut_fast_export(repository: &Repository) -> rut::Result<Vec<u8>> {
+    let mut stream = vec![];
+    fast_export::fast_export(repository, &mut stream)?;
+    Ok(stream)
+}
+
+pub fn rut_fast_import(
+    stream: &[u8],
+    options: &fast_import::Options,
+    repository: &Repository,
+) -> rut::Result<String> {
+    let mut output_writer = CapturingOutputWriter {
+        output: String::new(),
+    };
+    fast_import::fast_import(&mut &stream[..], options, repository, &mut output_writer)?;
+    Ok(output_writer.output)
 }
 
 struct CapturingOutputWriter {
@@ -150,15 +200,49 @@ impl OutputWriter for NoopOutputWriter {
 }
 
 pub fn rut_add(path: &Path, repository: &Repository) {
-    add::add(path.to_owned(), repository).expect("Failed to add file");
+    add::add(&[path], repository, &mut NoProgress).expect("Failed to add file");
+}
+
+pub fn rut_add_patch(answers: &str, repository: &Repository) -> rut::Result<String> {
+    let mut output_writer = CapturingOutputWriter {
+        output: String::new(),
+    };
+    let mut prompt = LinePrompt::new(answers.as_bytes());
+    add::add_patch::<&str>(&[], repository, &mut prompt, &mut output_writer)?;
+    Ok(output_writer.output)
+}
+
+pub fn rut_difftool(
+    answers: &str,
+    options: &difftool::Options,
+    repository: &Repository,
+) -> rut::Result<String> {
+    let mut output_writer = CapturingOutputWriter {
+        output: String::new(),
+    };
+    let mut prompt = LinePrompt::new(answers.as_bytes());
+    let diff_options = Default::default();
+    difftool::difftool(
+        repository,
+        &diff_options,
+        options,
+        &mut prompt,
+        &mut output_writer,
+    )?;
+    Ok(output_writer.output)
 }
 
 pub fn rut_rm(path: &PathBuf, repository: &Repository) {
-    rm::rm(path, repository).expect("Failed to remove file");
+    rm::rm(&[path], &rm::Options::default(), repository).expect("Failed to remove file");
 }
 
 pub fn rut_init(repository: &Repository) {
-    init::init(repository.git_dir(), &mut NoopOutputWriter).expect("Failed to initialize repo");
+    init::init(
+        repository.git_dir(),
+        &init::Options::default(),
+        &mut NoopOutputWriter,
+    )
+    .expect("Failed to initialize repo");
 }
 
 pub fn rut_status_porcelain(repository: &Repository) -> rut::Result<String> {
@@ -187,7 +271,7 @@ pub fn rut_restore(
     options: &restore::Options,
     repository: &Repository,
 ) -> rut::Result<()> {
-    restore::restore_worktree(file, options, repository)?;
+    restore::restore_worktree(&[file], options, repository)?;
     Ok(())
 }
 
@@ -204,18 +288,87 @@ pub fn rut_diff(repository: &Repository, options: &diff::Options) -> rut::Result
     Ok(output_writer.output)
 }
 
-pub fn assert_healthy_repo(git_dir: &PathBuf) {
-    let git_dir_arg = git_dir.as_os_str().to_str().unwrap();
-    let output = Command::new("git")
-        .args(["--git-dir", git_dir_arg, "status"])
-        .output()
-        .expect("Failed running 'git status'");
-    assert_eq!(output.status.code().unwrap(), 0);
+pub fn rut_hash_object_stdin(
+    input: &str,
+    options: &hash_object::Options,
+    repository: &Repository,
+) -> rut::Result<String> {
+    let mut output_writer = CapturingOutputWriter {
+        output: String::new(),
+    };
+    hash_object::hash_object_stdin(
+        &mut input.as_bytes(),
+        options,
+        repository,
+        &mut output_writer,
+    )?;
+    Ok(output_writer.output)
+}
+
+/// Assert that every object reachable from the refs, HEAD and the index of the repository is
+/// present and well-formed.
+pub fn assert_healthy_repo(git_dir: &Path) {
+    let worktree_root = git_dir.parent().expect("git dir should be in the worktree");
+    let (exit_status, output) =
+        run_command_with_exit_status(vec!["fsck"], worktree_root).expect("Failed running fsck");
+    assert_eq!(exit_status, 0, "fsck reported problems:\n{}", output);
+}
+
+/// Stage a submodule at the path by adding a gitlink for the commit to the index, without
+/// anything at the path in the worktree.
+pub fn rut_stage_gitlink(path: &str, commit_id: &str, repository: &Repository) {
+    let args = format!(
+        "update-index --add --cacheinfo 160000,{},{}",
+        commit_id, path
+    );
+    run_command_string(args, repository).expect("Failed running 'update-index'");
+}
+
+/// Merge the branch into HEAD in a way that leaves conflicts, as a merge with conflicts is left by
+/// `git merge`: the conflicts are in the index and the worktree, and the merge is recorded as in
+/// progress with the message to commit it with. Returns the paths with conflicts.
+pub fn rut_merge_with_conflicts(
+    branch: &str,
+    repository: &Repository,
+) -> rut::Result<Vec<PathBuf>> {
+    let database = &repository.database;
+    let ours_id = RefHandler::new(repository).head()?;
+    let theirs_id = Revision::parse(branch)?.resolve(repository)?;
+    let base_tree = rev_walk::merge_base(repository, &ours_id, &theirs_id)?
+        .map(|base_id| database.peel_to_tree(&base_id))
+        .transpose()?;
+
+    let mut index = repository.load_index()?;
+    let conflicts = merge::merge_trees(
+        base_tree.as_ref(),
+        &database.peel_to_tree(&ours_id)?,
+        &database.peel_to_tree(&theirs_id)?,
+        "HEAD",
+        branch,
+        repository,
+        index.as_mut(),
+    )?;
+    index.write()?;
+    assert!(
+        !conflicts.is_empty(),
+        "expected the merge to have conflicts"
+    );
+
+    let conflict_lines = conflicts
+        .iter()
+        .map(|path| format!("#\t{}\n", path.display()))
+        .collect::<String>();
+    let message = format!(
+        "Merge branch '{}'\n\n# Conflicts:\n{}",
+        branch, conflict_lines
+    );
+    merge::write_merge_state(&[theirs_id], &message, repository)?;
+    Ok(conflicts)
 }
 
 pub fn create_repository() -> Repository {
     let workdir = create_temporary_directory();
-    let repository = Repository::from_worktree_root(workdir);
+    let repository = Repository::from_worktree_root(workdir).expect("Failed to open repository");
     rut_init(&repository);
     repository
 }
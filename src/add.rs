@@ -1,51 +1,233 @@
-use std::{fs, path::Path};
+use std::collections::VecDeque;
+use std::{fs, io, path::Path};
+
+use rayon::prelude::*;
 
 use crate::{
+    diff::{self, Chunk},
     file,
+    filter::Filters,
     index::{Index, IndexEntry},
     objects::{Blob, GitObject},
+    output::{OutputWriter, Progress},
+    pathspec::Pathspec,
+    prompt::Prompt,
+    status::{self, ChangeType},
     workspace::Repository,
 };
 
 pub static GITIGNORE: [&str; 2] = ["Cargo.lock", "target"];
 
-pub fn add<P: AsRef<Path>>(path: P, repository: &Repository) -> crate::Result<()> {
-    if GITIGNORE.contains(&path.as_ref().to_str().expect("Path was bad UTF8")) {
+/// The number of files that are stored in parallel between progress updates.
+const PROGRESS_CHUNK_SIZE: usize = 256;
+
+/// Add the files selected by the pathspecs to the index. Tracked files that are selected but no
+/// longer exist in the worktree are removed from the index.
+pub fn add<P: AsRef<Path>>(
+    pathspecs: &[P],
+    repository: &Repository,
+    progress: &mut dyn Progress,
+) -> crate::Result<()> {
+    let pathspecs = pathspecs
+        .iter()
+        .filter(|pathspec| !GITIGNORE.contains(&pathspec.as_ref().to_str().unwrap_or_default()))
+        .collect::<Vec<_>>();
+    if pathspecs.is_empty() {
         return Ok(());
     }
 
-    let absolute_path = repository.worktree().root().join(&path);
-    let mut index = repository.load_index()?;
+    let worktree = repository.worktree();
+    let pathspec = Pathspec::parse(&pathspecs, worktree)?;
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
 
-    if absolute_path.try_exists().unwrap_or(false) {
-        for path in file::resolve_files(&absolute_path) {
-            add_file(&path, index.as_mut(), repository)?;
-        }
+    let worktree_files = pathspec.resolve_worktree_files(worktree);
+    let deleted_files = index
+        .get_entries()
+        .iter()
+        .map(|entry| &entry.path)
+        .filter(|path| pathspec.matches(path) && !worktree.root().join(path).exists())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let matched_files = [&worktree_files[..], &deleted_files[..]].concat();
+    if let Some(unmatched) = pathspec.find_unmatched(&matched_files) {
+        let message = format!("pathspec '{}' did not match any files", unmatched);
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let filters = repository.filters()?;
+    // storing the blobs is independent for each file, but the index is updated in a single pass
+    progress.start("Adding files", Some(worktree_files.len() as u64));
+    let mut entries = Vec::with_capacity(worktree_files.len());
+    for chunk in worktree_files.chunks(PROGRESS_CHUNK_SIZE) {
+        let chunk_entries = chunk
+            .par_iter()
+            .map(|path| store_file(&worktree.root().join(path), filters, repository))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.extend(chunk_entries);
+        progress.update(entries.len() as u64);
+    }
+    progress.finish();
+    for entry in entries {
+        index.add_entry(entry);
+    }
+    for path in deleted_files {
+        index.remove(&path);
+    }
+
+    Ok(index_lockfile.write()?)
+}
+
+pub(crate) fn add_file(
+    absolute_path: &Path,
+    index: &mut Index,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let entry = store_file(absolute_path, repository.filters()?, repository)?;
+    index.add_entry(entry);
+
+    Ok(())
+}
+
+/// Store the content of the file as a blob and create the index entry that points at it. The
+/// file is streamed into the database unless its content has to be filtered first.
+fn store_file(
+    absolute_path: &Path,
+    filters: &Filters,
+    repository: &Repository,
+) -> io::Result<IndexEntry> {
+    let relative_path = repository.worktree().relativize_path(absolute_path);
+    let object_id = if filters.converts_to_database(&relative_path) {
+        let content = filters.to_database(&relative_path, file::read_file(absolute_path)?);
+        let blob = Blob::with_hash_algorithm(content, repository.hash_algorithm());
+        repository.database.store_object(&blob)?;
+        blob.id().clone()
     } else {
-        let relative_path = repository.worktree().relativize_path(&absolute_path);
-        match index.as_mut().remove(&relative_path) {
-            Some(_) => (),
-            None => {
-                let message = format!("pathspec {:?} did not match any files", path.as_ref());
-                return Err(crate::Error::Fatal(None, message));
-            }
+        repository.database.store_blob_from_file(absolute_path)?
+    };
+
+    let metadata = fs::metadata(absolute_path)?;
+    Ok(IndexEntry::new(relative_path, object_id, &metadata))
+}
+
+const PATCH_HELP: &str = "\
+y - stage this hunk
+n - do not stage this hunk
+q - quit; do not stage this hunk or any of the remaining ones
+s - split the current hunk into smaller hunks";
+
+/// Interactively choose which hunks of the unstaged modifications to stage. Only files selected
+/// by the pathspecs are considered, or all tracked files if no pathspecs are given.
+pub fn add_patch<P: AsRef<Path>>(
+    pathspecs: &[P],
+    repository: &Repository,
+    prompt: &mut dyn Prompt,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    let worktree = repository.worktree();
+
+    let pathspec = Pathspec::parse(pathspecs, worktree)?;
+    let mut modified_paths = status::resolve_unstaged_changes(repository, index)
+        .into_iter()
+        .filter(|change| matches!(change.change_type, ChangeType::Modified))
+        .map(|change| change.path)
+        .filter(|path| pathspec.matches(path))
+        .collect::<Vec<_>>();
+    modified_paths.sort();
+
+    for path in modified_paths {
+        let should_quit = stage_hunks(&path, index, repository, prompt, writer)?;
+        if should_quit {
+            break;
         }
     }
 
-    Ok(index.write()?)
+    Ok(index_lockfile.write()?)
 }
 
-fn add_file(absolute_path: &Path, index: &mut Index, repository: &Repository) -> crate::Result<()> {
-    let file_bytes = file::read_file(absolute_path)?;
-    let blob = Blob::new(file_bytes);
-    repository.database.store_object(&blob)?;
+/// Ask which hunks of the file to stage and store the resulting content in the index. Files that
+/// aren't UTF-8 text are skipped, as hunks of them can't be shown. Returns true if the user chose
+/// to quit.
+fn stage_hunks(
+    path: &Path,
+    index: &mut Index,
+    repository: &Repository,
+    prompt: &mut dyn Prompt,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let index_entry = index.get(path).unwrap();
+    let index_blob = repository.database.load_blob(&index_entry.object_id)?;
+    let worktree_content = file::read_file(repository.worktree().root().join(path))?;
+    let worktree_content = repository.filters()?.to_database(path, worktree_content);
+    let worktree_blob = Blob::with_hash_algorithm(worktree_content, repository.hash_algorithm());
+    let (Ok(index_content), Ok(worktree_content)) = (
+        std::str::from_utf8(index_blob.content()),
+        std::str::from_utf8(worktree_blob.content()),
+    ) else {
+        writer.writeln(format!(
+            "Skipping {}, as it is not UTF-8 text",
+            path.display()
+        ))?;
+        return Ok(false);
+    };
+    let index_lines = index_content.split('\n').collect::<Vec<_>>();
+    let worktree_lines = worktree_content.split('\n').collect::<Vec<_>>();
 
-    let metadata = fs::metadata(absolute_path)?;
+    let algorithm = diff::Algorithm::from_config(repository)?;
+    let edit_script = diff::edit_script_with_algorithm(&index_lines, &worktree_lines, algorithm);
+    let mut pending_chunks: VecDeque<Chunk<&str>> =
+        diff::chunk_edit_script(&edit_script, diff::DEFAULT_CONTEXT_LINES).into();
+    let mut selected_chunks = vec![];
+    let mut should_quit = false;
 
-    let relative_path = repository.worktree().relativize_path(absolute_path);
-    let entry = IndexEntry::new(relative_path, blob.id().clone(), &metadata);
+    diff::write_header(
+        path,
+        Some(index_blob.short_id_as_string()),
+        Some(worktree_blob.short_id_as_string()),
+        writer,
+    )?;
+    while let Some(chunk) = pending_chunks.pop_front() {
+        diff::write_chunk(&chunk, writer)?;
+        let split_chunks = chunk.split();
+        let choices = if split_chunks.len() > 1 {
+            "y,n,q,s"
+        } else {
+            "y,n,q"
+        };
+        writer.write(format!("Stage this hunk [{}]? ", choices))?;
 
-    index.add_entry(entry);
+        match prompt.read_answer()?.as_deref() {
+            Some("y") => selected_chunks.push(chunk),
+            Some("n") => (),
+            Some("s") if split_chunks.len() > 1 => {
+                writer.writeln(format!("Split into {} hunks.", split_chunks.len()))?;
+                for split_chunk in split_chunks.into_iter().rev() {
+                    pending_chunks.push_front(split_chunk);
+                }
+            }
+            Some("q") | None => {
+                should_quit = true;
+                break;
+            }
+            Some(_) => {
+                writer.writeln(String::from(PATCH_HELP))?;
+                pending_chunks.push_front(chunk);
+            }
+        }
+    }
 
-    Ok(())
+    if !selected_chunks.is_empty() {
+        let staged_content = diff::apply_chunks(&edit_script, &selected_chunks).join("\n");
+        let staged_blob =
+            Blob::with_hash_algorithm(staged_content.into_bytes(), repository.hash_algorithm());
+        repository.database.store_object(&staged_blob)?;
+        // keep the stat data of the entry, as the worktree file may still differ from it
+        index.get_mut(path).unwrap().object_id = staged_blob.id().clone();
+        index.invalidate_cache_tree(path);
+    }
+
+    Ok(should_quit)
 }
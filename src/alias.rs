@@ -0,0 +1,86 @@
+//! Aliases from the `[alias]` section of the config, which expand to other commands, as in
+//! `st = status --porcelain`, or to shell commands when starting with `!`.
+
+use crate::config::Config;
+
+#[derive(Debug, PartialEq)]
+pub enum Alias {
+    /// The arguments of a rut command, split like a shell would split them.
+    Command(Vec<String>),
+    /// A command line that is run by the shell, with any further arguments appended.
+    Shell(String),
+}
+
+impl Alias {
+    /// Parse the value of an alias.
+    ///
+    /// # Examples
+    /// ```
+    /// use rut::alias::Alias;
+    ///
+    /// assert_eq!(
+    ///     Alias::parse("log --pretty='format:%h %s'"),
+    ///     Ok(Alias::Command(vec![
+    ///         String::from("log"),
+    ///         String::from("--pretty=format:%h %s")
+    ///     ]))
+    /// );
+    /// assert_eq!(
+    ///     Alias::parse("!echo hello"),
+    ///     Ok(Alias::Shell(String::from("echo hello")))
+    /// );
+    /// assert!(Alias::parse("log \"unterminated").is_err());
+    /// ```
+    pub fn parse(value: &str) -> Result<Alias, String> {
+        if let Some(shell_command) = value.strip_prefix('!') {
+            return Ok(Alias::Shell(shell_command.to_owned()));
+        }
+        let args = split_command_line(value)?;
+        if args.is_empty() {
+            return Err(String::from("empty alias"));
+        }
+        Ok(Alias::Command(args))
+    }
+}
+
+/// Look up the alias of the name in the config.
+pub fn lookup(name: &str, config: &Config) -> crate::Result<Option<Alias>> {
+    let Some(value) = config.get_string(&format!("alias.{}", name)) else {
+        return Ok(None);
+    };
+    Alias::parse(&value)
+        .map(Some)
+        .map_err(|reason| crate::Error::Fatal(None, format!("bad alias.{}: {}", name, reason)))
+}
+
+/// Split a command line into arguments at unquoted whitespace. Single quotes preserve everything
+/// within them, double quotes preserve everything but backslash escapes, and a backslash outside
+/// of quotes escapes the next character.
+fn split_command_line(s: &str) -> Result<Vec<String>, String> {
+    let mut args = vec![];
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => args.extend(current.take()),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (Some(q), c) if c == q => quote = None,
+            (None | Some('"'), '\\') => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| String::from("unfinished escape"))?;
+                current.get_or_insert_with(String::new).push(escaped);
+            }
+            (_, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(String::from("unclosed quote"));
+    }
+    args.extend(current);
+    Ok(args)
+}
@@ -0,0 +1,309 @@
+//! Applying patches from mailboxes, such as those written by `format-patch`, as commits.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    add, commit, file,
+    index::Index,
+    objects::Author,
+    output::OutputWriter,
+    patch::{self, FilePatch},
+    status,
+    workspace::Repository,
+};
+
+/// A patch mailed as by `format-patch`.
+struct Mail {
+    author: Author,
+    date: String,
+    subject: String,
+    /// The message following the headers, up to the `---` line that precedes the patch.
+    body: String,
+    file_patches: Vec<FilePatch>,
+}
+
+impl Mail {
+    fn commit_message(&self) -> String {
+        if self.body.is_empty() {
+            format!("{}\n", self.subject)
+        } else {
+            format!("{}\n\n{}\n", self.subject, self.body)
+        }
+    }
+}
+
+/// Apply the patches in the mailboxes as commits on top of HEAD, in order, keeping the author,
+/// date and message of each one. Applying stops at the first patch that doesn't apply, leaving
+/// the previous patches committed.
+pub fn am<P: AsRef<Path>>(
+    mboxes: &[P],
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let mut mails = vec![];
+    for mbox in mboxes {
+        let content = fs::read_to_string(mbox)?;
+        for mail in split_mbox(&content) {
+            mails.push(parse_mail(mail)?);
+        }
+    }
+
+    let mut index_lockfile = repository.load_index()?;
+    let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
+    let dirty_files = status::resolve_files_with_staged_changes(
+        &path_to_committed_id,
+        repository,
+        index_lockfile.as_mut(),
+    )?;
+    if !dirty_files.is_empty() {
+        let dirty_paths = dirty_files
+            .iter()
+            .map(|path| repository.worktree().relativize_path(path))
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let message = format!(
+            "Dirty index: cannot apply patches (dirty: {})",
+            dirty_paths.join(" ")
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    for (i, mail) in mails.iter().enumerate() {
+        writer.writeln(format!("Applying: {}", mail.subject))?;
+        let index = index_lockfile.as_mut();
+        if let Err(error) = apply_file_patches(&mail.file_patches, index, repository) {
+            // keep the index of the patches that were applied
+            index_lockfile.write()?;
+            let reason = match error {
+                crate::Error::Fatal(_, reason) => reason,
+                error => error.to_string(),
+            };
+            let message = format!("{}\nPatch failed at {:04} {}", reason, i + 1, mail.subject);
+            return Err(crate::Error::Fatal(None, message));
+        }
+        commit::commit_as_author(
+            repository,
+            index,
+            &mail.commit_message(),
+            mail.author.clone(),
+            &mail.date,
+            "am",
+        )?;
+    }
+
+    Ok(index_lockfile.write()?)
+}
+
+/// Split the content of a mailbox into mails, each of which starts with a `From ` line.
+fn split_mbox(content: &str) -> Vec<&str> {
+    let mut starts = vec![];
+    let mut offset = 0;
+    let mut previous_line = "";
+    for line in content.split_inclusive('\n') {
+        if is_from_line(line) && (offset == 0 || previous_line == "\n") {
+            starts.push(offset);
+        }
+        offset += line.len();
+        previous_line = line;
+    }
+    if starts.first() != Some(&0) {
+        // a file with a single mail may lack the From line
+        starts.insert(0, 0);
+    }
+
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&content.len()]))
+        .map(|(start, end)| &content[*start..*end])
+        .filter(|mail| !mail.trim().is_empty())
+        .collect()
+}
+
+/// Whether the line starts a mail, like `From <id> Mon Sep 17 00:00:00 2001`. Lines in the body
+/// of a mail may also start with `From `, but rarely end with a year.
+fn is_from_line(line: &str) -> bool {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    words.len() > 2
+        && words[0] == "From"
+        && words[words.len() - 1].len() == 4
+        && words[words.len() - 1].chars().all(|c| c.is_ascii_digit())
+}
+
+fn parse_mail(mail: &str) -> crate::Result<Mail> {
+    let (header_block, message) = mail.split_once("\n\n").unwrap_or((mail, ""));
+    let mut headers: Vec<(String, String)> = vec![];
+    for line in header_block.lines() {
+        if line.starts_with([' ', '\t']) {
+            // a folded header continues the previous one
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.to_ascii_lowercase(), value.trim().to_owned()));
+        }
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header_name, _)| header_name == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let error = || {
+        crate::Error::Fatal(
+            None,
+            String::from("patch does not have a valid e-mail address"),
+        )
+    };
+    let from = header("from").ok_or_else(error)?;
+    let author = match from.rsplit_once('<') {
+        Some((name, email)) => Author {
+            name: name.trim().trim_matches('"').to_owned(),
+            email: email.trim_end_matches('>').to_owned(),
+        },
+        None => Author {
+            name: from.to_owned(),
+            email: from.to_owned(),
+        },
+    };
+    let date = header("date").ok_or_else(error)?.to_owned();
+    let subject = strip_subject_prefixes(header("subject").unwrap_or_default()).to_owned();
+
+    let body = message
+        .lines()
+        .take_while(|line| *line != "---" && !line.starts_with("diff --git "))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(Mail {
+        author,
+        date,
+        subject,
+        body: body.trim().to_owned(),
+        file_patches: patch::parse(message)?,
+    })
+}
+
+/// Strip any bracketed prefixes like `[PATCH 1/2]` from the subject.
+fn strip_subject_prefixes(subject: &str) -> &str {
+    let mut subject = subject.trim();
+    while let Some(rest) = subject.strip_prefix('[') {
+        match rest.split_once(']') {
+            Some((_, rest)) => subject = rest.trim_start(),
+            None => break,
+        }
+    }
+    subject
+}
+
+/// Apply the file patches to the index and the worktree. Nothing is changed unless all of them
+/// apply, and the files they change must not have unstaged changes.
+fn apply_file_patches(
+    file_patches: &[FilePatch],
+    index: &mut Index,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let error = |message: String| crate::Error::Fatal(None, message);
+    let unstaged_paths = status::resolve_unstaged_changes(repository, index)
+        .into_iter()
+        .map(|change| change.path)
+        .collect::<Vec<_>>();
+
+    let mut new_contents: Vec<(&FilePatch, Option<String>)> = vec![];
+    for file_patch in file_patches {
+        let old_content = match &file_patch.old_path {
+            Some(old_path) => {
+                let entry = index.get(old_path).ok_or_else(|| {
+                    error(format!("{}: does not exist in index", old_path.display()))
+                })?;
+                if unstaged_paths.contains(old_path) {
+                    return Err(error(format!(
+                        "{}: does not match index",
+                        old_path.display()
+                    )));
+                }
+                let blob = repository.database.load_blob(&entry.object_id)?;
+                String::from_utf8_lossy(blob.content()).into_owned()
+            }
+            None => String::new(),
+        };
+        if let Some(new_path) = &file_patch.new_path {
+            let is_created = file_patch.old_path.as_ref() != Some(new_path);
+            if is_created && index.get(new_path).is_some() {
+                let message = format!("{}: already exists in index", new_path.display());
+                return Err(error(message));
+            }
+        }
+
+        let new_content = patch::apply(&old_content, &file_patch.hunks).ok_or_else(|| {
+            let path = file_patch
+                .old_path
+                .as_ref()
+                .or(file_patch.new_path.as_ref());
+            error(format!("patch failed: {}", path.unwrap().display()))
+        })?;
+        new_contents.push((
+            file_patch,
+            file_patch.new_path.as_ref().map(|_| new_content),
+        ));
+    }
+
+    let worktree_root = repository.worktree().root();
+    for (file_patch, new_content) in new_contents {
+        if let Some(old_path) = &file_patch.old_path {
+            if file_patch.new_path.as_ref() != Some(old_path) {
+                remove_file(old_path, index, worktree_root)?;
+            }
+        }
+        if let (Some(new_path), Some(new_content)) = (&file_patch.new_path, new_content) {
+            let absolute_path = worktree_root.join(new_path);
+            if let Some(parent) = absolute_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&absolute_path, new_content)?;
+            add::add_file(&absolute_path, index, repository)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_file(path: &PathBuf, index: &mut Index, worktree_root: &Path) -> io::Result<()> {
+    index.remove(path);
+    let absolute_path = worktree_root.join(path);
+    match fs::remove_file(&absolute_path) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+        other => other?,
+    }
+    file::remove_empty_parents(&absolute_path, worktree_root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = "From 1 Mon Sep 17 00:00:00 2001\nSubject: a\n\nFrom me\n\nFrom 2 Mon Sep 17 00:00:00 2001\nSubject: b\n";
+
+        let mails = split_mbox(mbox);
+
+        assert_eq!(
+            mails,
+            vec![
+                "From 1 Mon Sep 17 00:00:00 2001\nSubject: a\n\nFrom me\n\n",
+                "From 2 Mon Sep 17 00:00:00 2001\nSubject: b\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_subject_prefixes() {
+        assert_eq!(strip_subject_prefixes("[PATCH 1/2] Fix it"), "Fix it");
+        assert_eq!(strip_subject_prefixes("[RFC] [PATCH] Fix [it]"), "Fix [it]");
+    }
+}
@@ -0,0 +1,402 @@
+//! Writing the files of a tree to a tar or zip archive, without checking them out.
+
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Datelike, Timelike};
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression, Crc,
+};
+
+use crate::{
+    index::FileMode,
+    objects::{ObjectId, ObjectType, Tree},
+    refs::Revision,
+    workspace::Repository,
+};
+
+/// The size of the blocks that a tar archive consists of.
+const TAR_BLOCK_SIZE: usize = 512;
+/// Tar archives are padded to a multiple of this size, as they are by git and tar.
+const TAR_RECORD_SIZE: usize = 20 * TAR_BLOCK_SIZE;
+/// The longest path that fits in the name field of a tar header. Longer paths are stored in an
+/// extended pax header.
+const TAR_NAME_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Tar,
+    /// A gzip-compressed tar archive.
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    /// The format implied by the extension of the file the archive is written to, if any.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::path::Path;
+    /// use rut::archive::Format;
+    ///
+    /// assert_eq!(Format::from_path(Path::new("out.zip")), Some(Format::Zip));
+    /// assert_eq!(Format::from_path(Path::new("out.tar.gz")), Some(Format::TarGz));
+    /// assert_eq!(Format::from_path(Path::new("out")), None);
+    /// ```
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn parse_format(s: &str) -> Result<Format, String> {
+    match s {
+        "tar" => Ok(Format::Tar),
+        "tar.gz" | "tgz" => Ok(Format::TarGz),
+        "zip" => Ok(Format::Zip),
+        _ => Err(format!("unknown archive format '{}'", s)),
+    }
+}
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub format: Format,
+    /// Prepended to the path of each file, such as `project/` to put all files in a directory.
+    #[builder(default)]
+    pub prefix: String,
+}
+
+/// A file or directory in the archive.
+struct Entry {
+    /// The path in the archive, which ends with a slash for directories.
+    path: String,
+    mode: FileMode,
+    /// The blob with the content of a file, or None for a directory.
+    blob_id: Option<ObjectId>,
+}
+
+impl Entry {
+    fn unix_mode(&self) -> u32 {
+        match self.mode {
+            FileMode::Regular => 0o644,
+            _ => 0o755,
+        }
+    }
+
+    fn load_content(&self, repository: &Repository) -> crate::Result<Vec<u8>> {
+        match &self.blob_id {
+            Some(blob_id) => Ok(repository.database.load_object(blob_id)?.1.to_vec()),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Write the files of the tree, or of the root tree of a commit, to an archive. Files are dated
+/// with the commit date of a commit, and with the current time otherwise. The id of a commit is
+/// also recorded in the archive, where `git get-tar-commit-id` can find it.
+pub fn archive(
+    tree_ish: &str,
+    options: &Options,
+    repository: &Repository,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    let database = &repository.database;
+    let object_id = Revision::parse(tree_ish)?.resolve(repository)?;
+    let tree = database.peel_to_tree(&object_id).map_err(|error| {
+        crate::Error::Fatal(
+            Some(Box::new(error)),
+            format!("not a tree object: {}", tree_ish),
+        )
+    })?;
+    let (object_type, _) = database.load_object(&object_id)?;
+    let commit_id = (object_type == ObjectType::Commit).then_some(&object_id);
+    let mtime = match commit_id {
+        Some(commit_id) => database.load_commit(commit_id)?.committer_timestamp,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+    };
+
+    let mut entries = vec![];
+    if options.prefix.ends_with('/') {
+        entries.push(Entry {
+            path: options.prefix.clone(),
+            mode: FileMode::Directory,
+            blob_id: None,
+        });
+    }
+    collect_entries(&options.prefix, &tree, repository, &mut entries)?;
+
+    match options.format {
+        Format::Tar => write_tar(&entries, mtime, commit_id, repository, output),
+        Format::TarGz => {
+            let mut encoder = GzEncoder::new(output, Compression::default());
+            write_tar(&entries, mtime, commit_id, repository, &mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        Format::Zip => write_zip(&entries, mtime, commit_id, repository, output),
+    }
+}
+
+fn collect_entries(
+    base_path: &str,
+    tree: &Tree,
+    repository: &Repository,
+    entries: &mut Vec<Entry>,
+) -> crate::Result<()> {
+    for tree_entry in tree.entries() {
+        let path = format!("{}{}", base_path, tree_entry.name);
+        match tree_entry.mode {
+            FileMode::Directory => {
+                let path = format!("{}/", path);
+                let subtree = repository.database.load_tree(&tree_entry.object_id)?;
+                entries.push(Entry {
+                    path: path.clone(),
+                    mode: FileMode::Directory,
+                    blob_id: None,
+                });
+                collect_entries(&path, &subtree, repository, entries)?;
+            }
+            // the contents of submodules are not in the database, so they are empty directories
+            FileMode::Gitlink => entries.push(Entry {
+                path: format!("{}/", path),
+                mode: FileMode::Directory,
+                blob_id: None,
+            }),
+            mode => entries.push(Entry {
+                path,
+                mode,
+                blob_id: Some(tree_entry.object_id.clone()),
+            }),
+        }
+    }
+    Ok(())
+}
+
+fn write_tar(
+    entries: &[Entry],
+    mtime: u64,
+    commit_id: Option<&ObjectId>,
+    repository: &Repository,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    let mut size = 0;
+    if let Some(commit_id) = commit_id {
+        let records = pax_record("comment", &commit_id.to_string());
+        size += write_tar_entry("pax_global_header", 0o666, b'g', mtime, &records, output)?;
+    }
+
+    for entry in entries {
+        if entry.path.len() > TAR_NAME_SIZE {
+            let records = pax_record("path", &entry.path);
+            size += write_tar_entry(&entry.path, 0o666, b'x', mtime, &records, output)?;
+        }
+        let content = entry.load_content(repository)?;
+        let typeflag = match entry.blob_id {
+            Some(_) => b'0',
+            None => b'5',
+        };
+        size += write_tar_entry(
+            &entry.path,
+            entry.unix_mode(),
+            typeflag,
+            mtime,
+            &content,
+            output,
+        )?;
+    }
+
+    // the archive ends with two empty blocks, and is then padded to a whole record
+    let end_size = 2 * TAR_BLOCK_SIZE;
+    let padding = (TAR_RECORD_SIZE - (size + end_size) % TAR_RECORD_SIZE) % TAR_RECORD_SIZE;
+    output.write_all(&vec![0; end_size + padding])?;
+    Ok(())
+}
+
+/// Write a ustar header followed by the content, padded to whole blocks. Returns the number of
+/// bytes written.
+fn write_tar_entry(
+    path: &str,
+    mode: u32,
+    typeflag: u8,
+    mtime: u64,
+    content: &[u8],
+    output: &mut dyn Write,
+) -> crate::Result<usize> {
+    let mut header = [0; TAR_BLOCK_SIZE];
+    let name = &path.as_bytes()[..path.len().min(TAR_NAME_SIZE)];
+    header[..name.len()].copy_from_slice(name);
+    write_octal(&mut header[100..108], mode as u64);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], content.len() as u64);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = typeflag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[265..269].copy_from_slice(b"root");
+    header[297..301].copy_from_slice(b"root");
+    // the checksum is computed as if its own field were filled with spaces
+    header[148..156].fill(b' ');
+    let checksum = header.iter().map(|byte| *byte as u64).sum::<u64>();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+    let padding = (TAR_BLOCK_SIZE - content.len() % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+    output.write_all(&header)?;
+    output.write_all(content)?;
+    output.write_all(&vec![0; padding])?;
+    Ok(TAR_BLOCK_SIZE + content.len() + padding)
+}
+
+/// Write the value as zero-padded octal digits terminated by NUL, filling the field.
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+/// A record of a pax extended header, on the form `<length> <key>=<value>\n` where the length
+/// counts the whole record, including its own digits.
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let content_length = key.len() + value.len() + 3;
+    let mut length = content_length;
+    loop {
+        let total_length = content_length + length.to_string().len();
+        if total_length == length {
+            break;
+        }
+        length = total_length;
+    }
+    format!("{} {}={}\n", length, key, value).into_bytes()
+}
+
+fn write_zip(
+    entries: &[Entry],
+    mtime: u64,
+    commit_id: Option<&ObjectId>,
+    repository: &Repository,
+    output: &mut dyn Write,
+) -> crate::Result<()> {
+    let too_large = || {
+        crate::Error::Fatal(
+            None,
+            String::from("archive is too large for the zip format"),
+        )
+    };
+    let (dos_time, dos_date) = dos_date_time(mtime);
+
+    let mut offset: u64 = 0;
+    let mut central_directory = vec![];
+    for entry in entries {
+        let content = entry.load_content(repository)?;
+        let mut crc = Crc::new();
+        crc.update(&content);
+        let uncompressed_size = content.len();
+        let compressed = deflate(&content)?;
+        // content that doesn't shrink is stored as is
+        let (method, data): (u16, _) = if compressed.len() < content.len() {
+            (8, compressed)
+        } else {
+            (0, content)
+        };
+
+        let name = entry.path.as_bytes();
+        let mut fields = vec![];
+        push_u16(&mut fields, if method == 8 { 20 } else { 10 });
+        // names that aren't ASCII are marked as UTF-8
+        push_u16(&mut fields, if entry.path.is_ascii() { 0 } else { 0x0800 });
+        push_u16(&mut fields, method);
+        push_u16(&mut fields, dos_time);
+        push_u16(&mut fields, dos_date);
+        push_u32(&mut fields, crc.sum());
+        push_u32(&mut fields, data.len().try_into().map_err(|_| too_large())?);
+        push_u32(&mut fields, uncompressed_size as u32);
+        push_u16(&mut fields, name.len() as u16);
+        push_u16(&mut fields, 0);
+
+        let mut local_header = vec![];
+        push_u32(&mut local_header, 0x04034b50);
+        local_header.extend_from_slice(&fields);
+        local_header.extend_from_slice(name);
+        output.write_all(&local_header)?;
+        output.write_all(&data)?;
+
+        let (file_type, attributes) = match entry.blob_id {
+            Some(_) => (0o100000, 0),
+            None => (0o040000, 0x10),
+        };
+        push_u32(&mut central_directory, 0x02014b50);
+        // made by version 2.0 on unix, so that the unix modes are used
+        push_u16(&mut central_directory, (3 << 8) | 20);
+        central_directory.extend_from_slice(&fields);
+        push_u16(&mut central_directory, 0);
+        push_u16(&mut central_directory, 0);
+        push_u16(&mut central_directory, 0);
+        push_u32(
+            &mut central_directory,
+            ((file_type | entry.unix_mode()) << 16) | attributes,
+        );
+        push_u32(
+            &mut central_directory,
+            offset.try_into().map_err(|_| too_large())?,
+        );
+        central_directory.extend_from_slice(name);
+        offset += (local_header.len() + data.len()) as u64;
+    }
+
+    let comment = commit_id.map(ObjectId::to_string).unwrap_or_default();
+    let entry_count = u16::try_from(entries.len()).map_err(|_| too_large())?;
+    let mut end_record = vec![];
+    push_u32(&mut end_record, 0x06054b50);
+    push_u16(&mut end_record, 0);
+    push_u16(&mut end_record, 0);
+    push_u16(&mut end_record, entry_count);
+    push_u16(&mut end_record, entry_count);
+    push_u32(&mut end_record, central_directory.len() as u32);
+    push_u32(&mut end_record, offset.try_into().map_err(|_| too_large())?);
+    push_u16(&mut end_record, comment.len() as u16);
+    end_record.extend_from_slice(comment.as_bytes());
+
+    output.write_all(&central_directory)?;
+    output.write_all(&end_record)?;
+    Ok(())
+}
+
+fn deflate(content: &[u8]) -> crate::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(content)?;
+    Ok(encoder.finish()?)
+}
+
+/// Convert the timestamp to the time and date fields of zip headers, which can't represent
+/// times before 1980.
+fn dos_date_time(timestamp: u64) -> (u16, u16) {
+    let datetime = DateTime::from_timestamp(timestamp as i64, 0).unwrap_or_default();
+    if datetime.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (datetime.hour() << 11) | (datetime.minute() << 5) | (datetime.second() / 2);
+    let date = (((datetime.year() - 1980) as u32) << 9) | (datetime.month() << 5) | datetime.day();
+    (time as u16, date as u16)
+}
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
@@ -12,8 +12,7 @@ pub fn main() {
 
 fn internal_main() -> i32 {
     let args: Vec<String> = env::args().collect();
-
-    let mut writer = StdoutWriter::new(!args.contains(&"--help".to_string()));
+    let mut writer = StdoutWriter::new();
 
     let workdir = match env::current_dir() {
         Ok(dir) => dir,
@@ -21,8 +20,8 @@ fn internal_main() -> i32 {
     };
 
     match cli::run_command(args, workdir, &mut writer) {
-        Ok(_) => 0,
-        Err(fatal @ rut::Error::Fatal(_, _)) => {
+        Ok(exit_status) => exit_status,
+        Err(fatal) if fatal.is_fatal() => {
             writer
                 .writeln(format!("{}", fatal))
                 .expect("Failed to write to stdout");
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{Local, TimeZone};
+
+use crate::diff::{self, EditKind};
+use crate::mailmap::Mailmap;
+use crate::object_resolver::ObjectResolver;
+use crate::objects::{Blob, Commit, GitObject, ObjectId};
+use crate::output::OutputWriter;
+use crate::refs::RefHandler;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Only blame the lines in this 1-indexed, inclusive range.
+    #[builder(default)]
+    pub line_range: Option<(usize, usize)>,
+}
+
+/// Parse a line range on the form `<start>,<end>`.
+///
+/// # Examples
+/// ```
+/// use rut::blame;
+///
+/// assert_eq!(blame::parse_line_range("2,4"), Ok((2, 4)));
+/// assert!(blame::parse_line_range("4,2").is_err());
+/// ```
+pub fn parse_line_range(s: &str) -> Result<(usize, usize), String> {
+    let error = || format!("invalid line range '{}', expected <start>,<end>", s);
+    let (start, end) = s.split_once(',').ok_or_else(error)?;
+    let start = start.trim().parse::<usize>().map_err(|_| error())?;
+    let end = end.trim().parse::<usize>().map_err(|_| error())?;
+
+    if start == 0 || end < start {
+        return Err(error());
+    }
+
+    Ok((start, end))
+}
+
+/// Attribute each line of the file at HEAD to the commit that introduced it.
+pub fn blame<P: AsRef<Path>>(
+    path: P,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let path = path.as_ref();
+    let head_id = RefHandler::new(repository).head()?;
+    let head_blob = find_blob(&head_id, path, repository)?.ok_or_else(|| {
+        crate::Error::Fatal(None, format!("no such path {} in HEAD", path.display()))
+    })?;
+
+    let head_content = String::from_utf8_lossy(head_blob.content()).into_owned();
+    let lines = head_content.lines().collect::<Vec<_>>();
+
+    let (start, end) = options.line_range.unwrap_or((1, lines.len()));
+    if end > lines.len() {
+        let message = format!("file {} has only {} lines", path.display(), lines.len());
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let attributions = attribute_lines(head_id, head_blob, path, repository)?;
+
+    let mailmap = Mailmap::load(repository)?;
+    let mut commits: HashMap<ObjectId, Commit> = HashMap::new();
+    for commit_id in &attributions[(start - 1)..end] {
+        if !commits.contains_key(commit_id) {
+            let mut commit = repository.database.load_commit(commit_id)?;
+            commit.author = mailmap.lookup(&commit.author);
+            commits.insert(commit_id.clone(), commit);
+        }
+    }
+
+    let author_width = commits
+        .values()
+        .map(|commit| commit.author.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let line_number_width = end.to_string().len();
+
+    for line_number in start..=end {
+        let commit = &commits[&attributions[line_number - 1]];
+        writer.writeln(format!(
+            "{} ({:<author_width$} {} {:>line_number_width$}) {}",
+            commit.short_id_as_string(),
+            commit.author.name,
+            to_blame_timestring(commit.timestamp).unwrap_or_default(),
+            line_number,
+            lines[line_number - 1],
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Walk the history from the start commit and find the commit that introduced each line of the
+/// start blob. Only first parents are followed, so a line that a merge brought in from another
+/// parent is attributed to the merge commit.
+fn attribute_lines(
+    start_commit_id: ObjectId,
+    start_blob: Blob,
+    path: &Path,
+    repository: &Repository,
+) -> crate::Result<Vec<ObjectId>> {
+    let start_content = String::from_utf8_lossy(start_blob.content()).into_owned();
+    let num_lines = start_content.lines().count();
+
+    let mut attributions: Vec<Option<ObjectId>> = vec![None; num_lines];
+    // position of each line of the start blob in the blob of the commit currently being visited
+    let mut positions: Vec<Option<usize>> = (0..num_lines).map(Some).collect();
+
+    let mut commit_id = start_commit_id;
+    let mut blob = start_blob;
+
+    loop {
+        let commit = repository.database.load_commit(&commit_id)?;
+        let parent_blob = match commit.parent() {
+            Some(parent_id) => find_blob(parent_id, path, repository)?,
+            None => None,
+        };
+
+        let (parent_id, parent_blob) = match (commit.parent(), parent_blob) {
+            (Some(parent_id), Some(parent_blob)) => (parent_id.clone(), parent_blob),
+            _ => {
+                for (attribution, position) in attributions.iter_mut().zip(positions.iter()) {
+                    if position.is_some() {
+                        *attribution = Some(commit_id.clone());
+                    }
+                }
+                break;
+            }
+        };
+
+        if parent_blob.id() != blob.id() {
+            let mapping = map_lines_to_parent(&parent_blob, &blob);
+            for (attribution, position) in attributions.iter_mut().zip(positions.iter_mut()) {
+                if let Some(current_position) = position {
+                    *position = mapping[*current_position];
+                    if position.is_none() {
+                        *attribution = Some(commit_id.clone());
+                    }
+                }
+            }
+        }
+
+        if positions.iter().all(|position| position.is_none()) {
+            break;
+        }
+
+        commit_id = parent_id;
+        blob = parent_blob;
+    }
+
+    Ok(attributions.into_iter().map(Option::unwrap).collect())
+}
+
+/// Map each line of the blob to its position in the parent blob, if it was unchanged.
+fn map_lines_to_parent(parent_blob: &Blob, blob: &Blob) -> Vec<Option<usize>> {
+    let parent_content = String::from_utf8_lossy(parent_blob.content()).into_owned();
+    let content = String::from_utf8_lossy(blob.content()).into_owned();
+    let parent_lines = parent_content.lines().collect::<Vec<_>>();
+    let lines = content.lines().collect::<Vec<_>>();
+
+    let mut mapping = vec![None; lines.len()];
+    for edit in diff::edit_script(&parent_lines, &lines) {
+        if let (EditKind::Equal, Some(a_position), Some(b_position)) =
+            (edit.kind(), edit.a_position(), edit.b_position())
+        {
+            mapping[b_position] = Some(a_position);
+        }
+    }
+    mapping
+}
+
+fn find_blob(
+    commit_id: &ObjectId,
+    path: &Path,
+    repository: &Repository,
+) -> crate::Result<Option<Blob>> {
+    let mut resolver = ObjectResolver::from_reference(&commit_id.to_string(), repository)?;
+    resolver.find_blob_by_path_if_present(path)
+}
+
+fn to_blame_timestring(timestamp: u64) -> Option<String> {
+    let datetime = Local.timestamp_opt(timestamp as i64, 0).single()?;
+    Some(datetime.format("%Y-%m-%d %H:%M:%S %z").to_string())
+}
@@ -1,18 +1,90 @@
 use crate::{
+    config,
+    objects::{GitObject, ObjectId},
+    output::{Color, JsonValue, OutputWriter, Serializer},
     refs::{RefHandler, Revision},
+    rev_walk,
     workspace::Repository,
 };
 
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    /// Create the named branch, or list all branches if no name is given.
+    #[default]
+    CreateOrList,
+    /// Delete the named branch. Unless forced, refuse if it's not merged into HEAD.
+    Delete { force: bool },
+    /// Rename the named branch, or the current branch if no name is given, to the new name.
+    Rename(String),
+    /// Make the named branch, or the current branch if no name is given, track the upstream.
+    SetUpstream(String),
+}
+
 #[derive(Default, Builder, Debug)]
 pub struct Options {
     pub name: Option<String>,
     pub start_point: Option<String>,
+
+    #[builder(default)]
+    pub mode: Mode,
+
+    /// When listing branches, also show the commit each branch points to.
+    #[builder(default)]
+    pub verbose: bool,
+
+    /// When listing branches, write one JSON object per line for each branch.
+    #[builder(default)]
+    pub json: bool,
 }
 
-pub fn branch(options: &Options, repository: &Repository) -> crate::Result<()> {
-    if let Some(name) = &options.name {
-        let refs = RefHandler::new(repository);
+/// Create, list, delete or rename branches depending on the mode.
+pub fn branch(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
 
+    match (&options.mode, &options.name) {
+        (Mode::Delete { force }, Some(name)) => {
+            return delete_branch(name, *force, repository, writer);
+        }
+        (Mode::Delete { .. }, None) => {
+            return Err(crate::Error::Fatal(
+                None,
+                String::from("branch name required"),
+            ));
+        }
+        (Mode::Rename(new_name), name) => {
+            let old_name = match name {
+                Some(name) => name.clone(),
+                None => refs.current_branch()?.ok_or_else(|| {
+                    crate::Error::Fatal(
+                        None,
+                        String::from("cannot rename the current branch while not on any"),
+                    )
+                })?,
+            };
+            return refs.rename_ref(&old_name, new_name);
+        }
+        (Mode::SetUpstream(upstream), name) => {
+            let branch = match name {
+                Some(name) => name.clone(),
+                None => refs.current_branch()?.ok_or_else(|| {
+                    crate::Error::Fatal(
+                        None,
+                        String::from(
+                            "could not set upstream of HEAD when it does not point to any branch",
+                        ),
+                    )
+                })?,
+            };
+            return set_upstream(&branch, upstream, repository, writer);
+        }
+        (Mode::CreateOrList, _) => (),
+    }
+
+    if let Some(name) = &options.name {
         let start_point = match &options.start_point {
             Some(start_point) => Revision::parse(start_point)?.resolve(repository)?,
             None => refs.head()?,
@@ -21,5 +93,184 @@ pub fn branch(options: &Options, repository: &Repository) -> crate::Result<()> {
         return refs.create_ref(name, &start_point);
     }
 
+    if options.json {
+        list_branches_json(repository, writer)
+    } else {
+        list_branches(options, repository, writer)
+    }
+}
+
+/// Make the branch track the upstream, which is either a local branch or a remote-tracking branch
+/// like `origin/main`, by writing `branch.<name>.remote` and `branch.<name>.merge` to the local
+/// config.
+fn set_upstream(
+    branch: &str,
+    upstream: &str,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    if refs.find_branch(branch)?.is_none() {
+        let message = format!("branch '{}' does not exist", branch);
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let (remote, merged_branch) = if refs.find_branch(upstream)?.is_some() {
+        (".", upstream)
+    } else {
+        let remote_ref = format!("refs/remotes/{}", upstream);
+        match upstream.split_once('/') {
+            Some((remote, merged_branch)) if refs.read_ref(&remote_ref)?.is_some() => {
+                (remote, merged_branch)
+            }
+            _ => {
+                let message = format!(
+                    "the requested upstream branch '{}' does not exist",
+                    upstream
+                );
+                return Err(crate::Error::Fatal(None, message));
+            }
+        }
+    };
+
+    let config_file = repository.common_dir().join("config");
+    config::write_config_value(&config_file, &format!("branch.{}.remote", branch), remote)?;
+    config::write_config_value(
+        &config_file,
+        &format!("branch.{}.merge", branch),
+        &format!("refs/heads/{}", merged_branch),
+    )?;
+    writer.writeln(format!(
+        "branch '{}' set up to track '{}'.",
+        branch, upstream
+    ))?;
+    Ok(())
+}
+
+fn delete_branch(
+    name: &str,
+    force: bool,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let commit_id = refs
+        .find_branch(name)?
+        .ok_or_else(|| crate::Error::Fatal(None, format!("branch '{}' not found", name)))?;
+
+    if refs.current_branch()?.as_deref() == Some(name) {
+        let message = format!("cannot delete branch '{}' checked out", name);
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    if !force {
+        let head_id = refs.head()?;
+        let merge_base = rev_walk::merge_base(repository, &head_id, &commit_id)?;
+        if merge_base.as_ref() != Some(&commit_id) {
+            let message = format!(
+                "the branch '{}' is not fully merged.\n\
+                 If you are sure you want to delete it, run 'rut branch -D {}'",
+                name, name
+            );
+            return Err(crate::Error::Fatal(None, message));
+        }
+    }
+
+    refs.delete_ref(name)?;
+    writer.writeln(format!(
+        "Deleted branch {} (was {}).",
+        name,
+        commit_id.to_short_string()
+    ))?;
+    Ok(())
+}
+
+fn list_branches(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let current_branch = refs.current_branch()?;
+    let branches = refs.list_refs("refs/heads")?;
+
+    if current_branch.is_none() {
+        let head_id = refs.head()?;
+        writer
+            .write(String::from("* "))?
+            .set_color(Color::Green)?
+            .write(format!("(HEAD detached at {})", head_id.to_short_string()))?
+            .reset_formatting()?;
+        if options.verbose {
+            write_commit_summary(&head_id, repository, writer)?;
+        }
+        writer.linefeed()?;
+    }
+
+    // names are only padded to align the commits in verbose mode
+    let name_width = match options.verbose {
+        true => branches
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .max()
+            .unwrap_or(0),
+        false => 0,
+    };
+
+    for (name, commit_id) in branches {
+        if current_branch.as_ref() == Some(&name) {
+            writer
+                .write(String::from("* "))?
+                .set_color(Color::Green)?
+                .write(format!("{:<name_width$}", name))?
+                .reset_formatting()?;
+        } else {
+            writer.write(format!("  {:<name_width$}", name))?;
+        }
+
+        if options.verbose {
+            write_commit_summary(&commit_id, repository, writer)?;
+        }
+        writer.linefeed()?;
+    }
+
+    Ok(())
+}
+
+/// Write each branch as a JSON object with its name, commit, whether it's checked out and its
+/// upstream. A detached HEAD is written first, with the name `HEAD`.
+fn list_branches_json(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let current_branch = refs.current_branch()?;
+    let mut serializer = Serializer::new(writer);
+
+    if current_branch.is_none() {
+        serializer
+            .field("name", "HEAD")
+            .field("id", refs.head()?.to_string())
+            .field("current", true)
+            .field("upstream", JsonValue::Null)
+            .write()?;
+    }
+    for (name, commit_id) in refs.list_refs("refs/heads")? {
+        let upstream = refs.upstream(&name)?.map(|upstream| upstream.name);
+        serializer
+            .field("name", name.as_str())
+            .field("id", commit_id.to_string())
+            .field("current", current_branch.as_ref() == Some(&name))
+            .field("upstream", upstream)
+            .write()?;
+    }
+    Ok(())
+}
+
+fn write_commit_summary(
+    commit_id: &ObjectId,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let commit = repository.database.load_commit(commit_id)?;
+    let subject = commit.message.lines().next().unwrap_or_default();
+    writer.write(format!(" {} {}", commit.short_id_as_string(), subject))?;
     Ok(())
 }
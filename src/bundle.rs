@@ -0,0 +1,313 @@
+//! Bundles, files that carry refs along with a pack of the objects they need, for transferring
+//! history between repositories without a network connection.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::{
+    fsck,
+    hashing::HashAlgorithm,
+    objects::{ObjectId, ObjectType},
+    output::{OutputWriter, Progress},
+    pack,
+    refs::{self, RefHandler, Revision, RevisionRange},
+    workspace::{Database, Repository},
+};
+
+const V2_SIGNATURE: &str = "# v2 git bundle";
+const V3_SIGNATURE: &str = "# v3 git bundle";
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Bundle all refs and HEAD, in addition to any given revisions.
+    #[builder(default)]
+    pub all: bool,
+}
+
+/// The header of a bundle, which lists the refs it carries and the commits that the receiving
+/// repository must already have.
+struct Header {
+    hash_algorithm: HashAlgorithm,
+    /// The commits that the objects in the pack build on, with the subjects of their messages.
+    prerequisites: Vec<(ObjectId, String)>,
+    refs: Vec<(ObjectId, String)>,
+}
+
+/// Create a bundle of the refs named by the revisions, with the objects that are reachable from
+/// them but not from any excluded revisions, as in `main~3..main` or `^origin/main main`. The
+/// commits that the bundled commits build on become prerequisites of the bundle.
+pub fn create<S: AsRef<str>>(
+    path: &Path,
+    revisions: &[S],
+    options: &Options,
+    repository: &Repository,
+    progress: &mut dyn Progress,
+) -> crate::Result<()> {
+    let ref_handler = RefHandler::new(repository);
+    let all_refs = ref_handler.list_all_refs()?;
+    let mut refs = vec![];
+    let mut included = vec![];
+    let mut excluded = vec![];
+    if options.all {
+        for (name, object_id) in &all_refs {
+            refs.push((object_id.clone(), name.clone()));
+        }
+        if let Ok(head_id) = ref_handler.head() {
+            refs.push((head_id, String::from("HEAD")));
+        }
+    }
+    for revision in revisions {
+        let revision = revision.as_ref();
+        if let Some(excluded_revision) = revision.strip_prefix('^') {
+            excluded.push(Revision::parse(excluded_revision)?.resolve(repository)?);
+            continue;
+        }
+        let included_revision = match RevisionRange::parse(revision)? {
+            RevisionRange::Single(revision) => revision,
+            RevisionRange::Range(exclude, include) => {
+                excluded.push(exclude.resolve(repository)?);
+                include
+            }
+            RevisionRange::SymmetricDifference(_, _) => {
+                let message = format!("symmetric differences can't be bundled: {}", revision);
+                return Err(crate::Error::Fatal(None, message));
+            }
+        };
+        let object_id = included_revision.resolve(repository)?;
+        if let Some(name) = full_ref_name(&included_revision, &all_refs) {
+            refs.push((object_id.clone(), name));
+        }
+        included.push(object_id);
+    }
+    included.extend(refs.iter().map(|(object_id, _)| object_id.clone()));
+    if refs.is_empty() {
+        let message = String::from("Refusing to create empty bundle.");
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let database = &repository.database;
+    let excluded_objects = find_reachable(&excluded, database)?;
+    let mut object_ids = find_reachable(&included, database)?
+        .into_iter()
+        .filter(|object_id| !excluded_objects.contains(object_id))
+        .collect::<Vec<_>>();
+    object_ids.sort();
+
+    // the prerequisites are the excluded commits that bundled commits have as parents
+    let mut prerequisites = vec![];
+    let mut seen = HashSet::new();
+    for object_id in &object_ids {
+        if database.load_object(object_id)?.0 != ObjectType::Commit {
+            continue;
+        }
+        for parent in database.load_commit(object_id)?.parents {
+            if excluded_objects.contains(&parent) && seen.insert(parent.clone()) {
+                let message = database.load_commit(&parent)?.message;
+                let subject = message.lines().next().unwrap_or_default().to_owned();
+                prerequisites.push((parent, subject));
+            }
+        }
+    }
+
+    let header = Header {
+        hash_algorithm: repository.hash_algorithm(),
+        prerequisites,
+        refs,
+    };
+    let mut output = BufWriter::new(fs::File::create(path)?);
+    write_header(&header, &mut output)?;
+    pack::write_pack(&object_ids, database, &mut output, progress)?;
+    output.flush()?;
+    Ok(())
+}
+
+/// Check that the bundle is intact and that the repository has its prerequisites, and print the
+/// refs it carries and the commits it requires.
+pub fn verify(
+    path: &Path,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let (header, _) = read_verified_bundle(path, repository)?;
+    let count = |count: usize, noun: &str| match count {
+        1 => format!("this {}", noun),
+        count => format!("these {} {}s", count, noun),
+    };
+
+    writer.writeln(format!(
+        "The bundle contains {}:",
+        count(header.refs.len(), "ref")
+    ))?;
+    for (object_id, name) in &header.refs {
+        writer.writeln(format!("{} {}", object_id, name))?;
+    }
+    if header.prerequisites.is_empty() {
+        writer.writeln(String::from("The bundle records a complete history."))?;
+    } else {
+        writer.writeln(format!(
+            "The bundle requires {}:",
+            count(header.prerequisites.len(), "ref")
+        ))?;
+        for (object_id, subject) in &header.prerequisites {
+            writer.writeln(format!("{} {}", object_id, subject))?;
+        }
+    }
+    let path = relative_to_working_directory(path, repository);
+    writer.writeln(format!("{} is okay", path.display()))?;
+    Ok(())
+}
+
+/// Store the objects of the bundle in the repository and print the refs it carries, which are
+/// left to the caller to update.
+pub fn unbundle(
+    path: &Path,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let (header, pack) = read_verified_bundle(path, repository)?;
+    let database = &repository.database;
+    for object in pack::read_pack(&pack, database)? {
+        database.store_object(&object)?;
+    }
+    for (object_id, name) in &header.refs {
+        writer.writeln(format!("{} {}", object_id, name))?;
+    }
+    Ok(())
+}
+
+/// The path as the user gave it, relative to the directory they are working in.
+fn relative_to_working_directory<'a>(path: &'a Path, repository: &Repository) -> &'a Path {
+    let worktree = repository.worktree();
+    let working_directory = worktree.root().join(worktree.prefix());
+    path.strip_prefix(&working_directory).unwrap_or(path)
+}
+
+/// The full name of the ref that the revision names, if it names one.
+fn full_ref_name(revision: &Revision, all_refs: &[(String, ObjectId)]) -> Option<String> {
+    let Revision::Reference(name) = revision else {
+        return None;
+    };
+    if name == "HEAD" {
+        return Some(name.clone());
+    }
+    all_refs
+        .iter()
+        .map(|(full_name, _)| full_name)
+        .find(|full_name| *full_name == name || refs::shorten_ref_name(full_name) == name)
+        .cloned()
+}
+
+fn find_reachable(roots: &[ObjectId], database: &Database) -> crate::Result<HashSet<ObjectId>> {
+    fsck::find_reachable(roots, |object_id| {
+        let (object_type, _) = database.load_object(object_id)?;
+        Ok(fsck::load_links(object_id, object_type, database)?
+            .into_iter()
+            .map(|(_, object_id)| object_id)
+            .collect())
+    })
+}
+
+fn write_header(header: &Header, output: &mut dyn Write) -> crate::Result<()> {
+    match header.hash_algorithm {
+        HashAlgorithm::Sha1 => writeln!(output, "{}", V2_SIGNATURE)?,
+        algorithm => {
+            writeln!(output, "{}", V3_SIGNATURE)?;
+            writeln!(output, "@object-format={}", algorithm.name())?;
+        }
+    }
+    for (object_id, subject) in &header.prerequisites {
+        writeln!(output, "-{} {}", object_id, subject)?;
+    }
+    for (object_id, name) in &header.refs {
+        writeln!(output, "{} {}", object_id, name)?;
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+/// Read the header and the pack of the bundle, and check that the pack is intact and that the
+/// repository has all prerequisites.
+fn read_verified_bundle(path: &Path, repository: &Repository) -> crate::Result<(Header, Vec<u8>)> {
+    let content = fs::read(path).map_err(|error| {
+        let path = relative_to_working_directory(path, repository);
+        let message = format!("could not open '{}': {}", path.display(), error);
+        crate::Error::Fatal(Some(Box::new(error)), message)
+    })?;
+    let (header, pack_start) = parse_header(&content).ok_or_else(|| {
+        let message = format!(
+            "'{}' does not look like a v2 or v3 bundle file",
+            path.display()
+        );
+        crate::Error::Fatal(None, message)
+    })?;
+    if header.hash_algorithm != repository.hash_algorithm() {
+        let message = format!(
+            "bundle uses {} object ids, but the repository uses {}",
+            header.hash_algorithm.name(),
+            repository.hash_algorithm().name()
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let pack = content[pack_start..].to_vec();
+    pack::verify_pack(&pack, header.hash_algorithm)?;
+
+    let missing = header
+        .prerequisites
+        .iter()
+        .filter(|(object_id, _)| !repository.database.object_path(object_id).exists())
+        .map(|(object_id, subject)| format!("error: {} {}", object_id, subject))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        let message = format!(
+            "Repository lacks these prerequisite commits:\n{}",
+            missing.join("\n")
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    Ok((header, pack))
+}
+
+/// Parse the header of a bundle, and return it along with the offset at which the pack starts.
+fn parse_header(content: &[u8]) -> Option<(Header, usize)> {
+    let mut header = Header {
+        hash_algorithm: HashAlgorithm::Sha1,
+        prerequisites: vec![],
+        refs: vec![],
+    };
+    let mut position = 0;
+    let mut next_line = || {
+        let length = content[position..].iter().position(|byte| *byte == b'\n')?;
+        let line = std::str::from_utf8(&content[position..position + length]).ok()?;
+        position += length + 1;
+        Some(line)
+    };
+
+    let is_v3 = match next_line()? {
+        V2_SIGNATURE => false,
+        V3_SIGNATURE => true,
+        _ => return None,
+    };
+    loop {
+        let line = next_line()?;
+        if line.is_empty() {
+            break;
+        } else if let Some(capability) = line.strip_prefix('@').filter(|_| is_v3) {
+            // other capabilities, such as filters, can't be honored
+            let format = capability.strip_prefix("object-format=")?;
+            header.hash_algorithm = HashAlgorithm::parse(format)?;
+        } else if let Some(prerequisite) = line.strip_prefix('-') {
+            let (object_id, subject) = prerequisite.split_once(' ').unwrap_or((prerequisite, ""));
+            let object_id = ObjectId::from_sha(object_id).ok()?;
+            header.prerequisites.push((object_id, subject.to_owned()));
+        } else {
+            let (object_id, name) = line.split_once(' ')?;
+            let object_id = ObjectId::from_sha(object_id).ok()?;
+            header.refs.push((object_id, name.to_owned()));
+        }
+    }
+    Some((header, position))
+}
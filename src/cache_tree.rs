@@ -0,0 +1,145 @@
+//! The cache tree index extension, which records the tree id of every directory in the index so
+//! that trees of unchanged directories need not be rebuilt when committing. A directory is
+//! invalidated whenever an entry in it, or in any of its subdirectories, is added or removed.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use crate::objects::ObjectId;
+
+pub const SIGNATURE: &[u8; 4] = b"TREE";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CacheTree {
+    /// The number of index entries in the directory and its subdirectories, if it's valid.
+    entry_count: Option<usize>,
+    object_id: Option<ObjectId>,
+    subtrees: BTreeMap<String, CacheTree>,
+}
+
+impl CacheTree {
+    /// Create a valid cache tree for a directory with the given tree id.
+    pub fn new(
+        object_id: ObjectId,
+        entry_count: usize,
+        subtrees: BTreeMap<String, CacheTree>,
+    ) -> CacheTree {
+        CacheTree {
+            entry_count: Some(entry_count),
+            object_id: Some(object_id),
+            subtrees,
+        }
+    }
+
+    /// The tree id of the directory, if it's still valid.
+    pub fn object_id(&self) -> Option<&ObjectId> {
+        self.entry_count.and(self.object_id.as_ref())
+    }
+
+    pub fn entry_count(&self) -> Option<usize> {
+        self.entry_count
+    }
+
+    pub fn subtree(&self, name: &str) -> Option<&CacheTree> {
+        self.subtrees.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entry_count.is_none() && self.subtrees.is_empty()
+    }
+
+    /// Invalidate the directories that contain the path, starting from this one.
+    pub fn invalidate(&mut self, path: &Path) {
+        let mut cache_tree = self;
+        cache_tree.entry_count = None;
+        cache_tree.object_id = None;
+
+        for component in path.parent().into_iter().flat_map(Path::components) {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            cache_tree = match cache_tree.subtrees.get_mut(&*name.to_string_lossy()) {
+                Some(subtree) => subtree,
+                None => break,
+            };
+            cache_tree.entry_count = None;
+            cache_tree.object_id = None;
+        }
+    }
+
+    /// Parse the data of the extension, which lists the directories in pre-order. Each directory
+    /// is given as its name, a NUL byte, the entry count and the number of subtrees in ASCII
+    /// separated by a space and terminated by a newline, and finally the tree id if the entry
+    /// count is not negative.
+    pub fn from_bytes(bytes: &[u8], raw_id_size: usize) -> Result<CacheTree, String> {
+        let (_, cache_tree, consumed_bytes) = CacheTree::parse_node(bytes, raw_id_size)?;
+        if consumed_bytes != bytes.len() {
+            return Err(String::from("trailing data in cache tree extension"));
+        }
+        Ok(cache_tree)
+    }
+
+    fn parse_node(bytes: &[u8], raw_id_size: usize) -> Result<(String, CacheTree, usize), String> {
+        let corrupt = || String::from("corrupt cache tree extension");
+
+        let name_end = bytes
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(corrupt)?;
+        let name = String::from_utf8(bytes[..name_end].to_vec()).map_err(|_| corrupt())?;
+        let mut position = name_end + 1;
+
+        let counts_end = bytes[position..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(corrupt)?
+            + position;
+        let counts = std::str::from_utf8(&bytes[position..counts_end]).map_err(|_| corrupt())?;
+        let (entry_count, subtree_count) = counts.split_once(' ').ok_or_else(corrupt)?;
+        let entry_count: i64 = entry_count.parse().map_err(|_| corrupt())?;
+        let subtree_count: usize = subtree_count.parse().map_err(|_| corrupt())?;
+        position = counts_end + 1;
+
+        let mut cache_tree = CacheTree::default();
+        if entry_count >= 0 {
+            let raw_id = bytes
+                .get(position..(position + raw_id_size))
+                .ok_or_else(corrupt)?;
+            cache_tree.entry_count = Some(entry_count as usize);
+            cache_tree.object_id = Some(ObjectId::from_sha_bytes(raw_id)?);
+            position += raw_id_size;
+        }
+
+        for _ in 0..subtree_count {
+            let (subtree_name, subtree, consumed_bytes) =
+                CacheTree::parse_node(&bytes[position..], raw_id_size)?;
+            cache_tree.subtrees.insert(subtree_name, subtree);
+            position += consumed_bytes;
+        }
+
+        Ok((name, cache_tree, position))
+    }
+
+    pub fn as_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_node("", &mut bytes);
+        bytes
+    }
+
+    fn write_node(&self, name: &str, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.push(0);
+        let entry_count = match self.entry_count {
+            Some(entry_count) => entry_count.to_string(),
+            None => String::from("-1"),
+        };
+        bytes.extend(format!("{} {}\n", entry_count, self.subtrees.len()).into_bytes());
+        if let Some(object_id) = self.object_id() {
+            bytes.extend_from_slice(object_id.bytes());
+        }
+
+        for (subtree_name, subtree) in &self.subtrees {
+            subtree.write_node(subtree_name, bytes);
+        }
+    }
+}
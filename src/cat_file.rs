@@ -0,0 +1,128 @@
+use std::io::BufRead;
+use std::str;
+use std::sync::Arc;
+
+use crate::index::FileMode;
+use crate::objects::{ObjectId, ObjectType};
+use crate::output::OutputWriter;
+use crate::refs::Revision;
+use crate::workspace::Repository;
+
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    #[default]
+    Pretty,
+    Type,
+    Size,
+}
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub mode: Mode,
+}
+
+/// Print the type, size or pretty-printed content of the object named by `object`.
+pub fn cat_file(
+    object: &str,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let object_id = Revision::parse(object)?.resolve(repository)?;
+    let (object_type, content) = load_object(&object_id, object, repository)?;
+
+    match options.mode {
+        Mode::Type => {
+            writer.writeln(object_type.to_string())?;
+        }
+        Mode::Size => {
+            writer.writeln(content.len().to_string())?;
+        }
+        Mode::Pretty => match object_type {
+            ObjectType::Tree => write_tree(&object_id, repository, writer)?,
+            _ => {
+                writer.write(String::from_utf8_lossy(&content).into_owned())?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Read object names from the reader, one per line, and write each object in the format
+/// `<oid> <type> <size>\n<content>\n`. Objects that can't be found are reported as
+/// `<name> missing`.
+pub fn cat_file_batch(
+    reader: &mut dyn BufRead,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let object = line.trim();
+        if object.is_empty() {
+            continue;
+        }
+
+        let loaded = Revision::parse(object)
+            .ok()
+            .and_then(|revision| revision.resolve(repository).ok())
+            .and_then(|object_id| {
+                repository
+                    .database
+                    .load_object(&object_id)
+                    .ok()
+                    .map(|(object_type, content)| (object_id, object_type, content))
+            });
+
+        match loaded {
+            Some((object_id, object_type, content)) => {
+                writer.writeln(format!("{} {} {}", object_id, object_type, content.len()))?;
+                writer.write(String::from_utf8_lossy(&content).into_owned())?;
+                writer.linefeed()?;
+            }
+            None => {
+                writer.writeln(format!("{} missing", object))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn load_object(
+    object_id: &ObjectId,
+    name: &str,
+    repository: &Repository,
+) -> crate::Result<(ObjectType, Arc<[u8]>)> {
+    repository.database.load_object(object_id).map_err(|error| {
+        crate::Error::Fatal(
+            Some(Box::new(error)),
+            format!("Not a valid object name {}", name),
+        )
+    })
+}
+
+fn write_tree(
+    tree_id: &ObjectId,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let tree = repository.database.load_tree(tree_id)?;
+    for entry in tree.entries() {
+        let object_type = match entry.mode {
+            FileMode::Directory => ObjectType::Tree,
+            FileMode::Gitlink => ObjectType::Commit,
+            _ => ObjectType::Blob,
+        };
+        writer.writeln(format!(
+            "{:0>6} {} {}\t{}",
+            entry.mode.as_str(),
+            object_type,
+            entry.object_id,
+            entry.name
+        ))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Writing files from the index to the worktree, as the low-level counterpart to `restore`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::index::{FileMode, IndexEntry};
+use crate::objects::TreeEntry;
+use crate::output::OutputWriter;
+use crate::restore;
+use crate::status::{self, FileCheck};
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Check out all files in the index instead of the given paths.
+    #[builder(default)]
+    pub all: bool,
+
+    /// Overwrite files that already exist in the worktree.
+    #[builder(default)]
+    pub force: bool,
+}
+
+/// Write the files at the paths, given relative to the root of the worktree, with their content in
+/// the index, and refresh the stat information of their entries. Files that already exist are
+/// only overwritten if forced, and are reported unless they are up to date. Returns whether all
+/// files were checked out.
+pub fn checkout_index<P: AsRef<Path>>(
+    paths: &[P],
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let root = repository.worktree().root();
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    let paths = if options.all {
+        index
+            .get_entries()
+            .into_iter()
+            .map(|entry| entry.path.clone())
+            .collect::<Vec<_>>()
+    } else {
+        paths.iter().map(|path| path.as_ref().to_owned()).collect()
+    };
+
+    let mut all_checked_out = true;
+    for path in paths {
+        let Some(entry) = index.get(&path) else {
+            let reason = if index.is_unmerged(&path) {
+                "is unmerged"
+            } else {
+                "is not in the cache"
+            };
+            writer.writeln(format!("error: {} {}", path.display(), reason))?;
+            all_checked_out = false;
+            continue;
+        };
+        if entry.file_mode() == FileMode::Gitlink {
+            continue;
+        }
+
+        let absolute_path = root.join(&path);
+        if !options.force && fs::symlink_metadata(&absolute_path).is_ok() {
+            let check = status::check_file(&absolute_path, entry, repository);
+            if matches!(check, FileCheck::Modified) {
+                writer.writeln(format!("{} already exists, no checkout", path.display()))?;
+                all_checked_out = false;
+            }
+            continue;
+        }
+
+        let tree_entry = TreeEntry::new(&path, entry.object_id.clone(), entry.file_mode());
+        restore::write_tree_entry(&absolute_path, &tree_entry, repository)?;
+        let metadata = fs::metadata(&absolute_path)?;
+        index.add_entry(IndexEntry::new(
+            PathBuf::from(&path),
+            tree_entry.object_id,
+            &metadata,
+        ));
+    }
+
+    index_lockfile.write()?;
+    Ok(all_checked_out)
+}
@@ -0,0 +1,91 @@
+//! Finding the commits of a branch that have yet to be applied to its upstream, which tells
+//! which commits a rebase would replay and which were already cherry-picked.
+
+use std::collections::HashSet;
+use std::slice;
+
+use crate::log;
+use crate::objects::{GitObject, ObjectId};
+use crate::output::OutputWriter;
+use crate::patch_id;
+use crate::refs::{RefHandler, Revision};
+use crate::rev_walk::RevWalk;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// The revision to compare against, defaults to the upstream of the current branch.
+    #[builder(default)]
+    pub upstream: Option<String>,
+
+    /// The revision whose commits are compared, defaults to HEAD.
+    #[builder(default)]
+    pub head: Option<String>,
+
+    /// Show the subject of each commit along with its id.
+    #[builder(default)]
+    pub verbose: bool,
+}
+
+/// Write the commits reachable from the head but not from the upstream, oldest first. Commits
+/// with the same changes as a commit of the upstream, as told by their patch ids, are prefixed with
+/// `-`, and all others with `+`. Merge commits are left out.
+pub fn cherry(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let upstream_id = match &options.upstream {
+        Some(upstream) => Revision::parse(upstream)?.resolve(repository)?,
+        None => resolve_tracked_upstream(repository)?,
+    };
+    let head_id =
+        Revision::parse(options.head.as_deref().unwrap_or("HEAD"))?.resolve(repository)?;
+
+    let upstream_patch_ids = RevWalk::new(
+        slice::from_ref(&upstream_id),
+        slice::from_ref(&head_id),
+        repository,
+    )?
+    .map(|commit| patch_id::patch_id(&commit?, repository))
+    .filter_map(Result::transpose)
+    .collect::<crate::Result<HashSet<_>>>()?;
+
+    let mut commits = RevWalk::new(
+        slice::from_ref(&head_id),
+        slice::from_ref(&upstream_id),
+        repository,
+    )?
+    .collect::<crate::Result<Vec<_>>>()?;
+    commits.retain(|commit| commit.parents.len() <= 1);
+    commits.reverse();
+
+    for commit in &commits {
+        let is_upstream = patch_id::patch_id(commit, repository)?
+            .is_some_and(|patch_id| upstream_patch_ids.contains(&patch_id));
+        let sign = if is_upstream { '-' } else { '+' };
+        let mut line = format!("{} {}", sign, commit.id());
+        if options.verbose {
+            let (subject, _) = log::split_message(&commit.message);
+            line = format!("{} {}", line, subject);
+        }
+        writer.writeln(line)?;
+    }
+
+    Ok(())
+}
+
+fn resolve_tracked_upstream(repository: &Repository) -> crate::Result<ObjectId> {
+    let refs = RefHandler::new(repository);
+    let upstream = match refs.current_branch()? {
+        Some(branch) => refs.upstream(&branch)?,
+        None => None,
+    };
+    upstream
+        .and_then(|upstream| upstream.object_id)
+        .ok_or_else(|| {
+            let message =
+                "could not find a tracked remote branch, please specify <upstream> manually";
+            crate::Error::Fatal(None, message.to_owned())
+        })
+}
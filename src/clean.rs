@@ -0,0 +1,62 @@
+use std::fs;
+
+use crate::output::OutputWriter;
+use crate::status;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub dry_run: bool,
+
+    #[builder(default)]
+    pub force: bool,
+
+    /// Also remove untracked directories.
+    #[builder(default)]
+    pub directories: bool,
+}
+
+/// Remove untracked files from the worktree, and untracked directories if requested.
+pub fn clean(
+    repository: &Repository,
+    options: &Options,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if !options.dry_run && !options.force {
+        let message =
+            "clean.requireForce defaults to true and neither -n nor -f given; refusing to clean";
+        return Err(crate::Error::Fatal(None, message.to_owned()));
+    }
+
+    let worktree = repository.worktree();
+    let index = repository.load_index_unlocked()?;
+    let untracked_paths = status::resolve_untracked(repository, &index);
+
+    for path in untracked_paths {
+        let is_dir = path.is_dir();
+        if is_dir && !options.directories {
+            continue;
+        }
+
+        let relative_path = worktree.relativize_path(&path);
+        let suffix = if is_dir { "/" } else { "" };
+        if options.dry_run {
+            writer.writeln(format!(
+                "Would remove {}{}",
+                relative_path.display(),
+                suffix
+            ))?;
+            continue;
+        }
+
+        if is_dir {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        writer.writeln(format!("Removing {}{}", relative_path.display(), suffix))?;
+    }
+
+    Ok(())
+}
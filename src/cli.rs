@@ -1,198 +1,1634 @@
-use std::ffi::{c_int, OsString};
+use std::env;
+use std::ffi::OsString;
 use std::fmt::Debug;
-use std::io::{Error, Write};
-use std::os::unix::io::AsRawFd;
+use std::fs;
+use std::io::{IsTerminal, Write};
 
-use crate::output::{Color, OutputWriter, Style};
-use crate::{add, commit, diff, init, log, restore, rm, status, workspace::Repository};
-use crate::{branch, revparse};
+use crate::alias::Alias;
+use crate::file;
+use crate::hashing::HashAlgorithm;
+use crate::objects::GitObject;
+use crate::output::{self, Color, ColorMode, OutputWriter, Progress, Style, WriteAdapter};
+use crate::prompt::LinePrompt;
+use crate::refs::Revision;
+use crate::{
+    add, alias, am, commit, config, diff, difftool, format_patch, init, log, mv, restore, rm,
+    status, switch, trace, workspace::Repository,
+};
+use crate::{
+    archive, blame, branch, bundle, cat_file, checkout_index, cherry, clean, commit_graph,
+    count_objects, fast_export, fast_import, for_each_ref, fsck, grep, hash_object, ls_files,
+    ls_tree, merge_base, prune, range_diff, read_tree, rebase, rename, rev_list, revparse,
+    shortlog, show_ref, submodule, symbolic_ref, update_index, update_ref, worktree,
+};
 use std::io;
 use std::path::{Path, PathBuf};
-use std::process::{Child, Command, Stdio};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant};
 
-use clap::{Parser, Subcommand};
+use clap::builder::FalseyValueParser;
+use clap::{ArgGroup, Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 struct Args {
+    /// Run as if rut was started in this directory.
+    #[arg(short = 'C', value_name = "PATH")]
+    directory: Option<PathBuf>,
+
+    /// Path to the git directory, instead of looking for `.git` in the current directory and its
+    /// parents.
+    #[arg(long, env = "GIT_DIR", value_name = "PATH")]
+    git_dir: Option<PathBuf>,
+
+    /// Path to the root of the worktree. Defaults to the current directory if the git directory
+    /// is given.
+    #[arg(long, env = "GIT_WORK_TREE", value_name = "PATH")]
+    work_tree: Option<PathBuf>,
+
+    /// Page the output of any command, not only of those that show history and diffs.
+    #[arg(short = 'p', long)]
+    paginate: bool,
+
+    /// Never page the output.
+    #[arg(long, overrides_with = "paginate")]
+    no_pager: bool,
+
+    /// Trace object reads and writes, locks, ref updates and the time taken by the command to
+    /// stderr.
+    #[arg(long, env = "RUT_TRACE", value_parser = FalseyValueParser::new())]
+    verbose: bool,
+
+    /// When to color the output: `always`, `never` or `auto`, which colors it only in a terminal.
+    /// Defaults to the `color.ui` config.
+    #[arg(long, value_name = "WHEN", value_parser = output::parse_color_mode)]
+    color: Option<ColorMode>,
+
     #[command(subcommand)]
     action: Action,
 }
 
 #[derive(Subcommand, Debug)]
 enum Action {
-    Init,
+    Init {
+        #[arg(short = 'b', long)]
+        initial_branch: Option<String>,
+        #[arg(long)]
+        template: Option<PathBuf>,
+        #[arg(long, value_parser = init::parse_object_format)]
+        object_format: Option<HashAlgorithm>,
+    },
     Commit {
         #[arg(short, long)]
         message: Option<String>,
+        #[arg(long)]
+        amend: bool,
+        #[arg(short, long)]
+        all: bool,
+        #[arg(long, conflicts_with = "message")]
+        no_edit: bool,
+        #[arg(short = 'n', long)]
+        no_verify: bool,
     },
     Add {
-        path: String,
+        #[arg(required_unless_present = "patch")]
+        pathspecs: Vec<String>,
+        #[arg(short, long)]
+        patch: bool,
     },
     Rm {
-        path: String,
+        #[arg(required = true)]
+        pathspecs: Vec<String>,
+        #[arg(long)]
+        cached: bool,
+        #[arg(short)]
+        recursive: bool,
+        #[arg(short, long)]
+        force: bool,
+    },
+    Mv {
+        source: String,
+        destination: String,
     },
     Status {
+        #[arg(
+            long,
+            value_name = "version",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "v1",
+            value_parser = status::parse_porcelain_version
+        )]
+        porcelain: Option<status::OutputFormat>,
+        #[arg(long, value_parser = status::parse_format, conflicts_with = "porcelain")]
+        format: Option<status::OutputFormat>,
+        #[arg(short, long)]
+        branch: bool,
+        #[arg(short = 'z')]
+        null_terminated: bool,
+        #[arg(
+            short = 'u',
+            long,
+            value_name = "mode",
+            num_args = 0..=1,
+            default_missing_value = "all",
+            value_parser = status::parse_untracked_files
+        )]
+        untracked_files: Option<status::UntrackedFiles>,
         #[arg(long)]
-        porcelain: bool,
+        ignored: bool,
+        #[arg(
+            long,
+            value_name = "n",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "50%",
+            value_parser = rename::parse_threshold
+        )]
+        find_renames: Option<u32>,
+        pathspecs: Vec<String>,
     },
     Diff {
         #[arg(long)]
         cached: bool,
+        #[arg(long, group = "diff_format")]
+        stat: bool,
+        #[arg(long, group = "diff_format")]
+        name_only: bool,
+        #[arg(long, group = "diff_format")]
+        name_status: bool,
+        #[arg(long, value_parser = ["json"], requires = "name_status")]
+        format: Option<String>,
+        #[arg(short = 'z')]
+        null_terminated: bool,
+        #[arg(short = 'U', long = "unified", value_name = "n")]
+        context_lines: Option<usize>,
+        #[arg(long, value_parser = diff::parse_algorithm)]
+        diff_algorithm: Option<diff::Algorithm>,
+        /// Exit with status 1 if there are differences, and 0 otherwise.
+        #[arg(long)]
+        exit_code: bool,
+        /// Don't show the differences. Implies --exit-code.
+        #[arg(long)]
+        quiet: bool,
+        /// Don't run the program in `diff.external`.
+        #[arg(long)]
+        no_ext_diff: bool,
+        #[arg(
+            short = 'M',
+            long,
+            value_name = "n",
+            num_args = 0..=1,
+            default_missing_value = "50%",
+            value_parser = rename::parse_threshold
+        )]
+        find_renames: Option<u32>,
+        /// An optional revision followed by pathspecs, unless separated by `--`.
+        args: Vec<String>,
+        #[arg(last = true)]
+        pathspecs: Vec<String>,
+    },
+    Difftool {
+        #[arg(long)]
+        cached: bool,
+        #[arg(short, long)]
+        tool: Option<String>,
+        #[arg(short = 'x', long)]
+        extcmd: Option<String>,
+        #[arg(short = 'y', long, overrides_with = "prompt")]
+        no_prompt: bool,
+        #[arg(long, overrides_with = "no_prompt")]
+        prompt: bool,
+        /// An optional revision followed by pathspecs, unless separated by `--`.
+        args: Vec<String>,
+        #[arg(last = true)]
+        pathspecs: Vec<String>,
+    },
+    FormatPatch {
+        /// The commits to export, or the commit since which to export up to HEAD.
+        revision: String,
+        #[arg(short, long = "output-directory", value_name = "DIR")]
+        output_directory: Option<PathBuf>,
+    },
+    Am {
+        #[arg(required = true)]
+        mboxes: Vec<PathBuf>,
+    },
+    Rebase {
+        /// The commit to replay the commits of the current branch on top of.
+        #[arg(required_unless_present_any = ["continue_rebase", "abort"])]
+        upstream: Option<String>,
+        /// Continue the rebase after resolving conflicts.
+        #[arg(long = "continue", conflicts_with_all = ["upstream", "abort"])]
+        continue_rebase: bool,
+        /// Abort the rebase and return to the original branch.
+        #[arg(long, conflicts_with = "upstream")]
+        abort: bool,
+    },
+    Cherry {
+        /// The branch to look for the commits in, defaults to the upstream of the current branch.
+        upstream: Option<String>,
+        /// The branch whose commits to look for, defaults to HEAD.
+        head: Option<String>,
+        /// Show the subject of each commit.
+        #[arg(short, long)]
+        verbose: bool,
     },
     Restore {
-        path: String,
+        #[arg(required = true)]
+        pathspecs: Vec<String>,
         #[arg(long, default_value = "HEAD")]
         source: String,
     },
+    Switch {
+        #[arg(required_unless_present = "create")]
+        target: Option<String>,
+        #[arg(long, conflicts_with = "create")]
+        detach: bool,
+        #[arg(short, long)]
+        create: Option<String>,
+    },
     Log {
         #[arg(short = 'n', long)]
         max_count: Option<u32>,
         #[arg(long)]
         oneline: bool,
+        #[arg(long, value_parser = log::parse_format, conflicts_with = "oneline")]
+        pretty: Option<log::Format>,
+        #[arg(long, value_parser = log::parse_format, conflicts_with_all = ["oneline", "pretty"])]
+        format: Option<log::Format>,
+        #[arg(short, long)]
+        patch: bool,
+        #[arg(long)]
+        stat: bool,
+        #[arg(long, requires = "paths")]
+        follow: bool,
+        #[arg(long)]
+        graph: bool,
+        #[arg(long)]
+        author: Option<String>,
+        #[arg(long)]
+        grep: Option<String>,
+        #[arg(long, value_parser = log::parse_date)]
+        since: Option<u64>,
+        #[arg(long, value_parser = log::parse_date)]
+        until: Option<u64>,
+        #[arg(long, overrides_with = "no_mailmap")]
+        use_mailmap: bool,
+        #[arg(long, overrides_with = "use_mailmap")]
+        no_mailmap: bool,
+        revision: Option<String>,
+        #[arg(last = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Summarize the history by author, with the subjects of their commits.
+    Shortlog {
+        /// Sort authors by their number of commits instead of by name.
+        #[arg(short, long)]
+        numbered: bool,
+        /// Only show the number of commits of each author.
+        #[arg(short, long)]
+        summary: bool,
+        /// Show the email of each author.
+        #[arg(short, long)]
+        email: bool,
+        revision: Option<String>,
     },
     Branch {
         name: Option<String>,
         start_point: Option<String>,
+        #[arg(short, long, conflicts_with = "name")]
+        verbose: bool,
+        #[arg(long, value_parser = ["json"], conflicts_with_all = ["name", "verbose"])]
+        format: Option<String>,
+        #[arg(short, long, group = "branch_mode", requires = "name")]
+        delete: bool,
+        #[arg(short = 'D', group = "branch_mode", requires = "name")]
+        force_delete: bool,
+        #[arg(short = 'm', long = "move", group = "branch_mode", requires = "name")]
+        rename: bool,
+        #[arg(
+            short = 'u',
+            long,
+            value_name = "UPSTREAM",
+            group = "branch_mode",
+            conflicts_with = "start_point"
+        )]
+        set_upstream_to: Option<String>,
     },
     RevParse {
+        revision: Option<String>,
+        #[arg(
+            long,
+            value_name = "n",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "7"
+        )]
+        short: Option<usize>,
+        #[arg(long)]
+        abbrev_ref: bool,
+        #[arg(long)]
+        verify: bool,
+        #[arg(short, long)]
+        quiet: bool,
+        #[arg(long)]
+        git_dir: bool,
+        #[arg(long)]
+        show_toplevel: bool,
+    },
+    RevList {
         revision: String,
+        #[arg(long)]
+        count: bool,
+        #[arg(short = 'n', long)]
+        max_count: Option<u32>,
+        /// Mark each commit with the side of a symmetric difference it is reachable from.
+        #[arg(long, conflicts_with = "count")]
+        left_right: bool,
+    },
+    RangeDiff {
+        /// The commits of the old version of the series, as a range `A..B`.
+        old_range: String,
+        /// The commits of the new version of the series, as a range `C..D`.
+        new_range: String,
+    },
+    MergeBase {
+        first_revision: String,
+        second_revision: String,
+    },
+    #[command(group(
+        ArgGroup::new("mode")
+            .required(true)
+            .args(["show_type", "size", "pretty", "batch"]),
+    ))]
+    CatFile {
+        #[arg(short = 't')]
+        show_type: bool,
+        #[arg(short = 's')]
+        size: bool,
+        #[arg(short = 'p')]
+        pretty: bool,
+        #[arg(long)]
+        batch: bool,
+        #[arg(required_unless_present = "batch")]
+        object: Option<String>,
+    },
+    CountObjects {
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    Fsck {
+        #[arg(long)]
+        unreachable: bool,
+    },
+    UpdateRef {
+        #[arg(short = 'd')]
+        delete: bool,
+        #[arg(value_name = "REF")]
+        ref_name: String,
+        /// The new value, or with -d the old value that the ref must have.
+        #[arg(required_unless_present = "delete")]
+        new_value: Option<String>,
+        /// The old value that the ref must have, which is empty or the null id if it must not
+        /// exist.
+        #[arg(conflicts_with = "delete")]
+        old_value: Option<String>,
+    },
+    SymbolicRef {
+        #[arg(long)]
+        short: bool,
+        name: String,
+        target: Option<String>,
+    },
+    ShowRef {
+        #[arg(long)]
+        heads: bool,
+        #[arg(long)]
+        tags: bool,
+        #[arg(long)]
+        hash: bool,
+        patterns: Vec<String>,
+    },
+    ForEachRef {
+        #[arg(long)]
+        format: Option<String>,
+        #[arg(long)]
+        sort: Vec<String>,
+        #[arg(long)]
+        count: Option<usize>,
+        patterns: Vec<String>,
+    },
+    Prune {
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        #[arg(short, long)]
+        verbose: bool,
+        /// Only prune objects older than this date.
+        #[arg(long, value_parser = log::parse_date, default_value = "2 weeks ago")]
+        expire: u64,
+    },
+    HashObject {
+        #[arg(short = 'w')]
+        write: bool,
+        #[arg(long)]
+        stdin: bool,
+        files: Vec<String>,
+    },
+    LsFiles {
+        #[arg(short, long)]
+        stage: bool,
+        #[arg(short = 'z')]
+        null_terminated: bool,
+    },
+    /// Write files from the index to the worktree.
+    CheckoutIndex {
+        #[arg(short, long, conflicts_with = "paths")]
+        all: bool,
+        /// Overwrite files that already exist.
+        #[arg(short, long)]
+        force: bool,
+        #[arg(required_unless_present = "all")]
+        paths: Vec<PathBuf>,
+    },
+    /// Update the entries of files in the index from the worktree.
+    UpdateIndex {
+        #[arg(long)]
+        add: bool,
+        #[arg(long)]
+        remove: bool,
+        #[arg(long)]
+        refresh: bool,
+        /// Add an entry on the form `<mode>,<object>,<path>` without reading the worktree.
+        #[arg(long, value_parser = update_index::parse_cacheinfo)]
+        cacheinfo: Vec<update_index::CacheInfo>,
+        paths: Vec<PathBuf>,
+    },
+    LsTree {
+        #[arg(short)]
+        recursive: bool,
+        tree_ish: String,
+        path: Option<String>,
+    },
+    /// Write the trees of the index and print the id of the root tree.
+    WriteTree,
+    /// Create a commit of a tree and print its id, without updating any refs.
+    CommitTree {
+        tree: String,
+        #[arg(short = 'p', value_name = "PARENT")]
+        parents: Vec<String>,
+        /// Each message is a paragraph of the commit message, which is read from stdin if none
+        /// are given.
+        #[arg(short = 'm', value_name = "MESSAGE")]
+        messages: Vec<String>,
+    },
+    /// Replace the entries of the index with the files of a tree.
+    ReadTree {
+        tree_ish: String,
+    },
+    Archive {
+        tree_ish: String,
+        /// Defaults to the format implied by the extension of the output file, or tar.
+        #[arg(long, value_parser = archive::parse_format)]
+        format: Option<archive::Format>,
+        #[arg(long, default_value = "")]
+        prefix: String,
+        /// Write the archive to this file instead of to stdout.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    Blame {
+        #[arg(short = 'L', value_parser = blame::parse_line_range)]
+        line_range: Option<(usize, usize)>,
+        path: String,
+    },
+    Grep {
+        pattern: String,
+        revision: Option<String>,
+    },
+    Clean {
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+        #[arg(short, long)]
+        force: bool,
+        #[arg(short = 'd')]
+        directories: bool,
+    },
+    #[command(group(ArgGroup::new("level").args(["local", "global", "system"])))]
+    Config {
+        #[arg(long)]
+        local: bool,
+        #[arg(long)]
+        global: bool,
+        #[arg(long)]
+        system: bool,
+        #[arg(long, conflicts_with_all = ["unset", "list"])]
+        get: bool,
+        #[arg(long, conflicts_with = "list")]
+        unset: bool,
+        #[arg(short, long)]
+        list: bool,
+        #[arg(required_unless_present = "list", conflicts_with = "list")]
+        key: Option<String>,
+        #[arg(conflicts_with_all = ["get", "unset"])]
+        value: Option<String>,
+    },
+    Submodule {
+        #[command(subcommand)]
+        command: Option<SubmoduleCommand>,
+    },
+    Worktree {
+        #[command(subcommand)]
+        command: WorktreeCommand,
+    },
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommand,
+    },
+    CommitGraph {
+        #[command(subcommand)]
+        command: CommitGraphCommand,
+    },
+    /// Write the history of all refs to stdout as a fast-import stream.
+    FastExport,
+    /// Read a fast-import stream from stdin and create the objects and refs it describes.
+    FastImport {
+        /// Update refs even if that loses commits.
+        #[arg(long)]
+        force: bool,
+    },
+    /// A command that isn't built in, which is looked up among the aliases in the config.
+    #[command(external_subcommand)]
+    Alias(Vec<OsString>),
+}
+
+#[derive(Subcommand, Debug)]
+enum SubmoduleCommand {
+    Add { url: String, path: Option<PathBuf> },
+    Init,
+    Update,
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleCommand {
+    Create {
+        file: PathBuf,
+        #[arg(long)]
+        all: bool,
+        #[arg(allow_hyphen_values = true)]
+        revisions: Vec<String>,
+    },
+    Verify {
+        file: PathBuf,
+    },
+    Unbundle {
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CommitGraphCommand {
+    /// Write a commit-graph of all commits reachable from the refs and HEAD.
+    Write,
+}
+
+#[derive(Subcommand, Debug)]
+enum WorktreeCommand {
+    Add {
+        #[arg(short = 'b', conflicts_with = "detach")]
+        create: Option<String>,
+        #[arg(long)]
+        detach: bool,
+        path: PathBuf,
+        commit_ish: Option<String>,
+    },
+    List,
+    Remove {
+        #[arg(short, long)]
+        force: bool,
+        path: PathBuf,
     },
 }
 
+/// Run the command given by the arguments, and return the exit status.
 pub fn run_command<P: AsRef<Path>, S: Into<OsString> + Clone>(
     args: Vec<S>,
     workdir: P,
     writer: &mut dyn OutputWriter,
-) -> crate::Result<()> {
-    let git_dir = workdir.as_ref().join(".git");
+) -> crate::Result<i32> {
+    let args = args.into_iter().map(Into::into).collect();
+    let started_at = Instant::now();
+    let result = run_command_with_aliases(args, workdir.as_ref(), &mut vec![], writer);
+    trace::trace(
+        "command",
+        format_args!("finished in {:.3?}", started_at.elapsed()),
+    );
+    result
+}
 
-    let repository = Repository::from_worktree_root(workdir);
+/// Run the command, expanding it if it's an alias. The aliases that have been expanded so far are
+/// kept to detect aliases that expand to themselves.
+fn run_command_with_aliases(
+    raw_args: Vec<OsString>,
+    original_workdir: &Path,
+    expanded_aliases: &mut Vec<String>,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<i32> {
+    let args = Args::parse_from(&raw_args);
+    if args.verbose {
+        trace::set_enabled(true);
+    }
+    trace::trace(
+        "command",
+        format_args!(
+            "rut {}",
+            raw_args[1..]
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" ")
+        ),
+    );
+    let workdir = match &args.directory {
+        Some(directory) => file::normalize_path(original_workdir.join(directory)),
+        None => original_workdir.to_owned(),
+    };
 
-    let args = Args::parse_from(args);
+    if let Action::Init {
+        initial_branch,
+        template,
+        object_format,
+    } = args.action
+    {
+        let git_dir = match &args.git_dir {
+            Some(git_dir) => workdir.join(git_dir),
+            None => workdir.join(".git"),
+        };
+        let options = init::OptionsBuilder::default()
+            .initial_branch(initial_branch)
+            .template(template.map(|template| workdir.join(template)))
+            .object_format(object_format)
+            .build()
+            .unwrap();
+        init::init(git_dir, &options, writer)?;
+        return Ok(0);
+    }
+    let repository = open_repository(&args, &workdir);
 
-    match args.action {
-        Action::Init => {
-            init::init(&git_dir, writer)?;
+    if let Action::Alias(alias_args) = &args.action {
+        let name = alias_args[0].to_string_lossy().into_owned();
+        let config = match &repository {
+            Ok(repository) => repository.config()?,
+            Err(_) => config::Config::load(None)?,
+        };
+        let Some(alias) = alias::lookup(&name, &config)? else {
+            let message = format!("'{}' is not a rut command. See 'rut --help'.", name);
+            return Err(crate::Error::Fatal(None, message));
+        };
+        if expanded_aliases.contains(&name) {
+            let mut message = format!(
+                "alias loop detected: expansion of '{}' does not terminate:",
+                expanded_aliases[0]
+            );
+            for expanded_alias in expanded_aliases.iter() {
+                let marker = if *expanded_alias == name { " <==" } else { "" };
+                message.push_str(&format!("\n  {}{}", expanded_alias, marker));
+            }
+            message.push_str(&format!("\n  {} ==>", name));
+            return Err(crate::Error::Fatal(None, message));
         }
-        Action::Commit { message } => {
+        expanded_aliases.push(name);
+
+        let rest = &alias_args[1..];
+        match alias {
+            Alias::Shell(command) => {
+                // like Git, shell aliases run in the root of the worktree
+                let (directory, prefix) = match &repository {
+                    Ok(repository) => {
+                        let worktree = repository.worktree();
+                        let prefix = match worktree.prefix().as_os_str().is_empty() {
+                            true => String::new(),
+                            false => format!("{}/", worktree.prefix().display()),
+                        };
+                        (worktree.root().to_owned(), prefix)
+                    }
+                    Err(_) => (workdir.clone(), String::new()),
+                };
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(format!("{} \"$@\"", command))
+                    .arg(&command)
+                    .args(rest)
+                    .current_dir(directory)
+                    .env("GIT_PREFIX", prefix)
+                    .stdin(Stdio::inherit())
+                    .stderr(Stdio::inherit())
+                    .output()?;
+                writer.write(String::from_utf8_lossy(&output.stdout).into_owned())?;
+                return Ok(output.status.code().unwrap_or(1));
+            }
+            Alias::Command(expansion) => {
+                let command_start = raw_args.len() - alias_args.len();
+                let mut expanded_args = raw_args[..command_start].to_vec();
+                expanded_args.extend(expansion.into_iter().map(OsString::from));
+                expanded_args.extend(rest.iter().cloned());
+                return run_command_with_aliases(
+                    expanded_args,
+                    original_workdir,
+                    expanded_aliases,
+                    writer,
+                );
+            }
+        }
+    }
+
+    if let Action::Config {
+        local,
+        global,
+        system,
+        get: _,
+        unset,
+        list,
+        key,
+        value,
+    } = args.action
+    {
+        // global and system config can be used outside of a repository
+        let git_dir = repository.ok().map(|repository| repository.common_dir());
+        let level = match (local, global, system) {
+            (true, _, _) => Some(config::ConfigLevel::Local),
+            (_, true, _) => Some(config::ConfigLevel::Global),
+            (_, _, true) => Some(config::ConfigLevel::System),
+            _ => None,
+        };
+        let action = match (key, value) {
+            _ if list => config::ConfigAction::List,
+            (Some(key), _) if unset => config::ConfigAction::Unset(key),
+            (Some(key), Some(value)) => config::ConfigAction::Set(key, value),
+            (Some(key), None) => config::ConfigAction::Get(key),
+            (None, _) => unreachable!("clap requires a key unless listing"),
+        };
+        let options = config::OptionsBuilder::default()
+            .level(level)
+            .build()
+            .unwrap();
+        let is_found = config::config(&action, &options, git_dir.as_deref(), writer)?;
+        return Ok(if is_found { 0 } else { 1 });
+    }
+    let repository = repository?;
+
+    let config = repository.config()?;
+    let color_mode = match args.color {
+        Some(color_mode) => color_mode,
+        None => ColorMode::from_config(&config)?,
+    };
+    writer.set_color_mode(color_mode);
+    if let Some(pager) = pager_command(&args, &config) {
+        writer.start_pager(&pager)?;
+    }
+
+    match args.action {
+        Action::Init { .. } => unreachable!("init does not need an existing repository"),
+        Action::Config { .. } => unreachable!("config does not need an existing repository"),
+        Action::Alias(_) => unreachable!("aliases are expanded before running commands"),
+        Action::Commit {
+            message,
+            amend,
+            all,
+            no_edit,
+            no_verify,
+        } => {
             let options = commit::OptionsBuilder::default()
                 .message(message)
+                .amend(amend)
+                .all(all)
+                .no_edit(no_edit)
+                .no_verify(no_verify)
                 .build()
                 .unwrap();
             commit::commit(&repository, &options, writer)?;
         }
-        Action::Add { path } => {
-            add::add(path, &repository)?;
+        Action::Add { pathspecs, patch } => {
+            if patch {
+                let mut prompt = LinePrompt::new(io::stdin().lock());
+                add::add_patch(&pathspecs, &repository, &mut prompt, writer)?;
+            } else {
+                add::add(&pathspecs, &repository, &mut StderrProgress::new())?;
+            }
         }
-        Action::Rm { path } => {
-            rm::rm(resolve_path(&path, &repository)?, &repository)?;
+        Action::Rm {
+            pathspecs,
+            cached,
+            recursive,
+            force,
+        } => {
+            let options = rm::OptionsBuilder::default()
+                .cached(cached)
+                .recursive(recursive)
+                .force(force)
+                .build()
+                .unwrap();
+            rm::rm(&pathspecs, &options, &repository)?;
+        }
+        Action::Mv {
+            source,
+            destination,
+        } => {
+            mv::mv(source, destination, &repository)?;
         }
-        Action::Status { porcelain } => {
+        Action::Status {
+            porcelain,
+            format,
+            branch,
+            null_terminated,
+            untracked_files,
+            ignored,
+            find_renames,
+            pathspecs,
+        } => {
             let options = status::Options {
-                output_format: if porcelain {
-                    status::OutputFormat::Porcelain
-                } else {
-                    status::OutputFormat::HumanReadable
-                },
+                output_format: porcelain.or(format).unwrap_or_default(),
+                pathspecs,
+                rename_threshold: find_renames,
+                branch,
+                null_terminated,
+                untracked_files: untracked_files.unwrap_or_default(),
+                ignored,
             };
             status::status(&repository, &options, writer)?;
         }
-        Action::Diff { cached } => {
+        Action::Diff {
+            cached,
+            stat,
+            name_only,
+            name_status,
+            format,
+            null_terminated,
+            context_lines,
+            diff_algorithm,
+            exit_code,
+            quiet,
+            no_ext_diff,
+            find_renames,
+            args,
+            pathspecs,
+        } => {
+            let output_format = if stat {
+                diff::OutputFormat::Stat
+            } else if name_only {
+                diff::OutputFormat::NameOnly
+            } else if name_status && format.is_some() {
+                diff::OutputFormat::NameStatusJson
+            } else if name_status {
+                diff::OutputFormat::NameStatus
+            } else {
+                diff::OutputFormat::Patch
+            };
+            let (revision, pathspecs) = split_revision_and_pathspecs(args, pathspecs, &repository)?;
             let options = diff::OptionsBuilder::default()
                 .cached(cached)
+                .revision(revision)
+                .output_format(output_format)
+                .pathspecs(pathspecs)
+                .context_lines(context_lines.unwrap_or(diff::DEFAULT_CONTEXT_LINES))
+                .algorithm(diff_algorithm)
+                .rename_threshold(find_renames)
+                .quiet(quiet)
+                .external(!no_ext_diff)
+                .null_terminated(null_terminated)
+                .build()
+                .unwrap();
+            let has_differences = diff::diff_repository(&repository, &options, writer)?;
+            if has_differences && (exit_code || quiet) {
+                return Ok(1);
+            }
+        }
+        Action::Difftool {
+            cached,
+            tool,
+            extcmd,
+            no_prompt,
+            prompt,
+            args,
+            pathspecs,
+        } => {
+            let (revision, pathspecs) = split_revision_and_pathspecs(args, pathspecs, &repository)?;
+            let diff_options = diff::OptionsBuilder::default()
+                .cached(cached)
+                .revision(revision)
+                .pathspecs(pathspecs)
+                .build()
+                .unwrap();
+            let options = difftool::OptionsBuilder::default()
+                .tool(tool)
+                .extcmd(extcmd)
+                .prompt(match (prompt, no_prompt) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                })
+                .build()
+                .unwrap();
+            let mut line_prompt = LinePrompt::new(io::stdin().lock());
+            difftool::difftool(
+                &repository,
+                &diff_options,
+                &options,
+                &mut line_prompt,
+                writer,
+            )?;
+        }
+        Action::FormatPatch {
+            revision,
+            output_directory,
+        } => {
+            let options = format_patch::OptionsBuilder::default()
+                .output_directory(output_directory)
+                .build()
+                .unwrap();
+            format_patch::format_patch(&revision, &options, &repository, writer)?;
+        }
+        Action::Am { mboxes } => {
+            let mboxes = mboxes
+                .iter()
+                .map(|mbox| workdir.join(mbox))
+                .collect::<Vec<_>>();
+            am::am(&mboxes, &repository, writer)?;
+        }
+        Action::Rebase {
+            upstream,
+            continue_rebase,
+            abort,
+        } => {
+            if continue_rebase {
+                rebase::continue_rebase(&repository, writer)?;
+            } else if abort {
+                rebase::abort(&repository)?;
+            } else {
+                rebase::rebase(&upstream.unwrap(), &repository, writer)?;
+            }
+        }
+        Action::Cherry {
+            upstream,
+            head,
+            verbose,
+        } => {
+            let options = cherry::OptionsBuilder::default()
+                .upstream(upstream)
+                .head(head)
+                .verbose(verbose)
                 .build()
                 .unwrap();
-            diff::diff_repository(&repository, &options, writer)?;
+            cherry::cherry(&options, &repository, writer)?;
         }
-        Action::Restore { path, source } => {
+        Action::Restore { pathspecs, source } => {
             let options = restore::OptionsBuilder::default()
                 .source(source)
                 .build()
                 .unwrap();
-            restore::restore_worktree(resolve_path(&path, &repository)?, &options, &repository)?;
+            restore::restore_worktree(&pathspecs, &options, &repository)?;
         }
-        Action::Log { max_count, oneline } => {
+        Action::Switch {
+            target,
+            detach,
+            create,
+        } => {
+            let options = switch::OptionsBuilder::default()
+                .detach(detach)
+                .create(create)
+                .build()
+                .unwrap();
+            let mut progress = StderrProgress::new();
+            switch::switch(
+                target.as_deref(),
+                &options,
+                &repository,
+                writer,
+                &mut progress,
+            )?;
+        }
+        Action::Log {
+            max_count,
+            oneline,
+            pretty,
+            format,
+            patch,
+            stat,
+            follow,
+            graph,
+            author,
+            grep,
+            since,
+            until,
+            use_mailmap,
+            no_mailmap,
+            revision,
+            paths,
+        } => {
             let format = if oneline {
                 log::Format::Oneline
             } else {
-                log::Format::Default
+                pretty.or(format).unwrap_or_default()
             };
 
             let options = log::OptionsBuilder::default()
                 .max_count(max_count)
                 .format(format)
+                .patch(patch)
+                .stat(stat)
+                .paths(resolve_user_paths(&paths, &repository)?)
+                .follow(follow)
+                .revision(revision)
+                .graph(graph)
+                .author(author)
+                .grep(grep)
+                .since(since)
+                .until(until)
+                .use_mailmap(match (use_mailmap, no_mailmap) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                })
                 .build()
                 .unwrap();
             log::log(&repository, &options, writer)?;
         }
-        Action::Branch { name, start_point } => {
+        Action::Shortlog {
+            numbered,
+            summary,
+            email,
+            revision,
+        } => {
+            let options = shortlog::OptionsBuilder::default()
+                .numbered(numbered)
+                .summary(summary)
+                .email(email)
+                .revision(revision)
+                .build()
+                .unwrap();
+            shortlog::shortlog(&options, &repository, writer)?;
+        }
+        Action::Branch {
+            name,
+            start_point,
+            verbose,
+            format,
+            delete,
+            force_delete,
+            rename,
+            set_upstream_to,
+        } => {
+            let (name, start_point, mode) = if let Some(upstream) = set_upstream_to {
+                (name, None, branch::Mode::SetUpstream(upstream))
+            } else if rename {
+                // with a single name, the current branch is renamed to it
+                match start_point {
+                    Some(new_name) => (name, None, branch::Mode::Rename(new_name)),
+                    None => (None, None, branch::Mode::Rename(name.unwrap())),
+                }
+            } else if delete || force_delete {
+                let mode = branch::Mode::Delete {
+                    force: force_delete,
+                };
+                (name, start_point, mode)
+            } else {
+                (name, start_point, branch::Mode::CreateOrList)
+            };
+
             let options = branch::OptionsBuilder::default()
                 .name(name)
                 .start_point(start_point)
+                .mode(mode)
+                .verbose(verbose)
+                .json(format.is_some())
+                .build()
+                .unwrap();
+            branch::branch(&options, &repository, writer)?;
+        }
+        Action::RevParse {
+            revision,
+            short,
+            abbrev_ref,
+            verify,
+            quiet,
+            git_dir,
+            show_toplevel,
+        } => {
+            if git_dir {
+                revparse::show_git_dir(&repository, writer)?;
+            }
+            if show_toplevel {
+                revparse::show_toplevel(&repository, writer)?;
+            }
+            let options = revparse::OptionsBuilder::default()
+                .short(short)
+                .abbrev_ref(abbrev_ref)
+                .verify(verify)
+                .quiet(quiet)
+                .build()
+                .unwrap();
+            if let Some(revision) = revision {
+                if !revparse::rev_parse(&revision, &options, writer, &repository)? {
+                    return Ok(1);
+                }
+            } else if verify {
+                let message = String::from("Needed a single revision");
+                return Err(crate::Error::Fatal(None, message));
+            }
+        }
+        Action::RevList {
+            revision,
+            count,
+            max_count,
+            left_right,
+        } => {
+            let options = rev_list::OptionsBuilder::default()
+                .count(count)
+                .max_count(max_count)
+                .left_right(left_right)
+                .build()
+                .unwrap();
+            rev_list::rev_list(&revision, &options, &repository, writer)?;
+        }
+        Action::RangeDiff {
+            old_range,
+            new_range,
+        } => {
+            range_diff::range_diff(&old_range, &new_range, &repository, writer)?;
+        }
+        Action::MergeBase {
+            first_revision,
+            second_revision,
+        } => {
+            merge_base::merge_base(&first_revision, &second_revision, &repository, writer)?;
+        }
+        Action::CatFile {
+            show_type,
+            size,
+            pretty: _,
+            batch,
+            object,
+        } => {
+            if batch {
+                cat_file::cat_file_batch(&mut io::stdin().lock(), &repository, writer)?;
+            } else {
+                let mode = if show_type {
+                    cat_file::Mode::Type
+                } else if size {
+                    cat_file::Mode::Size
+                } else {
+                    cat_file::Mode::Pretty
+                };
+                let options = cat_file::OptionsBuilder::default()
+                    .mode(mode)
+                    .build()
+                    .unwrap();
+                cat_file::cat_file(&object.unwrap(), &options, &repository, writer)?;
+            }
+        }
+        Action::CountObjects { verbose } => {
+            let options = count_objects::OptionsBuilder::default()
+                .verbose(verbose)
+                .build()
+                .unwrap();
+            count_objects::count_objects(&options, &repository, writer)?;
+        }
+        Action::Fsck { unreachable } => {
+            let options = fsck::OptionsBuilder::default()
+                .unreachable(unreachable)
+                .build()
+                .unwrap();
+            if !fsck::fsck(&options, &repository, writer)? {
+                return Ok(1);
+            }
+        }
+        Action::UpdateRef {
+            delete,
+            ref_name,
+            new_value,
+            old_value,
+        } => {
+            if delete {
+                update_ref::delete_ref(&ref_name, new_value.as_deref(), &repository)?;
+            } else {
+                let new_value = new_value.unwrap();
+                update_ref::update_ref(&ref_name, &new_value, old_value.as_deref(), &repository)?;
+            }
+        }
+        Action::SymbolicRef {
+            short,
+            name,
+            target,
+        } => match target {
+            Some(target) => symbolic_ref::write_symbolic_ref(&name, &target, &repository)?,
+            None => {
+                let options = symbolic_ref::OptionsBuilder::default()
+                    .short(short)
+                    .build()
+                    .unwrap();
+                symbolic_ref::read_symbolic_ref(&name, &options, &repository, writer)?;
+            }
+        },
+        Action::ShowRef {
+            heads,
+            tags,
+            hash,
+            patterns,
+        } => {
+            let options = show_ref::OptionsBuilder::default()
+                .heads(heads)
+                .tags(tags)
+                .hash(hash)
+                .build()
+                .unwrap();
+            if !show_ref::show_ref(&patterns, &options, &repository, writer)? {
+                return Ok(1);
+            }
+        }
+        Action::ForEachRef {
+            format,
+            sort,
+            count,
+            patterns,
+        } => {
+            let options = for_each_ref::OptionsBuilder::default()
+                .format(format)
+                .sort(sort)
+                .count(count)
+                .build()
+                .unwrap();
+            for_each_ref::for_each_ref(&patterns, &options, &repository, writer)?;
+        }
+        Action::Prune {
+            dry_run,
+            verbose,
+            expire,
+        } => {
+            let options = prune::OptionsBuilder::default()
+                .dry_run(dry_run)
+                .verbose(verbose)
+                .expire(Some(expire))
+                .build()
+                .unwrap();
+            prune::prune(&options, &repository, writer)?;
+        }
+        Action::HashObject {
+            write,
+            stdin,
+            files,
+        } => {
+            let options = hash_object::OptionsBuilder::default()
+                .write(write)
+                .build()
+                .unwrap();
+            if stdin {
+                hash_object::hash_object_stdin(
+                    &mut io::stdin().lock(),
+                    &options,
+                    &repository,
+                    writer,
+                )?;
+            }
+            hash_object::hash_object(&files, &options, &repository, writer)?;
+        }
+        Action::LsFiles {
+            stage,
+            null_terminated,
+        } => {
+            let options = ls_files::OptionsBuilder::default()
+                .stage(stage)
+                .null_terminated(null_terminated)
+                .build()
+                .unwrap();
+            ls_files::ls_files(&repository, &options, writer)?;
+        }
+        Action::CheckoutIndex { all, force, paths } => {
+            let options = checkout_index::OptionsBuilder::default()
+                .all(all)
+                .force(force)
+                .build()
+                .unwrap();
+            let paths = resolve_user_paths(&paths, &repository)?;
+            if !checkout_index::checkout_index(&paths, &options, &repository, writer)? {
+                return Ok(1);
+            }
+        }
+        Action::UpdateIndex {
+            add,
+            remove,
+            refresh,
+            cacheinfo,
+            paths,
+        } => {
+            let cacheinfo = cacheinfo
+                .into_iter()
+                .map(|cacheinfo| {
+                    let path = repository.worktree().resolve_user_path(&cacheinfo.path)?;
+                    Ok(update_index::CacheInfo { path, ..cacheinfo })
+                })
+                .collect::<crate::Result<Vec<_>>>()?;
+            let options = update_index::OptionsBuilder::default()
+                .add(add)
+                .remove(remove)
+                .refresh(refresh)
+                .cacheinfo(cacheinfo)
+                .build()
+                .unwrap();
+            let paths = resolve_user_paths(&paths, &repository)?;
+            if !update_index::update_index(&paths, &options, &repository, writer)? {
+                return Ok(1);
+            }
+        }
+        Action::LsTree {
+            recursive,
+            tree_ish,
+            path,
+        } => {
+            let options = ls_tree::OptionsBuilder::default()
+                .recursive(recursive)
+                .path(path)
+                .build()
+                .unwrap();
+            ls_tree::ls_tree(&tree_ish, &options, &repository, writer)?;
+        }
+        Action::WriteTree => {
+            let tree_id = commit::write_tree(&repository)?;
+            writer.writeln(tree_id.to_string())?;
+        }
+        Action::CommitTree {
+            tree,
+            parents,
+            messages,
+        } => {
+            let tree_id = Revision::parse(&tree)?.resolve(&repository)?;
+            let parent_ids = parents
+                .iter()
+                .map(|parent| Revision::parse(parent)?.resolve(&repository))
+                .collect::<crate::Result<Vec<_>>>()?;
+            let message = if messages.is_empty() {
+                io::read_to_string(io::stdin().lock())?
+            } else {
+                messages
+                    .iter()
+                    .map(|message| format!("{}\n", message.trim_end()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            let commit = commit::commit_tree(&tree_id, parent_ids, message, &repository)?;
+            writer.writeln(commit.id_as_string())?;
+        }
+        Action::ReadTree { tree_ish } => {
+            read_tree::read_tree(&tree_ish, &repository)?;
+        }
+        Action::Archive {
+            tree_ish,
+            format,
+            prefix,
+            output,
+        } => {
+            let output = output.map(|output| workdir.join(output));
+            let format = format
+                .or_else(|| output.as_deref().and_then(archive::Format::from_path))
+                .unwrap_or_default();
+            let options = archive::OptionsBuilder::default()
+                .format(format)
+                .prefix(prefix)
+                .build()
+                .unwrap();
+            match output {
+                Some(output) => {
+                    let mut file = io::BufWriter::new(fs::File::create(output)?);
+                    archive::archive(&tree_ish, &options, &repository, &mut file)?;
+                    file.flush()?;
+                }
+                None => {
+                    let mut stdout = io::stdout().lock();
+                    archive::archive(&tree_ish, &options, &repository, &mut stdout)?;
+                    stdout.flush()?;
+                }
+            }
+        }
+        Action::Blame { line_range, path } => {
+            let options = blame::OptionsBuilder::default()
+                .line_range(line_range)
+                .build()
+                .unwrap();
+            let path = repository.worktree().resolve_user_path(path)?;
+            blame::blame(path, &options, &repository, writer)?;
+        }
+        Action::Grep { pattern, revision } => {
+            let options = grep::OptionsBuilder::default()
+                .revision(revision)
                 .build()
                 .unwrap();
-            branch::branch(&options, &repository)?;
+            grep::grep(&pattern, &options, &repository, writer)?;
+        }
+        Action::Clean {
+            dry_run,
+            force,
+            directories,
+        } => {
+            let options = clean::OptionsBuilder::default()
+                .dry_run(dry_run)
+                .force(force)
+                .directories(directories)
+                .build()
+                .unwrap();
+            clean::clean(&repository, &options, writer)?;
+        }
+        Action::Submodule { command } => {
+            let mode = match command {
+                Some(SubmoduleCommand::Add { url, path }) => submodule::Mode::Add { url, path },
+                Some(SubmoduleCommand::Init) => submodule::Mode::Init,
+                Some(SubmoduleCommand::Update) => submodule::Mode::Update,
+                Some(SubmoduleCommand::Status) | None => submodule::Mode::Status,
+            };
+            let options = submodule::OptionsBuilder::default()
+                .mode(mode)
+                .build()
+                .unwrap();
+            submodule::submodule(&options, &repository, writer)?;
+        }
+        Action::Worktree { command } => {
+            let mut builder = worktree::OptionsBuilder::default();
+            let mode = match command {
+                WorktreeCommand::Add {
+                    create,
+                    detach,
+                    path,
+                    commit_ish,
+                } => {
+                    builder.create(create).detach(detach);
+                    let path = file::normalize_path(workdir.join(path));
+                    worktree::Mode::Add { path, commit_ish }
+                }
+                WorktreeCommand::List => worktree::Mode::List,
+                WorktreeCommand::Remove { force, path } => {
+                    builder.force(force);
+                    let path = file::normalize_path(workdir.join(path));
+                    worktree::Mode::Remove { path }
+                }
+            };
+            let options = builder.mode(mode).build().unwrap();
+            worktree::worktree(&options, &repository, writer)?;
+        }
+        Action::Bundle { command } => match command {
+            BundleCommand::Create {
+                file,
+                all,
+                revisions,
+            } => {
+                let options = bundle::OptionsBuilder::default().all(all).build().unwrap();
+                let mut progress = StderrProgress::new();
+                bundle::create(
+                    &workdir.join(file),
+                    &revisions,
+                    &options,
+                    &repository,
+                    &mut progress,
+                )?;
+            }
+            BundleCommand::Verify { file } => {
+                bundle::verify(&workdir.join(file), &repository, writer)?;
+            }
+            BundleCommand::Unbundle { file } => {
+                bundle::unbundle(&workdir.join(file), &repository, writer)?;
+            }
+        },
+        Action::CommitGraph { command } => match command {
+            CommitGraphCommand::Write => commit_graph::write(&repository)?,
+        },
+        Action::FastExport => {
+            let mut stdout = io::BufWriter::new(io::stdout().lock());
+            fast_export::fast_export(&repository, &mut stdout)?;
+            stdout.flush()?;
         }
-        Action::RevParse { revision } => {
-            revparse::rev_parse(&revision, writer, &repository)?;
+        Action::FastImport { force } => {
+            let options = fast_import::OptionsBuilder::default()
+                .force(force)
+                .build()
+                .unwrap();
+            let mut stdin = io::stdin().lock();
+            if !fast_import::fast_import(&mut stdin, &options, &repository, writer)? {
+                return Ok(1);
+            }
         }
     }
 
-    Ok(())
+    Ok(0)
 }
 
+/// The pager to page the output of the command through, if any. Commands that show history,
+/// diffs or search results are paged unless `--no-pager` is given, and other commands only with
+/// `--paginate`. The pager is taken from `GIT_PAGER`, `core.pager` or `PAGER`, where an empty
+/// pager or `cat` disables paging.
+fn pager_command(args: &Args, config: &config::Config) -> Option<String> {
+    let is_paged_by_default = matches!(
+        args.action,
+        Action::Log { .. }
+            | Action::Diff { .. }
+            | Action::Blame { .. }
+            | Action::Shortlog { .. }
+            | Action::Grep { .. }
+    );
+    if args.no_pager || !(args.paginate || is_paged_by_default) {
+        return None;
+    }
+
+    let pager = env::var("GIT_PAGER")
+        .ok()
+        .or_else(|| config.get_string("core.pager"))
+        .or_else(|| env::var("PAGER").ok())
+        .unwrap_or_else(|| String::from("less"));
+    match pager.trim() {
+        "" | "cat" => None,
+        pager => Some(pager.to_owned()),
+    }
+}
+
+/// Writes to stdout, through a pager once one is started, and emits ANSI codes for colors as
+/// decided by the color mode, by default only when stdout is a terminal.
 pub struct StdoutWriter {
+    adapter: WriteAdapter<StdoutTarget>,
     isatty: bool,
     pager: Option<Child>,
 }
 
-impl Default for StdoutWriter {
-    fn default() -> Self {
-        Self::new(true)
+/// Where the output of a StdoutWriter goes. A pipe that was closed by the reader, such as a pager
+/// that was quit, silently discards the rest of the output.
+enum StdoutTarget {
+    Stdout(io::Stdout),
+    Pager(ChildStdin),
+}
+
+impl io::Write for StdoutTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = match self {
+            StdoutTarget::Stdout(stdout) => stdout.write(buf),
+            StdoutTarget::Pager(pager_stdin) => pager_stdin.write(buf),
+        };
+        match result {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(buf.len()),
+            result => result,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = match self {
+            StdoutTarget::Stdout(stdout) => stdout.flush(),
+            StdoutTarget::Pager(pager_stdin) => pager_stdin.flush(),
+        };
+        match result {
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => result,
+        }
     }
 }
 
-extern "C" {
-    fn isatty(fd: c_int) -> c_int;
+impl Default for StdoutWriter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StdoutWriter {
-    pub fn new(use_pager: bool) -> Self {
-        let stdout_fd = io::stdout().as_raw_fd();
-        let isatty = unsafe { isatty(stdout_fd) } != 0;
+    pub fn new() -> Self {
+        let isatty = io::stdout().is_terminal();
         Self {
+            adapter: WriteAdapter::new(StdoutTarget::Stdout(io::stdout())).with_ansi(isatty),
             isatty,
-            pager: if use_pager {
-                Self::create_pager()
-            } else {
-                None
-            },
+            pager: None,
         }
     }
 
-    fn print_ansi_code(&mut self, ansi_code: &str) -> io::Result<&mut dyn OutputWriter> {
-        if !self.isatty {
-            return Ok(self);
+    /// Spawn the pager, through the shell only if the command needs it, so that a missing pager
+    /// fails to spawn instead of failing in the shell. Like Git, `less` is told to show colors and
+    /// to quit if the output fits on one screen, unless `LESS` is set.
+    fn spawn_pager(pager_command: &str) -> io::Result<Child> {
+        let needs_shell = pager_command
+            .contains(|c: char| c.is_whitespace() || "|&;<>()$`\\\"'*?[#~=%".contains(c));
+        let mut command = if needs_shell {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(pager_command);
+            command
+        } else {
+            Command::new(pager_command)
+        };
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
         }
-
-        self.write(format!("\x1b[{}m", ansi_code))
-    }
-
-    fn create_pager() -> Option<Child> {
-        Some(
-            Command::new("less")
-                .arg("-R")
-                .arg("-F")
-                .stdin(Stdio::piped())
-                .spawn()
-                .unwrap(),
-        )
+        if env::var_os("LV").is_none() {
+            command.env("LV", "-c");
+        }
+        command.stdin(Stdio::piped()).spawn()
     }
 }
 
 impl Drop for StdoutWriter {
     fn drop(&mut self) {
         if let Some(ref mut pager) = self.pager {
+            // closing the pipe tells the pager that the output is complete
+            *self.adapter.get_mut() = StdoutTarget::Stdout(io::stdout());
             pager.wait().unwrap();
         }
     }
@@ -200,48 +1636,190 @@ impl Drop for StdoutWriter {
 
 impl OutputWriter for StdoutWriter {
     fn write(&mut self, content: String) -> io::Result<&mut dyn OutputWriter> {
-        if let Some(ref mut pager) = self.pager {
-            pager
-                .stdin
-                .as_mut()
-                .unwrap()
-                .write_all(content.as_bytes())
-                .unwrap();
-        } else {
-            print!("{}", content);
-        }
+        self.adapter.write(content)?;
         Ok(self)
     }
 
     fn set_color(&mut self, color: Color) -> io::Result<&mut dyn OutputWriter> {
-        let ansi_code = match color {
-            Color::Red => "31",
-            Color::Green => "32",
-            Color::Cyan => "36",
-            Color::Brown => "38;5;130",
-        };
-        self.print_ansi_code(ansi_code)
+        self.adapter.set_color(color)?;
+        Ok(self)
     }
 
     fn set_style(&mut self, style: Style) -> io::Result<&mut dyn OutputWriter> {
-        let ansi_code = match style {
-            Style::Bold => "1",
-            Style::Normal => "22",
-        };
-        self.print_ansi_code(ansi_code)
+        self.adapter.set_style(style)?;
+        Ok(self)
     }
 
     fn reset_formatting(&mut self) -> io::Result<&mut dyn OutputWriter> {
-        self.print_ansi_code("0")
+        self.adapter.reset_formatting()?;
+        Ok(self)
+    }
+
+    fn start_pager(&mut self, pager_command: &str) -> io::Result<()> {
+        if !self.isatty || self.pager.is_some() {
+            return Ok(());
+        }
+        // without a pager, such as when it isn't installed, the output is written directly
+        if let Ok(mut pager) = Self::spawn_pager(pager_command) {
+            if let Some(pager_stdin) = pager.stdin.take() {
+                *self.adapter.get_mut() = StdoutTarget::Pager(pager_stdin);
+            }
+            self.pager = Some(pager);
+        }
+        Ok(())
+    }
+
+    fn set_color_mode(&mut self, color_mode: ColorMode) {
+        self.adapter.set_color_mode(color_mode);
     }
 }
 
-fn resolve_path(path: &str, repository: &Repository) -> io::Result<PathBuf> {
-    let resolved = repository.worktree().root().join(path);
-    if resolved.exists() {
-        Ok(resolved)
-    } else {
-        let message = format!("pathspec {:?} did not match any files", resolved);
-        Err(Error::new(io::ErrorKind::Other, message))
+/// Shows progress on stderr when it's a terminal, like `Writing objects:  45% (9/20)`. Phases
+/// that finish quickly aren't shown at all, and the line is redrawn when the percentage changes,
+/// or at most every [`StderrProgress::REDRAW_INTERVAL`] if the total is unknown.
+pub struct StderrProgress {
+    enabled: bool,
+    title: String,
+    total: Option<u64>,
+    done: u64,
+    started_at: Instant,
+    last_drawn: Option<(Instant, u64)>,
+}
+
+impl Default for StderrProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StderrProgress {
+    const DELAY: Duration = Duration::from_secs(1);
+    const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+    pub fn new() -> Self {
+        Self {
+            enabled: io::stderr().is_terminal(),
+            title: String::new(),
+            total: None,
+            done: 0,
+            started_at: Instant::now(),
+            last_drawn: None,
+        }
+    }
+
+    fn draw(&mut self, suffix: &str) {
+        let counts = match self.total {
+            Some(total) => format!("{:3}% ({}/{})", self.percent(), self.done, total),
+            None => self.done.to_string(),
+        };
+        // progress is best effort, so failing to show it must not fail the operation
+        let _ = write!(io::stderr(), "\r{}: {}{}", self.title, counts, suffix);
+        self.last_drawn = Some((Instant::now(), self.percent()));
+    }
+
+    fn percent(&self) -> u64 {
+        self.total.map_or(0, |total| {
+            (self.done * 100).checked_div(total).unwrap_or(100)
+        })
+    }
+}
+
+impl Progress for StderrProgress {
+    fn start(&mut self, title: &str, total: Option<u64>) {
+        self.title = title.to_owned();
+        self.total = total;
+        self.done = 0;
+        self.started_at = Instant::now();
+        self.last_drawn = None;
+    }
+
+    fn update(&mut self, done: u64) {
+        self.done = done;
+        if !self.enabled || self.started_at.elapsed() < Self::DELAY {
+            return;
+        }
+        let needs_redraw = match self.last_drawn {
+            None => true,
+            Some((_, percent)) if self.total.is_some() => percent != self.percent(),
+            Some((drawn_at, _)) => drawn_at.elapsed() >= Self::REDRAW_INTERVAL,
+        };
+        if needs_redraw {
+            self.draw("");
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.last_drawn.is_some() {
+            self.draw(", done.\n");
+        }
+        self.last_drawn = None;
+    }
+}
+
+/// Open the repository given by the global options, or discover it from the working directory.
+fn open_repository(args: &Args, workdir: &Path) -> crate::Result<Repository> {
+    let repository = match (&args.git_dir, &args.work_tree) {
+        (None, None) => Repository::discover(workdir)?,
+        (Some(git_dir), work_tree) => {
+            let worktree_root = work_tree
+                .as_ref()
+                .map_or(workdir.to_owned(), |work_tree| workdir.join(work_tree));
+            Repository::new(workdir.join(git_dir), worktree_root)?
+        }
+        (None, Some(work_tree)) => {
+            let git_dir = Repository::discover(workdir)?.git_dir();
+            Repository::new(git_dir, workdir.join(work_tree))?
+        }
+    };
+    Ok(repository.with_working_directory(workdir))
+}
+
+/// Split the arguments of a diff into an optional revision and pathspecs. Everything before `--`
+/// is a revision if `--` is given, and otherwise the first argument may be one.
+fn split_revision_and_pathspecs(
+    args: Vec<String>,
+    pathspecs: Vec<String>,
+    repository: &Repository,
+) -> crate::Result<(Option<String>, Vec<String>)> {
+    if pathspecs.is_empty() {
+        return Ok(split_revision(args, repository));
     }
+
+    match &args[..] {
+        [] => Ok((None, pathspecs)),
+        [revision] => Ok((Some(revision.clone()), pathspecs)),
+        _ => {
+            let message = String::from("diff takes at most one revision");
+            Err(crate::Error::Fatal(None, message))
+        }
+    }
+}
+
+/// Take the first argument as a revision if it names one and isn't a path in the worktree, like
+/// Git does when revisions and paths are mixed.
+fn split_revision(mut args: Vec<String>, repository: &Repository) -> (Option<String>, Vec<String>) {
+    let is_revision = |arg: &str| {
+        let is_path = repository
+            .worktree()
+            .resolve_user_path(arg)
+            .is_ok_and(|path| repository.worktree().root().join(path).exists());
+        let resolves =
+            || Revision::parse(arg).is_ok_and(|revision| revision.resolve(repository).is_ok());
+        !is_path && resolves()
+    };
+
+    match args.first() {
+        Some(first) if is_revision(first) => {
+            let revision = args.remove(0);
+            (Some(revision), args)
+        }
+        _ => (None, args),
+    }
+}
+
+fn resolve_user_paths(paths: &[PathBuf], repository: &Repository) -> crate::Result<Vec<PathBuf>> {
+    paths
+        .iter()
+        .map(|path| repository.worktree().resolve_user_path(path))
+        .collect()
 }
@@ -1,18 +1,71 @@
+use std::collections::BTreeMap;
 use std::iter::Peekable;
 use std::path::Component;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{fs, io, path::PathBuf};
+use std::{env, fs, io, path::PathBuf};
 
-use crate::hex::to_hex_string;
+use chrono::{DateTime, Local};
+
+use crate::add;
+use crate::cache_tree::CacheTree;
+use crate::config::Config;
+use crate::editor;
+use crate::hashing::HashAlgorithm;
+use crate::hooks;
 use crate::index::{FileMode, Index, IndexEntry};
-use crate::objects::{Author, Commit, GitObject, ObjectId, Tree, TreeEntry};
+use crate::merge;
+use crate::objects::{Author, Commit, GitObject, ObjectId, ObjectType, Tree, TreeEntry};
 use crate::output::OutputWriter;
+use crate::reflog;
 use crate::refs::RefHandler;
+use crate::rerere;
+use crate::status::{self, ChangeType};
 use crate::workspace::Repository;
 
 #[derive(Default, Builder, Debug)]
 pub struct Options {
     pub message: Option<String>,
+
+    /// Replace the HEAD commit instead of creating a new commit on top of it.
+    #[builder(default)]
+    pub amend: bool,
+
+    /// Stage all modified and deleted tracked files before committing.
+    #[builder(default)]
+    pub all: bool,
+
+    /// Reuse the message of the amended commit, or the message prepared for the merge in progress,
+    /// without launching an editor.
+    #[builder(default)]
+    pub no_edit: bool,
+
+    /// Skip the pre-commit and commit-msg hooks.
+    #[builder(default)]
+    pub no_verify: bool,
+}
+
+const COMMIT_MESSAGE_INSTRUCTIONS: &str = "\
+Please enter the commit message for your changes. Lines starting
+with '#' will be ignored, and an empty message aborts the commit.
+
+";
+
+const IDENTITY_INSTRUCTIONS: &str = "\
+*** Please tell me who you are.
+
+Run
+
+  rut config --global user.email \"you@example.com\"
+  rut config --global user.name \"Your Name\"
+
+to set your account's default identity.
+Omit --global to set the identity only in this repository.
+";
+
+/// An author or committer along with when they signed off on the commit.
+struct Signature {
+    identity: Author,
+    timestamp: u64,
+    timezone: String,
 }
 
 pub fn commit(
@@ -20,19 +73,113 @@ pub fn commit(
     options: &Options,
     writer: &mut dyn OutputWriter,
 ) -> crate::Result<()> {
+    let (author, committer) = read_signatures(&repository.config()?)?;
+    let commit_message_file = repository.git_dir().join("COMMIT_EDITMSG");
     if let Some(message) = &options.message {
-        fs::write(repository.git_dir().join("COMMIT_EDITMSG"), message)?;
+        fs::write(&commit_message_file, message)?;
     }
     let mut index = repository.load_index()?;
+    if index.as_mut().has_unmerged_entries() {
+        let message = "Committing is not possible because you have unmerged files.";
+        return Err(crate::Error::Fatal(None, message.to_owned()));
+    }
+    rerere::record_resolutions(repository, writer)?;
+    if options.all {
+        stage_tracked_changes(repository, index.as_mut())?;
+    }
+    if !options.no_verify {
+        hooks::run_hook("pre-commit", &[], repository)?;
+    }
 
     let head_ref = repository.head().expect("HEAD does not exist");
-    let commit = create_commit(repository, index.as_mut(), &head_ref)?;
+    let ref_handler = RefHandler::new(repository);
+    // HEAD must not move while the message is written, or the commit would drop a commit
+    let previous_head = ref_handler.read_ref("HEAD")?;
+    let merge_heads = merge::read_merge_heads(repository)?;
+    if options.amend && !merge_heads.is_empty() {
+        let message = "You are in the middle of a merge -- cannot amend.";
+        return Err(crate::Error::Fatal(None, message.to_owned()));
+    }
+    let (parent_commit, previous_message) = if options.amend {
+        let head_commit_id = ref_handler
+            .deref(&head_ref)
+            .map_err(|_| crate::Error::Fatal(None, String::from("You have nothing to amend.")))?;
+        let head_commit = repository.database.load_commit(&head_commit_id)?;
+        (head_commit.parent().cloned(), Some(head_commit.message))
+    } else {
+        // a merge in progress, or a rebase stopped at a conflict, has a message prepared
+        (
+            ref_handler.deref(&head_ref).ok(),
+            merge::read_merge_message(repository)?,
+        )
+    };
+
+    if options.message.is_none() {
+        match previous_message {
+            Some(previous_message) if options.no_edit => {
+                fs::write(&commit_message_file, previous_message)?
+            }
+            _ => {
+                let summary = status::commented_summary(repository, index.as_mut())?;
+                let template = format!(
+                    "{}\n{}{}",
+                    previous_message.unwrap_or_default(),
+                    editor::comment(COMMIT_MESSAGE_INSTRUCTIONS),
+                    summary
+                );
+                let message = editor::edit("COMMIT_EDITMSG", &template, repository)?;
+                if message.is_empty() {
+                    let message = "Aborting commit due to empty commit message.";
+                    return Err(crate::Error::Fatal(None, message.to_owned()));
+                }
+                fs::write(&commit_message_file, message)?;
+            }
+        }
+    }
+    if !options.no_verify {
+        let commit_message_path = commit_message_file.to_string_lossy();
+        hooks::run_hook("commit-msg", &[&commit_message_path], repository)?;
+    }
+
+    let is_merge = !merge_heads.is_empty();
+    let parents = parent_commit.into_iter().chain(merge_heads).collect();
+    let commit =
+        create_commit_with_parents(repository, index.as_mut(), parents, author, committer)?;
     repository.database.store_object(&commit)?;
+    ref_handler.update_head(commit.id(), previous_head.clone().into())?;
+    let reflog_action = match (&previous_head, options.amend) {
+        (None, _) => "commit (initial)",
+        (Some(_), true) => "commit (amend)",
+        (Some(_), false) if is_merge => "commit (merge)",
+        (Some(_), false) => "commit",
+    };
+    reflog::append_head_entry(
+        previous_head.as_ref(),
+        commit.id(),
+        &format!("{}: {}", reflog_action, commit.message),
+        repository,
+    )?;
 
-    let ref_handler = RefHandler::new(repository);
-    ref_handler.write_ref(&head_ref, commit.id())?;
+    merge::clear_merge_state(repository)?;
 
     write_commit_status(&commit, writer)?;
+    index.write()?;
+
+    // the commit is already done, so a failing post-commit hook can't affect the outcome
+    let _ = hooks::run_hook("post-commit", &[], repository);
+    Ok(())
+}
+
+fn stage_tracked_changes(repository: &Repository, index: &mut Index) -> crate::Result<()> {
+    let worktree = repository.worktree();
+    for change in status::resolve_unstaged_changes(repository, index) {
+        match change.change_type {
+            ChangeType::Deleted => {
+                index.remove(&change.path);
+            }
+            _ => add::add_file(&worktree.root().join(&change.path), index, repository)?,
+        }
+    }
 
     Ok(())
 }
@@ -42,40 +189,234 @@ pub fn create_commit<'a>(
     index: &'a mut Index,
     head_ref: &'a str,
 ) -> crate::Result<Commit> {
-    let (root_tree, containing_trees) = build_tree(&index.get_entries()[..]);
-    for tree in containing_trees.iter() {
-        repository.database.store_object(tree)?;
+    let ref_handler = RefHandler::new(repository);
+    let parent_commit = ref_handler.deref(head_ref).ok();
+    let (author, committer) = read_signatures(&repository.config()?)?;
+    let parents = parent_commit.into_iter().collect();
+    create_commit_with_parents(repository, index, parents, author, committer)
+}
+
+/// Write the trees of the index to the database, and return the id of the root tree.
+pub fn write_tree(repository: &Repository) -> crate::Result<ObjectId> {
+    let mut index = repository.load_index()?;
+    if let Some((path, _)) = index.as_mut().get_unmerged_entries().first() {
+        let message = format!(
+            "write-tree: error building trees, {} is unmerged",
+            path.display()
+        );
+        return Err(crate::Error::Fatal(None, message));
     }
-    repository.database.store_object(&root_tree)?;
 
+    let root_tree_id = store_trees(repository, index.as_mut())?;
+    index.write()?;
+    Ok(root_tree_id)
+}
+
+/// Create a commit of the tree with the given parents and message, without updating any refs.
+/// The author and committer are read as for any other commit.
+pub fn commit_tree(
+    tree: &ObjectId,
+    parents: Vec<ObjectId>,
+    message: String,
+    repository: &Repository,
+) -> crate::Result<Commit> {
+    let database = &repository.database;
+    if database.load_object(tree)?.0 != ObjectType::Tree {
+        let message = format!("{} is not a valid 'tree' object", tree);
+        return Err(crate::Error::Fatal(None, message));
+    }
+    for parent in &parents {
+        if database.load_object(parent)?.0 != ObjectType::Commit {
+            let message = format!("{} is not a valid 'commit' object", parent);
+            return Err(crate::Error::Fatal(None, message));
+        }
+    }
+
+    let (author, committer) = read_signatures(&repository.config()?)?;
+    let commit = Commit::with_parents(
+        tree.clone(),
+        author.identity,
+        message,
+        parents,
+        author.timestamp,
+    )
+    .with_timezone(author.timezone)
+    .with_committer(committer.identity, committer.timestamp, committer.timezone)
+    .with_hash_algorithm(repository.hash_algorithm());
+    database.store_object(&commit)?;
+    Ok(commit)
+}
+
+/// Commit the index on top of HEAD with the given message, preserving the author and the date it
+/// was authored, as when applying a patch that someone else wrote. The committer is read as for
+/// any other commit, and the reflog entry is prefixed with the action, such as `am`.
+pub(crate) fn commit_as_author(
+    repository: &Repository,
+    index: &mut Index,
+    message: &str,
+    author: Author,
+    date: &str,
+    reflog_action: &str,
+) -> crate::Result<Commit> {
+    let (_, committer) = read_signatures(&repository.config()?)?;
+    let (timestamp, timezone) = parse_date(date)?;
+    let author = Signature {
+        identity: author,
+        timestamp,
+        timezone,
+    };
+    fs::write(repository.git_dir().join("COMMIT_EDITMSG"), message)?;
+
+    let head_ref = repository.head().expect("HEAD does not exist");
     let ref_handler = RefHandler::new(repository);
-    let parent_commit = ref_handler.deref(head_ref).ok();
-    Ok(create_commit_with_tree(
-        root_tree.id(),
-        parent_commit,
+    let parent_commit = ref_handler.deref(&head_ref).ok();
+    let parents = parent_commit.iter().cloned().collect();
+    let commit = create_commit_with_parents(repository, index, parents, author, committer)?;
+    repository.database.store_object(&commit)?;
+    ref_handler.update_head(commit.id(), parent_commit.clone().into())?;
+    let reflog_message = format!("{}: {}", reflog_action, commit.message);
+    reflog::append_head_entry(
+        parent_commit.as_ref(),
+        commit.id(),
+        &reflog_message,
         repository,
-    ))
+    )?;
+    Ok(commit)
+}
+
+fn create_commit_with_parents(
+    repository: &Repository,
+    index: &mut Index,
+    parents: Vec<ObjectId>,
+    author: Signature,
+    committer: Signature,
+) -> crate::Result<Commit> {
+    let root_tree_id = store_trees(repository, index)?;
+    create_commit_with_tree(&root_tree_id, parents, author, committer, repository)
+}
+
+/// Build and store the trees of the index, and update its cache tree. Returns the id of the root
+/// tree.
+fn store_trees(repository: &Repository, index: &mut Index) -> crate::Result<ObjectId> {
+    let (root_tree_id, trees, cache_tree) = build_tree(
+        &index.get_entries()[..],
+        index.cache_tree(),
+        repository.hash_algorithm(),
+    );
+    for tree in trees.iter() {
+        repository.database.store_object(tree)?;
+    }
+    index.set_cache_tree(cache_tree);
+    Ok(root_tree_id)
 }
 
 fn create_commit_with_tree(
     tree: &ObjectId,
-    parent: Option<ObjectId>,
+    parents: Vec<ObjectId>,
+    author: Signature,
+    committer: Signature,
     repository: &Repository,
-) -> Commit {
-    let config = repository.config();
-    let author = Author {
-        name: config.author_name,
-        email: config.author_email,
+) -> crate::Result<Commit> {
+    let message = fs::read_to_string(repository.git_dir().join("COMMIT_EDITMSG"))?;
+
+    Ok(Commit::with_parents(
+        tree.clone(),
+        author.identity,
+        message,
+        parents,
+        author.timestamp,
+    )
+    .with_timezone(author.timezone)
+    .with_committer(committer.identity, committer.timestamp, committer.timezone)
+    .with_hash_algorithm(repository.hash_algorithm()))
+}
+
+/// Read the author and committer from the `GIT_<ROLE>_NAME`, `GIT_<ROLE>_EMAIL` and
+/// `GIT_<ROLE>_DATE` environment variables, falling back to `user.name` and `user.email` from
+/// the config and the current time. The committer defaults to the author.
+fn read_signatures(config: &Config) -> crate::Result<(Signature, Signature)> {
+    let read_identity = |role: &str| {
+        let read_variable = |env_variable: &str, config_key: &str| {
+            env::var(format!("GIT_{}_{}", role, env_variable))
+                .ok()
+                .or_else(|| config.get_string(config_key))
+                .filter(|value| !value.trim().is_empty())
+        };
+        match (
+            read_variable("NAME", "user.name"),
+            read_variable("EMAIL", "user.email"),
+        ) {
+            (Some(name), Some(email)) => Some(Author { name, email }),
+            _ => None,
+        }
+    };
+    let read_date = |role: &str| match env::var(format!("GIT_{}_DATE", role)) {
+        Ok(date) => parse_date(&date),
+        Err(_) => {
+            let now = Local::now();
+            Ok((now.timestamp() as u64, now.format("%z").to_string()))
+        }
     };
-    let message = fs::read_to_string(repository.git_dir().join("COMMIT_EDITMSG"))
-        .expect("failed to read commit message");
 
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    let author_identity = read_identity("AUTHOR").ok_or_else(|| {
+        let message = format!("Author identity unknown\n\n{}", IDENTITY_INSTRUCTIONS);
+        crate::Error::Fatal(None, message)
+    })?;
+    let committer_identity = read_identity("COMMITTER").unwrap_or_else(|| author_identity.clone());
+    let (author_timestamp, author_timezone) = read_date("AUTHOR")?;
+    let (committer_timestamp, committer_timezone) = read_date("COMMITTER")?;
+
+    let author = Signature {
+        identity: author_identity,
+        timestamp: author_timestamp,
+        timezone: author_timezone,
+    };
+    let committer = Signature {
+        identity: committer_identity,
+        timestamp: committer_timestamp,
+        timezone: committer_timezone,
+    };
+    Ok((author, committer))
+}
+
+/// The committer as recorded in reflogs: `<name> <<email>> <timestamp> <timezone>`. Unlike for
+/// commits, an unknown identity isn't an error, as it would stop commands like `switch`.
+pub(crate) fn reflog_identity(config: &Config) -> String {
+    match read_signatures(config) {
+        Ok((_, committer)) => format!(
+            "{} {} {}",
+            committer.identity, committer.timestamp, committer.timezone
+        ),
+        Err(_) => {
+            let now = Local::now();
+            format!("unknown <unknown> {} {}", now.timestamp(), now.format("%z"))
+        }
+    }
+}
+
+/// Parse a date given as `[@]<seconds since the epoch> [<UTC offset>]`, in RFC 2822 or in
+/// ISO 8601 format, into a timestamp and the UTC offset of its time zone.
+fn parse_date(date: &str) -> crate::Result<(u64, String)> {
+    let date = date.trim();
+    let error = || crate::Error::Fatal(None, format!("invalid date format: {}", date));
+
+    let raw_date = date.strip_prefix('@').unwrap_or(date);
+    let (seconds, offset) = raw_date.split_once(' ').unwrap_or((raw_date, "+0000"));
+    let is_offset = |offset: &str| {
+        offset.len() == 5
+            && offset.starts_with(['+', '-'])
+            && offset[1..].chars().all(|c| c.is_ascii_digit())
+    };
+    if let (Ok(seconds), true) = (seconds.parse::<u64>(), is_offset(offset)) {
+        return Ok((seconds, offset.to_owned()));
+    }
 
-    Commit::new(tree.clone(), author, message, parent, timestamp)
+    let datetime = DateTime::parse_from_rfc2822(date)
+        .or_else(|_| DateTime::parse_from_rfc3339(date))
+        .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z"))
+        .map_err(|_| error())?;
+    let timestamp = u64::try_from(datetime.timestamp()).map_err(|_| error())?;
+    Ok((timestamp, datetime.format("%z").to_string()))
 }
 
 fn write_commit_status(commit: &Commit, writer: &mut dyn OutputWriter) -> io::Result<()> {
@@ -85,26 +426,48 @@ fn write_commit_status(commit: &Commit, writer: &mut dyn OutputWriter) -> io::Re
         .next()
         .expect("Not a single line in the commit message");
 
-    let root_commit_notice = commit.parent.to_owned().map_or("(root commit) ", |_| "");
+    let root_commit_notice = commit.parent().map_or("(root commit) ", |_| "");
 
     let message = format!(
         "[{}{}] {}",
         root_commit_notice,
-        to_hex_string(&commit.short_id()),
+        commit.short_id_as_string(),
         first_line,
     );
     writer.writeln(message)?;
     Ok(())
 }
 
-fn build_tree(entries: &[&IndexEntry]) -> (Tree, Vec<Tree>) {
+/// Build the trees of the index entries, reusing the ids of directories that are still valid in
+/// the cache tree. Returns the id of the root tree, the trees that had to be built and the cache
+/// tree of the result.
+fn build_tree(
+    entries: &[&IndexEntry],
+    cache_tree: &CacheTree,
+    hash_algorithm: HashAlgorithm,
+) -> (ObjectId, Vec<Tree>, CacheTree) {
     let tmp_entries = entries.iter().map(|entry| TmpEntry {
         path: PathBuf::from(&entry.path),
         object_id: &entry.object_id,
         file_mode: entry.file_mode(),
     });
 
-    build_tree_from_tmp_entries(tmp_entries)
+    build_tree_from_tmp_entries(tmp_entries, Some(cache_tree), hash_algorithm)
+}
+
+/// Build the trees of the files, given as paths relative to the root of the worktree along with
+/// their ids and modes, sorted by path. Returns the id of the root tree and the trees to store.
+pub(crate) fn build_tree_from_paths<'a>(
+    files: impl Iterator<Item = (PathBuf, &'a ObjectId, FileMode)>,
+    hash_algorithm: HashAlgorithm,
+) -> (ObjectId, Vec<Tree>) {
+    let tmp_entries = files.map(|(path, object_id, file_mode)| TmpEntry {
+        path,
+        object_id,
+        file_mode,
+    });
+    let (root_tree_id, trees, _) = build_tree_from_tmp_entries(tmp_entries, None, hash_algorithm);
+    (root_tree_id, trees)
 }
 
 #[derive(Debug)]
@@ -116,11 +479,23 @@ struct TmpEntry<'a> {
 
 fn build_tree_from_tmp_entries<'a>(
     entries: impl Iterator<Item = TmpEntry<'a>>,
-) -> (Tree, Vec<Tree>) {
-    let mut entry_iter = entries.peekable();
+    cache_tree: Option<&CacheTree>,
+    hash_algorithm: HashAlgorithm,
+) -> (ObjectId, Vec<Tree>, CacheTree) {
+    let entries: Vec<TmpEntry> = entries.collect();
+    let entry_count = entries.len();
+    if let Some(cache_tree) = cache_tree.filter(|cache_tree| {
+        cache_tree.object_id().is_some() && cache_tree.entry_count() == Some(entry_count)
+    }) {
+        let object_id = cache_tree.object_id().unwrap().clone();
+        return (object_id, Vec::new(), cache_tree.clone());
+    }
+
+    let mut entry_iter = entries.into_iter().peekable();
     let mut tree_entries = Vec::new();
 
     let mut trees: Vec<Tree> = Vec::new();
+    let mut subtrees = BTreeMap::new();
 
     while let Some(entry) = entry_iter.next() {
         let tree_entry = if entry.path.parent() == Some(&PathBuf::from("")) {
@@ -155,16 +530,20 @@ fn build_tree_from_tmp_entries<'a>(
                     }
                 })
                 .collect();
-            let (root_tree, containing_trees) =
-                build_tree_from_tmp_entries(tmp_entries.into_iter());
+            let name = prefix.as_os_str().to_string_lossy().into_owned();
+            let (subtree_id, containing_trees, subtree_cache) = build_tree_from_tmp_entries(
+                tmp_entries.into_iter(),
+                cache_tree.and_then(|cache_tree| cache_tree.subtree(&name)),
+                hash_algorithm,
+            );
             let tree_entry = TreeEntry::new(
                 &PathBuf::from(prefix.as_os_str()),
-                root_tree.id().clone(),
+                subtree_id,
                 FileMode::Directory,
             );
 
-            trees.push(root_tree);
             trees.extend(containing_trees);
+            subtrees.insert(name, subtree_cache);
 
             tree_entry
         };
@@ -172,7 +551,11 @@ fn build_tree_from_tmp_entries<'a>(
         tree_entries.push(tree_entry);
     }
 
-    (Tree::new(tree_entries), trees)
+    let tree = Tree::new(tree_entries, hash_algorithm);
+    let cache_tree = CacheTree::new(tree.id().clone(), entry_count, subtrees);
+    let object_id = tree.id().clone();
+    trees.push(tree);
+    (object_id, trees, cache_tree)
 }
 
 fn next_if_prefixed_with<'a>(
@@ -0,0 +1,449 @@
+//! The commit-graph file, `.git/objects/info/commit-graph`, which holds the parents, commit time
+//! and generation number of each commit, so that walking the history doesn't require inflating and
+//! parsing every commit on the way. The generation number of a commit is one more than the
+//! largest generation number of its parents, so a commit can't be reachable from a commit with a
+//! lower generation number.
+//!
+//! The file is in the format of Git: a header, a table of contents of its chunks, and a checksum
+//! of it all. The chunks are the fanout of the first byte of the ids (`OIDF`), the sorted ids
+//! (`OIDL`), the tree, parents, generation number and commit time of each commit (`CDAT`), and the
+//! parents of octopus merges beyond the first (`EDGE`). Parents are given by their position in the
+//! sorted ids.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::file::LockFile;
+use crate::hashing::HashAlgorithm;
+use crate::objects::{Commit, ObjectId, ObjectType};
+use crate::refs::RefHandler;
+use crate::trace;
+use crate::workspace::Repository;
+
+const SIGNATURE: &[u8; 4] = b"CGPH";
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 8;
+const CHUNK_ENTRY_SIZE: usize = 12;
+const FANOUT_SIZE: usize = 256 * 4;
+
+const FANOUT_CHUNK: &[u8; 4] = b"OIDF";
+const LOOKUP_CHUNK: &[u8; 4] = b"OIDL";
+const COMMIT_DATA_CHUNK: &[u8; 4] = b"CDAT";
+const EXTRA_EDGES_CHUNK: &[u8; 4] = b"EDGE";
+
+const PARENT_NONE: u32 = 0x7000_0000;
+/// Marks the second parent as the position of the rest of the parents in the extra edges, and
+/// the last of those parents.
+const EXTRA_EDGE_BIT: u32 = 0x8000_0000;
+const GENERATION_MAX: u32 = 0x3FFF_FFFF;
+
+/// The generation number of commits that aren't in the commit-graph, which are treated as newer
+/// than all commits that are.
+pub const GENERATION_INFINITY: u32 = u32::MAX;
+
+/// A commit as recorded in the commit-graph.
+#[derive(Debug, PartialEq)]
+pub struct GraphCommit {
+    pub parents: Vec<ObjectId>,
+    pub generation: u32,
+    /// When the commit was committed, in seconds since the epoch.
+    pub timestamp: u64,
+}
+
+/// A commit-graph file loaded into memory.
+pub struct CommitGraph {
+    data: Vec<u8>,
+    hash_size: usize,
+    num_commits: usize,
+    fanout_offset: usize,
+    lookup_offset: usize,
+    commit_data_offset: usize,
+    extra_edges_offset: Option<usize>,
+}
+
+impl CommitGraph {
+    /// Load the commit-graph of the repository, or return None if it has none or if it's disabled
+    /// with `core.commitGraph`. A commit-graph that can't be read is ignored, as the commits can
+    /// be read from the object database instead.
+    pub fn load(repository: &Repository) -> crate::Result<Option<CommitGraph>> {
+        if repository.config()?.get_bool("core.commitGraph")? == Some(false) {
+            return Ok(None);
+        }
+        let path = commit_graph_path(repository);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        match CommitGraph::parse(data, repository.hash_algorithm()) {
+            Ok(graph) => Ok(Some(graph)),
+            Err(reason) => {
+                trace::trace("commit-graph", format_args!("ignored: {}", reason));
+                Ok(None)
+            }
+        }
+    }
+
+    fn parse(data: Vec<u8>, hash_algorithm: HashAlgorithm) -> Result<CommitGraph, String> {
+        let hash_size = hash_algorithm.raw_size();
+        if data.len() < HEADER_SIZE + CHUNK_ENTRY_SIZE + hash_size || !data.starts_with(SIGNATURE) {
+            return Err(String::from("not a commit-graph file"));
+        }
+        if data[4] != VERSION {
+            return Err(format!("unsupported version {}", data[4]));
+        }
+        if data[5] != hash_version(hash_algorithm) {
+            return Err(format!("unsupported hash version {}", data[5]));
+        }
+        let num_chunks = data[6] as usize;
+        let chunks_end = data.len() - hash_size;
+        if HEADER_SIZE + (num_chunks + 1) * CHUNK_ENTRY_SIZE > chunks_end {
+            return Err(String::from("table of contents is truncated"));
+        }
+
+        // the end of each chunk is given by the offset of the next entry
+        let mut chunks = HashMap::new();
+        for i in 0..num_chunks {
+            let entry = HEADER_SIZE + i * CHUNK_ENTRY_SIZE;
+            let id: [u8; 4] = data[entry..entry + 4].try_into().unwrap();
+            let start = read_u64(&data, entry + 4) as usize;
+            let end = read_u64(&data, entry + 4 + CHUNK_ENTRY_SIZE) as usize;
+            if start > end || end > chunks_end {
+                return Err(format!(
+                    "chunk {} is out of bounds",
+                    String::from_utf8_lossy(&id)
+                ));
+            }
+            chunks.insert(id, (start, end - start));
+        }
+        let find_chunk = |id: &[u8; 4]| {
+            chunks
+                .get(id)
+                .copied()
+                .ok_or_else(|| format!("missing chunk {}", String::from_utf8_lossy(id)))
+        };
+
+        let (fanout_offset, fanout_size) = find_chunk(FANOUT_CHUNK)?;
+        let (lookup_offset, lookup_size) = find_chunk(LOOKUP_CHUNK)?;
+        let (commit_data_offset, commit_data_size) = find_chunk(COMMIT_DATA_CHUNK)?;
+        let num_commits = lookup_size / hash_size;
+        if fanout_size != FANOUT_SIZE
+            || read_u32(&data, fanout_offset + FANOUT_SIZE - 4) as usize != num_commits
+            || commit_data_size != num_commits * (hash_size + 16)
+        {
+            return Err(String::from(
+                "chunk sizes don't match the number of commits",
+            ));
+        }
+
+        Ok(CommitGraph {
+            extra_edges_offset: chunks.get(EXTRA_EDGES_CHUNK).map(|(offset, _)| *offset),
+            data,
+            hash_size,
+            num_commits,
+            fanout_offset,
+            lookup_offset,
+            commit_data_offset,
+        })
+    }
+
+    /// Look up a commit, or return None if it's not in the commit-graph.
+    pub fn lookup(&self, commit_id: &ObjectId) -> Option<GraphCommit> {
+        let position = self.position(commit_id)?;
+        let entry = self.commit_data_offset + position * (self.hash_size + 16) + self.hash_size;
+
+        let mut parents = vec![];
+        for parent in [read_u32(&self.data, entry), read_u32(&self.data, entry + 4)] {
+            if parent == PARENT_NONE {
+                break;
+            } else if parent & EXTRA_EDGE_BIT == 0 {
+                parents.push(self.id_at(parent as usize)?);
+                continue;
+            }
+            let mut edge = self.extra_edges_offset? + (parent & !EXTRA_EDGE_BIT) as usize * 4;
+            loop {
+                let extra_parent = read_u32(self.data.get(..edge + 4)?, edge);
+                parents.push(self.id_at((extra_parent & !EXTRA_EDGE_BIT) as usize)?);
+                if extra_parent & EXTRA_EDGE_BIT != 0 {
+                    break;
+                }
+                edge += 4;
+            }
+        }
+
+        let generation_and_time_high = read_u32(&self.data, entry + 8);
+        let time_low = read_u32(&self.data, entry + 12);
+        Some(GraphCommit {
+            parents,
+            generation: generation_and_time_high >> 2,
+            timestamp: ((generation_and_time_high as u64 & 0x3) << 32) | time_low as u64,
+        })
+    }
+
+    /// Find the position of the commit in the sorted ids, by searching the ids that start with
+    /// the same byte as given by the fanout.
+    fn position(&self, commit_id: &ObjectId) -> Option<usize> {
+        let first_byte = *commit_id.bytes().first()? as usize;
+        let start = match first_byte {
+            0 => 0,
+            _ => read_u32(&self.data, self.fanout_offset + (first_byte - 1) * 4) as usize,
+        };
+        let end = read_u32(&self.data, self.fanout_offset + first_byte * 4) as usize;
+        let (mut low, mut high) = (start, end.min(self.num_commits));
+        while low < high {
+            let middle = (low + high) / 2;
+            match self.id_bytes_at(middle).cmp(commit_id.bytes()) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return Some(middle),
+            }
+        }
+        None
+    }
+
+    fn id_at(&self, position: usize) -> Option<ObjectId> {
+        if position >= self.num_commits {
+            return None;
+        }
+        ObjectId::from_sha_bytes(self.id_bytes_at(position)).ok()
+    }
+
+    fn id_bytes_at(&self, position: usize) -> &[u8] {
+        let start = self.lookup_offset + position * self.hash_size;
+        &self.data[start..start + self.hash_size]
+    }
+}
+
+/// Write a commit-graph of all commits reachable from the refs and HEAD, replacing any existing
+/// one.
+pub fn write(repository: &Repository) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let mut stack = refs
+        .list_all_refs()?
+        .into_iter()
+        .map(|(_, object_id)| object_id)
+        .collect::<Vec<_>>();
+    stack.extend(refs.head().ok());
+
+    let mut commits = BTreeMap::new();
+    while let Some(object_id) = stack.pop() {
+        if commits.contains_key(&object_id) {
+            continue;
+        }
+        // refs may point to trees or blobs, which have no place in the graph
+        let (object_type, _) = repository.database.load_object(&object_id)?;
+        if object_type != ObjectType::Commit {
+            continue;
+        }
+        let commit = repository.database.load_commit(&object_id)?;
+        stack.extend(commit.parents.iter().cloned());
+        commits.insert(object_id, commit);
+    }
+
+    let content = encode(&commits, repository.hash_algorithm());
+    let path = commit_graph_path(repository);
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut lockfile = LockFile::acquire(&path)?;
+    lockfile.write(&content)?;
+    Ok(())
+}
+
+/// Encode the commits, which must include all of their parents, as a commit-graph file.
+fn encode(commits: &BTreeMap<ObjectId, Commit>, hash_algorithm: HashAlgorithm) -> Vec<u8> {
+    let positions = commits
+        .keys()
+        .enumerate()
+        .map(|(position, id)| (id, position as u32))
+        .collect::<HashMap<_, _>>();
+    let generations = compute_generations(commits);
+
+    let mut fanout = vec![0u32; 256];
+    for id in commits.keys() {
+        fanout[id.bytes()[0] as usize] += 1;
+    }
+    for i in 1..fanout.len() {
+        fanout[i] += fanout[i - 1];
+    }
+    let fanout_chunk = fanout
+        .iter()
+        .flat_map(|count| count.to_be_bytes())
+        .collect::<Vec<_>>();
+    let lookup_chunk = commits
+        .keys()
+        .flat_map(|id| id.bytes().to_vec())
+        .collect::<Vec<_>>();
+
+    let mut commit_data_chunk = vec![];
+    let mut extra_edges_chunk: Vec<u8> = vec![];
+    for (id, commit) in commits {
+        let parent_positions = commit
+            .parents
+            .iter()
+            .map(|parent| positions[parent])
+            .collect::<Vec<_>>();
+        let (first_parent, second_parent) = match parent_positions.as_slice() {
+            [] => (PARENT_NONE, PARENT_NONE),
+            [parent] => (*parent, PARENT_NONE),
+            [first, second] => (*first, *second),
+            [first, rest @ ..] => {
+                let edge_index = (extra_edges_chunk.len() / 4) as u32;
+                for (i, parent) in rest.iter().enumerate() {
+                    let last_bit = if i == rest.len() - 1 {
+                        EXTRA_EDGE_BIT
+                    } else {
+                        0
+                    };
+                    extra_edges_chunk.extend((parent | last_bit).to_be_bytes());
+                }
+                (*first, EXTRA_EDGE_BIT | edge_index)
+            }
+        };
+        let timestamp = commit.committer_timestamp;
+        commit_data_chunk.extend(commit.tree.bytes());
+        commit_data_chunk.extend(first_parent.to_be_bytes());
+        commit_data_chunk.extend(second_parent.to_be_bytes());
+        let generation_and_time_high = (generations[id] << 2) | ((timestamp >> 32) as u32 & 0x3);
+        commit_data_chunk.extend(generation_and_time_high.to_be_bytes());
+        commit_data_chunk.extend((timestamp as u32).to_be_bytes());
+    }
+
+    let mut chunks = vec![
+        (FANOUT_CHUNK, fanout_chunk),
+        (LOOKUP_CHUNK, lookup_chunk),
+        (COMMIT_DATA_CHUNK, commit_data_chunk),
+    ];
+    if !extra_edges_chunk.is_empty() {
+        chunks.push((EXTRA_EDGES_CHUNK, extra_edges_chunk));
+    }
+
+    let mut content = SIGNATURE.to_vec();
+    content.extend([VERSION, hash_version(hash_algorithm), chunks.len() as u8, 0]);
+    let mut offset = (HEADER_SIZE + (chunks.len() + 1) * CHUNK_ENTRY_SIZE) as u64;
+    for (id, chunk) in &chunks {
+        content.extend(*id);
+        content.extend(offset.to_be_bytes());
+        offset += chunk.len() as u64;
+    }
+    content.extend([0; 4]);
+    content.extend(offset.to_be_bytes());
+    for (_, chunk) in &chunks {
+        content.extend(chunk);
+    }
+    let checksum = hash_algorithm.hash(&content);
+    content.extend(checksum);
+    content
+}
+
+/// Compute the generation number of each commit, parents first, without recursing as the history
+/// may be deep.
+fn compute_generations(commits: &BTreeMap<ObjectId, Commit>) -> HashMap<&ObjectId, u32> {
+    let mut generations = HashMap::new();
+    for id in commits.keys() {
+        let mut stack = vec![id];
+        while let Some(&id) = stack.last() {
+            if generations.contains_key(id) {
+                stack.pop();
+                continue;
+            }
+            let parents = &commits[id].parents;
+            let pending = parents
+                .iter()
+                .filter(|parent| !generations.contains_key(parent))
+                .collect::<Vec<_>>();
+            if !pending.is_empty() {
+                stack.extend(pending);
+                continue;
+            }
+            let max_parent_generation = parents
+                .iter()
+                .map(|parent| generations[parent])
+                .max()
+                .unwrap_or(0);
+            generations.insert(id, (max_parent_generation + 1).min(GENERATION_MAX));
+            stack.pop();
+        }
+    }
+    generations
+}
+
+fn commit_graph_path(repository: &Repository) -> PathBuf {
+    repository
+        .common_dir()
+        .join("objects")
+        .join("info")
+        .join("commit-graph")
+}
+
+fn hash_version(hash_algorithm: HashAlgorithm) -> u8 {
+    match hash_algorithm {
+        HashAlgorithm::Sha1 => 1,
+        HashAlgorithm::Sha256 => 2,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Author, GitObject};
+
+    #[test]
+    fn test_encoded_commits_are_looked_up_with_generation_numbers() {
+        let tree = ObjectId::from_sha_bytes(&[0x11; 20]).unwrap();
+        let author = Author {
+            name: String::from("Author"),
+            email: String::from("author@example.com"),
+        };
+        let commit = |parents: Vec<&Commit>, timestamp: u64| {
+            let parents = parents.iter().map(|parent| parent.id().clone()).collect();
+            Commit::with_parents(
+                tree.clone(),
+                author.clone(),
+                String::new(),
+                parents,
+                timestamp,
+            )
+        };
+        let root = commit(vec![], 1);
+        let sides = (2..5).map(|i| commit(vec![&root], i)).collect::<Vec<_>>();
+        // the timestamp doesn't fit in 32 bits
+        let octopus = commit(sides.iter().collect(), 1 << 33);
+        let (root_id, octopus_id) = (root.id().clone(), octopus.id().clone());
+        let side_ids = sides.iter().map(|side| side.id().clone()).collect();
+        let commits = sides
+            .into_iter()
+            .chain([root, octopus])
+            .map(|commit| (commit.id().clone(), commit))
+            .collect::<BTreeMap<_, _>>();
+
+        let graph =
+            CommitGraph::parse(encode(&commits, HashAlgorithm::Sha1), HashAlgorithm::Sha1).unwrap();
+
+        assert_eq!(
+            graph.lookup(&root_id),
+            Some(GraphCommit {
+                parents: vec![],
+                generation: 1,
+                timestamp: 1,
+            })
+        );
+        assert_eq!(
+            graph.lookup(&octopus_id),
+            Some(GraphCommit {
+                parents: side_ids,
+                generation: 3,
+                timestamp: 1 << 33,
+            })
+        );
+        assert_eq!(graph.lookup(&tree), None);
+    }
+}
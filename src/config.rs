@@ -1,54 +1,629 @@
-use ini::Ini;
 use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 
+use crate::file::LockFile;
+use crate::output::OutputWriter;
+
+/// The level a config file applies to. Values from later levels take precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigLevel {
+    /// `/etc/gitconfig`, shared by all users.
+    System,
+    /// `$XDG_CONFIG_HOME/git/config` and `~/.gitconfig`, for the current user.
+    Global,
+    /// `.git/config`, for a single repository.
+    Local,
+}
+
+impl fmt::Display for ConfigLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ConfigLevel::System => "system",
+            ConfigLevel::Global => "global",
+            ConfigLevel::Local => "local",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A single variable from a config file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEntry {
+    /// The canonical key on the form `section.name` or `section.subsection.name`, where the
+    /// section and name are lowercase.
+    pub key: String,
+    /// The value, or None if the variable was given without `=`, which means true.
+    pub value: Option<String>,
+    pub level: ConfigLevel,
+}
+
+/// The merged configuration from all config files, in order of increasing precedence.
+#[derive(Debug, Default)]
 pub struct Config {
-    pub author_name: String,
-    pub author_email: String,
+    entries: Vec<ConfigEntry>,
 }
 
-pub fn read_config() -> Result<Config, env::VarError> {
-    let gitconfig = parse_gitconfig(get_gitconfig_path().unwrap()).unwrap();
+impl Config {
+    /// Load the system, global and local config files. The local config is only read if a git
+    /// directory is given. Files that don't exist are skipped.
+    pub fn load(git_dir: Option<&Path>) -> crate::Result<Config> {
+        let mut files = vec![];
+        if env::var_os("GIT_CONFIG_NOSYSTEM").is_none() {
+            files.push((ConfigLevel::System, system_config_path()));
+        }
+        files.extend(
+            global_config_paths()
+                .into_iter()
+                .map(|path| (ConfigLevel::Global, path)),
+        );
+        if let Some(git_dir) = git_dir {
+            files.push((ConfigLevel::Local, git_dir.join("config")));
+        }
+        Config::from_files(&files)
+    }
 
-    Ok(Config {
-        author_name: env::var("GIT_AUTHOR_NAME").or_else(|_| Ok(gitconfig.name.unwrap()))?,
-        author_email: env::var("GIT_AUTHOR_EMAIL").or_else(|_| Ok(gitconfig.email.unwrap()))?,
-    })
+    /// Read and merge the given config files, which must be ordered by increasing precedence.
+    pub fn from_files<P: AsRef<Path>>(files: &[(ConfigLevel, P)]) -> crate::Result<Config> {
+        let mut entries = vec![];
+        for (level, path) in files {
+            entries.extend(read_config_file(path.as_ref(), *level)?);
+        }
+        Ok(Config { entries })
+    }
+
+    /// All entries, in order of increasing precedence.
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// Get the value of the key with the highest precedence. A variable without a value is
+    /// returned as an empty string.
+    pub fn get_string(&self, key: &str) -> Option<String> {
+        self.find_last(key)
+            .map(|entry| entry.value.clone().unwrap_or_default())
+    }
+
+    /// Get every value of a multi-valued key, in order of increasing precedence.
+    pub fn get_all(&self, key: &str) -> Vec<String> {
+        let key = canonicalize_key(key);
+        self.entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .map(|entry| entry.value.clone().unwrap_or_default())
+            .collect()
+    }
+
+    /// Get the value of the key as a boolean. Accepts `true`, `yes`, `on` and `1` as true, and
+    /// `false`, `no`, `off`, `0` and the empty string as false.
+    pub fn get_bool(&self, key: &str) -> crate::Result<Option<bool>> {
+        let entry = match self.find_last(key) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        match entry.value.as_deref().map(str::to_lowercase).as_deref() {
+            None | Some("true") | Some("yes") | Some("on") | Some("1") => Ok(Some(true)),
+            Some("false") | Some("no") | Some("off") | Some("0") | Some("") => Ok(Some(false)),
+            Some(value) => {
+                let message = format!("bad boolean config value '{}' for '{}'", value, key);
+                Err(crate::Error::Fatal(None, message))
+            }
+        }
+    }
+
+    /// Get the value of the key as an integer, optionally suffixed with `k`, `m` or `g` to scale
+    /// it by 1024, 1024^2 or 1024^3.
+    pub fn get_int(&self, key: &str) -> crate::Result<Option<i64>> {
+        let value = match self.get_string(key) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let error = || {
+            let message = format!("bad numeric config value '{}' for '{}'", value, key);
+            crate::Error::Fatal(None, message)
+        };
+
+        let trimmed = value.trim();
+        let (digits, scale) = match trimmed.chars().last().map(|c| c.to_ascii_lowercase()) {
+            Some('k') => (&trimmed[..trimmed.len() - 1], 1 << 10),
+            Some('m') => (&trimmed[..trimmed.len() - 1], 1 << 20),
+            Some('g') => (&trimmed[..trimmed.len() - 1], 1 << 30),
+            _ => (trimmed, 1),
+        };
+        let number = digits.parse::<i64>().map_err(|_| error())?;
+        number.checked_mul(scale).map(Some).ok_or_else(error)
+    }
+
+    fn find_last(&self, key: &str) -> Option<&ConfigEntry> {
+        let key = canonicalize_key(key);
+        self.entries.iter().rev().find(|entry| entry.key == key)
+    }
 }
 
-pub struct UserConfig {
-    pub name: Option<String>,
-    pub email: Option<String>,
+/// What the config command should do.
+#[derive(Debug)]
+pub enum ConfigAction {
+    Get(String),
+    Set(String, String),
+    Unset(String),
+    List,
 }
 
-fn get_gitconfig_path() -> Option<PathBuf> {
-    let home_dir = env::var("HOME").ok()?;
-    Some(PathBuf::from(home_dir).join(".gitconfig"))
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Only use the config files of this level. Otherwise, reads use the merged configuration
+    /// and writes go to the local config.
+    #[builder(default)]
+    pub level: Option<ConfigLevel>,
 }
 
-pub fn parse_gitconfig<P: AsRef<Path>>(gitconfig_path: P) -> Result<UserConfig, ini::Error> {
-    if !gitconfig_path.as_ref().is_file() {
-        return Ok(UserConfig {
-            name: None,
-            email: None,
-        });
+/// Read or write configuration. The local config is only available if a git directory is given.
+/// Returns false if the key to get is not set.
+pub fn config(
+    action: &ConfigAction,
+    options: &Options,
+    git_dir: Option<&Path>,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let read_config = || match options.level {
+        Some(level) => {
+            let files = config_files(level, git_dir)?
+                .into_iter()
+                .map(|path| (level, path))
+                .collect::<Vec<_>>();
+            Config::from_files(&files)
+        }
+        None => Config::load(git_dir),
+    };
+    let writable_file = || {
+        let level = options.level.unwrap_or(ConfigLevel::Local);
+        config_files(level, git_dir).map(|mut files| files.pop().unwrap())
+    };
+
+    match action {
+        ConfigAction::Get(key) => match read_config()?.get_string(key) {
+            Some(value) => {
+                writer.writeln(value)?;
+            }
+            None => return Ok(false),
+        },
+        ConfigAction::List => {
+            for entry in read_config()?.entries() {
+                match &entry.value {
+                    Some(value) => writer.writeln(format!("{}={}", entry.key, value))?,
+                    None => writer.writeln(entry.key.clone())?,
+                };
+            }
+        }
+        ConfigAction::Set(key, value) => write_config_value(&writable_file()?, key, value)?,
+        ConfigAction::Unset(key) => edit_config_file(&writable_file()?, |content, path| {
+            unset_value(content, path, key)
+        })?,
     }
 
-    let conf = Ini::load_from_file(&gitconfig_path)?;
+    Ok(true)
+}
+
+/// Set a variable in the config file at the path, creating the file if it doesn't exist.
+pub fn write_config_value(path: &Path, key: &str, value: &str) -> crate::Result<()> {
+    edit_config_file(path, |content, path| set_value(content, path, key, value))
+}
+
+/// The config files of the level in order of increasing precedence. The last one is the one
+/// that is written to.
+fn config_files(level: ConfigLevel, git_dir: Option<&Path>) -> crate::Result<Vec<PathBuf>> {
+    match level {
+        ConfigLevel::System => Ok(vec![system_config_path()]),
+        ConfigLevel::Global => {
+            let mut paths = global_config_paths();
+            // like Git, write to an existing XDG config rather than creating ~/.gitconfig
+            if let [xdg_config, home_config] = &paths[..] {
+                if !home_config.exists() && xdg_config.exists() {
+                    paths.swap(0, 1);
+                }
+            }
+            if paths.is_empty() {
+                let message = "$HOME not set".to_owned();
+                return Err(crate::Error::Fatal(None, message));
+            }
+            Ok(paths)
+        }
+        ConfigLevel::Local => match git_dir {
+            Some(git_dir) => Ok(vec![git_dir.join("config")]),
+            None => {
+                let message = "--local can only be used inside a git repository".to_owned();
+                Err(crate::Error::Fatal(None, message))
+            }
+        },
+    }
+}
 
-    let mut user = UserConfig {
-        name: None,
-        email: None,
+/// Edit the content of a config file while holding the lock on it. A file that doesn't exist is
+/// created.
+fn edit_config_file<F>(path: &Path, edit: F) -> crate::Result<()>
+where
+    F: FnOnce(&str, &Path) -> crate::Result<String>,
+{
+    let mut lockfile = LockFile::acquire(&path.to_owned())?;
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error.into()),
     };
+    let edited_content = edit(&content, path)?;
+    lockfile.write(edited_content.as_bytes())?;
+    Ok(())
+}
+
+/// Set a variable in the content of a config file, keeping all other lines as they are. An
+/// existing variable is replaced in place, while a new one is added to the end of the last
+/// section it belongs to, or to a new section at the end of the file.
+fn set_value(content: &str, path: &Path, key: &str, value: &str) -> crate::Result<String> {
+    let (section, name) = split_key(key)?;
+    let config_lines =
+        scan_config(content).map_err(|line_number| bad_config_line(line_number, path))?;
+    let mut lines = content.lines().map(str::to_owned).collect::<Vec<_>>();
+    let entry = format!("\t{} = {}", name, format_value(value));
+
+    match find_entries(&config_lines, &section, name)[..] {
+        [existing] => {
+            let range = existing.first_line..existing.first_line + existing.num_lines;
+            lines.splice(range, [entry]);
+        }
+        [] => {
+            let last_section_line = config_lines.iter().rev().find(|line| match &line.kind {
+                LineKind::Section(line_section) => *line_section == section,
+                LineKind::Entry {
+                    section: line_section,
+                    ..
+                } => *line_section == section,
+                LineKind::Blank => false,
+            });
+            match last_section_line {
+                Some(line) => lines.insert(line.first_line + line.num_lines, entry),
+                None => {
+                    lines.push(format_section_header(&section));
+                    lines.push(entry);
+                }
+            }
+        }
+        _ => {
+            let message = format!(
+                "cannot overwrite multiple values of '{}' with a single value",
+                key
+            );
+            return Err(crate::Error::Fatal(None, message));
+        }
+    }
+
+    Ok(lines.iter().map(|line| format!("{}\n", line)).collect())
+}
+
+/// Remove a variable from the content of a config file, keeping all other lines as they are.
+fn unset_value(content: &str, path: &Path, key: &str) -> crate::Result<String> {
+    let (section, name) = split_key(key)?;
+    let config_lines =
+        scan_config(content).map_err(|line_number| bad_config_line(line_number, path))?;
+    let mut lines = content.lines().collect::<Vec<_>>();
 
-    if let Some(section) = conf.section(Some("user")) {
-        if let Some(name) = section.get("name") {
-            user.name = Some(name.to_string());
+    match find_entries(&config_lines, &section, name)[..] {
+        [existing] => {
+            lines.drain(existing.first_line..existing.first_line + existing.num_lines);
         }
-        if let Some(email) = section.get("email") {
-            user.email = Some(email.to_string());
+        [] => {
+            let message = format!("key '{}' is not set", key);
+            return Err(crate::Error::Fatal(None, message));
+        }
+        _ => {
+            let message = format!("key '{}' has multiple values", key);
+            return Err(crate::Error::Fatal(None, message));
+        }
+    }
+
+    Ok(lines.iter().map(|line| format!("{}\n", line)).collect())
+}
+
+fn find_entries<'a>(
+    config_lines: &'a [ConfigLine<'a>],
+    section: &str,
+    name: &str,
+) -> Vec<&'a ConfigLine<'a>> {
+    config_lines
+        .iter()
+        .filter(|line| match &line.kind {
+            LineKind::Entry {
+                section: line_section,
+                name: line_name,
+                ..
+            } => line_section == section && line_name.eq_ignore_ascii_case(name),
+            _ => false,
+        })
+        .collect()
+}
+
+/// Split a key into the canonical key prefix of its section and the variable name.
+fn split_key(key: &str) -> crate::Result<(String, &str)> {
+    let (section, name) = key.rsplit_once('.').ok_or_else(|| {
+        let message = format!("key does not contain a section: {}", key);
+        crate::Error::Fatal(None, message)
+    })?;
+    if section.is_empty() || !is_valid_name(name) {
+        let message = format!("invalid key: {}", key);
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let canonical_key = canonicalize_key(key);
+    let canonical_section = &canonical_key[..canonical_key.len() - name.len() - 1];
+    Ok((canonical_section.to_owned(), name))
+}
+
+fn format_section_header(section: &str) -> String {
+    match section.split_once('.') {
+        Some((name, subsection)) => format!(
+            "[{} \"{}\"]",
+            name,
+            subsection.replace('\\', "\\\\").replace('"', "\\\"")
+        ),
+        None => format!("[{}]", section),
+    }
+}
+
+/// Format a value such that it's parsed back as the same value, quoting and escaping as needed.
+fn format_value(value: &str) -> String {
+    let escaped_value = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    let needs_quotes = value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['#', ';']);
+    if needs_quotes {
+        format!("\"{}\"", escaped_value)
+    } else {
+        escaped_value
+    }
+}
+
+/// Read `init.defaultBranch` from the system and global config, if it's set.
+pub fn read_default_branch() -> Option<String> {
+    Config::load(None).ok()?.get_string("init.defaultBranch")
+}
+
+fn system_config_path() -> PathBuf {
+    env::var_os("GIT_CONFIG_SYSTEM")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/etc/gitconfig"))
+}
+
+/// The global config files in order of increasing precedence.
+fn global_config_paths() -> Vec<PathBuf> {
+    if let Some(global_config) = env::var_os("GIT_CONFIG_GLOBAL") {
+        return vec![PathBuf::from(global_config)];
+    }
+
+    let home_dir = env::var_os("HOME").map(PathBuf::from);
+    let xdg_config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir.as_ref().map(|home_dir| home_dir.join(".config")));
+
+    xdg_config_home
+        .map(|xdg_config_home| xdg_config_home.join("git/config"))
+        .into_iter()
+        .chain(home_dir.map(|home_dir| home_dir.join(".gitconfig")))
+        .collect()
+}
+
+/// Turn a key into its canonical form, in which the section and variable names are lowercase
+/// but the subsection keeps its case.
+fn canonicalize_key(key: &str) -> String {
+    match (key.find('.'), key.rfind('.')) {
+        (Some(first_dot), Some(last_dot)) if first_dot != last_dot => format!(
+            "{}{}{}",
+            key[..first_dot].to_lowercase(),
+            &key[first_dot..last_dot],
+            key[last_dot..].to_lowercase()
+        ),
+        _ => key.to_lowercase(),
+    }
+}
+
+/// Parse a config file into its entries. A file that doesn't exist has no entries.
+pub fn read_config_file(path: &Path, level: ConfigLevel) -> crate::Result<Vec<ConfigEntry>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+
+    parse_config(&content)
+        .map(|entries| {
+            entries
+                .into_iter()
+                .map(|(key, value)| ConfigEntry { key, value, level })
+                .collect()
+        })
+        .map_err(|line_number| bad_config_line(line_number, path))
+}
+
+fn bad_config_line(line_number: usize, path: &Path) -> crate::Error {
+    let message = format!("bad config line {} in file {}", line_number, path.display());
+    crate::Error::Fatal(None, message)
+}
+
+/// Parse the content of a config file into pairs of canonical keys and values. On failure, the
+/// 1-indexed number of the offending line is returned.
+fn parse_config(content: &str) -> Result<Vec<(String, Option<String>)>, usize> {
+    let mut entries = vec![];
+    for line in scan_config(content)? {
+        if let LineKind::Entry {
+            section,
+            name,
+            raw_value,
+        } = line.kind
+        {
+            let value = match raw_value {
+                Some(raw_value) => Some(parse_value(&raw_value).ok_or(line.first_line + 1)?),
+                None => None,
+            };
+            entries.push((format!("{}.{}", section, name.to_lowercase()), value));
+        }
+    }
+    Ok(entries)
+}
+
+/// A logical line of a config file, which may span several physical lines if a value is
+/// continued with a trailing backslash.
+struct ConfigLine<'a> {
+    /// 0-indexed number of the first physical line.
+    first_line: usize,
+    num_lines: usize,
+    kind: LineKind<'a>,
+}
+
+enum LineKind<'a> {
+    /// A section header, with the canonical key prefix of the section.
+    Section(String),
+    Entry {
+        section: String,
+        name: &'a str,
+        raw_value: Option<String>,
+    },
+    /// A blank line or a comment.
+    Blank,
+}
+
+/// Split the content of a config file into logical lines. On failure, the 1-indexed number of
+/// the offending line is returned.
+fn scan_config(content: &str) -> Result<Vec<ConfigLine<'_>>, usize> {
+    let mut config_lines = vec![];
+    let mut section: Option<String> = None;
+    let mut lines = content.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let trimmed = line.trim();
+        let mut num_lines = 1;
+
+        let kind = if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+            LineKind::Blank
+        } else if trimmed.starts_with('[') {
+            let header = parse_section_header(trimmed).ok_or(line_number)?;
+            section = Some(header.clone());
+            LineKind::Section(header)
+        } else {
+            let section = section.clone().ok_or(line_number)?;
+            let (name, raw_value) = match trimmed.split_once('=') {
+                Some((name, raw_value)) => (name.trim(), Some(raw_value.to_owned())),
+                None => (trimmed, None),
+            };
+            if !is_valid_name(name) {
+                return Err(line_number);
+            }
+
+            let raw_value = match raw_value {
+                Some(mut raw_value) => {
+                    // a trailing backslash continues the value on the next line
+                    while raw_value.ends_with('\\') && !raw_value.ends_with("\\\\") {
+                        raw_value.pop();
+                        let (_, next_line) = lines.next().ok_or(line_number)?;
+                        raw_value.push_str(next_line);
+                        num_lines += 1;
+                    }
+                    Some(raw_value)
+                }
+                None => None,
+            };
+            LineKind::Entry {
+                section,
+                name,
+                raw_value,
+            }
+        };
+
+        config_lines.push(ConfigLine {
+            first_line: index,
+            num_lines,
+            kind,
+        });
+    }
+
+    Ok(config_lines)
+}
+
+/// Parse a section header on the form `[section]`, `[section "subsection"]` or the deprecated
+/// `[section.subsection]` into the canonical key prefix. Anything after the header is ignored if
+/// it's a comment.
+fn parse_section_header(line: &str) -> Option<String> {
+    let end = line.find(']')?;
+    let rest = line[end + 1..].trim();
+    if !(rest.is_empty() || rest.starts_with('#') || rest.starts_with(';')) {
+        return None;
+    }
+    let header = &line[1..end];
+
+    match header.split_once(|c: char| c.is_whitespace()) {
+        Some((name, subsection)) => {
+            let subsection = subsection.trim();
+            let subsection = subsection.strip_prefix('"')?.strip_suffix('"')?;
+            let subsection = subsection.replace("\\\"", "\"").replace("\\\\", "\\");
+            is_valid_name(name).then(|| format!("{}.{}", name.to_lowercase(), subsection))
+        }
+        None => {
+            let is_valid = !header.is_empty()
+                && header
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+            is_valid.then(|| header.to_lowercase())
+        }
+    }
+}
+
+fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Parse a raw value by stripping comments and surrounding whitespace, removing quotes and
+/// resolving escape sequences. Whitespace inside quotes is kept.
+fn parse_value(raw_value: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut in_quotes = false;
+    // whitespace outside of quotes is only kept if it's followed by more of the value
+    let mut pending_whitespace = String::new();
+    let mut chars = raw_value.trim_start().chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                value.push_str(&pending_whitespace);
+                pending_whitespace.clear();
+                in_quotes = !in_quotes;
+            }
+            '#' | ';' if !in_quotes => break,
+            c if c.is_whitespace() && !in_quotes => pending_whitespace.push(c),
+            '\\' => {
+                value.push_str(&pending_whitespace);
+                pending_whitespace.clear();
+                let escaped = match chars.next()? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    '"' => '"',
+                    '\\' => '\\',
+                    _ => return None,
+                };
+                value.push(escaped);
+            }
+            c => {
+                value.push_str(&pending_whitespace);
+                pending_whitespace.clear();
+                value.push(c);
+            }
         }
     }
 
-    Ok(user)
+    (!in_quotes).then_some(value)
 }
@@ -0,0 +1,39 @@
+//! Counting the objects in the object database and the space they take up on disk.
+
+use crate::{output::OutputWriter, workspace::Repository};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Also report packs and garbage files, one statistic per line.
+    #[builder(default)]
+    pub verbose: bool,
+}
+
+pub fn count_objects(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let stats = repository.database.stats()?;
+    let kilobytes = |bytes: u64| bytes / 1024;
+
+    if options.verbose {
+        writer
+            .writeln(format!("count: {}", stats.loose_count))?
+            .writeln(format!("size: {}", kilobytes(stats.loose_size)))?
+            .writeln(format!("in-pack: {}", stats.packed_count))?
+            .writeln(format!("packs: {}", stats.pack_count))?
+            .writeln(format!("size-pack: {}", kilobytes(stats.pack_size)))?
+            .writeln(format!("prune-packable: {}", stats.prune_packable_count))?
+            .writeln(format!("garbage: {}", stats.garbage.len()))?
+            .writeln(format!("size-garbage: {}", kilobytes(stats.garbage_size)))?;
+    } else {
+        writer.writeln(format!(
+            "{} objects, {} kilobytes",
+            stats.loose_count,
+            kilobytes(stats.loose_size)
+        ))?;
+    }
+
+    Ok(())
+}
@@ -1,208 +1,894 @@
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::{Debug, Display},
-    fs, io,
-    path::Path,
+    fs,
+    hash::Hash,
+    io,
+    path::{Path, PathBuf},
 };
 
 use crate::{
-    index::{Index, IndexEntry},
-    object_resolver::ObjectResolver,
-    objects::{Blob, GitObject},
-    output::{Color, OutputWriter},
-    status,
-    workspace::Repository,
+    diff_algorithms, difftool,
+    index::{FileMode, Index, IndexEntry},
+    objects::{Blob, GitObject, ObjectId, Tree, TreeEntry},
+    output::{Color, OutputWriter, Serializer},
+    pathspec::Pathspec,
+    refs::Revision,
+    rename::{self, Rename},
+    status::{self, Change, ChangePlace, ChangeType},
+    workspace::{Database, Repository},
 };
 
-const MAX_DIFF_CONTEXT_LINES: usize = 3;
+/// The number of unchanged lines shown around each change by default.
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+const MAX_STAT_GRAPH_WIDTH: usize = 50;
 
 #[derive(Default, Builder, Debug)]
 pub struct Options {
     pub cached: bool,
+
+    /// Diff against the tree of this revision rather than against the index, or against HEAD if
+    /// `cached` is set.
+    #[builder(default)]
+    pub revision: Option<String>,
+
+    #[builder(default)]
+    pub output_format: OutputFormat,
+
+    /// The number of unchanged lines to show around each change.
+    #[builder(default = "DEFAULT_CONTEXT_LINES")]
+    pub context_lines: usize,
+
+    /// The algorithm to compute the diffs with. Defaults to the `diff.algorithm` config, or Myers
+    /// if it's not set.
+    #[builder(default)]
+    pub algorithm: Option<Algorithm>,
+
+    /// Only diff the paths selected by these pathspecs.
+    #[builder(default)]
+    pub pathspecs: Vec<String>,
+
+    /// The minimum similarity, in percent, for a deleted and a created file to be diffed as a
+    /// rename. Defaults to [`rename::DEFAULT_THRESHOLD`].
+    #[builder(default)]
+    pub rename_threshold: Option<u32>,
+
+    /// Don't write the diffs, only find out whether there are any.
+    #[builder(default)]
+    pub quiet: bool,
+
+    /// Run the program in `GIT_EXTERNAL_DIFF` or the `diff.external` config, if any, for each
+    /// changed file instead of writing patches.
+    #[builder(default)]
+    pub external: bool,
+
+    /// Terminate the paths written by the `NameOnly` and `NameStatus` formats with NUL instead of
+    /// a linefeed, and also separate the status from the paths with NUL.
+    #[builder(default)]
+    pub null_terminated: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub enum OutputFormat {
+    #[default]
+    Patch,
+    Stat,
+    /// Only the paths of the changed files.
+    NameOnly,
+    /// The paths of the changed files, each preceded by a letter that tells how it changed.
+    NameStatus,
+    /// The same changes as `NameStatus`, as one JSON object per line that also has the ids of
+    /// both sides.
+    NameStatusJson,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    /// The algorithm by Eugene Myers, which finds a minimal diff.
+    #[default]
+    Myers,
+    Patience,
+    Histogram,
+}
+
+impl Algorithm {
+    /// Read the algorithm from the `diff.algorithm` config, or use Myers if it's not set.
+    pub fn from_config(repository: &Repository) -> crate::Result<Algorithm> {
+        match repository.config()?.get_string("diff.algorithm") {
+            Some(name) => {
+                parse_algorithm(&name).map_err(|message| crate::Error::Fatal(None, message))
+            }
+            None => Ok(Algorithm::default()),
+        }
+    }
+}
+
+pub fn parse_algorithm(s: &str) -> Result<Algorithm, String> {
+    match s {
+        "myers" | "default" | "minimal" => Ok(Algorithm::Myers),
+        "patience" => Ok(Algorithm::Patience),
+        "histogram" => Ok(Algorithm::Histogram),
+        _ => Err(format!("invalid diff algorithm: {}", s)),
+    }
+}
+
+/// The content of a single file on both sides of a diff.
+pub struct FileDiff {
+    path: PathBuf,
+    a_oid: Option<String>,
+    a_lines: Vec<String>,
+    b_oid: Option<String>,
+    b_lines: Vec<String>,
+    /// The raw content of both sides, for external diff programs.
+    a_content: Option<Vec<u8>>,
+    b_content: Option<Vec<u8>>,
+    /// The rename that this diff is the result of, if any. The path of the diff is then the
+    /// destination of the rename.
+    rename: Option<Rename>,
+    /// The full ids of the blobs on both sides, where the `a_oid` and `b_oid` are abbreviated.
+    a_id: Option<ObjectId>,
+    b_id: Option<ObjectId>,
+}
+
+impl FileDiff {
+    fn from_blobs(
+        a_blob: Option<&Blob>,
+        b_blob: Option<&Blob>,
+        path: &Path,
+        database: &Database,
+    ) -> FileDiff {
+        let to_lines = |blob: Option<&Blob>| {
+            blob.map(|blob| {
+                String::from_utf8_lossy(blob.content())
+                    .lines()
+                    .map(|line| line.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+        };
+
+        FileDiff {
+            path: path.to_owned(),
+            a_oid: a_blob.map(|blob| database.abbreviate(blob.id())),
+            a_lines: to_lines(a_blob),
+            b_oid: b_blob.map(|blob| database.abbreviate(blob.id())),
+            b_lines: to_lines(b_blob),
+            a_content: a_blob.map(|blob| blob.content().to_vec()),
+            b_content: b_blob.map(|blob| blob.content().to_vec()),
+            rename: None,
+            a_id: a_blob.map(|blob| blob.id().clone()),
+            b_id: b_blob.map(|blob| blob.id().clone()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The abbreviated ids of the blobs on both sides, if the file exists on that side.
+    pub fn oids(&self) -> (Option<&str>, Option<&str>) {
+        (self.a_oid.as_deref(), self.b_oid.as_deref())
+    }
+
+    /// The content on both sides, if the file exists on that side.
+    pub fn contents(&self) -> (Option<&[u8]>, Option<&[u8]>) {
+        (self.a_content.as_deref(), self.b_content.as_deref())
+    }
+
+    /// The path as shown in a diffstat, which includes the source of a rename.
+    fn display_path(&self) -> String {
+        match &self.rename {
+            Some(rename) => format!("{} => {}", rename.from.display(), self.path.display()),
+            None => self.path.display().to_string(),
+        }
+    }
+
+    /// Count the lines inserted and deleted by this diff.
+    pub fn count_changes(&self, algorithm: Algorithm) -> (usize, usize) {
+        let a_lines = self.a_lines.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let b_lines = self.b_lines.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let edit_script = edit_script_with_algorithm(&a_lines, &b_lines, algorithm);
+        let chunks = chunk_edit_script(&edit_script, 0);
+
+        let edits = chunks.iter().flat_map(|chunk| chunk.edits.iter());
+        edits.fold((0, 0), |(insertions, deletions), edit| match edit.kind {
+            EditKind::Addition => (insertions + 1, deletions),
+            EditKind::Deletion => (insertions, deletions + 1),
+            EditKind::Equal => (insertions, deletions),
+        })
+    }
 }
 
+/// Diff the worktree or the index as described by the options, and return whether there were any
+/// differences.
 pub fn diff_repository(
     repository: &Repository,
     options: &Options,
     writer: &mut dyn OutputWriter,
-) -> crate::Result<()> {
-    if options.cached {
-        diff_repository_cached(repository, writer)
-    } else {
-        diff_repository_default(repository, writer)
+) -> crate::Result<bool> {
+    let file_diffs = resolve_file_diffs(repository, options)?;
+    let algorithm = match options.algorithm {
+        Some(algorithm) => algorithm,
+        None => Algorithm::from_config(repository)?,
+    };
+    let external_diff = match options.output_format {
+        OutputFormat::Patch if options.external => difftool::external_diff_program(repository)?,
+        _ => None,
+    };
+
+    if options.quiet {
+        return Ok(!file_diffs.is_empty());
+    }
+    match external_diff {
+        Some(program) => {
+            for file_diff in &file_diffs {
+                difftool::run_external_diff(&program, file_diff, repository, writer)?;
+            }
+        }
+        None => write_file_diffs(
+            &file_diffs,
+            options.output_format,
+            options.context_lines,
+            algorithm,
+            options.null_terminated,
+            writer,
+        )?,
     }
+    Ok(!file_diffs.is_empty())
 }
 
-fn diff_repository_cached(
+/// Diff the worktree or the index as described by the options, without writing anything.
+pub fn resolve_file_diffs(
     repository: &Repository,
+    options: &Options,
+) -> crate::Result<Vec<FileDiff>> {
+    let pathspec = Pathspec::parse(&options.pathspecs, repository.worktree())?;
+    let rename_threshold = options
+        .rename_threshold
+        .unwrap_or(rename::DEFAULT_THRESHOLD);
+    let revision_tree = options
+        .revision
+        .as_deref()
+        .map(|revision| -> crate::Result<Tree> {
+            let commit_id = Revision::parse(revision)?.resolve(repository)?;
+            repository.database.peel_to_tree(&commit_id)
+        })
+        .transpose()?;
+    let mut file_diffs = match (&revision_tree, options.cached) {
+        (None, true) => diff_repository_cached(None, rename_threshold, repository)?,
+        (Some(tree), true) => diff_repository_cached(Some(tree), rename_threshold, repository)?,
+        (Some(tree), false) => diff_worktree_against_tree(tree, repository)?,
+        (None, false) => diff_repository_default(repository)?,
+    };
+    file_diffs.retain(|file_diff| pathspec.matches(&file_diff.path));
+    Ok(file_diffs)
+}
+
+/// Write the file diffs in the given output format. Names are terminated by NUL if requested.
+pub fn write_file_diffs(
+    file_diffs: &[FileDiff],
+    output_format: OutputFormat,
+    context_lines: usize,
+    algorithm: Algorithm,
+    null_terminated: bool,
     writer: &mut dyn OutputWriter,
-) -> crate::Result<()> {
+) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Patch => write_patch(file_diffs, context_lines, algorithm, writer),
+        OutputFormat::Stat => write_stat(file_diffs, algorithm, writer),
+        OutputFormat::NameOnly => write_names(file_diffs, false, null_terminated, writer),
+        OutputFormat::NameStatus => write_names(file_diffs, true, null_terminated, writer),
+        OutputFormat::NameStatusJson => write_names_json(file_diffs, writer),
+    }
+}
+
+/// Diff the index against the given tree, or against the tree of HEAD if none is given.
+fn diff_repository_cached(
+    tree: Option<&Tree>,
+    rename_threshold: u32,
+    repository: &Repository,
+) -> crate::Result<Vec<FileDiff>> {
     let mut index = repository.load_index()?;
-    let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
-    let files_with_staged_changes = status::resolve_files_with_staged_changes(
-        &path_to_committed_id,
+    let committed_paths_and_ids = match tree {
+        Some(tree) => resolve_tree_paths_and_ids(Some(tree), repository)?,
+        None => status::resolve_committed_paths_and_ids(repository)?
+            .into_iter()
+            .collect(),
+    };
+    let staged_paths_and_ids = index
+        .as_mut()
+        .get_entries()
+        .into_iter()
+        .filter(|entry| entry.file_mode() != FileMode::Gitlink)
+        .map(|entry| (entry.path.clone(), entry.object_id.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    diff_paths_and_ids(
+        &committed_paths_and_ids,
+        &staged_paths_and_ids,
+        rename_threshold,
         repository,
-        index.as_mut(),
-    )?;
+    )
+}
 
-    let mut object_cache = ObjectResolver::from_head_commit(repository)?;
+/// Diff the tracked files in the worktree against the tree. Only files with unstaged changes are
+/// read from the worktree, as the content of the others is already in the index.
+fn diff_worktree_against_tree(
+    tree: &Tree,
+    repository: &Repository,
+) -> crate::Result<Vec<FileDiff>> {
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    let committed_paths_and_ids = resolve_tree_paths_and_ids(Some(tree), repository)?;
+    let unstaged_changes = status::resolve_unstaged_changes(repository, index)
+        .into_iter()
+        .map(|change| (change.path, change.change_type))
+        .collect::<HashMap<_, _>>();
+    let tracked_paths_and_ids = index
+        .get_entries()
+        .into_iter()
+        .filter(|entry| entry.file_mode() != FileMode::Gitlink)
+        .filter(|entry| !matches!(unstaged_changes.get(&entry.path), Some(ChangeType::Deleted)))
+        .map(|entry| (entry.path.clone(), entry.object_id.clone()))
+        .collect::<BTreeMap<_, _>>();
+    let all_paths = committed_paths_and_ids
+        .keys()
+        .chain(tracked_paths_and_ids.keys())
+        .collect::<BTreeSet<_>>();
+
+    let mut file_diffs = vec![];
+    for path in all_paths {
+        let committed_id = committed_paths_and_ids.get(path);
+        let worktree_blob = match tracked_paths_and_ids.get(path) {
+            Some(_) if unstaged_changes.contains_key(path) => {
+                let content = fs::read(repository.worktree().root().join(path))?;
+                let content = repository.filters()?.to_database(path, content);
+                Some(Blob::with_hash_algorithm(
+                    content,
+                    repository.hash_algorithm(),
+                ))
+            }
+            Some(indexed_id) if Some(indexed_id) == committed_id => continue,
+            Some(indexed_id) => Some(repository.database.load_blob(indexed_id)?),
+            None => None,
+        };
+        if committed_id == worktree_blob.as_ref().map(Blob::id) {
+            continue;
+        }
 
-    for file in files_with_staged_changes {
-        let relative_path = repository.worktree().relativize_path(file);
-        let staged_blob_id = &index.as_mut().get(&relative_path).unwrap().object_id;
-        let staged_blob = repository.database.load_blob(staged_blob_id)?;
-        let committed_blob = object_cache.find_blob_by_path(&relative_path).ok();
-        diff_blobs(
+        let committed_blob = committed_id
+            .map(|id| repository.database.load_blob(id))
+            .transpose()?;
+        file_diffs.push(FileDiff::from_blobs(
             committed_blob.as_ref(),
-            Some(&staged_blob),
-            &relative_path,
-            writer,
-        )?;
+            worktree_blob.as_ref(),
+            path,
+            &repository.database,
+        ));
     }
 
-    Ok(())
+    Ok(file_diffs)
+}
+
+fn diff_repository_default(repository: &Repository) -> crate::Result<Vec<FileDiff>> {
+    let mut index = repository.load_index()?;
+    let unstaged_changes = status::resolve_unstaged_changes(repository, index.as_mut());
+
+    unstaged_changes
+        .iter()
+        .map(|change| diff_unstaged_change(index.as_mut(), change, repository))
+        .collect()
 }
 
-fn diff_repository_default(
+/// Diff the trees of two revisions, and return whether there were any differences.
+pub fn diff_refs(
+    a_ref: &str,
+    b_ref: &str,
     repository: &Repository,
     writer: &mut dyn OutputWriter,
-) -> crate::Result<()> {
-    let mut index = repository.load_index()?;
-    let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
+) -> crate::Result<bool> {
+    let a_commit_id = Revision::parse(a_ref)?.resolve(repository)?;
+    let b_commit_id = Revision::parse(b_ref)?.resolve(repository)?;
+    let file_diffs = diff_commits(Some(&a_commit_id), &b_commit_id, repository)?;
+    let algorithm = Algorithm::from_config(repository)?;
+    write_patch(&file_diffs, DEFAULT_CONTEXT_LINES, algorithm, writer)?;
+    Ok(!file_diffs.is_empty())
+}
+
+/// Diff the tree of a commit against the tree of another commit, or against the empty tree if
+/// there is no other commit. Renames are detected with the default threshold.
+pub fn diff_commits(
+    a_commit_id: Option<&ObjectId>,
+    b_commit_id: &ObjectId,
+    repository: &Repository,
+) -> crate::Result<Vec<FileDiff>> {
+    let a_tree = a_commit_id
+        .map(|id| repository.database.peel_to_tree(id))
+        .transpose()?;
+    let b_tree = repository.database.peel_to_tree(b_commit_id)?;
+
+    let a_paths_and_ids = resolve_tree_paths_and_ids(a_tree.as_ref(), repository)?;
+    let b_paths_and_ids = resolve_tree_paths_and_ids(Some(&b_tree), repository)?;
+
+    diff_paths_and_ids(
+        &a_paths_and_ids,
+        &b_paths_and_ids,
+        rename::DEFAULT_THRESHOLD,
+        repository,
+    )
+}
+
+/// Diff the files of two snapshots given as paths and blob ids. Deleted and created files that
+/// are similar enough are diffed against each other as renames.
+fn diff_paths_and_ids(
+    a_paths_and_ids: &BTreeMap<PathBuf, ObjectId>,
+    b_paths_and_ids: &BTreeMap<PathBuf, ObjectId>,
+    rename_threshold: u32,
+    repository: &Repository,
+) -> crate::Result<Vec<FileDiff>> {
+    let changes = compare_paths_and_ids(a_paths_and_ids, b_paths_and_ids);
+    let mut deleted = vec![];
+    let mut created = vec![];
+    for change in &changes {
+        match change.change_type {
+            ChangeType::Deleted => {
+                deleted.push((change.path.as_path(), &a_paths_and_ids[&change.path]))
+            }
+            ChangeType::Created => {
+                created.push((change.path.as_path(), &b_paths_and_ids[&change.path]))
+            }
+            _ => (),
+        }
+    }
+    let renames = rename::detect_renames(&deleted, &created, rename_threshold, repository)?;
+    let sources = renames
+        .iter()
+        .map(|rename| rename.from.as_path())
+        .collect::<HashSet<_>>();
+    let destination_to_rename = renames
+        .iter()
+        .map(|rename| (rename.to.as_path(), rename))
+        .collect::<HashMap<_, _>>();
+
+    let mut file_diffs = vec![];
+    for change in &changes {
+        if sources.contains(change.path.as_path()) {
+            continue;
+        }
+        let rename = destination_to_rename.get(change.path.as_path());
+        let a_path = rename.map_or(&change.path, |rename| &rename.from);
+        let a_blob = a_paths_and_ids
+            .get(a_path)
+            .map(|id| repository.database.load_blob(id))
+            .transpose()?;
+        let b_blob = b_paths_and_ids
+            .get(&change.path)
+            .map(|id| repository.database.load_blob(id))
+            .transpose()?;
+        let mut file_diff = FileDiff::from_blobs(
+            a_blob.as_ref(),
+            b_blob.as_ref(),
+            &change.path,
+            &repository.database,
+        );
+        file_diff.rename = rename.map(|rename| (*rename).clone());
+        file_diffs.push(file_diff);
+    }
+
+    Ok(file_diffs)
+}
 
-    let tracked_paths =
-        status::resolve_tracked_paths(&path_to_committed_id, repository.worktree(), index.as_mut());
-    let mut unstaged_changes =
-        status::resolve_unstaged_changes(&tracked_paths, repository, index.as_mut());
-    unstaged_changes.sort_by(|a, b| a.path.cmp(&b.path));
+/// Compare two trees recursively and return the paths of all files that differ between them,
+/// sorted by path. A missing tree is treated as empty. Subtrees with the same id in both trees
+/// are skipped without being loaded.
+pub fn compare_trees(
+    a_tree: Option<&Tree>,
+    b_tree: Option<&Tree>,
+    repository: &Repository,
+) -> crate::Result<Vec<Change>> {
+    let mut changes = vec![];
+    compare_tree_entries(a_tree, b_tree, Path::new(""), repository, &mut changes)?;
+    Ok(changes)
+}
 
-    for change in unstaged_changes {
-        diff_unstaged_change(index.as_mut(), &change, repository, writer)?;
+/// Walk the entries of both trees in name order, pairing up the entries with the same name.
+fn compare_tree_entries(
+    a_tree: Option<&Tree>,
+    b_tree: Option<&Tree>,
+    directory: &Path,
+    repository: &Repository,
+    changes: &mut Vec<Change>,
+) -> crate::Result<()> {
+    let mut a_entries = sorted_tree_entries(a_tree).into_iter().peekable();
+    let mut b_entries = sorted_tree_entries(b_tree).into_iter().peekable();
+
+    loop {
+        let ordering = match (a_entries.peek(), b_entries.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a_entry), Some(b_entry)) => a_entry.name.cmp(&b_entry.name),
+        };
+        let a_entry = a_entries.next_if(|_| ordering.is_le());
+        let b_entry = b_entries.next_if(|_| ordering.is_ge());
+        let name = a_entry.or(b_entry).map(|entry| &entry.name).unwrap();
+        compare_tree_entry(a_entry, b_entry, &directory.join(name), repository, changes)?;
     }
 
     Ok(())
 }
 
-fn diff_unstaged_change(
-    index: &mut Index,
-    change: &status::Change,
+/// The entries of the tree other than submodules, sorted by name.
+fn sorted_tree_entries(tree: Option<&Tree>) -> Vec<&TreeEntry> {
+    let mut entries = tree
+        .map_or(&[][..], Tree::entries)
+        .iter()
+        .filter(|entry| entry.mode != FileMode::Gitlink)
+        .collect::<Vec<_>>();
+    entries.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+    entries
+}
+
+/// Compare the entries with the same path in both trees, either of which may be missing. A file
+/// that is replaced by a directory is deleted, and the files in the directory are created.
+fn compare_tree_entry(
+    a_entry: Option<&TreeEntry>,
+    b_entry: Option<&TreeEntry>,
+    path: &Path,
     repository: &Repository,
-    writer: &mut dyn OutputWriter,
+    changes: &mut Vec<Change>,
 ) -> crate::Result<()> {
-    let a_index_entry = index.get(&change.path).unwrap();
-    let (a_lines, a_oid) = read_blob_from_index_entry(a_index_entry, repository)?;
-    let a_lines_ref = a_lines.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    if let (Some(a_entry), Some(b_entry)) = (a_entry, b_entry) {
+        if a_entry.object_id == b_entry.object_id && a_entry.mode == b_entry.mode {
+            return Ok(());
+        }
+    }
 
-    let (b_lines, b_oid) = read_blob_from_worktree(change, repository)?;
-    let b_lines_ref = b_lines.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+    let is_tree = |entry: &&TreeEntry| entry.mode == FileMode::Directory;
+    let a_file = a_entry.filter(|entry| !is_tree(entry));
+    let b_file = b_entry.filter(|entry| !is_tree(entry));
+    let change_type = match (a_file, b_file) {
+        (Some(a_file), Some(b_file)) if a_file.object_id != b_file.object_id => {
+            Some(ChangeType::Modified)
+        }
+        (Some(_), None) => Some(ChangeType::Deleted),
+        (None, Some(_)) => Some(ChangeType::Created),
+        _ => None,
+    };
+    if let Some(change_type) = change_type {
+        changes.push(Change {
+            path: path.to_owned(),
+            change_type,
+            changed_in: ChangePlace::Index,
+        });
+    }
 
-    diff_content(
-        &change.path,
-        &a_lines_ref,
-        a_oid,
-        &b_lines_ref,
-        b_oid,
-        writer,
-    )?;
+    let load_tree = |entry: Option<&TreeEntry>| {
+        entry
+            .filter(is_tree)
+            .map(|entry| repository.database.load_tree(&entry.object_id))
+            .transpose()
+    };
+    let a_tree = load_tree(a_entry)?;
+    let b_tree = load_tree(b_entry)?;
+    if a_tree.is_some() || b_tree.is_some() {
+        compare_tree_entries(a_tree.as_ref(), b_tree.as_ref(), path, repository, changes)?;
+    }
 
     Ok(())
 }
 
+fn resolve_tree_paths_and_ids(
+    tree: Option<&Tree>,
+    repository: &Repository,
+) -> crate::Result<BTreeMap<PathBuf, ObjectId>> {
+    let mut entries = vec![];
+    if let Some(tree) = tree {
+        repository
+            .database
+            .extract_entries_from_tree(String::new(), tree, &mut entries)?;
+    }
+
+    Ok(entries
+        .into_iter()
+        .filter(|(_, entry)| entry.mode != FileMode::Gitlink)
+        .map(|(path, entry)| (PathBuf::from(path), entry.object_id))
+        .collect())
+}
+
+/// Compare the paths and ids of two snapshots by walking both in path order.
+fn compare_paths_and_ids(
+    a_paths_and_ids: &BTreeMap<PathBuf, ObjectId>,
+    b_paths_and_ids: &BTreeMap<PathBuf, ObjectId>,
+) -> Vec<Change> {
+    let mut a_entries = a_paths_and_ids.iter().peekable();
+    let mut b_entries = b_paths_and_ids.iter().peekable();
+    let mut changes = vec![];
+
+    loop {
+        let ordering = match (a_entries.peek(), b_entries.peek()) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((a_path, _)), Some((b_path, _))) => a_path.cmp(b_path),
+        };
+        let a_entry = a_entries.next_if(|_| ordering.is_le());
+        let b_entry = b_entries.next_if(|_| ordering.is_ge());
+        let (path, change_type) = match (a_entry, b_entry) {
+            (Some((path, a_id)), Some((_, b_id))) if a_id != b_id => (path, ChangeType::Modified),
+            (Some((path, _)), None) => (path, ChangeType::Deleted),
+            (None, Some((path, _))) => (path, ChangeType::Created),
+            _ => continue,
+        };
+        changes.push(Change {
+            path: path.to_owned(),
+            change_type,
+            changed_in: ChangePlace::Index,
+        });
+    }
+
+    changes
+}
+
+fn diff_unstaged_change(
+    index: &mut Index,
+    change: &status::Change,
+    repository: &Repository,
+) -> crate::Result<FileDiff> {
+    let a_index_entry = index.get(&change.path).unwrap();
+    let (a_lines, a_id) = read_blob_from_index_entry(a_index_entry, repository)?;
+    let (b_lines, b_id) = read_blob_from_worktree(change, repository)?;
+
+    // the lines are split on every linefeed, so joining them gives back the content
+    let a_content = a_id.as_ref().map(|_| a_lines.join("\n").into_bytes());
+    let b_content = b_id.as_ref().map(|_| b_lines.join("\n").into_bytes());
+    let abbreviate = |id: &ObjectId| repository.database.abbreviate(id);
+    Ok(FileDiff {
+        path: change.path.clone(),
+        a_oid: a_id.as_ref().map(abbreviate),
+        a_lines,
+        b_oid: b_id.as_ref().map(abbreviate),
+        b_lines,
+        a_content,
+        b_content,
+        rename: None,
+        a_id,
+        b_id,
+    })
+}
+
 fn read_blob_from_index_entry(
     index_entry: &IndexEntry,
     repository: &Repository,
-) -> crate::Result<(Vec<String>, Option<String>)> {
+) -> crate::Result<(Vec<String>, Option<ObjectId>)> {
     let blob = repository.database.load_blob(&index_entry.object_id)?;
     let content = String::from_utf8(blob.content().to_vec()).ok().unwrap();
     let lines: Vec<String> = content.split('\n').map(|s| s.to_owned()).collect();
-    let object_id = Some(index_entry.object_id.to_short_string());
-    Ok((lines, object_id))
+    Ok((lines, Some(index_entry.object_id.clone())))
 }
 
 fn read_blob_from_worktree(
     change: &status::Change,
     repository: &Repository,
-) -> crate::Result<(Vec<String>, Option<String>)> {
-    let (b_lines, b_oid) = match change.change_type {
+) -> crate::Result<(Vec<String>, Option<ObjectId>)> {
+    let (b_lines, b_id) = match change.change_type {
         status::ChangeType::Deleted => (vec![], None),
         _ => {
             let b_raw = fs::read(repository.worktree().root().join(&change.path))?;
+            let b_raw = repository.filters()?.to_database(&change.path, b_raw);
             let b = String::from_utf8(b_raw.clone()).unwrap();
-            let b_blob = Blob::new(b_raw);
+            let b_blob = Blob::with_hash_algorithm(b_raw, repository.hash_algorithm());
             let b_lines = b.split('\n').map(|s| s.to_owned()).collect::<Vec<String>>();
-            let b_oid = Some(b_blob.short_id_as_string());
-            (b_lines, b_oid)
+            (b_lines, Some(b_blob.id().clone()))
         }
     };
-    Ok((b_lines, b_oid))
+    Ok((b_lines, b_id))
 }
 
-fn diff_blobs(
-    committed_blob: Option<&Blob>,
-    staged_blob: Option<&Blob>,
-    relative_path: &Path,
+/// Write the file diffs as unified diffs with the given number of context lines.
+pub fn write_patch(
+    file_diffs: &[FileDiff],
+    context_lines: usize,
+    algorithm: Algorithm,
     writer: &mut dyn OutputWriter,
-) -> crate::Result<()> {
-    let empty_string = || "".to_string();
-    let committed_content = committed_blob
-        .and_then(|blob| String::from_utf8(blob.content().to_vec()).ok())
-        .unwrap_or_else(empty_string);
-    let staged_content = staged_blob
-        .and_then(|blob| String::from_utf8(blob.content().to_vec()).ok())
-        .unwrap_or_else(empty_string);
+) -> io::Result<()> {
+    for file_diff in file_diffs {
+        let a_lines = file_diff
+            .a_lines
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>();
+        let b_lines = file_diff
+            .b_lines
+            .iter()
+            .map(|s| s.as_str())
+            .collect::<Vec<_>>();
+        let edit_script = edit_script_with_algorithm(&a_lines, &b_lines, algorithm);
+        let chunks = chunk_edit_script(&edit_script, context_lines);
+
+        match &file_diff.rename {
+            Some(rename) => write_rename_header(
+                rename,
+                file_diff.a_oid.clone(),
+                file_diff.b_oid.clone(),
+                writer,
+            )?,
+            None => write_header(
+                &file_diff.path,
+                file_diff.a_oid.clone(),
+                file_diff.b_oid.clone(),
+                writer,
+            )?,
+        };
+        write_chunks(&chunks, writer)?;
+    }
 
-    let committed_lines = committed_content.lines().collect::<Vec<_>>();
-    let staged_lines = staged_content.lines().collect::<Vec<_>>();
+    Ok(())
+}
 
-    let edit_script = edit_script(&committed_lines, &staged_lines);
-    let chunks = chunk_edit_script(&edit_script, MAX_DIFF_CONTEXT_LINES);
+/// Write the path of each changed file, optionally preceded by its status letter and a tab. The
+/// status of a rename also includes the similarity and the source path.
+fn write_names(
+    file_diffs: &[FileDiff],
+    with_status: bool,
+    null_terminated: bool,
+    writer: &mut dyn OutputWriter,
+) -> io::Result<()> {
+    let (separator, terminator) = if null_terminated {
+        ('\0', '\0')
+    } else {
+        ('\t', '\n')
+    };
+    for file_diff in file_diffs {
+        let path = file_diff.path.display();
+        if !with_status {
+            writer.write(format!("{}{}", path, terminator))?;
+            continue;
+        }
 
-    write_header(
-        relative_path,
-        committed_blob.map(|blob| blob.short_id_as_string()),
-        staged_blob.map(|blob| blob.short_id_as_string()),
-        writer,
-    )?;
+        let line = match (&file_diff.rename, &file_diff.a_oid, &file_diff.b_oid) {
+            (Some(rename), _, _) => format!(
+                "R{:03}{}{}{}{}",
+                rename.similarity,
+                separator,
+                rename.from.display(),
+                separator,
+                path
+            ),
+            (None, None, _) => format!("A{}{}", separator, path),
+            (None, _, None) => format!("D{}{}", separator, path),
+            (None, _, _) => format!("M{}{}", separator, path),
+        };
+        writer.write(format!("{}{}", line, terminator))?;
+    }
 
-    write_chunks(&chunks, writer)?;
+    Ok(())
+}
 
+fn write_names_json(file_diffs: &[FileDiff], writer: &mut dyn OutputWriter) -> io::Result<()> {
+    let mut serializer = Serializer::new(writer);
+    for file_diff in file_diffs {
+        serializer.field("path", file_diff.path.to_string_lossy().into_owned());
+        match (&file_diff.rename, &file_diff.a_oid, &file_diff.b_oid) {
+            (Some(rename), _, _) => serializer
+                .field("status", "renamed")
+                .field("from", rename.from.to_string_lossy().into_owned())
+                .field("similarity", rename.similarity),
+            (None, None, _) => serializer.field("status", "added"),
+            (None, _, None) => serializer.field("status", "deleted"),
+            (None, _, _) => serializer.field("status", "modified"),
+        };
+        serializer
+            .field("old_id", file_diff.a_id.as_ref().map(ObjectId::to_string))
+            .field("new_id", file_diff.b_id.as_ref().map(ObjectId::to_string))
+            .write()?;
+    }
     Ok(())
 }
 
-fn diff_content(
-    relative_path: &Path,
-    a_lines: &[&str],
-    a_oid: Option<String>,
-    b_lines: &[&str],
-    b_oid: Option<String>,
+/// Write a summary of the amount of inserted and deleted lines per file, followed by the total.
+pub fn write_stat(
+    file_diffs: &[FileDiff],
+    algorithm: Algorithm,
     writer: &mut dyn OutputWriter,
-) -> crate::Result<()> {
-    let edit_script = edit_script(a_lines, b_lines);
-    let chunks = chunk_edit_script(&edit_script, MAX_DIFF_CONTEXT_LINES);
+) -> io::Result<()> {
+    if file_diffs.is_empty() {
+        return Ok(());
+    }
+
+    let counts = file_diffs
+        .iter()
+        .map(|file_diff| file_diff.count_changes(algorithm))
+        .collect::<Vec<_>>();
+    let path_width = file_diffs
+        .iter()
+        .map(|file_diff| file_diff.display_path().chars().count())
+        .max()
+        .unwrap_or(0);
+    let max_changes = counts
+        .iter()
+        .map(|(insertions, deletions)| insertions + deletions)
+        .max()
+        .unwrap_or(0);
+    let count_width = max_changes.to_string().len();
+    let scale = |num_lines: usize| {
+        if max_changes <= MAX_STAT_GRAPH_WIDTH || num_lines == 0 {
+            num_lines
+        } else {
+            (num_lines * MAX_STAT_GRAPH_WIDTH / max_changes).max(1)
+        }
+    };
+
+    for (file_diff, (insertions, deletions)) in file_diffs.iter().zip(counts.iter()) {
+        writer.write(format!(
+            " {:<path_width$} | {:>count_width$} ",
+            file_diff.display_path(),
+            insertions + deletions
+        ))?;
+        writer
+            .set_color(Color::Green)?
+            .write("+".repeat(scale(*insertions)))?
+            .set_color(Color::Red)?
+            .write("-".repeat(scale(*deletions)))?
+            .reset_formatting()?
+            .linefeed()?;
+    }
 
-    write_header(relative_path, a_oid, b_oid, writer)?;
-    write_chunks(&chunks, writer)?;
+    let (total_insertions, total_deletions) = counts
+        .iter()
+        .fold((0, 0), |(total_ins, total_del), (ins, del)| {
+            (total_ins + ins, total_del + del)
+        });
+    let pluralize = |count: usize, singular: &str, plural: &str| {
+        format!("{} {}", count, if count == 1 { singular } else { plural })
+    };
+
+    let mut summary = format!(
+        " {}",
+        pluralize(file_diffs.len(), "file changed", "files changed")
+    );
+    if total_insertions > 0 || total_deletions == 0 {
+        summary.push_str(&format!(
+            ", {}",
+            pluralize(total_insertions, "insertion(+)", "insertions(+)")
+        ));
+    }
+    if total_deletions > 0 || total_insertions == 0 {
+        summary.push_str(&format!(
+            ", {}",
+            pluralize(total_deletions, "deletion(-)", "deletions(-)")
+        ));
+    }
+    writer.writeln(summary)?;
 
     Ok(())
 }
 
 fn write_chunks(chunks: &Vec<Chunk<&str>>, writer: &mut dyn OutputWriter) -> io::Result<()> {
     for chunk in chunks {
-        write_chunk_header(chunk, writer)?;
-        for edit in &chunk.edits {
-            match edit.kind {
-                EditKind::Equal => {
-                    writer.writeln(format!(" {}", edit.content))?;
-                }
-                EditKind::Deletion => {
-                    writer.set_color(Color::Red)?;
-                    writer.writeln(format!("-{}", edit.content))?;
-                    writer.reset_formatting()?;
-                }
-                EditKind::Addition => {
-                    writer.set_color(Color::Green)?;
-                    writer.writeln(format!("+{}", edit.content))?;
-                    writer.reset_formatting()?;
-                }
+        write_chunk(chunk, writer)?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn write_chunk(chunk: &Chunk<&str>, writer: &mut dyn OutputWriter) -> io::Result<()> {
+    write_chunk_header(chunk, writer)?;
+    for edit in &chunk.edits {
+        match edit.kind {
+            EditKind::Equal => {
+                writer.writeln(format!(" {}", edit.content))?;
+            }
+            EditKind::Deletion => {
+                writer.set_color(Color::Red)?;
+                writer.writeln(format!("-{}", edit.content))?;
+                writer.reset_formatting()?;
+            }
+            EditKind::Addition => {
+                writer.set_color(Color::Green)?;
+                writer.writeln(format!("+{}", edit.content))?;
+                writer.reset_formatting()?;
             }
         }
     }
@@ -214,25 +900,21 @@ fn write_chunk_header<'a, S: Eq>(
     chunk: &Chunk<S>,
     writer: &'a mut dyn OutputWriter,
 ) -> io::Result<&'a mut dyn OutputWriter> {
-    let a_size = chunk.a_end - chunk.a_start;
-    let b_size = chunk.b_end - chunk.b_start;
+    // an empty range is given by the line before it, and a range of one line by that line alone
+    let format_range = |offset: usize, count: usize| match count {
+        0 => format!("{},0", offset),
+        1 => format!("{}", offset + 1),
+        _ => format!("{},{}", offset + 1, count),
+    };
+    let (a_count, b_count) = chunk.line_counts();
 
     writer
         .set_color(Color::Cyan)?
-        .write(String::from("@@"))?
-        .write(format!(" -{}", chunk.a_start))?
-        .write(if a_size != 1 {
-            format!(",{} ", a_size)
-        } else {
-            String::from(" ")
-        })?
-        .write(format!("+{}", chunk.b_start))?
-        .write(if b_size != 1 {
-            format!(",{} ", b_size)
-        } else {
-            String::from(" ")
-        })?
-        .write(String::from("@@"))?
+        .write(format!(
+            "@@ -{} +{} @@",
+            format_range(chunk.a_offset, a_count),
+            format_range(chunk.b_offset, b_count)
+        ))?
         .reset_formatting()?;
 
     writer.linefeed()?;
@@ -240,7 +922,7 @@ fn write_chunk_header<'a, S: Eq>(
     Ok(writer)
 }
 
-fn write_header<'a>(
+pub(crate) fn write_header<'a>(
     path: &Path,
     a_oid: Option<String>,
     b_oid: Option<String>,
@@ -270,128 +952,180 @@ fn write_header<'a>(
         .writeln(format!("+++ {}", b_path))
 }
 
+/// Write the header of a renamed file. The index line and the file names are only written if the
+/// content was changed along with the path.
+fn write_rename_header<'a>(
+    rename: &Rename,
+    a_oid: Option<String>,
+    b_oid: Option<String>,
+    writer: &'a mut dyn OutputWriter,
+) -> io::Result<&'a mut dyn OutputWriter> {
+    writer
+        .writeln(format!(
+            "diff --git a/{} b/{}",
+            rename.from.display(),
+            rename.to.display()
+        ))?
+        .writeln(format!("similarity index {}%", rename.similarity))?
+        .writeln(format!("rename from {}", rename.from.display()))?
+        .writeln(format!("rename to {}", rename.to.display()))?;
+
+    if a_oid == b_oid {
+        return Ok(writer);
+    }
+    writer
+        .writeln(format!(
+            "index {}..{}",
+            a_oid.unwrap_or_default(),
+            b_oid.unwrap_or_default()
+        ))?
+        .writeln(format!("--- a/{}", rename.from.display()))?
+        .writeln(format!("+++ b/{}", rename.to.display()))
+}
+
+/// A group of nearby edits in an edit script, along with the surrounding context lines.
 #[derive(Debug, PartialEq, Eq)]
-struct Chunk<'a, S: Eq> {
+pub(crate) struct Chunk<'a, S: Eq> {
     edits: Vec<&'a Edit<S>>,
-    a_start: usize,
-    a_end: usize,
-    b_start: usize,
-    b_end: usize,
+    /// The number of lines of the a side that precede the chunk.
+    a_offset: usize,
+    /// The number of lines of the b side that precede the chunk.
+    b_offset: usize,
 }
 
 impl<'a, S: Eq> Chunk<'a, S> {
-    fn new(edits: Vec<&'a Edit<S>>) -> Self {
-        let mut a_start = None;
-        let mut a_end = None;
-        let mut b_start = None;
-        let mut b_end = None;
+    fn new(edits: Vec<&'a Edit<S>>, a_offset: usize, b_offset: usize) -> Self {
+        Chunk {
+            edits,
+            a_offset,
+            b_offset,
+        }
+    }
 
-        for edit in edits.iter() {
-            match edit.kind {
-                EditKind::Equal => {
-                    if a_start.is_none() {
-                        a_start = edit.a_position;
-                    }
-                    if b_start.is_none() {
-                        b_start = edit.b_position;
-                    }
-                    a_end = edit.a_position;
-                    b_end = edit.b_position;
-                }
-                EditKind::Deletion => {
-                    if a_start.is_none() {
-                        a_start = edit.a_position;
-                    }
-                    a_end = edit.a_position;
-                }
-                EditKind::Addition => {
-                    if b_start.is_none() {
-                        b_start = edit.b_position;
-                    }
-                    b_end = edit.b_position;
-                }
+    /// Count the lines of the a side and the b side that the chunk spans.
+    fn line_counts(&self) -> (usize, usize) {
+        count_lines(&self.edits)
+    }
+
+    /// Split the chunk at the context lines between its groups of changes. Each resulting chunk
+    /// holds a single group of changes along with all context around it, so context between two
+    /// groups ends up in both chunks.
+    pub(crate) fn split(&self) -> Vec<Chunk<'a, S>> {
+        let mut change_groups = vec![];
+        let mut i = 0;
+        while i < self.edits.len() {
+            if matches!(self.edits[i].kind, EditKind::Equal) {
+                i += 1;
+                continue;
+            }
+            let group_start = i;
+            while i < self.edits.len() && !matches!(self.edits[i].kind, EditKind::Equal) {
+                i += 1;
             }
+            change_groups.push((group_start, i));
         }
 
-        Chunk {
-            edits,
-            // Note: Add 1 to make 1-indexed
-            a_start: a_start.map(|x| x + 1).unwrap_or(0),
-            // Note: Add 1 to make 1-indexed and another 1 to make range exclusive in end
-            a_end: a_end.map(|x| x + 2).unwrap_or(0),
-            b_start: b_start.map(|x| x + 1).unwrap_or(0),
-            b_end: b_end.map(|x| x + 2).unwrap_or(0),
-        }
+        (0..change_groups.len())
+            .map(|group| {
+                let context_start = match group {
+                    0 => 0,
+                    _ => change_groups[group - 1].1,
+                };
+                let context_end = change_groups
+                    .get(group + 1)
+                    .map(|(next_group_start, _)| *next_group_start)
+                    .unwrap_or(self.edits.len());
+                let (a_lines_before, b_lines_before) = count_lines(&self.edits[..context_start]);
+                Chunk::new(
+                    self.edits[context_start..context_end].to_vec(),
+                    self.a_offset + a_lines_before,
+                    self.b_offset + b_lines_before,
+                )
+            })
+            .collect()
     }
 }
 
-fn chunk_edit_script<'a>(
+/// Apply the changes of the selected chunks of the edit script to its original sequence, leaving
+/// all other changes out.
+pub(crate) fn apply_chunks<'a>(
+    edit_script: &[Edit<&'a str>],
+    selected_chunks: &[Chunk<&str>],
+) -> Vec<&'a str> {
+    let selected_edits = selected_chunks
+        .iter()
+        .flat_map(|chunk| chunk.edits.iter())
+        .filter(|edit| !matches!(edit.kind, EditKind::Equal))
+        .map(|edit| (edit.a_position, edit.b_position))
+        .collect::<HashSet<_>>();
+
+    edit_script
+        .iter()
+        .filter(|edit| {
+            let is_selected = || selected_edits.contains(&(edit.a_position, edit.b_position));
+            match edit.kind {
+                EditKind::Equal => true,
+                EditKind::Deletion => !is_selected(),
+                EditKind::Addition => is_selected(),
+            }
+        })
+        .map(|edit| edit.content)
+        .collect()
+}
+
+/// Group the changes of the edit script into chunks, each surrounded by up to the given number of
+/// unchanged lines. Changes that are separated by at most twice that many unchanged lines end up
+/// in the same chunk, so that no line is shown twice.
+pub(crate) fn chunk_edit_script<'a>(
     edit_script: &'a [Edit<&'a str>],
     context_size: usize,
 ) -> Vec<Chunk<'a, &'a str>> {
-    let mut chunks = vec![];
-    let mut chunk_content: Vec<&Edit<&str>> = vec![];
-    let mut context: Vec<&Edit<&str>> = vec![];
-
-    let mut last_mutating_edit_idx = 0;
-
-    for (i, edit) in edit_script.iter().enumerate() {
-        match edit.kind {
-            EditKind::Equal => {
-                if i - last_mutating_edit_idx > context_size && !chunk_content.is_empty() {
-                    chunk_content.append(&mut context);
-                    chunk_content.append(&mut context);
-                    chunks.push(Chunk::new(chunk_content));
-                    chunk_content = vec![];
-                }
-
-                if should_show(edit, i, edit_script.len()) {
-                    context.push(edit);
-                }
-            }
-            EditKind::Deletion => {
-                last_mutating_edit_idx = i;
-                drain_context_into_chunk(&mut context, &mut chunk_content, context_size);
+    // content split on newlines ends with an empty line if it ends with a newline
+    let shown_length = match edit_script.last() {
+        Some(edit) if edit.content.is_empty() && !matches!(edit.kind, EditKind::Addition) => {
+            edit_script.len() - 1
+        }
+        _ => edit_script.len(),
+    };
+    let edits = edit_script[..shown_length].iter().collect::<Vec<_>>();
+    let change_positions = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit.kind, EditKind::Equal))
+        .map(|(position, _)| position)
+        .collect::<Vec<_>>();
 
-                if should_show(edit, i, edit_script.len()) {
-                    chunk_content.push(edit);
-                }
-            }
-            EditKind::Addition => {
-                last_mutating_edit_idx = i;
-                drain_context_into_chunk(&mut context, &mut chunk_content, context_size);
-                chunk_content.push(edit);
-            }
+    let mut chunks = vec![];
+    let mut i = 0;
+    while i < change_positions.len() {
+        let first_change = change_positions[i];
+        let mut last_change = first_change;
+        while i + 1 < change_positions.len()
+            && change_positions[i + 1] - last_change - 1 <= 2 * context_size
+        {
+            i += 1;
+            last_change = change_positions[i];
         }
-    }
+        i += 1;
 
-    if !chunk_content.is_empty() {
-        drain_context_into_chunk(&mut context, &mut chunk_content, context_size);
-        chunks.push(Chunk::new(chunk_content));
+        let start = first_change.saturating_sub(context_size);
+        let end = (last_change + 1 + context_size).min(edits.len());
+        let (a_offset, b_offset) = count_lines(&edits[..start]);
+        chunks.push(Chunk::new(edits[start..end].to_vec(), a_offset, b_offset));
     }
 
     chunks
 }
 
-fn should_show(edit: &Edit<&str>, position: usize, edit_script_size: usize) -> bool {
-    if position < edit_script_size - 1 {
-        true
-    } else {
-        !edit.content.is_empty()
-    }
-}
-
-fn drain_context_into_chunk<'b, 'a: 'b, S: Eq>(
-    context: &mut Vec<&'a Edit<S>>,
-    chunk_content: &mut Vec<&'b Edit<S>>,
-    context_size: usize,
-) {
-    let context_to_skip = if context.len() > context_size {
-        context.len() - context_size
-    } else {
-        0
-    };
-    chunk_content.extend(context.drain(..).skip(context_to_skip));
+/// Count the lines of the a side and the b side that the edits span.
+fn count_lines<S: Eq>(edits: &[&Edit<S>]) -> (usize, usize) {
+    edits
+        .iter()
+        .fold((0, 0), |(a_lines, b_lines), edit| match edit.kind {
+            EditKind::Equal => (a_lines + 1, b_lines + 1),
+            EditKind::Deletion => (a_lines + 1, b_lines),
+            EditKind::Addition => (a_lines, b_lines + 1),
+        })
 }
 
 /// Computes a diff between two arbitrary sequences. The typical thing to use would be two lists of
@@ -428,7 +1162,20 @@ pub fn diff<S: Eq + Copy + Display>(a: &[S], b: &[S]) -> String {
     result
 }
 
-/// Computes an edit script between two arbitrary sequences.
+/// Computes an edit script between two arbitrary sequences with the given algorithm.
+pub fn edit_script_with_algorithm<S: Eq + Hash + Copy>(
+    a: &[S],
+    b: &[S],
+    algorithm: Algorithm,
+) -> Vec<Edit<S>> {
+    match algorithm {
+        Algorithm::Myers => edit_script(a, b),
+        Algorithm::Patience => diff_algorithms::patience_edit_script(a, b),
+        Algorithm::Histogram => diff_algorithms::histogram_edit_script(a, b),
+    }
+}
+
+/// Computes an edit script between two arbitrary sequences with the Myers algorithm.
 ///
 /// Example:
 /// ```
@@ -451,11 +1198,148 @@ pub fn diff<S: Eq + Copy + Display>(a: &[S], b: &[S]) -> String {
 /// assert_eq!(edit_script, expected_edits);
 /// ```
 pub fn edit_script<S: Eq + Copy>(a: &[S], b: &[S]) -> Vec<Edit<S>> {
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    push_edits(a, b, 0, 0, &mut edits);
+    edits
+}
+
+/// Sequences whose combined length is at most this are diffed by tracing every step of the search
+/// for the shortest edit path, which takes space quadratic in the length of the path.
+const MAX_TRACED_LENGTH: usize = 1024;
+
+/// Diff small sequences by tracing the search for the shortest edit path, and split larger ones
+/// in two at the middle snake of that path, which only takes linear space to find.
+fn push_edits<S: Eq + Copy>(
+    a: &[S],
+    b: &[S],
+    a_offset: usize,
+    b_offset: usize,
+    edits: &mut Vec<Edit<S>>,
+) {
+    let snake = if a.len() + b.len() > MAX_TRACED_LENGTH {
+        Some(find_middle_snake(a, b))
+    } else {
+        None
+    };
+
+    match snake {
+        Some(snake) if snake.depth > 1 => {
+            push_edits(
+                &a[..snake.x_start],
+                &b[..snake.y_start],
+                a_offset,
+                b_offset,
+                edits,
+            );
+            for (x, y) in (snake.x_start..snake.x_end).zip(snake.y_start..snake.y_end) {
+                edits.push(Edit::equal(a[x], a_offset + x, b_offset + y));
+            }
+            push_edits(
+                &a[snake.x_end..],
+                &b[snake.y_end..],
+                a_offset + snake.x_end,
+                b_offset + snake.y_end,
+                edits,
+            );
+        }
+        _ => edits.extend(
+            traced_edit_script(a, b)
+                .into_iter()
+                .map(|edit| edit.with_offsets(a_offset, b_offset)),
+        ),
+    }
+}
+
+fn traced_edit_script<S: Eq + Copy>(a: &[S], b: &[S]) -> Vec<Edit<S>> {
+    if a.is_empty() && b.is_empty() {
+        return vec![];
+    }
     let (final_k_value, edit_path_graph) = compute_edit_path_graph(a, b);
     let reversed_edit_trace = trace_edit_points(final_k_value, edit_path_graph);
     compute_edit_script(a, b, &reversed_edit_trace)
 }
 
+/// The diagonal run of equal elements in the middle of a shortest edit path, along with the
+/// number of additions and deletions on the path.
+#[derive(Debug, PartialEq, Eq)]
+struct MiddleSnake {
+    x_start: usize,
+    y_start: usize,
+    x_end: usize,
+    y_end: usize,
+    depth: usize,
+}
+
+/// Find the middle snake by searching for the shortest edit path from both ends at once, until
+/// the searches overlap. Only the furthest reaching x-values of the current depth are kept.
+fn find_middle_snake<S: Eq>(a: &[S], b: &[S]) -> MiddleSnake {
+    let (n, m) = (a.len() as i64, b.len() as i64);
+    let delta = n - m;
+    let is_odd = delta % 2 != 0;
+    let max_depth = (n + m + 1) / 2;
+    let offset = max_depth + 1;
+    let index = |k: i64| (k + offset) as usize;
+
+    // the backward search works on the reversed sequences, where diagonal k is diagonal delta - k
+    let mut forward = vec![0; 2 * offset as usize + 1];
+    let mut backward = vec![0; 2 * offset as usize + 1];
+    for d in 0..=max_depth {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let (x_start, y_start) = (x, x - k);
+            let mut y = y_start;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index(k)] = x;
+
+            let backward_k = delta - k;
+            if is_odd && (1 - d..d).contains(&backward_k) && x + backward[index(backward_k)] >= n {
+                return MiddleSnake {
+                    x_start: x_start as usize,
+                    y_start: y_start as usize,
+                    x_end: x as usize,
+                    y_end: y as usize,
+                    depth: (2 * d - 1) as usize,
+                };
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                backward[index(k + 1)]
+            } else {
+                backward[index(k - 1)] + 1
+            };
+            let (x_end, y_end) = (x, x - k);
+            let mut y = y_end;
+            while x < n && y < m && a[(n - x - 1) as usize] == b[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[index(k)] = x;
+
+            let forward_k = delta - k;
+            if !is_odd && (-d..=d).contains(&forward_k) && x + forward[index(forward_k)] >= n {
+                return MiddleSnake {
+                    x_start: (n - x) as usize,
+                    y_start: (m - y) as usize,
+                    x_end: (n - x_end) as usize,
+                    y_end: (m - y_end) as usize,
+                    depth: (2 * d) as usize,
+                };
+            }
+        }
+    }
+
+    panic!("could not find the middle snake")
+}
+
 fn compute_edit_path_graph<S: Eq>(a: &[S], b: &[S]) -> (i32, Vec<Vec<usize>>) {
     let max_depth = a.len() + b.len();
 
@@ -480,17 +1364,26 @@ fn compute_edit_path_graph<S: Eq>(a: &[S], b: &[S]) -> (i32, Vec<Vec<usize>>) {
             set(&mut v, k, x);
 
             if x >= a.len() && y >= b.len() {
-                trace.push(v.clone());
+                trace.push(reached_values(&v, d));
                 return (k, trace);
             }
         }
 
-        trace.push(v.clone());
+        trace.push(reached_values(&v, d));
     }
 
     panic!("could not find the shortest path")
 }
 
+/// The values of v for the k-values from -d to d, which are the only ones that have been reached
+/// at depth d. They use the same negative indexing as v.
+fn reached_values(v: &[usize], d: i32) -> Vec<usize> {
+    let d = d as usize;
+    let mut values = v[..=d].to_vec();
+    values.extend_from_slice(&v[v.len() - d..]);
+    values
+}
+
 #[derive(PartialEq, Eq)]
 pub struct Edit<S: Eq> {
     content: S,
@@ -544,6 +1437,33 @@ impl<S: Eq> Edit<S> {
             kind: EditKind::Equal,
         }
     }
+
+    /// Shift the positions of the edit, for an edit of subsequences that start at the offsets.
+    pub(crate) fn with_offsets(self, a_offset: usize, b_offset: usize) -> Edit<S> {
+        Edit {
+            a_position: self.a_position.map(|position| position + a_offset),
+            b_position: self.b_position.map(|position| position + b_offset),
+            ..self
+        }
+    }
+
+    pub fn content(&self) -> &S {
+        &self.content
+    }
+
+    /// Position of the edited element in the first sequence, if it is present there.
+    pub fn a_position(&self) -> Option<usize> {
+        self.a_position
+    }
+
+    /// Position of the edited element in the second sequence, if it is present there.
+    pub fn b_position(&self) -> Option<usize> {
+        self.b_position
+    }
+
+    pub fn kind(&self) -> &EditKind {
+        &self.kind
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -563,8 +1483,9 @@ fn trace_edit_points(final_k: i32, trace: Vec<Vec<usize>>) -> Vec<(i32, i32)> {
     edit_points.push((final_x, final_y));
 
     for d in (0..trace.len() - 1).rev() {
+        // the values after depth d tell which move was made at depth d + 1
         let v = &trace[d];
-        k = compute_previous_k(k, d as i32, v);
+        k = compute_previous_k(k, d as i32 + 1, v);
         let x = *get(v, k) as i32;
         let y = x - k;
         edit_points.push((x, y));
@@ -664,6 +1585,72 @@ mod tests {
         assert_eq!(shortest_paths.len(), 6);
     }
 
+    #[test]
+    fn test_find_middle_snake() {
+        let a = "ABCABBA".chars().collect::<Vec<char>>();
+        let b = "CBABAC".chars().collect::<Vec<char>>();
+
+        let snake = find_middle_snake(&a, &b);
+
+        assert_eq!(
+            snake,
+            MiddleSnake {
+                x_start: 3,
+                y_start: 2,
+                x_end: 5,
+                y_end: 4,
+                depth: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_edit_script_of_large_sequences_is_shortest() {
+        // sequences long enough to be split at their middle snakes, with scattered changes
+        let a = (0..2000).map(|i| i % 97).collect::<Vec<_>>();
+        let b = (0..2000)
+            .filter(|i| i % 13 != 0)
+            .map(|i| if i % 31 == 0 { 100 } else { i % 97 })
+            .collect::<Vec<_>>();
+
+        let edit_script = edit_script(&a, &b);
+
+        let count_changes = |edits: &[Edit<i32>]| {
+            edits
+                .iter()
+                .filter(|edit| edit.kind != EditKind::Equal)
+                .count()
+        };
+        assert_eq!(
+            count_changes(&edit_script),
+            count_changes(&traced_edit_script(&a, &b))
+        );
+        let a_positions = edit_script
+            .iter()
+            .filter_map(|edit| edit.a_position)
+            .collect::<Vec<_>>();
+        let b_contents = edit_script
+            .iter()
+            .filter(|edit| edit.kind != EditKind::Deletion)
+            .map(|edit| edit.content)
+            .collect::<Vec<_>>();
+        assert_eq!(a_positions, (0..a.len()).collect::<Vec<_>>());
+        assert_eq!(b_contents, b);
+    }
+
+    #[test]
+    fn test_edit_script_with_addition_before_first_line() {
+        let a = vec!["x"];
+        let b = vec!["new", "x"];
+
+        let edit_script = edit_script(&a, &b);
+
+        assert_eq!(
+            edit_script,
+            vec![Edit::addition("new", 0), Edit::equal("x", 0, 1)]
+        );
+    }
+
     #[test]
     fn test_trace_edit_points() {
         let a = "ABCABBA".chars().collect::<Vec<char>>();
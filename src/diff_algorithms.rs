@@ -0,0 +1,323 @@
+//! The patience and histogram diff algorithms. Both split the sequences at lines that they have
+//! in common and that occur rarely, which keeps unrelated changes apart better than Myers does
+//! when lines are moved around. Ranges without such lines are diffed with Myers.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::diff::{self, Edit};
+
+/// Lines that occur more often than this in the a side are not used to split the sequences by
+/// the histogram algorithm, the same limit as Git uses.
+const MAX_HISTOGRAM_OCCURRENCES: usize = 64;
+
+/// Compute an edit script by splitting the sequences at the longest increasing sequence of lines
+/// that occur exactly once in both of them.
+pub(crate) fn patience_edit_script<S: Eq + Hash + Copy>(a: &[S], b: &[S]) -> Vec<Edit<S>> {
+    let mut edits = vec![];
+    diff_range(a, b, 0, 0, &find_patience_anchors, &mut edits);
+    edits
+}
+
+/// Compute an edit script by splitting the sequences at the longest common region around the
+/// line with the fewest occurrences in the a side.
+pub(crate) fn histogram_edit_script<S: Eq + Hash + Copy>(a: &[S], b: &[S]) -> Vec<Edit<S>> {
+    let prefix_length = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix_length = a[prefix_length..]
+        .iter()
+        .rev()
+        .zip(b[prefix_length..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mut edits = vec![];
+    for (i, line) in a[..prefix_length].iter().enumerate() {
+        edits.push(Edit::equal(*line, i, i));
+    }
+    diff_range(
+        &a[prefix_length..a.len() - suffix_length],
+        &b[prefix_length..b.len() - suffix_length],
+        prefix_length,
+        prefix_length,
+        &find_histogram_region,
+        &mut edits,
+    );
+    let (a_suffix_start, b_suffix_start) = (a.len() - suffix_length, b.len() - suffix_length);
+    for i in 0..suffix_length {
+        edits.push(Edit::equal(
+            a[a_suffix_start + i],
+            a_suffix_start + i,
+            b_suffix_start + i,
+        ));
+    }
+    edits
+}
+
+/// Matching positions in a and b, in increasing order in both sequences.
+type Anchors = Vec<(usize, usize)>;
+
+/// Diff the sequences by matching up the anchors found in them, and recursively diffing the
+/// ranges in between. Equal lines next to an anchor are matched up before recursing.
+fn diff_range<S: Eq + Hash + Copy>(
+    a: &[S],
+    b: &[S],
+    a_offset: usize,
+    b_offset: usize,
+    find_anchors: &dyn Fn(&[S], &[S]) -> Anchors,
+    edits: &mut Vec<Edit<S>>,
+) {
+    let anchors = if a.is_empty() || b.is_empty() {
+        vec![]
+    } else {
+        find_anchors(a, b)
+    };
+    if anchors.is_empty() {
+        push_myers_edits(a, b, a_offset, b_offset, edits);
+        return;
+    }
+
+    let (mut a_start, mut b_start) = (0, 0);
+    for anchor in anchors.into_iter().map(Some).chain([None]) {
+        let (mut a_end, mut b_end) = anchor.unwrap_or((a.len(), b.len()));
+        let (a_anchor, b_anchor) = (a_end, b_end);
+        if anchor.is_some() {
+            while a_end > a_start && b_end > b_start && a[a_end - 1] == b[b_end - 1] {
+                a_end -= 1;
+                b_end -= 1;
+            }
+        }
+        while a_start < a_end && b_start < b_end && a[a_start] == b[b_start] {
+            edits.push(Edit::equal(
+                a[a_start],
+                a_offset + a_start,
+                b_offset + b_start,
+            ));
+            a_start += 1;
+            b_start += 1;
+        }
+
+        if a_start < a_end || b_start < b_end {
+            diff_range(
+                &a[a_start..a_end],
+                &b[b_start..b_end],
+                a_offset + a_start,
+                b_offset + b_start,
+                find_anchors,
+                edits,
+            );
+        }
+        if anchor.is_none() {
+            break;
+        }
+
+        for (i, j) in (a_end..=a_anchor).zip(b_end..=b_anchor) {
+            edits.push(Edit::equal(a[i], a_offset + i, b_offset + j));
+        }
+        (a_start, b_start) = (a_anchor + 1, b_anchor + 1);
+    }
+}
+
+fn push_myers_edits<S: Eq + Copy>(
+    a: &[S],
+    b: &[S],
+    a_offset: usize,
+    b_offset: usize,
+    edits: &mut Vec<Edit<S>>,
+) {
+    edits.extend(
+        diff::edit_script(a, b)
+            .into_iter()
+            .map(|edit| edit.with_offsets(a_offset, b_offset)),
+    );
+}
+
+fn find_patience_anchors<S: Eq + Hash + Copy>(a: &[S], b: &[S]) -> Anchors {
+    let mut counts: HashMap<S, (usize, usize, usize)> = HashMap::new();
+    for (i, line) in a.iter().enumerate() {
+        let entry = counts.entry(*line).or_insert((0, 0, 0));
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    let mut unique_matches = vec![];
+    for line in b {
+        if let Some(entry) = counts.get_mut(line) {
+            entry.1 += 1;
+        }
+    }
+    for (j, line) in b.iter().enumerate() {
+        if let Some((1, 1, i)) = counts.get(line) {
+            unique_matches.push((*i, j));
+        }
+    }
+
+    longest_increasing_sequence(&unique_matches)
+}
+
+/// Find the longest subsequence of the matches, which are ordered by their b positions, whose a
+/// positions are also increasing.
+fn longest_increasing_sequence(matches: &[(usize, usize)]) -> Anchors {
+    // the index of the last match of the best sequence of each length, found by patience sorting
+    let mut pile_tops: Vec<usize> = vec![];
+    let mut predecessors = vec![None; matches.len()];
+    for (index, (a_position, _)) in matches.iter().enumerate() {
+        let pile = pile_tops.partition_point(|top| matches[*top].0 < *a_position);
+        if pile > 0 {
+            predecessors[index] = Some(pile_tops[pile - 1]);
+        }
+        if pile == pile_tops.len() {
+            pile_tops.push(index);
+        } else {
+            pile_tops[pile] = index;
+        }
+    }
+
+    let mut sequence = vec![];
+    let mut current = pile_tops.last().copied();
+    while let Some(index) = current {
+        sequence.push(matches[index]);
+        current = predecessors[index];
+    }
+    sequence.reverse();
+    sequence
+}
+
+fn find_histogram_region<S: Eq + Hash + Copy>(a: &[S], b: &[S]) -> Anchors {
+    let mut occurrences: HashMap<S, Vec<usize>> = HashMap::new();
+    for (i, line) in a.iter().enumerate() {
+        occurrences.entry(*line).or_default().push(i);
+    }
+
+    // the lowest occurrence count, start in a, start in b and length of the best region
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut j = 0;
+    while j < b.len() {
+        let mut next_j = j + 1;
+        let positions = match occurrences.get(&b[j]) {
+            Some(positions) if positions.len() <= MAX_HISTOGRAM_OCCURRENCES => positions,
+            _ => {
+                j = next_j;
+                continue;
+            }
+        };
+
+        for &i in positions {
+            let (mut a_start, mut b_start) = (i, j);
+            while a_start > 0 && b_start > 0 && a[a_start - 1] == b[b_start - 1] {
+                a_start -= 1;
+                b_start -= 1;
+            }
+            let (mut a_end, mut b_end) = (i + 1, j + 1);
+            while a_end < a.len() && b_end < b.len() && a[a_end] == b[b_end] {
+                a_end += 1;
+                b_end += 1;
+            }
+
+            let count = a[a_start..a_end]
+                .iter()
+                .map(|line| occurrences[line].len())
+                .min()
+                .unwrap();
+            let length = a_end - a_start;
+            let is_better = match best {
+                None => true,
+                Some((best_count, _, _, best_length)) => {
+                    count < best_count || (count == best_count && length > best_length)
+                }
+            };
+            if is_better {
+                best = Some((count, a_start, b_start, length));
+            }
+            next_j = next_j.max(b_end);
+        }
+        j = next_j;
+    }
+
+    match best {
+        Some((_, a_start, b_start, length)) => (0..length)
+            .map(|offset| (a_start + offset, b_start + offset))
+            .collect(),
+        None => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::EditKind;
+
+    fn apply(a: &[&str], edits: &[Edit<&str>]) -> Vec<String> {
+        let mut a_position = 0;
+        let mut result = vec![];
+        for edit in edits {
+            match edit.kind() {
+                EditKind::Equal => {
+                    assert_eq!(edit.a_position(), Some(a_position));
+                    assert_eq!(edit.content(), &a[a_position]);
+                    result.push(edit.content().to_string());
+                    a_position += 1;
+                }
+                EditKind::Deletion => {
+                    assert_eq!(edit.a_position(), Some(a_position));
+                    a_position += 1;
+                }
+                EditKind::Addition => {
+                    assert_eq!(edit.b_position(), Some(result.len()));
+                    result.push(edit.content().to_string());
+                }
+            }
+        }
+        assert_eq!(a_position, a.len());
+        result
+    }
+
+    #[test]
+    fn test_patience_keeps_unique_lines_as_anchors() {
+        let a = vec!["{", "a", "}", "{", "b", "}", "unique"];
+        let b = vec!["unique", "{", "a", "}", "{", "b", "}"];
+
+        let edits = patience_edit_script(&a, &b);
+
+        assert_eq!(apply(&a, &edits), b);
+        let changes = edits
+            .iter()
+            .filter(|edit| !matches!(edit.kind(), EditKind::Equal))
+            .map(|edit| (edit.kind(), *edit.content()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (&EditKind::Addition, "unique"),
+                (&EditKind::Deletion, "unique")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_histogram_produces_valid_edit_script() {
+        let a = vec!["x", "a", "b", "x", "c", "d", "x", "e"];
+        let b = vec!["a", "x", "c", "d", "e", "x", "b", "f"];
+
+        let edits = histogram_edit_script(&a, &b);
+
+        assert_eq!(apply(&a, &edits), b);
+    }
+
+    #[test]
+    fn test_histogram_splits_at_rarest_common_region() {
+        let a = vec!["x", "x", "rare", "y"];
+        let b = vec!["rare", "x", "x"];
+
+        let anchors = find_histogram_region(&a, &b);
+
+        assert_eq!(anchors, vec![(2, 0)]);
+    }
+
+    #[test]
+    fn test_longest_increasing_sequence() {
+        let matches = vec![(3, 0), (0, 1), (4, 2), (1, 3), (2, 4)];
+
+        let sequence = longest_increasing_sequence(&matches);
+
+        assert_eq!(sequence, vec![(0, 1), (1, 3), (2, 4)]);
+    }
+}
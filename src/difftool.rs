@@ -0,0 +1,198 @@
+//! Viewing diffs with external programs. The program in `diff.external` replaces the patches
+//! written by `diff`, while `difftool` launches a configured tool, typically a graphical one, for
+//! each changed file. Both are given the two sides of each file as temporary files.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    diff::{self, FileDiff},
+    output::OutputWriter,
+    prompt::Prompt,
+    workspace::Repository,
+};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// The name of the tool to launch, whose command is taken from the `difftool.<tool>.cmd`
+    /// config. Defaults to the `diff.tool` config.
+    #[builder(default)]
+    pub tool: Option<String>,
+
+    /// A command to launch instead of a tool, with the two sides of each file as arguments.
+    #[builder(default)]
+    pub extcmd: Option<String>,
+
+    /// Whether to ask before launching the tool for each file. Defaults to the `difftool.prompt`
+    /// config, or true if it's not set.
+    #[builder(default)]
+    pub prompt: Option<bool>,
+}
+
+/// Launch the configured tool for each file that differs as described by the diff options. The
+/// tool is run from the root of the worktree with the `LOCAL` and `REMOTE` environment variables
+/// set to the paths of the two sides, and `MERGED` set to the path of the file. If no tool is
+/// configured, the diff is written as by `diff` instead.
+pub fn difftool(
+    repository: &Repository,
+    diff_options: &diff::Options,
+    options: &Options,
+    prompt: &mut dyn Prompt,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let config = repository.config()?;
+    let (tool_name, command) = match (&options.extcmd, &options.tool) {
+        (Some(extcmd), _) => (extcmd.clone(), format!("{} \"$LOCAL\" \"$REMOTE\"", extcmd)),
+        (None, tool) => {
+            let tool = tool.clone().or_else(|| config.get_string("diff.tool"));
+            let Some(tool) = tool else {
+                diff::diff_repository(repository, diff_options, writer)?;
+                return Ok(());
+            };
+            let command = config
+                .get_string(&format!("difftool.{}.cmd", tool))
+                .ok_or_else(|| {
+                    let message = format!("no command configured for the difftool '{}'", tool);
+                    crate::Error::Fatal(None, message)
+                })?;
+            (tool, command)
+        }
+    };
+    let should_prompt = match options.prompt {
+        Some(should_prompt) => should_prompt,
+        None => config.get_bool("difftool.prompt")?.unwrap_or(true),
+    };
+
+    let file_diffs = diff::resolve_file_diffs(repository, diff_options)?;
+    for (i, file_diff) in file_diffs.iter().enumerate() {
+        if should_prompt {
+            writer.writeln(format!(
+                "Viewing ({}/{}): '{}'",
+                i + 1,
+                file_diffs.len(),
+                file_diff.path().display()
+            ))?;
+            writer.write(format!("Launch '{}' [Y/n]? ", tool_name))?;
+            match prompt.read_answer()?.as_deref() {
+                Some("n") | Some("N") => continue,
+                Some(_) => (),
+                None => break,
+            }
+        }
+
+        let files = TemporaryFiles::write(file_diff)?;
+        // like Git, the exit status of the tool is ignored so that the remaining files are shown
+        Command::new("sh")
+            .arg("-c")
+            .arg(&command)
+            .current_dir(repository.worktree().root())
+            .env("LOCAL", &files.a_path)
+            .env("REMOTE", &files.b_path)
+            .env("MERGED", file_diff.path())
+            .status()?;
+    }
+
+    Ok(())
+}
+
+/// The program to run instead of writing patches, taken from `GIT_EXTERNAL_DIFF` or the
+/// `diff.external` config.
+pub fn external_diff_program(repository: &Repository) -> crate::Result<Option<String>> {
+    match env::var("GIT_EXTERNAL_DIFF") {
+        Ok(program) => Ok(Some(program)),
+        Err(_) => Ok(repository.config()?.get_string("diff.external")),
+    }
+}
+
+/// Run the external diff program for the file diff and write its output. The program gets the
+/// same seven arguments as from Git: the path, followed by the temporary file, blob id and mode
+/// of each side. A missing side is given as `/dev/null` with `.` as id and mode.
+pub fn run_external_diff(
+    program: &str,
+    file_diff: &FileDiff,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let files = TemporaryFiles::write(file_diff)?;
+    let (a_oid, b_oid) = file_diff.oids();
+    // the modes of the files aren't part of the diffs, so they are passed as regular files
+    let id_and_mode = |oid: Option<&str>| match oid {
+        Some(oid) => (oid.to_owned(), "100644"),
+        None => (String::from("."), "."),
+    };
+    let (a_id, a_mode) = id_and_mode(a_oid);
+    let (b_id, b_mode) = id_and_mode(b_oid);
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", program))
+        .arg(program)
+        .arg(file_diff.path())
+        .args([files.a_path.as_os_str(), a_id.as_ref(), a_mode.as_ref()])
+        .args([files.b_path.as_os_str(), b_id.as_ref(), b_mode.as_ref()])
+        .current_dir(repository.worktree().root())
+        .output()?;
+    writer.write(String::from_utf8_lossy(&output.stdout).into_owned())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = format!(
+            "external diff died, stopping at {}",
+            file_diff.path().display()
+        );
+        Err(crate::Error::Fatal(None, message))
+    }
+}
+
+/// The two sides of a file diff written to a temporary directory, which is removed on drop.
+/// Missing sides are given as `/dev/null`.
+struct TemporaryFiles {
+    directory: PathBuf,
+    a_path: PathBuf,
+    b_path: PathBuf,
+}
+
+impl TemporaryFiles {
+    fn write(file_diff: &FileDiff) -> io::Result<TemporaryFiles> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let directory = env::temp_dir().join(format!(
+            "rut-diff-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        // the files keep their names so that tools can tell what kind of file they are
+        let file_name = file_diff.path().file_name().unwrap_or_default();
+        let write_side = |side: &str, content: Option<&[u8]>| -> io::Result<PathBuf> {
+            match content {
+                Some(content) => {
+                    let side_directory = directory.join(side);
+                    fs::create_dir_all(&side_directory)?;
+                    let path = side_directory.join(file_name);
+                    fs::write(&path, content)?;
+                    Ok(path)
+                }
+                None => Ok(Path::new("/dev/null").to_owned()),
+            }
+        };
+
+        let (a_content, b_content) = file_diff.contents();
+        let a_path = write_side("a", a_content)?;
+        let b_path = write_side("b", b_content)?;
+        Ok(TemporaryFiles {
+            directory,
+            a_path,
+            b_path,
+        })
+    }
+}
+
+impl Drop for TemporaryFiles {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
@@ -0,0 +1,103 @@
+//! Launching the user's editor to write a message, such as a commit message, with instructions
+//! in comment lines that are removed from the result.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::workspace::Repository;
+
+/// The terminal of the process, which the editor is attached to if stdin or stdout isn't one.
+const TERMINAL: &str = "/dev/tty";
+
+/// Find the user's editor in `GIT_EDITOR`, the `core.editor` config, `VISUAL` or `EDITOR`, in
+/// that order, defaulting to `vi`.
+pub fn resolve_editor(config: &Config) -> String {
+    env::var("GIT_EDITOR")
+        .ok()
+        .or_else(|| config.get_string("core.editor"))
+        .or_else(|| env::var("VISUAL").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+/// Write the content to the file with the given name in the git directory, open it in the
+/// editor and return the edited content, cleaned with [`strip_comments`].
+pub fn edit(file_name: &str, content: &str, repository: &Repository) -> crate::Result<String> {
+    let path = repository.git_dir().join(file_name);
+    fs::write(&path, content)?;
+    launch(&path, repository)?;
+    Ok(strip_comments(&fs::read_to_string(&path)?))
+}
+
+/// Open the file in the editor and wait for it to exit. The editor runs through the shell so that
+/// it may have arguments, and is attached to the terminal even if the output of Rut goes to a
+/// pager or a pipe.
+pub fn launch(path: &Path, repository: &Repository) -> crate::Result<()> {
+    let editor = resolve_editor(&repository.config()?);
+    let exit_status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .stdin(terminal_or_inherit(io::stdin().is_terminal()))
+        .stdout(terminal_or_inherit(io::stdout().is_terminal()))
+        .status()?;
+
+    if !exit_status.success() {
+        let message = format!("there was a problem with the editor '{}'", editor);
+        return Err(crate::Error::Fatal(None, message));
+    }
+    Ok(())
+}
+
+fn terminal_or_inherit(is_terminal: bool) -> Stdio {
+    if is_terminal {
+        return Stdio::inherit();
+    }
+    let terminal = File::options().read(true).write(true).open(TERMINAL);
+    terminal.map_or_else(|_| Stdio::inherit(), Stdio::from)
+}
+
+/// Prefix each line of the text with `# `, or with only `#` if it's empty, to write instructions
+/// that are removed from the edited message.
+///
+/// # Examples
+/// ```
+/// use rut::editor;
+///
+/// assert_eq!(editor::comment("Line one\n\nLine two\n"), "# Line one\n#\n# Line two\n");
+/// ```
+pub fn comment(text: &str) -> String {
+    text.lines()
+        .map(|line| match line {
+            "" => String::from("#\n"),
+            line => format!("# {}\n", line),
+        })
+        .collect()
+}
+
+/// Remove comment lines, trailing whitespace and surrounding blank lines from an edited message.
+///
+/// # Examples
+/// ```
+/// use rut::editor;
+///
+/// assert_eq!(
+///     editor::strip_comments("\nSubject  \n\nBody\n# instructions\n\n"),
+///     "Subject\n\nBody"
+/// );
+/// ```
+pub fn strip_comments(message: &str) -> String {
+    message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
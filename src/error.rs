@@ -1,11 +1,22 @@
+use crate::objects::ObjectId;
 use crate::refs::ParseRevisionError;
 
-use std::{error, fmt, io, result};
+use std::{error, fmt, io, path::PathBuf, result};
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
     ParseRevision(ParseRevisionError),
+    /// A short object id that is the prefix of more than one object, which are given.
+    AmbiguousObjectId(String, Vec<ObjectId>),
+    /// A reference that names neither a ref nor an object.
+    AmbiguousRef(String),
+    /// An object that can't be parsed, with the reason.
+    CorruptObject(ObjectId, String),
+    /// An index file that can't be parsed, with the reason.
+    CorruptIndex(String),
+    /// Neither the directory nor any of its parents is in a repository.
+    NotARepository(PathBuf),
     Fatal(Option<Box<dyn std::error::Error>>, String),
 }
 
@@ -14,6 +25,11 @@ impl std::error::Error for Error {
         match self {
             Error::Io(err) => Some(err),
             Error::ParseRevision(err) => Some(err),
+            Error::AmbiguousObjectId(_, _)
+            | Error::AmbiguousRef(_)
+            | Error::CorruptObject(_, _)
+            | Error::CorruptIndex(_)
+            | Error::NotARepository(_) => None,
             Error::Fatal(Some(err), _) => err.source(),
             Error::Fatal(None, _) => None,
         }
@@ -25,11 +41,34 @@ impl fmt::Display for Error {
         match self {
             Error::Io(err) => write!(f, "Unhandled IO error: {}", err),
             Error::ParseRevision(err) => write!(f, "Unhandled parse error: {}", err),
+            Error::AmbiguousObjectId(prefix, _) => {
+                write!(f, "fatal: short object ID {} is ambiguous", prefix)
+            }
+            Error::AmbiguousRef(reference) => write!(
+                f,
+                "fatal: ambiguous argument '{}': unknown revision or path not in the working tree.",
+                reference
+            ),
+            Error::CorruptObject(object_id, reason) => {
+                write!(f, "fatal: object {} is corrupt: {}", object_id, reason)
+            }
+            Error::CorruptIndex(reason) => write!(f, "fatal: index file corrupt: {}", reason),
+            Error::NotARepository(_) => write!(
+                f,
+                "fatal: not a git repository (or any of the parent directories): .git"
+            ),
             Error::Fatal(_, msg) => write!(f, "fatal: {}", msg),
         }
     }
 }
 
+impl Error {
+    /// Whether the error is one that the user can act on, as opposed to an unexpected failure.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(self, Error::Io(_) | Error::ParseRevision(_))
+    }
+}
+
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
         Error::Io(err)
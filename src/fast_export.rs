@@ -0,0 +1,196 @@
+//! Exporting the history of all refs as a fast-import stream, the text format that `fast-import`
+//! and the importers of other version control tools read.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+
+use crate::{
+    index::FileMode,
+    objects::{Commit, ObjectId},
+    refs::RefHandler,
+    workspace::{Database, Repository},
+};
+
+/// The files of a tree by their paths, with their modes and ids.
+type Files = BTreeMap<String, (FileMode, ObjectId)>;
+
+/// Write the commits reachable from all refs, oldest first, along with the blobs they add. Each
+/// commit is written as the changes to its first parent, and blobs and commits are given marks
+/// that later commands refer to them by.
+pub fn fast_export(repository: &Repository, output: &mut dyn Write) -> crate::Result<()> {
+    let database = &repository.database;
+    let mut exporter = Exporter {
+        database,
+        output,
+        marks: HashMap::new(),
+    };
+
+    for (name, tip) in RefHandler::new(repository).list_all_refs()? {
+        let commit_ids = exporter.unexported_commits(&tip)?;
+        if commit_ids.is_empty() {
+            // the commit was exported for another ref
+            let mark = exporter.marks[&tip];
+            writeln!(exporter.output, "reset {}\nfrom :{}\n", name, mark)?;
+        }
+        for commit_id in commit_ids {
+            exporter.export_commit(&name, &commit_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+struct Exporter<'a> {
+    database: &'a Database,
+    output: &'a mut dyn Write,
+    /// The marks of the exported blobs and commits.
+    marks: HashMap<ObjectId, usize>,
+}
+
+impl Exporter<'_> {
+    /// The commits reachable from the tip that haven't been exported, with parents before their
+    /// children.
+    fn unexported_commits(&self, tip: &ObjectId) -> crate::Result<Vec<ObjectId>> {
+        let mut commit_ids = vec![];
+        let mut visited = HashSet::new();
+        // each commit is pushed once to visit its parents, and again to be added after them
+        let mut pending = vec![(tip.clone(), false)];
+        while let Some((commit_id, parents_added)) = pending.pop() {
+            if parents_added {
+                commit_ids.push(commit_id);
+            } else if !self.marks.contains_key(&commit_id) && visited.insert(commit_id.clone()) {
+                let commit = self.database.load_commit(&commit_id)?;
+                pending.push((commit_id, true));
+                pending.extend(
+                    commit
+                        .parents
+                        .into_iter()
+                        .rev()
+                        .map(|parent| (parent, false)),
+                );
+            }
+        }
+        Ok(commit_ids)
+    }
+
+    fn export_commit(&mut self, ref_name: &str, commit_id: &ObjectId) -> crate::Result<()> {
+        let commit = self.database.load_commit(commit_id)?;
+        let old_files = match commit.parent() {
+            Some(parent) => self.load_files(&self.database.load_commit(parent)?.tree)?,
+            None => Files::new(),
+        };
+        let new_files = self.load_files(&commit.tree)?;
+
+        let mut modified = vec![];
+        for (path, (mode, object_id)) in &new_files {
+            if old_files.get(path) != Some(&(*mode, object_id.clone())) {
+                modified.push((path, *mode, object_id));
+            }
+        }
+        for (_, mode, object_id) in &modified {
+            if *mode != FileMode::Gitlink {
+                self.export_blob(object_id)?;
+            }
+        }
+
+        let mark = self.next_mark();
+        self.marks.insert(commit_id.clone(), mark);
+        if commit.parents.is_empty() {
+            writeln!(self.output, "reset {}", ref_name)?;
+        }
+        writeln!(self.output, "commit {}", ref_name)?;
+        writeln!(self.output, "mark :{}", mark)?;
+        writeln!(self.output, "author {}", format_signature(&commit, false))?;
+        writeln!(self.output, "committer {}", format_signature(&commit, true))?;
+        // the line feed after the data is optional, and keeps the message apart from what follows
+        writeln!(self.output, "data {}", commit.raw_message().len())?;
+        self.output.write_all(commit.raw_message())?;
+        writeln!(self.output)?;
+        for (i, parent) in commit.parents.iter().enumerate() {
+            let command = if i == 0 { "from" } else { "merge" };
+            writeln!(self.output, "{} :{}", command, self.marks[parent])?;
+        }
+        for path in old_files.keys() {
+            if !new_files.contains_key(path) {
+                writeln!(self.output, "D {}", quote_path(path))?;
+            }
+        }
+        for (path, mode, object_id) in modified {
+            let data_ref = match mode {
+                FileMode::Gitlink => object_id.to_string(),
+                _ => format!(":{}", self.marks[object_id]),
+            };
+            let path = quote_path(path);
+            writeln!(self.output, "M {} {} {}", mode.as_str(), data_ref, path)?;
+        }
+        writeln!(self.output)?;
+        Ok(())
+    }
+
+    fn export_blob(&mut self, blob_id: &ObjectId) -> crate::Result<()> {
+        if self.marks.contains_key(blob_id) {
+            return Ok(());
+        }
+        let (_, content) = self.database.load_object(blob_id)?;
+        let mark = self.next_mark();
+        self.marks.insert(blob_id.clone(), mark);
+        write!(
+            self.output,
+            "blob\nmark :{}\ndata {}\n",
+            mark,
+            content.len()
+        )?;
+        self.output.write_all(&content)?;
+        writeln!(self.output)?;
+        Ok(())
+    }
+
+    fn next_mark(&self) -> usize {
+        self.marks.len() + 1
+    }
+
+    fn load_files(&self, tree_id: &ObjectId) -> crate::Result<Files> {
+        let mut entries = vec![];
+        let tree = self.database.load_tree(tree_id)?;
+        self.database
+            .extract_entries_from_tree(String::new(), &tree, &mut entries)?;
+        Ok(entries
+            .into_iter()
+            .map(|(path, entry)| (path, (entry.mode, entry.object_id)))
+            .collect())
+    }
+}
+
+/// The identity and raw date of the author or committer, as in `Jane <jane@example.com> 1700000000
+/// +0100`.
+fn format_signature(commit: &Commit, committer: bool) -> String {
+    if committer {
+        format!(
+            "{} {} {}",
+            commit.committer, commit.committer_timestamp, commit.committer_timezone
+        )
+    } else {
+        format!("{} {} {}", commit.author, commit.timestamp, commit.timezone)
+    }
+}
+
+/// Quote the path like a C string if it contains characters that would make it ambiguous, such
+/// as quotes or newlines.
+fn quote_path(path: &str) -> String {
+    if !path.contains(|c: char| c == '"' || c == '\\' || c.is_control()) {
+        return path.to_owned();
+    }
+    let mut quoted = String::from("\"");
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\{:03o}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
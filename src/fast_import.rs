@@ -0,0 +1,536 @@
+//! Importing a fast-import stream, as written by `fast-export` or the exporters of other version
+//! control tools, into blobs, trees, commits and refs.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Read};
+use std::path::PathBuf;
+
+use crate::{
+    commit,
+    index::FileMode,
+    objects::{Author, Blob, Commit, GitObject, ObjectId},
+    output::OutputWriter,
+    refs::{RefHandler, Revision},
+    rev_walk,
+    workspace::Repository,
+};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Update refs even if the old commit is not an ancestor of the new one, which would lose
+    /// commits.
+    #[builder(default)]
+    pub force: bool,
+}
+
+/// The files of a commit by their paths, with their modes and ids.
+type Files = BTreeMap<String, (FileMode, ObjectId)>;
+
+/// Read the commands of the stream and create the objects they describe. The refs that commits
+/// and resets are made on are updated at the end of the stream, and refs that would lose commits
+/// are left as they are unless forced. Returns whether all refs were updated.
+pub fn fast_import(
+    input: &mut dyn BufRead,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let mut importer = Importer {
+        input: StreamReader {
+            input,
+            pending_line: None,
+        },
+        repository,
+        force: options.force,
+        marks: HashMap::new(),
+        branches: BTreeMap::new(),
+    };
+
+    while let Some(line) = importer.input.next_line()? {
+        let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+        match command {
+            "blob" => importer.import_blob()?,
+            "commit" => importer.import_commit(argument)?,
+            "reset" => importer.reset(argument)?,
+            "progress" => {
+                writer.writeln(line.clone())?;
+            }
+            "feature" => importer.enable_feature(argument)?,
+            "done" => break,
+            "" | "checkpoint" | "option" => (),
+            _ if command.starts_with('#') => (),
+            "tag" => return Err(error("annotated tags are not supported")),
+            _ => return Err(error(&format!("unsupported command: {}", line))),
+        }
+    }
+
+    importer.update_refs(writer)
+}
+
+struct Importer<'a> {
+    input: StreamReader<'a>,
+    repository: &'a Repository,
+    force: bool,
+    marks: HashMap<usize, ObjectId>,
+    /// The commits that the branches point to after the commands so far, or None for a branch
+    /// that was reset to start over without a parent.
+    branches: BTreeMap<String, Option<ObjectId>>,
+}
+
+impl Importer<'_> {
+    fn import_blob(&mut self) -> crate::Result<()> {
+        let mark = self.read_mark()?;
+        self.input.skip_line_with_prefix("original-oid ")?;
+        let content = self.input.read_data()?;
+        let blob = Blob::with_hash_algorithm(content, self.repository.hash_algorithm());
+        self.repository.database.store_object(&blob)?;
+        if let Some(mark) = mark {
+            self.marks.insert(mark, blob.id().clone());
+        }
+        Ok(())
+    }
+
+    fn import_commit(&mut self, ref_name: &str) -> crate::Result<()> {
+        let mark = self.read_mark()?;
+        self.input.skip_line_with_prefix("original-oid ")?;
+        let author = match self.input.next_line_with_prefix("author ")? {
+            Some(author) => Some(parse_signature(&author)?),
+            None => None,
+        };
+        let committer = self
+            .input
+            .next_line_with_prefix("committer ")?
+            .ok_or_else(|| error("expected committer in commit"))?;
+        let committer = parse_signature(&committer)?;
+        let extra_headers = match self.input.next_line_with_prefix("encoding ")? {
+            Some(encoding) => format!("encoding {}\n", encoding).into_bytes(),
+            None => vec![],
+        };
+        let message = self.input.read_data()?;
+
+        let mut parents = vec![];
+        if let Some(from) = self.input.next_line_with_prefix("from ")? {
+            parents.push(self.resolve_commit_ish(&from)?);
+        } else if let Some(tip) = self.branch_tip(ref_name)? {
+            parents.push(tip);
+        }
+        while let Some(merge) = self.input.next_line_with_prefix("merge ")? {
+            parents.push(self.resolve_commit_ish(&merge)?);
+        }
+
+        let mut files = match parents.first() {
+            Some(parent) => self.load_files(parent)?,
+            None => Files::new(),
+        };
+        while let Some(line) = self.input.next_line()? {
+            if line.is_empty() {
+                break;
+            } else if !self.apply_file_command(&line, &mut files)? {
+                self.input.pending_line = Some(line);
+                break;
+            }
+        }
+
+        let database = &self.repository.database;
+        let hash_algorithm = self.repository.hash_algorithm();
+        let (tree_id, trees) = commit::build_tree_from_paths(
+            files
+                .iter()
+                .map(|(path, (mode, object_id))| (PathBuf::from(path), object_id, *mode)),
+            hash_algorithm,
+        );
+        for tree in &trees {
+            database.store_object(tree)?;
+        }
+
+        let author = author.unwrap_or(committer.clone());
+        let commit = Commit::from_parts(
+            tree_id,
+            parents,
+            author,
+            committer,
+            extra_headers,
+            message,
+            hash_algorithm,
+        );
+        database.store_object(&commit)?;
+
+        if let Some(mark) = mark {
+            self.marks.insert(mark, commit.id().clone());
+        }
+        self.branches
+            .insert(ref_name.to_owned(), Some(commit.id().clone()));
+        Ok(())
+    }
+
+    fn reset(&mut self, ref_name: &str) -> crate::Result<()> {
+        let tip = match self.input.next_line_with_prefix("from ")? {
+            Some(from) => Some(self.resolve_commit_ish(&from)?),
+            None => None,
+        };
+        self.branches.insert(ref_name.to_owned(), tip);
+        Ok(())
+    }
+
+    fn enable_feature(&mut self, feature: &str) -> crate::Result<()> {
+        match feature {
+            "done" | "date-format=raw" => Ok(()),
+            "force" => {
+                self.force = true;
+                Ok(())
+            }
+            _ => Err(error(&format!("unsupported feature: {}", feature))),
+        }
+    }
+
+    /// Apply a command that changes the files of a commit. Returns false if the line is not such
+    /// a command, which ends the commit.
+    fn apply_file_command(&mut self, line: &str, files: &mut Files) -> crate::Result<bool> {
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "M" => {
+                let mut parts = argument.splitn(3, ' ');
+                let (Some(mode), Some(data_ref), Some(path)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    return Err(error(&format!("invalid file modification: {}", line)));
+                };
+                let mode = parse_mode(mode)?;
+                let object_id = match data_ref {
+                    "inline" => {
+                        let blob = Blob::with_hash_algorithm(
+                            self.input.read_data()?,
+                            self.repository.hash_algorithm(),
+                        );
+                        self.repository.database.store_object(&blob)?;
+                        blob.id().clone()
+                    }
+                    data_ref => self.resolve_data_ref(data_ref)?,
+                };
+                let (path, _) = parse_path(path, true)?;
+                remove_path(files, &path);
+                files.insert(path, (mode, object_id));
+            }
+            "D" => {
+                let (path, _) = parse_path(argument, true)?;
+                remove_path(files, &path);
+            }
+            "C" | "R" => {
+                let (source, rest) = parse_path(argument, false)?;
+                let (destination, _) = parse_path(rest.trim_start(), true)?;
+                let copied = files
+                    .iter()
+                    .filter_map(|(path, value)| {
+                        let rest = path.strip_prefix(&source)?;
+                        (rest.is_empty() || rest.starts_with('/'))
+                            .then(|| (format!("{}{}", destination, rest), value.clone()))
+                    })
+                    .collect::<Vec<_>>();
+                if copied.is_empty() {
+                    return Err(error(&format!("path not in branch: {}", source)));
+                }
+                if command == "R" {
+                    remove_path(files, &source);
+                }
+                remove_path(files, &destination);
+                files.extend(copied);
+            }
+            "deleteall" => files.clear(),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn read_mark(&mut self) -> crate::Result<Option<usize>> {
+        match self.input.next_line_with_prefix("mark ")? {
+            Some(mark) => Ok(Some(parse_mark(&mark)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn resolve_data_ref(&self, data_ref: &str) -> crate::Result<ObjectId> {
+        match data_ref.strip_prefix(':') {
+            Some(mark) => self
+                .marks
+                .get(&parse_mark(mark)?)
+                .cloned()
+                .ok_or_else(|| error(&format!("mark :{} not declared", mark))),
+            None => ObjectId::from_sha(data_ref).map_err(|reason| error(&reason)),
+        }
+    }
+
+    /// Resolve a mark, a commit id, or a branch that is being imported or already exists.
+    fn resolve_commit_ish(&self, commit_ish: &str) -> crate::Result<ObjectId> {
+        if commit_ish.starts_with(':') {
+            return self.resolve_data_ref(commit_ish);
+        }
+        if let Ok(object_id) = ObjectId::from_sha(commit_ish) {
+            return Ok(object_id);
+        }
+        match self.branch_tip(commit_ish)? {
+            Some(object_id) => Ok(object_id),
+            None => Revision::parse(commit_ish)?.resolve(self.repository),
+        }
+    }
+
+    /// The commit that the branch points to, as changed by the stream so far.
+    fn branch_tip(&self, ref_name: &str) -> crate::Result<Option<ObjectId>> {
+        match self.branches.get(ref_name) {
+            Some(tip) => Ok(tip.clone()),
+            None => RefHandler::new(self.repository).read_ref(ref_name),
+        }
+    }
+
+    fn load_files(&self, commit_id: &ObjectId) -> crate::Result<Files> {
+        let database = &self.repository.database;
+        let tree = database.peel_to_tree(commit_id)?;
+        let mut entries = vec![];
+        database.extract_entries_from_tree(String::new(), &tree, &mut entries)?;
+        Ok(entries
+            .into_iter()
+            .map(|(path, entry)| (path, (entry.mode, entry.object_id)))
+            .collect())
+    }
+
+    /// Point the refs to the commits they ended up at, skipping refs that would lose commits
+    /// unless forced.
+    fn update_refs(&self, writer: &mut dyn OutputWriter) -> crate::Result<bool> {
+        let ref_handler = RefHandler::new(self.repository);
+        let mut transaction = ref_handler.transaction();
+        let mut all_updated = true;
+        for (ref_name, tip) in &self.branches {
+            let Some(tip) = tip else {
+                continue;
+            };
+            let old_tip = ref_handler.read_ref(ref_name)?;
+            if let Some(old_tip) = &old_tip {
+                let is_fast_forward =
+                    rev_walk::merge_base(self.repository, old_tip, tip)?.as_ref() == Some(old_tip);
+                if !self.force && !is_fast_forward {
+                    writer.writeln(format!(
+                        "warning: Not updating {} (new tip {} does not contain {})",
+                        ref_name, tip, old_tip
+                    ))?;
+                    all_updated = false;
+                    continue;
+                }
+            }
+            transaction = transaction.update(ref_name, tip, old_tip.into());
+        }
+        transaction.commit()?;
+        Ok(all_updated)
+    }
+}
+
+/// Reads the lines and data of a stream, where a line can be put back to be read by the next
+/// command.
+struct StreamReader<'a> {
+    input: &'a mut dyn BufRead,
+    pending_line: Option<String>,
+}
+
+impl StreamReader<'_> {
+    fn next_line(&mut self) -> crate::Result<Option<String>> {
+        if let Some(line) = self.pending_line.take() {
+            return Ok(Some(line));
+        }
+        let mut line = vec![];
+        if self.input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        String::from_utf8(line)
+            .map(Some)
+            .map_err(|_| error("command is not valid UTF-8"))
+    }
+
+    /// Read the next line if it starts with the prefix, and return the rest of it. Otherwise, the
+    /// line is left for the next read.
+    fn next_line_with_prefix(&mut self, prefix: &str) -> crate::Result<Option<String>> {
+        match self.next_line()? {
+            Some(line) => match line.strip_prefix(prefix) {
+                Some(rest) => Ok(Some(rest.to_owned())),
+                None => {
+                    self.pending_line = Some(line);
+                    Ok(None)
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn skip_line_with_prefix(&mut self, prefix: &str) -> crate::Result<()> {
+        self.next_line_with_prefix(prefix)?;
+        Ok(())
+    }
+
+    /// Read a `data` command, either with the exact number of bytes (`data 5`) or delimited by a
+    /// line (`data <<EOF`), and an optional line feed after it.
+    fn read_data(&mut self) -> crate::Result<Vec<u8>> {
+        let header = self
+            .next_line_with_prefix("data ")?
+            .ok_or_else(|| error("expected data command"))?;
+        let content = match header.strip_prefix("<<") {
+            Some(delimiter) => {
+                let mut content = vec![];
+                loop {
+                    let line = self.next_line()?.ok_or_else(|| {
+                        error(&format!("EOF in data (terminator '{}')", delimiter))
+                    })?;
+                    if line == delimiter {
+                        break;
+                    }
+                    content.extend_from_slice(line.as_bytes());
+                    content.push(b'\n');
+                }
+                content
+            }
+            None => {
+                let size = header
+                    .parse::<u64>()
+                    .map_err(|_| error(&format!("invalid data size: {}", header)))?;
+                let mut content = vec![];
+                self.input.take(size).read_to_end(&mut content)?;
+                if content.len() as u64 != size {
+                    return Err(error("EOF in data"));
+                }
+                content
+            }
+        };
+        match self.next_line()? {
+            Some(line) if line.is_empty() => (),
+            line => self.pending_line = line,
+        }
+        Ok(content)
+    }
+}
+
+/// Parse an identity with a raw date, as in `Jane <jane@example.com> 1700000000 +0100`.
+fn parse_signature(signature: &str) -> crate::Result<(Author, u64, String)> {
+    let invalid = || error(&format!("invalid signature: {}", signature));
+    let (identity, date) = signature.rsplit_once('>').ok_or_else(invalid)?;
+    let (name, email) = identity.split_once('<').ok_or_else(invalid)?;
+    let (timestamp, timezone) = date.trim().split_once(' ').ok_or_else(invalid)?;
+    let author = Author {
+        name: name.trim().to_owned(),
+        email: email.to_owned(),
+    };
+    let timestamp = timestamp.parse().map_err(|_| invalid())?;
+    Ok((author, timestamp, timezone.to_owned()))
+}
+
+fn parse_mark(mark: &str) -> crate::Result<usize> {
+    mark.trim_start_matches(':')
+        .parse()
+        .map_err(|_| error(&format!("invalid mark: {}", mark)))
+}
+
+fn parse_mode(mode: &str) -> crate::Result<FileMode> {
+    match mode {
+        "644" | "100644" => Ok(FileMode::Regular),
+        "755" | "100755" => Ok(FileMode::Executable),
+        "160000" => Ok(FileMode::Gitlink),
+        _ => Err(error(&format!("unsupported file mode: {}", mode))),
+    }
+}
+
+/// Parse a path that is either quoted like a C string or unquoted. An unquoted path ends at the
+/// end of the line if it's the last argument, and at the first space otherwise. Returns the path
+/// and the rest of the line.
+fn parse_path(s: &str, is_last: bool) -> crate::Result<(String, &str)> {
+    let Some(quoted) = s.strip_prefix('"') else {
+        let (path, rest) = match s.split_once(' ').filter(|_| !is_last) {
+            Some((path, rest)) => (path, rest),
+            None => (s, ""),
+        };
+        return Ok((check_path(path.to_owned())?, rest));
+    };
+
+    let invalid = || error(&format!("invalid quoted path: {}", s));
+    let mut bytes = vec![];
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let path = String::from_utf8(bytes).map_err(|_| invalid())?;
+                return Ok((check_path(path)?, &quoted[i + 1..]));
+            }
+            '\\' => match chars.next().ok_or_else(invalid)?.1 {
+                'n' => bytes.push(b'\n'),
+                't' => bytes.push(b'\t'),
+                c @ '0'..='7' => {
+                    let mut value = c.to_digit(8).unwrap();
+                    for _ in 0..2 {
+                        let digit = chars.next().and_then(|(_, c)| c.to_digit(8));
+                        value = value * 8 + digit.ok_or_else(invalid)?;
+                    }
+                    bytes.push(value as u8);
+                }
+                c => {
+                    let mut buffer = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+            },
+            c => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+        }
+    }
+    Err(invalid())
+}
+
+/// Check that the path can be written to a tree: it must be relative and can't have empty, `.`,
+/// `..` or `.git` components.
+fn check_path(path: String) -> crate::Result<String> {
+    let is_valid = path.split('/').all(|component| {
+        !matches!(component, "" | "." | "..") && !component.eq_ignore_ascii_case(".git")
+    });
+    match is_valid {
+        true => Ok(path),
+        false => Err(error(&format!("invalid path '{}'", path))),
+    }
+}
+
+/// Remove the file at the path, or all files in the directory at the path.
+fn remove_path(files: &mut Files, path: &str) {
+    let directory = format!("{}/", path);
+    files.retain(|file, _| file != path && !file.starts_with(&directory));
+}
+
+fn error(message: &str) -> crate::Error {
+    crate::Error::Fatal(None, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("a b c", true).unwrap(), ("a b c".to_owned(), ""));
+        assert_eq!(parse_path("a b c", false).unwrap(), ("a".to_owned(), "b c"));
+        assert_eq!(
+            parse_path("\"a \\\"b\\\"\\n\\303\\251\" c", false).unwrap(),
+            ("a \"b\"\né".to_owned(), " c")
+        );
+    }
+
+    #[test]
+    fn test_parse_path_rejects_paths_outside_of_tree() {
+        for path in [
+            "../escaped",
+            "/absolute",
+            "a//b",
+            "a/./b",
+            "",
+            ".git/config",
+            "a/.GIT",
+        ] {
+            assert!(parse_path(path, true).is_err(), "accepted {:?}", path);
+        }
+        assert!(parse_path("\"a/../b\" c", false).is_err());
+    }
+}
@@ -2,12 +2,14 @@ use std::{
     fs::{self, File, OpenOptions},
     io,
     io::{Read, Write},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
 use walkdir::{DirEntry, WalkDir};
 
 use crate::add::GITIGNORE;
+use crate::submodule::GITMODULES;
+use crate::trace;
 
 pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
     let mut file = File::open(path)?;
@@ -33,47 +35,42 @@ pub fn atomic_write(path: &Path, content: &[u8]) -> io::Result<()> {
     fs::rename(&buffer_file, path)
 }
 
-/// Create a new file and write the content to it. Fail if the file already exists.
-pub fn create_file(path: &Path, content: &[u8]) -> io::Result<()> {
-    let mut file = OpenOptions::new().create_new(true).write(true).open(path)?;
-    file.write_all(content)
-}
-
 /// Struct that enables synchronized atomic writing to files. On acquiring with a lock with
 /// [`LockFile::acquire`] an empty lockfile is created in the file system. You can then use
 /// [`LockFile::write`] to write content to the lockfile.
 ///
 /// When the [`LockFile`] goes out of scope, the lockfile itself is renamed to the target file for
 /// which the lock was acquired. Renames are atomic operations, so there is no risk that someone
-/// reading the file without acquiring the lock gets a partially written result.
+/// reading the file without acquiring the lock gets a partially written result. To decide when the
+/// lock is released, use [`LockFile::commit`] or [`LockFile::rollback`] instead.
 pub struct LockFile {
     path: PathBuf,
     lockfile: File,
     lockfile_path: PathBuf,
     has_write: bool,
+    is_released: bool,
 }
 
 impl LockFile {
+    /// Acquire the lock by creating `<path>.lock`, which fails if someone else holds the lock.
     pub fn acquire(path: &PathBuf) -> crate::Result<LockFile> {
-        let base_extension = String::from("lock");
-        let lockfile_extension = match path.extension() {
-            Some(ext) => format!("{:?}.{}", ext, base_extension),
-            None => base_extension,
-        };
-        let mut lockfile_path = PathBuf::from(path);
-        lockfile_path.set_extension(lockfile_extension);
+        let mut lockfile_path = path.clone().into_os_string();
+        lockfile_path.push(".lock");
+        let lockfile_path = PathBuf::from(lockfile_path);
 
         let lockfile_result = OpenOptions::new()
             .create_new(true)
             .write(true)
             .open(&lockfile_path);
         let lockfile = LockFile::handle_lockfile_create_failure(lockfile_result, &lockfile_path)?;
+        trace::trace("lock", format_args!("acquired {}", lockfile_path.display()));
 
         Ok(LockFile {
             path: path.to_owned(),
             lockfile,
             lockfile_path,
             has_write: false,
+            is_released: false,
         })
     }
 
@@ -82,6 +79,25 @@ impl LockFile {
         self.lockfile.write_all(text)
     }
 
+    /// Rename the lockfile to the target file now, reporting a failure rather than panicking.
+    pub fn commit(mut self) -> io::Result<()> {
+        self.is_released = true;
+        fs::rename(&self.lockfile_path, &self.path)?;
+        trace::trace("lock", format_args!("committed {}", self.path.display()));
+        Ok(())
+    }
+
+    /// Remove the lockfile and anything written to it, leaving the target file as it was.
+    pub fn rollback(mut self) -> io::Result<()> {
+        self.is_released = true;
+        fs::remove_file(&self.lockfile_path)?;
+        trace::trace(
+            "lock",
+            format_args!("released {}", self.lockfile_path.display()),
+        );
+        Ok(())
+    }
+
     fn handle_lockfile_create_failure(
         result: Result<File, io::Error>,
         lockfile_path: &Path,
@@ -101,15 +117,184 @@ impl LockFile {
 
 impl Drop for LockFile {
     fn drop(&mut self) {
+        if self.is_released {
+            return;
+        }
         let error_message = format!("Failed to commit changes for {:?}", self.lockfile);
         if self.has_write {
             fs::rename(&self.lockfile_path, &self.path).expect(&error_message);
+            trace::trace("lock", format_args!("committed {}", self.path.display()));
         } else {
             fs::remove_file(&self.lockfile_path).expect(&error_message);
+            trace::trace(
+                "lock",
+                format_args!("released {}", self.lockfile_path.display()),
+            );
         }
     }
 }
 
+pub trait AsVec<T> {
+    fn as_vec(&self) -> Vec<T>;
+}
+
+/// A resource backed by a lockfile. The final write is atomically transferred to the original file
+/// when this struct is destroyed.
+///
+/// Do note that any intermediate writes are simply discarded.
+pub struct LockFileResource<T: AsVec<u8>> {
+    lockfile: LockFile,
+    resource: T,
+}
+
+impl<T: AsVec<u8>> LockFileResource<T> {
+    pub fn new(lockfile: LockFile, resource: T) -> LockFileResource<T> {
+        LockFileResource { lockfile, resource }
+    }
+
+    /// Write the resource to the lockfile. The final write to the lockfile are committed to the
+    /// original resource once this struct is destroyed.
+    pub fn write(&mut self) -> io::Result<()> {
+        self.lockfile.write(&self.resource.as_vec())
+    }
+}
+
+impl<T: AsVec<u8>> AsMut<T> for LockFileResource<T> {
+    fn as_mut(&mut self) -> &mut T {
+        &mut self.resource
+    }
+}
+
+/// Lexically normalize the path by removing `.` components and resolving `..` components against
+/// their preceding components. Symbolic links are not taken into account.
+pub fn normalize_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut normalized_path = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized_path.pop();
+            }
+            component => normalized_path.push(component),
+        }
+    }
+    normalized_path
+}
+
+/// Recursively copy the files in the source directory into the destination directory, creating
+/// directories as needed. Files that already exist in the destination are left as they are.
+pub fn copy_directory(source: &Path, destination: &Path) -> io::Result<()> {
+    for source_file in WalkDir::new(source)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let relative_path = source_file
+            .path()
+            .strip_prefix(source)
+            .expect("walked files are inside the source directory");
+        let destination_file = destination.join(relative_path);
+        if destination_file.exists() {
+            continue;
+        }
+        if let Some(parent) = destination_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source_file.path(), &destination_file)?;
+    }
+    Ok(())
+}
+
+/// Remove the directories containing the path that have become empty, up to but not including
+/// the root.
+pub fn remove_empty_parents(path: &Path, root: &Path) -> io::Result<()> {
+    let mut current = path.parent();
+    while let Some(directory) = current {
+        if directory == root || !directory.is_dir() || fs::read_dir(directory)?.next().is_some() {
+            break;
+        }
+        fs::remove_dir(directory)?;
+        current = directory.parent();
+    }
+    Ok(())
+}
+
+pub fn resolve_paths<F>(root_path: &Path, filter: F) -> Vec<PathBuf>
+where
+    F: Fn(&DirEntry) -> bool,
+{
+    walk_paths(root_path, |entry| {
+        filter(entry) && !(is_hidden(entry) || is_ignored(entry))
+    })
+}
+
+/// Walk the paths below the root that pass the filter, which also applies to the root itself.
+fn walk_paths<F>(root_path: &Path, filter: F) -> Vec<PathBuf>
+where
+    F: Fn(&DirEntry) -> bool,
+{
+    if root_path.is_dir() {
+        let mut walker = WalkDir::new(root_path).into_iter().filter_entry(filter);
+
+        let mut paths = vec![];
+        while let Some(maybe_entry) = walker.next() {
+            let Ok(entry) = maybe_entry else {
+                continue;
+            };
+            if entry.depth() == 0 {
+                continue;
+            }
+            // the contents of nested repositories, such as submodules, belong to them
+            if entry.file_type().is_dir() && entry.path().join(".git").exists() {
+                walker.skip_current_dir();
+            }
+            paths.push(entry.into_path());
+        }
+        paths
+    } else {
+        vec![root_path.to_owned()]
+    }
+}
+
+pub fn resolve_files(path: &Path) -> Vec<PathBuf> {
+    resolve_paths(path, |_| true)
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Resolve the files like [`resolve_files`], but include the ignored ones, as everything in an
+/// ignored directory is ignored.
+pub fn resolve_ignored_files(path: &Path) -> Vec<PathBuf> {
+    walk_paths(path, |entry| !is_hidden(entry))
+        .into_iter()
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Check whether a file name is skipped when walking the worktree, either because the file is
+/// hidden or because it's ignored.
+pub fn is_excluded_name(name: &str) -> bool {
+    is_hidden_name(name) || is_ignored_name(name)
+}
+
+/// Check whether a file name is ignored, such that the file is neither tracked nor untracked.
+pub fn is_ignored_name(name: &str) -> bool {
+    GITIGNORE.contains(&name)
+}
+
+fn is_hidden_name(name: &str) -> bool {
+    name != "." && name != GITMODULES && name.starts_with('.')
+}
+
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.file_name().to_str().is_some_and(is_hidden_name)
+}
+
+fn is_ignored(entry: &DirEntry) -> bool {
+    entry.file_name().to_str().is_some_and(is_ignored_name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,8 +310,8 @@ mod tests {
         let first_lockfile = LockFile::acquire(&file);
         let second_lockfile = LockFile::acquire(&file);
 
-        assert_eq!(first_lockfile.is_ok(), true);
-        assert_eq!(second_lockfile.is_err(), true);
+        assert!(first_lockfile.is_ok());
+        assert!(second_lockfile.is_err());
     }
 
     #[test]
@@ -142,7 +327,7 @@ mod tests {
         }
         let second_lockfile = LockFile::acquire(&file);
 
-        assert_eq!(second_lockfile.is_ok(), true);
+        assert!(second_lockfile.is_ok());
     }
 
     #[test]
@@ -183,73 +368,3 @@ mod tests {
         )
     }
 }
-
-pub trait AsVec<T> {
-    fn as_vec(&self) -> Vec<T>;
-}
-
-/// A resource backed by a lockfile. The final write is atomically transferred to the original file
-/// when this struct is destroyed.
-///
-/// Do note that any intermediate writes are simply discarded.
-pub struct LockFileResource<T: AsVec<u8>> {
-    lockfile: LockFile,
-    resource: T,
-}
-
-impl<T: AsVec<u8>> LockFileResource<T> {
-    pub fn new(lockfile: LockFile, resource: T) -> LockFileResource<T> {
-        LockFileResource { lockfile, resource }
-    }
-
-    /// Write the resource to the lockfile. The final write to the lockfile are committed to the
-    /// original resource once this struct is destroyed.
-    pub fn write(&mut self) -> io::Result<()> {
-        self.lockfile.write(&self.resource.as_vec())
-    }
-}
-
-impl<T: AsVec<u8>> AsMut<T> for LockFileResource<T> {
-    fn as_mut(&mut self) -> &mut T {
-        &mut self.resource
-    }
-}
-
-pub fn resolve_paths<F>(root_path: &Path, filter: F) -> Vec<PathBuf>
-where
-    F: Fn(&DirEntry) -> bool,
-{
-    if root_path.is_dir() {
-        WalkDir::new(root_path)
-            .into_iter()
-            .filter_entry(|entry| filter(entry) && !(is_hidden(entry) || is_ignored(entry)))
-            .flat_map(|maybe_entry| maybe_entry.map(|entry| PathBuf::from(entry.path())))
-            .filter(|path| path != root_path)
-            .collect()
-    } else {
-        vec![root_path.to_owned()]
-    }
-}
-
-pub fn resolve_files(path: &Path) -> Vec<PathBuf> {
-    resolve_paths(path, |_| true)
-        .into_iter()
-        .filter(|path| path.is_file())
-        .collect()
-}
-
-fn is_hidden(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s != "." && s.starts_with('.'))
-        .unwrap_or(false)
-}
-
-fn is_ignored(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| GITIGNORE.contains(&s))
-        .unwrap_or(false)
-}
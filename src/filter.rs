@@ -0,0 +1,375 @@
+//! The filters that file content passes through on its way between the worktree and the object
+//! database. Text files are stored with LF line endings and can be checked out with CRLF line
+//! endings, as configured by `core.autocrlf`, `core.eol` and the `text` and `eol` attributes in
+//! `.gitattributes`, so that files edited on Windows don't show up as modified on every line.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use regex::Regex;
+
+use crate::pathspec;
+use crate::workspace::Repository;
+
+const ATTRIBUTES_FILE: &str = ".gitattributes";
+
+/// The number of leading bytes that are looked at to tell whether content is binary.
+const BINARY_DETECTION_LIMIT: usize = 8000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum AutoCrlf {
+    #[default]
+    False,
+    True,
+    Input,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Text {
+    Set,
+    Unset,
+    Auto,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum Eol {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+/// A line of an attributes file. The attributes are `None` if the line doesn't mention them, and
+/// `Some(None)` if it resets them to unspecified with `!text` or `!eol`.
+#[derive(Debug)]
+struct AttributeRule {
+    pattern: Regex,
+    /// Patterns without a slash match the file name in any directory below the attributes file.
+    /// Other patterns match the path relative to the worktree root.
+    matches_file_name: bool,
+    text: Option<Option<Text>>,
+    eol: Option<Option<Eol>>,
+}
+
+/// How the content of a path is converted, if at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Conversion {
+    text: Text,
+    eol: Eol,
+}
+
+/// The line ending conversion of a repository.
+#[derive(Debug, Default)]
+pub struct Filters {
+    autocrlf: AutoCrlf,
+    eol: Eol,
+    worktree_root: PathBuf,
+    /// The rules of `info/attributes` in the git directory.
+    info_rules: Vec<AttributeRule>,
+    /// The rules of the `.gitattributes` file in each directory, relative to the worktree root,
+    /// read when a path in the directory is first converted.
+    directory_rules: Mutex<HashMap<PathBuf, Arc<Vec<AttributeRule>>>>,
+}
+
+impl Filters {
+    /// Load the configuration and the attributes in `info/attributes` in the git directory and in
+    /// `.gitattributes` at the root of the worktree. The `.gitattributes` files of other
+    /// directories are read as paths in them are converted. Missing files are ignored.
+    pub fn load(repository: &Repository) -> crate::Result<Filters> {
+        let config = repository.config()?;
+        let autocrlf = match config.get_string("core.autocrlf").as_deref() {
+            Some("input") => AutoCrlf::Input,
+            _ if config.get_bool("core.autocrlf")? == Some(true) => AutoCrlf::True,
+            _ => AutoCrlf::False,
+        };
+        let eol = match config.get_string("core.eol").as_deref() {
+            Some("crlf") => Eol::Crlf,
+            _ => Eol::Lf,
+        };
+
+        let worktree_root = repository.worktree().root().to_owned();
+        let root_rules = read_if_exists(&worktree_root.join(ATTRIBUTES_FILE))?
+            .map(|content| parse_rules(Path::new(""), &content))
+            .unwrap_or_default();
+        let info_rules = read_if_exists(&repository.common_dir().join("info").join("attributes"))?
+            .map(|content| parse_rules(Path::new(""), &content))
+            .unwrap_or_default();
+
+        Ok(Filters {
+            autocrlf,
+            eol,
+            worktree_root,
+            info_rules,
+            directory_rules: Mutex::new(HashMap::from([(PathBuf::new(), Arc::new(root_rules))])),
+        })
+    }
+
+    /// Whether content of the path, relative to the worktree root, may be changed on its way to
+    /// the database. Content that isn't can be streamed into the database as it is.
+    pub fn converts_to_database(&self, path: &Path) -> bool {
+        self.conversion(path).is_some()
+    }
+
+    /// Convert worktree content of the path into what is stored in the database, which turns
+    /// CRLF line endings into LF in text files.
+    pub fn to_database(&self, path: &Path, content: Vec<u8>) -> Vec<u8> {
+        match self.conversion(path) {
+            Some(conversion) if conversion.text == Text::Set || !is_binary(&content) => {
+                crlf_to_lf(content)
+            }
+            _ => content,
+        }
+    }
+
+    /// Convert database content of the path into what is written to the worktree, which turns LF
+    /// line endings into CRLF in text files that are checked out with CRLF. Files that are only
+    /// detected as text are left alone if they already contain a CR.
+    pub fn to_worktree<'a>(&self, path: &Path, content: &'a [u8]) -> Cow<'a, [u8]> {
+        match self.conversion(path) {
+            Some(Conversion {
+                text,
+                eol: Eol::Crlf,
+            }) if text == Text::Set || (!is_binary(content) && !content.contains(&b'\r')) => {
+                Cow::Owned(lf_to_crlf(content))
+            }
+            _ => Cow::Borrowed(content),
+        }
+    }
+
+    /// Resolve the attributes of the path and fall back on `core.autocrlf` for files without a
+    /// `text` attribute. As in git, the attributes files of deeper directories take precedence
+    /// over those above them, and `info/attributes` over all of them. Returns `None` if the
+    /// content is never converted.
+    fn conversion(&self, path: &Path) -> Option<Conversion> {
+        let path_str = path.to_string_lossy();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let (mut text, mut eol) = (None, None);
+        let directories = path.ancestors().skip(1).collect::<Vec<_>>();
+        let directory_rules = directories
+            .into_iter()
+            .rev()
+            .map(|directory| self.directory_rules(directory))
+            .collect::<Vec<_>>();
+        let rules = directory_rules
+            .iter()
+            .flat_map(|rules| rules.iter())
+            .chain(&self.info_rules);
+        for rule in rules {
+            let subject = if rule.matches_file_name {
+                &file_name
+            } else {
+                &path_str
+            };
+            if rule.pattern.is_match(subject) {
+                text = rule.text.unwrap_or(text);
+                eol = rule.eol.unwrap_or(eol);
+            }
+        }
+
+        let text = match (text, eol) {
+            (Some(Text::Unset), _) => return None,
+            (Some(text), _) => text,
+            (None, Some(_)) => Text::Set,
+            (None, None) if self.autocrlf != AutoCrlf::False => Text::Auto,
+            (None, None) => return None,
+        };
+        let eol = eol.unwrap_or(match self.autocrlf {
+            AutoCrlf::True => Eol::Crlf,
+            AutoCrlf::Input => Eol::Lf,
+            AutoCrlf::False => self.eol,
+        });
+        Some(Conversion { text, eol })
+    }
+
+    /// The rules of the attributes file in the directory, relative to the worktree root. A file
+    /// that can't be read is treated as missing, as conversions can't fail.
+    fn directory_rules(&self, directory: &Path) -> Arc<Vec<AttributeRule>> {
+        let mut directory_rules = self.directory_rules.lock().unwrap();
+        let rules = directory_rules
+            .entry(directory.to_owned())
+            .or_insert_with(|| {
+                let path = self.worktree_root.join(directory).join(ATTRIBUTES_FILE);
+                let content = read_if_exists(&path).ok().flatten().unwrap_or_default();
+                Arc::new(parse_rules(directory, &content))
+            });
+        Arc::clone(rules)
+    }
+}
+
+/// Parse the lines of the attributes file in the directory, relative to the worktree root.
+fn parse_rules(directory: &Path, content: &str) -> Vec<AttributeRule> {
+    content
+        .lines()
+        .filter_map(|line| parse_rule(directory, line))
+        .collect()
+}
+
+/// Parse a line of the form `<pattern> <attribute>...` of the attributes file in the directory.
+/// Only the `text`, `eol` and `binary` attributes are kept, and lines without any of them are
+/// dropped along with comments.
+fn parse_rule(directory: &Path, line: &str) -> Option<AttributeRule> {
+    let mut words = line.split_whitespace();
+    let pattern = words.next().filter(|pattern| !pattern.starts_with('#'))?;
+
+    let (mut text, mut eol) = (None, None);
+    for attribute in words {
+        match attribute {
+            "text" => text = Some(Some(Text::Set)),
+            "-text" | "binary" => text = Some(Some(Text::Unset)),
+            "!text" => text = Some(None),
+            "text=auto" => text = Some(Some(Text::Auto)),
+            "eol=lf" => eol = Some(Some(Eol::Lf)),
+            "eol=crlf" => eol = Some(Some(Eol::Crlf)),
+            "!eol" => eol = Some(None),
+            _ => (),
+        }
+    }
+    if text.is_none() && eol.is_none() {
+        return None;
+    }
+
+    let matches_file_name = !pattern.contains('/');
+    let base_directory = if matches_file_name {
+        Path::new("")
+    } else {
+        directory
+    };
+    // like git, a pattern that can't be matched is ignored rather than failing every command
+    let pattern = pathspec::glob_to_regex(base_directory, pattern.trim_start_matches('/')).ok()?;
+    Some(AttributeRule {
+        pattern,
+        matches_file_name,
+        text,
+        eol,
+    })
+}
+
+/// Content is taken to be binary if it has a NUL byte near the start.
+fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_DETECTION_LIMIT)].contains(&0)
+}
+
+fn crlf_to_lf(content: Vec<u8>) -> Vec<u8> {
+    if !content.windows(2).any(|pair| pair == b"\r\n") {
+        return content;
+    }
+    let mut converted = Vec::with_capacity(content.len());
+    let mut bytes = content.iter().peekable();
+    while let Some(&byte) = bytes.next() {
+        if byte != b'\r' || bytes.peek() != Some(&&b'\n') {
+            converted.push(byte);
+        }
+    }
+    converted
+}
+
+fn lf_to_crlf(content: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(content.len() + content.len() / 32);
+    let mut previous = None;
+    for &byte in content {
+        if byte == b'\n' && previous != Some(b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+        previous = Some(byte);
+    }
+    converted
+}
+
+fn read_if_exists(path: &Path) -> crate::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(autocrlf: AutoCrlf, attributes: &str) -> Filters {
+        let worktree_root = rut_testhelpers::create_temporary_directory();
+        fs::write(worktree_root.join(ATTRIBUTES_FILE), attributes).unwrap();
+        Filters {
+            autocrlf,
+            eol: Eol::Lf,
+            worktree_root,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_autocrlf_converts_text_but_not_binary_content() {
+        let filters = filters(AutoCrlf::True, "");
+        let path = Path::new("dir/file.txt");
+
+        assert_eq!(filters.to_database(path, b"a\r\nb\r\n".to_vec()), b"a\nb\n");
+        assert_eq!(filters.to_worktree(path, b"a\nb\n").as_ref(), b"a\r\nb\r\n");
+        assert_eq!(filters.to_worktree(path, b"a\r\nb\n").as_ref(), b"a\r\nb\n");
+        assert_eq!(filters.to_database(path, b"\0\r\n".to_vec()), b"\0\r\n");
+        assert_eq!(filters.to_worktree(path, b"\0\n").as_ref(), b"\0\n");
+    }
+
+    #[test]
+    fn test_attributes_take_precedence_over_autocrlf() {
+        let filters = filters(
+            AutoCrlf::Input,
+            "# comment\n*.bat text eol=crlf\n/scripts/*.sh -text\n*.png binary\n",
+        );
+
+        assert_eq!(
+            filters
+                .to_worktree(Path::new("dir/run.bat"), b"a\n")
+                .as_ref(),
+            b"a\r\n"
+        );
+        assert_eq!(
+            filters
+                .to_worktree(Path::new("dir/file.txt"), b"a\n")
+                .as_ref(),
+            b"a\n"
+        );
+        assert_eq!(
+            filters.to_database(Path::new("dir/file.txt"), b"a\r\n".to_vec()),
+            b"a\n"
+        );
+        assert!(!filters.converts_to_database(Path::new("scripts/run.sh")));
+        assert!(filters.converts_to_database(Path::new("other/scripts/run.sh")));
+        assert!(!filters.converts_to_database(Path::new("image.png")));
+    }
+
+    #[test]
+    fn test_rule_with_invalid_pattern_is_skipped() {
+        let filters = filters(AutoCrlf::False, "[z-a] text\n[!]x] text\n");
+
+        assert_eq!(filters.directory_rules(Path::new("")).len(), 1);
+        assert!(filters.converts_to_database(Path::new("a")));
+    }
+
+    #[test]
+    fn test_deeper_attributes_files_take_precedence() {
+        let filters = filters(AutoCrlf::False, "*.txt text\n/top.sh text\n");
+        let sub_directory = filters.worktree_root.join("sub");
+        fs::create_dir_all(sub_directory.join("deeper")).unwrap();
+        fs::write(
+            sub_directory.join(ATTRIBUTES_FILE),
+            "*.txt -text\n/run.sh text\n",
+        )
+        .unwrap();
+
+        assert!(filters.converts_to_database(Path::new("file.txt")));
+        assert!(!filters.converts_to_database(Path::new("sub/file.txt")));
+        assert!(!filters.converts_to_database(Path::new("sub/deeper/file.txt")));
+        assert!(filters.converts_to_database(Path::new("sub/run.sh")));
+        assert!(!filters.converts_to_database(Path::new("run.sh")));
+        assert!(!filters.converts_to_database(Path::new("sub/deeper/run.sh")));
+        assert!(!filters.converts_to_database(Path::new("sub/top.sh")));
+    }
+}
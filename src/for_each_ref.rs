@@ -0,0 +1,241 @@
+//! Listing refs in a custom format, such as `%(refname:short) %(objectname)`, sorted by any of
+//! the fields that the format can show.
+
+use crate::{
+    log,
+    objects::{Commit, ObjectId, ObjectType},
+    output::OutputWriter,
+    refs::{self, RefHandler},
+    workspace::Repository,
+};
+
+/// The format of each ref when no other format is given.
+pub const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// The format of each ref, with `%(<field>)` placeholders. [`DEFAULT_FORMAT`] is used if
+    /// not given.
+    #[builder(default)]
+    pub format: Option<String>,
+
+    /// The fields to sort the refs by, where the last field is the primary key. A field prefixed
+    /// with `-` sorts in descending order. Refs are sorted by name if not given.
+    #[builder(default)]
+    pub sort: Vec<String>,
+
+    /// Stop after showing this many refs.
+    #[builder(default)]
+    pub count: Option<usize>,
+}
+
+/// A field of a ref that can be shown with `%(<field>)` or sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    RefName,
+    RefNameShort,
+    ObjectName,
+    ObjectNameShort,
+    ObjectType,
+    Subject,
+    AuthorName,
+    AuthorDate,
+    CommitterName,
+    CommitterDate,
+    /// `*` if the ref is the branch that HEAD points to, and a space otherwise.
+    Head,
+}
+
+impl Field {
+    fn parse(name: &str) -> crate::Result<Field> {
+        let field = match name {
+            "refname" => Field::RefName,
+            "refname:short" => Field::RefNameShort,
+            "objectname" => Field::ObjectName,
+            "objectname:short" => Field::ObjectNameShort,
+            "objecttype" => Field::ObjectType,
+            "subject" => Field::Subject,
+            "authorname" => Field::AuthorName,
+            "authordate" => Field::AuthorDate,
+            "committername" => Field::CommitterName,
+            "committerdate" => Field::CommitterDate,
+            "HEAD" => Field::Head,
+            _ => {
+                let message = format!("unknown field name: {}", name);
+                return Err(crate::Error::Fatal(None, message));
+            }
+        };
+        Ok(field)
+    }
+}
+
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+/// A ref along with the object it points to, which is loaded once for all of its fields.
+struct RefDetails {
+    name: String,
+    object_id: ObjectId,
+    object_type: ObjectType,
+    /// The commit that the ref points to, if it points to one.
+    commit: Option<Commit>,
+    is_head: bool,
+}
+
+/// The value of a field that refs are sorted by. Dates are sorted by time, not as text.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum SortValue {
+    Text(String),
+    Time(u64),
+}
+
+/// Show the refs whose names start with any of the patterns, or all refs if there are none, in
+/// the format and order given by the options. A pattern matches whole components of ref names,
+/// so `refs/heads` matches `refs/heads/main` but not `refs/headsup`.
+pub fn for_each_ref(
+    patterns: &[String],
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let format = parse_format(options.format.as_deref().unwrap_or(DEFAULT_FORMAT))?;
+    let sort_keys = options
+        .sort
+        .iter()
+        .map(|key| match key.strip_prefix('-') {
+            Some(key) => Ok((Field::parse(key)?, true)),
+            None => Ok((Field::parse(key)?, false)),
+        })
+        .collect::<crate::Result<Vec<_>>>()?;
+
+    let refs = RefHandler::new(repository);
+    let current_branch = refs
+        .current_branch()?
+        .map(|branch| format!("refs/heads/{}", branch));
+    let mut details = vec![];
+    for (name, object_id) in refs.list_all_refs()? {
+        let is_matching = patterns.is_empty()
+            || patterns.iter().any(|pattern| {
+                let pattern = pattern.trim_end_matches('/');
+                name == pattern || name.starts_with(&format!("{}/", pattern))
+            });
+        if !is_matching {
+            continue;
+        }
+
+        let (object_type, _) = repository.database.load_object(&object_id)?;
+        let commit = match object_type {
+            ObjectType::Commit => Some(repository.database.load_commit(&object_id)?),
+            _ => None,
+        };
+        details.push(RefDetails {
+            is_head: current_branch.as_ref() == Some(&name),
+            name,
+            object_id,
+            object_type,
+            commit,
+        });
+    }
+
+    // sorting by each key in turn is stable, which makes the last key the primary one
+    for (field, is_descending) in sort_keys {
+        details.sort_by(|lhs, rhs| {
+            let ordering =
+                sort_value(field, lhs, repository).cmp(&sort_value(field, rhs, repository));
+            if is_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    for ref_details in details.iter().take(options.count.unwrap_or(usize::MAX)) {
+        let line = format
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.clone(),
+                Segment::Field(field) => expand_field(*field, ref_details, repository),
+            })
+            .collect::<String>();
+        writer.writeln(line)?;
+    }
+
+    Ok(())
+}
+
+/// Parse a format into literal text and `%(<field>)` placeholders. `%%` is a literal `%`.
+fn parse_format(format: &str) -> crate::Result<Vec<Segment>> {
+    let mut segments = vec![];
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(position) = rest.find('%') {
+        literal.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        if let Some(after_percent) = rest.strip_prefix('%') {
+            literal.push('%');
+            rest = after_percent;
+        } else if let Some((name, after_field)) = rest
+            .strip_prefix('(')
+            .and_then(|field| field.split_once(')'))
+        {
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(Segment::Field(Field::parse(name)?));
+            rest = after_field;
+        } else {
+            literal.push('%');
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+
+    Ok(segments)
+}
+
+fn expand_field(field: Field, details: &RefDetails, repository: &Repository) -> String {
+    let commit = details.commit.as_ref();
+    let date = |timestamp: fn(&Commit) -> u64| {
+        commit
+            .and_then(|commit| log::to_local_timestring(timestamp(commit)))
+            .unwrap_or_default()
+    };
+
+    match field {
+        Field::RefName => details.name.clone(),
+        Field::RefNameShort => refs::shorten_ref_name(&details.name).to_owned(),
+        Field::ObjectName => details.object_id.to_string(),
+        Field::ObjectNameShort => repository.database.abbreviate(&details.object_id),
+        Field::ObjectType => details.object_type.to_string(),
+        Field::Subject => commit
+            .map(|commit| log::split_message(&commit.message).0.to_owned())
+            .unwrap_or_default(),
+        Field::AuthorName => commit
+            .map(|commit| commit.author.name.clone())
+            .unwrap_or_default(),
+        Field::AuthorDate => date(|commit| commit.timestamp),
+        Field::CommitterName => commit
+            .map(|commit| commit.committer.name.clone())
+            .unwrap_or_default(),
+        Field::CommitterDate => date(|commit| commit.committer_timestamp),
+        Field::Head if details.is_head => String::from("*"),
+        Field::Head => String::from(" "),
+    }
+}
+
+fn sort_value(field: Field, details: &RefDetails, repository: &Repository) -> SortValue {
+    let commit = details.commit.as_ref();
+    match field {
+        Field::AuthorDate => SortValue::Time(commit.map_or(0, |commit| commit.timestamp)),
+        Field::CommitterDate => {
+            SortValue::Time(commit.map_or(0, |commit| commit.committer_timestamp))
+        }
+        field => SortValue::Text(expand_field(field, details, repository)),
+    }
+}
@@ -0,0 +1,185 @@
+//! Exporting commits as patches in the mbox format, which can be mailed or applied with `am`.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use chrono::DateTime;
+
+use crate::{
+    diff, log,
+    objects::{Commit, GitObject},
+    output::{Color, OutputWriter, Style},
+    refs::{Revision, RevisionRange},
+    rev_walk::RevWalk,
+    workspace::Repository,
+};
+
+/// The maximum length of the subject in the file name of a patch, as in Git.
+const MAX_SUBJECT_LENGTH: usize = 57;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// The directory to write the patches to, relative to the directory the user is working in.
+    /// Defaults to that directory.
+    #[builder(default)]
+    pub output_directory: Option<PathBuf>,
+}
+
+/// Write a patch file for each commit in the revision range, oldest first, and write the path of
+/// each file. A single revision selects the commits since that revision up to HEAD. Merge commits
+/// are skipped as they can't be expressed as a single patch.
+pub fn format_patch(
+    revision: &str,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let range = match RevisionRange::parse(revision)? {
+        RevisionRange::Single(since) => RevisionRange::Range(since, Revision::parse("HEAD")?),
+        range => range,
+    };
+    let mut commits = RevWalk::from_range(&range, repository)?
+        .filter(|commit| !matches!(commit, Ok(commit) if commit.parents.len() > 1))
+        .collect::<crate::Result<Vec<_>>>()?;
+    commits.reverse();
+
+    let worktree = repository.worktree();
+    let working_directory = worktree.root().join(worktree.prefix());
+    let output_directory = options.output_directory.as_deref().unwrap_or(Path::new(""));
+    fs::create_dir_all(working_directory.join(output_directory))?;
+    for (i, commit) in commits.iter().enumerate() {
+        let mut patch_writer = PatchWriter::default();
+        write_patch_file(commit, i + 1, commits.len(), repository, &mut patch_writer)?;
+
+        let (subject, _) = log::split_message(&commit.message);
+        let file_name = format!(
+            "{:04}-{}.patch",
+            i + 1,
+            sanitize_subject(subject, MAX_SUBJECT_LENGTH)
+        );
+        let path = output_directory.join(file_name);
+        fs::write(working_directory.join(&path), patch_writer.content)?;
+        writer.writeln(path.display().to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Write the commit as a mail with the headers that `am` reads, followed by a summary of the
+/// changes and the patch.
+fn write_patch_file(
+    commit: &Commit,
+    number: usize,
+    num_patches: usize,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let (subject, body) = log::split_message(&commit.message);
+    let prefix = if num_patches == 1 {
+        String::from("[PATCH]")
+    } else {
+        format!("[PATCH {}/{}]", number, num_patches)
+    };
+    let date = DateTime::parse_from_str(
+        &format!("{} {}", commit.timestamp, commit.timezone),
+        "%s %z",
+    )
+    .map_err(|_| {
+        let message = format!("invalid date in commit {}", commit.id());
+        crate::Error::Fatal(None, message)
+    })?;
+
+    // the date of the From line is fixed, as it only marks the start of a mail in the mbox
+    writer
+        .writeln(format!("From {} Mon Sep 17 00:00:00 2001", commit.id()))?
+        .writeln(format!("From: {}", commit.author))?
+        .writeln(format!("Date: {}", date.to_rfc2822()))?
+        .writeln(format!("Subject: {} {}", prefix, subject))?
+        .linefeed()?;
+    if !body.is_empty() {
+        writer.writeln(body.to_owned())?;
+    }
+    writer.writeln(String::from("---"))?;
+
+    let algorithm = diff::Algorithm::from_config(repository)?;
+    let file_diffs = diff::diff_commits(commit.parent(), commit.id(), repository)?;
+    diff::write_stat(&file_diffs, algorithm, writer)?;
+    writer.linefeed()?;
+    diff::write_patch(&file_diffs, diff::DEFAULT_CONTEXT_LINES, algorithm, writer)?;
+    writer
+        .writeln(String::from("-- "))?
+        .writeln(format!("rut {}", env!("CARGO_PKG_VERSION")))?
+        .linefeed()?;
+
+    Ok(())
+}
+
+/// Turn the subject into a file name like Git does, by replacing each run of characters other
+/// than letters, digits, `.` and `_` with a `-`, and truncating it to the maximum length.
+fn sanitize_subject(subject: &str, max_length: usize) -> String {
+    let mut sanitized = String::new();
+    let mut is_separated = false;
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            if is_separated && !sanitized.is_empty() {
+                sanitized.push('-');
+            }
+            if !(c == '.' && sanitized.ends_with('.')) {
+                sanitized.push(c);
+            }
+            is_separated = false;
+        } else {
+            is_separated = true;
+        }
+    }
+    sanitized.truncate(max_length);
+    sanitized.trim_end_matches(['-', '.']).to_owned()
+}
+
+/// Collects the content of a patch file, without formatting.
+#[derive(Default)]
+struct PatchWriter {
+    content: String,
+}
+
+impl OutputWriter for PatchWriter {
+    fn write(&mut self, content: String) -> io::Result<&mut dyn OutputWriter> {
+        self.content.push_str(&content);
+        Ok(self)
+    }
+
+    fn set_color(&mut self, _: Color) -> io::Result<&mut dyn OutputWriter> {
+        Ok(self)
+    }
+
+    fn set_style(&mut self, _: Style) -> io::Result<&mut dyn OutputWriter> {
+        Ok(self)
+    }
+
+    fn reset_formatting(&mut self) -> io::Result<&mut dyn OutputWriter> {
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_subject() {
+        assert_eq!(
+            sanitize_subject("Fix the bug in `parse()`, again...", 57),
+            "Fix-the-bug-in-parse-again"
+        );
+        assert_eq!(
+            sanitize_subject("  [WIP] v1.0 release", 57),
+            "WIP-v1.0-release"
+        );
+        assert_eq!(
+            sanitize_subject("Add a very long subject", 11),
+            "Add-a-very"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Checking the integrity of the object database: that objects hash to their ids, that the
+//! objects they point to exist, and which objects can't be reached from any ref.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::{
+    index::FileMode,
+    objects::{ObjectId, ObjectType},
+    output::OutputWriter,
+    refs::RefHandler,
+    workspace::{Database, Repository},
+    worktree,
+};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Report all unreachable objects, not only the dangling ones that no other unreachable
+    /// object points to.
+    #[builder(default)]
+    pub unreachable: bool,
+}
+
+/// An object that could be read, along with the objects it points to.
+struct CheckedObject {
+    object_type: ObjectType,
+    links: Vec<(ObjectType, ObjectId)>,
+}
+
+/// Check all loose objects and report corrupt objects, broken links to missing objects and
+/// unreachable objects. Returns whether the repository is healthy, i.e. whether there were no
+/// corrupt or missing objects. Unreachable objects don't make a repository unhealthy.
+pub fn fsck(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let database = &repository.database;
+    let mut is_healthy = true;
+
+    let mut objects = BTreeMap::new();
+    for object_id in database.list_objects()? {
+        match check_object(&object_id, repository) {
+            Ok(Some(object)) => {
+                objects.insert(object_id, object);
+            }
+            Ok(None) => {
+                writer.writeln(format!("error: hash mismatch for {}", object_id))?;
+                is_healthy = false;
+            }
+            Err(crate::Error::CorruptObject(_, reason)) => {
+                writer.writeln(format!(
+                    "error: object {} is corrupt: {}",
+                    object_id, reason
+                ))?;
+                is_healthy = false;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+
+    let mut missing = BTreeMap::new();
+    for (object_id, object) in &objects {
+        for (link_type, link_id) in &object.links {
+            if !objects.contains_key(link_id) {
+                writer
+                    .writeln(format!(
+                        "broken link from {:>7} {}",
+                        object.object_type.as_str(),
+                        object_id
+                    ))?
+                    .writeln(format!(
+                        "              to {:>7} {}",
+                        link_type.as_str(),
+                        link_id
+                    ))?;
+                missing.insert(link_id.clone(), *link_type);
+            }
+        }
+    }
+    for (object_id, object_type) in &missing {
+        writer.writeln(format!("missing {} {}", object_type, object_id))?;
+        is_healthy = false;
+    }
+
+    let mut roots = vec![];
+    for (name, object_id) in resolve_roots(repository)? {
+        if objects.contains_key(&object_id) {
+            roots.push(object_id);
+        } else {
+            writer.writeln(format!("error: {}: invalid pointer {}", name, object_id))?;
+            is_healthy = false;
+        }
+    }
+    let reachable = find_reachable(&roots, |object_id| {
+        Ok(objects
+            .get(object_id)
+            .map(|object| object.links.iter().map(|(_, id)| id.clone()).collect())
+            .unwrap_or_default())
+    })?;
+
+    let unreachable = objects
+        .iter()
+        .filter(|(object_id, _)| !reachable.contains(*object_id))
+        .collect::<Vec<_>>();
+    let referenced_by_unreachable = unreachable
+        .iter()
+        .flat_map(|(_, object)| object.links.iter().map(|(_, id)| id))
+        .collect::<HashSet<_>>();
+    for (object_id, object) in unreachable {
+        if options.unreachable {
+            writer.writeln(format!("unreachable {} {}", object.object_type, object_id))?;
+        } else if !referenced_by_unreachable.contains(object_id) {
+            writer.writeln(format!("dangling {} {}", object.object_type, object_id))?;
+        }
+    }
+
+    Ok(is_healthy)
+}
+
+/// Verify the hash of the object and find the objects it points to. Returns None if the object
+/// doesn't hash to its id.
+fn check_object(
+    object_id: &ObjectId,
+    repository: &Repository,
+) -> crate::Result<Option<CheckedObject>> {
+    let database = &repository.database;
+    if !database.verify_object(object_id)? {
+        return Ok(None);
+    }
+
+    let (object_type, _) = database.load_object(object_id)?;
+    let links = load_links(object_id, object_type, database)?;
+    Ok(Some(CheckedObject { object_type, links }))
+}
+
+/// Load the objects that the object points to, along with their expected types. Submodule
+/// commits are not in the database, and so are not included.
+pub(crate) fn load_links(
+    object_id: &ObjectId,
+    object_type: ObjectType,
+    database: &Database,
+) -> crate::Result<Vec<(ObjectType, ObjectId)>> {
+    let links = match object_type {
+        ObjectType::Blob => vec![],
+        ObjectType::Tree => database
+            .load_tree(object_id)?
+            .entries()
+            .iter()
+            .filter_map(|entry| match entry.mode {
+                FileMode::Gitlink => None,
+                FileMode::Directory => Some((ObjectType::Tree, entry.object_id.clone())),
+                _ => Some((ObjectType::Blob, entry.object_id.clone())),
+            })
+            .collect(),
+        ObjectType::Commit => {
+            let commit = database.load_commit(object_id)?;
+            let parents = commit
+                .parents
+                .iter()
+                .map(|parent| (ObjectType::Commit, parent.clone()));
+            [(ObjectType::Tree, commit.tree.clone())]
+                .into_iter()
+                .chain(parents)
+                .collect()
+        }
+    };
+    Ok(links)
+}
+
+/// Resolve the objects that are in use, and so are reachable by definition, paired with where
+/// they are used: the HEAD and index of each worktree and all refs.
+pub(crate) fn resolve_roots(repository: &Repository) -> crate::Result<Vec<(String, ObjectId)>> {
+    let mut roots = vec![];
+    for worktree in worktree::list_worktrees(repository)? {
+        let worktree_repository = worktree.open()?;
+        let name = |what: &str| {
+            if worktree.is_main {
+                what.to_owned()
+            } else {
+                format!("{} of worktree {}", what, worktree.path.display())
+            }
+        };
+        if let Ok(head_id) = RefHandler::new(&worktree_repository).head() {
+            roots.push((name("HEAD"), head_id));
+        }
+        let index = worktree_repository.load_index_unlocked()?;
+        for entry in index.get_all_entries() {
+            if entry.file_mode() != FileMode::Gitlink {
+                let what = format!("index entry {}", entry.path.display());
+                roots.push((name(&what), entry.object_id.clone()));
+            }
+        }
+    }
+
+    roots.extend(RefHandler::new(repository).list_all_refs()?);
+
+    Ok(roots)
+}
+
+/// Find all objects reachable from the roots, given a function that lists the objects that an
+/// object points to.
+pub(crate) fn find_reachable(
+    roots: &[ObjectId],
+    links: impl Fn(&ObjectId) -> crate::Result<Vec<ObjectId>>,
+) -> crate::Result<HashSet<ObjectId>> {
+    let mut reachable = HashSet::new();
+    let mut pending = roots.to_vec();
+    while let Some(object_id) = pending.pop() {
+        if reachable.insert(object_id.clone()) {
+            pending.extend(links(&object_id)?);
+        }
+    }
+    Ok(reachable)
+}
@@ -0,0 +1,199 @@
+use std::io;
+
+use crate::objects::{Commit, GitObject, ObjectId};
+use crate::output::{Color, OutputWriter, Style};
+
+/// Tracks the lines of descent of an ASCII commit graph, as drawn by `log --graph`. Each column
+/// holds the id of the next commit expected on that line.
+#[derive(Default)]
+pub struct Graph {
+    columns: Vec<ObjectId>,
+    current_column: usize,
+}
+
+impl Graph {
+    /// Place the commit in the graph and return the prefix for the first line of its log entry,
+    /// and the prefix for any following lines.
+    pub fn place(&mut self, commit: &Commit) -> (String, String) {
+        self.current_column = self.find_or_add_column(commit.id());
+
+        let to_prefix = |symbols: Vec<char>| {
+            let symbols = symbols.iter().map(char::to_string).collect::<Vec<_>>();
+            format!("{} ", symbols.join(" "))
+        };
+        let commit_symbols = (0..self.columns.len())
+            .map(|i| if i == self.current_column { '*' } else { '|' })
+            .collect();
+        let continuation_symbols = (0..self.columns.len())
+            .map(|i| {
+                if i == self.current_column && commit.parents.is_empty() {
+                    ' '
+                } else {
+                    '|'
+                }
+            })
+            .collect();
+
+        (to_prefix(commit_symbols), to_prefix(continuation_symbols))
+    }
+
+    /// Replace the placed commit with its parents and return the lines connecting it to them.
+    pub fn advance(&mut self, commit: &Commit) -> Vec<String> {
+        let column = self.current_column;
+        let mut lines = vec![];
+
+        let (first_parent, other_parents) = match commit.parents.split_first() {
+            Some(parents) => parents,
+            None => {
+                let num_columns = self.columns.len();
+                self.columns.remove(column);
+                if column < self.columns.len() {
+                    lines.push(shift_left_line(column, num_columns, false));
+                }
+                return lines;
+            }
+        };
+
+        let num_columns = self.columns.len();
+        self.columns[column] = first_parent.clone();
+        let mut num_inserted = 0;
+        for parent in other_parents {
+            if !self.columns.contains(parent) {
+                num_inserted += 1;
+                self.columns.insert(column + num_inserted, parent.clone());
+            }
+        }
+        if num_inserted > 0 {
+            lines.push(merge_line(column, num_columns, num_inserted));
+        }
+
+        let first_occurrence = self.columns.iter().position(|id| id == first_parent);
+        if let Some(other_column) = first_occurrence.filter(|other| *other != column) {
+            let num_columns = self.columns.len();
+            let removed_column = other_column.max(column);
+            self.columns.remove(removed_column);
+            lines.push(shift_left_line(removed_column, num_columns, true));
+        }
+
+        lines
+    }
+
+    /// Advance past a commit that is not shown in the log.
+    pub fn skip(&mut self, commit: &Commit) {
+        self.place(commit);
+        self.advance(commit);
+    }
+
+    fn find_or_add_column(&mut self, commit_id: &ObjectId) -> usize {
+        match self.columns.iter().position(|id| id == commit_id) {
+            Some(column) => column,
+            None => {
+                self.columns.push(commit_id.clone());
+                self.columns.len() - 1
+            }
+        }
+    }
+}
+
+/// Draw the line where a merge commit in the given column branches out to the given number of new
+/// columns, one for each parent that isn't already on a line, pushing the columns to its right
+/// over.
+fn merge_line(column: usize, num_columns: usize, num_inserted: usize) -> String {
+    let mut symbols = vec![' '; 2 * (num_columns + num_inserted)];
+    for i in 0..=column {
+        symbols[2 * i] = '|';
+    }
+    for i in 0..num_inserted {
+        symbols[2 * (column + i) + 1] = '\\';
+    }
+    for i in (column + 1)..num_columns {
+        symbols[2 * (i + num_inserted) - 1] = '\\';
+    }
+    symbols.iter().collect::<String>().trim_end().to_owned()
+}
+
+/// Draw the line where the given column is removed and the columns to its right shift left. If
+/// the removed column is merging into a column to its left, it is drawn as well.
+fn shift_left_line(removed_column: usize, num_columns: usize, merging: bool) -> String {
+    let mut symbols = vec![' '; 2 * num_columns];
+    for i in 0..removed_column {
+        symbols[2 * i] = '|';
+    }
+    if merging && removed_column > 0 {
+        symbols[2 * removed_column - 1] = '/';
+    }
+    for i in (removed_column + 1)..num_columns {
+        symbols[2 * i - 1] = '/';
+    }
+    symbols.iter().collect::<String>().trim_end().to_owned()
+}
+
+/// Output writer that writes a graph prefix at the start of each line. The first line gets a
+/// different prefix than the following ones.
+pub struct GraphWriter<'a> {
+    inner: &'a mut dyn OutputWriter,
+    prefix: String,
+    continuation_prefix: String,
+    at_line_start: bool,
+}
+
+impl<'a> GraphWriter<'a> {
+    pub fn new(
+        inner: &'a mut dyn OutputWriter,
+        (prefix, continuation_prefix): (String, String),
+    ) -> GraphWriter<'a> {
+        GraphWriter {
+            inner,
+            prefix,
+            continuation_prefix,
+            at_line_start: true,
+        }
+    }
+
+    fn start_line(&mut self) -> io::Result<()> {
+        if self.at_line_start {
+            self.inner.write(self.prefix.clone())?;
+            self.at_line_start = false;
+        }
+        Ok(())
+    }
+}
+
+impl OutputWriter for GraphWriter<'_> {
+    fn write(&mut self, content: String) -> io::Result<&mut dyn OutputWriter> {
+        let mut segments = content.split('\n').peekable();
+        while let Some(segment) = segments.next() {
+            if !segment.is_empty() {
+                self.start_line()?;
+                self.inner.write(segment.to_owned())?;
+            }
+
+            if segments.peek().is_some() {
+                if self.at_line_start {
+                    self.inner.write(self.prefix.trim_end().to_owned())?;
+                }
+                self.inner.linefeed()?;
+                self.prefix = self.continuation_prefix.clone();
+                self.at_line_start = true;
+            }
+        }
+        Ok(self)
+    }
+
+    fn set_color(&mut self, color: Color) -> io::Result<&mut dyn OutputWriter> {
+        self.start_line()?;
+        self.inner.set_color(color)?;
+        Ok(self)
+    }
+
+    fn set_style(&mut self, style: Style) -> io::Result<&mut dyn OutputWriter> {
+        self.start_line()?;
+        self.inner.set_style(style)?;
+        Ok(self)
+    }
+
+    fn reset_formatting(&mut self) -> io::Result<&mut dyn OutputWriter> {
+        self.inner.reset_formatting()?;
+        Ok(self)
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::add::GITIGNORE;
+use crate::file;
+use crate::index::FileMode;
+use crate::output::{Color, OutputWriter, Style};
+use crate::refs::Revision;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Search the tree of this revision instead of the tracked files in the worktree.
+    #[builder(default)]
+    pub revision: Option<String>,
+}
+
+/// Search tracked content for lines matching the pattern and print them as
+/// `path:lineno:line`, prefixed with `<revision>:` when searching a revision.
+pub fn grep(
+    pattern: &str,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let regex = Regex::new(pattern).map_err(|error| {
+        crate::Error::Fatal(
+            Some(Box::new(error)),
+            format!("invalid pattern '{}'", pattern),
+        )
+    })?;
+
+    match &options.revision {
+        Some(revision) => grep_revision(&regex, revision, repository, writer),
+        None => grep_worktree(&regex, repository, writer),
+    }
+}
+
+fn grep_worktree(
+    regex: &Regex,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let index = repository.load_index_unlocked()?;
+    for entry in index.get_entries() {
+        if is_ignored(&entry.path) {
+            continue;
+        }
+
+        let absolute_path = repository.worktree().root().join(&entry.path);
+        if !absolute_path.is_file() {
+            continue;
+        }
+
+        let content = file::read_file(&absolute_path)?;
+        grep_content(regex, &entry.path.display().to_string(), &content, writer)?;
+    }
+
+    Ok(())
+}
+
+fn grep_revision(
+    regex: &Regex,
+    revision: &str,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let object_id = Revision::parse(revision)?.resolve(repository)?;
+    let tree = repository.database.peel_to_tree(&object_id)?;
+
+    let mut entries = vec![];
+    repository
+        .database
+        .extract_entries_from_tree(String::new(), &tree, &mut entries)?;
+
+    for (path, entry) in entries {
+        if is_ignored(Path::new(&path)) || entry.mode == FileMode::Gitlink {
+            continue;
+        }
+
+        let blob = repository.database.load_blob(&entry.object_id)?;
+        let name = format!("{}:{}", revision, path);
+        grep_content(regex, &name, blob.content(), writer)?;
+    }
+
+    Ok(())
+}
+
+fn grep_content(
+    regex: &Regex,
+    name: &str,
+    content: &[u8],
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let text = String::from_utf8_lossy(content);
+
+    if content.contains(&0) {
+        if regex.is_match(&text) {
+            writer.writeln(format!("Binary file {} matches", name))?;
+        }
+        return Ok(());
+    }
+
+    for (line_index, line) in text.lines().enumerate() {
+        if !regex.is_match(line) {
+            continue;
+        }
+
+        writer
+            .set_color(Color::Magenta)?
+            .write(name.to_owned())?
+            .set_color(Color::Cyan)?
+            .write(":".to_owned())?
+            .set_color(Color::Green)?
+            .write((line_index + 1).to_string())?
+            .set_color(Color::Cyan)?
+            .write(":".to_owned())?
+            .reset_formatting()?;
+
+        let mut last_end = 0;
+        for found in regex.find_iter(line) {
+            writer
+                .write(line[last_end..found.start()].to_owned())?
+                .set_color(Color::Red)?
+                .set_style(Style::Bold)?
+                .write(found.as_str().to_owned())?
+                .reset_formatting()?;
+            last_end = found.end();
+        }
+        writer.writeln(line[last_end..].to_owned())?;
+    }
+
+    Ok(())
+}
+
+fn is_ignored(path: &Path) -> bool {
+    path.iter()
+        .any(|component| GITIGNORE.contains(&component.to_str().unwrap_or("")))
+}
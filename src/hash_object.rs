@@ -0,0 +1,66 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::file;
+use crate::objects::{Blob, GitObject};
+use crate::output::OutputWriter;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub write: bool,
+}
+
+/// Compute the blob object id of each file and print it, after passing its content through the
+/// filters of the repository. If `write` is set, the blobs are also stored in the object database.
+pub fn hash_object<P: AsRef<Path>>(
+    files: &[P],
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    for path in files {
+        let worktree = repository.worktree();
+        let relative_path = worktree.prefix().join(path);
+        let absolute_path = worktree.root().join(&relative_path);
+        let content = file::read_file(&absolute_path).map_err(|error| {
+            let message = format!(
+                "could not open '{}' for reading: {}",
+                path.as_ref().display(),
+                error
+            );
+            crate::Error::Fatal(Some(Box::new(error)), message)
+        })?;
+        let content = repository.filters()?.to_database(&relative_path, content);
+        hash_content(content, options, repository, writer)?;
+    }
+
+    Ok(())
+}
+
+/// Compute the blob object id of everything read from the reader and print it.
+pub fn hash_object_stdin(
+    reader: &mut dyn Read,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let mut content = Vec::new();
+    reader.read_to_end(&mut content)?;
+    hash_content(content, options, repository, writer)
+}
+
+fn hash_content(
+    content: Vec<u8>,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let blob = Blob::with_hash_algorithm(content, repository.hash_algorithm());
+    if options.write {
+        repository.database.store_object(&blob)?;
+    }
+    writer.writeln(blob.id_as_string())?;
+    Ok(())
+}
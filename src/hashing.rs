@@ -1,7 +1,87 @@
 use sha1::{Digest, Sha1};
+use sha2::Sha256;
 
-pub fn sha1_hash(bytes: &[u8]) -> Vec<u8> {
-    let mut hasher = Sha1::new();
-    hasher.update(bytes);
-    hasher.finalize().to_vec()
+/// The hash algorithm of a repository, as given by `extensions.objectformat` in its config. It
+/// determines the object ids as well as the checksum of the index.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Parse the algorithm from its name in the config.
+    ///
+    /// # Examples
+    /// ```
+    /// use rut::hashing::HashAlgorithm;
+    ///
+    /// assert_eq!(HashAlgorithm::parse("SHA256"), Some(HashAlgorithm::Sha256));
+    /// assert_eq!(HashAlgorithm::parse("md5"), None);
+    /// ```
+    pub fn parse(name: &str) -> Option<HashAlgorithm> {
+        match name.to_lowercase().as_str() {
+            "sha1" => Some(HashAlgorithm::Sha1),
+            "sha256" => Some(HashAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// The size of a raw hash in bytes.
+    pub fn raw_size(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// The size of a hash as a hexadecimal string.
+    pub fn hex_size(&self) -> usize {
+        self.raw_size() * 2
+    }
+
+    pub fn hash(&self, bytes: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1 => Sha1::digest(bytes).to_vec(),
+            HashAlgorithm::Sha256 => Sha256::digest(bytes).to_vec(),
+        }
+    }
+
+    /// Create a hasher for hashing content that is not all in memory at once.
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+}
+
+/// Computes a hash incrementally, from content that is fed to it piece by piece.
+pub enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha1(hasher) => hasher.update(bytes),
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
 }
@@ -1,80 +1,104 @@
-use std::num::ParseIntError;
+use std::{error, fmt};
 
-pub fn to_hex_string(bytes: &[u8]) -> String {
-    bytes
-        .iter()
-        .map(|byte| format!("{:x}", byte))
-        .collect::<Vec<String>>()
-        .join("")
-}
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
 
-pub fn from_hex_string(hex: &str) -> Result<Vec<u8>, ParseIntError> {
-    hex.chars()
-        .map(|chr| u8::from_str_radix(&chr.to_string(), 16))
-        .collect()
+/// An error from parsing a hexadecimal string.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseHexError {
+    /// The string has an odd number of digits, so the last byte is incomplete.
+    OddLength(usize),
+    /// The character at the index is not a hexadecimal digit.
+    InvalidCharacter(char, usize),
 }
 
-pub fn from_hex_bytes(hex: &[u8]) -> Result<Vec<u8>, ParseIntError> {
-    hex.iter()
-        .map(|hex_byte| u8::from_str_radix(&hex_byte.to_string(), 16))
-        .collect()
+impl error::Error for ParseHexError {}
+
+impl fmt::Display for ParseHexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseHexError::OddLength(length) => {
+                write!(f, "odd number of hexadecimal digits ({})", length)
+            }
+            ParseHexError::InvalidCharacter(c, index) => {
+                write!(f, "invalid hexadecimal digit '{}' at index {}", c, index)
+            }
+        }
+    }
 }
 
-pub fn unhexlify(bytes: &[u8]) -> Vec<u8> {
-    let mut unhexlified = Vec::new();
-    for i in 0..bytes.len() {
-        let compressed_bytes = bytes.get(i).unwrap();
-        let left_byte = compressed_bytes >> 4;
-        let right_byte = compressed_bytes & 0b00001111;
-        unhexlified.push(left_byte);
-        unhexlified.push(right_byte);
+/// Format the bytes as a lowercase hexadecimal string, with two digits per byte.
+///
+/// # Examples
+/// ```
+/// use rut::hex;
+///
+/// assert_eq!(hex::to_hex_string(&[0x0a, 0xff, 0x00]), "0aff00");
+/// ```
+pub fn to_hex_string(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        hex.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        hex.push(HEX_DIGITS[(byte & 0x0f) as usize] as char);
     }
-    unhexlified
+    hex
 }
 
-pub fn hexlify(bytes: &[u8]) -> Vec<u8> {
-    let mut hexlified = Vec::new();
-    for i in (0..bytes.len() - 1).step_by(2) {
-        let left_byte = bytes.get(i).unwrap();
-        let right_byte = bytes.get(i + 1).unwrap();
-        let compressed = (left_byte << 4) | right_byte;
-        hexlified.push(compressed);
+/// Parse a hexadecimal string, with two digits per byte and in either case, into bytes.
+///
+/// # Examples
+/// ```
+/// use rut::hex::{self, ParseHexError};
+///
+/// assert_eq!(hex::from_hex_string("0aFF00"), Ok(vec![0x0a, 0xff, 0x00]));
+/// assert_eq!(hex::from_hex_string("abc"), Err(ParseHexError::OddLength(3)));
+/// assert_eq!(
+///     hex::from_hex_string("0g"),
+///     Err(ParseHexError::InvalidCharacter('g', 1))
+/// );
+/// ```
+pub fn from_hex_string(hex: &str) -> Result<Vec<u8>, ParseHexError> {
+    from_hex_bytes(hex.as_bytes())
+}
+
+/// Parse the bytes of a hexadecimal string into bytes, like [`from_hex_string`].
+pub fn from_hex_bytes(hex: &[u8]) -> Result<Vec<u8>, ParseHexError> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(ParseHexError::OddLength(hex.len()));
     }
 
-    hexlified
+    let digit = |index: usize| {
+        let byte = hex[index];
+        match byte {
+            b'0'..=b'9' => Ok(byte - b'0'),
+            b'a'..=b'f' => Ok(byte - b'a' + 10),
+            b'A'..=b'F' => Ok(byte - b'A' + 10),
+            _ => {
+                // report the whole character, which may span several bytes
+                let c = String::from_utf8_lossy(&hex[index..])
+                    .chars()
+                    .next()
+                    .unwrap();
+                Err(ParseHexError::InvalidCharacter(c, index))
+            }
+        }
+    };
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| Ok((digit(index)? << 4) | digit(index + 1)?))
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fmt::Debug;
 
     #[test]
-    fn hexlify_and_unhexlify_roundtrip_works() {
-        let bytes = vec![0, 1, 2, 3, 4, 6];
-
-        let hexlified = hexlify(&bytes);
-        let unhexlified = unhexlify(&hexlified);
+    fn test_to_hex_string_and_from_hex_string_round_trip() {
+        let bytes = (0..=255).collect::<Vec<u8>>();
 
-        assert_vectors_equal(&unhexlified.to_vec(), &bytes)
-    }
-
-    fn assert_vectors_equal<T: Debug + Eq>(actual: &Vec<T>, expected: &Vec<T>) {
-        if actual.len() != expected.len() {
-            panic!(
-                "expected vector has length {}, but actual vector has length {}",
-                expected.len(),
-                actual.len()
-            );
-        }
+        let hex = to_hex_string(&bytes);
 
-        for (actual, expected) in actual.iter().zip(expected.iter()) {
-            if actual != expected {
-                panic!(
-                    "mismatching characters, expected={:?}, actual={:?}",
-                    expected, actual
-                );
-            }
-        }
+        assert_eq!(hex.len(), 512);
+        assert_eq!(from_hex_string(&hex), Ok(bytes));
     }
 }
@@ -0,0 +1,30 @@
+use std::process::Command;
+
+use crate::platform;
+use crate::workspace::Repository;
+
+/// Run the hook with the given name from the hooks directory, if it exists and is executable.
+/// The hook is run from the root of the worktree. A hook that exits with a non-zero status
+/// results in an error.
+pub fn run_hook(name: &str, args: &[&str], repository: &Repository) -> crate::Result<()> {
+    let hook_path = repository.common_dir().join("hooks").join(name);
+    let is_executable = hook_path
+        .metadata()
+        .map(|metadata| platform::is_executable(&metadata))
+        .unwrap_or(false);
+    if !is_executable {
+        return Ok(());
+    }
+
+    let exit_status = Command::new(&hook_path)
+        .args(args)
+        .current_dir(repository.worktree().root())
+        .status()?;
+
+    if exit_status.success() {
+        Ok(())
+    } else {
+        let message = format!("{} hook exited with {}", name, exit_status);
+        Err(crate::Error::Fatal(None, message))
+    }
+}
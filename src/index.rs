@@ -1,29 +1,41 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fs::Metadata;
-use std::io;
-use std::os::linux::fs::MetadataExt;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str;
 
+use crate::cache_tree::{self, CacheTree};
 use crate::file;
 use crate::file::AsVec;
-use crate::hashing;
-use crate::hex;
+use crate::hashing::HashAlgorithm;
 use crate::objects::ObjectId;
+use crate::platform::FileStat;
 
 const SIGNATURE: &str = "DIRC";
-const VERSION: [u8; 4] = [0, 0, 0, 2];
+const DEFAULT_VERSION: u32 = 2;
+const SUPPORTED_VERSIONS: [u32; 3] = [2, 3, 4];
+
+// the flags that follow the object id of an entry
+const ASSUME_VALID_FLAG: u16 = 0x8000;
+const EXTENDED_FLAG: u16 = 0x4000;
+const STAGE_MASK: u16 = 0x3000;
+const STAGE_SHIFT: u16 = 12;
+const NAME_LENGTH_MASK: u16 = 0x0fff;
 
 const BYTES_PER_U32: usize = 4;
 const BYTES_PER_U16: usize = 2;
-const BYTES_PER_PACKED_OID: usize = 20;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Index {
     entries: HashMap<PathBuf, IndexEntry>,
+    /// The entries of paths with unresolved merge conflicts, which have up to one entry for each
+    /// of the stages 1 (the common ancestor), 2 (ours) and 3 (theirs), but none of stage 0.
+    unmerged_entries: HashMap<PathBuf, Vec<IndexEntry>>,
     directories: HashMap<PathBuf, HashSet<String>>,
+    hash_algorithm: HashAlgorithm,
+    version: u32,
+    cache_tree: CacheTree,
 }
 
 fn to_be_u32(bytes: &[u8]) -> Result<u32, String> {
@@ -53,45 +65,138 @@ fn to_be_u16(bytes: &[u8]) -> Result<u16, String> {
 
 impl Index {
     pub fn new() -> Index {
+        Index::with_hash_algorithm(HashAlgorithm::default())
+    }
+
+    /// Create an empty index for a repository with the hash algorithm, which determines the size
+    /// of object ids and the checksum.
+    pub fn with_hash_algorithm(hash_algorithm: HashAlgorithm) -> Index {
         Index {
             entries: HashMap::new(),
+            unmerged_entries: HashMap::new(),
             directories: HashMap::new(),
+            hash_algorithm,
+            version: DEFAULT_VERSION,
+            cache_tree: CacheTree::default(),
         }
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Result<Index, String> {
-        let preamble_end = SIGNATURE.len() + VERSION.len();
-        let num_entries = to_be_u32(&bytes[preamble_end..(preamble_end + 4)])?;
+    /// Parse an index of version 2, 3 or 4. Of the extensions that follow the entries, the cache
+    /// tree is read and other optional ones are skipped, while required extensions that we don't
+    /// understand are an error.
+    pub fn from_bytes(bytes: &[u8], hash_algorithm: HashAlgorithm) -> Result<Index, String> {
+        let header_size = SIGNATURE.len() + 2 * BYTES_PER_U32;
+        let checksum_start = bytes
+            .len()
+            .checked_sub(hash_algorithm.raw_size())
+            .filter(|checksum_start| *checksum_start >= header_size)
+            .ok_or("index file is too short")?;
+        if &bytes[..SIGNATURE.len()] != SIGNATURE.as_bytes() {
+            return Err(String::from("bad index signature"));
+        }
 
-        let mut index = Index {
-            entries: HashMap::new(),
-            directories: HashMap::new(),
-        };
+        let mut position = SIGNATURE.len();
+        let version = to_be_u32(&bytes[position..(position + BYTES_PER_U32)])?;
+        if !SUPPORTED_VERSIONS.contains(&version) {
+            return Err(format!("unsupported index version {}", version));
+        }
+        position += BYTES_PER_U32;
+        let num_entries = to_be_u32(&bytes[position..(position + BYTES_PER_U32)])?;
+        position += BYTES_PER_U32;
 
-        let mut position = preamble_end + 4;
+        let mut index = Index::with_hash_algorithm(hash_algorithm);
+        index.version = version;
+
+        let mut previous_path: Vec<u8> = Vec::new();
         for _ in 0..num_entries {
-            let (entry, consumed_bytes) = Index::parse_entry(&bytes[position..])?;
+            let entry_bytes = bytes
+                .get(position..checksum_start)
+                .ok_or("truncated index entry")?;
+            let (entry, consumed_bytes) = Index::parse_entry(
+                entry_bytes,
+                version,
+                &previous_path,
+                hash_algorithm.raw_size(),
+            )?;
             position += consumed_bytes;
+            previous_path = entry.path_bytes().to_vec();
             index.add_entry(entry);
         }
 
+        let extension_bytes = bytes
+            .get(position..checksum_start)
+            .ok_or("truncated index entry")?;
+        index.cache_tree = Index::parse_extensions(extension_bytes, hash_algorithm)?;
+
         Ok(index)
     }
 
-    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Index> {
+    /// Extensions consist of a four byte signature and a four byte size, followed by the data of
+    /// the extension. An extension is optional if its signature starts with an uppercase letter.
+    fn parse_extensions(
+        mut bytes: &[u8],
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<CacheTree, String> {
+        let mut cache_tree = CacheTree::default();
+        while !bytes.is_empty() {
+            let header_size = SIGNATURE.len() + BYTES_PER_U32;
+            if bytes.len() < header_size {
+                return Err(String::from("truncated index extension"));
+            }
+
+            let signature = &bytes[..SIGNATURE.len()];
+            if !signature[0].is_ascii_uppercase() {
+                return Err(format!(
+                    "index uses {} extension, which we do not understand",
+                    String::from_utf8_lossy(signature)
+                ));
+            }
+
+            let size = to_be_u32(&bytes[SIGNATURE.len()..header_size])? as usize;
+            let data = bytes
+                .get(header_size..(header_size + size))
+                .ok_or("truncated index extension")?;
+            if signature == cache_tree::SIGNATURE {
+                cache_tree = CacheTree::from_bytes(data, hash_algorithm.raw_size())?;
+            }
+            bytes = &bytes[(header_size + size)..];
+        }
+
+        Ok(cache_tree)
+    }
+
+    /// Read the index file at the path of a repository with sha1 object ids. A missing file is
+    /// an empty index.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Index> {
+        Index::from_file_with_hash_algorithm(path, HashAlgorithm::Sha1)
+    }
+
+    /// Read the index file at the path of a repository whose object ids are computed with the
+    /// hash algorithm. A missing file is an empty index.
+    pub fn from_file_with_hash_algorithm<P: AsRef<Path>>(
+        path: P,
+        hash_algorithm: HashAlgorithm,
+    ) -> crate::Result<Index> {
         let index = if path.as_ref().is_file() {
             let index_bytes = file::read_file(path)?;
-
-            // TODO handle error from reading index
-            Index::from_bytes(&index_bytes).ok().unwrap()
+            Index::from_bytes(&index_bytes, hash_algorithm).map_err(crate::Error::CorruptIndex)?
         } else {
-            Index::new()
+            Index::with_hash_algorithm(hash_algorithm)
         };
 
         Ok(index)
     }
 
-    fn parse_entry(bytes: &[u8]) -> Result<(IndexEntry, usize), String> {
+    fn parse_entry(
+        bytes: &[u8],
+        version: u32,
+        previous_path: &[u8],
+        raw_id_size: usize,
+    ) -> Result<(IndexEntry, usize), String> {
+        // ten u32 stat fields, the object id and the flags
+        if bytes.len() < 10 * BYTES_PER_U32 + raw_id_size + BYTES_PER_U16 {
+            return Err(String::from("truncated index entry"));
+        }
         let mut position = 0;
 
         let ctime_seconds = to_be_u32(&bytes[position..(position + BYTES_PER_U32)])?;
@@ -114,17 +219,60 @@ impl Index {
         position += BYTES_PER_U32;
         let file_size = to_be_u32(&bytes[position..(position + BYTES_PER_U32)])?;
         position += BYTES_PER_U32;
-        let raw_object_id = hex::unhexlify(&bytes[position..(position + BYTES_PER_PACKED_OID)]);
-        let object_id = ObjectId::from_sha_bytes(&raw_object_id)?;
-        position += BYTES_PER_PACKED_OID;
+        let object_id = ObjectId::from_sha_bytes(&bytes[position..(position + raw_id_size)])?;
+        position += raw_id_size;
 
-        let path_size = to_be_u16(&bytes[position..(position + BYTES_PER_U16)])? as usize;
+        let flags = to_be_u16(&bytes[position..(position + BYTES_PER_U16)])?;
         position += BYTES_PER_U16;
+        let extended_flags = if flags & EXTENDED_FLAG != 0 {
+            if version < 3 {
+                return Err(String::from("extended flags in index of version 2"));
+            }
+            let extended_flags = bytes
+                .get(position..(position + BYTES_PER_U16))
+                .ok_or("truncated index entry")?;
+            position += BYTES_PER_U16;
+            to_be_u16(extended_flags)?
+        } else {
+            0
+        };
 
-        // TODO fix error handling of parsing path
-        let path = std::str::from_utf8(&bytes[position..(position + path_size)])
-            .ok()
-            .unwrap();
+        let (path_bytes, entry_total_size) = if version == 4 {
+            // the path is given as the number of bytes to strip from the end of the previous
+            // path, followed by the suffix to append to what remains
+            let (strip_length, varint_size) = decode_varint(&bytes[position..])?;
+            position += varint_size;
+            let prefix_length = previous_path
+                .len()
+                .checked_sub(strip_length)
+                .ok_or("invalid path prefix in index entry")?;
+            let suffix_length = find_nul(&bytes[position..])?;
+            let mut path_bytes = previous_path[..prefix_length].to_vec();
+            path_bytes.extend_from_slice(&bytes[position..(position + suffix_length)]);
+            (path_bytes, position + suffix_length + 1)
+        } else {
+            // names that don't fit in the flags are only terminated by the NUL byte
+            let name_length = flags & NAME_LENGTH_MASK;
+            let path_size = if name_length < NAME_LENGTH_MASK {
+                name_length as usize
+            } else {
+                find_nul(&bytes[position..])?
+            };
+            let path_bytes = bytes
+                .get(position..(position + path_size))
+                .ok_or("truncated index entry")?
+                .to_vec();
+
+            let unpadded_entry_size = position + path_size + 1;
+            let entry_padding = if !unpadded_entry_size.is_multiple_of(8) {
+                8 - unpadded_entry_size % 8
+            } else {
+                0
+            };
+            (path_bytes, unpadded_entry_size + entry_padding)
+        };
+        let path = String::from_utf8(path_bytes)
+            .map_err(|_| String::from("index entry path is not valid UTF-8"))?;
 
         let entry = IndexEntry {
             ctime_seconds,
@@ -139,23 +287,32 @@ impl Index {
             file_size,
             path: PathBuf::from(path),
             object_id,
+            flags: flags & (ASSUME_VALID_FLAG | STAGE_MASK),
+            extended_flags,
         };
 
-        let unpadded_entry_size = position + path_size + 1;
-        let entry_padding = if unpadded_entry_size % 8 != 0 {
-            8 - unpadded_entry_size % 8
-        } else {
-            0
-        };
-        let entry_total_size = unpadded_entry_size + entry_padding;
-
         Ok((entry, entry_total_size))
     }
 
+    /// Add the entry, replacing any entry of the same path. Adding an entry of stage 0 resolves
+    /// a merge conflict of the path, while an entry of a higher stage is added alongside the
+    /// entries of the other stages of the path.
     pub fn add_entry(&mut self, entry: IndexEntry) {
-        self.discard_conflicting_entries(&entry.path);
-        self.insert_into_directories_map(&entry.path);
-        self.entries.insert(PathBuf::from(&entry.path), entry);
+        self.cache_tree.invalidate(&entry.path);
+        if entry.stage() == 0 {
+            self.discard_conflicting_entries(&entry.path);
+            self.insert_into_directories_map(&entry.path);
+            self.entries.insert(PathBuf::from(&entry.path), entry);
+        } else {
+            if !self.unmerged_entries.contains_key(&entry.path) {
+                self.discard_conflicting_entries(&entry.path);
+                self.insert_into_directories_map(&entry.path);
+            }
+            let stages = self.unmerged_entries.entry(entry.path.clone()).or_default();
+            stages.retain(|existing| existing.stage() != entry.stage());
+            stages.push(entry);
+            stages.sort_by_key(IndexEntry::stage);
+        }
     }
 
     fn insert_into_directories_map<P: AsRef<Path>>(&mut self, path: P) {
@@ -188,20 +345,35 @@ impl Index {
         }
     }
 
+    /// Remove the entry of the path, along with any unmerged entries of it. Returns the removed
+    /// entry of stage 0, or the first unmerged entry if the path is unmerged.
     pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<IndexEntry> {
-        if let Some(removed_entry) = self.entries.remove(path.as_ref()) {
-            self.remove_from_directories_map(path.as_ref());
-            Some(removed_entry)
-        } else {
-            None
+        let removed_entry = self.entries.remove(path.as_ref());
+        let removed_unmerged_entries = self.unmerged_entries.remove(path.as_ref());
+        if removed_entry.is_none() && removed_unmerged_entries.is_none() {
+            return None;
         }
+
+        self.cache_tree.invalidate(path.as_ref());
+        self.remove_from_directories_map(path.as_ref());
+        removed_entry.or_else(|| removed_unmerged_entries?.into_iter().next())
     }
 
-    /// Check whether a path exists as an entry in the index.
+    /// Check whether a path exists as an entry of stage 0 in the index.
     pub fn has_entry<P: AsRef<Path>>(&self, path: P) -> bool {
         self.entries.contains_key(path.as_ref())
     }
 
+    /// Check whether a path has unresolved merge conflicts.
+    pub fn is_unmerged<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.unmerged_entries.contains_key(path.as_ref())
+    }
+
+    /// Check whether any path has unresolved merge conflicts.
+    pub fn has_unmerged_entries(&self) -> bool {
+        !self.unmerged_entries.is_empty()
+    }
+
     /// Check whether a path is a tracked directory.
     pub fn is_tracked_directory<P: AsRef<Path>>(&self, path: P) -> bool {
         self.directories.contains_key(path.as_ref())
@@ -233,12 +405,70 @@ impl Index {
         }
     }
 
+    /// Get the entries of stage 0, which are the entries of all paths that are not unmerged.
     pub fn get_entries(&self) -> Vec<&IndexEntry> {
         let mut entries: Vec<&IndexEntry> = self.entries.values().collect();
         entries.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
         entries
     }
 
+    /// Get the unmerged entries of each unmerged path, sorted by path and then by stage.
+    pub fn get_unmerged_entries(&self) -> Vec<(&Path, &[IndexEntry])> {
+        let mut unmerged_entries = self
+            .unmerged_entries
+            .iter()
+            .map(|(path, entries)| (path.as_path(), entries.as_slice()))
+            .collect::<Vec<_>>();
+        unmerged_entries.sort_by_key(|(path, _)| *path);
+        unmerged_entries
+    }
+
+    /// Get the entries of all stages, sorted by path and then by stage, as they are stored.
+    pub fn get_all_entries(&self) -> Vec<&IndexEntry> {
+        let mut entries = self
+            .entries
+            .values()
+            .chain(self.unmerged_entries.values().flatten())
+            .collect::<Vec<_>>();
+        entries.sort_by(|lhs, rhs| (&lhs.path, lhs.stage()).cmp(&(&rhs.path, rhs.stage())));
+        entries
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// The version to write the index with. An index read as version 4 keeps its path
+    /// compression, and version 3 is only needed when some entry has extended flags.
+    fn write_version(&self) -> u32 {
+        if self.version == 4 {
+            4
+        } else if self
+            .get_all_entries()
+            .iter()
+            .any(|entry| entry.extended_flags != 0)
+        {
+            3
+        } else {
+            DEFAULT_VERSION
+        }
+    }
+
+    pub fn cache_tree(&self) -> &CacheTree {
+        &self.cache_tree
+    }
+
+    /// Replace the cache tree, typically with the one computed when writing the tree of a commit.
+    pub fn set_cache_tree(&mut self, cache_tree: CacheTree) {
+        self.cache_tree = cache_tree;
+    }
+
+    /// Invalidate the trees of the directories that contain the path. This is done automatically
+    /// when adding or removing entries, but must be done by hand when changing an entry in place.
+    pub fn invalidate_cache_tree<P: AsRef<Path>>(&mut self, path: P) {
+        self.cache_tree.invalidate(path.as_ref());
+    }
+
     pub fn get<P: AsRef<Path>>(&self, key: P) -> Option<&IndexEntry> {
         self.entries.get(key.as_ref())
     }
@@ -257,20 +487,33 @@ impl Default for Index {
 impl AsVec<u8> for Index {
     fn as_vec(&self) -> Vec<u8> {
         let signature = SIGNATURE.as_bytes();
-        let num_entries = (self.entries.len() as u32).to_be_bytes();
+        let version = self.write_version();
+        let entries = self.get_all_entries();
+        let num_entries = (entries.len() as u32).to_be_bytes();
 
         let mut index: Vec<u8> = Vec::new();
         index.extend_from_slice(signature);
-        index.extend_from_slice(&VERSION);
+        index.extend_from_slice(&version.to_be_bytes());
         index.extend_from_slice(&num_entries);
 
-        let entries = self.get_entries();
-
+        let mut previous_path: &[u8] = &[];
         for entry in entries {
-            index.extend(entry.as_vec());
+            if version == 4 {
+                index.extend(entry.as_compressed_vec(previous_path));
+                previous_path = entry.path_bytes();
+            } else {
+                index.extend(entry.as_vec());
+            }
+        }
+
+        if !self.cache_tree.is_empty() {
+            let cache_tree = self.cache_tree.as_vec();
+            index.extend_from_slice(cache_tree::SIGNATURE);
+            index.extend_from_slice(&(cache_tree.len() as u32).to_be_bytes());
+            index.extend(cache_tree);
         }
 
-        let index_checksum = hashing::sha1_hash(&index);
+        let index_checksum = self.hash_algorithm.hash(&index);
         index.extend_from_slice(&index_checksum);
 
         index
@@ -291,38 +534,98 @@ pub struct IndexEntry {
     pub file_size: u32,
     pub path: PathBuf,
     pub object_id: ObjectId,
+    /// The assume-valid and stage bits of the flags. The name length is computed when writing.
+    flags: u16,
+    /// Flags such as skip-worktree and intent-to-add, which require index version 3 or later.
+    extended_flags: u16,
 }
 
 impl IndexEntry {
     pub fn new<P: AsRef<Path>>(path: P, object_id: ObjectId, metadata: &Metadata) -> IndexEntry {
-        let ctime_seconds = metadata.st_ctime() as u32;
-        let ctime_nanoseconds = metadata.st_ctime_nsec() as u32;
-        let mtime_seconds = metadata.st_mtime() as u32;
-        let mtime_nanoseconds = metadata.st_mtime_nsec() as u32;
-        let dev = metadata.st_dev() as u32;
-        let ino = metadata.st_ino() as u32;
-        let mode = Mode::new(metadata.st_mode());
-        let uid = metadata.st_uid();
-        let gid = metadata.st_gid();
-        let file_size = metadata.st_size() as u32;
+        let stat = FileStat::new(metadata);
+        let mode = Mode::new(stat.mode);
+        let file_size = match mode.file_mode {
+            FileMode::Gitlink => 0,
+            _ => stat.size,
+        };
 
         IndexEntry {
-            ctime_seconds,
-            ctime_nanoseconds,
-            mtime_seconds,
-            mtime_nanoseconds,
-            dev,
-            ino,
+            ctime_seconds: stat.ctime_seconds,
+            ctime_nanoseconds: stat.ctime_nanoseconds,
+            mtime_seconds: stat.mtime_seconds,
+            mtime_nanoseconds: stat.mtime_nanoseconds,
+            dev: stat.dev,
+            ino: stat.ino,
             mode,
-            uid,
-            gid,
+            uid: stat.uid,
+            gid: stat.gid,
             file_size,
             path: path.as_ref().to_owned(),
             object_id,
+            flags: 0,
+            extended_flags: 0,
+        }
+    }
+
+    /// An entry for a file that isn't checked out, such as one read from a tree. The stat
+    /// information is zeroed apart from the size, so the file is compared by content until it's
+    /// staged again.
+    pub fn from_object<P: AsRef<Path>>(
+        path: P,
+        object_id: ObjectId,
+        file_mode: FileMode,
+        file_size: u32,
+    ) -> IndexEntry {
+        let raw_mode = u32::from_str_radix(file_mode.as_str(), 8).unwrap();
+        IndexEntry {
+            ctime_seconds: 0,
+            ctime_nanoseconds: 0,
+            mtime_seconds: 0,
+            mtime_nanoseconds: 0,
+            dev: 0,
+            ino: 0,
+            mode: Mode::new(raw_mode),
+            uid: 0,
+            gid: 0,
+            file_size,
+            path: path.as_ref().to_owned(),
+            object_id,
+            flags: 0,
+            extended_flags: 0,
         }
     }
 
     pub fn as_vec(&self) -> Vec<u8> {
+        let mut bytes = self.fields_as_vec();
+
+        let path_bytes = self.path_bytes();
+        bytes.extend_from_slice(path_bytes);
+        bytes.push(0);
+
+        pad_to_block_size(&mut bytes);
+
+        bytes
+    }
+
+    /// Encode the entry with its path compressed relative to the previous path, as in index
+    /// version 4. Such entries are not padded.
+    fn as_compressed_vec(&self, previous_path: &[u8]) -> Vec<u8> {
+        let mut bytes = self.fields_as_vec();
+
+        let path_bytes = self.path_bytes();
+        let common_prefix_length = previous_path
+            .iter()
+            .zip(path_bytes)
+            .take_while(|(lhs, rhs)| lhs == rhs)
+            .count();
+        bytes.extend(encode_varint(previous_path.len() - common_prefix_length));
+        bytes.extend_from_slice(&path_bytes[common_prefix_length..]);
+        bytes.push(0);
+
+        bytes
+    }
+
+    fn fields_as_vec(&self) -> Vec<u8> {
         let mut bytes: Vec<u8> = Vec::new();
 
         add_all(self.ctime_seconds, &mut bytes);
@@ -335,24 +638,43 @@ impl IndexEntry {
         add_all(self.uid, &mut bytes);
         add_all(self.gid, &mut bytes);
         add_all(self.file_size, &mut bytes);
-        hex::hexlify(self.object_id.bytes())
-            .into_iter()
-            .for_each(|byte| bytes.push(byte));
-
-        let path_bytes = self.path.to_str().unwrap().as_bytes().to_vec();
-        let path_length = (path_bytes.len() as u16).to_be_bytes().to_vec();
-        path_length.into_iter().for_each(|byte| bytes.push(byte));
-        path_bytes.into_iter().for_each(|byte| bytes.push(byte));
-        bytes.push(0);
+        bytes.extend_from_slice(self.object_id.bytes());
 
-        pad_to_block_size(&mut bytes);
+        let name_length = self.path_bytes().len().min(NAME_LENGTH_MASK as usize) as u16;
+        let mut flags = self.flags | name_length;
+        if self.extended_flags != 0 {
+            flags |= EXTENDED_FLAG;
+        }
+        bytes.extend_from_slice(&flags.to_be_bytes());
+        if self.extended_flags != 0 {
+            bytes.extend_from_slice(&self.extended_flags.to_be_bytes());
+        }
 
         bytes
     }
 
+    fn path_bytes(&self) -> &[u8] {
+        self.path.to_str().unwrap().as_bytes()
+    }
+
     pub fn file_mode(&self) -> FileMode {
         self.mode.file_mode
     }
+
+    /// The mode as it is stored in the index, e.g. `0o100644`.
+    pub fn raw_mode(&self) -> u32 {
+        self.mode.raw_mode
+    }
+
+    /// The merge stage of the entry, which is 0 unless the path has unresolved conflicts. Then,
+    /// stage 1 is the common ancestor, stage 2 is our side and stage 3 is their side.
+    pub fn stage(&self) -> u16 {
+        (self.flags & STAGE_MASK) >> STAGE_SHIFT
+    }
+
+    pub fn set_stage(&mut self, stage: u16) {
+        self.flags = (self.flags & !STAGE_MASK) | ((stage << STAGE_SHIFT) & STAGE_MASK);
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
@@ -360,6 +682,25 @@ pub enum FileMode {
     Directory,
     Executable,
     Regular,
+    /// A commit in another repository, which is how submodules are recorded.
+    Gitlink,
+}
+
+impl FileMode {
+    /// The octal mode as it is written in tree objects.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileMode::Directory => "40000",
+            FileMode::Regular => "100644",
+            FileMode::Executable => "100755",
+            FileMode::Gitlink => "160000",
+        }
+    }
+
+    /// The mode that a file with the given mode from its stat information is staged with.
+    pub fn from_stat_mode(stat_mode: u32) -> FileMode {
+        Mode::new(stat_mode).file_mode
+    }
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -370,8 +711,15 @@ struct Mode {
 
 impl Mode {
     fn new(actual_mode: u32) -> Mode {
+        let object_type_bits = 0o170000_u32;
         let world_executable_bits = 0o700_u32;
-        if actual_mode & world_executable_bits == world_executable_bits {
+        // the only directories that can be staged are the worktrees of submodules
+        if matches!(actual_mode & object_type_bits, 0o160000 | 0o040000) {
+            Mode {
+                file_mode: FileMode::Gitlink,
+                raw_mode: 0o160000,
+            }
+        } else if actual_mode & world_executable_bits == world_executable_bits {
             Mode {
                 file_mode: FileMode::Executable,
                 raw_mode: 0o100755,
@@ -387,11 +735,55 @@ impl Mode {
 
 fn pad_to_block_size(bytes: &mut Vec<u8>) {
     let block_size = 8;
-    while bytes.len() % block_size != 0 {
+    while !bytes.len().is_multiple_of(block_size) {
         bytes.push(0);
     }
 }
 
+/// Find the length of the bytes up to the terminating NUL byte.
+fn find_nul(bytes: &[u8]) -> Result<usize, String> {
+    bytes
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or_else(|| String::from("unterminated path in index entry"))
+}
+
+/// Decode a variable length integer as used by index version 4, returning it along with the
+/// number of bytes it occupied. Each continuation adds one to the value, so that there is only a
+/// single way to encode each number.
+fn decode_varint(bytes: &[u8]) -> Result<(usize, usize), String> {
+    let mut bytes_iter = bytes.iter();
+    let mut next_byte = || {
+        bytes_iter
+            .next()
+            .copied()
+            .ok_or_else(|| String::from("truncated varint in index entry"))
+    };
+
+    let mut byte = next_byte()?;
+    let mut value = (byte & 0x7f) as usize;
+    let mut size = 1;
+    while byte & 0x80 != 0 {
+        byte = next_byte()?;
+        size += 1;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+    }
+
+    Ok((value, size))
+}
+
+fn encode_varint(mut value: usize) -> Vec<u8> {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value != 0 {
+        value -= 1;
+        bytes.push(0x80 | (value & 0x7f) as u8);
+        value >>= 7;
+    }
+    bytes.reverse();
+    bytes
+}
+
 fn add_all(value: u32, bytes: &mut Vec<u8>) {
     value
         .to_be_bytes()
@@ -432,7 +824,9 @@ mod tests {
         index.add_entry(entry);
         let index_bytes = index.as_vec();
 
-        let index_from_bytes = Index::from_bytes(&index_bytes).ok().unwrap();
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
 
         assert_eq!(index_from_bytes, index);
     }
@@ -447,11 +841,150 @@ mod tests {
         index.add_entry(second_entry);
 
         let index_bytes = index.as_vec();
-        let index_from_bytes = Index::from_bytes(&index_bytes).ok().unwrap();
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
+
+        assert_eq!(index_from_bytes, index);
+    }
+
+    #[test]
+    fn test_path_compressed_index_round_trip() {
+        let mut index = Index::new();
+        index.version = 4;
+        index.add_entry(create_entry("nested/a.txt"));
+        index.add_entry(create_entry("nested/deeper/b.txt"));
+        index.add_entry(create_entry("top.txt"));
+
+        let index_bytes = index.as_vec();
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
 
         assert_eq!(index_from_bytes, index);
     }
 
+    #[test]
+    fn test_extended_flags_index_round_trip() {
+        let mut entry = create_entry("Cargo.toml");
+        entry.extended_flags = 0x4000;
+        let mut index = Index::new();
+        index.add_entry(entry);
+
+        let index_bytes = index.as_vec();
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
+
+        assert_eq!(index_bytes[4..8], [0, 0, 0, 3]);
+        assert_eq!(index_from_bytes.get_entries(), index.get_entries());
+    }
+
+    #[test]
+    fn test_unmerged_entries_round_trip() {
+        let mut index = Index::new();
+        index.add_entry(create_entry("Cargo.toml"));
+        for stage in 1..=3 {
+            let mut entry = create_entry("README.md");
+            entry.set_stage(stage);
+            index.add_entry(entry);
+        }
+
+        let index_bytes = index.as_vec();
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
+
+        assert_eq!(index_bytes[8..12], [0, 0, 0, 4]);
+        assert_eq!(index_from_bytes, index);
+        assert!(index_from_bytes.is_unmerged("README.md"));
+        assert!(!index_from_bytes.has_entry("README.md"));
+        let stages = index_from_bytes.get_unmerged_entries()[0]
+            .1
+            .iter()
+            .map(IndexEntry::stage)
+            .collect::<Vec<_>>();
+        assert_eq!(stages, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_adding_entry_of_stage_0_resolves_unmerged_path() {
+        let mut index = Index::new();
+        let mut entry = create_entry("README.md");
+        entry.set_stage(2);
+        index.add_entry(entry);
+
+        index.add_entry(create_entry("README.md"));
+
+        assert!(!index.is_unmerged("README.md"));
+        assert!(index.has_entry("README.md"));
+        assert_eq!(index.get_all_entries().len(), 1);
+    }
+
+    #[test]
+    fn test_optional_extension_is_skipped() {
+        let mut index = Index::new();
+        index.add_entry(create_entry("Cargo.toml"));
+        let index_bytes = with_extension(&index.as_vec(), b"REUC", &[1, 2, 3]);
+
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
+
+        assert_eq!(index_from_bytes, index);
+    }
+
+    #[test]
+    fn test_cache_tree_round_trip() {
+        let mut index = Index::new();
+        index.add_entry(create_entry("nested/file.txt"));
+        let tree_id = create_entry("").object_id;
+        let subtrees = [(
+            String::from("nested"),
+            CacheTree::new(tree_id.clone(), 1, Default::default()),
+        )];
+        index.set_cache_tree(CacheTree::new(tree_id, 1, subtrees.into()));
+        index.invalidate_cache_tree("other.txt");
+
+        let index_bytes = index.as_vec();
+        let index_from_bytes = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .ok()
+            .unwrap();
+
+        assert_eq!(index_from_bytes, index);
+        assert_eq!(index_from_bytes.cache_tree().object_id(), None);
+        assert!(index_from_bytes
+            .cache_tree()
+            .subtree("nested")
+            .and_then(CacheTree::object_id)
+            .is_some());
+    }
+
+    #[test]
+    fn test_error_on_unknown_required_extension() {
+        let index_bytes = with_extension(&Index::new().as_vec(), b"link", &[1, 2, 3]);
+
+        let error = Index::from_bytes(&index_bytes, HashAlgorithm::Sha1)
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error,
+            "index uses link extension, which we do not understand"
+        );
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 16511, 16512, 1 << 20] {
+            let bytes = encode_varint(value);
+
+            let (decoded, size) = decode_varint(&bytes).unwrap();
+
+            assert_eq!((decoded, size), (value, bytes.len()));
+        }
+    }
+
     #[test]
     fn test_add_file_in_directory_with_name_clashing_with_index_entry() {
         let expected_index = {
@@ -504,9 +1037,9 @@ mod tests {
 
     #[test]
     fn test_as_vec() {
-        let bytes: Vec<u8> = (0..10).cycle().map(|i| i as u8).take(40).collect();
+        let bytes: Vec<u8> = (0..10).cycle().map(|i| i as u8).take(20).collect();
         let object_id = ObjectId::from_sha_bytes(&bytes).unwrap();
-        let hexlified_object_id = hex::hexlify(object_id.bytes());
+        let raw_object_id = object_id.bytes().to_vec();
         let entry = IndexEntry {
             ctime_seconds: 1657658046,
             ctime_nanoseconds: 444900053,
@@ -520,13 +1053,15 @@ mod tests {
             file_size: 262,
             path: PathBuf::from("Cargo.toml"),
             object_id,
+            flags: 0,
+            extended_flags: 0,
         };
 
         let mut expected_vec: Vec<u8> = vec![
             98, 205, 218, 190, 26, 132, 162, 213, 98, 205, 218, 190, 26, 132, 162, 213, 0, 0, 254,
             2, 0, 58, 117, 220, 0, 0, 129, 164, 0, 0, 3, 232, 0, 0, 3, 217, 0, 0, 1, 6,
         ];
-        expected_vec.extend_from_slice(&hexlified_object_id);
+        expected_vec.extend_from_slice(&raw_object_id);
         expected_vec.extend(vec![
             0, 10, 67, 97, 114, 103, 111, 46, 116, 111, 109, 108, 0, 0, 0, 0, 0, 0, 0, 0,
         ]);
@@ -535,7 +1070,7 @@ mod tests {
     }
 
     fn create_entry(path: &str) -> IndexEntry {
-        let bytes: Vec<u8> = (0..10).cycle().map(|i| i as u8).take(40).collect();
+        let bytes: Vec<u8> = (0..10).cycle().map(|i| i as u8).take(20).collect();
         let object_id = ObjectId::from_sha_bytes(&bytes).unwrap();
         IndexEntry {
             ctime_seconds: 1657658046,
@@ -550,10 +1085,23 @@ mod tests {
             file_size: 262,
             path: PathBuf::from(path),
             object_id,
+            flags: 0,
+            extended_flags: 0,
         }
     }
 
-    fn assert_vectors_equal<T: Debug + Eq>(actual: &Vec<T>, expected: &Vec<T>) {
+    /// Insert an extension between the entries and the checksum, and recompute the checksum.
+    fn with_extension(index_bytes: &[u8], signature: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut bytes = index_bytes[..(index_bytes.len() - 20)].to_vec();
+        bytes.extend_from_slice(signature);
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(data);
+        let checksum = HashAlgorithm::Sha1.hash(&bytes);
+        bytes.extend(checksum);
+        bytes
+    }
+
+    fn assert_vectors_equal<T: Debug + Eq>(actual: &[T], expected: &[T]) {
         if actual.len() != expected.len() {
             panic!(
                 "expected vector has length {}, but actual vector has length {}",
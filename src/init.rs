@@ -1,18 +1,131 @@
-use std::{fs, io, path::Path};
+use std::{fs, io, path::Path, path::PathBuf};
 
+use crate::config;
+use crate::file;
+use crate::hashing::HashAlgorithm;
 use crate::output::OutputWriter;
+use crate::refs;
+use crate::workspace;
+
+const DEFAULT_INITIAL_BRANCH: &str = "main";
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Name of the branch HEAD initially points to. Defaults to `init.defaultBranch` from the
+    /// config, or `main`.
+    #[builder(default)]
+    pub initial_branch: Option<String>,
+
+    /// Directory whose contents, such as hooks and `info/exclude`, are copied into the new git
+    /// directory.
+    #[builder(default)]
+    pub template: Option<PathBuf>,
+
+    /// Hash algorithm of the new repository. Defaults to sha1.
+    #[builder(default)]
+    pub object_format: Option<HashAlgorithm>,
+}
+
+/// Parse the name of a hash algorithm given to `--object-format`.
+///
+/// # Examples
+/// ```
+/// use rut::hashing::HashAlgorithm;
+/// use rut::init;
+///
+/// assert_eq!(init::parse_object_format("sha256"), Ok(HashAlgorithm::Sha256));
+/// assert!(init::parse_object_format("md5").is_err());
+/// ```
+pub fn parse_object_format(s: &str) -> Result<HashAlgorithm, String> {
+    HashAlgorithm::parse(s).ok_or_else(|| format!("unknown hash algorithm '{}'", s))
+}
+
+/// Initialize a repository in the git directory. If there already is a repository, only missing
+/// directories and template files are created, and HEAD and all refs are left untouched.
+pub fn init<P: AsRef<Path>>(
+    git_dir: P,
+    options: &Options,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let git_dir = git_dir.as_ref();
+    let is_reinit = git_dir.join("HEAD").is_file();
+    let initial_branch = options
+        .initial_branch
+        .clone()
+        .or_else(config::read_default_branch)
+        .unwrap_or_else(|| String::from(DEFAULT_INITIAL_BRANCH));
+    if !refs::is_valid_branch_name(&initial_branch) {
+        let message = format!("invalid initial branch name: '{}'", initial_branch);
+        return Err(crate::Error::Fatal(None, message));
+    }
 
-pub fn init<P: AsRef<Path>>(git_dir: P, writer: &mut dyn OutputWriter) -> io::Result<()> {
     for subdir in ["objects", "refs/heads"] {
-        fs::create_dir_all(git_dir.as_ref().join(subdir))?;
+        fs::create_dir_all(git_dir.join(subdir))?;
+    }
+    if let Some(template) = &options.template {
+        copy_template(template, git_dir)?;
+    }
+
+    if is_reinit {
+        let existing_format = workspace::read_hash_algorithm(git_dir)?;
+        if options
+            .object_format
+            .is_some_and(|format| format != existing_format)
+        {
+            let message = "attempt to reinitialize repository with different hash".to_owned();
+            return Err(crate::Error::Fatal(None, message));
+        }
+        if let Some(initial_branch) = &options.initial_branch {
+            writer.writeln(format!(
+                "warning: re-init: ignored --initial-branch={}",
+                initial_branch
+            ))?;
+        }
+    } else {
+        fs::write(
+            git_dir.join("HEAD"),
+            format!("ref: refs/heads/{}", initial_branch),
+        )?;
+        if let Some(object_format) = options
+            .object_format
+            .filter(|format| *format != HashAlgorithm::Sha1)
+        {
+            // repository format version 1 tells other tools to respect the extension
+            let config_file = git_dir.join("config");
+            config::write_config_value(&config_file, "core.repositoryformatversion", "1")?;
+            config::write_config_value(
+                &config_file,
+                "extensions.objectformat",
+                object_format.name(),
+            )?;
+        }
+    }
+
+    Ok(write_init_message(git_dir, is_reinit, writer)?)
+}
+
+/// Copy the files of the template directory into the git directory, without overwriting any
+/// existing files.
+fn copy_template(template: &Path, git_dir: &Path) -> crate::Result<()> {
+    if !template.is_dir() {
+        let message = format!("template directory '{}' does not exist", template.display());
+        return Err(crate::Error::Fatal(None, message));
     }
-    fs::write(git_dir.as_ref().join("HEAD"), "ref: refs/heads/main")?;
 
-    write_init_message(git_dir.as_ref(), writer)
+    // copying also preserves the permissions, which keeps hooks executable
+    Ok(file::copy_directory(template, git_dir)?)
 }
 
-fn write_init_message(git_dir: &Path, writer: &mut dyn OutputWriter) -> io::Result<()> {
-    let message = format!("Initialized empty Rut repository in {:#?}", git_dir);
+fn write_init_message(
+    git_dir: &Path,
+    is_reinit: bool,
+    writer: &mut dyn OutputWriter,
+) -> io::Result<()> {
+    let message = if is_reinit {
+        format!("Reinitialized existing Rut repository in {:#?}", git_dir)
+    } else {
+        format!("Initialized empty Rut repository in {:#?}", git_dir)
+    };
     writer.write(message)?;
     Ok(())
 }
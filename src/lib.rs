@@ -7,42 +7,156 @@ pub mod workspace;
 
 pub mod config;
 
+pub mod alias;
+
 pub mod init;
 
 pub mod commit;
 
+pub mod editor;
+
 pub mod objects;
 
+pub mod object_cache;
+
 pub mod hex;
 
 pub mod index;
 
+pub mod cache_tree;
+
+pub mod untracked_cache;
+
 pub mod add;
 
+pub mod pathspec;
+
+pub mod filter;
+
+pub mod prompt;
+
 pub mod hashing;
 
+pub mod platform;
+
 mod file;
 
 pub mod rm;
 
+pub mod mv;
+
+pub mod hooks;
+
 pub mod refs;
 
+pub mod reflog;
+
+pub mod update_ref;
+
+pub mod symbolic_ref;
+
+pub mod show_ref;
+
+pub mod for_each_ref;
+
 pub mod output;
 
+pub mod trace;
+
 pub mod status;
 
 pub mod diff;
 
+mod diff_algorithms;
+
+pub mod difftool;
+
+pub mod patch;
+
+pub mod patch_id;
+
+pub mod range_diff;
+
+pub mod format_patch;
+
+pub mod am;
+
+pub mod rename;
+
 pub mod restore;
 
+pub mod switch;
+
 pub mod log;
 
+pub mod shortlog;
+
+pub mod mailmap;
+
 pub mod branch;
 
 mod object_resolver;
 
 pub mod revparse;
 
+pub mod rev_walk;
+
+pub mod commit_graph;
+
+pub mod rev_list;
+
+pub mod merge_base;
+
+pub mod merge;
+
+pub mod rerere;
+
+pub mod rebase;
+
+pub mod cherry;
+
+mod graph;
+
+pub mod cat_file;
+
+pub mod count_objects;
+
+pub mod fsck;
+
+pub mod prune;
+
+pub mod hash_object;
+
+pub mod ls_files;
+
+pub mod checkout_index;
+
+pub mod update_index;
+
+pub mod ls_tree;
+
+pub mod read_tree;
+
+pub mod archive;
+
+pub mod pack;
+
+pub mod bundle;
+
+pub mod fast_export;
+
+pub mod fast_import;
+
+pub mod blame;
+
+pub mod grep;
+
+pub mod clean;
+
+pub mod submodule;
+
+pub mod worktree;
+
 pub mod error;
 
 pub use crate::error::{Error, Result};
@@ -1,17 +1,67 @@
+use std::collections::HashMap;
 use std::io;
+use std::path::{Path, PathBuf};
 
-use chrono::{Local, TimeZone};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use regex::Regex;
 
-use crate::objects::{Commit, GitObject};
-use crate::output::{Color, OutputWriter, Style};
-use crate::refs::RefHandler;
+use crate::diff;
+use crate::graph::{Graph, GraphWriter};
+use crate::mailmap::Mailmap;
+use crate::object_resolver::ObjectResolver;
+use crate::objects::{Author, Commit, GitObject, ObjectId};
+use crate::output::{Color, JsonValue, OutputWriter, Serializer, Style};
+use crate::refs::{RefHandler, RevisionRange};
+use crate::rev_walk::RevWalk;
+use crate::status::ChangeType;
 use crate::workspace::Repository;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub enum Format {
+    /// The `medium` preset.
     #[default]
     Default,
     Oneline,
+    Short,
+    Full,
+    Fuller,
+    /// A template with placeholders, see [`format_commit`].
+    Custom(String),
+    /// One JSON object per line for each commit. Diffs and stats are left out.
+    Json,
+}
+
+/// Parse the argument to `--pretty`, which is either the name of a preset or a template on the
+/// form `format:<template>`.
+///
+/// # Examples
+/// ```
+/// use rut::log::{self, Format};
+///
+/// assert_eq!(log::parse_format("fuller"), Ok(Format::Fuller));
+/// assert_eq!(
+///     log::parse_format("format:%h %s"),
+///     Ok(Format::Custom(String::from("%h %s")))
+/// );
+/// assert!(log::parse_format("prettiest").is_err());
+/// ```
+pub fn parse_format(s: &str) -> Result<Format, String> {
+    if let Some(template) = s
+        .strip_prefix("format:")
+        .or_else(|| s.strip_prefix("tformat:"))
+    {
+        return Ok(Format::Custom(template.to_owned()));
+    }
+
+    match s {
+        "oneline" => Ok(Format::Oneline),
+        "short" => Ok(Format::Short),
+        "medium" => Ok(Format::Default),
+        "full" => Ok(Format::Full),
+        "fuller" => Ok(Format::Fuller),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("invalid --pretty format: {}", s)),
+    }
 }
 
 #[derive(Default, Builder, Debug)]
@@ -21,6 +71,192 @@ pub struct Options {
 
     #[builder(default)]
     pub format: Format,
+
+    /// Show the diff of each commit against its parent.
+    #[builder(default)]
+    pub patch: bool,
+
+    /// Show a summary of the changed files of each commit.
+    #[builder(default)]
+    pub stat: bool,
+
+    /// Only show commits that touch these paths.
+    #[builder(default)]
+    pub paths: Vec<PathBuf>,
+
+    /// Continue listing the history of a single path across renames.
+    #[builder(default)]
+    pub follow: bool,
+
+    /// The revision or revision range to log, defaults to HEAD.
+    #[builder(default)]
+    pub revision: Option<String>,
+
+    /// Draw an ASCII graph of the history next to the log.
+    #[builder(default)]
+    pub graph: bool,
+
+    /// Only show commits whose author matches this pattern.
+    #[builder(default)]
+    pub author: Option<String>,
+
+    /// Only show commits whose message matches this pattern.
+    #[builder(default)]
+    pub grep: Option<String>,
+
+    /// Only show commits made at or after this Unix timestamp.
+    #[builder(default)]
+    pub since: Option<u64>,
+
+    /// Only show commits made at or before this Unix timestamp.
+    #[builder(default)]
+    pub until: Option<u64>,
+
+    /// Show authors and committers by their canonical identity in the mailmap. Defaults to the
+    /// `log.mailmap` config, or true if it's unset.
+    #[builder(default)]
+    pub use_mailmap: Option<bool>,
+}
+
+/// Parse a date as either an absolute date on the form `YYYY-MM-DD`, optionally followed by a
+/// time `HH:MM:SS`, a relative date on the form `<number> <unit> ago`, or `now`. Returns the date
+/// as a Unix timestamp.
+///
+/// # Examples
+/// ```
+/// use rut::log;
+///
+/// assert_eq!(log::parse_date("1970-01-02 00:00:00 +0000"), Ok(86400));
+/// assert!(log::parse_date("2 weeks ago").is_ok());
+/// assert!(log::parse_date("now").is_ok());
+/// assert!(log::parse_date("the other day").is_err());
+/// ```
+pub fn parse_date(s: &str) -> Result<u64, String> {
+    let error = || format!("invalid date '{}'", s);
+    let s = s.trim();
+
+    let timestamp = if s == "now" {
+        Local::now().timestamp()
+    } else if let Some(relative) = s.strip_suffix(" ago") {
+        let (amount, unit) = relative.trim().split_once(' ').ok_or_else(error)?;
+        let amount = amount.parse::<i64>().map_err(|_| error())?;
+        let unit_seconds = match unit.trim_end_matches('s') {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 60 * 60,
+            "day" => 24 * 60 * 60,
+            "week" => 7 * 24 * 60 * 60,
+            "month" => 30 * 24 * 60 * 60,
+            "year" => 365 * 24 * 60 * 60,
+            _ => return Err(error()),
+        };
+        Local::now().timestamp() - amount * unit_seconds
+    } else if let Ok(datetime) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S %z") {
+        datetime.timestamp()
+    } else if let Ok(datetime) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S") {
+        to_local_timestamp(datetime).ok_or_else(error)?
+    } else if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        to_local_timestamp(date.and_time(NaiveTime::MIN)).ok_or_else(error)?
+    } else {
+        return Err(error());
+    };
+
+    u64::try_from(timestamp).map_err(|_| error())
+}
+
+fn to_local_timestamp(datetime: NaiveDateTime) -> Option<i64> {
+    Local
+        .from_local_datetime(&datetime)
+        .earliest()
+        .map(|datetime| datetime.timestamp())
+}
+
+/// A ref pointing at a commit, shown next to the commit in the log.
+#[derive(Debug, PartialEq)]
+pub enum Decoration {
+    /// HEAD, along with the branch it points to unless it's detached.
+    Head(Option<String>),
+    Branch(String),
+    Tag(String),
+}
+
+/// Map each commit pointed to by HEAD, a local branch or a tag to its decorations.
+fn resolve_decorations(
+    repository: &Repository,
+) -> crate::Result<HashMap<ObjectId, Vec<Decoration>>> {
+    let refs = RefHandler::new(repository);
+    let current_branch = refs.current_branch()?;
+
+    let mut decorations: HashMap<ObjectId, Vec<Decoration>> = HashMap::new();
+    if let Ok(head_commit_id) = refs.head() {
+        decorations
+            .entry(head_commit_id)
+            .or_default()
+            .push(Decoration::Head(current_branch.clone()));
+    }
+
+    for (branch, commit_id) in refs.list_refs("refs/heads")? {
+        if current_branch.as_ref() != Some(&branch) {
+            decorations
+                .entry(commit_id)
+                .or_default()
+                .push(Decoration::Branch(branch));
+        }
+    }
+    for (tag, commit_id) in refs.list_refs("refs/tags")? {
+        decorations
+            .entry(commit_id)
+            .or_default()
+            .push(Decoration::Tag(tag));
+    }
+
+    Ok(decorations)
+}
+
+/// Filters on the author, message and date of a commit.
+struct CommitFilter {
+    author: Option<Regex>,
+    grep: Option<Regex>,
+    since: Option<u64>,
+    until: Option<u64>,
+}
+
+impl CommitFilter {
+    fn new(options: &Options) -> crate::Result<CommitFilter> {
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_ref()
+                .map(|pattern| {
+                    Regex::new(pattern).map_err(|error| {
+                        let message = format!("invalid pattern '{}'", pattern);
+                        crate::Error::Fatal(Some(Box::new(error)), message)
+                    })
+                })
+                .transpose()
+        };
+
+        Ok(CommitFilter {
+            author: compile(&options.author)?,
+            grep: compile(&options.grep)?,
+            since: options.since,
+            until: options.until,
+        })
+    }
+
+    fn matches(&self, commit: &Commit) -> bool {
+        let author_matches = self
+            .author
+            .as_ref()
+            .is_none_or(|author| author.is_match(&commit.author.to_string()));
+        let message_matches = self
+            .grep
+            .as_ref()
+            .is_none_or(|grep| grep.is_match(&commit.message));
+        let is_after_since = self.since.is_none_or(|since| commit.timestamp >= since);
+        let is_before_until = self.until.is_none_or(|until| commit.timestamp <= until);
+
+        author_matches && message_matches && is_after_since && is_before_until
+    }
 }
 
 pub fn log(
@@ -28,41 +264,394 @@ pub fn log(
     options: &Options,
     writer: &mut dyn OutputWriter,
 ) -> crate::Result<()> {
-    let refs = RefHandler::new(repository);
-    let head_commit = repository.database.load_commit(&refs.head()?)?;
+    let decorations = resolve_decorations(repository)?;
 
-    let write_log = match options.format {
-        Format::Oneline => write_log_message_oneline,
-        Format::Default => write_log_message,
+    let mut paths = options.paths.clone();
+    let mut num_written_commits = 0;
+    let max_count = options.max_count.unwrap_or(u32::MAX);
+
+    let filter = CommitFilter::new(options)?;
+    let use_mailmap = match options.use_mailmap {
+        Some(use_mailmap) => use_mailmap,
+        None => repository
+            .config()?
+            .get_bool("log.mailmap")?
+            .unwrap_or(true),
+    };
+    let mailmap = if use_mailmap {
+        Mailmap::load(repository)?
+    } else {
+        Mailmap::default()
     };
+    let mut graph = Graph::default();
+    let range = RevisionRange::parse(options.revision.as_deref().unwrap_or("HEAD"))?;
+    for commit in RevWalk::from_range(&range, repository)? {
+        if num_written_commits >= max_count {
+            break;
+        }
 
-    write_log(&head_commit, Some("main"), writer)?;
+        let mut commit = commit?;
+        commit.author = mailmap.lookup(&commit.author);
+        commit.committer = mailmap.lookup(&commit.committer);
+        let mut diff_paths = paths.clone();
+        let is_shown = filter.matches(&commit)
+            && (paths.is_empty()
+                || touches_paths(&commit, &mut paths, options.follow, repository)?);
+        // the diff of a commit that renames a followed path shows both of its names
+        if diff_paths != paths {
+            diff_paths.extend(paths.iter().cloned());
+        }
+        if !is_shown {
+            if options.graph {
+                graph.skip(&commit);
+            }
+            continue;
+        }
 
-    let mut num_written_commits = 1;
-    let max_count = options.max_count.unwrap_or(u32::MAX);
+        let commit_decorations = decorations
+            .get(commit.id())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
 
-    let mut commit = head_commit;
-    while commit.parent.is_some() && num_written_commits < max_count {
-        commit = repository.database.load_commit(&commit.parent.unwrap())?;
-        write_log(&commit, None, writer)?;
+        if options.graph {
+            let mut graph_writer = GraphWriter::new(writer, graph.place(&commit));
+            write_log_entry(
+                &commit,
+                commit_decorations,
+                &options.format,
+                repository,
+                &mut graph_writer,
+            )?;
+            write_diff(&commit, options, &diff_paths, repository, &mut graph_writer)?;
+            for line in graph.advance(&commit) {
+                writer.writeln(line)?;
+            }
+        } else {
+            write_log_entry(
+                &commit,
+                commit_decorations,
+                &options.format,
+                repository,
+                writer,
+            )?;
+            write_diff(&commit, options, &diff_paths, repository, writer)?;
+        }
         num_written_commits += 1;
     }
 
     Ok(())
 }
 
+/// Check if the commit changes any of the paths compared to its parent. When following renames,
+/// a path that was renamed in this commit is replaced with its name in the parent.
+fn touches_paths(
+    commit: &Commit,
+    paths: &mut [PathBuf],
+    follow: bool,
+    repository: &Repository,
+) -> crate::Result<bool> {
+    let parent_tree = commit
+        .parent()
+        .as_ref()
+        .map(|parent_id| repository.database.peel_to_tree(parent_id))
+        .transpose()?;
+    let tree = repository.database.load_tree(&commit.tree)?;
+    let changes = diff::compare_trees(parent_tree.as_ref(), Some(&tree), repository)?;
+
+    let touched = changes
+        .iter()
+        .any(|change| matches_paths(&change.path, paths));
+
+    if follow && touched {
+        if let (Some(parent_id), [path]) = (commit.parent(), paths) {
+            let created_here = changes.iter().any(|change| {
+                matches!(change.change_type, ChangeType::Created) && change.path == *path
+            });
+            if created_here {
+                let deleted_paths = changes
+                    .iter()
+                    .filter(|change| matches!(change.change_type, ChangeType::Deleted))
+                    .map(|change| &change.path);
+                if let Some(source) =
+                    find_rename_source(commit.id(), parent_id, path, deleted_paths, repository)?
+                {
+                    *path = source;
+                }
+            }
+        }
+    }
+
+    Ok(touched)
+}
+
+/// Find the deleted path in the parent that has the exact same content as the created path.
+fn find_rename_source<'a>(
+    commit_id: &ObjectId,
+    parent_id: &ObjectId,
+    created_path: &Path,
+    deleted_paths: impl Iterator<Item = &'a PathBuf>,
+    repository: &Repository,
+) -> crate::Result<Option<PathBuf>> {
+    let mut resolver = ObjectResolver::from_reference(&commit_id.to_string(), repository)?;
+    let mut parent_resolver = ObjectResolver::from_reference(&parent_id.to_string(), repository)?;
+    let created_blob = resolver.find_blob_by_path(created_path)?;
+
+    for deleted_path in deleted_paths {
+        let deleted_blob = parent_resolver.find_blob_by_path(deleted_path)?;
+        if deleted_blob.id() == created_blob.id() {
+            return Ok(Some(deleted_path.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn matches_paths(path: &Path, paths: &[PathBuf]) -> bool {
+    paths.is_empty() || paths.iter().any(|prefix| path.starts_with(prefix))
+}
+
+fn write_diff(
+    commit: &Commit,
+    options: &Options,
+    paths: &[PathBuf],
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if !options.patch && !options.stat || options.format == Format::Json {
+        return Ok(());
+    }
+
+    if !matches!(options.format, Format::Oneline | Format::Custom(_)) {
+        writer.linefeed()?;
+    }
+
+    let mut file_diffs = diff::diff_commits(commit.parent(), commit.id(), repository)?;
+    file_diffs.retain(|file_diff| matches_paths(file_diff.path(), paths));
+    let algorithm = diff::Algorithm::from_config(repository)?;
+    if options.stat {
+        diff::write_stat(&file_diffs, algorithm, writer)?;
+    }
+    if options.patch {
+        if options.stat {
+            writer.linefeed()?;
+        }
+        diff::write_patch(&file_diffs, diff::DEFAULT_CONTEXT_LINES, algorithm, writer)?;
+    }
+
+    Ok(())
+}
+
+fn write_log_entry(
+    commit: &Commit,
+    decorations: &[Decoration],
+    format: &Format,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> io::Result<()> {
+    let abbreviate = |object_id: &ObjectId| repository.database.abbreviate(object_id);
+    match format {
+        Format::Oneline => write_log_message_oneline(commit, decorations, &abbreviate, writer),
+        Format::Custom(template) => {
+            writer.writeln(format_commit(template, commit, decorations, &abbreviate))?;
+            Ok(())
+        }
+        Format::Json => write_log_json(commit, decorations, writer),
+        preset => write_log_message(commit, decorations, preset, writer),
+    }
+}
+
+/// Write the commit as a JSON object, with its refs given by their full names.
+fn write_log_json(
+    commit: &Commit,
+    decorations: &[Decoration],
+    writer: &mut dyn OutputWriter,
+) -> io::Result<()> {
+    let identity = |author: &Author| {
+        JsonValue::Object(vec![
+            (String::from("name"), author.name.as_str().into()),
+            (String::from("email"), author.email.as_str().into()),
+        ])
+    };
+    let parents = commit
+        .parents
+        .iter()
+        .map(ObjectId::to_string)
+        .collect::<Vec<_>>();
+    let refs = decorations
+        .iter()
+        .flat_map(|decoration| match decoration {
+            Decoration::Head(Some(branch)) => {
+                vec![String::from("HEAD"), format!("refs/heads/{}", branch)]
+            }
+            Decoration::Head(None) => vec![String::from("HEAD")],
+            Decoration::Branch(branch) => vec![format!("refs/heads/{}", branch)],
+            Decoration::Tag(tag) => vec![format!("refs/tags/{}", tag)],
+        })
+        .collect::<Vec<_>>();
+    Serializer::new(writer)
+        .field("id", commit.id_as_string())
+        .field("tree", commit.tree.to_string())
+        .field("parents", parents)
+        .field("author", identity(&commit.author))
+        .field("author_timestamp", commit.timestamp)
+        .field("author_timezone", commit.timezone.as_str())
+        .field("committer", identity(&commit.committer))
+        .field("committer_timestamp", commit.committer_timestamp)
+        .field("committer_timezone", commit.committer_timezone.as_str())
+        .field("refs", refs)
+        .field("subject", split_message(&commit.message).0)
+        .field("message", commit.message.as_str())
+        .write()
+}
+
+/// Expand the placeholders in the template with the details of the commit. Supported
+/// placeholders are:
+///
+/// * `%H`, `%h`: commit hash and abbreviated commit hash
+/// * `%T`, `%t`: tree hash and abbreviated tree hash
+/// * `%P`, `%p`: parent hashes and abbreviated parent hashes
+/// * `%an`, `%ae`: author name and email
+/// * `%ad`, `%at`, `%ai`, `%as`: author date in the default format, as a Unix timestamp, in ISO
+///   8601-like format and as a short `YYYY-MM-DD` date
+/// * `%cn`, `%ce`, `%cd`, `%ct`, `%ci`, `%cs`: the same for the committer
+/// * `%s`, `%b`, `%B`: subject, body and raw message
+/// * `%d`, `%D`: decorations with and without the surrounding parentheses
+/// * `%n`, `%%`: a newline and a literal `%`
+///
+/// Unknown placeholders are left as is. Abbreviated hashes are abbreviated with the given
+/// function.
+///
+/// # Examples
+/// ```
+/// use rut::log;
+/// use rut::objects::{Author, Commit, ObjectId};
+///
+/// let tree = ObjectId::from_sha("4b825dc642cb6eb9a060e54bf8d69288fbee4904").unwrap();
+/// let author = Author {
+///     name: String::from("Jane Doe"),
+///     email: String::from("jane@example.com"),
+/// };
+/// let commit = Commit::new(tree, author, String::from("Fix it"), None, 0);
+///
+/// assert_eq!(
+///     log::format_commit("%an <%ae>: %s%n%t", &commit, &[], &ObjectId::to_short_string),
+///     "Jane Doe <jane@example.com>: Fix it\n4b825dc"
+/// );
+/// ```
+pub fn format_commit(
+    template: &str,
+    commit: &Commit,
+    decorations: &[Decoration],
+    abbreviate: &dyn Fn(&ObjectId) -> String,
+) -> String {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(position) = rest.find('%') {
+        output.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        let (expansion, placeholder_len) =
+            expand_placeholder(rest, commit, decorations, abbreviate)
+                .unwrap_or_else(|| (String::from("%"), 0));
+        output.push_str(&expansion);
+        rest = &rest[placeholder_len..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Expand the placeholder at the start of the string, returning the expansion and the length of
+/// the placeholder.
+fn expand_placeholder(
+    s: &str,
+    commit: &Commit,
+    decorations: &[Decoration],
+    abbreviate: &dyn Fn(&ObjectId) -> String,
+) -> Option<(String, usize)> {
+    let join_ids = |to_string: &dyn Fn(&ObjectId) -> String| {
+        commit
+            .parents
+            .iter()
+            .map(to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let (subject, body) = split_message(&commit.message);
+
+    let single_char_expansion = match s.chars().next()? {
+        'H' => Some(commit.id_as_string()),
+        'h' => Some(abbreviate(commit.id())),
+        'T' => Some(commit.tree.to_string()),
+        't' => Some(abbreviate(&commit.tree)),
+        'P' => Some(join_ids(&ObjectId::to_string)),
+        'p' => Some(join_ids(abbreviate)),
+        's' => Some(subject.to_owned()),
+        'b' => Some(body.to_owned()),
+        'B' => Some(commit.message.clone()),
+        'd' if decorations.is_empty() => Some(String::new()),
+        'd' => Some(format!(" ({})", decorations_to_string(decorations))),
+        'D' => Some(decorations_to_string(decorations)),
+        'n' => Some(String::from("\n")),
+        '%' => Some(String::from("%")),
+        _ => None,
+    };
+    if let Some(expansion) = single_char_expansion {
+        return Some((expansion, 1));
+    }
+
+    let expansion = match s.get(..2)? {
+        "an" => commit.author.name.clone(),
+        "ae" => commit.author.email.clone(),
+        "ad" => to_local_timestring(commit.timestamp)?,
+        "at" => commit.timestamp.to_string(),
+        "ai" => format_local_timestamp(commit.timestamp, "%Y-%m-%d %H:%M:%S %z")?,
+        "as" => format_local_timestamp(commit.timestamp, "%Y-%m-%d")?,
+        "cn" => commit.committer.name.clone(),
+        "ce" => commit.committer.email.clone(),
+        "cd" => to_local_timestring(commit.committer_timestamp)?,
+        "ct" => commit.committer_timestamp.to_string(),
+        "ci" => format_local_timestamp(commit.committer_timestamp, "%Y-%m-%d %H:%M:%S %z")?,
+        "cs" => format_local_timestamp(commit.committer_timestamp, "%Y-%m-%d")?,
+        _ => return None,
+    };
+    Some((expansion, 2))
+}
+
+/// Split a commit message into its subject line and the body following the first blank line.
+pub(crate) fn split_message(message: &str) -> (&str, &str) {
+    let message = message.trim_end();
+    match message.split_once("\n\n") {
+        Some((subject, body)) => (subject.trim_end(), body.trim_start_matches('\n')),
+        None => (message.lines().next().unwrap_or_default(), ""),
+    }
+}
+
+fn decorations_to_string(decorations: &[Decoration]) -> String {
+    decorations
+        .iter()
+        .map(|decoration| match decoration {
+            Decoration::Head(Some(branch)) => format!("HEAD -> {}", branch),
+            Decoration::Head(None) => String::from("HEAD"),
+            Decoration::Branch(branch) => branch.clone(),
+            Decoration::Tag(tag) => format!("tag: {}", tag),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 fn write_log_message_oneline(
     commit: &Commit,
-    branch: Option<&str>,
+    decorations: &[Decoration],
+    abbreviate: &dyn Fn(&ObjectId) -> String,
     writer: &mut dyn OutputWriter,
 ) -> io::Result<()> {
     writer
         .set_color(Color::Brown)?
-        .write(commit.short_id_as_string())?;
+        .write(abbreviate(commit.id()))?;
 
-    if let Some(branch) = branch {
-        write_branch(branch, writer)?
-    }
+    write_decorations(decorations, writer)?;
 
     let first_line_of_message = commit.message.lines().next().unwrap();
     writer
@@ -71,56 +660,104 @@ fn write_log_message_oneline(
     Ok(())
 }
 
+/// Write the commit in one of the multi-line presets.
 fn write_log_message(
     commit: &Commit,
-    branch: Option<&str>,
+    decorations: &[Decoration],
+    preset: &Format,
     writer: &mut dyn OutputWriter,
 ) -> io::Result<()> {
-    let timestamp_parse_error = io::Error::new(io::ErrorKind::Other, "Failed to parse timestamp");
+    let timestring = to_local_timestring(commit.timestamp)
+        .ok_or_else(|| io::Error::other("Failed to parse timestamp"))?;
 
     writer
         .set_color(Color::Brown)?
         .write(format!("commit {}", commit.id_as_string()))?;
 
-    if let Some(branch) = branch {
-        write_branch(branch, writer)?
-    }
+    write_decorations(decorations, writer)?;
+    writer.reset_formatting()?.linefeed()?;
 
-    writer.reset_formatting()?.writeln(format!(
-        "
-Author: {}
-Date:   {}
+    let header = match preset {
+        Format::Short => format!("Author: {}", commit.author),
+        Format::Full => format!("Author: {}\nCommit: {}", commit.author, commit.committer),
+        Format::Fuller => format!(
+            "Author:     {}\nAuthorDate: {}\nCommit:     {}\nCommitDate: {}",
+            commit.author,
+            timestring,
+            commit.committer,
+            to_local_timestring(commit.committer_timestamp)
+                .ok_or_else(|| io::Error::other("Failed to parse timestamp"))?
+        ),
+        _ => format!("Author: {}\nDate:   {}", commit.author, timestring),
+    };
+    writer.writeln(header)?;
+    writer.linefeed()?;
 
-    {}",
-        commit.author,
-        to_local_timestring(commit.timestamp).ok_or(timestamp_parse_error)?,
-        commit.message
-    ))?;
+    let message = match preset {
+        Format::Short => split_message(&commit.message).0,
+        _ => commit.message.trim_end(),
+    };
+    for line in message.lines() {
+        if line.is_empty() {
+            writer.linefeed()?;
+        } else {
+            writer.writeln(format!("    {}", line))?;
+        }
+    }
     Ok(())
 }
 
-fn write_branch(branch: &str, writer: &mut dyn OutputWriter) -> io::Result<()> {
-    writer
-        .write(" (".to_string())?
-        .set_color(Color::Cyan)?
-        .set_style(Style::Bold)?
-        .write("HEAD -> ".to_string())?
-        .set_color(Color::Green)?
-        .write(branch.to_string())?
-        .set_color(Color::Brown)?
-        .set_style(Style::Normal)?
-        .write(")".to_string())?;
+fn write_decorations(decorations: &[Decoration], writer: &mut dyn OutputWriter) -> io::Result<()> {
+    if decorations.is_empty() {
+        return Ok(());
+    }
+
+    writer.write(" (".to_string())?;
+    for (i, decoration) in decorations.iter().enumerate() {
+        if i > 0 {
+            writer.write(", ".to_string())?;
+        }
+
+        match decoration {
+            Decoration::Head(branch) => {
+                writer
+                    .set_color(Color::Cyan)?
+                    .set_style(Style::Bold)?
+                    .write("HEAD".to_string())?;
+                if let Some(branch) = branch {
+                    writer
+                        .write(" -> ".to_string())?
+                        .set_color(Color::Green)?
+                        .write(branch.to_string())?;
+                }
+            }
+            Decoration::Branch(branch) => {
+                writer
+                    .set_color(Color::Green)?
+                    .set_style(Style::Bold)?
+                    .write(branch.to_string())?;
+            }
+            Decoration::Tag(tag) => {
+                writer
+                    .set_color(Color::Brown)?
+                    .set_style(Style::Bold)?
+                    .write(format!("tag: {}", tag))?;
+            }
+        }
+        writer.set_color(Color::Brown)?.set_style(Style::Normal)?;
+    }
+    writer.write(")".to_string())?;
     Ok(())
 }
 
 pub fn to_local_timestring(timestamp: u64) -> Option<String> {
+    format_local_timestamp(timestamp, "%a %b%e %T %Y %z")
+}
+
+fn format_local_timestamp(timestamp: u64, format: &str) -> Option<String> {
     let local_time = Local::now();
-    let datetime =
-        local_time
-            .timezone()
-            .from_utc_datetime(&chrono::NaiveDateTime::from_timestamp_opt(
-                timestamp as i64,
-                0,
-            )?);
-    Some(datetime.format("%a %b%e %T %Y %z").to_string())
+    let datetime = local_time
+        .timezone()
+        .from_utc_datetime(&chrono::DateTime::from_timestamp(timestamp as i64, 0)?.naive_utc());
+    Some(datetime.format(format).to_string())
 }
@@ -0,0 +1,40 @@
+use crate::output::OutputWriter;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub stage: bool,
+
+    /// Terminate each record with NUL instead of a linefeed.
+    #[builder(default)]
+    pub null_terminated: bool,
+}
+
+/// List the paths tracked in the index in sorted order.
+pub fn ls_files(
+    repository: &Repository,
+    options: &Options,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let index = repository.load_index_unlocked()?;
+    let terminator = if options.null_terminated { "\0" } else { "\n" };
+
+    for entry in index.get_all_entries() {
+        let path = entry.path.display();
+        let record = if options.stage {
+            format!(
+                "{:o} {} {}\t{}",
+                entry.raw_mode(),
+                entry.object_id,
+                entry.stage(),
+                path
+            )
+        } else {
+            path.to_string()
+        };
+        writer.write(format!("{}{}", record, terminator))?;
+    }
+
+    Ok(())
+}
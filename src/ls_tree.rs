@@ -0,0 +1,138 @@
+use std::path::Path;
+
+use crate::index::FileMode;
+use crate::objects::{ObjectType, Tree, TreeEntry};
+use crate::output::OutputWriter;
+use crate::refs::Revision;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub recursive: bool,
+
+    /// Only show entries at or below this path.
+    #[builder(default)]
+    pub path: Option<String>,
+}
+
+/// List the contents of a tree, or of the root tree of a commit.
+pub fn ls_tree(
+    tree_ish: &str,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let object_id = Revision::parse(tree_ish)?.resolve(repository)?;
+    let tree = repository
+        .database
+        .peel_to_tree(&object_id)
+        .map_err(|error| {
+            crate::Error::Fatal(
+                Some(Box::new(error)),
+                format!("not a tree object: {}", tree_ish),
+            )
+        })?;
+
+    let entries = match &options.path {
+        Some(path) if options.recursive => {
+            let prefix = path.trim_end_matches('/');
+            collect_recursive(&tree, repository)?
+                .into_iter()
+                .filter(|(entry_path, _)| Path::new(entry_path).starts_with(prefix))
+                .collect()
+        }
+        Some(path) => collect_at_path(&tree, path, repository)?,
+        None if options.recursive => collect_recursive(&tree, repository)?,
+        None => with_paths("", tree.entries()),
+    };
+
+    for (path, entry) in entries {
+        write_entry(&path, &entry, writer)?;
+    }
+
+    Ok(())
+}
+
+fn collect_recursive(
+    tree: &Tree,
+    repository: &Repository,
+) -> crate::Result<Vec<(String, TreeEntry)>> {
+    let mut entries = vec![];
+    repository
+        .database
+        .extract_entries_from_tree(String::new(), tree, &mut entries)?;
+    Ok(entries)
+}
+
+/// Find the entry named by the path. If the path ends with a slash and names a directory, the
+/// contents of the directory are returned instead.
+fn collect_at_path(
+    root_tree: &Tree,
+    path: &str,
+    repository: &Repository,
+) -> crate::Result<Vec<(String, TreeEntry)>> {
+    let list_contents = path.ends_with('/');
+    let path = Path::new(path.trim_end_matches('/'));
+
+    let mut subtree: Option<Tree> = None;
+    let mut components = path.iter().peekable();
+    let mut parent_path = String::new();
+    while let Some(component) = components.next() {
+        let tree = subtree.as_ref().unwrap_or(root_tree);
+        let name = component.to_str().unwrap_or("");
+        let entry = match tree.entries().iter().find(|entry| entry.name == name) {
+            Some(entry) => entry.clone(),
+            None => return Ok(vec![]),
+        };
+        let entry_path = join(&parent_path, name);
+
+        if components.peek().is_none() {
+            return if list_contents && entry.mode == FileMode::Directory {
+                let subtree = repository.database.load_tree(&entry.object_id)?;
+                Ok(with_paths(&entry_path, subtree.entries()))
+            } else {
+                Ok(vec![(entry_path, entry)])
+            };
+        }
+
+        if entry.mode != FileMode::Directory {
+            return Ok(vec![]);
+        }
+        subtree = Some(repository.database.load_tree(&entry.object_id)?);
+        parent_path = entry_path;
+    }
+
+    Ok(with_paths("", root_tree.entries()))
+}
+
+fn with_paths(parent_path: &str, entries: &[TreeEntry]) -> Vec<(String, TreeEntry)> {
+    entries
+        .iter()
+        .map(|entry| (join(parent_path, &entry.name), entry.clone()))
+        .collect()
+}
+
+fn join(parent_path: &str, name: &str) -> String {
+    if parent_path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}/{}", parent_path, name)
+    }
+}
+
+fn write_entry(path: &str, entry: &TreeEntry, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let object_type = match entry.mode {
+        FileMode::Directory => ObjectType::Tree,
+        FileMode::Gitlink => ObjectType::Commit,
+        _ => ObjectType::Blob,
+    };
+    writer.writeln(format!(
+        "{:0>6} {} {}\t{}",
+        entry.mode.as_str(),
+        object_type,
+        entry.object_id,
+        path
+    ))?;
+    Ok(())
+}
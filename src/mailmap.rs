@@ -0,0 +1,155 @@
+//! The mailmap, which maps the names and emails that people have committed under to their
+//! canonical identity, so that a person who has changed name or email shows up as one author.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::objects::Author;
+use crate::workspace::Repository;
+
+const MAILMAP_FILE: &str = ".mailmap";
+
+/// A mapping from the identity in a commit to the proper name and email of the person.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Mailmap {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    proper_name: Option<String>,
+    proper_email: Option<String>,
+    /// Only map commits with this name, if any.
+    commit_name: Option<String>,
+    commit_email: String,
+}
+
+impl Mailmap {
+    /// Load the `.mailmap` file at the root of the worktree, followed by the file named by the
+    /// `mailmap.file` config. Entries in the later file take precedence. Missing files are
+    /// ignored.
+    pub fn load(repository: &Repository) -> crate::Result<Mailmap> {
+        let root = repository.worktree().root();
+        let mut paths = vec![root.join(MAILMAP_FILE)];
+        if let Some(path) = repository.config()?.get_string("mailmap.file") {
+            paths.push(root.join(path));
+        }
+
+        let mut mailmap = Mailmap::default();
+        for path in paths {
+            if let Some(content) = read_if_exists(&path)? {
+                mailmap.entries.extend(Mailmap::parse(&content).entries);
+            }
+        }
+        Ok(mailmap)
+    }
+
+    /// Parse the lines of a mailmap, which have one of these forms:
+    ///
+    /// * `Proper Name <commit@email>`
+    /// * `<proper@email> <commit@email>`
+    /// * `Proper Name <proper@email> <commit@email>`
+    /// * `Proper Name <proper@email> Commit Name <commit@email>`
+    ///
+    /// Lines starting with `#` are comments, and malformed lines are ignored.
+    ///
+    /// # Examples
+    /// ```
+    /// use rut::mailmap::Mailmap;
+    /// use rut::objects::Author;
+    ///
+    /// let mailmap = Mailmap::parse("Jane Doe <jane@example.com> <jane@old.example.com>\n");
+    /// let author = Author {
+    ///     name: String::from("jane"),
+    ///     email: String::from("Jane@Old.example.com"),
+    /// };
+    ///
+    /// assert_eq!(
+    ///     mailmap.lookup(&author).to_string(),
+    ///     "Jane Doe <jane@example.com>"
+    /// );
+    /// ```
+    pub fn parse(content: &str) -> Mailmap {
+        let entries = content
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(parse_entry)
+            .collect();
+        Mailmap { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Find the canonical identity of the author. Emails and names are matched case-insensitively,
+    /// an entry that also matches the name takes precedence over one that only matches the email,
+    /// and later entries take precedence over earlier ones. Authors without an entry are returned
+    /// as they are.
+    pub fn lookup(&self, author: &Author) -> Author {
+        let matches_email = |entry: &&Entry| entry.commit_email.eq_ignore_ascii_case(&author.email);
+        let matches_name = |entry: &&Entry| {
+            entry
+                .commit_name
+                .as_ref()
+                .is_some_and(|name| name.to_lowercase() == author.name.to_lowercase())
+        };
+        let entry = self
+            .entries
+            .iter()
+            .rev()
+            .filter(matches_email)
+            .find(matches_name)
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rev()
+                    .filter(matches_email)
+                    .find(|entry| entry.commit_name.is_none())
+            });
+
+        match entry {
+            Some(entry) => Author {
+                name: entry.proper_name.clone().unwrap_or(author.name.clone()),
+                email: entry.proper_email.clone().unwrap_or(author.email.clone()),
+            },
+            None => author.clone(),
+        }
+    }
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let (first_name, first_email, rest) = parse_identity(line)?;
+    let Some((second_name, second_email, _)) = parse_identity(rest) else {
+        return Some(Entry {
+            proper_name: first_name,
+            proper_email: None,
+            commit_name: None,
+            commit_email: first_email,
+        });
+    };
+    Some(Entry {
+        proper_name: first_name,
+        proper_email: Some(first_email),
+        commit_name: second_name,
+        commit_email: second_email,
+    })
+}
+
+/// Parse an optional name followed by an email in angle brackets, and return them along with the
+/// rest of the line.
+fn parse_identity(s: &str) -> Option<(Option<String>, String, &str)> {
+    let (name, rest) = s.split_once('<')?;
+    let (email, rest) = rest.split_once('>')?;
+    let name = Some(name.trim()).filter(|name| !name.is_empty());
+    Some((name.map(str::to_owned), email.trim().to_owned(), rest))
+}
+
+fn read_if_exists(path: &Path) -> crate::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
@@ -0,0 +1,453 @@
+//! Three-way merges of files and trees, which combine the changes that two sides made from a
+//! common base, as when applying the changes of a commit on top of another one.
+//!
+//! A merge in progress is recorded in the git directory until it's committed: `MERGE_HEAD` lists
+//! the commits being merged into HEAD, one per line, and `MERGE_MSG` holds the message prepared
+//! for the commit. A rebase that stops at a conflict writes only `MERGE_MSG`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::diff::{self, EditKind};
+use crate::file;
+use crate::index::{FileMode, Index, IndexEntry};
+use crate::objects::{Blob, GitObject, ObjectId, Tree, TreeEntry};
+use crate::platform;
+use crate::restore;
+use crate::workspace::Repository;
+
+const MERGE_HEAD: &str = "MERGE_HEAD";
+const MERGE_MSG: &str = "MERGE_MSG";
+
+/// The result of merging the changes of two versions of a file.
+#[derive(Debug, PartialEq)]
+pub struct FileMerge {
+    /// The merged content, with conflict markers around the changes that overlap.
+    pub content: String,
+    /// Whether the changes merged without conflicts.
+    pub is_clean: bool,
+}
+
+enum Chunk<'a> {
+    Clean(&'a [&'a str]),
+    Conflict {
+        ours: &'a [&'a str],
+        theirs: &'a [&'a str],
+    },
+}
+
+/// Merge the changes that `ours` and `theirs` made to `base`. Changes to different lines are
+/// combined, while changes to the same lines are conflicts that are marked up with the labels of
+/// the sides.
+///
+/// # Examples
+/// ```
+/// use rut::merge;
+///
+/// let clean = merge::merge_files("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n", "HEAD", "theirs");
+/// assert!(clean.is_clean);
+/// assert_eq!(clean.content, "A\nb\nC\n");
+///
+/// let conflict = merge::merge_files("a\n", "b\n", "c\n", "HEAD", "theirs");
+/// assert!(!conflict.is_clean);
+/// assert_eq!(
+///     conflict.content,
+///     "<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> theirs\n"
+/// );
+/// ```
+pub fn merge_files(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    ours_label: &str,
+    theirs_label: &str,
+) -> FileMerge {
+    let base = base.split_inclusive('\n').collect::<Vec<_>>();
+    let ours = ours.split_inclusive('\n').collect::<Vec<_>>();
+    let theirs = theirs.split_inclusive('\n').collect::<Vec<_>>();
+
+    let mut content = String::new();
+    let mut is_clean = true;
+    for chunk in merge_chunks(&base, &ours, &theirs) {
+        match chunk {
+            Chunk::Clean(lines) => content.extend(lines.iter().copied()),
+            Chunk::Conflict { ours, theirs } => {
+                is_clean = false;
+                content.push_str(&format!("<<<<<<< {}\n", ours_label));
+                push_lines(&mut content, ours);
+                content.push_str("=======\n");
+                push_lines(&mut content, theirs);
+                content.push_str(&format!(">>>>>>> {}\n", theirs_label));
+            }
+        }
+    }
+    FileMerge { content, is_clean }
+}
+
+/// Push the lines, ending them with a newline so that a conflict marker can follow.
+fn push_lines(content: &mut String, lines: &[&str]) {
+    content.extend(lines.iter().copied());
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+/// Split the sides into chunks that are either unchanged on both sides, changed on one side or
+/// changed on both sides, as in diff3.
+///
+/// The lines of the base are matched with the lines of each side by diffing them. Lines that
+/// match on both sides make up the stable parts, and the lines between them are resolved by
+/// comparing what each side has in place of the base.
+fn merge_chunks<'a>(
+    base: &'a [&'a str],
+    ours: &'a [&'a str],
+    theirs: &'a [&'a str],
+) -> Vec<Chunk<'a>> {
+    // line numbers start at 1, so that 0 is the position before the first line
+    let ours_matches = match_lines(base, ours);
+    let theirs_matches = match_lines(base, theirs);
+    let (mut base_line, mut ours_line, mut theirs_line) = (0, 0, 0);
+    let mut chunks = vec![];
+
+    loop {
+        let in_bounds = |i: usize| {
+            base_line + i <= base.len()
+                || ours_line + i <= ours.len()
+                || theirs_line + i <= theirs.len()
+        };
+        let is_match = |i: usize| {
+            ours_matches.get(&(base_line + i)) == Some(&(ours_line + i))
+                && theirs_matches.get(&(base_line + i)) == Some(&(theirs_line + i))
+        };
+        let mut i = 1;
+        while in_bounds(i) && is_match(i) {
+            i += 1;
+        }
+
+        let next_match = if !in_bounds(i) {
+            None
+        } else if i > 1 {
+            Some((base_line + i, ours_line + i, theirs_line + i))
+        } else {
+            (base_line + 1..=base.len())
+                .find(|line| ours_matches.contains_key(line) && theirs_matches.contains_key(line))
+                .map(|line| (line, ours_matches[&line], theirs_matches[&line]))
+        };
+
+        match next_match {
+            Some((next_base, next_ours, next_theirs)) => {
+                chunks.push(resolve_chunk(
+                    &base[base_line..next_base - 1],
+                    &ours[ours_line..next_ours - 1],
+                    &theirs[theirs_line..next_theirs - 1],
+                ));
+                (base_line, ours_line, theirs_line) =
+                    (next_base - 1, next_ours - 1, next_theirs - 1);
+            }
+            None => {
+                chunks.push(resolve_chunk(
+                    &base[base_line..],
+                    &ours[ours_line..],
+                    &theirs[theirs_line..],
+                ));
+                return chunks;
+            }
+        }
+    }
+}
+
+/// Map the line numbers of the lines of the base to those of the other side that they match.
+fn match_lines(base: &[&str], other: &[&str]) -> HashMap<usize, usize> {
+    diff::edit_script(base, other)
+        .iter()
+        .filter(|edit| *edit.kind() == EditKind::Equal)
+        .map(|edit| {
+            (
+                edit.a_position().unwrap() + 1,
+                edit.b_position().unwrap() + 1,
+            )
+        })
+        .collect()
+}
+
+fn resolve_chunk<'a>(base: &'a [&'a str], ours: &'a [&'a str], theirs: &'a [&'a str]) -> Chunk<'a> {
+    if ours == base || ours == theirs {
+        Chunk::Clean(theirs)
+    } else if theirs == base {
+        Chunk::Clean(ours)
+    } else {
+        Chunk::Conflict { ours, theirs }
+    }
+}
+
+/// Merge the changes from the base tree to `theirs` into the index and the worktree, which must
+/// match `ours`. Paths that both sides changed are merged line by line, and those that can't be
+/// merged are left with conflict markers in the worktree and their base, ours and theirs entries
+/// in stages 1, 2 and 3 of the index. Returns the paths with conflicts.
+///
+/// Fails without changing anything if an untracked file is in the way of a file to create.
+pub fn merge_trees(
+    base: Option<&Tree>,
+    ours: &Tree,
+    theirs: &Tree,
+    ours_label: &str,
+    theirs_label: &str,
+    repository: &Repository,
+    index: &mut Index,
+) -> crate::Result<Vec<PathBuf>> {
+    let base_entries = match base {
+        Some(base) => flatten_tree(base, repository)?,
+        None => HashMap::new(),
+    };
+    let ours_entries = flatten_tree(ours, repository)?;
+    let theirs_entries = flatten_tree(theirs, repository)?;
+    let paths = base_entries
+        .keys()
+        .chain(ours_entries.keys())
+        .chain(theirs_entries.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    let worktree_root = repository.worktree().root();
+    let mut changed_paths = vec![];
+    let mut untracked_in_the_way = vec![];
+    for path in paths {
+        let base_entry = base_entries.get(&path);
+        let ours_entry = ours_entries.get(&path);
+        let theirs_entry = theirs_entries.get(&path);
+        if base_entry == theirs_entry || ours_entry == theirs_entry {
+            continue;
+        }
+        if ours_entry.is_none() && worktree_root.join(&path).exists() && !index.has_entry(&path) {
+            untracked_in_the_way.push(path.display().to_string());
+        }
+        changed_paths.push((path, base_entry, ours_entry, theirs_entry));
+    }
+    if !untracked_in_the_way.is_empty() {
+        let message = format!(
+            "The following untracked working tree files would be overwritten by merge:\n\t{}\n\
+             Please move or remove them before you merge.",
+            untracked_in_the_way.join("\n\t")
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let mut conflicts = vec![];
+    for (path, base_entry, ours_entry, theirs_entry) in changed_paths {
+        let absolute_path = worktree_root.join(&path);
+        if base_entry == ours_entry {
+            match theirs_entry {
+                Some(entry) => checkout_entry(&path, entry, repository, index)?,
+                None => {
+                    index.remove(&path);
+                    match fs::remove_file(&absolute_path) {
+                        Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                        other => other?,
+                    }
+                    file::remove_empty_parents(&absolute_path, worktree_root)?;
+                }
+            }
+            continue;
+        }
+
+        let merged = match (ours_entry, theirs_entry) {
+            (Some(ours_entry), Some(theirs_entry)) => merge_entries(
+                base_entry,
+                ours_entry,
+                theirs_entry,
+                ours_label,
+                theirs_label,
+                repository,
+            )?,
+            _ => None,
+        };
+        match merged {
+            Some((content, mode, true)) => {
+                let blob =
+                    Blob::with_hash_algorithm(content.into_bytes(), repository.hash_algorithm());
+                repository.database.store_object(&blob)?;
+                let entry = TreeEntry::new(&path, blob.id().clone(), mode);
+                checkout_entry(&path, &entry, repository, index)?;
+            }
+            merged => {
+                match (merged, theirs_entry) {
+                    (Some((content, mode, _)), _) => {
+                        let filters = repository.filters()?;
+                        let content = filters.to_worktree(&path, content.as_bytes());
+                        file::atomic_write(&absolute_path, &content)?;
+                        platform::set_executable(&absolute_path, mode == FileMode::Executable)?;
+                    }
+                    // a file that was deleted on our side, but modified on theirs
+                    (None, Some(theirs_entry)) if ours_entry.is_none() => {
+                        restore::write_tree_entry(&absolute_path, theirs_entry, repository)?
+                    }
+                    _ => (),
+                }
+                for (stage, entry) in [(1, base_entry), (2, ours_entry), (3, theirs_entry)] {
+                    if let Some(entry) = entry {
+                        add_unmerged_entry(&path, entry, stage, repository, index)?;
+                    }
+                }
+                conflicts.push(path);
+            }
+        }
+    }
+
+    Ok(conflicts)
+}
+
+/// Merge the content of two files, returning the content, the file mode and whether the merge
+/// is clean. Returns None for files that can't be merged line by line, such as binary files.
+fn merge_entries(
+    base_entry: Option<&TreeEntry>,
+    ours_entry: &TreeEntry,
+    theirs_entry: &TreeEntry,
+    ours_label: &str,
+    theirs_label: &str,
+    repository: &Repository,
+) -> crate::Result<Option<(String, FileMode, bool)>> {
+    let entries = [base_entry, Some(ours_entry), Some(theirs_entry)];
+    if entries
+        .iter()
+        .flatten()
+        .any(|entry| !matches!(entry.mode, FileMode::Regular | FileMode::Executable))
+    {
+        return Ok(None);
+    }
+
+    let mut contents = vec![];
+    for entry in entries {
+        let content = match entry {
+            Some(entry) => repository
+                .database
+                .load_blob(&entry.object_id)?
+                .content()
+                .to_vec(),
+            None => vec![],
+        };
+        match String::from_utf8(content) {
+            Ok(content) if !content.contains('\0') => contents.push(content),
+            _ => return Ok(None),
+        }
+    }
+
+    let merged = merge_files(
+        &contents[0],
+        &contents[1],
+        &contents[2],
+        ours_label,
+        theirs_label,
+    );
+    let mode = if base_entry.map(|entry| &entry.mode) == Some(&ours_entry.mode) {
+        theirs_entry.mode
+    } else {
+        ours_entry.mode
+    };
+    Ok(Some((merged.content, mode, merged.is_clean)))
+}
+
+/// Write the entry to the worktree and stage it.
+fn checkout_entry(
+    path: &Path,
+    entry: &TreeEntry,
+    repository: &Repository,
+    index: &mut Index,
+) -> crate::Result<()> {
+    let absolute_path = repository.worktree().root().join(path);
+    restore::write_tree_entry(&absolute_path, entry, repository)?;
+    let metadata = fs::metadata(&absolute_path)?;
+    index.add_entry(IndexEntry::new(path, entry.object_id.clone(), &metadata));
+    Ok(())
+}
+
+fn add_unmerged_entry(
+    path: &Path,
+    entry: &TreeEntry,
+    stage: u16,
+    repository: &Repository,
+    index: &mut Index,
+) -> crate::Result<()> {
+    let file_size = match entry.mode {
+        FileMode::Gitlink => 0,
+        _ => repository
+            .database
+            .load_blob(&entry.object_id)?
+            .content()
+            .len() as u32,
+    };
+    let mut index_entry =
+        IndexEntry::from_object(path, entry.object_id.clone(), entry.mode, file_size);
+    index_entry.set_stage(stage);
+    index.add_entry(index_entry);
+    Ok(())
+}
+
+fn flatten_tree(
+    tree: &Tree,
+    repository: &Repository,
+) -> crate::Result<HashMap<PathBuf, TreeEntry>> {
+    let mut entries = vec![];
+    repository
+        .database
+        .extract_entries_from_tree(String::new(), tree, &mut entries)?;
+    Ok(entries
+        .into_iter()
+        .map(|(path, entry)| (PathBuf::from(path), entry))
+        .collect())
+}
+
+/// The commits being merged into HEAD by the merge in progress, which is empty if there is none.
+pub fn read_merge_heads(repository: &Repository) -> crate::Result<Vec<ObjectId>> {
+    let content = match fs::read_to_string(repository.git_dir().join(MERGE_HEAD)) {
+        Ok(content) => content,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            ObjectId::from_sha(line.trim()).map_err(|reason| crate::Error::Fatal(None, reason))
+        })
+        .collect()
+}
+
+/// The message prepared for committing the merge in progress, if any.
+pub fn read_merge_message(repository: &Repository) -> io::Result<Option<String>> {
+    match fs::read_to_string(repository.git_dir().join(MERGE_MSG)) {
+        Ok(message) => Ok(Some(message)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Record a merge in progress of the commits into HEAD, with the message to commit it with. Without
+/// any commits, only the message is recorded.
+pub fn write_merge_state(
+    merge_heads: &[ObjectId],
+    message: &str,
+    repository: &Repository,
+) -> io::Result<()> {
+    let git_dir = repository.git_dir();
+    if !merge_heads.is_empty() {
+        let content = merge_heads
+            .iter()
+            .map(|merge_head| format!("{}\n", merge_head))
+            .collect::<String>();
+        fs::write(git_dir.join(MERGE_HEAD), content)?;
+    }
+    fs::write(git_dir.join(MERGE_MSG), message)
+}
+
+/// Remove the record of the merge in progress, once it's committed or aborted.
+pub fn clear_merge_state(repository: &Repository) -> io::Result<()> {
+    for name in [MERGE_HEAD, MERGE_MSG] {
+        match fs::remove_file(repository.git_dir().join(name)) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            other => other?,
+        }
+    }
+    Ok(())
+}
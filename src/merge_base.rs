@@ -0,0 +1,21 @@
+use crate::output::OutputWriter;
+use crate::refs::Revision;
+use crate::rev_walk;
+use crate::workspace::Repository;
+
+/// Print the best common ancestor of the two revisions, if there is one.
+pub fn merge_base(
+    first_revision: &str,
+    second_revision: &str,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let first_commit_id = Revision::parse(first_revision)?.resolve(repository)?;
+    let second_commit_id = Revision::parse(second_revision)?.resolve(repository)?;
+
+    if let Some(base_id) = rev_walk::merge_base(repository, &first_commit_id, &second_commit_id)? {
+        writer.writeln(base_id.to_string())?;
+    }
+
+    Ok(())
+}
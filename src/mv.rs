@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::index::IndexEntry;
+use crate::workspace::Repository;
+
+/// Move or rename a tracked file or directory, updating the index to match. If the destination
+/// is an existing directory, the source is moved into it. Relative paths are resolved against the
+/// directory the user is working in.
+pub fn mv<P: AsRef<Path>, Q: AsRef<Path>>(
+    source: P,
+    destination: Q,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    let worktree = repository.worktree();
+
+    let absolute_source = worktree.root().join(worktree.resolve_user_path(source)?);
+    let mut absolute_destination = worktree
+        .root()
+        .join(worktree.resolve_user_path(destination)?);
+    if absolute_destination.is_dir() {
+        if let Some(file_name) = absolute_source.file_name() {
+            absolute_destination = absolute_destination.join(file_name);
+        }
+    }
+
+    let relative_source = worktree.relativize_path(&absolute_source);
+    let relative_destination = worktree.relativize_path(&absolute_destination);
+    let error = |reason: &str| {
+        let message = format!(
+            "{}, source={}, destination={}",
+            reason,
+            relative_source.display(),
+            relative_destination.display()
+        );
+        crate::Error::Fatal(None, message)
+    };
+
+    if !absolute_source.exists() {
+        return Err(error("bad source"));
+    }
+    if absolute_destination.exists() {
+        return Err(error("destination exists"));
+    }
+    if !absolute_destination
+        .parent()
+        .map(Path::is_dir)
+        .unwrap_or(false)
+    {
+        return Err(error("destination directory does not exist"));
+    }
+
+    let moved_paths: Vec<(PathBuf, PathBuf)> = if index.has_entry(&relative_source) {
+        vec![(relative_source.clone(), relative_destination.clone())]
+    } else if index.is_tracked_directory(&relative_source) {
+        index
+            .get_entries()
+            .iter()
+            .filter_map(|entry| {
+                let suffix = entry.path.strip_prefix(&relative_source).ok()?;
+                Some((entry.path.clone(), relative_destination.join(suffix)))
+            })
+            .collect()
+    } else {
+        return Err(error("not under version control"));
+    };
+
+    fs::rename(&absolute_source, &absolute_destination)?;
+
+    for (old_path, new_path) in moved_paths {
+        let old_entry = index.remove(&old_path).unwrap();
+        let metadata = fs::metadata(worktree.root().join(&new_path))?;
+        index.add_entry(IndexEntry::new(new_path, old_entry.object_id, &metadata));
+    }
+
+    Ok(index_lockfile.write()?)
+}
@@ -0,0 +1,112 @@
+//! A bounded cache of inflated objects, so that commands that visit the same objects many times,
+//! such as log walks and diffs, only read and inflate each of them once.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::objects::{ObjectId, ObjectType};
+
+/// The default bound on the total size of the cached content, in bytes.
+pub const DEFAULT_LIMIT: usize = 32 << 20;
+
+/// A least-recently-used cache of the types and contents of objects, which evicts the objects
+/// that were used the longest time ago when the total size of the contents exceeds the limit.
+pub struct ObjectCache {
+    limit: usize,
+    size: usize,
+    /// Incremented on each use, to order the objects by when they were last used.
+    clock: u64,
+    objects: HashMap<ObjectId, CachedObject>,
+    last_uses: BTreeMap<u64, ObjectId>,
+}
+
+struct CachedObject {
+    object_type: ObjectType,
+    content: Arc<[u8]>,
+    last_use: u64,
+}
+
+impl ObjectCache {
+    pub fn new(limit: usize) -> ObjectCache {
+        ObjectCache {
+            limit,
+            size: 0,
+            clock: 0,
+            objects: HashMap::new(),
+            last_uses: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, object_id: &ObjectId) -> Option<(ObjectType, Arc<[u8]>)> {
+        self.clock += 1;
+        let object = self.objects.get_mut(object_id)?;
+        self.last_uses.remove(&object.last_use);
+        self.last_uses.insert(self.clock, object_id.clone());
+        object.last_use = self.clock;
+        Some((object.object_type, Arc::clone(&object.content)))
+    }
+
+    /// Cache the object, evicting the least recently used objects to stay within the limit.
+    /// Objects larger than the limit are not cached at all.
+    pub fn insert(&mut self, object_id: ObjectId, object_type: ObjectType, content: Arc<[u8]>) {
+        if content.len() > self.limit || self.objects.contains_key(&object_id) {
+            return;
+        }
+        while self.size + content.len() > self.limit {
+            self.evict_least_recently_used();
+        }
+
+        self.clock += 1;
+        self.size += content.len();
+        self.last_uses.insert(self.clock, object_id.clone());
+        let object = CachedObject {
+            object_type,
+            content,
+            last_use: self.clock,
+        };
+        self.objects.insert(object_id, object);
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        if let Some((_, object_id)) = self.last_uses.pop_first() {
+            if let Some(object) = self.objects.remove(&object_id) {
+                self.size -= object.content.len();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{Blob, GitObject};
+
+    fn object_id(content: &[u8]) -> ObjectId {
+        Blob::new(content.to_vec()).id().clone()
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_object() {
+        let mut cache = ObjectCache::new(8);
+        let (first, second, third) = (object_id(b"1"), object_id(b"2"), object_id(b"3"));
+        cache.insert(first.clone(), ObjectType::Blob, Arc::from(&b"1111"[..]));
+        cache.insert(second.clone(), ObjectType::Blob, Arc::from(&b"2222"[..]));
+
+        cache.get(&first);
+        cache.insert(third.clone(), ObjectType::Blob, Arc::from(&b"3333"[..]));
+
+        assert!(cache.get(&first).is_some());
+        assert!(cache.get(&second).is_none());
+        assert!(cache.get(&third).is_some());
+    }
+
+    #[test]
+    fn test_does_not_cache_object_larger_than_limit() {
+        let mut cache = ObjectCache::new(2);
+        let id = object_id(b"large");
+
+        cache.insert(id.clone(), ObjectType::Blob, Arc::from(&b"large"[..]));
+
+        assert!(cache.get(&id).is_none());
+    }
+}
@@ -4,6 +4,7 @@ use std::{
 };
 
 use crate::{
+    index::FileMode,
     objects::{Blob, Tree},
     refs::RefHandler,
     workspace::{Database, Repository},
@@ -26,10 +27,6 @@ impl<'a> ObjectResolver<'a> {
         }
     }
 
-    pub fn from_head_commit(repository: &'a Repository) -> crate::Result<Self> {
-        ObjectResolver::from_reference("HEAD", repository)
-    }
-
     pub fn from_reference(reference: &str, repository: &'a Repository) -> crate::Result<Self> {
         let commit_id = RefHandler::new(repository).deref(reference)?;
         let commit = repository.database.load_commit(&commit_id)?;
@@ -40,8 +37,15 @@ impl<'a> ObjectResolver<'a> {
 
     /// Find a blob by its path, relative to the root tree of this ObjectResolver.
     pub fn find_blob_by_path(&mut self, path: &Path) -> crate::Result<Blob> {
+        self.find_blob_by_path_if_present(path)?
+            .ok_or_else(|| pathspec_error(path))
+    }
+
+    /// Find a blob by its path like [`ObjectResolver::find_blob_by_path`], but return `None`
+    /// if there is no blob at the path. Objects that can't be loaded are still errors.
+    pub fn find_blob_by_path_if_present(&mut self, path: &Path) -> crate::Result<Option<Blob>> {
         if let Some(blob) = self.blobs.get(path) {
-            return Ok(blob.clone());
+            return Ok(Some(blob.clone()));
         }
 
         let parent_path = self.resolve_closest_cached_tree_path(path);
@@ -55,14 +59,15 @@ impl<'a> ObjectResolver<'a> {
             return path.to_owned();
         }
 
-        self.resolve_closest_cached_tree_path(path.parent().unwrap())
+        // the root tree is always cached, so an ancestor is found before running out of parents
+        self.resolve_closest_cached_tree_path(path.parent().unwrap_or(Path::new("")))
     }
 
     fn find_blob_in_tree_(
         &mut self,
         parent_path: &Path,
         remaining_path: &Path,
-    ) -> crate::Result<Blob> {
+    ) -> crate::Result<Option<Blob>> {
         if remaining_path.components().count() <= 1 {
             return self.get_blob(&parent_path.join(remaining_path));
         }
@@ -75,24 +80,28 @@ impl<'a> ObjectResolver<'a> {
         &mut self,
         parent_path: &Path,
         remaining_path: &Path,
-    ) -> crate::Result<Blob> {
-        let mut path_components = remaining_path.iter().map(|p| p.to_str().unwrap());
-        let root_component = path_components.next().unwrap();
-        let current_path = parent_path.join(root_component);
+    ) -> crate::Result<Option<Blob>> {
+        let mut path_components = remaining_path.iter().map(|p| p.to_string_lossy());
+        let Some(root_component) = path_components.next() else {
+            return Ok(None);
+        };
+        let current_path = parent_path.join(root_component.as_ref());
 
         let mut curent_remaining_path = PathBuf::new();
         for component in path_components {
-            curent_remaining_path = curent_remaining_path.join(component);
+            curent_remaining_path = curent_remaining_path.join(component.as_ref());
         }
 
-        let parent_tree = self.trees.get(parent_path).unwrap();
+        let parent_tree = &self.trees[parent_path];
 
-        let tree_entry = parent_tree
+        let Some(tree_entry) = parent_tree
             .entries()
             .iter()
-            .find(|e| e.name == root_component)
-            .unwrap();
-        let current_tree = self.database.load_tree(&tree_entry.object_id).unwrap();
+            .find(|e| *e.name == *root_component && e.mode == FileMode::Directory)
+        else {
+            return Ok(None);
+        };
+        let current_tree = self.database.load_tree(&tree_entry.object_id)?;
 
         self.trees.insert(current_path.clone(), current_tree);
 
@@ -100,22 +109,29 @@ impl<'a> ObjectResolver<'a> {
     }
 
     /// Get a blob assuming its parent tree is already cached.
-    fn get_blob(&mut self, blob_path: &Path) -> crate::Result<Blob> {
-        let file_name = blob_path.file_name().unwrap().to_str().unwrap();
-        let tree = &self.trees[blob_path.parent().unwrap()];
+    fn get_blob(&mut self, blob_path: &Path) -> crate::Result<Option<Blob>> {
+        let (Some(file_name), Some(parent_path)) = (blob_path.file_name(), blob_path.parent())
+        else {
+            return Ok(None);
+        };
+        let tree = &self.trees[parent_path];
 
         for entry in tree.entries() {
-            if entry.name == file_name {
-                let committed_blob = self.database.load_blob(&entry.object_id).unwrap();
+            if *entry.name == *file_name && entry.mode != FileMode::Directory {
+                let committed_blob = self.database.load_blob(&entry.object_id)?;
                 self.blobs
                     .insert(blob_path.to_path_buf(), committed_blob.clone());
-                return Ok(committed_blob);
+                return Ok(Some(committed_blob));
             }
         }
 
-        Err(crate::Error::Fatal(
-            None,
-            format!("pathspec '{}' did not match any files", blob_path.display()),
-        ))
+        Ok(None)
     }
 }
+
+fn pathspec_error(path: &Path) -> crate::Error {
+    crate::Error::Fatal(
+        None,
+        format!("pathspec '{}' did not match any files", path.display()),
+    )
+}
@@ -1,23 +1,23 @@
 use std::path::Path;
+use std::sync::OnceLock;
 use std::{fmt::Display, str};
 
 use chrono::Local;
 
-use crate::hashing;
+use crate::hashing::HashAlgorithm;
 use crate::hex;
 use crate::index::FileMode;
 
+/// The minimum length of an abbreviated object id, as Git's default for `core.abbrev`.
+pub const MIN_ABBREV_LENGTH: usize = 7;
+
 pub trait GitObject<'a> {
-    fn id(&'a self) -> &ObjectId;
+    fn id(&'a self) -> &'a ObjectId;
 
     fn id_as_string(&'a self) -> String {
         self.id().to_string()
     }
 
-    fn short_id(&'a self) -> Vec<u8> {
-        self.id().bytes()[0..7].to_vec()
-    }
-
     fn short_id_as_string(&'a self) -> String {
         self.id().to_short_string()
     }
@@ -25,11 +25,53 @@ pub trait GitObject<'a> {
     fn to_object_format(&self) -> Vec<u8>;
 }
 
-/// A Git object id is the sha1 hash of the object's content, which is represented as a 40 byte
-/// hexadecimal string. This struct encapsulates this concept and provides some utility methods
+/// The type of a Git object, as recorded in the header of the object format.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+}
+
+impl ObjectType {
+    /// Parse an object type from its name in the object header.
+    ///
+    /// # Examples
+    /// ```
+    /// use rut::objects::ObjectType;
+    ///
+    /// assert_eq!(ObjectType::parse("tree"), Some(ObjectType::Tree));
+    /// assert_eq!(ObjectType::parse("tag"), None);
+    /// ```
+    pub fn parse(s: &str) -> Option<ObjectType> {
+        match s {
+            "blob" => Some(ObjectType::Blob),
+            "tree" => Some(ObjectType::Tree),
+            "commit" => Some(ObjectType::Commit),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectType::Blob => "blob",
+            ObjectType::Tree => "tree",
+            ObjectType::Commit => "commit",
+        }
+    }
+}
+
+impl Display for ObjectType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A Git object id is the sha1 or sha256 hash of the object's content, which is represented as a
+/// 40 or 64 byte hexadecimal string. This struct encapsulates this concept and provides some utility methods
 /// related to common operations on object ids, such as finding out the filepath in the object
 /// database.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
 pub struct ObjectId {
     bytes: Vec<u8>,
 }
@@ -45,11 +87,12 @@ impl ObjectId {
     /// assert_eq!(id.to_string(), "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3");
     /// ```
     pub fn from_sha(s: &str) -> Result<ObjectId, String> {
-        let bytes = hex::from_hex_string(s).map_err(|e| e.to_string())?;
-        Self::from_sha_bytes(&bytes)
+        let bytes =
+            hex::from_hex_string(s).map_err(|e| format!("invalid object id '{}': {}", s, e))?;
+        Self::from_sha_bytes(&bytes).map_err(|e| format!("invalid object id '{}': {}", s, e))
     }
 
-    /// Turn a string that is the utf8 encoded version of a sha1 hash into an ObjectId.
+    /// Turn a string that is the utf8 encoded version of a hash into an ObjectId.
     ///
     /// # Examples
     /// ```
@@ -64,46 +107,62 @@ impl ObjectId {
         Self::from_sha(s)
     }
 
-    /// Turn bytes into an ObjectId. This is the inverse of bytes().
+    /// Turn the raw bytes of a hash into an ObjectId. This is the inverse of bytes().
     ///
     /// # Examples
     /// ```
     /// use rut::objects::ObjectId;
     ///
-    /// let bytes = "a94a8fe5ccb19ba61c4c0873d391e987982fbbd3".as_bytes();
-    /// let id = ObjectId::from_sha_bytes(bytes).unwrap();
+    /// let bytes = [0xa9; 20];
+    /// let id = ObjectId::from_sha_bytes(&bytes).unwrap();
     /// assert_eq!(id.bytes(), bytes);
+    /// assert_eq!(id.to_string(), "a9".repeat(20));
+    /// ```
     pub fn from_sha_bytes(bytes: &[u8]) -> Result<ObjectId, String> {
-        let unhexlified_bytes = if bytes.len() == 20 {
-            hex::unhexlify(bytes)
-        } else if bytes.len() == 40 {
-            bytes.to_vec()
-        } else {
-            return Err(
-                "Object ID must be hexflified (20 bytes long) or in full (40 bytes long)"
-                    .to_string(),
-            );
-        };
+        match bytes.len() {
+            20 | 32 => Ok(ObjectId {
+                bytes: bytes.to_vec(),
+            }),
+            length => Err(format!(
+                "expected a hash of 20 or 32 bytes, got {} bytes",
+                length
+            )),
+        }
+    }
 
-        Ok(ObjectId {
-            bytes: unhexlified_bytes,
-        })
+    /// Compute the id of an object from its object format.
+    pub fn compute(object_format: &[u8], hash_algorithm: HashAlgorithm) -> ObjectId {
+        ObjectId::from_sha_bytes(&hash_algorithm.hash(object_format)).unwrap()
+    }
+
+    /// The id consisting of only zeroes, which doesn't identify any object.
+    pub fn null(hash_algorithm: HashAlgorithm) -> ObjectId {
+        ObjectId {
+            bytes: vec![0; hash_algorithm.raw_size()],
+        }
     }
 
     pub fn bytes(&self) -> &[u8] {
         &self.bytes
     }
 
+    /// The id abbreviated to the minimum length, without regard for whether other objects share
+    /// the abbreviation. See [`crate::workspace::Database::abbreviate`] for a unique one.
     pub fn to_short_string(&self) -> String {
-        hex::to_hex_string(&self.bytes[0..7])
+        let mut hex = self.to_string();
+        hex.truncate(MIN_ABBREV_LENGTH);
+        hex
     }
 
+    /// The name of the directory of the object in the object database, i.e. the first two
+    /// characters of the id.
     pub fn dirname(&self) -> String {
-        hex::to_hex_string(&self.bytes[0..2])
+        hex::to_hex_string(&self.bytes[0..1])
     }
 
+    /// The name of the file of the object in its directory, i.e. the rest of the id.
     pub fn filename(&self) -> String {
-        hex::to_hex_string(&self.bytes[2..])
+        hex::to_hex_string(&self.bytes[1..])
     }
 }
 
@@ -121,10 +180,14 @@ pub struct Blob {
 }
 
 impl Blob {
+    /// Create a blob of the content, with a sha1 id.
     pub fn new(bytes: Vec<u8>) -> Blob {
-        let object_format = to_object_format("blob", &bytes);
-        let raw_id = &hashing::sha1_hash(&object_format);
-        let id = ObjectId::from_sha_bytes(raw_id).unwrap();
+        Blob::with_hash_algorithm(bytes, HashAlgorithm::Sha1)
+    }
+
+    /// Create a blob of the content, with an id computed with the hash algorithm.
+    pub fn with_hash_algorithm(bytes: Vec<u8>, hash_algorithm: HashAlgorithm) -> Blob {
+        let id = ObjectId::compute(&to_object_format("blob", &bytes), hash_algorithm);
         Blob { bytes, id }
     }
 
@@ -139,7 +202,7 @@ impl Blob {
 }
 
 impl<'a> GitObject<'a> for Blob {
-    fn id(&'a self) -> &ObjectId {
+    fn id(&'a self) -> &'a ObjectId {
         &self.id
     }
 
@@ -148,6 +211,48 @@ impl<'a> GitObject<'a> for Blob {
     }
 }
 
+/// An object of any type held as its raw content, such as one read from a pack, which is stored
+/// as is without being parsed.
+pub struct RawObject {
+    object_type: ObjectType,
+    bytes: Vec<u8>,
+    id: ObjectId,
+}
+
+impl RawObject {
+    pub fn new(
+        object_type: ObjectType,
+        bytes: Vec<u8>,
+        hash_algorithm: HashAlgorithm,
+    ) -> RawObject {
+        let object_format = to_object_format(object_type.as_str(), &bytes);
+        let id = ObjectId::compute(&object_format, hash_algorithm);
+        RawObject {
+            object_type,
+            bytes,
+            id,
+        }
+    }
+
+    pub fn object_type(&self) -> ObjectType {
+        self.object_type
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<'a> GitObject<'a> for RawObject {
+    fn id(&'a self) -> &'a ObjectId {
+        &self.id
+    }
+
+    fn to_object_format(&self) -> Vec<u8> {
+        to_object_format(self.object_type.as_str(), &self.bytes)
+    }
+}
+
 fn to_object_format(object_type: &str, bytes: &[u8]) -> Vec<u8> {
     let mut object_format = object_type.as_bytes().to_vec();
     let byte_count = format!(" {}", bytes.len());
@@ -158,7 +263,7 @@ fn to_object_format(object_type: &str, bytes: &[u8]) -> Vec<u8> {
     object_format
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct TreeEntry {
     pub name: String,
     pub object_id: ObjectId,
@@ -187,12 +292,10 @@ pub struct Tree {
 }
 
 impl Tree {
-    pub fn new(entries: Vec<TreeEntry>) -> Self {
+    pub fn new(entries: Vec<TreeEntry>, hash_algorithm: HashAlgorithm) -> Self {
         let mut mutable_entries = entries;
         mutable_entries.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
-        let object_format = Self::to_object_format(&mutable_entries);
-        let hash = hashing::sha1_hash(&object_format);
-        let id = ObjectId::from_sha_bytes(&hash).unwrap();
+        let id = ObjectId::compute(&Self::to_object_format(&mutable_entries), hash_algorithm);
         Self {
             entries: mutable_entries,
             id,
@@ -208,17 +311,11 @@ impl Tree {
         for entry in entries.iter() {
             let name_bytes = entry.name.as_bytes();
 
-            let mode = match entry.mode {
-                FileMode::Directory => "40000",
-                FileMode::Regular => "100644",
-                FileMode::Executable => "100755",
-            };
-
-            bytes.extend_from_slice(mode.as_bytes());
+            bytes.extend_from_slice(entry.mode.as_str().as_bytes());
             bytes.extend_from_slice(" ".as_bytes());
             bytes.extend_from_slice(name_bytes);
             bytes.push(0);
-            bytes.extend_from_slice(&hex::hexlify(entry.object_id.bytes()));
+            bytes.extend_from_slice(entry.object_id.bytes());
         }
 
         to_object_format("tree", &bytes)
@@ -226,7 +323,7 @@ impl Tree {
 }
 
 impl<'a> GitObject<'a> for Tree {
-    fn id(&'a self) -> &ObjectId {
+    fn id(&'a self) -> &'a ObjectId {
         &self.id
     }
 
@@ -235,7 +332,7 @@ impl<'a> GitObject<'a> for Tree {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Author {
     pub name: String,
     pub email: String,
@@ -248,14 +345,28 @@ impl Display for Author {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Commit {
     pub tree: ObjectId,
     pub author: Author,
+    /// The message, with any bytes that aren't valid UTF-8 replaced.
     pub message: String,
-    pub parent: Option<ObjectId>,
+    /// The message as it is stored, which may be in another encoding than UTF-8.
+    raw_message: Vec<u8>,
+    pub parents: Vec<ObjectId>,
+    /// When the commit was authored, in seconds since the epoch.
     pub timestamp: u64,
-    id: ObjectId,
+    /// The UTC offset of the author's time zone, such as `+0100`.
+    pub timezone: String,
+    pub committer: Author,
+    pub committer_timestamp: u64,
+    pub committer_timezone: String,
+    /// Raw header lines that rut doesn't interpret, such as `gpgsig` or `encoding`, which are
+    /// written after the committer.
+    pub extra_headers: Vec<u8>,
+    hash_algorithm: HashAlgorithm,
+    /// Computed when first asked for, as commits are often built in several steps.
+    id: OnceLock<ObjectId>,
 }
 
 impl Commit {
@@ -266,62 +377,167 @@ impl Commit {
         parent: Option<ObjectId>,
         timestamp: u64,
     ) -> Self {
-        let object_format =
-            Self::to_object_format(&tree, &author, &message, parent.as_ref(), timestamp);
-        let hash = hashing::sha1_hash(&object_format);
-        let id = ObjectId::from_sha_bytes(&hash).unwrap();
+        Self::with_parents(
+            tree,
+            author,
+            message,
+            parent.into_iter().collect(),
+            timestamp,
+        )
+    }
+
+    /// Create a commit with any number of parents, where the first parent is the mainline. The
+    /// author is also the committer, both times are in the local time zone, and the id is a sha1
+    /// hash.
+    pub fn with_parents(
+        tree: ObjectId,
+        author: Author,
+        message: String,
+        parents: Vec<ObjectId>,
+        timestamp: u64,
+    ) -> Self {
+        let timezone = Local::now().format("%z").to_string();
         Self {
             tree,
+            committer: author.clone(),
             author,
+            raw_message: message.as_bytes().to_vec(),
             message,
-            parent,
+            parents,
             timestamp,
-            id,
+            timezone: timezone.clone(),
+            committer_timestamp: timestamp,
+            committer_timezone: timezone,
+            extra_headers: vec![],
+            hash_algorithm: HashAlgorithm::default(),
+            id: OnceLock::new(),
         }
     }
 
-    fn to_object_format(
-        tree: &ObjectId,
-        author: &Author,
-        message: &str,
-        parent: Option<&ObjectId>,
-        timestamp: u64,
-    ) -> Vec<u8> {
-        let offset = Local::now().format("%z").to_string();
-        let author_with_timestamp = format!("{} {} {}", author, timestamp, offset);
-
-        let content = match &parent {
-            Some(parent) => {
-                format!(
-                    "tree {}\nparent {}\nauthor {}\ncommitter {}\n\n{}",
-                    tree, parent, author_with_timestamp, author_with_timestamp, message
-                )
-            }
-            None => {
-                format!(
-                    "tree {}\nauthor {}\ncommitter {}\n\n{}",
-                    tree, author_with_timestamp, author_with_timestamp, message
-                )
-            }
-        };
-
-        to_object_format("commit", content.as_bytes())
+    /// Create a commit from all of its parts, as when reading a stored commit. The author and
+    /// committer each come with the time in seconds since the epoch and the time zone.
+    pub(crate) fn from_parts(
+        tree: ObjectId,
+        parents: Vec<ObjectId>,
+        (author, timestamp, timezone): (Author, u64, String),
+        (committer, committer_timestamp, committer_timezone): (Author, u64, String),
+        extra_headers: Vec<u8>,
+        raw_message: Vec<u8>,
+        hash_algorithm: HashAlgorithm,
+    ) -> Self {
+        Self {
+            tree,
+            author,
+            message: String::from_utf8_lossy(&raw_message).into_owned(),
+            raw_message,
+            parents,
+            timestamp,
+            timezone,
+            committer,
+            committer_timestamp,
+            committer_timezone,
+            extra_headers,
+            hash_algorithm,
+            id: OnceLock::new(),
+        }
+    }
+
+    /// Use the id the commit is already known by, such as the id it was loaded with, instead of
+    /// computing it.
+    pub(crate) fn with_known_id(self, id: ObjectId) -> Self {
+        Self {
+            id: OnceLock::from(id),
+            ..self
+        }
+    }
+
+    /// Replace the time zone the commit was authored in.
+    pub fn with_timezone(mut self, timezone: String) -> Self {
+        self.timezone = timezone;
+        self.id.take();
+        self
+    }
+
+    /// Replace the committer along with the time and time zone of committing.
+    pub fn with_committer(mut self, committer: Author, timestamp: u64, timezone: String) -> Self {
+        self.committer = committer;
+        self.committer_timestamp = timestamp;
+        self.committer_timezone = timezone;
+        self.id.take();
+        self
+    }
+
+    /// Replace the raw header lines that follow the committer. Each line must end with a
+    /// newline.
+    pub fn with_extra_headers(mut self, extra_headers: Vec<u8>) -> Self {
+        self.extra_headers = extra_headers;
+        self.id.take();
+        self
+    }
+
+    /// Replace the message with bytes that need not be valid UTF-8, such as a message in the
+    /// encoding named by an `encoding` header.
+    pub fn with_raw_message(mut self, raw_message: Vec<u8>) -> Self {
+        self.message = String::from_utf8_lossy(&raw_message).into_owned();
+        self.raw_message = raw_message;
+        self.id.take();
+        self
+    }
+
+    /// The message exactly as it is stored.
+    pub fn raw_message(&self) -> &[u8] {
+        &self.raw_message
+    }
+
+    /// Replace the hash algorithm that the id of the commit is computed with.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self.id.take();
+        self
+    }
+
+    /// The first parent of the commit, if any.
+    pub fn parent(&self) -> Option<&ObjectId> {
+        self.parents.first()
+    }
+}
+
+/// Commits are equal if they have the same id, which covers all of their content.
+impl PartialEq for Commit {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
     }
 }
 
 impl<'a> GitObject<'a> for Commit {
     fn id(&self) -> &ObjectId {
-        &self.id
+        self.id
+            .get_or_init(|| ObjectId::compute(&self.to_object_format(), self.hash_algorithm))
     }
 
     fn to_object_format(&self) -> Vec<u8> {
-        Self::to_object_format(
-            &self.tree,
-            &self.author,
-            &self.message,
-            self.parent.as_ref(),
+        let parent_lines = self
+            .parents
+            .iter()
+            .map(|parent| format!("parent {}\n", parent))
+            .collect::<String>();
+        let mut content = format!(
+            "tree {}\n{}author {} {} {}\ncommitter {} {} {}\n",
+            self.tree,
+            parent_lines,
+            self.author,
             self.timestamp,
+            self.timezone,
+            self.committer,
+            self.committer_timestamp,
+            self.committer_timezone,
         )
+        .into_bytes();
+        content.extend_from_slice(&self.extra_headers);
+        content.push(b'\n');
+        content.extend_from_slice(&self.raw_message);
+
+        to_object_format("commit", &content)
     }
 }
 
@@ -353,10 +569,32 @@ mod tests {
         }
     }
 
-    mod objectid_tests {
+    mod commit_tests {
         use super::*;
 
-        use std::num::ParseIntError;
+        #[test]
+        fn commit_id_is_computed_after_changes() {
+            let tree = ObjectId::from_sha("4b825dc642cb6eb9a060e54bf8d69288fbee4904").unwrap();
+            let author = Author {
+                name: String::from("Author"),
+                email: String::from("author@example.com"),
+            };
+            let commit = Commit::with_parents(tree, author, String::from("Message\n"), vec![], 0)
+                .with_timezone(String::from("+0000"));
+            let id_before = commit.id().clone();
+
+            let commit = commit.with_timezone(String::from("+0100"));
+
+            assert_ne!(commit.id(), &id_before);
+            assert_eq!(
+                commit.id(),
+                &ObjectId::compute(&commit.to_object_format(), HashAlgorithm::Sha1)
+            );
+        }
+    }
+
+    mod objectid_tests {
+        use super::*;
 
         #[test]
         fn from_sha_error_on_invalid_length() {
@@ -375,11 +613,24 @@ mod tests {
         }
 
         #[test]
-        fn from_sha_bytes_accepts_hexlified_bytes() -> Result<(), ParseIntError> {
+        fn from_sha_error_describes_invalid_character() {
+            let hash = "ce013625030ba8dba906f756967f9e9ca39446xa";
+            let result = ObjectId::from_sha(hash);
+
+            assert_eq!(
+                result,
+                Err(format!(
+                    "invalid object id '{}': invalid hexadecimal digit 'x' at index 38",
+                    hash
+                ))
+            );
+        }
+
+        #[test]
+        fn from_sha_bytes_accepts_raw_bytes() -> Result<(), hex::ParseHexError> {
             let hash = "ce013625030ba8dba906f756967f9e9ca394464a";
             let bytes = hex::from_hex_string(hash)?;
-            let hexlified_bytes = hex::hexlify(&bytes);
-            let result = ObjectId::from_sha_bytes(&hexlified_bytes);
+            let result = ObjectId::from_sha_bytes(&bytes);
 
             assert_eq!(result.unwrap().to_string(), hash);
 
@@ -1,5 +1,8 @@
+use std::fmt;
 use std::io;
 
+use crate::config::Config;
+
 /// Abstraction of an output writer used by Rut commands to write status messages.
 pub trait OutputWriter {
     /// Write the content to the output.
@@ -23,6 +26,85 @@ pub trait OutputWriter {
 
     /// Reset all output formatting.
     fn reset_formatting(&mut self) -> io::Result<&mut dyn OutputWriter>;
+
+    /// Send the rest of the output through the pager command, if the output is shown in a
+    /// terminal. Writers that don't write to a terminal ignore this.
+    fn start_pager(&mut self, _pager_command: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Decide when colors and styles are emitted. Writers that never emit them ignore this.
+    fn set_color_mode(&mut self, _color_mode: ColorMode) {}
+}
+
+/// Abstraction of a progress meter, used by Rut commands to report on long operations such as
+/// adding or checking out many files.
+pub trait Progress {
+    /// Start a phase of the operation, with the total number of items if it's known.
+    fn start(&mut self, title: &str, total: Option<u64>);
+
+    /// Report how many items of the current phase are done.
+    fn update(&mut self, done: u64);
+
+    /// Finish the current phase.
+    fn finish(&mut self);
+}
+
+/// A Progress that doesn't report anything.
+#[derive(Debug, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn start(&mut self, _title: &str, _total: Option<u64>) {}
+
+    fn update(&mut self, _done: u64) {}
+
+    fn finish(&mut self) {}
+}
+
+/// When an OutputWriter emits colors and styles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    Always,
+    Never,
+    /// Only when the output is shown in a terminal.
+    #[default]
+    Auto,
+}
+
+impl ColorMode {
+    /// Read the color mode from the `color.ui` config, where a boolean true means `auto`, or use
+    /// `auto` if it's not set.
+    pub fn from_config(config: &Config) -> crate::Result<ColorMode> {
+        let value = match config.get_string("color.ui") {
+            Some(value) => value.to_lowercase(),
+            None => return Ok(ColorMode::default()),
+        };
+        match value.as_str() {
+            "true" | "yes" | "on" | "1" => Ok(ColorMode::Auto),
+            "false" | "no" | "off" | "0" => Ok(ColorMode::Never),
+            _ => parse_color_mode(&value).map_err(|message| crate::Error::Fatal(None, message)),
+        }
+    }
+}
+
+/// Parse a color mode given as `always`, `never` or `auto`.
+///
+/// # Examples
+/// ```
+/// use rut::output::{self, ColorMode};
+///
+/// assert_eq!(output::parse_color_mode("always"), Ok(ColorMode::Always));
+/// assert_eq!(output::parse_color_mode("never"), Ok(ColorMode::Never));
+/// assert!(output::parse_color_mode("sometimes").is_err());
+/// ```
+pub fn parse_color_mode(s: &str) -> Result<ColorMode, String> {
+    match s {
+        "always" => Ok(ColorMode::Always),
+        "never" => Ok(ColorMode::Never),
+        "auto" => Ok(ColorMode::Auto),
+        _ => Err(format!("invalid color mode '{}'", s)),
+    }
 }
 
 /// A color used by an OutputWriter.
@@ -31,6 +113,7 @@ pub enum Color {
     Green,
     Cyan,
     Brown,
+    Magenta,
 }
 
 /// A style used by an OutputWriter.
@@ -38,3 +121,254 @@ pub enum Style {
     Bold,
     Normal,
 }
+
+/// An OutputWriter that writes to any `io::Write`, such as a file, a buffer or a socket. Colors
+/// and styles are written as ANSI escape codes if enabled, and are otherwise left out.
+///
+/// # Examples
+/// ```
+/// use rut::output::{Color, OutputWriter, WriteAdapter};
+///
+/// let mut writer = WriteAdapter::new(Vec::new()).with_ansi(true);
+/// writer
+///     .set_color(Color::Green)?
+///     .write(String::from("ok"))?
+///     .reset_formatting()?;
+///
+/// assert_eq!(writer.into_inner(), b"\x1b[32mok\x1b[0m");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct WriteAdapter<W: io::Write> {
+    inner: W,
+    ansi: bool,
+}
+
+impl<W: io::Write> WriteAdapter<W> {
+    /// Write to the inner writer without colors or styles and without buffering.
+    pub fn new(inner: W) -> Self {
+        Self { inner, ansi: false }
+    }
+
+    /// Write through a buffer, which is flushed when the adapter is dropped or flushed.
+    pub fn buffered(inner: W) -> WriteAdapter<io::BufWriter<W>> {
+        WriteAdapter::new(io::BufWriter::new(inner))
+    }
+
+    /// Enable or disable ANSI escape codes for colors and styles.
+    pub fn with_ansi(mut self, ansi: bool) -> Self {
+        self.ansi = ansi;
+        self
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    fn write_ansi_code(&mut self, ansi_code: &str) -> io::Result<&mut dyn OutputWriter> {
+        if self.ansi {
+            write!(self.inner, "\x1b[{}m", ansi_code)?;
+        }
+        Ok(self)
+    }
+}
+
+impl<W: io::Write> OutputWriter for WriteAdapter<W> {
+    fn write(&mut self, content: String) -> io::Result<&mut dyn OutputWriter> {
+        self.inner.write_all(content.as_bytes())?;
+        Ok(self)
+    }
+
+    fn set_color(&mut self, color: Color) -> io::Result<&mut dyn OutputWriter> {
+        let ansi_code = match color {
+            Color::Red => "31",
+            Color::Green => "32",
+            Color::Cyan => "36",
+            Color::Brown => "38;5;130",
+            Color::Magenta => "35",
+        };
+        self.write_ansi_code(ansi_code)
+    }
+
+    fn set_style(&mut self, style: Style) -> io::Result<&mut dyn OutputWriter> {
+        let ansi_code = match style {
+            Style::Bold => "1",
+            Style::Normal => "22",
+        };
+        self.write_ansi_code(ansi_code)
+    }
+
+    fn reset_formatting(&mut self) -> io::Result<&mut dyn OutputWriter> {
+        self.write_ansi_code("0")
+    }
+
+    /// Always and never force ANSI codes on or off, while auto keeps what the adapter was created
+    /// with, as only its creator knows whether the inner writer is a terminal.
+    fn set_color_mode(&mut self, color_mode: ColorMode) {
+        match color_mode {
+            ColorMode::Always => self.ansi = true,
+            ColorMode::Never => self.ansi = false,
+            ColorMode::Auto => (),
+        }
+    }
+}
+
+/// A JSON value, as written by a [`Serializer`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(i64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// The fields of an object, in the order they are written.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(value) => write!(f, "{}", value),
+            JsonValue::Number(value) => write!(f, "{}", value),
+            JsonValue::String(value) => write_json_string(value, f),
+            JsonValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(f, "{}{}", separator, value)?;
+                }
+                write!(f, "]")
+            }
+            JsonValue::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(f, "{}", separator)?;
+                    write_json_string(name, f)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_json_string(s: &str, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl From<&str> for JsonValue {
+    fn from(value: &str) -> Self {
+        JsonValue::String(value.to_owned())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(value: String) -> Self {
+        JsonValue::String(value)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(value: bool) -> Self {
+        JsonValue::Bool(value)
+    }
+}
+
+impl From<i64> for JsonValue {
+    fn from(value: i64) -> Self {
+        JsonValue::Number(value)
+    }
+}
+
+impl From<u64> for JsonValue {
+    fn from(value: u64) -> Self {
+        JsonValue::Number(value as i64)
+    }
+}
+
+impl From<u32> for JsonValue {
+    fn from(value: u32) -> Self {
+        JsonValue::Number(value.into())
+    }
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(JsonValue::Null, Into::into)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Vec<T>> for JsonValue {
+    fn from(values: Vec<T>) -> Self {
+        JsonValue::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Writes results as JSON lines, with one JSON object per line, for tools that would otherwise
+/// have to parse the text formats.
+///
+/// # Examples
+/// ```
+/// use rut::output::{Serializer, WriteAdapter};
+///
+/// let mut writer = WriteAdapter::new(Vec::new());
+/// Serializer::new(&mut writer)
+///     .field("path", "say \"hi\".txt")
+///     .field("size", 3u64)
+///     .write()?;
+///
+/// assert_eq!(writer.into_inner(), b"{\"path\":\"say \\\"hi\\\".txt\",\"size\":3}\n");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Serializer<'a> {
+    writer: &'a mut dyn OutputWriter,
+    fields: Vec<(String, JsonValue)>,
+}
+
+impl<'a> Serializer<'a> {
+    pub fn new(writer: &'a mut dyn OutputWriter) -> Self {
+        Self {
+            writer,
+            fields: vec![],
+        }
+    }
+
+    /// Add a field to the current record.
+    pub fn field<V: Into<JsonValue>>(&mut self, name: &str, value: V) -> &mut Self {
+        self.fields.push((name.to_owned(), value.into()));
+        self
+    }
+
+    /// Write the current record as a line, and start a new one.
+    pub fn write(&mut self) -> io::Result<()> {
+        let record = JsonValue::Object(std::mem::take(&mut self.fields));
+        self.writer.writeln(record.to_string())?;
+        Ok(())
+    }
+}
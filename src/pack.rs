@@ -0,0 +1,405 @@
+//! Reading and writing packfiles, the format in which objects are transferred between
+//! repositories: a header, each object compressed on its own, and a checksum of it all.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    hashing::{HashAlgorithm, Hasher},
+    objects::{GitObject, ObjectId, ObjectType, RawObject},
+    output::Progress,
+    workspace::Database,
+};
+
+const SIGNATURE: &[u8; 4] = b"PACK";
+const VERSION: u32 = 2;
+
+/// The type numbers of pack entries. Deltas are stored as the differences to a base object, which
+/// is given either by its offset in the pack or by its id.
+const COMMIT_TYPE: u8 = 1;
+const TREE_TYPE: u8 = 2;
+const BLOB_TYPE: u8 = 3;
+const TAG_TYPE: u8 = 4;
+const OFS_DELTA_TYPE: u8 = 6;
+const REF_DELTA_TYPE: u8 = 7;
+
+/// Write the objects to a pack, each of them whole rather than as a delta.
+pub fn write_pack(
+    object_ids: &[ObjectId],
+    database: &Database,
+    output: &mut dyn Write,
+    progress: &mut dyn Progress,
+) -> crate::Result<()> {
+    let mut output = HashingWriter {
+        output,
+        hasher: database.hash_algorithm().hasher(),
+    };
+    output.write_all(SIGNATURE)?;
+    output.write_all(&VERSION.to_be_bytes())?;
+    output.write_all(&(object_ids.len() as u32).to_be_bytes())?;
+
+    progress.start("Writing objects", Some(object_ids.len() as u64));
+    for (i, object_id) in object_ids.iter().enumerate() {
+        let (object_type, content) = database.load_object(object_id)?;
+        let type_number = match object_type {
+            ObjectType::Commit => COMMIT_TYPE,
+            ObjectType::Tree => TREE_TYPE,
+            ObjectType::Blob => BLOB_TYPE,
+        };
+        output.write_all(&encode_entry_header(type_number, content.len()))?;
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&content)?;
+        output.write_all(&encoder.finish()?)?;
+        progress.update(i as u64 + 1);
+    }
+    progress.finish();
+
+    let checksum = output.hasher.finalize();
+    output.output.write_all(&checksum)?;
+    Ok(())
+}
+
+/// Check that the pack starts with a pack header and ends with the checksum of its content.
+pub fn verify_pack(pack: &[u8], hash_algorithm: HashAlgorithm) -> crate::Result<()> {
+    let checksum_start = pack
+        .len()
+        .checked_sub(hash_algorithm.raw_size())
+        .filter(|start| *start >= 12 && pack.starts_with(SIGNATURE))
+        .ok_or_else(|| corrupt("bad pack header"))?;
+    if hash_algorithm.hash(&pack[..checksum_start]) != pack[checksum_start..] {
+        return Err(corrupt("pack checksum mismatch"));
+    }
+    Ok(())
+}
+
+/// An entry of a pack, before its deltas are resolved.
+enum PackEntry {
+    Whole(ObjectType, Vec<u8>),
+    OffsetDelta(usize, Vec<u8>),
+    RefDelta(ObjectId, Vec<u8>),
+}
+
+/// Read all objects in the pack, applying deltas to their bases. The bases of deltas given by id
+/// may be in the database rather than in the pack, as in thin packs.
+pub fn read_pack(pack: &[u8], database: &Database) -> crate::Result<Vec<RawObject>> {
+    let hash_algorithm = database.hash_algorithm();
+    verify_pack(pack, hash_algorithm)?;
+    let version = u32::from_be_bytes(pack[4..8].try_into().unwrap());
+    if version != 2 && version != 3 {
+        return Err(corrupt(&format!("unsupported pack version {}", version)));
+    }
+    let count = u32::from_be_bytes(pack[8..12].try_into().unwrap()) as usize;
+    let end = pack.len() - hash_algorithm.raw_size();
+
+    // every entry takes at least a byte, so a count beyond the size of the pack is corrupt anyway
+    let mut entries = Vec::with_capacity(count.min(end - 12));
+    let mut position = 12;
+    for _ in 0..count {
+        let entry_start = position;
+        let (type_number, size) = decode_entry_header(pack, &mut position, end)?;
+        let entry = match type_number {
+            OFS_DELTA_TYPE => {
+                let offset = decode_base_offset(pack, &mut position, end)?;
+                let base_start = entry_start
+                    .checked_sub(offset)
+                    .ok_or_else(|| corrupt("delta base offset out of bounds"))?;
+                PackEntry::OffsetDelta(base_start, inflate(pack, &mut position, end, size)?)
+            }
+            REF_DELTA_TYPE => {
+                let id_end = position + hash_algorithm.raw_size();
+                let base_id = pack[..end]
+                    .get(position..id_end)
+                    .and_then(|bytes| ObjectId::from_sha_bytes(bytes).ok())
+                    .ok_or_else(|| corrupt("truncated delta base id"))?;
+                position = id_end;
+                PackEntry::RefDelta(base_id, inflate(pack, &mut position, end, size)?)
+            }
+            type_number => {
+                let object_type = match type_number {
+                    COMMIT_TYPE => ObjectType::Commit,
+                    TREE_TYPE => ObjectType::Tree,
+                    BLOB_TYPE => ObjectType::Blob,
+                    TAG_TYPE => return Err(corrupt("tag objects are not supported")),
+                    _ => return Err(corrupt("unknown object type")),
+                };
+                PackEntry::Whole(object_type, inflate(pack, &mut position, end, size)?)
+            }
+        };
+        entries.push((entry_start, entry));
+    }
+
+    resolve_deltas(entries, database)
+}
+
+/// Resolve the deltas of the entries in passes, each of which resolves the deltas whose bases
+/// were resolved in the previous passes, until all of them are resolved.
+fn resolve_deltas(
+    entries: Vec<(usize, PackEntry)>,
+    database: &Database,
+) -> crate::Result<Vec<RawObject>> {
+    let hash_algorithm = database.hash_algorithm();
+    let mut objects: Vec<RawObject> = vec![];
+    let mut index_by_offset: HashMap<usize, usize> = HashMap::new();
+    let mut index_by_id: HashMap<ObjectId, usize> = HashMap::new();
+    let mut pending = entries;
+    while !pending.is_empty() {
+        let mut unresolved = vec![];
+        let pending_count = pending.len();
+        for (offset, entry) in pending {
+            let (object_type, content) = match entry {
+                PackEntry::Whole(object_type, content) => (object_type, content),
+                PackEntry::OffsetDelta(base_offset, delta) => {
+                    match index_by_offset.get(&base_offset) {
+                        Some(&index) => {
+                            let base: &RawObject = &objects[index];
+                            (base.object_type(), apply_delta(base.content(), &delta)?)
+                        }
+                        None => {
+                            unresolved.push((offset, PackEntry::OffsetDelta(base_offset, delta)));
+                            continue;
+                        }
+                    }
+                }
+                PackEntry::RefDelta(base_id, delta) => match index_by_id.get(&base_id) {
+                    Some(&index) => {
+                        let base: &RawObject = &objects[index];
+                        (base.object_type(), apply_delta(base.content(), &delta)?)
+                    }
+                    None if database.object_path(&base_id).exists() => {
+                        let (object_type, content) = database.load_object(&base_id)?;
+                        (object_type, apply_delta(&content, &delta)?)
+                    }
+                    None => {
+                        unresolved.push((offset, PackEntry::RefDelta(base_id, delta)));
+                        continue;
+                    }
+                },
+            };
+            let object = RawObject::new(object_type, content, hash_algorithm);
+            index_by_offset.insert(offset, objects.len());
+            index_by_id.insert(object.id().clone(), objects.len());
+            objects.push(object);
+        }
+        if unresolved.len() == pending_count {
+            return Err(corrupt("delta base is missing"));
+        }
+        pending = unresolved;
+    }
+    Ok(objects)
+}
+
+/// Build the object described by the delta from its base. A delta starts with the sizes of the
+/// base and the result, followed by instructions to either copy a range of the base or insert
+/// the bytes that follow the instruction.
+fn apply_delta(base: &[u8], delta: &[u8]) -> crate::Result<Vec<u8>> {
+    let error = || corrupt("invalid delta");
+    let mut position = 0;
+    let base_size = decode_size(delta, &mut position).ok_or_else(error)?;
+    let result_size = decode_size(delta, &mut position).ok_or_else(error)?;
+    if base_size != base.len() {
+        return Err(error());
+    }
+
+    // the result size is only trusted once the result is built
+    let mut result = Vec::with_capacity(result_size.min(base.len() + delta.len()));
+    while position < delta.len() {
+        let instruction = delta[position];
+        position += 1;
+        if instruction & 0x80 != 0 {
+            // the bits say which bytes of the offset and size are present, in little-endian order
+            let mut read_bytes = |bits: u8, count: usize| -> Option<usize> {
+                let mut value = 0;
+                for i in 0..count {
+                    if bits & (1 << i) != 0 {
+                        value |= (*delta.get(position)? as usize) << (8 * i);
+                        position += 1;
+                    }
+                }
+                Some(value)
+            };
+            let offset = read_bytes(instruction, 4).ok_or_else(error)?;
+            let size = match read_bytes(instruction >> 4, 3).ok_or_else(error)? {
+                0 => 0x10000,
+                size => size,
+            };
+            result.extend_from_slice(base.get(offset..offset + size).ok_or_else(error)?);
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            let bytes = delta.get(position..position + size).ok_or_else(error)?;
+            result.extend_from_slice(bytes);
+            position += size;
+        } else {
+            return Err(error());
+        }
+    }
+
+    if result.len() != result_size {
+        return Err(error());
+    }
+    Ok(result)
+}
+
+/// Encode the type and size of an entry: the type in bits 4-6 of the first byte, and the size in
+/// the low 4 bits of it followed by 7 bits per byte, where the high bit marks that more follow.
+fn encode_entry_header(type_number: u8, size: usize) -> Vec<u8> {
+    let mut bytes = vec![(type_number << 4) | (size & 0x0f) as u8];
+    let mut size = size >> 4;
+    while size > 0 {
+        *bytes.last_mut().unwrap() |= 0x80;
+        bytes.push((size & 0x7f) as u8);
+        size >>= 7;
+    }
+    bytes
+}
+
+fn decode_entry_header(
+    pack: &[u8],
+    position: &mut usize,
+    end: usize,
+) -> crate::Result<(u8, usize)> {
+    let error = || corrupt("truncated object header");
+    let mut byte = *pack[..end].get(*position).ok_or_else(error)?;
+    *position += 1;
+    let type_number = (byte >> 4) & 0x07;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+        byte = *pack[..end].get(*position).ok_or_else(error)?;
+        *position += 1;
+        if shift > usize::BITS - 7 {
+            return Err(corrupt("object size too large"));
+        }
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+    Ok((type_number, size))
+}
+
+/// Decode the offset back to the base of an offset delta, in which each byte but the last is
+/// also incremented by one, so that each offset has a single encoding.
+fn decode_base_offset(pack: &[u8], position: &mut usize, end: usize) -> crate::Result<usize> {
+    let error = || corrupt("truncated delta base offset");
+    let mut byte = *pack[..end].get(*position).ok_or_else(error)?;
+    *position += 1;
+    let mut offset = (byte & 0x7f) as usize;
+    while byte & 0x80 != 0 {
+        byte = *pack[..end].get(*position).ok_or_else(error)?;
+        *position += 1;
+        offset = offset
+            .checked_add(1)
+            .and_then(|offset| offset.checked_mul(1 << 7))
+            .ok_or_else(|| corrupt("delta base offset out of bounds"))?
+            | (byte & 0x7f) as usize;
+    }
+    Ok(offset)
+}
+
+/// Decode a size in a delta, 7 bits per byte in little-endian order.
+fn decode_size(delta: &[u8], position: &mut usize) -> Option<usize> {
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *delta.get(*position)?;
+        *position += 1;
+        if shift > usize::BITS - 7 {
+            return None;
+        }
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(size);
+        }
+    }
+}
+
+/// Inflate the compressed data at the position, and move the position past it.
+fn inflate(pack: &[u8], position: &mut usize, end: usize, size: usize) -> crate::Result<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(&pack[*position..end]);
+    // the size is only trusted once the data is inflated, and no more than it is inflated
+    let mut content = Vec::with_capacity(size.min(end - *position));
+    decoder
+        .by_ref()
+        .take(size as u64 + 1)
+        .read_to_end(&mut content)?;
+    if content.len() != size {
+        return Err(corrupt("object size mismatch"));
+    }
+    *position += decoder.total_in() as usize;
+    Ok(content)
+}
+
+fn corrupt(reason: &str) -> crate::Error {
+    crate::Error::Fatal(None, format!("corrupt pack: {}", reason))
+}
+
+/// Writes to the output while hashing everything that is written.
+struct HashingWriter<'a> {
+    output: &'a mut dyn Write,
+    hasher: Hasher,
+}
+
+impl Write for HashingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.output.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_delta_copies_and_inserts() {
+        let base = b"Hello, world!";
+        // sizes 13 and 13, copy 7 bytes from offset 0, insert "there", then copy "!"
+        let delta = [
+            13, 13, 0x90, 7, 5, b't', b'h', b'e', b'r', b'e', 0x91, 12, 1,
+        ];
+
+        let result = apply_delta(base, &delta).unwrap();
+
+        assert_eq!(result, b"Hello, there!");
+    }
+
+    #[test]
+    fn test_entry_header_round_trip() {
+        let header = encode_entry_header(BLOB_TYPE, 1000);
+        let mut position = 0;
+
+        let decoded = decode_entry_header(&header, &mut position, header.len()).unwrap();
+
+        assert_eq!(decoded, (BLOB_TYPE, 1000));
+        assert_eq!(position, header.len());
+    }
+
+    #[test]
+    fn test_oversized_header_and_offset_are_corrupt() {
+        let header = [0xff; 12];
+        let mut position = 0;
+
+        assert!(decode_entry_header(&header, &mut position, header.len()).is_err());
+        position = 0;
+        assert!(decode_base_offset(&header, &mut position, header.len()).is_err());
+        assert_eq!(decode_size(&header, &mut 0), None);
+    }
+
+    #[test]
+    fn test_pack_with_huge_entry_count_is_corrupt() {
+        let mut pack = [&SIGNATURE[..], &2u32.to_be_bytes(), &u32::MAX.to_be_bytes()].concat();
+        pack.extend(HashAlgorithm::Sha1.hash(&pack));
+        let workdir = rut_testhelpers::create_temporary_directory();
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
+
+        let result = read_pack(&pack, &database);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,279 @@
+//! Parsing and applying patches in the unified diff format written by `diff`, which is also the
+//! format of Git's patches. Only textual changes are understood, binary patches are not.
+
+use std::path::PathBuf;
+
+/// The changes of a single file in a patch.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FilePatch {
+    /// The path before the change, or None if the file is created.
+    pub old_path: Option<PathBuf>,
+    /// The path after the change, or None if the file is deleted.
+    pub new_path: Option<PathBuf>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A contiguous range of changed lines. The lines include their linefeeds, if any.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Hunk {
+    /// The index of the first line of the range in the old content.
+    old_start: usize,
+    old_lines: Vec<String>,
+    new_lines: Vec<String>,
+}
+
+/// Parse the file patches of a patch. Any text before the first `diff --git` line is ignored, as
+/// is everything after a `-- ` line, which starts the signature of a mailed patch.
+pub fn parse(patch: &str) -> crate::Result<Vec<FilePatch>> {
+    let error = |message: &str| crate::Error::Fatal(None, format!("corrupt patch: {}", message));
+
+    let mut file_patches = vec![];
+    let mut lines = patch
+        .split_inclusive('\n')
+        .skip_while(|line| !line.starts_with("diff --git "))
+        .take_while(|line| line.trim_end_matches('\n') != "-- ")
+        .peekable();
+
+    while let Some(line) = lines.next() {
+        let names = line
+            .trim_end()
+            .strip_prefix("diff --git a/")
+            .ok_or_else(|| error(line.trim_end()))?;
+        let (old_name, new_name) = names
+            .split_once(" b/")
+            .ok_or_else(|| error(line.trim_end()))?;
+        let mut file_patch = FilePatch {
+            old_path: Some(PathBuf::from(old_name)),
+            new_path: Some(PathBuf::from(new_name)),
+            hunks: vec![],
+        };
+
+        while let Some(line) = lines.next_if(|line| !line.starts_with("diff --git ")) {
+            let line = line.trim_end_matches('\n');
+            if line.starts_with("new file mode") || line == "--- /dev/null" {
+                file_patch.old_path = None;
+            } else if line.starts_with("deleted file mode") || line == "+++ /dev/null" {
+                file_patch.new_path = None;
+            } else if let Some(old_name) = line.strip_prefix("rename from ") {
+                file_patch.old_path = Some(PathBuf::from(old_name));
+            } else if let Some(new_name) = line.strip_prefix("rename to ") {
+                file_patch.new_path = Some(PathBuf::from(new_name));
+            } else if let Some(ranges) = line.strip_prefix("@@ -") {
+                let hunk = parse_hunk(ranges, &mut lines).ok_or_else(|| error(line))?;
+                file_patch.hunks.push(hunk);
+            }
+        }
+        file_patches.push(file_patch);
+    }
+
+    Ok(file_patches)
+}
+
+/// Parse the lines of a hunk, given the ranges of its header.
+fn parse_hunk<'a, I: Iterator<Item = &'a str>>(
+    ranges: &str,
+    lines: &mut std::iter::Peekable<I>,
+) -> Option<Hunk> {
+    let (old_range, rest) = ranges.split_once(" +")?;
+    let (new_range, _) = rest.split_once(" @@")?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, mut old_remaining) = parse_range(old_range)?;
+    let (_, mut new_remaining) = parse_range(new_range)?;
+
+    let mut hunk = Hunk {
+        // an empty range is given by the line before it
+        old_start: if old_remaining == 0 {
+            old_start
+        } else {
+            old_start.checked_sub(1)?
+        },
+        old_lines: vec![],
+        new_lines: vec![],
+    };
+    let mut last_kind = ' ';
+    let is_missing_newline = |line: Option<&&str>| line.is_some_and(|line| line.starts_with('\\'));
+    while old_remaining > 0 || new_remaining > 0 || is_missing_newline(lines.peek()) {
+        let line = lines.next()?;
+        let (kind, content) = match line.chars().next()? {
+            // some editors strip the space of empty context lines
+            '\n' => (' ', line),
+            kind => (kind, &line[1..]),
+        };
+        match kind {
+            ' ' if old_remaining > 0 && new_remaining > 0 => {
+                hunk.old_lines.push(content.to_owned());
+                hunk.new_lines.push(content.to_owned());
+                old_remaining -= 1;
+                new_remaining -= 1;
+            }
+            '-' if old_remaining > 0 => {
+                hunk.old_lines.push(content.to_owned());
+                old_remaining -= 1;
+            }
+            '+' if new_remaining > 0 => {
+                hunk.new_lines.push(content.to_owned());
+                new_remaining -= 1;
+            }
+            // "\ No newline at end of file" applies to the line before it
+            '\\' => {
+                let strip_linefeed = |lines: &mut Vec<String>| {
+                    if let Some(last_line) = lines.last_mut() {
+                        last_line.pop();
+                    }
+                };
+                if last_kind != '+' {
+                    strip_linefeed(&mut hunk.old_lines);
+                }
+                if last_kind != '-' {
+                    strip_linefeed(&mut hunk.new_lines);
+                }
+            }
+            _ => return None,
+        }
+        last_kind = kind;
+    }
+
+    Some(hunk)
+}
+
+/// Apply the hunks to the content. A hunk whose lines don't match at the position given in its
+/// header is applied at the nearest position where they do, like Git does, as the file may have
+/// changed elsewhere. Returns None if some hunk doesn't match anywhere.
+pub fn apply(content: &str, hunks: &[Hunk]) -> Option<String> {
+    let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    let mut result = String::new();
+    // lines before this have been copied or replaced
+    let mut position = 0;
+    // how far the previous hunk was moved from its position
+    let mut offset = 0_isize;
+
+    for hunk in hunks {
+        let expected_start = (hunk.old_start as isize + offset).max(position as isize) as usize;
+        let start = find_hunk_start(&lines, hunk, position, expected_start)?;
+        offset = start as isize - hunk.old_start as isize;
+
+        result.extend(lines[position..start].iter().copied());
+        result.extend(hunk.new_lines.iter().map(String::as_str));
+        position = start + hunk.old_lines.len();
+    }
+    result.extend(lines[position..].iter().copied());
+
+    Some(result)
+}
+
+/// Find the start of the lines of the hunk at or after the minimum start, searching outwards
+/// from the expected start.
+fn find_hunk_start(
+    lines: &[&str],
+    hunk: &Hunk,
+    min_start: usize,
+    expected_start: usize,
+) -> Option<usize> {
+    let matches_at = |start: usize| {
+        start + hunk.old_lines.len() <= lines.len()
+            && hunk
+                .old_lines
+                .iter()
+                .zip(&lines[start..])
+                .all(|(old_line, line)| old_line == line)
+    };
+    if hunk.old_lines.is_empty() {
+        return (expected_start <= lines.len()).then_some(expected_start);
+    }
+
+    (0..=lines.len()).find_map(|distance| {
+        let after = expected_start + distance;
+        let before = expected_start
+            .checked_sub(distance)
+            .filter(|start| *start >= min_start);
+        [Some(after), before]
+            .into_iter()
+            .flatten()
+            .find(|start| matches_at(*start))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "\
+diff --git a/file.txt b/file.txt
+index 1234567..89abcde
+--- a/file.txt
++++ b/file.txt
+@@ -1,3 +1,3 @@
+ a
+-b
++x
+ c
+diff --git a/new.txt b/new.txt
+index 0000000..1234567
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++new
+";
+
+    #[test]
+    fn test_parse_modification_and_creation() {
+        let file_patches = parse(PATCH).unwrap();
+
+        assert_eq!(
+            file_patches,
+            vec![
+                FilePatch {
+                    old_path: Some(PathBuf::from("file.txt")),
+                    new_path: Some(PathBuf::from("file.txt")),
+                    hunks: vec![Hunk {
+                        old_start: 0,
+                        old_lines: vec!["a\n".into(), "b\n".into(), "c\n".into()],
+                        new_lines: vec!["a\n".into(), "x\n".into(), "c\n".into()],
+                    }],
+                },
+                FilePatch {
+                    old_path: None,
+                    new_path: Some(PathBuf::from("new.txt")),
+                    hunks: vec![Hunk {
+                        old_start: 0,
+                        old_lines: vec![],
+                        new_lines: vec!["new\n".into()],
+                    }],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_newline_at_end_of_file() {
+        let patch = "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n\\ No newline at end of file\n+b\n";
+
+        let file_patches = parse(patch).unwrap();
+
+        assert_eq!(file_patches[0].hunks[0].old_lines, vec!["a"]);
+        assert_eq!(file_patches[0].hunks[0].new_lines, vec!["b\n"]);
+    }
+
+    #[test]
+    fn test_apply_at_offset() {
+        let file_patches = parse(PATCH).unwrap();
+
+        let result = apply("new first line\na\nb\nc\n", &file_patches[0].hunks);
+
+        assert_eq!(result.as_deref(), Some("new first line\na\nx\nc\n"));
+    }
+
+    #[test]
+    fn test_apply_fails_without_matching_lines() {
+        let file_patches = parse(PATCH).unwrap();
+
+        let result = apply("a\ny\nc\n", &file_patches[0].hunks);
+
+        assert_eq!(result, None);
+    }
+}
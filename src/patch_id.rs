@@ -0,0 +1,68 @@
+//! Patch ids, which identify the changes made by a commit regardless of where in the history the
+//! commit is. A commit that is cherry-picked or rebased onto another commit keeps its patch id as
+//! long as its changes apply without modification.
+
+use crate::diff;
+use crate::objects::{Commit, GitObject, ObjectId};
+use crate::output::WriteAdapter;
+use crate::workspace::Repository;
+
+/// Compute the patch id of the commit, which is the hash of its diff against its parent with all
+/// whitespace, the index lines and the hunk headers left out, so that neither line numbers nor
+/// indentation changes affect it. Merge commits and commits without changes have no patch id.
+pub fn patch_id(commit: &Commit, repository: &Repository) -> crate::Result<Option<ObjectId>> {
+    if commit.parents.len() > 1 {
+        return Ok(None);
+    }
+    let file_diffs = diff::diff_commits(commit.parent(), commit.id(), repository)?;
+    if file_diffs.is_empty() {
+        return Ok(None);
+    }
+
+    let mut writer = WriteAdapter::new(Vec::new());
+    diff::write_patch(
+        &file_diffs,
+        diff::DEFAULT_CONTEXT_LINES,
+        diff::Algorithm::default(),
+        &mut writer,
+    )?;
+    let patch = String::from_utf8_lossy(writer.get_ref()).into_owned();
+    Ok(Some(ObjectId::compute(
+        normalize(&patch).as_bytes(),
+        repository.hash_algorithm(),
+    )))
+}
+
+/// Strip a patch of what doesn't identify the changes: whitespace, the abbreviated blob ids of the
+/// index lines and the line numbers of the hunk headers.
+fn normalize(patch: &str) -> String {
+    patch
+        .lines()
+        .filter(|line| !line.starts_with("index ") && !line.starts_with("@@ "))
+        .flat_map(|line| line.chars().filter(|c| !c.is_whitespace()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_ignores_line_numbers_and_whitespace() {
+        let patch = "diff --git a/file.txt b/file.txt\n\
+                     index 1234567..89abcde 100644\n\
+                     --- a/file.txt\n\
+                     +++ b/file.txt\n\
+                     @@ -1,2 +1,2 @@\n \
+                     one\n\
+                     -two\n\
+                     +\tTWO\n";
+        let moved_patch = patch
+            .replace("1234567..89abcde", "7654321..edcba98")
+            .replace("@@ -1,2 +1,2 @@", "@@ -10,2 +10,2 @@")
+            .replace("+\tTWO", "+    TWO");
+
+        assert_eq!(normalize(patch), normalize(&moved_patch));
+        assert_ne!(normalize(patch), normalize(&patch.replace("TWO", "2")));
+    }
+}
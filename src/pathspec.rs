@@ -0,0 +1,330 @@
+use std::path::{Component, Path, PathBuf};
+
+use regex::Regex;
+
+use crate::file;
+use crate::workspace::Worktree;
+
+/// A set of patterns that select paths in the worktree, relative to its root.
+///
+/// Each pattern is one of:
+///
+/// * A literal path, which matches the path itself and everything below it if it's a directory.
+///   `.` matches every path.
+/// * A glob containing `*`, `?` or `[...]`, which matches against the whole path. Like in Git,
+///   `*` also matches `/`, so `*.md` matches Markdown files in every directory.
+/// * An exclusion, written as `:!<pattern>`, `:^<pattern>` or `:(exclude)<pattern>`, which removes
+///   the paths it matches from the selection.
+///
+/// Patterns are relative to the directory the user is working in, unless prefixed with `:/` to
+/// make them relative to the root of the worktree.
+///
+/// A pathspec without any including patterns selects every path that isn't excluded.
+#[derive(Debug, Default)]
+pub struct Pathspec {
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    /// The pattern as it should be presented to the user.
+    text: String,
+    exclude: bool,
+    matcher: Matcher,
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Everything,
+    Literal(PathBuf),
+    Glob(Regex),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum MatchKind {
+    /// The pattern names the path itself.
+    Exact,
+    /// The pattern names a directory containing the path.
+    Leading,
+}
+
+impl Pathspec {
+    /// Parse the given patterns. Relative patterns are resolved against the directory the user is
+    /// working in, while absolute paths must be inside the worktree.
+    pub fn parse<P: AsRef<Path>>(patterns: &[P], worktree: &Worktree) -> crate::Result<Pathspec> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| Pattern::parse(&pattern.as_ref().to_string_lossy(), worktree))
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(Pathspec { patterns })
+    }
+
+    /// Check if the relative path is selected by this pathspec.
+    pub fn matches<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        let (excludes, includes): (Vec<&Pattern>, Vec<&Pattern>) =
+            self.patterns.iter().partition(|pattern| pattern.exclude);
+
+        let is_included = includes.is_empty()
+            || includes
+                .iter()
+                .any(|pattern| pattern.match_path(path).is_some());
+        is_included
+            && !excludes
+                .iter()
+                .any(|pattern| pattern.match_path(path).is_some())
+    }
+
+    /// Find the first including pattern that doesn't match any of the given relative paths.
+    pub fn find_unmatched<P: AsRef<Path>>(&self, paths: &[P]) -> Option<&str> {
+        self.patterns
+            .iter()
+            .filter(|pattern| !pattern.exclude)
+            .find(|pattern| {
+                !paths
+                    .iter()
+                    .any(|path| pattern.match_path(path.as_ref()).is_some())
+            })
+            .map(|pattern| pattern.text.as_str())
+    }
+
+    /// Find the first including pattern that only matches any of the given relative paths
+    /// because it names a directory containing them.
+    pub fn find_directory_match<P: AsRef<Path>>(&self, paths: &[P]) -> Option<&str> {
+        self.patterns
+            .iter()
+            .filter(|pattern| !pattern.exclude)
+            .find(|pattern| {
+                let match_kinds = paths
+                    .iter()
+                    .filter_map(|path| pattern.match_path(path.as_ref()))
+                    .collect::<Vec<_>>();
+                !match_kinds.is_empty() && !match_kinds.contains(&MatchKind::Exact)
+            })
+            .map(|pattern| pattern.text.as_str())
+    }
+
+    /// Find the files in the worktree that are selected by this pathspec, as paths relative to
+    /// the root of the worktree. Only the parts of the worktree that literal patterns point to
+    /// are searched, unless there are other including patterns.
+    pub fn resolve_worktree_files(&self, worktree: &Worktree) -> Vec<PathBuf> {
+        let literals = self
+            .patterns
+            .iter()
+            .filter(|pattern| !pattern.exclude)
+            .map(|pattern| match &pattern.matcher {
+                Matcher::Literal(path) => Some(path),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|literals| !literals.is_empty());
+        let search_roots = match literals {
+            Some(literals) => literals
+                .into_iter()
+                .map(|path| worktree.root().join(path))
+                .filter(|path| path.exists())
+                .collect(),
+            None => vec![worktree.root().to_owned()],
+        };
+
+        let mut files = search_roots
+            .iter()
+            .flat_map(|search_root| file::resolve_files(search_root))
+            .map(|path| worktree.relativize_path(path))
+            .filter(|path| self.matches(path))
+            .collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+impl Pattern {
+    fn parse(text: &str, worktree: &Worktree) -> crate::Result<Pattern> {
+        let (exclude, pattern) = if let Some(pattern) = text.strip_prefix(":(exclude)") {
+            (true, pattern)
+        } else if let Some(pattern) = text.strip_prefix(":!").or(text.strip_prefix(":^")) {
+            (true, pattern)
+        } else {
+            (false, text)
+        };
+
+        let (base_directory, pattern) = match pattern.strip_prefix(":/") {
+            Some(pattern) => (Path::new(""), pattern),
+            None => (worktree.prefix(), pattern),
+        };
+
+        if pattern.contains(['*', '?', '[']) {
+            return Ok(Pattern {
+                text: text.to_owned(),
+                exclude,
+                matcher: Matcher::Glob(glob_to_regex(base_directory, pattern)?),
+            });
+        }
+
+        let resolved_path =
+            worktree.resolve_user_path(worktree.root().join(base_directory).join(pattern))?;
+        let normalized_path = resolved_path
+            .components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect::<PathBuf>();
+
+        let matcher = if normalized_path.as_os_str().is_empty() {
+            Matcher::Everything
+        } else {
+            Matcher::Literal(normalized_path.clone())
+        };
+        let text = match exclude {
+            true => text.to_owned(),
+            false if normalized_path.as_os_str().is_empty() => String::from("."),
+            false => normalized_path.display().to_string(),
+        };
+
+        Ok(Pattern {
+            text,
+            exclude,
+            matcher,
+        })
+    }
+
+    fn match_path(&self, path: &Path) -> Option<MatchKind> {
+        match &self.matcher {
+            Matcher::Everything => Some(MatchKind::Leading),
+            Matcher::Literal(literal) if path == literal => Some(MatchKind::Exact),
+            Matcher::Literal(literal) if path.starts_with(literal) => Some(MatchKind::Leading),
+            Matcher::Literal(_) => None,
+            Matcher::Glob(regex) => path
+                .to_str()
+                .filter(|path| regex.is_match(path))
+                .map(|_| MatchKind::Exact),
+        }
+    }
+}
+
+/// Translate a glob relative to the base directory into an anchored regex. A `[` without a
+/// closing `]` is taken literally. Fails if a bracket expression is not a valid class, such as
+/// one with a reversed range.
+pub(crate) fn glob_to_regex(base_directory: &Path, glob: &str) -> crate::Result<Regex> {
+    let mut regex = String::from("^");
+    if !base_directory.as_os_str().is_empty() {
+        regex.push_str(&regex::escape(&format!("{}/", base_directory.display())));
+    }
+    let mut chars = glob.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let rest = chars.as_str();
+                match bracket_to_class(rest) {
+                    Some((class, length)) => {
+                        regex.push_str(&class);
+                        chars = rest[length..].chars();
+                    }
+                    None => regex.push_str(&regex::escape("[")),
+                }
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).map_err(|error| {
+        let message = format!("invalid glob '{}'", glob);
+        crate::Error::Fatal(Some(Box::new(error)), message)
+    })
+}
+
+/// Translate the bracket expression that follows a `[` into a regex class, along with the length
+/// of the expression including its closing `]`. None if there is no closing `]`.
+fn bracket_to_class(rest: &str) -> Option<(String, usize)> {
+    let (negation, members_start) = match rest.chars().next() {
+        Some('!' | '^') => ("^", 1),
+        _ => ("", 0),
+    };
+    // a `]` first among the members is one of them rather than the end of the class
+    let search_start = members_start + usize::from(rest[members_start..].starts_with(']'));
+    let end = search_start + rest[search_start..].find(']')?;
+    let members = regex::escape(&rest[members_start..end]).replace("\\-", "-");
+    Some((format!("[{}{}]", negation, members), end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(patterns: &[&str]) -> Pathspec {
+        Pathspec::parse(patterns, &Worktree::new("/repo")).unwrap()
+    }
+
+    #[test]
+    fn test_literal_matches_path_and_directory_contents() {
+        let pathspec = parse(&["src"]);
+
+        assert!(pathspec.matches("src"));
+        assert!(pathspec.matches("src/main.rs"));
+        assert!(!pathspec.matches("srcs/main.rs"));
+    }
+
+    #[test]
+    fn test_absolute_literal_is_relativized() {
+        let pathspec = parse(&["/repo/src/main.rs"]);
+
+        assert!(pathspec.matches("src/main.rs"));
+        assert!(Pathspec::parse(&["/elsewhere"], &Worktree::new("/repo")).is_err());
+    }
+
+    #[test]
+    fn test_glob_matches_across_directories() {
+        let pathspec = parse(&["*.md"]);
+
+        assert!(pathspec.matches("README.md"));
+        assert!(pathspec.matches("docs/guide.md"));
+        assert!(!pathspec.matches("README.txt"));
+    }
+
+    #[test]
+    fn test_glob_character_classes() {
+        let pathspec = parse(&["file[0-2].txt", "other[!a].txt"]);
+
+        assert!(pathspec.matches("file1.txt"));
+        assert!(!pathspec.matches("file3.txt"));
+        assert!(pathspec.matches("otherb.txt"));
+        assert!(!pathspec.matches("othera.txt"));
+    }
+
+    #[test]
+    fn test_glob_bracket_edge_cases() {
+        let pathspec = parse(&["[!]x]", "[^a]b", "[]]c", "[!]"]);
+
+        assert!(pathspec.matches("a"));
+        assert!(!pathspec.matches("]"));
+        assert!(!pathspec.matches("x"));
+        assert!(pathspec.matches("cb"));
+        assert!(!pathspec.matches("ab"));
+        assert!(pathspec.matches("]c"));
+        assert!(pathspec.matches("[!]"));
+    }
+
+    #[test]
+    fn test_glob_with_invalid_class_is_an_error() {
+        assert!(Pathspec::parse(&["[z-a]"], &Worktree::new("/repo")).is_err());
+    }
+
+    #[test]
+    fn test_exclude_without_includes_matches_everything_else() {
+        let pathspec = parse(&[":!target", ":^*.log"]);
+
+        assert!(pathspec.matches("src/main.rs"));
+        assert!(!pathspec.matches("target/debug/rut"));
+        assert!(!pathspec.matches("build.log"));
+    }
+
+    #[test]
+    fn test_find_unmatched_and_directory_match() {
+        let pathspec = parse(&["src", "missing"]);
+        let paths = ["src/main.rs"];
+
+        assert_eq!(pathspec.find_unmatched(&paths), Some("missing"));
+        assert_eq!(pathspec.find_directory_match(&paths), Some("src"));
+    }
+}
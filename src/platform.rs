@@ -0,0 +1,113 @@
+//! File system details that differ between operating systems. Unix platforms expose the full
+//! `stat` information that Git records in the index, while other platforms fall back to what the
+//! standard library offers.
+
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+
+/// The status information of a file that is recorded in index entries.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub ctime_seconds: u32,
+    pub ctime_nanoseconds: u32,
+    pub mtime_seconds: u32,
+    pub mtime_nanoseconds: u32,
+    pub dev: u32,
+    pub ino: u32,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u32,
+}
+
+#[cfg(unix)]
+impl FileStat {
+    pub fn new(metadata: &Metadata) -> FileStat {
+        use std::os::unix::fs::MetadataExt;
+
+        FileStat {
+            ctime_seconds: metadata.ctime() as u32,
+            ctime_nanoseconds: metadata.ctime_nsec() as u32,
+            mtime_seconds: metadata.mtime() as u32,
+            mtime_nanoseconds: metadata.mtime_nsec() as u32,
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            mode: metadata.mode(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.size() as u32,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+impl FileStat {
+    /// There is no device, inode or owner to record, and the change time is approximated by the
+    /// creation time. Files are never considered executable.
+    pub fn new(metadata: &Metadata) -> FileStat {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let since_epoch = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mtime = metadata.modified().map(since_epoch).unwrap_or_default();
+        let ctime = metadata.created().map(since_epoch).unwrap_or(mtime);
+        let mode = if metadata.is_dir() {
+            0o040000
+        } else {
+            0o100644
+        };
+
+        FileStat {
+            ctime_seconds: ctime.as_secs() as u32,
+            ctime_nanoseconds: ctime.subsec_nanos(),
+            mtime_seconds: mtime.as_secs() as u32,
+            mtime_nanoseconds: mtime.subsec_nanos(),
+            mode,
+            size: metadata.len() as u32,
+            ..Default::default()
+        }
+    }
+}
+
+/// Check whether the file can be executed by its owner, group or others.
+#[cfg(unix)]
+pub fn is_executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+/// Git hooks are run regardless of permissions where there is no executable bit.
+#[cfg(not(unix))]
+pub fn is_executable(metadata: &Metadata) -> bool {
+    metadata.is_file()
+}
+
+/// Set the permissions of the file to `0755` if it should be executable, or `0644` otherwise.
+#[cfg(unix)]
+pub fn set_executable(path: &Path, executable: bool) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = if executable { 0o755 } else { 0o644 };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+pub fn set_executable(path: &Path, _executable: bool) -> io::Result<()> {
+    fs::metadata(path).map(|_| ())
+}
+
+/// The space that the file takes up on disk, in bytes, which is usually more than its length as
+/// files occupy whole blocks.
+#[cfg(unix)]
+pub fn disk_usage(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.blocks() * 512
+}
+
+/// The length of the file approximates its usage where the number of blocks is unknown.
+#[cfg(not(unix))]
+pub fn disk_usage(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
@@ -0,0 +1,32 @@
+use std::io::{self, BufRead, Write};
+
+/// Abstraction of an interactive prompt used by Rut commands to read answers from the user.
+pub trait Prompt {
+    /// Read the answer to a question that has already been written to the output. Returns None
+    /// if there is no more input.
+    fn read_answer(&mut self) -> io::Result<Option<String>>;
+}
+
+/// A prompt that reads one answer per line, for example from stdin.
+pub struct LinePrompt<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> LinePrompt<R> {
+    pub fn new(reader: R) -> LinePrompt<R> {
+        LinePrompt { reader }
+    }
+}
+
+impl<R: BufRead> Prompt for LinePrompt<R> {
+    fn read_answer(&mut self) -> io::Result<Option<String>> {
+        // questions are written without a trailing linefeed, so they must be flushed to be seen
+        io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if self.reader.read_line(&mut answer)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(answer.trim().to_owned()))
+    }
+}
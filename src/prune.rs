@@ -0,0 +1,97 @@
+//! Deleting loose objects that can't be reached from any ref, nor from the HEAD or index of any
+//! worktree.
+
+use std::{fs, io, path::Path, time::UNIX_EPOCH};
+
+use crate::{file, fsck, output::OutputWriter, workspace::Repository};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Only report the objects that would be pruned, without deleting them.
+    #[builder(default)]
+    pub dry_run: bool,
+
+    /// Report each object as it is pruned.
+    #[builder(default)]
+    pub verbose: bool,
+
+    /// Only prune objects last modified at or before this Unix timestamp, so that objects that
+    /// are being written, and aren't yet referenced, are kept. All unreachable objects are pruned
+    /// if not given.
+    #[builder(default)]
+    pub expire: Option<u64>,
+}
+
+/// Delete the unreachable loose objects that are older than the expiry time, along with stale
+/// temporary files left in the object directory by writes that never finished.
+pub fn prune(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let database = &repository.database;
+    let roots = fsck::resolve_roots(repository)?
+        .into_iter()
+        .map(|(_, object_id)| object_id)
+        .collect::<Vec<_>>();
+    let reachable = fsck::find_reachable(&roots, |object_id| {
+        match database.load_object(object_id) {
+            Ok((object_type, _)) => Ok(fsck::load_links(object_id, object_type, database)?
+                .into_iter()
+                .map(|(_, object_id)| object_id)
+                .collect()),
+            // a missing object keeps nothing else reachable, which fsck reports
+            Err(crate::Error::Io(error)) if error.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+            Err(error) => Err(error),
+        }
+    })?;
+
+    let objects_dir = repository.objects_dir();
+    for object_id in database.list_objects()? {
+        let object_path = database.object_path(&object_id);
+        if reachable.contains(&object_id) || !is_expired(&object_path, options.expire)? {
+            continue;
+        }
+        if options.dry_run || options.verbose {
+            let object_type = database
+                .load_object(&object_id)
+                .map(|(object_type, _)| object_type.to_string())
+                .unwrap_or_else(|_| String::from("unknown"));
+            writer.writeln(format!("{} {}", object_id, object_type))?;
+        }
+        if !options.dry_run {
+            fs::remove_file(&object_path)?;
+            file::remove_empty_parents(&object_path, &objects_dir)?;
+        }
+    }
+
+    for file in objects_dir.read_dir()? {
+        let path = file?.path();
+        let is_temporary = path
+            .file_name()
+            .and_then(|filename| filename.to_str())
+            .is_some_and(|filename| filename.starts_with("tmp_"));
+        if !is_temporary || !path.is_file() || !is_expired(&path, options.expire)? {
+            continue;
+        }
+        if options.dry_run || options.verbose {
+            writer.writeln(format!("Removing stale temporary file {}", path.display()))?;
+        }
+        if !options.dry_run {
+            fs::remove_file(&path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn is_expired(path: &Path, expire: Option<u64>) -> io::Result<bool> {
+    let Some(expire) = expire else {
+        return Ok(true);
+    };
+    let modified = fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(modified.as_secs() <= expire)
+}
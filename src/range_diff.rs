@@ -0,0 +1,384 @@
+//! Comparing two versions of a series of commits, such as a branch before and after a rebase,
+//! by pairing up the commits of the two versions and showing how each pair differs.
+
+use std::slice;
+
+use crate::diff::{self, EditKind};
+use crate::log;
+use crate::objects::{Commit, GitObject, ObjectId};
+use crate::output::{Color, OutputWriter, WriteAdapter};
+use crate::patch_id;
+use crate::refs::RevisionRange;
+use crate::rev_walk::RevWalk;
+use crate::workspace::Repository;
+
+/// The percentage of the size of a patch that it costs to treat the patch as dropped from one
+/// version and added to the other, rather than as modified between them, as in Git.
+const CREATION_FACTOR: usize = 60;
+
+/// A commit of one of the ranges, along with its changes in a form that can be compared between
+/// versions of the commit.
+struct Patch {
+    commit: Commit,
+    patch_id: Option<ObjectId>,
+    /// The commit message followed by the changes of each file, without blob ids or line numbers.
+    text: String,
+    /// Where the changes start in the text, after the commit message.
+    changes_offset: usize,
+}
+
+impl Patch {
+    fn load(commit: Commit, repository: &Repository) -> crate::Result<Patch> {
+        let mut text = String::from(" ## Commit message ##\n");
+        for line in commit.message.trim_end().lines() {
+            text.push_str(format!("    {}", line).trim_end());
+            text.push('\n');
+        }
+        text.push('\n');
+        let changes_offset = text.len();
+
+        let file_diffs = diff::diff_commits(commit.parent(), commit.id(), repository)?;
+        for file_diff in &file_diffs {
+            let mut buffer = WriteAdapter::new(Vec::new());
+            diff::write_patch(
+                slice::from_ref(file_diff),
+                diff::DEFAULT_CONTEXT_LINES,
+                diff::Algorithm::default(),
+                &mut buffer,
+            )?;
+            text.push_str(&format!(" ## {} ##\n", file_diff.path().display()));
+            // the header lines before the first hunk only repeat the path and the blob ids
+            let patch = String::from_utf8_lossy(buffer.get_ref()).into_owned();
+            for line in patch.lines().skip_while(|line| !line.starts_with("@@ ")) {
+                text.push_str(if line.starts_with("@@ ") { "@@" } else { line });
+                text.push('\n');
+            }
+        }
+
+        Ok(Patch {
+            patch_id: patch_id::patch_id(&commit, repository)?,
+            commit,
+            text,
+            changes_offset,
+        })
+    }
+
+    fn changes(&self) -> &str {
+        &self.text[self.changes_offset..]
+    }
+
+    /// The cost of treating the patch as dropped or added, rather than as modified.
+    fn creation_cost(&self) -> usize {
+        self.changes().lines().count() * CREATION_FACTOR / 100
+    }
+
+    fn subject(&self) -> &str {
+        log::split_message(&self.commit.message).0
+    }
+}
+
+/// Pair up the commits of the two revision ranges, oldest first, and write each pair. Commits
+/// with the same patch id are paired first, and the rest are paired so that the pairs differ as
+/// little as possible in total, while commits that differ too much from all others are shown as
+/// dropped from the old range or added in the new one. Merge commits are left out.
+///
+/// Each pair is marked with `=` if the commits have the same message and changes, and with `!`
+/// followed by a diff of the two patches if they don't. Dropped commits are marked with `<`, and
+/// added commits with `>`.
+pub fn range_diff(
+    old_range: &str,
+    new_range: &str,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let old_patches = load_patches(old_range, repository)?;
+    let new_patches = load_patches(new_range, repository)?;
+    let (old_to_new, new_to_old) = match_patches(&old_patches, &new_patches);
+
+    let number_width = (old_patches.len().max(new_patches.len()) + 1)
+        .to_string()
+        .len();
+    let pair_writer = PairWriter {
+        old_patches: &old_patches,
+        new_patches: &new_patches,
+        number_width,
+    };
+
+    // each pair is shown in the order of the new range, with dropped commits shown as soon as the
+    // commits before them in the old range are shown
+    let mut old_shown = vec![false; old_patches.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old_patches.len() || j < new_patches.len() {
+        while i < old_patches.len() && old_shown[i] {
+            i += 1;
+        }
+        if i < old_patches.len() && old_to_new[i].is_none() {
+            pair_writer.write(Some(i), None, writer)?;
+            i += 1;
+            continue;
+        }
+        while j < new_patches.len() && new_to_old[j].is_none() {
+            pair_writer.write(None, Some(j), writer)?;
+            j += 1;
+        }
+        if let Some(old_index) = new_to_old.get(j).copied().flatten() {
+            pair_writer.write(Some(old_index), Some(j), writer)?;
+            old_shown[old_index] = true;
+            j += 1;
+        }
+    }
+
+    Ok(())
+}
+
+fn load_patches(range: &str, repository: &Repository) -> crate::Result<Vec<Patch>> {
+    let range = match RevisionRange::parse(range)? {
+        range @ RevisionRange::Range(..) => range,
+        _ => {
+            let message = format!("not a commit range: '{}'", range);
+            return Err(crate::Error::Fatal(None, message));
+        }
+    };
+    let mut commits =
+        RevWalk::from_range(&range, repository)?.collect::<crate::Result<Vec<_>>>()?;
+    commits.retain(|commit| commit.parents.len() <= 1);
+    commits.reverse();
+    commits
+        .into_iter()
+        .map(|commit| Patch::load(commit, repository))
+        .collect()
+}
+
+/// Pair up the old and new patches, and return the index of the paired patch of each old patch
+/// and of each new patch.
+fn match_patches(
+    old_patches: &[Patch],
+    new_patches: &[Patch],
+) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+    let mut old_to_new = vec![None; old_patches.len()];
+    let mut new_to_old = vec![None; new_patches.len()];
+    for (j, new_patch) in new_patches.iter().enumerate() {
+        let Some(patch_id) = &new_patch.patch_id else {
+            continue;
+        };
+        let exact_match = (0..old_patches.len()).find(|&i| {
+            old_to_new[i].is_none() && old_patches[i].patch_id.as_ref() == Some(patch_id)
+        });
+        if let Some(i) = exact_match {
+            old_to_new[i] = Some(j);
+            new_to_old[j] = Some(i);
+        }
+    }
+
+    let unmatched_old = (0..old_patches.len())
+        .filter(|&i| old_to_new[i].is_none())
+        .collect::<Vec<_>>();
+    let unmatched_new = (0..new_patches.len())
+        .filter(|&j| new_to_old[j].is_none())
+        .collect::<Vec<_>>();
+
+    // pairing an old patch with a new one costs the size of their difference, while leaving one
+    // unpaired costs its creation cost, by pairing it with one of the extra rows or columns
+    let size = unmatched_old.len() + unmatched_new.len();
+    let mut cost = vec![vec![0; size]; size];
+    for (row, &i) in cost.iter_mut().zip(&unmatched_old) {
+        let (paired, unpaired) = row.split_at_mut(unmatched_new.len());
+        for (cell, &j) in paired.iter_mut().zip(&unmatched_new) {
+            *cell = diff_size(old_patches[i].changes(), new_patches[j].changes());
+        }
+        unpaired.fill(old_patches[i].creation_cost());
+    }
+    for row in cost.iter_mut().skip(unmatched_old.len()) {
+        for (cell, &j) in row.iter_mut().zip(&unmatched_new) {
+            *cell = new_patches[j].creation_cost();
+        }
+    }
+
+    let assignment = compute_assignment(&cost);
+    for (row, &i) in unmatched_old.iter().enumerate() {
+        if let Some(&j) = unmatched_new.get(assignment[row]) {
+            old_to_new[i] = Some(j);
+            new_to_old[j] = Some(i);
+        }
+    }
+    (old_to_new, new_to_old)
+}
+
+/// Count the lines that differ between the two texts.
+fn diff_size(a: &str, b: &str) -> usize {
+    let a_lines = a.lines().collect::<Vec<_>>();
+    let b_lines = b.lines().collect::<Vec<_>>();
+    diff::edit_script(&a_lines, &b_lines)
+        .iter()
+        .filter(|edit| !matches!(edit.kind(), EditKind::Equal))
+        .count()
+}
+
+/// Assign a column to each row of the square cost matrix so that the total cost is minimal, with
+/// the Hungarian algorithm. Returns the column of each row.
+fn compute_assignment(cost: &[Vec<usize>]) -> Vec<usize> {
+    let size = cost.len();
+    // the potentials of the rows and columns, and the row assigned to each column, all offset by
+    // one so that index 0 is a free column to start from
+    let mut row_potentials = vec![0i64; size + 1];
+    let mut column_potentials = vec![0i64; size + 1];
+    let mut column_to_row = vec![0; size + 1];
+    let mut previous_column = vec![0; size + 1];
+
+    for row in 1..=size {
+        column_to_row[0] = row;
+        let mut column = 0;
+        let mut min_slack = vec![i64::MAX; size + 1];
+        let mut used = vec![false; size + 1];
+        loop {
+            used[column] = true;
+            let current_row = column_to_row[column];
+            let mut delta = i64::MAX;
+            let mut next_column = 0;
+            for j in 1..=size {
+                if used[j] {
+                    continue;
+                }
+                let slack = cost[current_row - 1][j - 1] as i64
+                    - row_potentials[current_row]
+                    - column_potentials[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    previous_column[j] = column;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    next_column = j;
+                }
+            }
+            for j in 0..=size {
+                if used[j] {
+                    row_potentials[column_to_row[j]] += delta;
+                    column_potentials[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            column = next_column;
+            if column_to_row[column] == 0 {
+                break;
+            }
+        }
+        // flip the assignments along the augmenting path
+        while column != 0 {
+            let previous = previous_column[column];
+            column_to_row[column] = column_to_row[previous];
+            column = previous;
+        }
+    }
+
+    let mut assignment = vec![0; size];
+    for column in 1..=size {
+        assignment[column_to_row[column] - 1] = column - 1;
+    }
+    assignment
+}
+
+struct PairWriter<'a> {
+    old_patches: &'a [Patch],
+    new_patches: &'a [Patch],
+    number_width: usize,
+}
+
+impl PairWriter<'_> {
+    /// Write a line with the number and abbreviated id of the old and new commit of the pair,
+    /// followed by the differences between them if both are present and differ.
+    fn write(
+        &self,
+        old: Option<usize>,
+        new: Option<usize>,
+        writer: &mut dyn OutputWriter,
+    ) -> crate::Result<()> {
+        let old_patch = old.map(|i| &self.old_patches[i]);
+        let new_patch = new.map(|j| &self.new_patches[j]);
+        let (marker, color) = match (old_patch, new_patch) {
+            (Some(old_patch), Some(new_patch)) if old_patch.text == new_patch.text => ('=', None),
+            (Some(_), Some(_)) => ('!', Some(Color::Brown)),
+            (Some(_), None) => ('<', Some(Color::Red)),
+            _ => ('>', Some(Color::Green)),
+        };
+        let subject = new_patch
+            .or(old_patch)
+            .map(Patch::subject)
+            .unwrap_or_default();
+        let line = format!(
+            "{} {} {} {}",
+            self.format_side(old, old_patch, new_patch),
+            marker,
+            self.format_side(new, new_patch, old_patch),
+            subject
+        );
+        match color {
+            Some(color) => writer
+                .set_color(color)?
+                .write(line)?
+                .reset_formatting()?
+                .linefeed()?,
+            None => writer.writeln(line)?,
+        };
+
+        if let (Some(old_patch), Some(new_patch), '!') = (old_patch, new_patch, marker) {
+            write_patch_diff(old_patch, new_patch, writer)?;
+        }
+        Ok(())
+    }
+
+    fn format_side(
+        &self,
+        index: Option<usize>,
+        patch: Option<&Patch>,
+        other_patch: Option<&Patch>,
+    ) -> String {
+        let width = self.number_width;
+        match (index, patch) {
+            (Some(index), Some(patch)) => format!(
+                "{:>width$}:  {}",
+                index + 1,
+                patch.commit.short_id_as_string()
+            ),
+            _ => {
+                let id_length = other_patch
+                    .map(|patch| patch.commit.short_id_as_string().len())
+                    .unwrap_or_default();
+                format!("{:>width$}:  {}", "-", "-".repeat(id_length))
+            }
+        }
+    }
+}
+
+/// Write a diff of the two patches, indented to set it apart from the lines of the pairs.
+fn write_patch_diff(
+    old_patch: &Patch,
+    new_patch: &Patch,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let old_lines = old_patch.text.lines().collect::<Vec<_>>();
+    let new_lines = new_patch.text.lines().collect::<Vec<_>>();
+    let edit_script = diff::edit_script(&old_lines, &new_lines);
+    let mut buffer = WriteAdapter::new(Vec::new());
+    for chunk in diff::chunk_edit_script(&edit_script, diff::DEFAULT_CONTEXT_LINES) {
+        diff::write_chunk(&chunk, &mut buffer)?;
+    }
+    for line in String::from_utf8_lossy(buffer.get_ref()).lines() {
+        writer.writeln(format!("    {}", line))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_assignment_minimizes_total_cost() {
+        // the cheapest choice for the first row leaves only expensive choices for the others
+        let cost = vec![vec![1, 2, 9], vec![1, 9, 9], vec![9, 9, 5]];
+
+        assert_eq!(compute_assignment(&cost), [1, 0, 2]);
+    }
+}
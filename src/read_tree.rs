@@ -0,0 +1,42 @@
+//! Reading a tree into the index, replacing its entries, without touching the worktree.
+
+use crate::index::{FileMode, IndexEntry};
+use crate::refs::Revision;
+use crate::workspace::Repository;
+
+/// Replace the entries of the index with the files of the tree-ish. The worktree is left as is,
+/// so files that differ from the tree show up as unstaged changes.
+pub fn read_tree(tree_ish: &str, repository: &Repository) -> crate::Result<()> {
+    let database = &repository.database;
+    let object_id = Revision::parse(tree_ish)?.resolve(repository)?;
+    let tree = database.peel_to_tree(&object_id)?;
+    let mut tree_entries = vec![];
+    database.extract_entries_from_tree(String::new(), &tree, &mut tree_entries)?;
+
+    let mut index = repository.load_index()?;
+    let paths = index
+        .as_mut()
+        .get_all_entries()
+        .into_iter()
+        .map(|entry| entry.path.clone())
+        .collect::<Vec<_>>();
+    for path in paths {
+        index.as_mut().remove(path);
+    }
+
+    for (path, tree_entry) in tree_entries {
+        // the size lets a checked out file that hasn't changed be recognized by its content
+        let file_size = match tree_entry.mode {
+            FileMode::Gitlink => 0,
+            _ => database.load_blob(&tree_entry.object_id)?.content().len() as u32,
+        };
+        index.as_mut().add_entry(IndexEntry::from_object(
+            path,
+            tree_entry.object_id,
+            tree_entry.mode,
+            file_size,
+        ));
+    }
+    index.write()?;
+    Ok(())
+}
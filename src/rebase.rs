@@ -0,0 +1,388 @@
+//! Rebasing the current branch, which replays the commits of the branch on top of another commit.
+//!
+//! The state of a rebase in progress is kept in `.git/rebase-merge`, so that it can be continued
+//! after resolving conflicts, or aborted:
+//!
+//! * `head-name`: the full name of the branch being rebased, or `detached HEAD`
+//! * `onto`: the commit that the commits are replayed on top of
+//! * `orig-head`: the commit that HEAD pointed to before the rebase
+//! * `git-rebase-todo`: the commits left to replay, as `pick <id> <subject>` lines
+//! * `stopped-sha`: the commit that stopped the rebase with conflicts, if any
+//!
+//! Starting a rebase points `ORIG_HEAD` to the commit that HEAD pointed to, so that the rebase
+//! can be undone after it's done.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::commit;
+use crate::file;
+use crate::index::{Index, IndexEntry};
+use crate::merge;
+use crate::objects::{Commit, GitObject, ObjectId};
+use crate::output::{NoProgress, OutputWriter};
+use crate::reflog;
+use crate::refs::{self, ExpectedValue, RefHandler, Revision};
+use crate::rerere;
+use crate::restore;
+use crate::rev_walk::{self, RevWalk};
+use crate::status;
+use crate::switch;
+use crate::workspace::Repository;
+
+const STATE_DIR: &str = "rebase-merge";
+const DETACHED_HEAD: &str = "detached HEAD";
+
+/// A rebase in progress.
+struct State {
+    dir: PathBuf,
+    head_name: String,
+    onto: ObjectId,
+    orig_head: ObjectId,
+}
+
+impl State {
+    fn read(repository: &Repository) -> crate::Result<Option<State>> {
+        let dir = repository.git_dir().join(STATE_DIR);
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        let read_id = |name: &str| -> crate::Result<ObjectId> {
+            let content = fs::read_to_string(dir.join(name))?;
+            ObjectId::from_sha(content.trim()).map_err(|reason| crate::Error::Fatal(None, reason))
+        };
+        Ok(Some(State {
+            head_name: fs::read_to_string(dir.join("head-name"))?.trim().to_owned(),
+            onto: read_id("onto")?,
+            orig_head: read_id("orig-head")?,
+            dir,
+        }))
+    }
+
+    fn write(&self, todo: &[Commit]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join("head-name"), format!("{}\n", self.head_name))?;
+        fs::write(self.dir.join("onto"), format!("{}\n", self.onto))?;
+        fs::write(self.dir.join("orig-head"), format!("{}\n", self.orig_head))?;
+        self.write_todo(todo)
+    }
+
+    fn read_todo(&self, repository: &Repository) -> crate::Result<Vec<Commit>> {
+        let content = fs::read_to_string(self.dir.join("git-rebase-todo"))?;
+        let mut todo = vec![];
+        for line in content.lines() {
+            let Some(commit_id) = line
+                .strip_prefix("pick ")
+                .and_then(|rest| rest.split(' ').next())
+            else {
+                continue;
+            };
+            let commit_id = ObjectId::from_sha(commit_id)
+                .map_err(|reason| crate::Error::Fatal(None, reason))?;
+            todo.push(repository.database.load_commit(&commit_id)?);
+        }
+        Ok(todo)
+    }
+
+    fn write_todo(&self, todo: &[Commit]) -> io::Result<()> {
+        let content = todo
+            .iter()
+            .map(|commit| format!("pick {} {}\n", commit.id(), subject(commit)))
+            .collect::<String>();
+        fs::write(self.dir.join("git-rebase-todo"), content)
+    }
+
+    fn stopped_sha_path(&self) -> PathBuf {
+        self.dir.join("stopped-sha")
+    }
+}
+
+/// Replay the commits of the current branch that aren't reachable from the upstream on top of
+/// it, oldest first, and point the branch to the result. Merge commits are left out. Each commit
+/// keeps its author, date and message, while commits whose changes are already in the upstream
+/// are dropped.
+///
+/// If a commit can't be applied without conflicts, the rebase stops with the conflicts in the
+/// index and the worktree, to be resolved and continued with [`continue_rebase`].
+pub fn rebase(
+    upstream: &str,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if State::read(repository)?.is_some() {
+        let message = "It seems that there is already a rebase in progress.\n\
+                       Use \"rut rebase --continue\" or \"rut rebase --abort\" to finish it.";
+        return Err(crate::Error::Fatal(None, message.to_owned()));
+    }
+    let refs = RefHandler::new(repository);
+    let upstream_id = Revision::parse(upstream)?.resolve(repository)?;
+    let head_id = refs.head()?;
+    let head_name = match refs.current_branch()? {
+        Some(branch) => format!("refs/heads/{}", branch),
+        None => DETACHED_HEAD.to_owned(),
+    };
+
+    let mut index = repository.load_index()?;
+    ensure_clean(repository, index.as_mut())?;
+    if rev_walk::merge_base(repository, &head_id, &upstream_id)? == Some(upstream_id.clone()) {
+        let name = refs::shorten_ref_name(&head_name);
+        writer.writeln(format!("Current branch {} is up to date.", name))?;
+        return Ok(());
+    }
+    let mut todo = RevWalk::new(
+        std::slice::from_ref(&head_id),
+        std::slice::from_ref(&upstream_id),
+        repository,
+    )?
+    .collect::<crate::Result<Vec<_>>>()?;
+    todo.retain(|commit| commit.parents.len() <= 1);
+    todo.reverse();
+
+    switch::checkout_commit(&upstream_id, repository, index.as_mut(), &mut NoProgress)?;
+    index.write()?;
+    drop(index);
+
+    let state = State {
+        dir: repository.git_dir().join(STATE_DIR),
+        head_name,
+        onto: upstream_id.clone(),
+        orig_head: head_id.clone(),
+    };
+    state.write(&todo)?;
+    refs.transaction()
+        .update("ORIG_HEAD", &head_id, ExpectedValue::Any)
+        .commit()?;
+    refs.detach_head(&upstream_id)?;
+    let message = format!("rebase (start): checkout {}", upstream);
+    reflog::append_head_entry(Some(&head_id), &upstream_id, &message, repository)?;
+
+    run(&state, repository, writer)
+}
+
+/// Continue the rebase in progress after the conflicts of the commit that stopped it have been
+/// resolved and staged. The resolved commit is committed, and the rest of the commits replayed.
+pub fn continue_rebase(
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let state = read_state(repository)?;
+    let stopped_sha_path = state.stopped_sha_path();
+    if stopped_sha_path.exists() {
+        rerere::record_resolutions(repository, writer)?;
+        let mut index = repository.load_index()?;
+        if index.as_mut().has_unmerged_entries() {
+            let message = "You must edit all merge conflicts and then\n\
+                           mark them as resolved using rut add";
+            return Err(crate::Error::Fatal(None, message.to_owned()));
+        }
+        let commit_id = ObjectId::from_sha(fs::read_to_string(&stopped_sha_path)?.trim())
+            .map_err(|reason| crate::Error::Fatal(None, reason))?;
+        let commit = repository.database.load_commit(&commit_id)?;
+        commit_picked(&commit, repository, index.as_mut())?;
+        index.write()?;
+        fs::remove_file(&stopped_sha_path)?;
+        merge::clear_merge_state(repository)?;
+    }
+
+    run(&state, repository, writer)
+}
+
+/// Abort the rebase in progress, and return to the branch or commit that was rebased, discarding
+/// the commits replayed so far along with any changes to tracked files.
+pub fn abort(repository: &Repository) -> crate::Result<()> {
+    let state = read_state(repository)?;
+    let refs = RefHandler::new(repository);
+    let head_id = refs.read_ref("HEAD")?;
+
+    let mut index = repository.load_index()?;
+    reset_hard(&state.orig_head, repository, index.as_mut())?;
+    index.write()?;
+    merge::clear_merge_state(repository)?;
+    rerere::clear(repository)?;
+    match state.head_name.strip_prefix("refs/heads/") {
+        Some(branch) => refs.attach_head(branch)?,
+        None => refs.detach_head(&state.orig_head)?,
+    }
+    let message = format!("rebase (abort): returning to {}", state.head_name);
+    reflog::append_head_entry(head_id.as_ref(), &state.orig_head, &message, repository)?;
+
+    Ok(fs::remove_dir_all(&state.dir)?)
+}
+
+fn read_state(repository: &Repository) -> crate::Result<State> {
+    State::read(repository)?
+        .ok_or_else(|| crate::Error::Fatal(None, String::from("No rebase in progress?")))
+}
+
+fn ensure_clean(repository: &Repository, index: &mut Index) -> crate::Result<()> {
+    let reason = if !status::resolve_files_with_unstaged_changes(repository, index).is_empty() {
+        "You have unstaged changes."
+    } else if !status::resolve_files_with_staged_changes(
+        &status::resolve_committed_paths_and_ids(repository)?,
+        repository,
+        index,
+    )?
+    .is_empty()
+    {
+        "Your index contains uncommitted changes."
+    } else {
+        return Ok(());
+    };
+    let message = format!("cannot rebase: {}\nPlease commit or stash them.", reason);
+    Err(crate::Error::Fatal(None, message))
+}
+
+/// Replay the commits left to do, and finish the rebase once they are all done.
+fn run(state: &State, repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let mut todo = state.read_todo(repository)?;
+    while !todo.is_empty() {
+        let commit = todo.remove(0);
+        state.write_todo(&todo)?;
+        pick(&commit, state, repository, writer)?;
+    }
+
+    let refs = RefHandler::new(repository);
+    let head_id = refs.head()?;
+    if let Some(branch) = state.head_name.strip_prefix("refs/heads/") {
+        refs.transaction()
+            .update(
+                &state.head_name,
+                &head_id,
+                ExpectedValue::Object(state.orig_head.clone()),
+            )
+            .commit()?;
+        refs.attach_head(branch)?;
+    }
+    let message = format!("rebase (finish): returning to {}", state.head_name);
+    reflog::append_head_entry(Some(&head_id), &head_id, &message, repository)?;
+    fs::remove_dir_all(&state.dir)?;
+
+    writer.writeln(format!(
+        "Successfully rebased and updated {}.",
+        state.head_name
+    ))?;
+    Ok(())
+}
+
+/// Apply the changes of the commit on top of HEAD and commit them. Stops the rebase if there are
+/// conflicts.
+fn pick(
+    commit: &Commit,
+    state: &State,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let database = &repository.database;
+    let base_tree = commit
+        .parent()
+        .map(|parent_id| database.peel_to_tree(parent_id))
+        .transpose()?;
+    let head_tree = database.peel_to_tree(&RefHandler::new(repository).head()?)?;
+    let commit_tree = database.peel_to_tree(commit.id())?;
+    let label = format!("{} ({})", commit.short_id_as_string(), subject(commit));
+
+    let mut index = repository.load_index()?;
+    let conflicts = merge::merge_trees(
+        base_tree.as_ref(),
+        &head_tree,
+        &commit_tree,
+        "HEAD",
+        &label,
+        repository,
+        index.as_mut(),
+    )?;
+    if !conflicts.is_empty() {
+        index.write()?;
+        fs::write(state.stopped_sha_path(), format!("{}\n", commit.id()))?;
+        // lets a plain commit of the resolved conflicts reuse the message
+        merge::write_merge_state(&[], &commit.message, repository)?;
+        for path in &conflicts {
+            writer.writeln(format!("CONFLICT: Merge conflict in {}", path.display()))?;
+        }
+        rerere::record_conflicts(&conflicts, repository, writer)?;
+        let message = format!(
+            "could not apply {}\n\
+             Resolve all conflicts manually, mark them as resolved with \"rut add\", then run \
+             \"rut rebase --continue\".\n\
+             To abort and get back to the state before \"rut rebase\", run \"rut rebase --abort\".",
+            label
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    commit_picked(commit, repository, index.as_mut())?;
+    Ok(index.write()?)
+}
+
+/// Commit the index with the author, date and message of the picked commit, unless the index
+/// matches HEAD, as when the changes of the commit are already upstream.
+fn commit_picked(commit: &Commit, repository: &Repository, index: &mut Index) -> crate::Result<()> {
+    let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
+    if status::resolve_files_with_staged_changes(&path_to_committed_id, repository, index)?
+        .is_empty()
+    {
+        return Ok(());
+    }
+    let date = format!("{} {}", commit.timestamp, commit.timezone);
+    commit::commit_as_author(
+        repository,
+        index,
+        &commit.message,
+        commit.author.clone(),
+        &date,
+        "rebase (pick)",
+    )?;
+    Ok(())
+}
+
+/// Make the index and the worktree match the commit, discarding changes to tracked files and
+/// unresolved conflicts.
+fn reset_hard(
+    commit_id: &ObjectId,
+    repository: &Repository,
+    index: &mut Index,
+) -> crate::Result<()> {
+    let tree = repository.database.peel_to_tree(commit_id)?;
+    let mut tree_entries = vec![];
+    repository
+        .database
+        .extract_entries_from_tree(String::new(), &tree, &mut tree_entries)?;
+
+    let tree_paths = tree_entries
+        .iter()
+        .map(|(path, _)| PathBuf::from(path))
+        .collect::<HashSet<_>>();
+
+    let worktree_root = repository.worktree().root();
+    let tracked_paths = index
+        .get_all_entries()
+        .into_iter()
+        .map(|entry| entry.path.clone())
+        .collect::<BTreeSet<_>>();
+    for path in tracked_paths {
+        index.remove(&path);
+        if tree_paths.contains(&path) {
+            continue;
+        }
+        let absolute_path = worktree_root.join(&path);
+        match fs::remove_file(&absolute_path) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+            other => other?,
+        }
+        file::remove_empty_parents(&absolute_path, worktree_root)?;
+    }
+
+    for (path, entry) in tree_entries {
+        let absolute_path = worktree_root.join(&path);
+        restore::write_tree_entry(&absolute_path, &entry, repository)?;
+        let metadata = fs::metadata(&absolute_path)?;
+        index.add_entry(IndexEntry::new(&path, entry.object_id, &metadata));
+    }
+    Ok(())
+}
+
+fn subject(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or_default()
+}
@@ -0,0 +1,84 @@
+//! The reflog of HEAD, which records each commit and checkout that moves HEAD as a line in
+//! `logs/HEAD`: the old and new object ids, who moved it and when, and a message such as
+//! `checkout: moving from main to topic`.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::{commit, objects::ObjectId, workspace::Repository};
+
+/// A move of HEAD from one object to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    /// The object HEAD pointed to before, which is the null id if there was none.
+    pub old_id: ObjectId,
+    pub new_id: ObjectId,
+    pub message: String,
+}
+
+/// Record that HEAD moved from the old object, if any, to the new object.
+pub fn append_head_entry(
+    old_id: Option<&ObjectId>,
+    new_id: &ObjectId,
+    message: &str,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let log_path = repository.git_dir().join("logs").join("HEAD");
+    fs::create_dir_all(log_path.parent().unwrap())?;
+
+    let null_id = ObjectId::null(repository.hash_algorithm());
+    let line = format!(
+        "{} {} {}\t{}\n",
+        old_id.unwrap_or(&null_id),
+        new_id,
+        commit::reflog_identity(&repository.config()?),
+        message.lines().next().unwrap_or_default()
+    );
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)?;
+    Ok(file.write_all(line.as_bytes())?)
+}
+
+/// Read the entries of the reflog of HEAD, oldest first.
+pub fn read_head_entries(repository: &Repository) -> crate::Result<Vec<ReflogEntry>> {
+    let log_path = repository.git_dir().join("logs").join("HEAD");
+    let content = match fs::read_to_string(log_path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+
+    let corrupt = |line: &str| crate::Error::Fatal(None, format!("corrupt reflog entry: {}", line));
+    content
+        .lines()
+        .map(|line| {
+            let (ids_and_identity, message) = line.split_once('\t').unwrap_or((line, ""));
+            let mut words = ids_and_identity.split(' ');
+            let mut next_id = || {
+                words
+                    .next()
+                    .and_then(|id| ObjectId::from_sha(id).ok())
+                    .ok_or_else(|| corrupt(line))
+            };
+            Ok(ReflogEntry {
+                old_id: next_id()?,
+                new_id: next_id()?,
+                message: message.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Find the branch or commit that was checked out before the n:th most recent checkout, as
+/// written by `@{-n}`.
+pub fn find_previous_checkout(n: usize, repository: &Repository) -> crate::Result<Option<String>> {
+    Ok(read_head_entries(repository)?
+        .iter()
+        .rev()
+        .filter_map(|entry| entry.message.strip_prefix("checkout: moving from "))
+        .filter_map(|moved| moved.split_once(" to "))
+        .nth(n.saturating_sub(1))
+        .map(|(from, _)| from.to_owned()))
+}
@@ -6,26 +6,261 @@ use std::str;
 use std::str::FromStr;
 
 use regex::Regex;
+use walkdir::WalkDir;
 
 use crate::file;
-use crate::hex;
-use crate::objects::ObjectId;
+use crate::file::LockFile;
+use crate::objects::{GitObject, ObjectId, ObjectType};
+use crate::reflog;
+use crate::trace;
 use crate::workspace::Repository;
 
 pub struct RefHandler<'a> {
     repository: &'a Repository,
 }
 
-const SHA1_SIZE: usize = 40;
-
 const INVALID_BRANCH_NAME_PATTERN: &str =
     r"^\.|\/\.|\.\.|^\/|\/$|\.lock$|@\{|[\x00-\x20*:?\[\\^~\x7F]";
 
+/// Check if the name follows the rules for branch names, such as not containing `..` or spaces.
+pub fn is_valid_branch_name(name: &str) -> bool {
+    !Regex::new(INVALID_BRANCH_NAME_PATTERN)
+        .unwrap()
+        .is_match(name)
+}
+
+/// Check if the full name of a ref, such as `refs/heads/main` or `HEAD`, is valid. Refs outside
+/// of `refs/` are named in uppercase, like `HEAD` and `ORIG_HEAD`.
+pub fn is_valid_ref_name(full_name: &str) -> bool {
+    match full_name.strip_prefix("refs/") {
+        Some(name) => is_valid_branch_name(name),
+        None => {
+            !full_name.is_empty()
+                && full_name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c == '_')
+        }
+    }
+}
+
+/// Shorten the full name of a ref by removing the `refs/heads/`, `refs/tags/` or `refs/remotes/`
+/// prefix, or only `refs/` for other refs.
+///
+/// # Examples
+/// ```
+/// use rut::refs;
+///
+/// assert_eq!(refs::shorten_ref_name("refs/heads/main"), "main");
+/// assert_eq!(refs::shorten_ref_name("refs/remotes/origin/main"), "origin/main");
+/// assert_eq!(refs::shorten_ref_name("refs/notes/commits"), "notes/commits");
+/// assert_eq!(refs::shorten_ref_name("HEAD"), "HEAD");
+/// ```
+pub fn shorten_ref_name(full_name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| full_name.strip_prefix(prefix))
+        .unwrap_or(full_name)
+}
+
+/// The value that a ref must have for an update of it in a [`RefTransaction`] to go through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectedValue {
+    /// The ref is updated regardless of its value.
+    Any,
+    /// The ref must not exist.
+    Missing,
+    /// The ref must point to the object.
+    Object(ObjectId),
+}
+
+impl From<Option<ObjectId>> for ExpectedValue {
+    /// The ref must have the given value, or not exist if there is none.
+    fn from(object_id: Option<ObjectId>) -> ExpectedValue {
+        object_id.map_or(ExpectedValue::Missing, ExpectedValue::Object)
+    }
+}
+
+struct RefUpdate {
+    name: String,
+    /// The object to point the ref to, or None to delete it.
+    new_value: Option<ObjectId>,
+    expected: ExpectedValue,
+}
+
+/// A set of ref updates that are applied together, and only if every ref has its expected value.
+/// Each ref is locked with `<ref>.lock` while its value is verified and written, so that
+/// concurrent updates of the same ref can't silently overwrite each other: whichever comes second
+/// either fails to take the lock or finds that the ref has moved.
+pub struct RefTransaction<'a> {
+    refs: RefHandler<'a>,
+    updates: Vec<RefUpdate>,
+}
+
+impl<'a> RefTransaction<'a> {
+    /// Point the ref, given by its full name, to the object. Symbolic refs are updated through
+    /// the refs they point to.
+    pub fn update(mut self, name: &str, object_id: &ObjectId, expected: ExpectedValue) -> Self {
+        self.updates.push(RefUpdate {
+            name: name.to_owned(),
+            new_value: Some(object_id.clone()),
+            expected,
+        });
+        self
+    }
+
+    /// Delete the ref, given by its full name, whether it's a loose ref or a packed one.
+    pub fn delete(mut self, name: &str, expected: ExpectedValue) -> Self {
+        self.updates.push(RefUpdate {
+            name: name.to_owned(),
+            new_value: None,
+            expected,
+        });
+        self
+    }
+
+    /// Lock all refs, verify their values and write the new values to the lockfiles before any ref
+    /// is changed, and only then rename the lockfiles to the refs. Nothing is changed if any ref
+    /// is locked by someone else, doesn't have its expected value or can't be written, and all
+    /// lockfiles are removed on failure.
+    pub fn commit(mut self) -> crate::Result<()> {
+        let fatal = |message: String| crate::Error::Fatal(None, message);
+        for update in &mut self.updates {
+            if !is_valid_ref_name(&update.name) {
+                return Err(fatal(format!("invalid ref name '{}'", update.name)));
+            }
+            update.name = self.refs.resolve_symbolic_ref(&update.name)?;
+        }
+        self.updates.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+        if let Some(pair) = self
+            .updates
+            .windows(2)
+            .find(|pair| pair[0].name == pair[1].name)
+        {
+            let message = format!("multiple updates for ref '{}' not allowed", pair[0].name);
+            return Err(fatal(message));
+        }
+
+        let mut lockfiles = vec![];
+        for update in &self.updates {
+            let ref_path = self.refs.ref_path(&update.name);
+            if let Some(parent) = ref_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let lockfile = LockFile::acquire(&ref_path).map_err(|error| match error {
+                crate::Error::Fatal(source, message) => crate::Error::Fatal(
+                    source,
+                    format!("cannot lock ref '{}': {}", update.name, message),
+                ),
+                error => error,
+            })?;
+            lockfiles.push(lockfile);
+
+            let current_value = self.refs.read_ref(&update.name)?;
+            match (&update.expected, current_value) {
+                (ExpectedValue::Missing, Some(_)) => {
+                    let message = format!(
+                        "cannot lock ref '{}': reference already exists",
+                        update.name
+                    );
+                    return Err(fatal(message));
+                }
+                (ExpectedValue::Object(_), None) => {
+                    let message = format!(
+                        "cannot lock ref '{}': unable to resolve reference '{}'",
+                        update.name, update.name
+                    );
+                    return Err(fatal(message));
+                }
+                (ExpectedValue::Object(expected), Some(current)) if *expected != current => {
+                    let message = format!(
+                        "cannot lock ref '{}': is at {} but expected {}",
+                        update.name, current, expected
+                    );
+                    return Err(fatal(message));
+                }
+                _ => (),
+            }
+        }
+
+        // every lockfile is written before any ref changes, so that a failure leaves all refs as
+        // they were
+        let deleted_names = self
+            .updates
+            .iter()
+            .filter(|update| update.new_value.is_none())
+            .map(|update| update.name.as_str())
+            .collect::<Vec<_>>();
+        let prepared = self
+            .updates
+            .iter()
+            .zip(lockfiles.iter_mut())
+            .try_for_each(|(update, lockfile)| match &update.new_value {
+                Some(object_id) => lockfile.write(format!("{}\n", object_id).as_bytes()),
+                None => Ok(()),
+            })
+            .map_err(crate::Error::from)
+            .and_then(|()| self.refs.remove_packed_refs(&deleted_names));
+        if let Err(error) = prepared {
+            rollback_all(lockfiles);
+            return Err(error);
+        }
+
+        let mut lockfiles = lockfiles.into_iter();
+        for (update, lockfile) in self.updates.iter().zip(lockfiles.by_ref()) {
+            let new_value = update.new_value.as_ref().map(ObjectId::to_string);
+            trace::trace(
+                "ref",
+                format_args!(
+                    "update {} to {}",
+                    update.name,
+                    new_value.as_deref().unwrap_or("(deleted)")
+                ),
+            );
+            let applied = match &update.new_value {
+                Some(_) => lockfile.commit(),
+                None => {
+                    let removed = match fs::remove_file(self.refs.ref_path(&update.name)) {
+                        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+                        other => other,
+                    };
+                    let released = lockfile.rollback();
+                    removed.and(released)
+                }
+            };
+            if let Err(error) = applied {
+                rollback_all(lockfiles);
+                return Err(error.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remove the lockfiles without touching their refs. Failures are ignored, as this only happens
+/// when an error is already being reported.
+fn rollback_all(lockfiles: impl IntoIterator<Item = LockFile>) {
+    for lockfile in lockfiles {
+        let _ = lockfile.rollback();
+    }
+}
+
+/// The branch that a local branch tracks.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Upstream {
+    /// The short name of the upstream, such as `origin/main`.
+    pub name: String,
+    /// The commit the upstream points to, or None if it no longer exists.
+    pub object_id: Option<ObjectId>,
+}
+
 const PARENT_PATTERN: &str = r"^(.*)\^$";
 const ANCESTOR_PATTERN: &str = r"^(.*)~(\d+)$";
+const PEEL_PATTERN: &str = r"^(.*)\^\{(\w*)\}$";
+const UPSTREAM_PATTERN: &str = r"^(.*)@\{(?:u|upstream)\}$";
+const PREVIOUS_CHECKOUT_PATTERN: &str = r"^@\{-(\d+)\}$";
 
 impl<'a> RefHandler<'a> {
-    pub fn new(repository: &Repository) -> RefHandler {
+    pub fn new(repository: &Repository) -> RefHandler<'_> {
         RefHandler { repository }
     }
 
@@ -34,32 +269,42 @@ impl<'a> RefHandler<'a> {
         if reference == "HEAD" {
             return self.head();
         }
+        // refs outside of refs/, like ORIG_HEAD, are files in the git directory
+        if !reference.starts_with("refs/")
+            && is_valid_ref_name(reference)
+            && self.repository.git_dir().join(reference).is_file()
+        {
+            if let Some(object_id) = self.read_ref(reference)? {
+                return Ok(object_id);
+            }
+        }
 
         let trimmed_reference = reference.trim().trim_start_matches("refs/heads/");
         let ref_file = self
             .repository
-            .git_dir()
+            .common_dir()
             .join("refs/heads/")
             .join(trimmed_reference);
 
-        let error = Err(crate::Error::Fatal(
-            None,
-            format!(
-                "ambiguous argument '{}': unknown revision or path not in the working tree.",
-                reference
-            ),
-        ));
+        let error = Err(crate::Error::AmbiguousRef(reference.to_owned()));
 
-        let result = if reference.len() == SHA1_SIZE {
+        let packed_ref = format!("refs/heads/{}", trimmed_reference);
+        let result = if reference.len() == self.repository.hash_algorithm().hex_size() {
             reference.to_owned()
         } else if ref_file.is_file() {
             fs::read_to_string(&ref_file).map(|content| content.trim().to_owned())?
+        } else if let Some((_, object_id)) = self
+            .read_packed_refs()?
+            .into_iter()
+            .find(|(name, _)| *name == packed_ref)
+        {
+            return Ok(object_id);
         } else if let Ok(mut oids) = self.repository.database.prefix_match(reference) {
-            if oids.len() == 1 {
-                return Ok(oids.pop().unwrap());
-            } else {
-                return error;
-            }
+            return match oids.len() {
+                0 => error,
+                1 => Ok(oids.pop().unwrap()),
+                _ => Err(crate::Error::AmbiguousObjectId(reference.to_owned(), oids)),
+            };
         } else {
             return error;
         };
@@ -67,33 +312,345 @@ impl<'a> RefHandler<'a> {
         ObjectId::from_sha(&result).map_err(|err| crate::Error::Fatal(None, err))
     }
 
+    /// Start a transaction of ref updates, which are applied when it's committed.
+    pub fn transaction(&self) -> RefTransaction<'a> {
+        RefTransaction {
+            refs: RefHandler::new(self.repository),
+            updates: vec![],
+        }
+    }
+
+    /// Point a branch to the object, regardless of where it pointed before.
     pub fn write_ref(&self, ref_name: &str, object_id: &ObjectId) -> crate::Result<()> {
-        let ref_path = self.get_ref_path(ref_name)?;
-        let hex_string = hex::to_hex_string(object_id.bytes());
-        Ok(file::atomic_write(&ref_path, hex_string.as_bytes())?)
+        let full_name = self.branch_ref_name(ref_name)?;
+        self.transaction()
+            .update(&full_name, object_id, ExpectedValue::Any)
+            .commit()
     }
 
+    /// Create a branch pointing to the object. Fail if the branch already exists.
     pub fn create_ref(&self, ref_name: &str, object_id: &ObjectId) -> crate::Result<()> {
-        let ref_path = self.get_ref_path(ref_name)?;
-        let hex_string = hex::to_hex_string(object_id.bytes());
-        let result = file::create_file(&ref_path, hex_string.as_bytes());
-
-        match result {
-            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
-                let message = format!("a branch named '{}' already exists", ref_name);
-                Err(crate::Error::Fatal(Some(Box::new(error)), message))
-            }
-            other => Ok(other?),
+        let full_name = self.branch_ref_name(ref_name)?;
+        self.ensure_branch_is_missing(ref_name)?;
+        self.transaction()
+            .update(&full_name, object_id, ExpectedValue::Missing)
+            .commit()
+    }
+
+    /// Delete a branch, whether it's a loose ref or only present in the packed-refs file.
+    pub fn delete_ref(&self, ref_name: &str) -> crate::Result<()> {
+        let full_name = self.branch_ref_name(ref_name)?;
+        let object_id = self
+            .read_ref(&full_name)?
+            .ok_or_else(|| crate::Error::Fatal(None, format!("branch '{}' not found", ref_name)))?;
+        self.transaction()
+            .delete(&full_name, ExpectedValue::Object(object_id))
+            .commit()
+    }
+
+    /// Rename a branch, and point HEAD to the new name if the branch is checked out.
+    pub fn rename_ref(&self, old_name: &str, new_name: &str) -> crate::Result<()> {
+        let old_full_name = self.branch_ref_name(old_name)?;
+        let new_full_name = self.branch_ref_name(new_name)?;
+        let object_id = self
+            .find_branch(old_name)?
+            .ok_or_else(|| crate::Error::Fatal(None, format!("no branch named '{}'", old_name)))?;
+
+        self.ensure_branch_is_missing(new_name)?;
+        self.transaction()
+            .update(&new_full_name, &object_id, ExpectedValue::Missing)
+            .delete(&old_full_name, ExpectedValue::Object(object_id.clone()))
+            .commit()?;
+
+        if self.current_branch()?.as_deref() == Some(old_name) {
+            self.attach_head(new_name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Point HEAD to the branch.
+    pub fn attach_head(&self, branch: &str) -> crate::Result<()> {
+        self.write_symbolic_ref("HEAD", &format!("refs/heads/{}", branch))
+    }
+
+    /// Make the ref with the full name a symbolic ref that points to the target ref, holding the
+    /// lock of the ref while writing it.
+    pub fn write_symbolic_ref(&self, full_name: &str, target: &str) -> crate::Result<()> {
+        if let Some(name) = [full_name, target]
+            .into_iter()
+            .find(|name| !is_valid_ref_name(name))
+        {
+            return Err(crate::Error::Fatal(
+                None,
+                format!("invalid ref name '{}'", name),
+            ));
+        }
+        let ref_path = self.ref_path(full_name);
+        if let Some(parent) = ref_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut lockfile = LockFile::acquire(&ref_path)?;
+        trace::trace(
+            "ref",
+            format_args!("update {} to ref: {}", full_name, target),
+        );
+        Ok(lockfile.write(format!("ref: {}", target).as_bytes())?)
+    }
+
+    /// Read the ref that the symbolic ref with the full name points to, or None if it isn't a
+    /// symbolic ref.
+    pub fn read_symbolic_ref(&self, full_name: &str) -> crate::Result<Option<String>> {
+        match fs::read_to_string(self.ref_path(full_name)) {
+            Ok(content) => Ok(content
+                .trim()
+                .strip_prefix("ref: ")
+                .map(|target| target.to_owned())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
         }
     }
 
-    fn get_ref_path(&self, ref_name: &str) -> crate::Result<PathBuf> {
-        let re = Regex::new(INVALID_BRANCH_NAME_PATTERN).unwrap();
-        if re.is_match(ref_name) {
-            let message = format!("'{}' is not a valid branch name", ref_name);
+    /// Point HEAD directly to the object, detaching it from any branch.
+    pub fn detach_head(&self, object_id: &ObjectId) -> crate::Result<()> {
+        let head_path = self.repository.git_dir().join("HEAD");
+        trace::trace("ref", format_args!("update HEAD to {}", object_id));
+        Ok(file::atomic_write(
+            &head_path,
+            object_id.to_string().as_bytes(),
+        )?)
+    }
+
+    /// Advance the current branch to the object, or HEAD itself if it's detached, provided that
+    /// HEAD still has the expected value.
+    pub fn update_head(&self, object_id: &ObjectId, expected: ExpectedValue) -> crate::Result<()> {
+        self.transaction()
+            .update("HEAD", object_id, expected)
+            .commit()
+    }
+
+    /// Find the commit that a local branch points to.
+    pub fn find_branch(&self, branch: &str) -> crate::Result<Option<ObjectId>> {
+        Ok(self
+            .list_refs("refs/heads")?
+            .into_iter()
+            .find(|(name, _)| name == branch)
+            .map(|(_, object_id)| object_id))
+    }
+
+    /// The full name of the ref of a branch, such as `refs/heads/main` for `main`.
+    fn branch_ref_name(&self, branch: &str) -> crate::Result<String> {
+        if !is_valid_branch_name(branch) {
+            let message = format!("'{}' is not a valid branch name", branch);
+            return Err(crate::Error::Fatal(None, message));
+        }
+        Ok(format!("refs/heads/{}", branch))
+    }
+
+    fn ensure_branch_is_missing(&self, branch: &str) -> crate::Result<()> {
+        if self.find_branch(branch)?.is_some() {
+            let message = format!("a branch named '{}' already exists", branch);
             return Err(crate::Error::Fatal(None, message));
         }
-        Ok(self.repository.git_dir().join("refs/heads/").join(ref_name))
+        Ok(())
+    }
+
+    /// The path of the loose ref with the full name. Refs under `refs/` are shared by all
+    /// worktrees, while others like `HEAD` belong to the current worktree.
+    fn ref_path(&self, full_name: &str) -> PathBuf {
+        if full_name.starts_with("refs/") {
+            self.repository.common_dir().join(full_name)
+        } else {
+            self.repository.git_dir().join(full_name)
+        }
+    }
+
+    /// Read the object that the ref with the full name points to, following symbolic refs, or
+    /// None if the ref doesn't exist. Loose refs take precedence over packed refs. Refs that list
+    /// several objects, like `MERGE_HEAD`, point to the first one.
+    pub fn read_ref(&self, full_name: &str) -> crate::Result<Option<ObjectId>> {
+        let full_name = self.resolve_symbolic_ref(full_name)?;
+        match fs::read_to_string(self.ref_path(&full_name)) {
+            Ok(content) => ObjectId::from_sha(content.lines().next().unwrap_or_default().trim())
+                .map(Some)
+                .map_err(|err| crate::Error::Fatal(None, err)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(self
+                .read_packed_refs()?
+                .into_iter()
+                .find(|(name, _)| *name == full_name)
+                .map(|(_, object_id)| object_id)),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Follow the ref with the full name through any symbolic refs, like `HEAD` pointing to
+    /// `refs/heads/main`, to the name of the ref that holds an object id.
+    pub fn resolve_symbolic_ref(&self, full_name: &str) -> crate::Result<String> {
+        const MAX_DEPTH: usize = 5;
+
+        let mut name = full_name.to_owned();
+        for _ in 0..MAX_DEPTH {
+            match fs::read_to_string(self.ref_path(&name)) {
+                Ok(content) => match content.trim().strip_prefix("ref: ") {
+                    Some(target) => name = target.to_owned(),
+                    None => return Ok(name),
+                },
+                Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(name),
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        let message = format!("symbolic ref '{}' points too deep", full_name);
+        Err(crate::Error::Fatal(None, message))
+    }
+
+    /// List all refs under `refs/`, both loose and packed, as pairs of the full ref name and the
+    /// object id it points to, sorted by name.
+    pub fn list_all_refs(&self) -> crate::Result<Vec<(String, ObjectId)>> {
+        Ok(self
+            .list_refs("refs")?
+            .into_iter()
+            .map(|(name, object_id)| (format!("refs/{}", name), object_id))
+            .collect())
+    }
+
+    /// List the refs in a namespace such as `refs/heads`, as pairs of the ref name relative to the
+    /// namespace and the object id it points to, sorted by name. Loose refs take precedence over
+    /// packed refs with the same name. Symbolic refs, such as `refs/remotes/origin/HEAD`, are
+    /// skipped.
+    pub fn list_refs(&self, namespace: &str) -> crate::Result<Vec<(String, ObjectId)>> {
+        let prefix = format!("{}/", namespace.trim_end_matches('/'));
+        let mut refs: Vec<(String, ObjectId)> = self
+            .read_packed_refs()?
+            .into_iter()
+            .filter_map(|(name, object_id)| {
+                name.strip_prefix(&prefix)
+                    .map(|name| (name.to_owned(), object_id))
+            })
+            .collect();
+
+        let namespace_dir = self.repository.common_dir().join(namespace);
+        if !namespace_dir.is_dir() {
+            refs.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+            return Ok(refs);
+        }
+
+        for entry in WalkDir::new(&namespace_dir).sort_by_file_name() {
+            let entry = entry.map_err(io::Error::from)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let name = entry
+                .path()
+                .strip_prefix(&namespace_dir)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            let content = fs::read_to_string(entry.path())?;
+            if content.starts_with("ref: ") {
+                continue;
+            }
+            let object_id =
+                ObjectId::from_sha(content.trim()).map_err(|err| crate::Error::Fatal(None, err))?;
+            refs.retain(|(packed_name, _)| *packed_name != name);
+            refs.push((name, object_id));
+        }
+
+        refs.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+        Ok(refs)
+    }
+
+    /// Remove refs by their full names from the packed-refs file, holding its lock while doing so.
+    fn remove_packed_refs(&self, full_names: &[&str]) -> crate::Result<()> {
+        let packed_refs_path = self.repository.common_dir().join("packed-refs");
+        if full_names.is_empty() || !packed_refs_path.is_file() {
+            return Ok(());
+        }
+
+        let mut lockfile = LockFile::acquire(&packed_refs_path)?;
+        let content = fs::read_to_string(&packed_refs_path)?;
+        let mut kept_lines = vec![];
+        let mut was_packed = false;
+        let mut is_removing_peeled_line = false;
+        for line in content.lines() {
+            if is_removing_peeled_line && line.starts_with('^') {
+                continue;
+            }
+            is_removing_peeled_line = line
+                .split_once(' ')
+                .is_some_and(|(_, name)| full_names.contains(&name));
+            if is_removing_peeled_line {
+                was_packed = true;
+            } else {
+                kept_lines.push(format!("{}\n", line));
+            }
+        }
+
+        if was_packed {
+            lockfile.write(kept_lines.concat().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read the full names and object ids of the refs in the `packed-refs` file, if it exists.
+    fn read_packed_refs(&self) -> crate::Result<Vec<(String, ObjectId)>> {
+        let packed_refs_path = self.repository.common_dir().join("packed-refs");
+        if !packed_refs_path.is_file() {
+            return Ok(vec![]);
+        }
+
+        let mut refs = vec![];
+        for line in fs::read_to_string(packed_refs_path)?.lines() {
+            // comments hold the file's traits, and lines starting with ^ are peeled tags
+            if line.starts_with('#') || line.starts_with('^') {
+                continue;
+            }
+            if let Some((sha, name)) = line.split_once(' ') {
+                let object_id =
+                    ObjectId::from_sha(sha).map_err(|err| crate::Error::Fatal(None, err))?;
+                refs.push((name.to_owned(), object_id));
+            }
+        }
+
+        Ok(refs)
+    }
+
+    /// The name of the branch that HEAD points to, or None if HEAD is detached.
+    pub fn current_branch(&self) -> crate::Result<Option<String>> {
+        let head_content = fs::read_to_string(self.repository.git_dir().join("HEAD"))?;
+        Ok(head_content
+            .trim()
+            .strip_prefix("ref: refs/heads/")
+            .map(|branch| branch.to_owned()))
+    }
+
+    /// Resolve the upstream of the local branch from the `branch.<name>.remote` and
+    /// `branch.<name>.merge` config, where the remote `.` means that the upstream is another local
+    /// branch. The upstream of a remote branch is its remote-tracking branch under
+    /// `refs/remotes/<remote>`.
+    pub fn upstream(&self, branch: &str) -> crate::Result<Option<Upstream>> {
+        let config = self.repository.config()?;
+        let remote = config.get_string(&format!("branch.{}.remote", branch));
+        let merge = config.get_string(&format!("branch.{}.merge", branch));
+        let (Some(remote), Some(merge)) = (remote, merge) else {
+            return Ok(None);
+        };
+        let merged_branch = merge.trim_start_matches("refs/heads/");
+
+        let (namespace, name) = if remote == "." {
+            (String::from("refs/heads"), merged_branch.to_owned())
+        } else {
+            let namespace = format!("refs/remotes/{}", remote);
+            (namespace, format!("{}/{}", remote, merged_branch))
+        };
+        let object_id = self
+            .list_refs(&namespace)?
+            .into_iter()
+            .find(|(ref_name, _)| ref_name == merged_branch)
+            .map(|(_, object_id)| object_id);
+
+        Ok(Some(Upstream { name, object_id }))
     }
 
     /// Convenience method to get the object id of the current HEAD.
@@ -111,7 +668,7 @@ pub enum ParseRevisionError {
 
 impl From<ParseRevisionError> for std::io::Error {
     fn from(error: ParseRevisionError) -> Self {
-        io::Error::new(io::ErrorKind::Other, error.to_string())
+        io::Error::other(error.to_string())
     }
 }
 
@@ -132,6 +689,16 @@ pub enum Revision {
     Reference(String),
     Parent(Box<Revision>),
     Ancestor(Box<Revision>, u32),
+    /// The object that the revision peels to, such as the tree of a commit with `^{tree}`. The
+    /// revision itself with `^{}` or `^{object}`, when there is no type.
+    Peel(Box<Revision>, Option<ObjectType>),
+    /// The upstream of the branch, or of the current branch if there is none (`@{u}`).
+    Upstream(Option<String>),
+    /// The branch or commit that was checked out before the n:th most recent checkout (`@{-n}`).
+    PreviousCheckout(usize),
+    /// The object at the path in the tree of the revision (`HEAD:src/lib.rs`), or in the index if
+    /// there is no revision (`:src/lib.rs`).
+    Path(Option<Box<Revision>>, PathBuf),
 }
 
 impl Revision {
@@ -157,15 +724,57 @@ impl Revision {
     ///   ancestor_revision,
     ///   Revision::Ancestor(Box::new(Revision::Reference("HEAD".to_owned())), 3)
     /// );
+    ///
+    /// let path_revision = Revision::parse("main:src/lib.rs").unwrap();
+    /// assert_eq!(
+    ///   path_revision,
+    ///   Revision::Path(
+    ///     Some(Box::new(Revision::Reference("main".to_owned()))),
+    ///     "src/lib.rs".into()
+    ///   )
+    /// );
+    ///
+    /// let upstream_revision = Revision::parse("@{u}").unwrap();
+    /// assert_eq!(upstream_revision, Revision::Upstream(None));
     /// ```
     ///
     pub fn parse(s: &str) -> Result<Revision, ParseRevisionError> {
         let invalid_regex = Regex::new(INVALID_BRANCH_NAME_PATTERN).unwrap();
         let parent_regex = Regex::new(PARENT_PATTERN).unwrap();
         let ancestor_regex = Regex::new(ANCESTOR_PATTERN).unwrap();
+        let peel_regex = Regex::new(PEEL_PATTERN).unwrap();
+        let upstream_regex = Regex::new(UPSTREAM_PATTERN).unwrap();
+        let previous_checkout_regex = Regex::new(PREVIOUS_CHECKOUT_PATTERN).unwrap();
         let err = ParseRevisionError::InvalidFormat(s.to_owned());
 
-        if let Some(group) = parent_regex.captures(s).and_then(|g| g.get(1)) {
+        // the path may contain anything, so it's split off before looking for other syntax
+        if let Some((revision, path)) = s.split_once(':') {
+            let revision = match revision {
+                "" => None,
+                revision => Some(Box::new(Revision::parse(revision)?)),
+            };
+            Ok(Revision::Path(revision, PathBuf::from(path)))
+        } else if let Some(matches) = peel_regex.captures(s) {
+            let nested_rev = Revision::parse(matches.get(1).unwrap().as_str())?;
+            let object_type = match matches.get(2).unwrap().as_str() {
+                "" | "object" => None,
+                object_type => Some(ObjectType::parse(object_type).ok_or(err)?),
+            };
+            Ok(Revision::Peel(Box::new(nested_rev), object_type))
+        } else if let Some(matches) = upstream_regex.captures(s) {
+            let branch = matches.get(1).unwrap().as_str();
+            let branch = Some(branch)
+                .filter(|branch| !branch.is_empty())
+                .map(str::to_owned);
+            Ok(Revision::Upstream(branch))
+        } else if let Some(matches) = previous_checkout_regex.captures(s) {
+            match matches.get(1).unwrap().as_str().parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Revision::PreviousCheckout(n)),
+                _ => Err(err),
+            }
+        } else if s == "@" {
+            Ok(Revision::Reference(String::from("HEAD")))
+        } else if let Some(group) = parent_regex.captures(s).and_then(|g| g.get(1)) {
             let nested_rev = Revision::parse(group.as_str())?;
             Ok(Revision::Parent(Box::new(nested_rev)))
         } else if let Some(matches) = ancestor_regex.captures(s) {
@@ -199,20 +808,151 @@ impl Revision {
             Revision::Parent(revision) => {
                 let oid = revision.resolve(repository)?;
                 let commit = repository.database.load_commit(&oid)?;
-                commit.parent.ok_or_else(|| err(revision))
+                commit.parent().cloned().ok_or_else(|| err(revision))
             }
             Revision::Ancestor(revision, count) => {
                 let oid = revision.resolve(repository)?;
                 let commit = repository.database.load_commit(&oid)?;
-                let mut parent_oid = commit.parent.ok_or_else(|| err(revision))?;
+                let mut parent_oid = commit.parent().cloned().ok_or_else(|| err(revision))?;
 
                 for _ in 1..*count {
                     let parent_commit = repository.database.load_commit(&parent_oid)?;
-                    parent_oid = parent_commit.parent.ok_or_else(|| err(revision))?;
+                    parent_oid = parent_commit
+                        .parent()
+                        .cloned()
+                        .ok_or_else(|| err(revision))?;
                 }
 
                 Ok(parent_oid)
             }
+            Revision::Peel(revision, object_type) => {
+                let oid = revision.resolve(repository)?;
+                match object_type {
+                    None => Ok(oid),
+                    Some(ObjectType::Tree) => {
+                        Ok(repository.database.peel_to_tree(&oid)?.id().clone())
+                    }
+                    Some(object_type) => {
+                        let (actual_type, _) = repository.database.load_object(&oid)?;
+                        if actual_type != *object_type {
+                            let message = format!(
+                                "object {} is a {}, not a {}",
+                                oid, actual_type, object_type
+                            );
+                            return Err(crate::Error::Fatal(None, message));
+                        }
+                        Ok(oid)
+                    }
+                }
+            }
+            Revision::Upstream(branch) => {
+                let branch = match branch {
+                    Some(branch) => branch.clone(),
+                    None => refs.current_branch()?.ok_or_else(|| {
+                        crate::Error::Fatal(None, String::from("HEAD does not point to a branch"))
+                    })?,
+                };
+                let upstream = refs.upstream(&branch)?.ok_or_else(|| {
+                    let message = format!("no upstream configured for branch '{}'", branch);
+                    crate::Error::Fatal(None, message)
+                })?;
+                upstream.object_id.ok_or_else(|| {
+                    let message = format!("upstream branch '{}' does not exist", upstream.name);
+                    crate::Error::Fatal(None, message)
+                })
+            }
+            Revision::PreviousCheckout(n) => {
+                let previous = reflog::find_previous_checkout(*n, repository)?
+                    .ok_or_else(|| crate::Error::AmbiguousRef(format!("@{{-{}}}", n)))?;
+                match refs.find_branch(&previous)? {
+                    Some(object_id) => Ok(object_id),
+                    None => refs.deref(&previous),
+                }
+            }
+            Revision::Path(revision, path) => {
+                let path_error = |place: &str| {
+                    let message = format!("path '{}' does not exist in {}", path.display(), place);
+                    crate::Error::Fatal(None, message)
+                };
+                match revision {
+                    Some(revision) => {
+                        let oid = revision.resolve(repository)?;
+                        if path.as_os_str().is_empty() {
+                            return Ok(repository.database.peel_to_tree(&oid)?.id().clone());
+                        }
+                        let entry = repository.database.find_tree_entry(&oid, path)?;
+                        entry
+                            .map(|entry| entry.object_id)
+                            .ok_or_else(|| path_error(&format!("'{}'", oid)))
+                    }
+                    None => repository
+                        .load_index_unlocked()?
+                        .get(path)
+                        .map(|entry| entry.object_id.clone())
+                        .ok_or_else(|| path_error("the index")),
+                }
+            }
+        }
+    }
+}
+
+/// A revision, or a range of revisions as understood by commands that walk the history.
+#[derive(Debug, PartialEq)]
+pub enum RevisionRange {
+    /// All commits reachable from the revision.
+    Single(Revision),
+    /// Commits reachable from the second revision but not from the first (`A..B`).
+    Range(Revision, Revision),
+    /// Commits reachable from either revision but not from both (`A...B`).
+    SymmetricDifference(Revision, Revision),
+}
+
+impl RevisionRange {
+    ///
+    /// Parse a revision range from a string. An omitted side of a range defaults to HEAD.
+    ///
+    /// # Examples:
+    ///
+    /// ```
+    /// use rut::refs::{Revision, RevisionRange};
+    ///
+    /// let range = RevisionRange::parse("main..HEAD").unwrap();
+    /// assert_eq!(
+    ///   range,
+    ///   RevisionRange::Range(
+    ///     Revision::Reference("main".to_owned()),
+    ///     Revision::Reference("HEAD".to_owned())
+    ///   )
+    /// );
+    ///
+    /// let range = RevisionRange::parse("main...").unwrap();
+    /// assert_eq!(
+    ///   range,
+    ///   RevisionRange::SymmetricDifference(
+    ///     Revision::Reference("main".to_owned()),
+    ///     Revision::Reference("HEAD".to_owned())
+    ///   )
+    /// );
+    /// ```
+    ///
+    pub fn parse(s: &str) -> Result<RevisionRange, ParseRevisionError> {
+        let parse_side = |side: &str| {
+            if side.is_empty() {
+                Revision::parse("HEAD")
+            } else {
+                Revision::parse(side)
+            }
+        };
+
+        if let Some((a, b)) = s.split_once("...") {
+            Ok(RevisionRange::SymmetricDifference(
+                parse_side(a)?,
+                parse_side(b)?,
+            ))
+        } else if let Some((a, b)) = s.split_once("..") {
+            Ok(RevisionRange::Range(parse_side(a)?, parse_side(b)?))
+        } else {
+            Ok(RevisionRange::Single(Revision::parse(s)?))
         }
     }
 }
@@ -0,0 +1,214 @@
+//! Detection of renamed files among the deleted and created files of a diff. A deleted and a
+//! created file are paired up as a rename if their content is identical, or failing that, if
+//! enough of the content of the larger file is also present in the other one.
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::objects::{Blob, ObjectId};
+use crate::workspace::Repository;
+
+/// The minimum similarity, in percent, for files to be considered renamed by default.
+pub const DEFAULT_THRESHOLD: u32 = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// The similarity of the content of the files, in percent.
+    pub similarity: u32,
+}
+
+/// Parse a rename threshold the way Git does. The digits are a fraction with an implied leading
+/// decimal point, so "5" and "50" both mean 50%, unless they are followed by a percent sign.
+pub fn parse_threshold(value: &str) -> Result<u32, String> {
+    let (digits, is_percentage) = match value.strip_suffix('%') {
+        Some(digits) => (digits, true),
+        None => (value, false),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!("invalid rename threshold '{}'", value));
+    }
+
+    if is_percentage {
+        Ok(digits
+            .parse::<u32>()
+            .map_or(100, |percent| percent.min(100)))
+    } else {
+        let fraction = format!("{:0<2}", &digits[..digits.len().min(2)]);
+        Ok(fraction.parse().unwrap())
+    }
+}
+
+/// Pair up the deleted files with the created files that they were renamed to. Files with
+/// identical content are paired first, and the remaining files are then paired in order of
+/// decreasing similarity, as long as it's at least the threshold. Each file is part of at most
+/// one rename, and the renames are sorted by their destination paths.
+pub fn detect_renames(
+    deleted: &[(&Path, &ObjectId)],
+    created: &[(&Path, &ObjectId)],
+    threshold: u32,
+    repository: &Repository,
+) -> crate::Result<Vec<Rename>> {
+    let mut renames = vec![];
+    if deleted.is_empty() || created.is_empty() {
+        return Ok(renames);
+    }
+
+    let mut remaining_deleted = deleted.to_vec();
+    let mut remaining_created = vec![];
+    for (path, object_id) in created {
+        let identical = remaining_deleted
+            .iter()
+            .position(|(_, deleted_id)| deleted_id == object_id);
+        match identical {
+            Some(position) => {
+                let (from, _) = remaining_deleted.remove(position);
+                renames.push(Rename {
+                    from: from.to_owned(),
+                    to: path.to_path_buf(),
+                    similarity: 100,
+                });
+            }
+            None => remaining_created.push((*path, *object_id)),
+        }
+    }
+
+    let load_blobs = |files: &[(&Path, &ObjectId)]| {
+        files
+            .iter()
+            .map(|(_, object_id)| repository.database.load_blob(object_id))
+            .collect::<crate::Result<Vec<_>>>()
+    };
+    let deleted_blobs = load_blobs(&remaining_deleted)?;
+    let created_blobs = load_blobs(&remaining_created)?;
+    let deleted_fingerprints = Fingerprint::of_blobs(&deleted_blobs);
+    let created_fingerprints = Fingerprint::of_blobs(&created_blobs);
+
+    let mut candidates = vec![];
+    for (deleted_index, deleted_fingerprint) in deleted_fingerprints.iter().enumerate() {
+        for (created_index, created_fingerprint) in created_fingerprints.iter().enumerate() {
+            let similarity = deleted_fingerprint.similarity(created_fingerprint);
+            if similarity >= threshold {
+                candidates.push((similarity, deleted_index, created_index));
+            }
+        }
+    }
+    candidates.sort_by_key(|(similarity, _, _)| Reverse(*similarity));
+
+    let mut paired_deleted = HashSet::new();
+    let mut paired_created = HashSet::new();
+    for (similarity, deleted_index, created_index) in candidates {
+        if paired_deleted.contains(&deleted_index) || paired_created.contains(&created_index) {
+            continue;
+        }
+        paired_deleted.insert(deleted_index);
+        paired_created.insert(created_index);
+        renames.push(Rename {
+            from: remaining_deleted[deleted_index].0.to_owned(),
+            to: remaining_created[created_index].0.to_owned(),
+            similarity,
+        });
+    }
+
+    renames.sort_by(|lhs, rhs| lhs.to.cmp(&rhs.to));
+    Ok(renames)
+}
+
+/// The lines of a file along with how many times each of them occurs.
+struct Fingerprint<'a> {
+    size: usize,
+    line_counts: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> Fingerprint<'a> {
+    fn new(content: &'a [u8]) -> Fingerprint<'a> {
+        let mut line_counts = HashMap::new();
+        for line in content.split_inclusive(|byte| *byte == b'\n') {
+            *line_counts.entry(line).or_insert(0) += 1;
+        }
+        Fingerprint {
+            size: content.len(),
+            line_counts,
+        }
+    }
+
+    fn of_blobs(blobs: &'a [Blob]) -> Vec<Fingerprint<'a>> {
+        blobs
+            .iter()
+            .map(|blob| Fingerprint::new(blob.content()))
+            .collect()
+    }
+
+    /// The amount of bytes in lines that occur in both files, as a percentage of the size of the
+    /// larger file. Empty files are not similar to anything.
+    fn similarity(&self, other: &Fingerprint) -> u32 {
+        if self.size == 0 || other.size == 0 {
+            return 0;
+        }
+
+        let common_size: usize = self
+            .line_counts
+            .iter()
+            .map(|(line, count)| {
+                let other_count = other.line_counts.get(line).copied().unwrap_or(0);
+                line.len() * (*count).min(other_count)
+            })
+            .sum();
+        (common_size * 100 / self.size.max(other.size)) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_threshold_as_fraction() {
+        assert_eq!(parse_threshold("5"), Ok(50));
+        assert_eq!(parse_threshold("50"), Ok(50));
+        assert_eq!(parse_threshold("9"), Ok(90));
+        assert_eq!(parse_threshold("075"), Ok(7));
+        assert_eq!(parse_threshold("100"), Ok(10));
+    }
+
+    #[test]
+    fn test_parse_threshold_as_percentage() {
+        assert_eq!(parse_threshold("5%"), Ok(5));
+        assert_eq!(parse_threshold("75%"), Ok(75));
+        assert_eq!(parse_threshold("150%"), Ok(100));
+    }
+
+    #[test]
+    fn test_parse_threshold_rejects_non_digits() {
+        assert!(parse_threshold("").is_err());
+        assert!(parse_threshold("%").is_err());
+        assert!(parse_threshold("5x").is_err());
+    }
+
+    #[test]
+    fn test_similarity_counts_bytes_of_common_lines() {
+        let a = Fingerprint::new(b"a\nb\nc\nd\n");
+        let b = Fingerprint::new(b"a\nb\nc\nx\n");
+
+        assert_eq!(a.similarity(&b), 75);
+        assert_eq!(b.similarity(&a), 75);
+    }
+
+    #[test]
+    fn test_similarity_is_relative_to_larger_file() {
+        let a = Fingerprint::new(b"line\n");
+        let b = Fingerprint::new(b"line\nline\nline\nline\n");
+
+        assert_eq!(a.similarity(&b), 25);
+    }
+
+    #[test]
+    fn test_empty_files_are_not_similar() {
+        let empty = Fingerprint::new(b"");
+
+        assert_eq!(empty.similarity(&Fingerprint::new(b"")), 0);
+        assert_eq!(empty.similarity(&Fingerprint::new(b"a\n")), 0);
+    }
+}
@@ -0,0 +1,223 @@
+//! Reuse of recorded resolutions of conflicts, known as rerere, which is enabled with
+//! `rerere.enabled`.
+//!
+//! The conflicts of a file are recorded in `.git/rr-cache/<id>/preimage` when a merge or rebase
+//! stops at them, and the resolved file in `postimage` once they are resolved. The id is a hash of
+//! the conflicting hunks, so that a later merge that runs into the same conflicts, even in another
+//! file, can replay the resolution. Conflicts waiting to be resolved are listed in
+//! `.git/MERGE_RR` as `<id>\t<path>` records, each terminated by a NUL byte.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::hashing::HashAlgorithm;
+use crate::hex;
+use crate::merge;
+use crate::output::OutputWriter;
+use crate::workspace::Repository;
+
+const RR_CACHE: &str = "rr-cache";
+const MERGE_RR: &str = "MERGE_RR";
+
+/// A file with conflicts, with the labels removed from the conflict markers and the two sides of
+/// each conflict in sorted order, so that the same conflicts look the same regardless of which
+/// side is ours.
+#[derive(Debug, PartialEq)]
+struct Preimage {
+    id: String,
+    content: String,
+}
+
+pub fn is_enabled(repository: &Repository) -> crate::Result<bool> {
+    Ok(repository
+        .config()?
+        .get_bool("rerere.enabled")?
+        .unwrap_or(false))
+}
+
+/// Resolve the conflicts of the files at the paths with recorded resolutions of the same
+/// conflicts, and record the conflicts of the files that have none. Resolved files are left
+/// unstaged, so that the resolutions can be reviewed before adding them.
+pub fn record_conflicts(
+    paths: &[PathBuf],
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if !is_enabled(repository)? {
+        return Ok(());
+    }
+
+    let mut merge_rr = read_merge_rr(repository)?;
+    for path in paths {
+        let absolute_path = repository.worktree().root().join(path);
+        // files deleted on one side, or that aren't text, have no conflict markers
+        let Some(preimage) = fs::read_to_string(&absolute_path)
+            .ok()
+            .and_then(|content| normalize(&content))
+        else {
+            continue;
+        };
+
+        let cache_dir = repository.git_dir().join(RR_CACHE).join(&preimage.id);
+        if let Some(postimage) = read_if_exists(&cache_dir.join("postimage"))? {
+            let recorded_preimage = read_if_exists(&cache_dir.join("preimage"))?
+                .unwrap_or_else(|| preimage.content.clone());
+            let resolved =
+                merge::merge_files(&recorded_preimage, &preimage.content, &postimage, "", "");
+            if resolved.is_clean {
+                fs::write(&absolute_path, resolved.content)?;
+                writer.writeln(format!(
+                    "Resolved '{}' using previous resolution.",
+                    path.display()
+                ))?;
+            }
+            continue;
+        }
+
+        fs::create_dir_all(&cache_dir)?;
+        fs::write(cache_dir.join("preimage"), &preimage.content)?;
+        writer.writeln(format!("Recorded preimage for '{}'", path.display()))?;
+        merge_rr.retain(|(_, recorded_path)| recorded_path != path);
+        merge_rr.push((preimage.id, path.clone()));
+    }
+
+    Ok(write_merge_rr(&merge_rr, repository)?)
+}
+
+/// Record the resolutions of the conflicts waiting to be resolved, for the files that no longer
+/// have conflict markers.
+pub fn record_resolutions(
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if !is_enabled(repository)? {
+        return Ok(());
+    }
+
+    let mut unresolved = vec![];
+    for (id, path) in read_merge_rr(repository)? {
+        let absolute_path = repository.worktree().root().join(&path);
+        // a deleted file has no resolution to record
+        let Some(content) = read_if_exists(&absolute_path)? else {
+            continue;
+        };
+        if normalize(&content).is_some() {
+            unresolved.push((id, path));
+            continue;
+        }
+        let cache_dir = repository.git_dir().join(RR_CACHE).join(&id);
+        fs::create_dir_all(&cache_dir)?;
+        fs::write(cache_dir.join("postimage"), content)?;
+        writer.writeln(format!("Recorded resolution for '{}'.", path.display()))?;
+    }
+
+    Ok(write_merge_rr(&unresolved, repository)?)
+}
+
+/// Forget the conflicts waiting to be resolved, as when the merge is aborted.
+pub fn clear(repository: &Repository) -> io::Result<()> {
+    write_merge_rr(&[], repository)
+}
+
+/// Normalize the content of a file with conflicts into a preimage, or return None if it has no
+/// complete conflicts. The id is a hash of the sides of each conflict, each side followed by a
+/// NUL byte.
+fn normalize(content: &str) -> Option<Preimage> {
+    let mut hasher = HashAlgorithm::Sha1.hasher();
+    let mut normalized = String::new();
+    let mut has_conflicts = false;
+    let mut lines = content.split_inclusive('\n');
+    while let Some(line) = lines.next() {
+        if !line.starts_with("<<<<<<<") {
+            normalized.push_str(line);
+            continue;
+        }
+
+        let mut sides = [String::new(), String::new()];
+        // the side being read: ours, the base of a diff3 style conflict, or theirs
+        let mut side = 0;
+        loop {
+            let line = lines.next()?;
+            if line.starts_with("|||||||") && side == 0 {
+                side = 1;
+            } else if line.starts_with("=======") && side < 2 {
+                side = 2;
+            } else if line.starts_with(">>>>>>>") && side == 2 {
+                break;
+            } else if side != 1 {
+                sides[side / 2].push_str(line);
+            }
+        }
+        sides.sort();
+        for side in &sides {
+            hasher.update(side.as_bytes());
+            hasher.update(b"\0");
+        }
+        normalized.push_str(&format!(
+            "<<<<<<<\n{}=======\n{}>>>>>>>\n",
+            sides[0], sides[1]
+        ));
+        has_conflicts = true;
+    }
+
+    has_conflicts.then(|| Preimage {
+        id: hex::to_hex_string(&hasher.finalize()),
+        content: normalized,
+    })
+}
+
+fn read_merge_rr(repository: &Repository) -> io::Result<Vec<(String, PathBuf)>> {
+    let content = read_if_exists(&repository.git_dir().join(MERGE_RR))?.unwrap_or_default();
+    Ok(content
+        .split_terminator('\0')
+        .filter_map(|record| record.split_once('\t'))
+        .map(|(id, path)| (id.to_owned(), PathBuf::from(path)))
+        .collect())
+}
+
+/// Write the conflicts waiting to be resolved, removing the file if there are none.
+fn write_merge_rr(merge_rr: &[(String, PathBuf)], repository: &Repository) -> io::Result<()> {
+    let merge_rr_path = repository.git_dir().join(MERGE_RR);
+    if merge_rr.is_empty() {
+        return match fs::remove_file(merge_rr_path) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        };
+    }
+    let content = merge_rr
+        .iter()
+        .map(|(id, path)| format!("{}\t{}\0", id, path.display()))
+        .collect::<String>();
+    fs::write(merge_rr_path, content)
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_sorts_sides_and_strips_labels() {
+        let ours_first = "a\n<<<<<<< HEAD\nzwei\n=======\nTWO\n>>>>>>> 1234567 (Change)\nc\n";
+        let theirs_first =
+            "a\n<<<<<<< ours\nTWO\n||||||| base\n2\n=======\nzwei\n>>>>>>> theirs\nc\n";
+
+        let preimage = normalize(ours_first).unwrap();
+
+        assert_eq!(
+            preimage.content,
+            "a\n<<<<<<<\nTWO\n=======\nzwei\n>>>>>>>\nc\n"
+        );
+        assert_eq!(normalize(theirs_first), Some(preimage));
+        assert_eq!(normalize("a\n<<<<<<< HEAD\nb\n"), None);
+        assert_eq!(normalize("a\n"), None);
+    }
+}
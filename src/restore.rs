@@ -1,6 +1,10 @@
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::{file, object_resolver::ObjectResolver, workspace::Repository};
+use crate::{
+    file, index::FileMode, objects::TreeEntry, pathspec::Pathspec, platform, refs::Revision,
+    workspace::Repository,
+};
 
 #[derive(Default, Builder, Debug)]
 pub struct Options {
@@ -8,36 +12,86 @@ pub struct Options {
     pub source: String,
 }
 
-/// Restores a file in the working directory to its state in the latest commit.
+/// Restores files in the working directory to their state in the latest commit.
 ///
-/// Given a file path and a reference to the repository, this function will retrieve the
-/// file's content from the latest commit and overwrite the current file in the working
-/// directory with the retrieved content.
+/// Given pathspecs selecting files or directories and a reference to the repository, this function will
+/// retrieve the content of all matching files from the latest commit and overwrite the current
+/// files in the working directory with the retrieved content. Files that have been deleted from
+/// the working directory are recreated.
 ///
-/// This is useful for discarding local changes made to a file that have not been staged.
+/// This is useful for discarding local changes made to files that have not been staged.
 ///
 /// # Arguments
 ///
-/// * `file`: A reference to the `Path` of the file to be restored.
-/// * `repository`: A reference to the `Repository` containing the file.
+/// * `pathspecs`: The pathspecs selecting the files or directories to be restored.
+/// * `options`: Options for the restore, such as the commit to restore from.
+/// * `repository`: A reference to the `Repository` containing the files.
 ///
 /// # Returns
 ///
 /// * `crate::Result<()>`: A result indicating success or failure. In case of success, the
-///   working directory file is overwritten with the content from the latest commit.
+///   working directory files are overwritten with the content from the latest commit. If any
+///   pathspec does not match a file in the commit, nothing is restored.
 pub fn restore_worktree<P: AsRef<Path>>(
-    file: P,
+    pathspecs: &[P],
     options: &Options,
     repository: &Repository,
 ) -> crate::Result<()> {
-    let mut object_cache = ObjectResolver::from_reference(&options.source, repository)?;
+    let worktree = repository.worktree();
+    let source_id = Revision::parse(&options.source)?.resolve(repository)?;
+    let source_tree = repository.database.peel_to_tree(&source_id)?;
 
-    let absolute_path = repository.worktree().root().join(file.as_ref());
-    let relative_path = repository.worktree().relativize_path(&absolute_path);
-    let blob = object_cache.find_blob_by_path(&relative_path)?;
+    let mut source_entries = vec![];
+    repository.database.extract_entries_from_tree(
+        String::new(),
+        &source_tree,
+        &mut source_entries,
+    )?;
 
-    let content = blob.content().to_vec();
-    file::atomic_write(&absolute_path, &content)?;
+    let pathspec = Pathspec::parse(pathspecs, worktree)?;
+    let restored_entries: Vec<(PathBuf, &TreeEntry)> = source_entries
+        .iter()
+        .filter(|(_, entry)| entry.mode != FileMode::Gitlink)
+        .map(|(entry_path, entry)| (PathBuf::from(entry_path), entry))
+        .filter(|(entry_path, _)| pathspec.matches(entry_path))
+        .collect();
+
+    let restored_paths = restored_entries
+        .iter()
+        .map(|(path, _)| path)
+        .collect::<Vec<_>>();
+    if let Some(unmatched) = pathspec.find_unmatched(&restored_paths) {
+        let message = format!(
+            "pathspec '{}' did not match any file(s) known to rut",
+            unmatched
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    for (relative_path, entry) in restored_entries {
+        write_tree_entry(&worktree.root().join(relative_path), entry, repository)?;
+    }
 
     Ok(())
 }
+
+/// Write the content of a blob tree entry to the worktree, after passing it through the filters of
+/// the repository, creating missing parent directories and setting the file mode of the entry.
+pub(crate) fn write_tree_entry(
+    absolute_path: &Path,
+    entry: &TreeEntry,
+    repository: &Repository,
+) -> crate::Result<()> {
+    if let Some(parent) = absolute_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let blob = repository.database.load_blob(&entry.object_id)?;
+    let relative_path = repository.worktree().relativize_path(absolute_path);
+    let content = repository
+        .filters()?
+        .to_worktree(&relative_path, blob.content());
+    file::atomic_write(absolute_path, &content)?;
+
+    let is_executable = entry.mode == FileMode::Executable;
+    Ok(platform::set_executable(absolute_path, is_executable)?)
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+use std::slice;
+
+use crate::objects::GitObject;
+use crate::output::OutputWriter;
+use crate::refs::RevisionRange;
+use crate::rev_walk::{self, RevWalk};
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Print the number of commits instead of their ids.
+    #[builder(default)]
+    pub count: bool,
+
+    #[builder(default)]
+    pub max_count: Option<u32>,
+
+    /// Prefix each commit of a symmetric difference `A...B` with `<` if it's reachable from `A`,
+    /// and with `>` if it's reachable from `B`. Commits of other ranges are prefixed with `>`.
+    #[builder(default)]
+    pub left_right: bool,
+}
+
+/// List the ids of the commits in the revision or revision range, newest first.
+pub fn rev_list(
+    revision: &str,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let range = RevisionRange::parse(revision)?;
+    let max_count = options.max_count.unwrap_or(u32::MAX) as usize;
+    let left_commits = match &range {
+        RevisionRange::SymmetricDifference(left, _) if options.left_right => {
+            let left_id = left.resolve(repository)?;
+            rev_walk::reachable_commits(slice::from_ref(&left_id), repository)?
+        }
+        _ => HashSet::new(),
+    };
+
+    let mut num_commits = 0;
+    for commit in RevWalk::from_range(&range, repository)?.take(max_count) {
+        let commit = commit?;
+        if !options.count {
+            let side = if !options.left_right {
+                ""
+            } else if left_commits.contains(commit.id()) {
+                "<"
+            } else {
+                ">"
+            };
+            writer.writeln(format!("{}{}", side, commit.id()))?;
+        }
+        num_commits += 1;
+    }
+
+    if options.count {
+        writer.writeln(num_commits.to_string())?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,383 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::slice;
+
+use crate::commit_graph::{self, CommitGraph};
+use crate::objects::{Commit, ObjectId};
+use crate::refs::RevisionRange;
+use crate::workspace::Repository;
+
+/// Iterator over commits reachable from a set of start commits, newest first by committer date.
+/// Commits reachable from any of the excluded commits are skipped, and each commit is visited at
+/// most once. The parents and dates of queued commits are read from the commit-graph when it has
+/// them, so only the commits that are actually yielded are parsed.
+pub struct RevWalk<'a> {
+    repository: &'a Repository,
+    loader: NodeLoader<'a>,
+    queue: BinaryHeap<QueuedCommit<CommitNode>>,
+    visited: HashSet<ObjectId>,
+    excluded: HashSet<ObjectId>,
+    num_queued: usize,
+}
+
+impl<'a> RevWalk<'a> {
+    pub fn new(
+        include: &[ObjectId],
+        exclude: &[ObjectId],
+        repository: &'a Repository,
+    ) -> crate::Result<RevWalk<'a>> {
+        let excluded = reachable_commits(exclude, repository)?;
+        RevWalk::with_excluded(include, excluded, repository)
+    }
+
+    pub fn from_range(range: &RevisionRange, repository: &'a Repository) -> crate::Result<Self> {
+        match range {
+            RevisionRange::Single(revision) => {
+                RevWalk::new(&[revision.resolve(repository)?], &[], repository)
+            }
+            RevisionRange::Range(exclude, include) => RevWalk::new(
+                &[include.resolve(repository)?],
+                &[exclude.resolve(repository)?],
+                repository,
+            ),
+            RevisionRange::SymmetricDifference(a, b) => {
+                let a_id = a.resolve(repository)?;
+                let b_id = b.resolve(repository)?;
+                let reachable_from_a = reachable_commits(slice::from_ref(&a_id), repository)?;
+                let reachable_from_b = reachable_commits(slice::from_ref(&b_id), repository)?;
+                let reachable_from_both = reachable_from_a
+                    .intersection(&reachable_from_b)
+                    .cloned()
+                    .collect();
+                RevWalk::with_excluded(&[a_id, b_id], reachable_from_both, repository)
+            }
+        }
+    }
+
+    fn with_excluded(
+        include: &[ObjectId],
+        excluded: HashSet<ObjectId>,
+        repository: &'a Repository,
+    ) -> crate::Result<RevWalk<'a>> {
+        let mut walk = RevWalk {
+            repository,
+            loader: NodeLoader::new(repository)?,
+            queue: BinaryHeap::new(),
+            visited: HashSet::new(),
+            excluded,
+            num_queued: 0,
+        };
+        for commit_id in include {
+            walk.enqueue(commit_id)?;
+        }
+        Ok(walk)
+    }
+
+    fn enqueue(&mut self, commit_id: &ObjectId) -> crate::Result<()> {
+        if self.excluded.contains(commit_id) || !self.visited.insert(commit_id.clone()) {
+            return Ok(());
+        }
+
+        let node = self.loader.load(commit_id)?;
+        // generation numbers are left out of the order, so that it is the same whether or not
+        // the commit-graph has the commits
+        self.queue.push(QueuedCommit {
+            generation: commit_graph::GENERATION_INFINITY,
+            timestamp: node.timestamp,
+            commit: node,
+            sequence_number: self.num_queued,
+        });
+        self.num_queued += 1;
+        Ok(())
+    }
+}
+
+impl Iterator for RevWalk<'_> {
+    type Item = crate::Result<Commit>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let QueuedCommit { commit: node, .. } = self.queue.pop()?;
+        for parent_id in &node.parents {
+            if let Err(error) = self.enqueue(parent_id) {
+                return Some(Err(error));
+            }
+        }
+        Some(self.repository.database.load_commit(&node.id))
+    }
+}
+
+const REACHABLE_FROM_A: u8 = 1;
+const REACHABLE_FROM_B: u8 = 1 << 1;
+const STALE: u8 = 1 << 2;
+
+/// Find the best common ancestor of the two commits, that is a commit reachable from both that
+/// is not an ancestor of any other such commit.
+///
+/// Walks the history of both commits at once, newest first, marking each commit with the side it
+/// is reachable from. A commit marked from both sides is a candidate, and its ancestors are
+/// marked stale as they can't be better candidates. The walk stops when only stale commits
+/// remain.
+pub fn merge_base(
+    repository: &Repository,
+    a_commit_id: &ObjectId,
+    b_commit_id: &ObjectId,
+) -> crate::Result<Option<ObjectId>> {
+    let mut queue = MarkedQueue::new(repository, |mark| mark & STALE == 0)?;
+    let mut candidates = Vec::new();
+
+    for (commit_id, mark) in [
+        (a_commit_id, REACHABLE_FROM_A),
+        (b_commit_id, REACHABLE_FROM_B),
+    ] {
+        queue.add_mark(commit_id, mark);
+        queue.push(commit_id)?;
+    }
+
+    while queue.has_active() {
+        let (commit, mut mark) = queue.pop().unwrap();
+        if mark & STALE == 0 && mark & REACHABLE_FROM_A != 0 && mark & REACHABLE_FROM_B != 0 {
+            candidates.push(commit.id.clone());
+            mark |= STALE;
+            queue.add_mark(&commit.id, STALE);
+        }
+
+        for parent_id in &commit.parents {
+            if queue.add_mark(parent_id, mark) {
+                queue.push(parent_id)?;
+            }
+        }
+    }
+
+    // with skewed commit timestamps a candidate may be found before its descendant
+    for candidate in &candidates {
+        let other_candidates = candidates
+            .iter()
+            .filter(|other| *other != candidate)
+            .cloned()
+            .collect::<Vec<_>>();
+        if !reachable_commits(&other_candidates, repository)?.contains(candidate) {
+            return Ok(Some(candidate.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Count the commits reachable from the first commit but not the second, and the other way around,
+/// such as how far a branch is ahead of and behind its upstream.
+///
+/// Walks the history of both commits at once like [`merge_base`], marking each commit with the
+/// sides it is reachable from. The walk stops once every queued commit is reachable from both
+/// sides, as their ancestors are then shared too.
+pub fn count_ahead_behind(
+    repository: &Repository,
+    a_commit_id: &ObjectId,
+    b_commit_id: &ObjectId,
+) -> crate::Result<(usize, usize)> {
+    let mut queue = MarkedQueue::new(repository, |mark| {
+        mark != REACHABLE_FROM_A | REACHABLE_FROM_B
+    })?;
+
+    for (commit_id, mark) in [
+        (a_commit_id, REACHABLE_FROM_A),
+        (b_commit_id, REACHABLE_FROM_B),
+    ] {
+        queue.add_mark(commit_id, mark);
+        queue.push(commit_id)?;
+    }
+
+    while queue.has_active() {
+        let (commit, mark) = queue.pop().unwrap();
+        for parent_id in &commit.parents {
+            if queue.add_mark(parent_id, mark) {
+                queue.push(parent_id)?;
+            }
+        }
+    }
+
+    // a commit may be walked from one side before the other side reaches it, so the commits are
+    // only counted once all marks are final
+    let count_marked = |side: u8| queue.marks.values().filter(|mark| **mark == side).count();
+    Ok((
+        count_marked(REACHABLE_FROM_A),
+        count_marked(REACHABLE_FROM_B),
+    ))
+}
+
+/// Collect the ids of all commits reachable from the start commits, including the start commits.
+pub fn reachable_commits(
+    start_commit_ids: &[ObjectId],
+    repository: &Repository,
+) -> crate::Result<HashSet<ObjectId>> {
+    let loader = NodeLoader::new(repository)?;
+    let mut reachable = HashSet::new();
+    let mut stack = start_commit_ids.to_vec();
+
+    while let Some(commit_id) = stack.pop() {
+        if !reachable.insert(commit_id.clone()) {
+            continue;
+        }
+        stack.extend(loader.load(&commit_id)?.parents);
+    }
+
+    Ok(reachable)
+}
+
+/// The queue of a walk that marks commits with the sides they are reachable from. It keeps count of
+/// the queued entries whose commits are still active, as told by their marks, so that the walk can
+/// tell when to stop without scanning the queue.
+struct MarkedQueue<'a> {
+    loader: NodeLoader<'a>,
+    queue: BinaryHeap<QueuedCommit<CommitNode>>,
+    marks: HashMap<ObjectId, u8>,
+    is_active: fn(u8) -> bool,
+    /// The number of entries in the queue for each commit, which may be queued once per mark.
+    num_entries: HashMap<ObjectId, usize>,
+    num_active_entries: usize,
+    num_queued: usize,
+}
+
+impl<'a> MarkedQueue<'a> {
+    fn new(repository: &'a Repository, is_active: fn(u8) -> bool) -> crate::Result<Self> {
+        Ok(MarkedQueue {
+            loader: NodeLoader::new(repository)?,
+            queue: BinaryHeap::new(),
+            marks: HashMap::new(),
+            is_active,
+            num_entries: HashMap::new(),
+            num_active_entries: 0,
+            num_queued: 0,
+        })
+    }
+
+    fn has_active(&self) -> bool {
+        self.num_active_entries > 0
+    }
+
+    /// Add the mark to those of the commit. Returns true if the commit didn't already have it.
+    fn add_mark(&mut self, commit_id: &ObjectId, mark: u8) -> bool {
+        let old_mark = self.marks.get(commit_id).copied().unwrap_or_default();
+        let new_mark = old_mark | mark;
+        if new_mark == old_mark {
+            return false;
+        }
+        self.marks.insert(commit_id.clone(), new_mark);
+        if (self.is_active)(old_mark) && !(self.is_active)(new_mark) {
+            self.num_active_entries -= self.num_entries.get(commit_id).copied().unwrap_or_default();
+        }
+        true
+    }
+
+    fn push(&mut self, commit_id: &ObjectId) -> crate::Result<()> {
+        let node = self.loader.load(commit_id)?;
+        self.queue.push(node.into_queued(self.num_queued));
+        self.num_queued += 1;
+        *self.num_entries.entry(commit_id.clone()).or_default() += 1;
+        if (self.is_active)(self.marks[commit_id]) {
+            self.num_active_entries += 1;
+        }
+        Ok(())
+    }
+
+    /// Take the next commit off the queue along with its marks.
+    fn pop(&mut self) -> Option<(CommitNode, u8)> {
+        let QueuedCommit { commit, .. } = self.queue.pop()?;
+        let mark = self.marks[&commit.id];
+        *self.num_entries.get_mut(&commit.id).unwrap() -= 1;
+        if (self.is_active)(mark) {
+            self.num_active_entries -= 1;
+        }
+        Some((commit, mark))
+    }
+}
+
+/// What a walk needs to know about a commit to find its way through the history.
+struct CommitNode {
+    id: ObjectId,
+    parents: Vec<ObjectId>,
+    generation: u32,
+    /// When the commit was committed, in seconds since the epoch.
+    timestamp: u64,
+}
+
+impl CommitNode {
+    fn into_queued(self, sequence_number: usize) -> QueuedCommit<CommitNode> {
+        QueuedCommit {
+            generation: self.generation,
+            timestamp: self.timestamp,
+            commit: self,
+            sequence_number,
+        }
+    }
+}
+
+/// Loads commit nodes from the commit-graph if there is one that has the commit, and otherwise by
+/// parsing the commit.
+struct NodeLoader<'a> {
+    repository: &'a Repository,
+    graph: Option<CommitGraph>,
+}
+
+impl<'a> NodeLoader<'a> {
+    fn new(repository: &'a Repository) -> crate::Result<NodeLoader<'a>> {
+        Ok(NodeLoader {
+            repository,
+            graph: CommitGraph::load(repository)?,
+        })
+    }
+
+    fn load(&self, commit_id: &ObjectId) -> crate::Result<CommitNode> {
+        if let Some(graph_commit) = self
+            .graph
+            .as_ref()
+            .and_then(|graph| graph.lookup(commit_id))
+        {
+            return Ok(CommitNode {
+                id: commit_id.clone(),
+                parents: graph_commit.parents,
+                generation: graph_commit.generation,
+                timestamp: graph_commit.timestamp,
+            });
+        }
+        let commit = self.repository.database.load_commit(commit_id)?;
+        Ok(CommitNode {
+            id: commit_id.clone(),
+            generation: commit_graph::GENERATION_INFINITY,
+            timestamp: commit.committer_timestamp,
+            parents: commit.parents,
+        })
+    }
+}
+
+/// A commit in the walk queue, ordered by generation number and then by timestamp, with ties
+/// broken by insertion order. Ordering by generation number first ensures that a commit is walked
+/// before its parents even if the commit timestamps are skewed.
+struct QueuedCommit<T> {
+    commit: T,
+    generation: u32,
+    timestamp: u64,
+    sequence_number: usize,
+}
+
+impl<T> Ord for QueuedCommit<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.generation
+            .cmp(&other.generation)
+            .then_with(|| self.timestamp.cmp(&other.timestamp))
+            .then_with(|| other.sequence_number.cmp(&self.sequence_number))
+    }
+}
+
+impl<T> PartialOrd for QueuedCommit<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for QueuedCommit<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for QueuedCommit<T> {}
@@ -1,11 +1,148 @@
-use crate::{output::OutputWriter, refs::Revision, workspace::Repository};
+use crate::{
+    objects::ObjectId,
+    output::OutputWriter,
+    reflog,
+    refs::{self, RefHandler, Revision, RevisionRange},
+    rev_walk,
+    workspace::Repository,
+};
 
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Abbreviate object ids to unique prefixes of at least this many characters.
+    #[builder(default)]
+    pub short: Option<usize>,
+    /// Print the short name of the ref that the revision names, such as the current branch for
+    /// `HEAD`, instead of its object id.
+    #[builder(default)]
+    pub abbrev_ref: bool,
+    /// Require the argument to name exactly one object.
+    #[builder(default)]
+    pub verify: bool,
+    /// With verify, don't print an error if the argument doesn't name an object.
+    #[builder(default)]
+    pub quiet: bool,
+}
+
+/// Print the object id of the revision. Ranges are printed as the included commits followed by
+/// the excluded commits prefixed with `^`. Returns false if the revision couldn't be verified
+/// and the error was not printed because of the quiet option.
 pub fn rev_parse(
     revision: &str,
+    options: &Options,
     writer: &mut dyn OutputWriter,
     repository: &Repository,
-) -> crate::Result<()> {
-    let oid = Revision::parse(revision)?.resolve(repository)?;
-    writer.writeln(oid.to_string())?;
+) -> crate::Result<bool> {
+    if options.verify {
+        return verify(revision, options, writer, repository);
+    }
+
+    let format = |object_id: &ObjectId| format_object_id(object_id, options, repository);
+    match RevisionRange::parse(revision)? {
+        RevisionRange::Single(revision) if options.abbrev_ref => {
+            writer.writeln(abbreviate_ref(&revision, options, repository)?)?;
+        }
+        RevisionRange::Single(revision) => {
+            writer.writeln(format(&revision.resolve(repository)?))?;
+        }
+        RevisionRange::Range(exclude, include) => {
+            writer.writeln(format(&include.resolve(repository)?))?;
+            writer.writeln(format!("^{}", format(&exclude.resolve(repository)?)))?;
+        }
+        RevisionRange::SymmetricDifference(a, b) => {
+            let a_id = a.resolve(repository)?;
+            let b_id = b.resolve(repository)?;
+            writer.writeln(format(&b_id))?;
+            writer.writeln(format(&a_id))?;
+            if let Some(base_id) = rev_walk::merge_base(repository, &a_id, &b_id)? {
+                writer.writeln(format!("^{}", format(&base_id)))?;
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Print the path of the git directory, relative to the directory the user is working in if it
+/// is inside of it.
+pub fn show_git_dir(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let worktree = repository.worktree();
+    let working_directory = worktree.root().join(worktree.prefix());
+    let git_dir = repository.git_dir();
+    let git_dir = git_dir
+        .strip_prefix(&working_directory)
+        .ok()
+        .filter(|path| !path.as_os_str().is_empty())
+        .unwrap_or(&git_dir);
+    writer.writeln(git_dir.display().to_string())?;
     Ok(())
 }
+
+/// Print the absolute path of the root of the worktree.
+pub fn show_toplevel(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    writer.writeln(repository.worktree().root().display().to_string())?;
+    Ok(())
+}
+
+fn verify(
+    revision: &str,
+    options: &Options,
+    writer: &mut dyn OutputWriter,
+    repository: &Repository,
+) -> crate::Result<bool> {
+    let object_id = match Revision::parse(revision) {
+        Ok(revision) => revision.resolve(repository).ok(),
+        Err(_) => None,
+    };
+    match object_id {
+        Some(object_id) => {
+            writer.writeln(format_object_id(&object_id, options, repository))?;
+            Ok(true)
+        }
+        None if options.quiet => Ok(false),
+        None => Err(crate::Error::Fatal(
+            None,
+            String::from("Needed a single revision"),
+        )),
+    }
+}
+
+fn format_object_id(object_id: &ObjectId, options: &Options, repository: &Repository) -> String {
+    match options.short {
+        Some(length) => repository.database.abbreviate_to(object_id, length),
+        None => object_id.to_string(),
+    }
+}
+
+/// The short name of the ref that the revision names. Revisions that don't name a ref, such as
+/// `HEAD~1`, are printed as object ids.
+fn abbreviate_ref(
+    revision: &Revision,
+    options: &Options,
+    repository: &Repository,
+) -> crate::Result<String> {
+    let object_id = revision.resolve(repository)?;
+    let refs = RefHandler::new(repository);
+    let name = match revision {
+        Revision::Reference(name) if name == "HEAD" => refs
+            .current_branch()?
+            .unwrap_or_else(|| String::from("HEAD")),
+        Revision::Reference(name) => refs::shorten_ref_name(name).to_owned(),
+        Revision::Upstream(branch) => {
+            // the revision resolved, so the branch and its upstream exist
+            let branch = match branch {
+                Some(branch) => branch.clone(),
+                None => refs.current_branch()?.unwrap_or_default(),
+            };
+            match refs.upstream(&branch)? {
+                Some(upstream) => upstream.name,
+                None => format_object_id(&object_id, options, repository),
+            }
+        }
+        Revision::PreviousCheckout(n) => match reflog::find_previous_checkout(*n, repository)? {
+            Some(previous) => previous,
+            None => format_object_id(&object_id, options, repository),
+        },
+        _ => format_object_id(&object_id, options, repository),
+    };
+    Ok(name)
+}
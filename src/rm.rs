@@ -1,13 +1,98 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file;
+use crate::pathspec::Pathspec;
+use crate::status;
 use crate::workspace::Repository;
-use std::path::Path;
 
-pub fn rm<P: AsRef<Path>>(path: P, repository: &Repository) -> crate::Result<()> {
-    let mut index = repository.load_index()?;
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Only remove the paths from the index, keeping the files in the worktree.
+    #[builder(default)]
+    pub cached: bool,
+
+    /// Allow removing all tracked files in a directory.
+    #[builder(default)]
+    pub recursive: bool,
+
+    /// Remove files even if they have local modifications.
+    #[builder(default)]
+    pub force: bool,
+}
+
+/// Remove the tracked files selected by the pathspecs from the index and the worktree. Selecting
+/// all tracked files in a directory by naming the directory requires the recursive option.
+pub fn rm<P: AsRef<Path>>(
+    pathspecs: &[P],
+    options: &Options,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
     let worktree = repository.worktree();
+    let pathspec = Pathspec::parse(pathspecs, worktree)?;
+
+    let removed_paths: Vec<PathBuf> = index
+        .get_entries()
+        .iter()
+        .map(|entry| entry.path.clone())
+        .filter(|path| pathspec.matches(path))
+        .collect();
+
+    if let Some(unmatched) = pathspec.find_unmatched(&removed_paths) {
+        let message = format!("pathspec '{}' did not match any files", unmatched);
+        return Err(crate::Error::Fatal(None, message));
+    }
+    if !options.recursive {
+        if let Some(directory) = pathspec.find_directory_match(&removed_paths) {
+            let message = format!("not removing '{}' recursively without -r", directory);
+            return Err(crate::Error::Fatal(None, message));
+        }
+    }
+
+    if !options.force {
+        let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
+        for removed_path in &removed_paths {
+            let staged_id = &index.get(removed_path).unwrap().object_id;
+            let absolute_path = worktree.root().join(removed_path);
+            let is_staged = path_to_committed_id.get(removed_path) != Some(staged_id);
+            let is_modified = absolute_path.is_file()
+                && status::hash_as_blob(&absolute_path, repository)? != *staged_id;
+
+            let reason = if is_staged && is_modified {
+                Some("has staged content different from both the file and the HEAD")
+            } else if is_staged && !options.cached {
+                Some("has changes staged in the index")
+            } else if is_modified && !options.cached {
+                Some("has local modifications")
+            } else {
+                None
+            };
+
+            if let Some(reason) = reason {
+                let message = format!(
+                    "the following file {}:\n    {}\n(use -f to force removal)",
+                    reason,
+                    removed_path.display()
+                );
+                return Err(crate::Error::Fatal(None, message));
+            }
+        }
+    }
 
-    let absolute_path = worktree.root().join(path);
-    let relative_path = worktree.relativize_path(absolute_path);
-    index.as_mut().remove(&relative_path);
+    for removed_path in removed_paths {
+        index.remove(&removed_path);
+        if !options.cached {
+            let absolute_path = worktree.root().join(&removed_path);
+            match fs::remove_file(&absolute_path) {
+                Err(error) if error.kind() == io::ErrorKind::NotFound => (),
+                other => other?,
+            }
+            file::remove_empty_parents(&absolute_path, worktree.root())?;
+        }
+    }
 
-    Ok(index.write()?)
+    Ok(index_lockfile.write()?)
 }
@@ -0,0 +1,82 @@
+//! Summarizing the history by author, as used for release notes and for seeing who contributed.
+
+use std::collections::HashMap;
+
+use crate::log;
+use crate::mailmap::Mailmap;
+use crate::output::OutputWriter;
+use crate::refs::RevisionRange;
+use crate::rev_walk::RevWalk;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Sort authors by their number of commits, most first, instead of by name.
+    #[builder(default)]
+    pub numbered: bool,
+
+    /// Only show the number of commits of each author, not their subjects.
+    #[builder(default)]
+    pub summary: bool,
+
+    /// Show the email of each author along with the name.
+    #[builder(default)]
+    pub email: bool,
+
+    /// The revision or revision range to summarize, defaults to HEAD.
+    #[builder(default)]
+    pub revision: Option<String>,
+}
+
+/// Group the commits by their author, as mapped by the mailmap, and write each author with the
+/// subjects of their commits, oldest first.
+pub fn shortlog(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let mailmap = Mailmap::load(repository)?;
+    let range = RevisionRange::parse(options.revision.as_deref().unwrap_or("HEAD"))?;
+
+    let mut subjects_by_author: HashMap<String, Vec<String>> = HashMap::new();
+    for commit in RevWalk::from_range(&range, repository)? {
+        let commit = commit?;
+        let author = mailmap.lookup(&commit.author);
+        let author = if options.email {
+            author.to_string()
+        } else {
+            author.name
+        };
+        let (subject, _) = log::split_message(&commit.message);
+        subjects_by_author
+            .entry(author)
+            .or_default()
+            .push(subject.to_owned());
+    }
+
+    let mut authors = subjects_by_author.into_iter().collect::<Vec<_>>();
+    if options.numbered {
+        authors.sort_by(|(name, subjects), (other_name, other_subjects)| {
+            other_subjects
+                .len()
+                .cmp(&subjects.len())
+                .then_with(|| name.cmp(other_name))
+        });
+    } else {
+        authors.sort();
+    }
+
+    for (author, subjects) in authors {
+        if options.summary {
+            writer.writeln(format!("{:>6}\t{}", subjects.len(), author))?;
+            continue;
+        }
+        writer.writeln(format!("{} ({}):", author, subjects.len()))?;
+        for subject in subjects.iter().rev() {
+            writer.writeln(format!("      {}", subject))?;
+        }
+        writer.linefeed()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Listing refs along with the objects they point to.
+
+use crate::{output::OutputWriter, refs::RefHandler, workspace::Repository};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Only show branches, and tags too if `tags` is set.
+    #[builder(default)]
+    pub heads: bool,
+
+    /// Only show tags, and branches too if `heads` is set.
+    #[builder(default)]
+    pub tags: bool,
+
+    /// Only show the object ids, not the names of the refs.
+    #[builder(default)]
+    pub hash: bool,
+}
+
+/// Show the refs that match any of the patterns, or all refs if there are none. A pattern
+/// matches a ref if it's the full name of the ref or a tail of it following a `/`, so that `main`
+/// matches both `refs/heads/main` and `refs/remotes/origin/main`. Returns whether any ref was
+/// shown.
+pub fn show_ref(
+    patterns: &[String],
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let mut namespaces = vec![];
+    if options.heads {
+        namespaces.push("refs/heads/");
+    }
+    if options.tags {
+        namespaces.push("refs/tags/");
+    }
+
+    let mut is_any_shown = false;
+    for (name, object_id) in RefHandler::new(repository).list_all_refs()? {
+        let is_in_namespace = namespaces.is_empty()
+            || namespaces
+                .iter()
+                .any(|namespace| name.starts_with(namespace));
+        let is_matching = patterns.is_empty()
+            || patterns.iter().any(|pattern| {
+                name == *pattern || name.ends_with(&format!("/{}", pattern.trim_start_matches('/')))
+            });
+        if !is_in_namespace || !is_matching {
+            continue;
+        }
+
+        if options.hash {
+            writer.writeln(object_id.to_string())?;
+        } else {
+            writer.writeln(format!("{} {}", object_id, name))?;
+        }
+        is_any_shown = true;
+    }
+
+    Ok(is_any_shown)
+}
@@ -1,20 +1,84 @@
 use std::collections::{HashMap, HashSet};
-use std::os::linux::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
-use walkdir::DirEntry;
+use rayon::prelude::*;
 
 use crate::file;
-use crate::index::Index;
-use crate::objects::{Blob, GitObject, ObjectId};
-use crate::output::{Color, OutputWriter};
+use crate::index::{FileMode, Index, IndexEntry};
+use crate::merge;
+use crate::objects::{Blob, GitObject, ObjectId, TreeEntry};
+use crate::output::{Color, OutputWriter, Serializer};
+use crate::pathspec::Pathspec;
+use crate::platform::FileStat;
 use crate::refs::RefHandler;
+use crate::rename;
+use crate::rev_walk;
+use crate::submodule;
+use crate::untracked_cache::UntrackedCache;
 use crate::workspace::{Repository, Worktree};
 
 #[derive(Default, Builder, Debug)]
 pub struct Options {
     pub output_format: OutputFormat,
+
+    /// Only show the status of the paths selected by these pathspecs.
+    #[builder(default)]
+    pub pathspecs: Vec<String>,
+
+    /// The minimum similarity, in percent, for a staged deletion and addition to be shown as a
+    /// rename. Defaults to [`rename::DEFAULT_THRESHOLD`].
+    #[builder(default)]
+    pub rename_threshold: Option<u32>,
+
+    /// Write the current branch and how it relates to its upstream before the changes in the
+    /// porcelain formats.
+    #[builder(default)]
+    pub branch: bool,
+
+    /// Terminate each record with NUL instead of a linefeed, and separate the paths of renames
+    /// with NUL. Implies the porcelain format, unless another one is given.
+    #[builder(default)]
+    pub null_terminated: bool,
+
+    #[builder(default)]
+    pub untracked_files: UntrackedFiles,
+
+    /// Also show the ignored paths. Ignored directories are shown as a whole, unless all
+    /// untracked files are shown.
+    #[builder(default)]
+    pub ignored: bool,
+}
+
+/// How to show untracked files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UntrackedFiles {
+    /// Don't show untracked files.
+    No,
+    /// Show untracked files, and untracked directories as a whole.
+    #[default]
+    Normal,
+    /// Show each untracked file, including those in untracked directories.
+    All,
+}
+
+/// Parse the mode given to `--untracked-files=<mode>`.
+///
+/// ```
+/// use rut::status::{self, UntrackedFiles};
+///
+/// assert_eq!(status::parse_untracked_files("no"), Ok(UntrackedFiles::No));
+/// assert_eq!(status::parse_untracked_files("normal"), Ok(UntrackedFiles::Normal));
+/// assert_eq!(status::parse_untracked_files("all"), Ok(UntrackedFiles::All));
+/// assert!(status::parse_untracked_files("some").is_err());
+/// ```
+pub fn parse_untracked_files(s: &str) -> Result<UntrackedFiles, String> {
+    match s {
+        "no" => Ok(UntrackedFiles::No),
+        "normal" => Ok(UntrackedFiles::Normal),
+        "all" => Ok(UntrackedFiles::All),
+        _ => Err(format!("invalid untracked files mode '{}'", s)),
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -22,6 +86,46 @@ pub enum OutputFormat {
     #[default]
     HumanReadable,
     Porcelain,
+    /// Git's version 2 of the porcelain format, which has a record with the modes and ids of each
+    /// changed file in HEAD, the index and the worktree.
+    PorcelainV2,
+    /// One JSON object per line for each change, untracked path and ignored path.
+    Json,
+}
+
+/// Parse the format given to `--format=<format>`.
+///
+/// ```
+/// use rut::status::{self, OutputFormat};
+///
+/// assert!(matches!(status::parse_format("json"), Ok(OutputFormat::Json)));
+/// assert!(status::parse_format("xml").is_err());
+/// ```
+pub fn parse_format(s: &str) -> Result<OutputFormat, String> {
+    match s {
+        "long" => Ok(OutputFormat::HumanReadable),
+        "porcelain" => Ok(OutputFormat::Porcelain),
+        "porcelain-v2" => Ok(OutputFormat::PorcelainV2),
+        "json" => Ok(OutputFormat::Json),
+        _ => Err(format!("invalid status format '{}'", s)),
+    }
+}
+
+/// Parse the version of the porcelain format given to `--porcelain=<version>`.
+///
+/// ```
+/// use rut::status::{self, OutputFormat};
+///
+/// assert!(matches!(status::parse_porcelain_version("v1"), Ok(OutputFormat::Porcelain)));
+/// assert!(matches!(status::parse_porcelain_version("v2"), Ok(OutputFormat::PorcelainV2)));
+/// assert!(status::parse_porcelain_version("v3").is_err());
+/// ```
+pub fn parse_porcelain_version(s: &str) -> Result<OutputFormat, String> {
+    match s {
+        "v1" => Ok(OutputFormat::Porcelain),
+        "v2" => Ok(OutputFormat::PorcelainV2),
+        _ => Err(format!("unsupported porcelain version '{}'", s)),
+    }
 }
 
 pub fn status(
@@ -34,34 +138,285 @@ pub fn status(
     let index = index_lockfile.as_mut();
     let path_to_committed_id = resolve_committed_paths_and_ids(repository)?;
 
-    let tracked_paths = resolve_tracked_paths(&path_to_committed_id, worktree, index);
-    let untracked_paths = resolve_untracked(&tracked_paths, worktree, index);
-
-    let mut unstaged_changes = resolve_unstaged_changes(&tracked_paths, repository, index);
-    let mut staged_changes = resolve_staged_changes(&path_to_committed_id, repository, index)?;
-
-    match options.output_format {
-        OutputFormat::HumanReadable => write_human_readable(
-            &mut staged_changes,
-            &mut unstaged_changes,
-            &untracked_paths,
-            worktree,
-            writer,
-        )?,
+    let pathspec = Pathspec::parse(&options.pathspecs, worktree)?;
+
+    let WorktreeScan {
+        mut untracked_paths,
+        mut ignored_paths,
+        mut unstaged_changes,
+    } = scan_worktree(repository, index);
+    match options.untracked_files {
+        UntrackedFiles::No => untracked_paths.clear(),
+        UntrackedFiles::Normal => (),
+        UntrackedFiles::All => {
+            untracked_paths = expand_directories(untracked_paths, file::resolve_files);
+            ignored_paths = expand_directories(ignored_paths, file::resolve_ignored_files);
+        }
+    }
+    if !options.ignored {
+        ignored_paths.clear();
+    }
+    untracked_paths.retain(|path| pathspec.matches(worktree.relativize_path(path)));
+    ignored_paths.retain(|path| pathspec.matches(worktree.relativize_path(path)));
+
+    let rename_threshold = options
+        .rename_threshold
+        .unwrap_or(rename::DEFAULT_THRESHOLD);
+    let mut staged_changes =
+        resolve_staged_changes(&path_to_committed_id, rename_threshold, repository, index)?;
+    unstaged_changes.extend(submodule::resolve_unstaged_changes(repository, index));
+    staged_changes.extend(submodule::resolve_staged_changes(repository, index)?);
+    let mut unmerged_changes = resolve_unmerged_changes(index);
+    unstaged_changes.retain(|change| pathspec.matches(&change.path));
+    staged_changes.retain(|change| pathspec.matches(&change.path));
+    unmerged_changes.retain(|change| pathspec.matches(&change.path));
+
+    let null_terminated = options.null_terminated;
+    let output_format = match options.output_format {
+        OutputFormat::HumanReadable if null_terminated => OutputFormat::Porcelain,
+        ref output_format => output_format.clone(),
+    };
+    match output_format {
+        OutputFormat::HumanReadable => {
+            write_branch_status(repository, writer)?;
+            write_merge_status(!unmerged_changes.is_empty(), repository, writer)?;
+            write_human_readable(
+                &mut staged_changes,
+                &unmerged_changes,
+                &mut unstaged_changes,
+                &untracked_paths,
+                &ignored_paths,
+                worktree,
+                writer,
+            )?
+        }
         OutputFormat::Porcelain => {
             let mut all_changes = vec![]
                 .into_iter()
                 .chain(unstaged_changes)
                 .chain(staged_changes)
+                .chain(unmerged_changes)
                 .collect::<Vec<_>>();
 
-            write_porcelain(&mut all_changes, &untracked_paths, worktree, writer)?
+            if options.branch {
+                write_branch_line(repository, null_terminated, writer)?;
+            }
+            write_porcelain(
+                &mut all_changes,
+                &untracked_paths,
+                &ignored_paths,
+                worktree,
+                null_terminated,
+                writer,
+            )?
+        }
+        OutputFormat::PorcelainV2 => {
+            if options.branch {
+                write_branch_headers_v2(repository, null_terminated, writer)?;
+            }
+            write_porcelain_v2(
+                &staged_changes,
+                &unstaged_changes,
+                &unmerged_changes,
+                index,
+                repository,
+                null_terminated,
+                writer,
+            )?;
+            print_paths("? ", &untracked_paths, worktree, null_terminated, writer)?;
+            print_paths("! ", &ignored_paths, worktree, null_terminated, writer)?
+        }
+        OutputFormat::Json => {
+            let mut all_changes = vec![]
+                .into_iter()
+                .chain(unstaged_changes)
+                .chain(staged_changes)
+                .chain(unmerged_changes)
+                .collect::<Vec<_>>();
+            write_json(
+                &mut all_changes,
+                &untracked_paths,
+                &ignored_paths,
+                worktree,
+                writer,
+            )?
         }
     }
 
     Ok(index_lockfile.write()?)
 }
 
+/// The upstream of the current branch, along with how many commits the branch is ahead of and
+/// behind it. The counts are None if the upstream no longer exists, or if there are no commits.
+struct Tracking {
+    upstream: String,
+    ahead_behind: Option<(usize, usize)>,
+}
+
+fn resolve_tracking(branch: &str, repository: &Repository) -> crate::Result<Option<Tracking>> {
+    let Some(upstream) = RefHandler::new(repository).upstream(branch)? else {
+        return Ok(None);
+    };
+    let ahead_behind = match (RefHandler::new(repository).head(), upstream.object_id) {
+        (Ok(head_id), Some(upstream_id)) => Some(rev_walk::count_ahead_behind(
+            repository,
+            &head_id,
+            &upstream_id,
+        )?),
+        _ => None,
+    };
+    Ok(Some(Tracking {
+        upstream: upstream.name,
+        ahead_behind,
+    }))
+}
+
+/// Write the current branch, or the commit that HEAD is detached at, followed by how the branch
+/// relates to its upstream.
+fn write_branch_status(
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let Some(branch) = refs.current_branch()? else {
+        let head_id = refs.head()?;
+        writer.writeln(format!("HEAD detached at {}", head_id.to_short_string()))?;
+        return Ok(());
+    };
+    writer.writeln(format!("On branch {}", branch))?;
+
+    let Some(Tracking {
+        upstream,
+        ahead_behind,
+    }) = resolve_tracking(&branch, repository)?
+    else {
+        return Ok(());
+    };
+    let commits = |count: usize| format!("{} commit{}", count, if count == 1 { "" } else { "s" });
+    let message = match ahead_behind {
+        None => format!("Your branch is based on '{}', but the upstream is gone.", upstream),
+        Some((0, 0)) => format!("Your branch is up to date with '{}'.", upstream),
+        Some((ahead, 0)) => format!("Your branch is ahead of '{}' by {}.", upstream, commits(ahead)),
+        Some((0, behind)) => format!(
+            "Your branch is behind '{}' by {}, and can be fast-forwarded.",
+            upstream,
+            commits(behind)
+        ),
+        Some((ahead, behind)) => format!(
+            "Your branch and '{}' have diverged,\nand have {} and {} different commits each, respectively.",
+            upstream, ahead, behind
+        ),
+    };
+    writer.writeln(message)?.linefeed()?;
+    Ok(())
+}
+
+/// Write whether the merge in progress, if any, still has conflicts to resolve.
+fn write_merge_status(
+    has_unmerged_paths: bool,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if merge::read_merge_heads(repository)?.is_empty() {
+        return Ok(());
+    }
+    let message = if has_unmerged_paths {
+        "You have unmerged paths."
+    } else {
+        "All conflicts fixed but you are still merging."
+    };
+    writer.writeln(message.to_owned())?.linefeed()?;
+    Ok(())
+}
+
+/// Write the `## <branch>...<upstream> [ahead <n>, behind <n>]` line of the porcelain format.
+fn write_branch_line(
+    repository: &Repository,
+    null_terminated: bool,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let Some(branch) = refs.current_branch()? else {
+        write_record(String::from("## HEAD (no branch)"), null_terminated, writer)?;
+        return Ok(());
+    };
+    if refs.head().is_err() {
+        let line = format!("## No commits yet on {}", branch);
+        write_record(line, null_terminated, writer)?;
+        return Ok(());
+    }
+
+    let line = match resolve_tracking(&branch, repository)? {
+        None => format!("## {}", branch),
+        Some(tracking) => {
+            let divergence = match tracking.ahead_behind {
+                None => String::from(" [gone]"),
+                Some((0, 0)) => String::new(),
+                Some((ahead, 0)) => format!(" [ahead {}]", ahead),
+                Some((0, behind)) => format!(" [behind {}]", behind),
+                Some((ahead, behind)) => format!(" [ahead {}, behind {}]", ahead, behind),
+            };
+            format!("## {}...{}{}", branch, tracking.upstream, divergence)
+        }
+    };
+    write_record(line, null_terminated, writer)?;
+    Ok(())
+}
+
+/// Summarize the status as comment lines, for use in a commit message template.
+pub fn commented_summary(repository: &Repository, index: &mut Index) -> crate::Result<String> {
+    let worktree = repository.worktree();
+    let path_to_committed_id = resolve_committed_paths_and_ids(repository)?;
+    let WorktreeScan {
+        untracked_paths,
+        mut unstaged_changes,
+        ..
+    } = scan_worktree(repository, index);
+    let mut staged_changes = resolve_staged_changes(
+        &path_to_committed_id,
+        rename::DEFAULT_THRESHOLD,
+        repository,
+        index,
+    )?;
+    unstaged_changes.extend(submodule::resolve_unstaged_changes(repository, index));
+    staged_changes.extend(submodule::resolve_staged_changes(repository, index)?);
+    staged_changes.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+    unstaged_changes.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+
+    let mut sections = vec![];
+    if !staged_changes.is_empty() {
+        let lines = staged_changes
+            .iter()
+            .map(|change| format!("#\t{}\n", change.human_readable_format()));
+        sections.push(format!(
+            "# Changes to be committed:\n{}",
+            lines.collect::<String>()
+        ));
+    }
+    if !unstaged_changes.is_empty() {
+        let lines = unstaged_changes
+            .iter()
+            .map(|change| format!("#\t{}\n", change.human_readable_format()));
+        sections.push(format!(
+            "# Changes not staged for commit:\n{}",
+            lines.collect::<String>()
+        ));
+    }
+    if !untracked_paths.is_empty() {
+        let lines = untracked_paths.iter().map(|path| {
+            let suffix = if path.is_dir() { "/" } else { "" };
+            format!(
+                "#\t{}{}\n",
+                worktree.relativize_path(path).display(),
+                suffix
+            )
+        });
+        sections.push(format!("# Untracked files:\n{}", lines.collect::<String>()));
+    }
+
+    Ok(sections.join("#\n"))
+}
+
 pub fn resolve_files_with_staged_changes(
     path_to_committed_id: &HashMap<PathBuf, ObjectId>,
     repository: &Repository,
@@ -76,21 +431,14 @@ pub fn resolve_files_with_staged_changes(
 }
 
 pub fn resolve_files_with_unstaged_changes(
-    path_to_committed_id: &HashMap<PathBuf, ObjectId>,
     repository: &Repository,
     index: &mut Index,
-) -> crate::Result<Vec<PathBuf>> {
-    let worktree = repository.worktree();
-    let tracked_paths = resolve_tracked_paths(path_to_committed_id, worktree, index);
-
-    let unstaged_modifications = resolve_unstaged_modifications(&tracked_paths, repository, index);
-    let unstaged_deletions = resolve_unstaged_deletions(&tracked_paths, repository.worktree());
-    let paths_with_unstaged_changes = unstaged_deletions
+) -> Vec<PathBuf> {
+    let worktree_root = repository.worktree().root();
+    resolve_unstaged_changes(repository, index)
         .into_iter()
-        .chain(unstaged_modifications.into_iter())
-        .map(|change| worktree.root().join(change.path));
-
-    Ok(paths_with_unstaged_changes.collect())
+        .map(|change| worktree_root.join(change.path))
+        .collect()
 }
 
 pub struct Change {
@@ -100,13 +448,24 @@ pub struct Change {
 }
 
 impl Change {
-    fn porcelain_format(&self) -> String {
+    /// Format the change as a record of the porcelain format. If the records are NUL-terminated,
+    /// a renamed path is followed by its original path rather than preceded by it.
+    fn porcelain_format(&self, null_terminated: bool) -> String {
+        let path = match &self.change_type {
+            ChangeType::Renamed { from, .. } if null_terminated => {
+                format!("{}\0{}", self.path.display(), from.display())
+            }
+            _ => self.display_path(),
+        };
+        if let ChangeType::Unmerged(conflict) = &self.change_type {
+            return format!("{} {}", conflict.code(), path);
+        }
         let character = self.change_type.to_char();
         let modification_shorthand = match self.changed_in {
             ChangePlace::Index => format!("{} ", character),
             ChangePlace::Worktree => format!(" {}", character),
         };
-        format!("{} {}", modification_shorthand, self.path.display())
+        format!("{} {}", modification_shorthand, path)
     }
 
     fn human_readable_format(&self) -> String {
@@ -115,14 +474,27 @@ impl Change {
                 ChangeType::Modified => "modified",
                 ChangeType::Deleted => "deleted",
                 ChangeType::Created => "new file",
+                ChangeType::Renamed { .. } => "renamed",
+                ChangeType::Unmerged(conflict) => conflict.description(),
             },
             ChangePlace::Worktree => match self.change_type {
                 ChangeType::Modified => "modified",
                 ChangeType::Deleted => "deleted",
-                ChangeType::Created => panic!("This should not happen"),
+                ChangeType::Created | ChangeType::Renamed { .. } | ChangeType::Unmerged(_) => {
+                    panic!("This should not happen")
+                }
             },
         };
-        format!("{}: {}", modification_longform, self.path.display())
+        format!("{}: {}", modification_longform, self.display_path())
+    }
+
+    fn display_path(&self) -> String {
+        match &self.change_type {
+            ChangeType::Renamed { from, .. } => {
+                format!("{} -> {}", from.display(), self.path.display())
+            }
+            _ => self.path.display().to_string(),
+        }
     }
 }
 
@@ -135,6 +507,14 @@ pub enum ChangeType {
     Modified,
     Deleted,
     Created,
+    /// The file was created from a deleted file with similar content, whose path is given along
+    /// with the similarity in percent.
+    Renamed {
+        from: PathBuf,
+        similarity: u32,
+    },
+    /// The path has unresolved merge conflicts.
+    Unmerged(Conflict),
 }
 
 impl ChangeType {
@@ -143,14 +523,75 @@ impl ChangeType {
             ChangeType::Modified => 'M',
             ChangeType::Deleted => 'D',
             ChangeType::Created => 'A',
+            ChangeType::Renamed { .. } => 'R',
+            ChangeType::Unmerged(_) => 'U',
+        }
+    }
+}
+
+/// How the two sides of a merge conflict changed an unmerged path, as given by which of the
+/// stages of the common ancestor (1), our side (2) and their side (3) the path has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    BothDeleted,
+    AddedByUs,
+    DeletedByThem,
+    AddedByThem,
+    DeletedByUs,
+    BothAdded,
+    BothModified,
+}
+
+impl Conflict {
+    fn from_stages(stages: &[u16]) -> Conflict {
+        match (
+            stages.contains(&1),
+            stages.contains(&2),
+            stages.contains(&3),
+        ) {
+            (true, false, false) => Conflict::BothDeleted,
+            (false, true, false) => Conflict::AddedByUs,
+            (true, true, false) => Conflict::DeletedByThem,
+            (false, false, true) => Conflict::AddedByThem,
+            (true, false, true) => Conflict::DeletedByUs,
+            (false, true, true) => Conflict::BothAdded,
+            // all three stages, as an unmerged path has at least one
+            _ => Conflict::BothModified,
+        }
+    }
+
+    /// The two-letter code of the conflict in the porcelain formats.
+    fn code(&self) -> &'static str {
+        match self {
+            Conflict::BothDeleted => "DD",
+            Conflict::AddedByUs => "AU",
+            Conflict::DeletedByThem => "UD",
+            Conflict::AddedByThem => "UA",
+            Conflict::DeletedByUs => "DU",
+            Conflict::BothAdded => "AA",
+            Conflict::BothModified => "UU",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Conflict::BothDeleted => "both deleted",
+            Conflict::AddedByUs => "added by us",
+            Conflict::DeletedByThem => "deleted by them",
+            Conflict::AddedByThem => "added by them",
+            Conflict::DeletedByUs => "deleted by us",
+            Conflict::BothAdded => "both added",
+            Conflict::BothModified => "both modified",
         }
     }
 }
 
 fn write_human_readable(
     staged_changes: &mut Vec<Change>,
+    unmerged_changes: &[Change],
     unstaged_changes: &mut Vec<Change>,
     untracked_paths: &[PathBuf],
+    ignored_paths: &[PathBuf],
     worktree: &Worktree,
     writer: &mut dyn OutputWriter,
 ) -> io::Result<()> {
@@ -170,6 +611,21 @@ fn write_human_readable(
         written = true;
     }
 
+    if !unmerged_changes.is_empty() {
+        if written {
+            writer.writeln("".to_string())?;
+        }
+
+        writer.writeln("Unmerged paths:".to_string())?;
+        for change in unmerged_changes {
+            writer.set_color(Color::Red)?;
+            writer.writeln(format!("\t{}", change.human_readable_format()))?;
+            writer.reset_formatting()?;
+        }
+
+        written = true;
+    }
+
     if !unstaged_changes.is_empty() {
         if written {
             writer.writeln("".to_string())?;
@@ -193,7 +649,22 @@ fn write_human_readable(
         writer.writeln("Untracked files:".to_string())?;
         for untracked in untracked_paths {
             writer.set_color(Color::Red)?;
-            print_path("\t", untracked, worktree, writer)?;
+            print_path("\t", untracked, worktree, false, writer)?;
+            writer.reset_formatting()?;
+        }
+
+        written = true;
+    }
+
+    if !ignored_paths.is_empty() {
+        if written {
+            writer.writeln("".to_string())?;
+        }
+
+        writer.writeln("Ignored files:".to_string())?;
+        for ignored in ignored_paths {
+            writer.set_color(Color::Red)?;
+            print_path("\t", ignored, worktree, false, writer)?;
             writer.reset_formatting()?;
         }
     }
@@ -205,14 +676,211 @@ fn write_human_readable(
 fn write_porcelain(
     changes: &mut Vec<Change>,
     untracked_paths: &[PathBuf],
+    ignored_paths: &[PathBuf],
     worktree: &Worktree,
+    null_terminated: bool,
     writer: &mut dyn OutputWriter,
 ) -> io::Result<()> {
     changes.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
     for change in changes {
-        writer.writeln(change.porcelain_format())?;
+        write_record(
+            change.porcelain_format(null_terminated),
+            null_terminated,
+            writer,
+        )?;
+    }
+    print_paths("?? ", untracked_paths, worktree, null_terminated, writer)?;
+    print_paths("!! ", ignored_paths, worktree, null_terminated, writer)?;
+    Ok(())
+}
+
+/// Write a JSON object for each change, with the path, whether the change is in the index or the
+/// worktree, and what kind of change it is, followed by the untracked and ignored paths.
+fn write_json(
+    changes: &mut [Change],
+    untracked_paths: &[PathBuf],
+    ignored_paths: &[PathBuf],
+    worktree: &Worktree,
+    writer: &mut dyn OutputWriter,
+) -> io::Result<()> {
+    changes.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+    let mut serializer = Serializer::new(writer);
+    for change in changes.iter() {
+        let place = match change.changed_in {
+            ChangePlace::Index => "index",
+            ChangePlace::Worktree => "worktree",
+        };
+        serializer
+            .field("path", change.path.to_string_lossy().into_owned())
+            .field("place", place);
+        match &change.change_type {
+            ChangeType::Modified => serializer.field("change", "modified"),
+            ChangeType::Deleted => serializer.field("change", "deleted"),
+            ChangeType::Created => serializer.field("change", "added"),
+            ChangeType::Renamed { from, similarity } => serializer
+                .field("change", "renamed")
+                .field("from", from.to_string_lossy().into_owned())
+                .field("similarity", *similarity),
+            ChangeType::Unmerged(conflict) => serializer
+                .field("change", "unmerged")
+                .field("conflict", conflict.code()),
+        };
+        serializer.write()?;
+    }
+
+    for (change, paths) in [("untracked", untracked_paths), ("ignored", ignored_paths)] {
+        let mut sorted_paths = paths.iter().collect::<Vec<&PathBuf>>();
+        sorted_paths.sort();
+        for path in sorted_paths {
+            let suffix = if path.is_dir() { "/" } else { "" };
+            let relative_path = worktree.relativize_path(path);
+            serializer
+                .field("path", format!("{}{}", relative_path.display(), suffix))
+                .field("change", change)
+                .write()?;
+        }
     }
-    print_paths("?? ", untracked_paths, worktree, writer)?;
+    Ok(())
+}
+
+/// Write the `# branch.*` headers of the porcelain v2 format, with the current commit and branch,
+/// and the upstream of the branch along with how far ahead of and behind it the branch is.
+fn write_branch_headers_v2(
+    repository: &Repository,
+    null_terminated: bool,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let head_id = match refs.head() {
+        Ok(head_id) => head_id.to_string(),
+        Err(_) => String::from("(initial)"),
+    };
+    let branch = refs.current_branch()?;
+    let branch_name = branch.as_deref().unwrap_or("(detached)");
+    write_record(format!("# branch.oid {}", head_id), null_terminated, writer)?;
+    write_record(
+        format!("# branch.head {}", branch_name),
+        null_terminated,
+        writer,
+    )?;
+
+    let tracking = match &branch {
+        Some(branch) => resolve_tracking(branch, repository)?,
+        None => None,
+    };
+    if let Some(tracking) = tracking {
+        let line = format!("# branch.upstream {}", tracking.upstream);
+        write_record(line, null_terminated, writer)?;
+        if let Some((ahead, behind)) = tracking.ahead_behind {
+            let line = format!("# branch.ab +{} -{}", ahead, behind);
+            write_record(line, null_terminated, writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the changes in the porcelain v2 format, with one record per changed path that combines
+/// its staged and unstaged changes. Renames get a record of type `2`, unmerged paths one of type
+/// `u` with the entries of their stages, and all other changes one of type `1`.
+fn write_porcelain_v2(
+    staged_changes: &[Change],
+    unstaged_changes: &[Change],
+    unmerged_changes: &[Change],
+    index: &Index,
+    repository: &Repository,
+    null_terminated: bool,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let committed_entries = resolve_committed_entries(repository)?;
+    let null_id = ObjectId::null(repository.hash_algorithm()).to_string();
+    let format_mode = |mode: Option<FileMode>| match mode {
+        Some(mode) => format!("{:0>6}", mode.as_str()),
+        None => String::from("000000"),
+    };
+
+    let worktree_mode = |path: &Path| {
+        let metadata = fs::symlink_metadata(repository.worktree().root().join(path)).ok()?;
+        Some(FileMode::from_stat_mode(FileStat::new(&metadata).mode))
+    };
+    let unmerged_entries = index.get_unmerged_entries();
+
+    let mut paths = staged_changes
+        .iter()
+        .chain(unstaged_changes)
+        .chain(unmerged_changes)
+        .map(|change| &change.path)
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths.dedup();
+    for path in paths {
+        let unmerged_change = unmerged_changes.iter().find(|change| change.path == *path);
+        if let Some(ChangeType::Unmerged(conflict)) = unmerged_change.map(|c| &c.change_type) {
+            let entries = unmerged_entries
+                .iter()
+                .find(|(unmerged_path, _)| unmerged_path == path)
+                .map_or(&[][..], |(_, entries)| entries);
+            let stage_entry = |stage| entries.iter().find(|entry| entry.stage() == stage);
+            let stages = [1, 2, 3].map(stage_entry);
+            let modes = stages.map(|entry| format_mode(entry.map(IndexEntry::file_mode)));
+            let ids = stages
+                .map(|entry| entry.map_or(null_id.clone(), |entry| entry.object_id.to_string()));
+            let record = format!(
+                "u {} N... {} {} {} {}",
+                conflict.code(),
+                modes.join(" "),
+                format_mode(worktree_mode(path)),
+                ids.join(" "),
+                path.display()
+            );
+            write_record(record, null_terminated, writer)?;
+            continue;
+        }
+
+        let staged_change = staged_changes.iter().find(|change| change.path == *path);
+        let unstaged_change = unstaged_changes.iter().find(|change| change.path == *path);
+        let status =
+            |change: Option<&Change>| change.map_or('.', |change| change.change_type.to_char());
+
+        let rename = staged_change.and_then(|change| match &change.change_type {
+            ChangeType::Renamed { from, similarity } => Some((from, *similarity)),
+            _ => None,
+        });
+        let committed_entry = committed_entries.get(rename.map_or(path, |(from, _)| from));
+        let index_entry = index.get(path);
+        let worktree_mode = index_entry.and_then(|_| worktree_mode(path));
+        let is_submodule = index_entry.map(|entry| entry.file_mode()) == Some(FileMode::Gitlink)
+            || committed_entry.map(|entry| entry.mode) == Some(FileMode::Gitlink);
+        // whether the submodule has uncommitted changes or untracked files isn't checked
+        let submodule_state = match (is_submodule, staged_change.or(unstaged_change)) {
+            (false, _) => "N...",
+            (true, Some(change)) if matches!(change.change_type, ChangeType::Modified) => "SC..",
+            (true, _) => "S...",
+        };
+
+        let fields = [
+            format!("{}{}", status(staged_change), status(unstaged_change)),
+            submodule_state.to_owned(),
+            format_mode(committed_entry.map(|entry| entry.mode)),
+            format_mode(index_entry.map(|entry| entry.file_mode())),
+            format_mode(worktree_mode),
+            committed_entry.map_or(null_id.clone(), |entry| entry.object_id.to_string()),
+            index_entry.map_or(null_id.clone(), |entry| entry.object_id.to_string()),
+        ]
+        .join(" ");
+        let record = match rename {
+            Some((from, similarity)) => format!(
+                "2 {} R{} {}{}{}",
+                fields,
+                similarity,
+                path.display(),
+                if null_terminated { '\0' } else { '\t' },
+                from.display()
+            ),
+            None => format!("1 {} {}", fields, path.display()),
+        };
+        write_record(record, null_terminated, writer)?;
+    }
+
     Ok(())
 }
 
@@ -220,12 +888,13 @@ fn print_paths(
     prefix: &str,
     paths: &[PathBuf],
     worktree: &Worktree,
+    null_terminated: bool,
     writer: &mut dyn OutputWriter,
 ) -> io::Result<()> {
     let mut sorted_paths = paths.iter().collect::<Vec<&PathBuf>>();
     sorted_paths.sort();
     for path in sorted_paths {
-        print_path(prefix, path, worktree, writer)?;
+        print_path(prefix, path, worktree, null_terminated, writer)?;
     }
     Ok(())
 }
@@ -234,6 +903,7 @@ fn print_path(
     prefix: &str,
     path: &PathBuf,
     worktree: &Worktree,
+    null_terminated: bool,
     writer: &mut dyn OutputWriter,
 ) -> io::Result<()> {
     let relative_path = worktree.relativize_path(path);
@@ -244,85 +914,228 @@ fn print_path(
         relative_path.as_os_str().to_str().unwrap(),
         suffix
     );
-    writer.writeln(line)?;
+    write_record(line, null_terminated, writer)
+}
+
+/// Write a record of the porcelain formats, terminated by NUL or a linefeed.
+fn write_record(
+    record: String,
+    null_terminated: bool,
+    writer: &mut dyn OutputWriter,
+) -> io::Result<()> {
+    let terminator = if null_terminated { '\0' } else { '\n' };
+    writer.write(format!("{}{}", record, terminator))?;
     Ok(())
 }
 
-pub fn resolve_tracked_paths(
-    path_to_committed_id: &HashMap<PathBuf, ObjectId>,
-    worktree: &Worktree,
-    index: &Index,
-) -> Vec<PathBuf> {
-    let root = worktree.root();
+/// The state of the worktree relative to the index, as found by a single walk over the worktree.
+pub struct WorktreeScan {
+    /// Absolute paths of untracked files, and of untracked directories as a whole.
+    pub untracked_paths: Vec<PathBuf>,
+    /// Absolute paths of ignored files, and of ignored directories as a whole.
+    pub ignored_paths: Vec<PathBuf>,
+    pub unstaged_changes: Vec<Change>,
+}
+
+/// Walk the worktree once to find the untracked paths and the tracked files that are modified or
+/// deleted compared to the index. Files whose stat information is stale are hashed in parallel,
+/// and the index is refreshed with the mtimes of those whose content turns out to be unchanged.
+pub fn scan_worktree(repository: &Repository, index: &mut Index) -> WorktreeScan {
+    let walk = walk_worktree(repository, index);
 
-    let mut paths = index
+    let candidates = index
         .get_entries()
-        .iter()
-        .map(|entry| root.join(&entry.path))
-        .collect::<HashSet<PathBuf>>();
-    let paths_in_last_commit = path_to_committed_id.keys().map(|path| root.join(path));
+        .into_iter()
+        .filter(|entry| entry.file_mode() != FileMode::Gitlink)
+        .filter(|entry| walk.present_files.contains(&entry.path))
+        .collect::<Vec<_>>();
+    let root = repository.worktree().root();
+    let outcomes = candidates
+        .par_iter()
+        .map(|entry| check_file(&root.join(&entry.path), entry, repository))
+        .collect::<Vec<_>>();
 
-    paths.extend(paths_in_last_commit);
-    paths.into_iter().collect()
+    let mut modified_paths = vec![];
+    let mut refreshed = vec![];
+    for (entry, outcome) in candidates.iter().zip(outcomes) {
+        match outcome {
+            FileCheck::Unchanged => (),
+            FileCheck::Refreshed(stat) => refreshed.push((entry.path.clone(), stat)),
+            FileCheck::Modified => modified_paths.push(entry.path.clone()),
+        }
+    }
+    for (path, stat) in refreshed {
+        if let Some(entry) = index.get_mut(&path) {
+            entry.mtime_seconds = stat.mtime_seconds;
+            entry.mtime_nanoseconds = stat.mtime_nanoseconds;
+        }
+    }
+
+    let deleted_paths = index
+        .get_entries()
+        .into_iter()
+        .filter(|entry| entry.file_mode() != FileMode::Gitlink)
+        .filter(|entry| !walk.present_files.contains(&entry.path))
+        .map(|entry| entry.path.clone())
+        .collect::<Vec<_>>();
+
+    let mut unstaged_changes = modified_paths
+        .into_iter()
+        .map(|path| (path, ChangeType::Modified))
+        .chain(
+            deleted_paths
+                .into_iter()
+                .map(|path| (path, ChangeType::Deleted)),
+        )
+        .map(|(path, change_type)| Change {
+            path,
+            change_type,
+            changed_in: ChangePlace::Worktree,
+        })
+        .collect::<Vec<_>>();
+    unstaged_changes.sort_by(|lhs, rhs| lhs.path.cmp(&rhs.path));
+
+    WorktreeScan {
+        untracked_paths: walk.untracked_paths,
+        ignored_paths: walk.ignored_paths,
+        unstaged_changes,
+    }
 }
 
-fn resolve_untracked(
-    tracked_paths: &[PathBuf],
-    worktree: &Worktree,
-    index: &Index,
-) -> Vec<PathBuf> {
-    let tracked_path_set = tracked_paths
-        .iter()
-        .map(|path| path.as_path())
-        .collect::<HashSet<_>>();
+/// Resolve all paths in the worktree that are not in the index. Directories that contain no
+/// tracked files are returned as a single path rather than being expanded into their files.
+pub fn resolve_untracked(repository: &Repository, index: &Index) -> Vec<PathBuf> {
+    walk_worktree(repository, index).untracked_paths
+}
 
-    let untracked_directories = file::resolve_paths(worktree.root(), |entry| {
-        if !entry.path().is_dir() {
-            return false;
-        }
+/// Resolve the tracked files that are modified or deleted in the worktree compared to the index.
+pub fn resolve_unstaged_changes(repository: &Repository, index: &mut Index) -> Vec<Change> {
+    scan_worktree(repository, index).unstaged_changes
+}
 
-        let relative_path = worktree.relativize_path(entry.path());
-        let parent = relative_path.parent().unwrap();
-        let parent_is_tracked =
-            parent.to_str().unwrap() == "" || index.is_tracked_directory(parent);
+struct WorktreeWalk {
+    untracked_paths: Vec<PathBuf>,
+    ignored_paths: Vec<PathBuf>,
+    /// The paths of index entries that exist as files in the worktree, relative to the root.
+    present_files: HashSet<PathBuf>,
+}
 
-        parent_is_tracked && !index.is_tracked_directory(relative_path)
-    });
+/// Walk the tracked directories of the worktree. Untracked directories are not descended into,
+/// and the entries of directories are taken from the untracked cache when the directories are
+/// unchanged. The cache is updated in the process.
+fn walk_worktree(repository: &Repository, index: &Index) -> WorktreeWalk {
+    let worktree = repository.worktree();
+    let previous_cache = UntrackedCache::load(repository);
+    let mut untracked_cache = UntrackedCache::default();
+    let mut untracked_paths = vec![];
+    let mut ignored_paths = vec![];
+    let mut present_files = HashSet::new();
+
+    let mut directories = vec![PathBuf::new()];
+    while let Some(directory) = directories.pop() {
+        let Ok(entries) = untracked_cache.read_dir(&previous_cache, worktree.root(), &directory)
+        else {
+            continue;
+        };
 
-    let untracked_files = file::resolve_paths(worktree.root(), |entry| {
-        if entry.path().is_dir() {
-            return true;
+        for entry in entries {
+            let relative_path = directory.join(&entry.name);
+            // tracked paths are examined even if they would otherwise be excluded
+            if let Some(index_entry) = index.get(&relative_path) {
+                if index_entry.file_mode() == FileMode::Gitlink {
+                    continue;
+                }
+                if entry.is_dir {
+                    // a tracked file that has been replaced by a directory is deleted
+                    untracked_paths.push(worktree.root().join(relative_path));
+                } else {
+                    present_files.insert(relative_path);
+                }
+            } else if index.is_unmerged(&relative_path) {
+                // unmerged paths are neither tracked nor untracked until resolved
+                continue;
+            } else if entry.is_dir && index.is_tracked_directory(&relative_path) {
+                directories.push(relative_path);
+            } else if file::is_ignored_name(&entry.name) {
+                ignored_paths.push(worktree.root().join(relative_path));
+            } else if !file::is_excluded_name(&entry.name) {
+                untracked_paths.push(worktree.root().join(relative_path));
+            }
         }
+    }
+
+    // the cache only saves work, so failing to write it is no reason to fail
+    let _ = untracked_cache.write(repository);
+    untracked_paths.sort();
+    ignored_paths.sort();
 
-        let relative_path = worktree.relativize_path(entry.path());
-        let parent = relative_path.parent().unwrap();
+    WorktreeWalk {
+        untracked_paths,
+        ignored_paths,
+        present_files,
+    }
+}
 
-        let parent_is_tracked =
-            parent.to_str().unwrap() == "" || index.is_tracked_directory(parent);
-        let is_path_tracked = || {
-            if entry.path().is_dir() {
-                index.is_tracked_directory(relative_path)
+/// Replace the directories among the paths with the files they contain, as resolved by the
+/// function. Nested repositories are kept as a whole, as their files belong to them.
+fn expand_directories(
+    paths: Vec<PathBuf>,
+    resolve_files: fn(&Path) -> Vec<PathBuf>,
+) -> Vec<PathBuf> {
+    let mut expanded = paths
+        .into_iter()
+        .flat_map(|path| {
+            if path.is_dir() && !path.join(".git").exists() {
+                resolve_files(&path)
             } else {
-                tracked_path_set.contains(entry.path())
+                vec![path]
             }
-        };
+        })
+        .collect::<Vec<_>>();
+    expanded.sort();
+    expanded
+}
 
-        parent_is_tracked && !is_path_tracked()
-    })
-    .into_iter()
-    .filter(|path| !path.is_dir());
+pub(crate) enum FileCheck {
+    Unchanged,
+    /// The content is unchanged, but the stat information in the index is stale.
+    Refreshed(FileStat),
+    Modified,
+}
 
-    let mut untracked_paths = untracked_directories
-        .into_iter()
-        .chain(untracked_files)
-        .collect::<Vec<_>>();
-    untracked_paths.sort();
+/// Check whether the file differs from its index entry. The file is only hashed if its mtime
+/// differs from the one in the index while its size is the same, or while its content is filtered
+/// on its way to the database, which may change its size.
+pub(crate) fn check_file(
+    absolute_path: &Path,
+    entry: &IndexEntry,
+    repository: &Repository,
+) -> FileCheck {
+    let Ok(metadata) = fs::metadata(absolute_path) else {
+        return FileCheck::Modified;
+    };
+    let stat = FileStat::new(&metadata);
+    if entry.mtime_seconds == stat.mtime_seconds
+        && entry.mtime_nanoseconds == stat.mtime_nanoseconds
+    {
+        return FileCheck::Unchanged;
+    }
+    let is_filtered = repository
+        .filters()
+        .map_or(true, |filters| filters.converts_to_database(&entry.path));
+    if entry.file_size != stat.size && !is_filtered {
+        return FileCheck::Modified;
+    }
 
-    untracked_paths
+    match hash_as_blob(absolute_path, repository) {
+        Ok(object_id) if object_id == entry.object_id => FileCheck::Refreshed(stat),
+        _ => FileCheck::Modified,
+    }
 }
 
 fn resolve_staged_changes(
     path_to_committed_id: &HashMap<PathBuf, ObjectId>,
+    rename_threshold: u32,
     repository: &Repository,
     index: &mut Index,
 ) -> crate::Result<Vec<Change>> {
@@ -332,28 +1145,75 @@ fn resolve_staged_changes(
         repository.worktree(),
         index,
     ));
-    Ok(staged_changes)
+    detect_staged_renames(
+        staged_changes,
+        path_to_committed_id,
+        rename_threshold,
+        repository,
+        index,
+    )
 }
 
-fn resolve_staged_modifications(
+/// Replace staged deletions and additions that are renames of each other with a single rename.
+fn detect_staged_renames(
+    staged_changes: Vec<Change>,
     path_to_committed_id: &HashMap<PathBuf, ObjectId>,
+    rename_threshold: u32,
     repository: &Repository,
     index: &Index,
 ) -> crate::Result<Vec<Change>> {
-    let staged_paths_filter = |entry: &DirEntry| {
-        if entry.path().is_dir() {
-            return true;
+    let mut deleted = vec![];
+    let mut created = vec![];
+    for change in &staged_changes {
+        match change.change_type {
+            ChangeType::Deleted => {
+                deleted.push((change.path.as_path(), &path_to_committed_id[&change.path]))
+            }
+            ChangeType::Created => created.push((
+                change.path.as_path(),
+                &index.get(&change.path).unwrap().object_id,
+            )),
+            _ => (),
         }
+    }
 
-        let relative_path = repository.worktree().relativize_path(entry.path());
-        index.has_entry(relative_path)
-    };
+    let renames = rename::detect_renames(&deleted, &created, rename_threshold, repository)?;
+    if renames.is_empty() {
+        return Ok(staged_changes);
+    }
+    let sources = renames
+        .iter()
+        .map(|rename| rename.from.clone())
+        .collect::<HashSet<_>>();
+    let mut destination_to_source = renames
+        .into_iter()
+        .map(|rename| (rename.to, (rename.from, rename.similarity)))
+        .collect::<HashMap<_, _>>();
+
+    Ok(staged_changes
+        .into_iter()
+        .filter(|change| !sources.contains(&change.path))
+        .map(|change| match destination_to_source.remove(&change.path) {
+            Some((from, similarity)) => Change {
+                change_type: ChangeType::Renamed { from, similarity },
+                ..change
+            },
+            None => change,
+        })
+        .collect())
+}
 
-    let staged_paths: Vec<PathBuf> =
-        file::resolve_paths(repository.worktree().root(), staged_paths_filter)
-            .into_iter()
-            .filter(|path| path.is_file())
-            .collect();
+fn resolve_staged_modifications(
+    path_to_committed_id: &HashMap<PathBuf, ObjectId>,
+    repository: &Repository,
+    index: &Index,
+) -> crate::Result<Vec<Change>> {
+    let staged_paths: Vec<PathBuf> = index
+        .get_entries()
+        .iter()
+        .filter(|entry| entry.file_mode() != FileMode::Gitlink)
+        .map(|entry| repository.worktree().root().join(&entry.path))
+        .collect();
 
     classify_staged_changes(&staged_paths, path_to_committed_id, repository, index)
 }
@@ -397,8 +1257,8 @@ fn resolve_staged_deletions(
 ) -> Vec<Change> {
     path_to_committed_id
         .keys()
+        .filter(|path| !index.has_entry(path) && !index.is_unmerged(path))
         .cloned()
-        .filter(|path| !index.has_entry(path))
         .map(|path| worktree.root().join(path))
         .map(|path| Change {
             path: worktree.relativize_path(path),
@@ -408,112 +1268,63 @@ fn resolve_staged_deletions(
         .collect()
 }
 
-fn resolve_unstaged_deletions<'a>(
-    tracked_paths: &'a [PathBuf],
-    worktree: &'a Worktree,
-) -> impl Iterator<Item = Change> + 'a {
-    tracked_paths
-        .iter()
-        .filter(|path| !path.exists())
-        .map(|path| Change {
-            path: worktree.relativize_path(path),
-            change_type: ChangeType::Deleted,
-            changed_in: ChangePlace::Worktree,
+/// Resolve the paths with unresolved merge conflicts, along with how each of them conflicts.
+fn resolve_unmerged_changes(index: &Index) -> Vec<Change> {
+    index
+        .get_unmerged_entries()
+        .into_iter()
+        .map(|(path, entries)| {
+            let stages = entries.iter().map(IndexEntry::stage).collect::<Vec<_>>();
+            Change {
+                path: path.to_owned(),
+                change_type: ChangeType::Unmerged(Conflict::from_stages(&stages)),
+                changed_in: ChangePlace::Index,
+            }
         })
+        .collect()
 }
 
 pub fn resolve_committed_paths_and_ids(
     repository: &Repository,
 ) -> crate::Result<HashMap<PathBuf, ObjectId>> {
-    let head_commit_id_opt = RefHandler::new(repository).head();
-    if head_commit_id_opt.is_err() {
-        return Ok(HashMap::new());
-    }
-    let head_commit_id = head_commit_id_opt.ok().unwrap();
-
-    let commit = repository.database.load_commit(&head_commit_id)?;
-    let tree = repository.database.load_tree(&commit.tree)?;
-
-    let mut paths_in_head = vec![];
-    repository
-        .database
-        .extract_paths_from_tree(String::from(""), &tree, &mut paths_in_head)?;
-    let path_to_id: HashMap<PathBuf, ObjectId> = paths_in_head
+    let path_to_id: HashMap<PathBuf, ObjectId> = resolve_committed_entries(repository)?
         .into_iter()
-        .map(|(id, path)| (PathBuf::from(path), ObjectId::from_sha(&id).unwrap()))
+        .filter(|(_, entry)| entry.mode != FileMode::Gitlink)
+        .map(|(path, entry)| (path, entry.object_id))
         .collect();
 
     Ok(path_to_id)
 }
 
-pub fn resolve_unstaged_changes(
-    tracked_paths: &[PathBuf],
+/// Resolve the files and submodules in the HEAD commit by their paths.
+fn resolve_committed_entries(
     repository: &Repository,
-    index: &mut Index,
-) -> Vec<Change> {
-    resolve_unstaged_modifications(tracked_paths, repository, index)
-        .chain(resolve_unstaged_deletions(
-            tracked_paths,
-            repository.worktree(),
-        ))
-        .collect()
-}
-
-fn resolve_unstaged_modifications<'a>(
-    tracked_paths: &'a [PathBuf],
-    repository: &'a Repository,
-    index: &'a mut Index,
-) -> impl Iterator<Item = Change> + 'a {
-    let worktree = repository.worktree();
-    tracked_paths
-        .iter()
-        .filter(|path| {
-            is_modified(path, &worktree.relativize_path(path), index)
-                .ok()
-                .unwrap_or(false)
-        })
-        .map(|path| Change {
-            path: repository.worktree().relativize_path(path),
-            change_type: ChangeType::Modified,
-            changed_in: ChangePlace::Worktree,
-        })
-}
-
-/// Returns true if the file at the given path has been modified since the last commit.
-///
-/// Side effect: Updates the index with new mtimes if they've been updatet without the content being
-/// changed.
-fn is_modified(
-    absolute_path: &Path,
-    tracked_path: &Path,
-    index: &mut Index,
-) -> crate::Result<bool> {
-    let is_modified = if let Some(index_entry) = index.get_mut(tracked_path) {
-        let metadata = fs::metadata(absolute_path)?;
-        let mtimes_differ = index_entry.mtime_seconds != metadata.st_mtime() as u32
-            || index_entry.mtime_nanoseconds != metadata.st_mtime_nsec() as u32;
-
-        if mtimes_differ {
-            let current_object_id = hash_as_blob(absolute_path)?;
-            if current_object_id != index_entry.object_id {
-                true
-            } else {
-                index_entry.mtime_seconds = metadata.st_mtime() as u32;
-                index_entry.mtime_nanoseconds = metadata.st_mtime_nsec() as u32;
-                false
-            }
-        } else {
-            false
-        }
-    } else {
-        false
+) -> crate::Result<HashMap<PathBuf, TreeEntry>> {
+    let Ok(head_commit_id) = RefHandler::new(repository).head() else {
+        return Ok(HashMap::new());
     };
 
-    Ok(is_modified)
+    let commit = repository.database.load_commit(&head_commit_id)?;
+    let tree = repository.database.load_tree(&commit.tree)?;
+
+    let mut entries_in_head = vec![];
+    repository
+        .database
+        .extract_entries_from_tree(String::new(), &tree, &mut entries_in_head)?;
+    Ok(entries_in_head
+        .into_iter()
+        .map(|(path, entry)| (PathBuf::from(path), entry))
+        .collect())
 }
 
-fn hash_as_blob(absolute_path: &Path) -> crate::Result<ObjectId> {
+/// Compute the id that the file would have as a blob, after filtering its content.
+pub(crate) fn hash_as_blob(
+    absolute_path: &Path,
+    repository: &Repository,
+) -> crate::Result<ObjectId> {
+    let relative_path = repository.worktree().relativize_path(absolute_path);
     let content = file::read_file(absolute_path)?;
-    let blob = Blob::new(content);
+    let content = repository.filters()?.to_database(&relative_path, content);
+    let blob = Blob::with_hash_algorithm(content, repository.hash_algorithm());
     Ok(blob.id().clone())
 }
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::add;
+use crate::config::{self, Config, ConfigLevel};
+use crate::file;
+use crate::index::{FileMode, Index, IndexEntry};
+use crate::objects::ObjectId;
+use crate::output::{NoProgress, OutputWriter};
+use crate::refs::RefHandler;
+use crate::status::{Change, ChangePlace, ChangeType};
+use crate::switch;
+use crate::workspace::{self, Repository};
+
+/// The file at the root of the worktree that configures the submodules.
+pub const GITMODULES: &str = ".gitmodules";
+
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    /// Clone the repository at the URL into the path, which defaults to the name of the
+    /// repository, and stage it as a submodule.
+    Add { url: String, path: Option<PathBuf> },
+    /// Register the URLs of the submodules in `.gitmodules` in the local config.
+    Init,
+    /// Clone the registered submodules that are missing and check out the commits recorded in
+    /// the index.
+    Update,
+    /// Show the commit each submodule is at.
+    #[default]
+    Status,
+}
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub mode: Mode,
+}
+
+/// A submodule as configured in `.gitmodules`.
+#[derive(Debug, PartialEq)]
+pub struct Submodule {
+    pub name: String,
+    /// The path of the submodule, relative to the root of the worktree.
+    pub path: PathBuf,
+    pub url: String,
+}
+
+/// Add, register, update or show submodules depending on the mode. Only repositories on the
+/// local file system can be cloned.
+pub fn submodule(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    match &options.mode {
+        Mode::Add { url, path } => add(url, path.as_deref(), repository, writer),
+        Mode::Init => init(repository, writer),
+        Mode::Update => update(repository, writer),
+        Mode::Status => status(repository, writer),
+    }
+}
+
+/// Read the submodules in `.gitmodules`, in the order they are configured. Submodules without a
+/// path or URL are skipped. Fails if a name or path would lead outside of the worktree or the
+/// modules directory, or into a `.git` directory.
+pub fn read_gitmodules(repository: &Repository) -> crate::Result<Vec<Submodule>> {
+    let gitmodules_path = repository.worktree().root().join(GITMODULES);
+    let config = Config::from_files(&[(ConfigLevel::Local, gitmodules_path)])?;
+
+    let mut names: Vec<&str> = vec![];
+    for entry in config.entries() {
+        let name = entry
+            .key
+            .strip_prefix("submodule.")
+            .and_then(|rest| rest.rsplit_once('.'))
+            .map(|(name, _)| name);
+        if let Some(name) = name.filter(|name| !names.contains(name)) {
+            names.push(name);
+        }
+    }
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let path = config.get_string(&format!("submodule.{}.path", name))?;
+            let url = config.get_string(&format!("submodule.{}.url", name))?;
+            Some((name, path, url))
+        })
+        .map(|(name, path, url)| {
+            if !is_safe_path(Path::new(name)) || !is_safe_path(Path::new(&path)) {
+                let message = format!("invalid submodule '{}' with path '{}'", name, path);
+                return Err(crate::Error::Fatal(None, message));
+            }
+            Ok(Submodule {
+                name: name.to_owned(),
+                path: PathBuf::from(path),
+                url,
+            })
+        })
+        .collect()
+}
+
+/// Whether the relative path stays inside the directory it is joined to without going through a
+/// `.git` directory, which on case-insensitive file systems includes any casing of `.git`.
+fn is_safe_path(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path.components().all(|component| match component {
+            Component::Normal(name) => !name.eq_ignore_ascii_case(".git"),
+            Component::CurDir => true,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => false,
+        })
+}
+
+fn add(
+    url: &str,
+    path: Option<&Path>,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let source_path = resolve_url(url, repository)?;
+    let source_git_dir = find_git_dir(&source_path)?;
+    let path = match path {
+        Some(path) => repository.worktree().resolve_user_path(path)?,
+        None => {
+            let name = source_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            PathBuf::from(name.trim_end_matches(".git"))
+        }
+    };
+
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    if index.has_entry(&path) || index.is_tracked_directory(&path) {
+        let message = format!("'{}' already exists in the index", path.display());
+        return Err(crate::Error::Fatal(None, message));
+    }
+    if !is_safe_path(&path) {
+        let message = format!("invalid submodule path '{}'", path.display());
+        return Err(crate::Error::Fatal(None, message));
+    }
+    let worktree_path = repository.worktree().root().join(&path);
+    if worktree_path.exists() && fs::read_dir(&worktree_path)?.next().is_some() {
+        let message = format!("'{}' already exists and is not empty", path.display());
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let name = path.to_string_lossy().into_owned();
+    writer.writeln(format!("Cloning into '{}'...", worktree_path.display()))?;
+    let submodule_repository = clone(&source_git_dir, &name, &worktree_path, repository)?;
+
+    // check out the commit before writing HEAD, as the checkout starts from the HEAD commit
+    let source_repository = Repository::new(&source_git_dir, &source_path)?;
+    let commit_id = RefHandler::new(&source_repository).head()?;
+    checkout(&commit_id, &submodule_repository)?;
+    fs::copy(
+        source_git_dir.join("HEAD"),
+        submodule_repository.git_dir().join("HEAD"),
+    )?;
+
+    let gitmodules_path = repository.worktree().root().join(GITMODULES);
+    let path_string = path.to_string_lossy();
+    config::write_config_value(
+        &gitmodules_path,
+        &format!("submodule.{}.path", name),
+        &path_string,
+    )?;
+    config::write_config_value(&gitmodules_path, &format!("submodule.{}.url", name), url)?;
+    register(&name, &source_path, repository)?;
+
+    add::add_file(&gitmodules_path, index, repository)?;
+    let metadata = fs::metadata(&worktree_path)?;
+    index.add_entry(IndexEntry::new(&path, commit_id, &metadata));
+    Ok(index_lockfile.write()?)
+}
+
+fn init(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let config = repository.config()?;
+    for submodule in read_gitmodules(repository)? {
+        if config.get_string(&url_key(&submodule.name)).is_some() {
+            continue;
+        }
+
+        let source_path = resolve_url(&submodule.url, repository)?;
+        register(&submodule.name, &source_path, repository)?;
+        writer.writeln(format!(
+            "Submodule '{}' ({}) registered for path '{}'",
+            submodule.name,
+            submodule.url,
+            submodule.path.display()
+        ))?;
+    }
+    Ok(())
+}
+
+fn update(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let config = repository.config()?;
+    let index = repository.load_index_unlocked()?;
+
+    for submodule in read_gitmodules(repository)? {
+        let (Some(url), Some(recorded_id)) = (
+            config.get_string(&url_key(&submodule.name)),
+            recorded_commit(&index, &submodule.path),
+        ) else {
+            continue;
+        };
+
+        let worktree_path = repository.worktree().root().join(&submodule.path);
+        let module_git_dir = module_git_dir(&submodule.name, repository);
+        let submodule_repository = if module_git_dir.is_dir() {
+            connect_worktree(&module_git_dir, &worktree_path)?;
+            Repository::new(module_git_dir, &worktree_path)?
+        } else {
+            writer.writeln(format!("Cloning into '{}'...", worktree_path.display()))?;
+            let source_git_dir = find_git_dir(Path::new(&url))?;
+            clone(&source_git_dir, &submodule.name, &worktree_path, repository)?
+        };
+
+        let refs = RefHandler::new(&submodule_repository);
+        if refs.head().ok().as_ref() == Some(recorded_id) {
+            continue;
+        }
+        checkout(recorded_id, &submodule_repository)?;
+        refs.detach_head(recorded_id)?;
+        writer.writeln(format!(
+            "Submodule path '{}': checked out '{}'",
+            submodule.path.display(),
+            recorded_id
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Show the commit recorded for each submodule, prefixed with `-` if the submodule is not
+/// registered and checked out, or the checked out commit prefixed with `+` if it differs from the
+/// recorded one.
+fn status(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let config = repository.config()?;
+    let index = repository.load_index_unlocked()?;
+
+    for submodule in read_gitmodules(repository)? {
+        let Some(recorded_id) = recorded_commit(&index, &submodule.path) else {
+            continue;
+        };
+        let is_registered = config.get_string(&url_key(&submodule.name)).is_some();
+        let line = match checked_out_commit(&submodule.path, repository) {
+            Some(commit_id) if is_registered && &commit_id != recorded_id => {
+                format!("+{} {}", commit_id, submodule.path.display())
+            }
+            Some(_) if is_registered => format!(" {} {}", recorded_id, submodule.path.display()),
+            _ => format!("-{} {}", recorded_id, submodule.path.display()),
+        };
+        writer.writeln(line)?;
+    }
+
+    Ok(())
+}
+
+/// Resolve the submodules whose commit in the index differs from the one in the HEAD commit.
+pub(crate) fn resolve_staged_changes(
+    repository: &Repository,
+    index: &Index,
+) -> crate::Result<Vec<Change>> {
+    let committed_gitlinks = resolve_committed_gitlinks(repository)?;
+    let staged_gitlinks = index
+        .get_entries()
+        .into_iter()
+        .filter(|entry| entry.file_mode() == FileMode::Gitlink)
+        .map(|entry| (&entry.path, &entry.object_id))
+        .collect::<HashMap<_, _>>();
+
+    let mut changes = vec![];
+    for (path, staged_id) in &staged_gitlinks {
+        let change_type = match committed_gitlinks.get(*path) {
+            None => ChangeType::Created,
+            Some(committed_id) if committed_id != *staged_id => ChangeType::Modified,
+            Some(_) => continue,
+        };
+        changes.push(Change {
+            path: path.to_path_buf(),
+            change_type,
+            changed_in: ChangePlace::Index,
+        });
+    }
+    for path in committed_gitlinks.keys() {
+        if !index.has_entry(path) {
+            changes.push(Change {
+                path: path.clone(),
+                change_type: ChangeType::Deleted,
+                changed_in: ChangePlace::Index,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Resolve the submodules whose checked out commit differs from the one in the index.
+pub(crate) fn resolve_unstaged_changes(repository: &Repository, index: &Index) -> Vec<Change> {
+    index
+        .get_entries()
+        .into_iter()
+        .filter(|entry| entry.file_mode() == FileMode::Gitlink)
+        .filter(|entry| {
+            checked_out_commit(&entry.path, repository)
+                .is_some_and(|commit_id| commit_id != entry.object_id)
+        })
+        .map(|entry| Change {
+            path: entry.path.clone(),
+            change_type: ChangeType::Modified,
+            changed_in: ChangePlace::Worktree,
+        })
+        .collect()
+}
+
+fn resolve_committed_gitlinks(
+    repository: &Repository,
+) -> crate::Result<HashMap<PathBuf, ObjectId>> {
+    let Ok(head_id) = RefHandler::new(repository).head() else {
+        return Ok(HashMap::new());
+    };
+    let tree = repository.database.peel_to_tree(&head_id)?;
+
+    let mut entries = vec![];
+    repository
+        .database
+        .extract_entries_from_tree(String::new(), &tree, &mut entries)?;
+    Ok(entries
+        .into_iter()
+        .filter(|(_, entry)| entry.mode == FileMode::Gitlink)
+        .map(|(path, entry)| (PathBuf::from(path), entry.object_id))
+        .collect())
+}
+
+fn recorded_commit<'a>(index: &'a Index, path: &Path) -> Option<&'a ObjectId> {
+    index
+        .get(path)
+        .filter(|entry| entry.file_mode() == FileMode::Gitlink)
+        .map(|entry| &entry.object_id)
+}
+
+/// The commit checked out in the submodule at the path, if it's checked out at all.
+fn checked_out_commit(path: &Path, repository: &Repository) -> Option<ObjectId> {
+    let worktree_path = repository.worktree().root().join(path);
+    let dot_git = worktree_path.join(".git");
+    let git_dir = if dot_git.is_file() {
+        workspace::read_gitfile(&dot_git).ok()?
+    } else if dot_git.is_dir() {
+        dot_git
+    } else {
+        return None;
+    };
+    RefHandler::new(&Repository::new(git_dir, worktree_path).ok()?)
+        .head()
+        .ok()
+}
+
+/// Resolve the URL of a submodule to the path of a repository on the local file system. Relative
+/// URLs are relative to the root of the worktree.
+fn resolve_url(url: &str, repository: &Repository) -> crate::Result<PathBuf> {
+    let is_remote = url.contains("://") || url.split('/').next().unwrap().contains(':');
+    if is_remote {
+        let message = format!(
+            "only local repositories are supported as submodules: {}",
+            url
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+    Ok(file::normalize_path(repository.worktree().root().join(url)))
+}
+
+/// Find the git directory of the repository at the path, which is either a worktree or a bare
+/// repository.
+fn find_git_dir(repository_path: &Path) -> crate::Result<PathBuf> {
+    let dot_git = repository_path.join(".git");
+    if dot_git.is_dir() {
+        Ok(dot_git)
+    } else if repository_path.join("HEAD").is_file() && repository_path.join("objects").is_dir() {
+        Ok(repository_path.to_owned())
+    } else {
+        let message = format!("repository '{}' does not exist", repository_path.display());
+        Err(crate::Error::Fatal(None, message))
+    }
+}
+
+/// Copy the objects and refs of the source repository into `.git/modules/<name>`, and point the
+/// worktree of the submodule at it. HEAD is left for the caller to write after checking out.
+fn clone(
+    source_git_dir: &Path,
+    name: &str,
+    worktree_path: &Path,
+    repository: &Repository,
+) -> crate::Result<Repository> {
+    let module_git_dir = module_git_dir(name, repository);
+    for directory in ["objects", "refs"] {
+        fs::create_dir_all(module_git_dir.join(directory))?;
+        file::copy_directory(
+            &source_git_dir.join(directory),
+            &module_git_dir.join(directory),
+        )?;
+    }
+    let packed_refs = source_git_dir.join("packed-refs");
+    if packed_refs.is_file() {
+        fs::copy(packed_refs, module_git_dir.join("packed-refs"))?;
+    }
+
+    connect_worktree(&module_git_dir, worktree_path)?;
+    Repository::new(module_git_dir, worktree_path)
+}
+
+/// Create the worktree directory of a submodule if needed, with a `.git` file pointing at its
+/// git directory.
+fn connect_worktree(module_git_dir: &Path, worktree_path: &Path) -> crate::Result<()> {
+    fs::create_dir_all(worktree_path)?;
+    let gitfile_content = format!("gitdir: {}\n", module_git_dir.display());
+    Ok(fs::write(worktree_path.join(".git"), gitfile_content)?)
+}
+
+/// Check out the commit in the submodule, starting from its current HEAD commit if any.
+fn checkout(commit_id: &ObjectId, submodule_repository: &Repository) -> crate::Result<()> {
+    let mut index_lockfile = submodule_repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    switch::checkout_commit(commit_id, submodule_repository, index, &mut NoProgress)?;
+    Ok(index_lockfile.write()?)
+}
+
+/// Record the URL of the submodule in the local config, which marks it as initialized.
+fn register(name: &str, source_path: &Path, repository: &Repository) -> crate::Result<()> {
+    config::write_config_value(
+        &repository.common_dir().join("config"),
+        &url_key(name),
+        &source_path.to_string_lossy(),
+    )
+}
+
+fn module_git_dir(name: &str, repository: &Repository) -> PathBuf {
+    repository.common_dir().join("modules").join(name)
+}
+
+fn url_key(name: &str) -> String {
+    format!("submodule.{}.url", name)
+}
@@ -0,0 +1,245 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::diff;
+use crate::file;
+use crate::index::{Index, IndexEntry};
+use crate::objects::{GitObject, ObjectId, TreeEntry};
+use crate::output::{OutputWriter, Progress};
+use crate::reflog;
+use crate::refs::{RefHandler, Revision};
+use crate::restore;
+use crate::status::{self, ChangeType};
+use crate::workspace::Repository;
+use crate::worktree;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Switch to the commit itself instead of a branch, detaching HEAD.
+    #[builder(default)]
+    pub detach: bool,
+
+    /// Create a branch with this name at the target, which defaults to HEAD, and switch to it.
+    #[builder(default)]
+    pub create: Option<String>,
+}
+
+/// Switch to a branch, or to any commit when detaching HEAD. The worktree and index are updated
+/// to match the commit, refusing to overwrite local changes.
+pub fn switch(
+    target: Option<&str>,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+    progress: &mut dyn Progress,
+) -> crate::Result<()> {
+    if let Some(branch) = &options.create {
+        let start_point = target.unwrap_or("HEAD");
+        return create_and_switch(branch, start_point, repository, writer, progress);
+    }
+
+    let target = target.ok_or_else(|| {
+        crate::Error::Fatal(None, String::from("missing branch or commit argument"))
+    })?;
+    let refs = RefHandler::new(repository);
+
+    let commit_id = if options.detach {
+        Revision::parse(target)?.resolve(repository)?
+    } else {
+        refs.find_branch(target)?.ok_or_else(|| {
+            let message =
+                if Revision::parse(target).is_ok_and(|rev| rev.resolve(repository).is_ok()) {
+                    format!("a branch is expected, got '{}'", target)
+                } else {
+                    format!("invalid reference: {}", target)
+                };
+            crate::Error::Fatal(None, message)
+        })?
+    };
+
+    if !options.detach && refs.current_branch()?.as_deref() == Some(target) {
+        writer.writeln(format!("Already on '{}'", target))?;
+        return Ok(());
+    }
+    if !options.detach {
+        if let Some(checkout_path) = worktree::find_checkout(target, repository)? {
+            let message = format!(
+                "'{}' is already checked out at '{}'",
+                target,
+                checkout_path.display()
+            );
+            return Err(crate::Error::Fatal(None, message));
+        }
+    }
+
+    let (previous_id, previous_name) = describe_head(repository)?;
+    let mut index_lockfile = repository.load_index()?;
+    checkout_commit(&commit_id, repository, index_lockfile.as_mut(), progress)?;
+    index_lockfile.write()?;
+
+    let message = format!("checkout: moving from {} to {}", previous_name, target);
+    if options.detach {
+        refs.detach_head(&commit_id)?;
+        let commit = repository.database.load_commit(&commit_id)?;
+        let subject = commit.message.lines().next().unwrap_or_default();
+        writer.writeln(format!(
+            "HEAD is now at {} {}",
+            commit.short_id_as_string(),
+            subject
+        ))?;
+    } else {
+        refs.attach_head(target)?;
+        writer.writeln(format!("Switched to branch '{}'", target))?;
+    }
+    reflog::append_head_entry(previous_id.as_ref(), &commit_id, &message, repository)?;
+
+    Ok(())
+}
+
+/// The object HEAD points to, if any, and the name of what is checked out as recorded in the
+/// reflog: the current branch, or the commit id if HEAD is detached.
+fn describe_head(repository: &Repository) -> crate::Result<(Option<ObjectId>, String)> {
+    let refs = RefHandler::new(repository);
+    let head_id = refs.read_ref("HEAD")?;
+    let name = match (refs.current_branch()?, &head_id) {
+        (Some(branch), _) => branch,
+        (None, Some(head_id)) => head_id.to_string(),
+        (None, None) => String::from("HEAD"),
+    };
+    Ok((head_id, name))
+}
+
+/// Create the branch at the start point and switch to it. If the switch fails, the branch is
+/// removed again.
+fn create_and_switch(
+    branch: &str,
+    start_point: &str,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+    progress: &mut dyn Progress,
+) -> crate::Result<()> {
+    let refs = RefHandler::new(repository);
+    let commit_id = Revision::parse(start_point)?.resolve(repository)?;
+    let (previous_id, previous_name) = describe_head(repository)?;
+    let mut index_lockfile = repository.load_index()?;
+    refs.create_ref(branch, &commit_id)?;
+
+    let result = checkout_commit(&commit_id, repository, index_lockfile.as_mut(), progress)
+        .and_then(|_| Ok(index_lockfile.write()?))
+        .and_then(|_| refs.attach_head(branch));
+    if let Err(error) = result {
+        // the failure to switch is what should be reported, even if the branch can't be removed
+        let _ = refs.delete_ref(branch);
+        return Err(error);
+    }
+
+    let message = format!("checkout: moving from {} to {}", previous_name, branch);
+    reflog::append_head_entry(previous_id.as_ref(), &commit_id, &message, repository)?;
+    writer.writeln(format!("Switched to a new branch '{}'", branch))?;
+    Ok(())
+}
+
+/// Update the worktree and index from the HEAD commit to the given commit. Fails without changing
+/// anything if a file that differs between the commits has local changes, or if an untracked
+/// file is in the way, or if there are unresolved merge conflicts.
+pub fn checkout_commit(
+    commit_id: &ObjectId,
+    repository: &Repository,
+    index: &mut Index,
+    progress: &mut dyn Progress,
+) -> crate::Result<()> {
+    if index.has_unmerged_entries() {
+        let message = String::from("you need to resolve your current index first");
+        return Err(crate::Error::Fatal(None, message));
+    }
+    let worktree_root = repository.worktree().root();
+    let head_tree = RefHandler::new(repository)
+        .head()
+        .ok()
+        .map(|head_id| repository.database.peel_to_tree(&head_id))
+        .transpose()?;
+    let target_tree = repository.database.peel_to_tree(commit_id)?;
+    let changes = diff::compare_trees(head_tree.as_ref(), Some(&target_tree), repository)?;
+
+    let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
+    let locally_changed: HashSet<PathBuf> =
+        status::resolve_files_with_staged_changes(&path_to_committed_id, repository, index)?
+            .into_iter()
+            .chain(status::resolve_files_with_unstaged_changes(
+                repository, index,
+            ))
+            .collect();
+
+    let mut overwritten = vec![];
+    let mut untracked_in_the_way = vec![];
+    for change in &changes {
+        let absolute_path = worktree_root.join(&change.path);
+        if locally_changed.contains(&absolute_path) {
+            overwritten.push(change.path.display().to_string());
+        } else if matches!(change.change_type, ChangeType::Created)
+            && absolute_path.exists()
+            && !index.has_entry(&change.path)
+        {
+            untracked_in_the_way.push(change.path.display().to_string());
+        }
+    }
+    if !overwritten.is_empty() {
+        let message = format!(
+            "Your local changes to the following files would be overwritten by checkout:\n\t{}\n\
+             Please commit your changes or stash them before you switch branches.",
+            overwritten.join("\n\t")
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+    if !untracked_in_the_way.is_empty() {
+        let message = format!(
+            "The following untracked working tree files would be overwritten by checkout:\n\t{}\n\
+             Please move or remove them before you switch branches.",
+            untracked_in_the_way.join("\n\t")
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let mut target_entries = vec![];
+    repository.database.extract_entries_from_tree(
+        String::new(),
+        &target_tree,
+        &mut target_entries,
+    )?;
+    let target_entries: HashMap<PathBuf, TreeEntry> = target_entries
+        .into_iter()
+        .map(|(path, entry)| (PathBuf::from(path), entry))
+        .collect();
+
+    progress.start("Updating files", Some(changes.len() as u64));
+    for (i, change) in changes.into_iter().enumerate() {
+        let absolute_path = worktree_root.join(&change.path);
+        match change.change_type {
+            ChangeType::Deleted => {
+                if absolute_path.exists() {
+                    fs::remove_file(&absolute_path)?;
+                }
+                file::remove_empty_parents(&absolute_path, worktree_root)?;
+                index.remove(&change.path);
+            }
+            ChangeType::Created | ChangeType::Modified => {
+                let entry = &target_entries[&change.path];
+                restore::write_tree_entry(&absolute_path, entry, repository)?;
+                let metadata = fs::metadata(&absolute_path)?;
+                index.add_entry(IndexEntry::new(
+                    &change.path,
+                    entry.object_id.clone(),
+                    &metadata,
+                ));
+            }
+            ChangeType::Renamed { .. } | ChangeType::Unmerged(_) => {
+                unreachable!("trees are compared without renames or conflicts")
+            }
+        }
+        progress.update(i as u64 + 1);
+    }
+    progress.finish();
+
+    Ok(())
+}
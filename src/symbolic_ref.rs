@@ -0,0 +1,43 @@
+//! Reading and writing symbolic refs, such as `HEAD` pointing to the current branch.
+
+use crate::{
+    output::OutputWriter,
+    refs::{self, RefHandler},
+    workspace::Repository,
+};
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Print the target without its `refs/heads/` or `refs/tags/` prefix.
+    #[builder(default)]
+    pub short: bool,
+}
+
+/// Print the ref that the symbolic ref points to.
+pub fn read_symbolic_ref(
+    name: &str,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    let target = RefHandler::new(repository)
+        .read_symbolic_ref(name)?
+        .ok_or_else(|| crate::Error::Fatal(None, format!("ref {} is not a symbolic ref", name)))?;
+    let target = if options.short {
+        refs::shorten_ref_name(&target)
+    } else {
+        &target
+    };
+    writer.writeln(target.to_owned())?;
+    Ok(())
+}
+
+/// Point the symbolic ref to the target ref, which must be under `refs/`. The target need not
+/// exist, as for a branch that has no commits yet.
+pub fn write_symbolic_ref(name: &str, target: &str, repository: &Repository) -> crate::Result<()> {
+    if !target.starts_with("refs/") {
+        let message = format!("Refusing to point {} outside of refs/", name);
+        return Err(crate::Error::Fatal(None, message));
+    }
+    RefHandler::new(repository).write_symbolic_ref(name, target)
+}
@@ -0,0 +1,27 @@
+//! Opt-in tracing of what Rut does under the hood, such as reading and writing objects, taking
+//! locks and updating refs, written to stderr to help debug commands that are slow or misbehave.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::Local;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn tracing on or off for the rest of the process.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Write the message to stderr with the time and the category, such as `object` or `ref`, if
+/// tracing is enabled. The message is only formatted if it's written.
+pub fn trace(category: &str, message: fmt::Arguments) {
+    if is_enabled() {
+        let time = Local::now().format("%H:%M:%S%.6f");
+        eprintln!("{} trace: {}: {}", time, category, message);
+    }
+}
@@ -0,0 +1,202 @@
+//! A cache of the entries of worktree directories, keyed on the mtime of each directory. Adding,
+//! removing or renaming an entry in a directory updates its mtime, so the entries of a directory
+//! with an unchanged mtime can be reused without reading the directory. Whether an entry is
+//! untracked is still decided against the index, as staging a file does not touch the worktree.
+//!
+//! The cache is stored in its own file in the git directory rather than as an index extension, as
+//! Git warns about every optional index extension that it doesn't know.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::file;
+use crate::platform::FileStat;
+use crate::workspace::Repository;
+
+const FILENAME: &str = "rut-untracked-cache";
+const SIGNATURE: &[u8; 4] = b"RUTU";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UntrackedCache {
+    directories: HashMap<PathBuf, CachedDirectory>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CachedDirectory {
+    mtime_seconds: u32,
+    mtime_nanoseconds: u32,
+    entries: Vec<DirectoryEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+impl UntrackedCache {
+    /// Load the cache of the worktree of the repository. A missing or corrupt cache is treated as
+    /// empty, as it can always be rebuilt.
+    pub fn load(repository: &Repository) -> UntrackedCache {
+        file::read_file(repository.git_dir().join(FILENAME))
+            .ok()
+            .filter(|bytes| bytes.starts_with(SIGNATURE))
+            .and_then(|bytes| UntrackedCache::from_bytes(&bytes[SIGNATURE.len()..]).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, repository: &Repository) -> io::Result<()> {
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(self.as_vec());
+        file::atomic_write(&repository.git_dir().join(FILENAME), &bytes)
+    }
+
+    /// List the entries of the directory, given relative to the worktree root. The entries are
+    /// taken from the previous cache if the mtime of the directory is unchanged, and are otherwise
+    /// read from the file system. Either way, they are recorded in this cache.
+    ///
+    /// Directories modified within the current second are not recorded, as a later modification
+    /// within the same second might leave the mtime unchanged on file systems with coarse
+    /// timestamps.
+    pub fn read_dir(
+        &mut self,
+        previous: &UntrackedCache,
+        root: &Path,
+        directory: &Path,
+    ) -> io::Result<Vec<DirectoryEntry>> {
+        let absolute_path = root.join(directory);
+        let stat = FileStat::new(&fs::metadata(&absolute_path)?);
+        let is_unchanged = |cached: &&CachedDirectory| {
+            cached.mtime_seconds == stat.mtime_seconds
+                && cached.mtime_nanoseconds == stat.mtime_nanoseconds
+        };
+
+        let entries = match previous.directories.get(directory).filter(is_unchanged) {
+            Some(cached) => cached.entries.clone(),
+            None => {
+                let mut entries = vec![];
+                for dir_entry in fs::read_dir(&absolute_path)? {
+                    let dir_entry = dir_entry?;
+                    entries.push(DirectoryEntry {
+                        name: dir_entry.file_name().to_string_lossy().into_owned(),
+                        is_dir: dir_entry.file_type()?.is_dir(),
+                    });
+                }
+                entries
+            }
+        };
+
+        let now_seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if (stat.mtime_seconds as u64) < now_seconds {
+            self.directories.insert(
+                directory.to_owned(),
+                CachedDirectory {
+                    mtime_seconds: stat.mtime_seconds,
+                    mtime_nanoseconds: stat.mtime_nanoseconds,
+                    entries: entries.clone(),
+                },
+            );
+        }
+
+        Ok(entries)
+    }
+
+    /// Parse the content of the cache, which is the number of directories followed by each
+    /// directory. A directory is given as its path and a NUL byte, its mtime seconds and
+    /// nanoseconds and its number of entries as 32-bit integers, and finally its entries. Each
+    /// entry is a byte that is 1 for directories and 0 otherwise, followed by the NUL-terminated
+    /// name.
+    fn from_bytes(bytes: &[u8]) -> Result<UntrackedCache, String> {
+        let mut reader = Reader { bytes, position: 0 };
+        let mut directories = HashMap::new();
+
+        for _ in 0..reader.read_u32()? {
+            let path = PathBuf::from(reader.read_string()?);
+            let mtime_seconds = reader.read_u32()?;
+            let mtime_nanoseconds = reader.read_u32()?;
+            let mut entries = vec![];
+            for _ in 0..reader.read_u32()? {
+                let is_dir = reader.read_byte()? == 1;
+                let name = reader.read_string()?;
+                entries.push(DirectoryEntry { name, is_dir });
+            }
+            directories.insert(
+                path,
+                CachedDirectory {
+                    mtime_seconds,
+                    mtime_nanoseconds,
+                    entries,
+                },
+            );
+        }
+
+        Ok(UntrackedCache { directories })
+    }
+
+    fn as_vec(&self) -> Vec<u8> {
+        let mut directories = self.directories.iter().collect::<Vec<_>>();
+        directories.sort_by_key(|(path, _)| *path);
+
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&(directories.len() as u32).to_be_bytes());
+        for (path, directory) in directories {
+            bytes.extend_from_slice(path.to_string_lossy().as_bytes());
+            bytes.push(0);
+            bytes.extend_from_slice(&directory.mtime_seconds.to_be_bytes());
+            bytes.extend_from_slice(&directory.mtime_nanoseconds.to_be_bytes());
+            bytes.extend_from_slice(&(directory.entries.len() as u32).to_be_bytes());
+            for entry in &directory.entries {
+                bytes.push(entry.is_dir as u8);
+                bytes.extend_from_slice(entry.name.as_bytes());
+                bytes.push(0);
+            }
+        }
+        bytes
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn read_u32(&mut self) -> Result<u32, String> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_byte(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_string(&mut self) -> Result<String, String> {
+        let remaining = &self.bytes[self.position..];
+        let length = remaining
+            .iter()
+            .position(|byte| *byte == 0)
+            .ok_or_else(corrupt)?;
+        let string = String::from_utf8(remaining[..length].to_vec()).map_err(|_| corrupt())?;
+        self.position += length + 1;
+        Ok(string)
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.position..(self.position + count))
+            .ok_or_else(corrupt)?;
+        self.position += count;
+        Ok(bytes)
+    }
+}
+
+fn corrupt() -> String {
+    String::from("corrupt untracked cache")
+}
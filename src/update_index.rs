@@ -0,0 +1,151 @@
+//! Low-level changes to the index, such as staging files without pathspec matching or adding
+//! entries for objects that aren't in the worktree.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::add;
+use crate::index::{FileMode, IndexEntry};
+use crate::objects::{ObjectId, ObjectType};
+use crate::output::OutputWriter;
+use crate::status;
+use crate::workspace::Repository;
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    /// Add files that aren't in the index yet, instead of failing.
+    #[builder(default)]
+    pub add: bool,
+
+    /// Remove the entries of files that no longer exist in the worktree, instead of failing.
+    #[builder(default)]
+    pub remove: bool,
+
+    /// Refresh the stat information of all entries whose files are unchanged, and report the
+    /// files that need to be updated.
+    #[builder(default)]
+    pub refresh: bool,
+
+    /// Entries to add as they are, whether their files and objects exist or not.
+    #[builder(default)]
+    pub cacheinfo: Vec<CacheInfo>,
+}
+
+/// An index entry given by its mode, object id and path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheInfo {
+    pub mode: FileMode,
+    pub object_id: ObjectId,
+    pub path: PathBuf,
+}
+
+/// Parse the argument to `--cacheinfo`, on the form `<mode>,<object id>,<path>`.
+///
+/// # Examples
+/// ```
+/// use std::path::PathBuf;
+/// use rut::index::FileMode;
+/// use rut::update_index;
+///
+/// let cacheinfo =
+///     update_index::parse_cacheinfo("100755,e69de29bb2d1d6434b8b29ae775ad8c2e48c5391,run.sh")
+///         .unwrap();
+/// assert_eq!(cacheinfo.mode, FileMode::Executable);
+/// assert_eq!(cacheinfo.path, PathBuf::from("run.sh"));
+/// assert!(update_index::parse_cacheinfo("100644,not-an-id,file.txt").is_err());
+/// ```
+pub fn parse_cacheinfo(s: &str) -> Result<CacheInfo, String> {
+    let error = || format!("invalid cacheinfo '{}', expected <mode>,<object>,<path>", s);
+    let mut parts = s.splitn(3, ',');
+    let (Some(mode), Some(object_id), Some(path)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(error());
+    };
+    let mode = match mode {
+        "100644" | "644" => FileMode::Regular,
+        "100755" | "755" => FileMode::Executable,
+        "160000" => FileMode::Gitlink,
+        _ => return Err(error()),
+    };
+    let object_id = ObjectId::from_sha(object_id).map_err(|_| error())?;
+    if path.is_empty() {
+        return Err(error());
+    }
+    Ok(CacheInfo {
+        mode,
+        object_id,
+        path: PathBuf::from(path),
+    })
+}
+
+/// Apply the cacheinfo entries, then update the entries of the paths, given relative to the root
+/// of the worktree, from the worktree, and finally refresh the index if asked to. Returns false if
+/// the refresh found files that need to be updated.
+pub fn update_index<P: AsRef<Path>>(
+    paths: &[P],
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<bool> {
+    let mut index_lockfile = repository.load_index()?;
+    let index = index_lockfile.as_mut();
+
+    for cacheinfo in &options.cacheinfo {
+        if !options.add && !index.has_entry(&cacheinfo.path) {
+            let message = format!(
+                "{}: cannot add to the index - missing --add option?",
+                cacheinfo.path.display()
+            );
+            return Err(crate::Error::Fatal(None, message));
+        }
+        // the size of an existing blob lets an identical file be recognized by its content
+        let file_size = match repository.database.load_object(&cacheinfo.object_id) {
+            Ok((ObjectType::Blob, content)) => content.len() as u32,
+            _ => 0,
+        };
+        index.add_entry(IndexEntry::from_object(
+            &cacheinfo.path,
+            cacheinfo.object_id.clone(),
+            cacheinfo.mode,
+            file_size,
+        ));
+    }
+
+    let root = repository.worktree().root();
+    for path in paths {
+        let path = path.as_ref();
+        let absolute_path = root.join(path);
+        if fs::symlink_metadata(&absolute_path).is_err() {
+            if !options.remove {
+                let message = format!("{}: does not exist and --remove not passed", path.display());
+                return Err(crate::Error::Fatal(None, message));
+            }
+            index.remove(path);
+        } else if absolute_path.is_dir() {
+            let message = format!(
+                "{}: is a directory - add files inside instead",
+                path.display()
+            );
+            return Err(crate::Error::Fatal(None, message));
+        } else if !options.add && !index.has_entry(path) && !index.is_unmerged(path) {
+            let message = format!(
+                "{}: cannot add to the index - missing --add option?",
+                path.display()
+            );
+            return Err(crate::Error::Fatal(None, message));
+        } else {
+            add::add_file(&absolute_path, index, repository)?;
+        }
+    }
+
+    let mut is_up_to_date = true;
+    if options.refresh {
+        for change in status::scan_worktree(repository, index).unstaged_changes {
+            writer.writeln(format!("{}: needs update", change.path.display()))?;
+            is_up_to_date = false;
+        }
+    }
+
+    index_lockfile.write()?;
+    Ok(is_up_to_date)
+}
@@ -0,0 +1,54 @@
+//! Updating and deleting refs directly, with an optional check of their old values, as plumbing
+//! for scripts.
+
+use crate::{
+    objects::ObjectId,
+    refs::{ExpectedValue, RefHandler, Revision},
+    workspace::Repository,
+};
+
+/// Point the ref, given by its full name such as `refs/heads/main`, to the object that the new
+/// value resolves to. If an old value is given, the ref must point to it, or not exist if the old
+/// value is empty or the null id. Symbolic refs like `HEAD` are updated through the refs they
+/// point to.
+pub fn update_ref(
+    ref_name: &str,
+    new_value: &str,
+    old_value: Option<&str>,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let object_id = Revision::parse(new_value)?.resolve(repository)?;
+    let expected = resolve_expected_value(old_value, repository)?;
+    RefHandler::new(repository)
+        .transaction()
+        .update(ref_name, &object_id, expected)
+        .commit()
+}
+
+/// Delete the ref, given by its full name. If an old value is given, the ref must point to it.
+pub fn delete_ref(
+    ref_name: &str,
+    old_value: Option<&str>,
+    repository: &Repository,
+) -> crate::Result<()> {
+    let expected = resolve_expected_value(old_value, repository)?;
+    RefHandler::new(repository)
+        .transaction()
+        .delete(ref_name, expected)
+        .commit()
+}
+
+fn resolve_expected_value(
+    old_value: Option<&str>,
+    repository: &Repository,
+) -> crate::Result<ExpectedValue> {
+    let null_id = ObjectId::null(repository.hash_algorithm());
+    match old_value {
+        None => Ok(ExpectedValue::Any),
+        Some("") => Ok(ExpectedValue::Missing),
+        Some(old_value) if old_value == null_id.to_string() => Ok(ExpectedValue::Missing),
+        Some(old_value) => Ok(ExpectedValue::Object(
+            Revision::parse(old_value)?.resolve(repository)?,
+        )),
+    }
+}
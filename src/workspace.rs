@@ -1,33 +1,82 @@
+use std::collections::HashSet;
 use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::io::BufWriter;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process;
 use std::str;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use flate2::bufread::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 
-use crate::config;
-use crate::config::Config;
+use crate::config::{Config, ConfigLevel};
 use crate::file;
 use crate::file::{LockFile, LockFileResource};
-use crate::hex;
+use crate::filter::Filters;
+use crate::hashing::{HashAlgorithm, Hasher};
 use crate::index::FileMode;
 use crate::index::Index;
+use crate::object_cache::{self, ObjectCache};
 use crate::objects::Blob;
-use crate::objects::{Author, Commit, GitObject, ObjectId, Tree, TreeEntry};
+use crate::objects::{
+    Author, Commit, GitObject, ObjectId, ObjectType, Tree, TreeEntry, MIN_ABBREV_LENGTH,
+};
+use crate::platform;
+use crate::trace;
+
+/// The size of the buffer that files are streamed through when they are stored as blobs.
+const STREAM_BUFFER_SIZE: usize = 64 << 10;
+
+/// Distinguishes the temporary files of objects that are being written at the same time.
+static TEMPORARY_OBJECT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Statistics of the object database, as reported by `count-objects`. Sizes are in bytes.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DatabaseStats {
+    pub loose_count: usize,
+    pub loose_size: u64,
+    /// The number of objects in packs.
+    pub packed_count: usize,
+    pub pack_count: usize,
+    pub pack_size: u64,
+    /// The number of loose objects that are also in packs.
+    pub prune_packable_count: usize,
+    /// Files in the object directories that are neither objects nor parts of packs.
+    pub garbage: Vec<PathBuf>,
+    pub garbage_size: u64,
+}
 
 pub struct Database {
     git_dir: PathBuf,
+    hash_algorithm: HashAlgorithm,
+    cache: Mutex<ObjectCache>,
 }
 
 impl Database {
-    pub fn new(git_dir: PathBuf) -> Database {
-        Database { git_dir }
+    pub fn new(git_dir: PathBuf, hash_algorithm: HashAlgorithm) -> Database {
+        Database {
+            git_dir,
+            hash_algorithm,
+            cache: Mutex::new(ObjectCache::new(object_cache::DEFAULT_LIMIT)),
+        }
+    }
+
+    /// Bound the total size of the loaded objects that are kept in memory, in bytes.
+    pub fn with_cache_limit(mut self, limit: usize) -> Database {
+        self.cache = Mutex::new(ObjectCache::new(limit));
+        self
+    }
+
+    /// The hash algorithm that object ids are computed with.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
     }
 
     pub fn store_object<'a>(
@@ -44,11 +93,62 @@ impl Database {
         let object_filepath = dirpath.join(object_id.filename());
         if !object_filepath.exists() {
             file::atomic_write(&object_filepath, &compressed_bytes)?;
+            trace::trace("object", format_args!("wrote {}", object_id));
         }
 
         Ok(object_filepath)
     }
 
+    /// Store the content of the file as a blob and return its id. The file is hashed and
+    /// compressed as it is read, through a buffer of fixed size, so that large files never have
+    /// to fit in memory.
+    pub fn store_blob_from_file(&self, path: &Path) -> io::Result<ObjectId> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+        let header = format!("blob {}\0", size);
+
+        let objects_dir = self.git_dir.join("objects");
+        let temporary_path = objects_dir.join(format!(
+            "tmp_obj_{}_{}",
+            process::id(),
+            TEMPORARY_OBJECT_COUNTER.fetch_add(1, atomic::Ordering::Relaxed)
+        ));
+        let mut hasher = self.hash_algorithm.hasher();
+        hasher.update(header.as_bytes());
+        let temporary_file = File::create(&temporary_path)?;
+        let mut encoder = ZlibEncoder::new(BufWriter::new(temporary_file), Compression::default());
+        encoder.write_all(header.as_bytes())?;
+
+        let bytes_written = match stream_content(&mut file, &mut hasher, &mut encoder) {
+            Ok(bytes_written) => bytes_written,
+            Err(error) => {
+                fs::remove_file(&temporary_path)?;
+                return Err(error);
+            }
+        };
+        if bytes_written != size {
+            fs::remove_file(&temporary_path)?;
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} changed while it was read", path.display()),
+            ));
+        }
+        encoder.finish()?.flush()?;
+
+        let object_id = ObjectId::from_sha_bytes(&hasher.finalize()).unwrap();
+        let dirpath = objects_dir.join(object_id.dirname());
+        fs::create_dir_all(&dirpath)?;
+        let object_filepath = dirpath.join(object_id.filename());
+        if object_filepath.exists() {
+            fs::remove_file(&temporary_path)?;
+        } else {
+            fs::rename(&temporary_path, &object_filepath)?;
+            trace::trace("object", format_args!("wrote {}", object_id));
+        }
+
+        Ok(object_id)
+    }
+
     fn compress(content: &[u8]) -> io::Result<Vec<u8>> {
         let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
         encoder.write_all(content)?;
@@ -56,11 +156,25 @@ impl Database {
         Ok(compressed_bytes)
     }
 
-    /// Expand the start of an object id into a full object id, if it is unambiguous.
+    /// Find all objects whose ids start with the prefix.
     pub fn prefix_match(&self, id_prefix: &str) -> crate::Result<Vec<ObjectId>> {
+        if id_prefix.is_empty() || !id_prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(vec![]);
+        }
+        let id_prefix = id_prefix.to_ascii_lowercase();
+        let id_prefix = id_prefix.as_str();
         let objects_dir = self.git_dir.join("objects");
 
-        let prefix_dirs = objects_dir.read_dir()?.filter_map(|entry| entry.ok());
+        // objects are fanned out into directories named by the first two characters of their ids
+        let prefix_dirs = objects_dir
+            .read_dir()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                let dirname = entry.file_name();
+                let dirname = dirname.to_str().unwrap_or_default();
+                dirname.len() == 2
+                    && (dirname.starts_with(id_prefix) || id_prefix.starts_with(dirname))
+            });
 
         let mut all_matches: Vec<ObjectId> = vec![];
         for prefix_dir in prefix_dirs {
@@ -90,86 +204,291 @@ impl Database {
         Ok(all_matches)
     }
 
-    pub fn load_commit(&self, commit_id: &ObjectId) -> io::Result<Commit> {
-        let content = self.load_data(commit_id)?;
-        Ok(self.parse_commit(&mut content.into_iter()))
+    /// List the ids of all loose objects, sorted.
+    pub fn list_objects(&self) -> crate::Result<Vec<ObjectId>> {
+        let objects_dir = self.git_dir.join("objects");
+        if !objects_dir.is_dir() {
+            return Ok(vec![]);
+        }
+
+        let hex_size = self.hash_algorithm.hex_size();
+        let mut object_ids = vec![];
+        for fanout_dir in objects_dir.read_dir()? {
+            let fanout_dir = fanout_dir?;
+            let dirname = fanout_dir.file_name().to_string_lossy().into_owned();
+            if dirname.len() != 2 || !fanout_dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fanout_dir.path().read_dir()? {
+                let filename = file?.file_name().to_string_lossy().into_owned();
+                if dirname.len() + filename.len() != hex_size {
+                    continue;
+                }
+                if let Ok(object_id) = ObjectId::from_sha(&format!("{}{}", dirname, filename)) {
+                    object_ids.push(object_id);
+                }
+            }
+        }
+
+        object_ids.sort();
+        Ok(object_ids)
     }
 
-    fn load_data(&self, object_id: &ObjectId) -> io::Result<Vec<u8>> {
-        let object_path = self
-            .git_dir
+    /// The path of the file of the loose object, which may not exist.
+    pub fn object_path(&self, object_id: &ObjectId) -> PathBuf {
+        self.git_dir
             .join("objects")
             .join(object_id.dirname())
-            .join(object_id.filename());
-        let data = Database::decompress(object_path)?;
+            .join(object_id.filename())
+    }
 
-        // TODO handle bad/unexpected object type
-        let object_type: Vec<u8> = data
-            .iter()
-            .map(|byte| byte.to_owned())
-            .take_while(|byte| byte != &b' ')
-            .collect();
+    /// Whether the content of the object hashes to its id, as read from disk.
+    pub fn verify_object(&self, object_id: &ObjectId) -> crate::Result<bool> {
+        let (object_type, content) = self.read_object(object_id)?;
+        let mut hasher = self.hash_algorithm.hasher();
+        hasher.update(format!("{} {}\0", object_type, content.len()).as_bytes());
+        hasher.update(&content);
+        Ok(hasher.finalize() == object_id.bytes())
+    }
 
-        let size_start = object_type.len() + 1;
-        let size: Vec<u8> = data[size_start..]
-            .iter()
-            .map(|byte| byte.to_owned())
-            .take_while(|byte| byte != &0)
-            .collect();
+    /// Count the loose objects and packs and the space they take up on disk, and find any
+    /// garbage files among them.
+    pub fn stats(&self) -> crate::Result<DatabaseStats> {
+        let mut stats = DatabaseStats::default();
+        let objects_dir = self.git_dir.join("objects");
+        if !objects_dir.is_dir() {
+            return Ok(stats);
+        }
 
-        let content_start = size_start + size.len() + 1;
-        let content = data[content_start..].to_owned();
+        let mut packed_ids = HashSet::new();
+        let pack_dir = objects_dir.join("pack");
+        if pack_dir.is_dir() {
+            for file in pack_dir.read_dir()? {
+                let file = file?;
+                let path = file.path();
+                let size = platform::disk_usage(&file.metadata()?);
+                match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("pack") => {
+                        stats.pack_count += 1;
+                        stats.pack_size += size;
+                    }
+                    Some("idx") => {
+                        let pack_ids = self.read_pack_index(&path)?;
+                        stats.packed_count += pack_ids.len();
+                        stats.pack_size += size;
+                        packed_ids.extend(pack_ids);
+                    }
+                    Some("keep" | "rev" | "bitmap" | "promisor" | "mtimes") => (),
+                    _ => {
+                        stats.garbage.push(path);
+                        stats.garbage_size += size;
+                    }
+                }
+            }
+        }
 
+        let hex_size = self.hash_algorithm.hex_size();
+        for fanout_dir in objects_dir.read_dir()? {
+            let fanout_dir = fanout_dir?;
+            let dirname = fanout_dir.file_name().to_string_lossy().into_owned();
+            let is_fanout_dir =
+                dirname.len() == 2 && dirname.chars().all(|c| c.is_ascii_hexdigit());
+            if !is_fanout_dir || !fanout_dir.file_type()?.is_dir() {
+                continue;
+            }
+            for file in fanout_dir.path().read_dir()? {
+                let file = file?;
+                let size = platform::disk_usage(&file.metadata()?);
+                let filename = file.file_name().to_string_lossy().into_owned();
+                let object_id = Some(format!("{}{}", dirname, filename))
+                    .filter(|hex| hex.len() == hex_size)
+                    .and_then(|hex| ObjectId::from_sha(&hex).ok());
+                match object_id {
+                    Some(object_id) => {
+                        stats.loose_count += 1;
+                        stats.loose_size += size;
+                        if packed_ids.contains(&object_id) {
+                            stats.prune_packable_count += 1;
+                        }
+                    }
+                    None => {
+                        stats.garbage.push(file.path());
+                        stats.garbage_size += size;
+                    }
+                }
+            }
+        }
+
+        stats.garbage.sort();
+        Ok(stats)
+    }
+
+    /// Read the ids of the objects in a pack from its index, which starts with a table of 256
+    /// cumulative counts of the objects by the first byte of their ids. Version 2 indexes are
+    /// preceded by a header and list the ids after the table, while version 1 indexes list each
+    /// id after the offset of the object in the pack.
+    fn read_pack_index(&self, path: &Path) -> crate::Result<Vec<ObjectId>> {
+        const FANOUT_SIZE: usize = 256 * 4;
+        const VERSION_2_HEADER: &[u8] = b"\xfftOc\0\0\0\x02";
+
+        let data = fs::read(path)?;
+        let corrupt =
+            || crate::Error::Fatal(None, format!("corrupt pack index {}", path.display()));
+        let (fanout_start, id_offset, id_stride) = if data.starts_with(VERSION_2_HEADER) {
+            let fanout_start = VERSION_2_HEADER.len();
+            (fanout_start, fanout_start + FANOUT_SIZE, 0)
+        } else {
+            (0, FANOUT_SIZE + 4, 4)
+        };
+        let count_bytes = data
+            .get(fanout_start + FANOUT_SIZE - 4..fanout_start + FANOUT_SIZE)
+            .ok_or_else(corrupt)?;
+        let count = u32::from_be_bytes(count_bytes.try_into().unwrap()) as usize;
+
+        let raw_size = self.hash_algorithm.raw_size();
+        (0..count)
+            .map(|i| {
+                let start = id_offset + i * (raw_size + id_stride);
+                let bytes = data.get(start..start + raw_size).ok_or_else(corrupt)?;
+                Ok(ObjectId::from_sha_bytes(bytes).unwrap())
+            })
+            .collect()
+    }
+
+    /// Abbreviate the object id to the shortest prefix of at least [`MIN_ABBREV_LENGTH`]
+    /// characters that no other object in the database starts with.
+    pub fn abbreviate(&self, object_id: &ObjectId) -> String {
+        self.abbreviate_to(object_id, MIN_ABBREV_LENGTH)
+    }
+
+    /// Abbreviate the object id like [`Database::abbreviate`], but to at least the given number
+    /// of characters. Prefixes shorter than four characters are never used.
+    pub fn abbreviate_to(&self, object_id: &ObjectId, min_length: usize) -> String {
+        let id = object_id.to_string();
+        let filename = object_id.filename();
+        let fanout_dir = self.git_dir.join("objects").join(object_id.dirname());
+        let longest_shared_prefix = fanout_dir
+            .read_dir()
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| *name != filename)
+            .map(|name| {
+                let shared_chars = name.chars().zip(filename.chars());
+                2 + shared_chars.take_while(|(a, b)| a == b).count()
+            })
+            .max()
+            .unwrap_or(0);
+
+        let length = (longest_shared_prefix + 1).clamp(min_length.max(4), id.len());
+        id[..length].to_owned()
+    }
+
+    pub fn load_commit(&self, commit_id: &ObjectId) -> crate::Result<Commit> {
+        let content = self.load_data(commit_id)?;
+        self.parse_commit(&mut content.iter().copied())
+            .map(|commit| commit.with_known_id(commit_id.clone()))
+            .map_err(|reason| crate::Error::CorruptObject(commit_id.clone(), reason))
+    }
+
+    fn load_data(&self, object_id: &ObjectId) -> crate::Result<Arc<[u8]>> {
+        let (_, content) = self.load_object(object_id)?;
         Ok(content)
     }
 
-    fn parse_commit(&self, content: &mut impl Iterator<Item = u8>) -> Commit {
-        let tree_line = next_line(content);
-        let author_or_parent_line = next_line(content);
+    /// Load the type and the raw content of any object, without parsing the content. Recently
+    /// loaded objects are served from memory, and share their content with the cache.
+    pub fn load_object(&self, object_id: &ObjectId) -> crate::Result<(ObjectType, Arc<[u8]>)> {
+        if let Some(object) = self.cache.lock().unwrap().get(object_id) {
+            return Ok(object);
+        }
 
-        let space = b' ';
-        let is_not_space = |item: &u8| *item != space;
+        let (object_type, content) = self.read_object(object_id)?;
+        let content: Arc<[u8]> = Arc::from(content);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(object_id.clone(), object_type, Arc::clone(&content));
+        Ok((object_type, content))
+    }
 
-        let (parent_line, author_line) = {
-            let line_start_bytes: Vec<u8> = author_or_parent_line
-                .iter()
-                .map(|byte| byte.to_owned())
-                .take_while(is_not_space)
-                .collect();
-            let line_start = str::from_utf8(&line_start_bytes).unwrap();
-            if line_start == "parent" {
-                (Some(author_or_parent_line), next_line(content))
-            } else if line_start == "author" {
-                (None, author_or_parent_line)
-            } else {
-                panic!("failed to parse commit");
-            }
-        };
+    fn read_object(&self, object_id: &ObjectId) -> crate::Result<(ObjectType, Vec<u8>)> {
+        let data = Database::decompress(self.object_path(object_id))?;
+        let corrupt = |reason: &str| crate::Error::CorruptObject(object_id.clone(), reason.into());
 
-        let parent = self
-            .parse_parent(parent_line.as_ref())
-            .map(|parent| ObjectId::from_sha(&parent).unwrap());
-        let (author_name, author_email, timestamp) = parse_author_details(&author_line);
+        let (header, content) = data
+            .iter()
+            .position(|byte| *byte == 0)
+            .map(|header_end| (&data[..header_end], &data[header_end + 1..]))
+            .ok_or_else(|| corrupt("missing header"))?;
+        let (object_type, size) = str::from_utf8(header)
+            .ok()
+            .and_then(|header| header.split_once(' '))
+            .ok_or_else(|| corrupt("malformed header"))?;
+        let object_type = ObjectType::parse(object_type).ok_or_else(|| corrupt("unknown type"))?;
+        if size.parse::<usize>().ok() != Some(content.len()) {
+            return Err(corrupt("size does not match content"));
+        }
+        trace::trace(
+            "object",
+            format_args!(
+                "read {} ({}, {} bytes)",
+                object_id,
+                object_type,
+                content.len()
+            ),
+        );
+
+        Ok((object_type, content.to_owned()))
+    }
 
-        let tree_object_id_bytes: Vec<u8> = tree_line
-            .into_iter()
-            .skip_while(is_not_space)
-            .skip(1)
-            .collect();
-        let tree_object_id = ObjectId::from_utf8_encoded_sha(&tree_object_id_bytes).unwrap();
+    fn parse_commit(&self, content: &mut impl Iterator<Item = u8>) -> Result<Commit, String> {
+        let mut tree = None;
+        let mut parents = vec![];
+        let mut author = None;
+        let mut committer = None;
+        // headers rut doesn't know about, such as `gpgsig` and `encoding`, are kept as raw bytes
+        // along with all continuation lines so that the commit serializes to the same bytes
+        let mut extra_headers = vec![];
+
+        loop {
+            let line = next_line(content);
+            if line.is_empty() {
+                break;
+            }
 
-        let _committer_line = next_line(content); // TODO handle committer line
-        let _empty_line = next_line(content);
-        let message_bytes: Vec<u8> = content.collect();
+            if line.starts_with(b"tree ") {
+                tree = ObjectId::from_utf8_encoded_sha(&line[b"tree ".len()..]).ok();
+            } else if line.starts_with(b"parent ") {
+                let parent = self
+                    .parse_parent(Some(&line))
+                    .ok_or_else(|| String::from("malformed parent"))?;
+                parents.push(ObjectId::from_sha(&parent)?);
+            } else if line.starts_with(b"author ") {
+                author = Some(parse_author_details(&line)?);
+            } else if line.starts_with(b"committer ") {
+                committer = Some(parse_author_details(&line)?);
+            } else {
+                extra_headers.extend_from_slice(&line);
+                extra_headers.push(b'\n');
+            }
+        }
 
-        let message = str::from_utf8(&message_bytes).unwrap().to_owned();
+        let raw_message: Vec<u8> = content.collect();
 
-        let author = Author {
-            name: author_name,
-            email: author_email,
-        };
+        let tree = tree.ok_or_else(|| String::from("missing tree"))?;
+        let author = author.ok_or_else(|| String::from("missing author"))?;
+        let committer = committer.ok_or_else(|| String::from("missing committer"))?;
 
-        Commit::new(tree_object_id, author, message, parent, timestamp)
+        Ok(Commit::from_parts(
+            tree,
+            parents,
+            author,
+            committer,
+            extra_headers,
+            raw_message,
+            self.hash_algorithm,
+        ))
     }
 
     fn parse_parent(&self, parent_line: Option<&Vec<u8>>) -> Option<String> {
@@ -185,16 +504,67 @@ impl Database {
         })
     }
 
-    pub fn load_tree(&self, tree_id: &ObjectId) -> io::Result<Tree> {
+    pub fn load_tree(&self, tree_id: &ObjectId) -> crate::Result<Tree> {
         let content = self.load_data(tree_id)?;
-        let tree_entries = parse_tree_entries(&mut content.into_iter());
-        Ok(Tree::new(tree_entries))
+        self.parse_tree(tree_id, &content)
     }
 
-    pub fn load_blob(&self, blob_id: &ObjectId) -> io::Result<Blob> {
+    fn parse_tree(&self, tree_id: &ObjectId, content: &[u8]) -> crate::Result<Tree> {
+        let tree_entries = parse_tree_entries(&mut content.iter().copied(), self.hash_algorithm)
+            .map_err(|reason| crate::Error::CorruptObject(tree_id.clone(), reason))?;
+        Ok(Tree::new(tree_entries, self.hash_algorithm))
+    }
+
+    /// Load the tree of a tree-ish object id, i.e. the tree itself or the tree of a commit.
+    pub fn peel_to_tree(&self, object_id: &ObjectId) -> crate::Result<Tree> {
+        let (object_type, content) = self.load_object(object_id)?;
+        match object_type {
+            ObjectType::Tree => self.parse_tree(object_id, &content),
+            ObjectType::Commit => {
+                let commit = self
+                    .parse_commit(&mut content.iter().copied())
+                    .map_err(|reason| crate::Error::CorruptObject(object_id.clone(), reason))?;
+                self.load_tree(&commit.tree)
+            }
+            ObjectType::Blob => Err(crate::Error::Fatal(
+                None,
+                format!("object {} is not a tree", object_id),
+            )),
+        }
+    }
+
+    /// Find the entry at the path, relative to the root of the tree of a tree-ish object id,
+    /// looking through subtrees. Returns None if there is no such entry.
+    pub fn find_tree_entry(
+        &self,
+        object_id: &ObjectId,
+        path: &Path,
+    ) -> crate::Result<Option<TreeEntry>> {
+        let mut tree = self.peel_to_tree(object_id)?;
+        let mut components = path.iter().peekable();
+        while let Some(component) = components.next() {
+            let name = component.to_string_lossy();
+            let Some(entry) = tree.entries().iter().find(|entry| entry.name == name) else {
+                return Ok(None);
+            };
+            if components.peek().is_none() {
+                return Ok(Some(entry.clone()));
+            }
+            if entry.mode != FileMode::Directory {
+                return Ok(None);
+            }
+            tree = self.load_tree(&entry.object_id)?;
+        }
+        Ok(None)
+    }
+
+    pub fn load_blob(&self, blob_id: &ObjectId) -> crate::Result<Blob> {
         let content = self.load_data(blob_id)?;
         // TODO fix Blob::with_hash
-        Ok(Blob::new(content))
+        Ok(Blob::with_hash_algorithm(
+            content.to_vec(),
+            self.hash_algorithm,
+        ))
     }
 
     fn decompress<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
@@ -206,7 +576,7 @@ impl Database {
         Ok(buf)
     }
 
-    pub fn print_paths(&self, path: String, tree: &Tree) -> io::Result<()> {
+    pub fn print_paths(&self, path: String, tree: &Tree) -> crate::Result<()> {
         let mut accumulator = vec![];
         self.extract_paths_from_tree(path, tree, &mut accumulator)?;
         for (object_id, file_path) in accumulator {
@@ -220,7 +590,24 @@ impl Database {
         base_path: String,
         tree: &Tree,
         accumulator: &mut Vec<(String, String)>,
-    ) -> io::Result<()> {
+    ) -> crate::Result<()> {
+        let mut entries = vec![];
+        self.extract_entries_from_tree(base_path, tree, &mut entries)?;
+        accumulator.extend(
+            entries
+                .into_iter()
+                .map(|(path, entry)| (entry.object_id.to_string(), path)),
+        );
+        Ok(())
+    }
+
+    /// Recursively collect all entries that are not trees, paired with their paths.
+    pub fn extract_entries_from_tree(
+        &self,
+        base_path: String,
+        tree: &Tree,
+        accumulator: &mut Vec<(String, TreeEntry)>,
+    ) -> crate::Result<()> {
         for tree_entry in tree.entries() {
             let next_path = if base_path.is_empty() {
                 String::from(&tree_entry.name)
@@ -230,10 +617,10 @@ impl Database {
             match tree_entry.mode {
                 FileMode::Directory => {
                     let tree = self.load_tree(&tree_entry.object_id)?;
-                    self.extract_paths_from_tree(next_path, &tree, accumulator)?;
+                    self.extract_entries_from_tree(next_path, &tree, accumulator)?;
                 }
                 _ => {
-                    accumulator.push((tree_entry.object_id.to_string(), next_path));
+                    accumulator.push((next_path, tree_entry.clone()));
                 }
             }
         }
@@ -242,8 +629,57 @@ impl Database {
     }
 }
 
-fn parse_author_details(author_line: &[u8]) -> (String, String, u64) {
-    let line_as_str = str::from_utf8(author_line).unwrap();
+/// Read the git directory that a `.git` file points to with a `gitdir: <path>` line. A relative
+/// path is relative to the directory containing the file.
+pub fn read_gitfile(path: &Path) -> crate::Result<PathBuf> {
+    let content = fs::read_to_string(path)?;
+    let git_dir = content
+        .strip_prefix("gitdir: ")
+        .map(str::trim_end)
+        .ok_or_else(|| {
+            let message = format!("invalid gitfile format: {}", path.display());
+            crate::Error::Fatal(None, message)
+        })?;
+    Ok(path.parent().unwrap().join(git_dir))
+}
+
+/// Read the hash algorithm from `extensions.objectformat` in the config of the repository.
+pub fn read_hash_algorithm(common_dir: &Path) -> crate::Result<HashAlgorithm> {
+    let config = Config::from_files(&[(ConfigLevel::Local, common_dir.join("config"))])?;
+    match config.get_string("extensions.objectformat") {
+        Some(name) => HashAlgorithm::parse(&name).ok_or_else(|| {
+            let message = format!("unknown repository object format '{}'", name);
+            crate::Error::Fatal(None, message)
+        }),
+        None => Ok(HashAlgorithm::default()),
+    }
+}
+
+/// Read the bound on the size of the object cache, in bytes, from `core.objectCacheLimit`.
+fn read_object_cache_limit(common_dir: &Path) -> crate::Result<Option<usize>> {
+    let config = Config::from_files(&[(ConfigLevel::Local, common_dir.join("config"))])?;
+    let limit = config.get_int("core.objectcachelimit")?;
+    Ok(limit.map(|limit| limit.max(0) as usize))
+}
+
+/// Read the `commondir` file of a linked worktree's git directory, which points at the common git
+/// directory relative to the git directory.
+fn read_commondir(git_dir: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(git_dir.join("commondir")).ok()?;
+    Some(file::normalize_path(git_dir.join(content.trim_end())))
+}
+
+/// Parse the identity, timestamp and time zone of an author or committer line. Identities that
+/// aren't valid UTF-8 are rejected rather than decoded lossily, as the commit would then no longer
+/// serialize to the bytes it was loaded from.
+fn parse_author_details(author_line: &[u8]) -> Result<(Author, u64, String), String> {
+    let line_as_str = str::from_utf8(author_line).map_err(|_| {
+        let header = author_line
+            .split(|byte| *byte == b' ')
+            .next()
+            .unwrap_or_default();
+        format!("{} is not valid UTF-8", String::from_utf8_lossy(header))
+    })?;
     let mut chars = line_as_str.chars().skip_while(|chr| chr != &' ');
     let name: String = take_while(&mut chars, |chr| *chr != '<').iter().collect();
     let email: String = take_while(&mut chars, |chr| *chr != '>').iter().collect();
@@ -255,42 +691,77 @@ fn parse_author_details(author_line: &[u8]) -> (String, String, u64) {
         .collect::<String>()
         .parse::<u64>()
         .unwrap_or(0);
-    (name.trim().to_owned(), email.trim().to_owned(), timestamp)
+    let timezone = chars.collect::<String>();
+
+    let author = Author {
+        name: name.trim().to_owned(),
+        email: email.trim().to_owned(),
+    };
+    Ok((author, timestamp, timezone))
 }
 
-fn parse_tree_entries(content: &mut impl Iterator<Item = u8>) -> Vec<TreeEntry> {
+fn parse_tree_entries(
+    content: &mut impl Iterator<Item = u8>,
+    hash_algorithm: HashAlgorithm,
+) -> Result<Vec<TreeEntry>, String> {
     let mut peekable_content = content.peekable();
     let mut entries = vec![];
 
     while peekable_content.peek().is_some() {
-        let entry = parse_tree_entry(&mut peekable_content);
+        let entry = parse_tree_entry(&mut peekable_content, hash_algorithm)?;
         entries.push(entry);
     }
 
-    entries
+    Ok(entries)
 }
 
-fn parse_tree_entry(content: &mut impl Iterator<Item = u8>) -> TreeEntry {
+fn parse_tree_entry(
+    content: &mut impl Iterator<Item = u8>,
+    hash_algorithm: HashAlgorithm,
+) -> Result<TreeEntry, String> {
     let mode_bytes = take_while(content, |byte: &u8| *byte != b' ');
     let name_bytes = take_while(content, |byte| *byte != 0);
-    let raw_object_id = hex::unhexlify(&content.take(20).collect::<Vec<u8>>());
-    let object_id = ObjectId::from_sha_bytes(&raw_object_id).unwrap();
+    let raw_size = hash_algorithm.raw_size();
+    let raw_object_id = content.take(raw_size).collect::<Vec<u8>>();
+    let object_id = ObjectId::from_sha_bytes(&raw_object_id)?;
 
-    // TODO handle bad mode bytes
-    let mode = match str::from_utf8(&mode_bytes).unwrap() {
+    let mode = match str::from_utf8(&mode_bytes).unwrap_or_default() {
         "40000" => FileMode::Directory,
         "100644" => FileMode::Regular,
         "100755" => FileMode::Executable,
-        unknown_mode => panic!("Unknown mode: {}", unknown_mode),
+        "160000" => FileMode::Gitlink,
+        unknown_mode => return Err(format!("unknown mode '{}'", unknown_mode)),
     };
 
-    // TODO handle bad name bytes
-    let name = str::from_utf8(&name_bytes).unwrap().to_owned();
+    let name =
+        String::from_utf8(name_bytes).map_err(|_| String::from("entry name is not valid UTF-8"))?;
 
-    TreeEntry {
+    Ok(TreeEntry {
         name,
         object_id,
         mode,
+    })
+}
+
+/// Feed the content of the reader to the hasher and the writer through a buffer of fixed size.
+/// Returns the number of bytes of the content.
+fn stream_content(
+    reader: &mut impl Read,
+    hasher: &mut Hasher,
+    writer: &mut impl Write,
+) -> io::Result<u64> {
+    let mut buffer = vec![0; STREAM_BUFFER_SIZE];
+    let mut total = 0;
+    loop {
+        let count = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(total),
+            Ok(count) => count,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        hasher.update(&buffer[..count]);
+        writer.write_all(&buffer[..count])?;
+        total += count as u64;
     }
 }
 
@@ -310,6 +781,192 @@ fn take_while<T>(iter: &mut impl Iterator<Item = T>, predicate: fn(&T) -> bool)
     result
 }
 
+pub struct Repository {
+    pub database: Database,
+    git_dir: PathBuf,
+    common_dir: PathBuf,
+    worktree: Worktree,
+    filters: OnceLock<Filters>,
+}
+
+impl Repository {
+    /// Create a repository whose git directory is not necessarily located in the root of the
+    /// worktree. If the git directory belongs to a linked worktree, the objects, refs and config
+    /// are read from the common git directory that it points to. Fails if the config names an
+    /// object format that rut doesn't know, as objects would otherwise be hashed wrongly.
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(
+        git_dir: P,
+        worktree_root: Q,
+    ) -> crate::Result<Repository> {
+        let git_dir = file::normalize_path(git_dir);
+        let common_dir = read_commondir(&git_dir).unwrap_or_else(|| git_dir.clone());
+        let hash_algorithm = read_hash_algorithm(&common_dir)?;
+        let cache_limit = read_object_cache_limit(&common_dir).ok().flatten();
+        let database = Database::new(common_dir.clone(), hash_algorithm)
+            .with_cache_limit(cache_limit.unwrap_or(object_cache::DEFAULT_LIMIT));
+        let worktree = Worktree::new(file::normalize_path(worktree_root));
+        Ok(Repository {
+            database,
+            git_dir,
+            common_dir,
+            worktree,
+            filters: OnceLock::new(),
+        })
+    }
+
+    pub fn from_worktree_root<P: AsRef<Path>>(worktree_root: P) -> crate::Result<Repository> {
+        Repository::new(worktree_root.as_ref().join(".git"), worktree_root)
+    }
+
+    /// Find the repository containing the start directory by looking for a `.git` directory in it
+    /// and each of its parents. A `.git` file pointing at the git directory elsewhere, as used by
+    /// submodules, is followed. The start directory is recorded as the directory the user is
+    /// working in, against which relative paths given by the user are resolved.
+    pub fn discover<P: AsRef<Path>>(start_dir: P) -> crate::Result<Repository> {
+        let start_dir = file::normalize_path(start_dir);
+        let worktree_root = start_dir
+            .ancestors()
+            .find(|directory| directory.join(".git").exists())
+            .ok_or_else(|| crate::Error::NotARepository(start_dir.clone()))?;
+
+        let dot_git = worktree_root.join(".git");
+        let git_dir = if dot_git.is_file() {
+            read_gitfile(&dot_git)?
+        } else {
+            dot_git
+        };
+        Ok(Repository::new(git_dir, worktree_root)?.with_working_directory(&start_dir))
+    }
+
+    /// Record the directory the user is working in, against which relative paths given by the
+    /// user are resolved. A directory outside of the worktree is treated as the worktree root.
+    pub fn with_working_directory<P: AsRef<Path>>(mut self, directory: P) -> Repository {
+        let directory = file::normalize_path(directory);
+        self.worktree.prefix = directory
+            .strip_prefix(&self.worktree.root)
+            .map(Path::to_owned)
+            .unwrap_or_default();
+        self
+    }
+
+    pub fn worktree(&self) -> &Worktree {
+        &self.worktree
+    }
+
+    pub fn index_file(&self) -> PathBuf {
+        self.git_dir().join("index")
+    }
+
+    pub fn load_index(&self) -> crate::Result<LockFileResource<Index>> {
+        let index_file_path = self.git_dir().join("index");
+        let lockfile = LockFile::acquire(&index_file_path)?;
+        let index = Index::from_file_with_hash_algorithm(&index_file_path, self.hash_algorithm())?;
+        Ok(LockFileResource::new(lockfile, index))
+    }
+
+    pub fn load_index_unlocked(&self) -> crate::Result<Index> {
+        let index_file_path = self.git_dir().join("index");
+        let index = Index::from_file_with_hash_algorithm(index_file_path, self.hash_algorithm())?;
+        Ok(index)
+    }
+
+    /// The git directory of the worktree, which holds its HEAD and index.
+    pub fn git_dir(&self) -> PathBuf {
+        self.git_dir.clone()
+    }
+
+    /// The git directory shared by all worktrees of the repository, which holds the objects, refs
+    /// and config. This is the same as the git directory unless in a linked worktree.
+    pub fn common_dir(&self) -> PathBuf {
+        self.common_dir.clone()
+    }
+
+    /// The hash algorithm of the repository, which is sha1 unless the repository was created
+    /// with another object format.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.database.hash_algorithm()
+    }
+
+    pub fn objects_dir(&self) -> PathBuf {
+        self.common_dir().join("objects")
+    }
+
+    /// The merged system, global and local configuration of the repository.
+    pub fn config(&self) -> crate::Result<Config> {
+        Config::load(Some(&self.common_dir))
+    }
+
+    /// The filters that content passes through between the worktree and the database. They are
+    /// loaded on first use and kept for the lifetime of the repository.
+    pub fn filters(&self) -> crate::Result<&Filters> {
+        if let Some(filters) = self.filters.get() {
+            return Ok(filters);
+        }
+        let filters = Filters::load(self)?;
+        Ok(self.filters.get_or_init(|| filters))
+    }
+
+    /// The name of the branch that HEAD points to, or the object id if HEAD is detached.
+    pub fn head(&self) -> io::Result<String> {
+        let head_file = self.git_dir().join("HEAD");
+        let head_content = fs::read_to_string(head_file)?;
+        let trimmed_head_content = head_content.trim();
+        Ok(trimmed_head_content
+            .trim_start_matches("ref: refs/heads/")
+            .to_owned())
+    }
+}
+
+pub struct Worktree {
+    root: PathBuf,
+    prefix: PathBuf,
+}
+
+impl Worktree {
+    pub fn new<P: AsRef<Path>>(root: P) -> Worktree {
+        Worktree {
+            root: root.as_ref().to_owned(),
+            prefix: PathBuf::new(),
+        }
+    }
+
+    /// Absolute path to the root of the worktree.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory the user is working in, relative to the root of the worktree. Empty if the
+    /// user is working in the root.
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// Resolve a path given by the user to a path relative to the root of the worktree. Relative
+    /// paths are taken to be relative to the directory the user is working in.
+    pub fn resolve_user_path<P: AsRef<Path>>(&self, path: P) -> crate::Result<PathBuf> {
+        let path = path.as_ref();
+        let normalized_path = file::normalize_path(self.root.join(&self.prefix).join(path));
+        if !normalized_path.starts_with(&self.root) {
+            let message = format!("'{}' is outside repository", path.display());
+            return Err(crate::Error::Fatal(None, message));
+        }
+        Ok(self.relativize_path(normalized_path))
+    }
+
+    /// Return the path relative to the root of this worktree.
+    pub fn relativize_path<P: AsRef<Path>>(&self, absolute_path: P) -> PathBuf {
+        let relative_path = absolute_path
+            .as_ref()
+            .strip_prefix(&self.root)
+            .expect("Bad path");
+        if relative_path.as_os_str() == "" {
+            PathBuf::from(".")
+        } else {
+            PathBuf::from(relative_path)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -320,16 +977,16 @@ mod tests {
     use rut_testhelpers;
 
     #[test]
-    fn test_load_empty_tree() -> io::Result<()> {
+    fn test_load_empty_tree() -> crate::Result<()> {
         // arrange
         let workdir = rut_testhelpers::create_temporary_directory();
-        let database = Database::new(workdir);
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
 
-        let empty_tree = Tree::new(vec![]);
+        let empty_tree = Tree::new(vec![], HashAlgorithm::Sha1);
         database.store_object(&empty_tree)?;
 
         // act
-        let parsed_tree = database.load_tree(&empty_tree.id())?;
+        let parsed_tree = database.load_tree(empty_tree.id())?;
 
         // assert
         assert_eq!(parsed_tree, empty_tree);
@@ -339,21 +996,21 @@ mod tests {
     }
 
     #[test]
-    fn test_load_single_entry_tree() -> io::Result<()> {
+    fn test_load_single_entry_tree() -> crate::Result<()> {
         // arrange
         let workdir = rut_testhelpers::create_temporary_directory();
-        let database = Database::new(workdir);
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
 
         let entry = TreeEntry {
             name: String::from("file.txt"),
             object_id: ObjectId::from_sha("097711d5840f84b87f5567843471e886f5733d9a").unwrap(),
             mode: FileMode::Regular,
         };
-        let tree = Tree::new(vec![entry]);
+        let tree = Tree::new(vec![entry], HashAlgorithm::Sha1);
         database.store_object(&tree)?;
 
         // act
-        let parsed_tree = database.load_tree(&tree.id())?;
+        let parsed_tree = database.load_tree(tree.id())?;
 
         // assert
         assert_eq!(parsed_tree, tree);
@@ -363,10 +1020,10 @@ mod tests {
     }
 
     #[test]
-    fn test_load_multiple_entry_tree() -> io::Result<()> {
+    fn test_load_multiple_entry_tree() -> crate::Result<()> {
         // arrange
         let workdir = rut_testhelpers::create_temporary_directory();
-        let database = Database::new(workdir);
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
 
         let regular_file_entry = TreeEntry {
             name: String::from("file.txt"),
@@ -384,11 +1041,14 @@ mod tests {
             mode: FileMode::Directory,
         };
 
-        let tree = Tree::new(vec![regular_file_entry, executable_file_entry, dir_entry]);
+        let tree = Tree::new(
+            vec![regular_file_entry, executable_file_entry, dir_entry],
+            HashAlgorithm::Sha1,
+        );
         database.store_object(&tree)?;
 
         // act
-        let parsed_tree = database.load_tree(&tree.id())?;
+        let parsed_tree = database.load_tree(tree.id())?;
 
         // assert
         assert_eq!(parsed_tree, tree);
@@ -398,16 +1058,121 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_without_parent() -> io::Result<()> {
+    fn test_load_object_shares_cached_content() -> crate::Result<()> {
+        // arrange
+        let workdir = rut_testhelpers::create_temporary_directory();
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
+        let blob = Blob::new(b"content".to_vec());
+        database.store_object(&blob)?;
+
+        // act
+        let (_, first_content) = database.load_object(blob.id())?;
+        let (_, second_content) = database.load_object(blob.id())?;
+
+        // assert
+        assert_eq!(first_content.as_ref(), blob.content());
+        assert!(Arc::ptr_eq(&first_content, &second_content));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abbreviate_extends_id_past_prefix_shared_with_other_object() -> io::Result<()> {
+        // arrange
+        let workdir = rut_testhelpers::create_temporary_directory();
+        let database = Database::new(workdir.clone(), HashAlgorithm::Sha1);
+        let fanout_dir = workdir.join("objects").join("ab");
+        fs::create_dir_all(&fanout_dir)?;
+        fs::write(
+            fanout_dir.join("cdef0123456789abcdef0123456789abcdef01"),
+            "",
+        )?;
+        fs::write(
+            fanout_dir.join("cdef0123ffffffffffffffffffffffffffffff"),
+            "",
+        )?;
+        let object_id = ObjectId::from_sha("abcdef0123456789abcdef0123456789abcdef01").unwrap();
+        let unique_id = ObjectId::from_sha("0123456789abcdef0123456789abcdef01234567").unwrap();
+
+        // act
+        let abbreviation = database.abbreviate(&object_id);
+        let unique_abbreviation = database.abbreviate(&unique_id);
+
+        // assert
+        assert_eq!(abbreviation, "abcdef01234");
+        assert_eq!(unique_abbreviation, "0123456");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_store_blob_from_file_streams_content_into_blob() -> crate::Result<()> {
+        // arrange
+        let workdir = rut_testhelpers::create_temporary_directory();
+        let database = Database::new(workdir.clone(), HashAlgorithm::Sha1);
+        fs::create_dir(workdir.join("objects"))?;
+        let content = (0..STREAM_BUFFER_SIZE * 3)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let path = workdir.join("file.bin");
+        fs::write(&path, &content)?;
+
+        // act
+        let object_id = database.store_blob_from_file(&path)?;
+
+        // assert
+        let expected_blob = Blob::new(content);
+        assert_eq!(&object_id, expected_blob.id());
+        assert_eq!(
+            database.load_blob(&object_id)?.content(),
+            expected_blob.content()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_tree_with_unknown_mode_is_corrupt() -> crate::Result<()> {
+        // arrange
+        let workdir = rut_testhelpers::create_temporary_directory();
+        let database = Database::new(workdir.clone(), HashAlgorithm::Sha1);
+        let mut content = b"12345 file.txt\0".to_vec();
+        content.extend([0xab; 20]);
+        let object_format = [format!("tree {}\0", content.len()).into_bytes(), content].concat();
+        let tree_id = ObjectId::compute(&object_format, HashAlgorithm::Sha1);
+        let object_dir = workdir.join("objects").join(tree_id.dirname());
+        fs::create_dir_all(&object_dir)?;
+        fs::write(
+            object_dir.join(tree_id.filename()),
+            Database::compress(&object_format)?,
+        )?;
+
+        // act
+        let result = database.load_tree(&tree_id);
+
+        // assert
+        match result {
+            Err(crate::Error::CorruptObject(object_id, reason)) => {
+                assert_eq!(object_id, tree_id);
+                assert_eq!(reason, "unknown mode '12345'");
+            }
+            other => panic!("expected a corrupt object, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_without_parent() -> crate::Result<()> {
         // arrange
         let workdir = rut_testhelpers::create_temporary_directory();
-        let database = Database::new(workdir);
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
 
         let commit = create_commit(None);
         database.store_object(&commit)?;
 
         // act
-        let parsed_commit = database.load_commit(&commit.id())?;
+        let parsed_commit = database.load_commit(commit.id())?;
 
         // assert
         assert_eq!(parsed_commit, commit);
@@ -417,10 +1182,10 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_commit_with_parent() -> io::Result<()> {
+    fn test_parse_commit_with_parent() -> crate::Result<()> {
         // arrange
         let workdir = rut_testhelpers::create_temporary_directory();
-        let database = Database::new(workdir);
+        let database = Database::new(workdir, HashAlgorithm::Sha1);
 
         let first_commit = create_commit(None);
         let second_commit = create_commit(Some(first_commit.id().clone()));
@@ -429,7 +1194,7 @@ mod tests {
         database.store_object(&second_commit)?;
 
         // act
-        let parsed_commit = database.load_commit(&second_commit.id())?;
+        let parsed_commit = database.load_commit(second_commit.id())?;
 
         // assert
         assert_eq!(parsed_commit, second_commit);
@@ -443,7 +1208,7 @@ mod tests {
             object_id: ObjectId::from_sha("ce013625030ba8dba906f756967f9e9ca394464a").unwrap(),
             mode: FileMode::Regular,
         };
-        let tree = Tree::new(vec![tree_entry]);
+        let tree = Tree::new(vec![tree_entry], HashAlgorithm::Sha1);
         let author = Author {
             name: String::from("Full Name"),
             email: String::from("name@example.com"),
@@ -457,88 +1222,3 @@ mod tests {
         )
     }
 }
-
-pub struct Repository {
-    pub database: Database,
-    worktree: Worktree,
-}
-
-impl Repository {
-    pub fn from_worktree_root<P: AsRef<Path>>(worktree_root: P) -> Repository {
-        let database = Database::new(worktree_root.as_ref().join(".git"));
-        let worktree = Worktree::new(worktree_root.as_ref());
-        Repository { database, worktree }
-    }
-
-    pub fn worktree(&self) -> &Worktree {
-        &self.worktree
-    }
-
-    pub fn index_file(&self) -> PathBuf {
-        self.git_dir().join("index")
-    }
-
-    pub fn load_index(&self) -> crate::Result<LockFileResource<Index>> {
-        let index_file_path = self.git_dir().join("index");
-        let lockfile = LockFile::acquire(&index_file_path)?;
-        let index = Index::from_file(&index_file_path)?;
-        Ok(LockFileResource::new(lockfile, index))
-    }
-
-    pub fn load_index_unlocked(&self) -> io::Result<Index> {
-        let index_file_path = self.git_dir().join("index");
-        let index = Index::from_file(index_file_path)?;
-        Ok(index)
-    }
-
-    pub fn git_dir(&self) -> PathBuf {
-        self.worktree.root().join(".git")
-    }
-
-    pub fn objects_dir(&self) -> PathBuf {
-        self.git_dir().join("objects")
-    }
-
-    pub fn config(&self) -> Config {
-        config::read_config().unwrap()
-    }
-
-    pub fn head(&self) -> io::Result<String> {
-        let head_file = self.git_dir().join("HEAD");
-        let head_content = fs::read_to_string(head_file)?;
-        let trimmed_head_content = head_content.trim();
-        Ok(trimmed_head_content
-            .trim_start_matches("ref: refs/heads/")
-            .to_owned())
-    }
-}
-
-pub struct Worktree {
-    root: PathBuf,
-}
-
-impl Worktree {
-    pub fn new<P: AsRef<Path>>(root: P) -> Worktree {
-        Worktree {
-            root: root.as_ref().to_owned(),
-        }
-    }
-
-    /// Absolute path to the root of the worktree.
-    pub fn root(&self) -> &Path {
-        &self.root
-    }
-
-    /// Return the path relative to the root of this worktree.
-    pub fn relativize_path<P: AsRef<Path>>(&self, absolute_path: P) -> PathBuf {
-        let relative_path = absolute_path
-            .as_ref()
-            .strip_prefix(&self.root)
-            .expect("Bad path");
-        if relative_path.as_os_str() == "" {
-            PathBuf::from(".")
-        } else {
-            PathBuf::from(relative_path)
-        }
-    }
-}
@@ -0,0 +1,294 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::objects::GitObject;
+use crate::output::{NoProgress, OutputWriter};
+use crate::refs::{RefHandler, Revision};
+use crate::status;
+use crate::switch;
+use crate::workspace::Repository;
+
+#[derive(Debug, Clone, Default)]
+pub enum Mode {
+    /// Check out the commit-ish, which defaults to a new branch named after the path, in a new
+    /// worktree at the path.
+    Add {
+        path: PathBuf,
+        commit_ish: Option<String>,
+    },
+    /// List the main worktree followed by the linked worktrees.
+    #[default]
+    List,
+    /// Remove the linked worktree at the path.
+    Remove { path: PathBuf },
+}
+
+#[derive(Default, Builder, Debug)]
+pub struct Options {
+    #[builder(default)]
+    pub mode: Mode,
+
+    /// Create a branch with this name at the commit-ish and check it out in the new worktree.
+    #[builder(default)]
+    pub create: Option<String>,
+
+    /// Check out the commit-ish with a detached HEAD in the new worktree.
+    #[builder(default)]
+    pub detach: bool,
+
+    /// Remove a worktree even if it has local changes or untracked files.
+    #[builder(default)]
+    pub force: bool,
+}
+
+/// A worktree of the repository, along with the git directory that holds its HEAD and index.
+#[derive(Debug)]
+pub struct WorktreeInfo {
+    pub path: PathBuf,
+    pub git_dir: PathBuf,
+    pub is_main: bool,
+}
+
+impl WorktreeInfo {
+    pub(crate) fn open(&self) -> crate::Result<Repository> {
+        Repository::new(&self.git_dir, &self.path)
+    }
+}
+
+/// Add, list or remove worktrees depending on the mode. Paths are expected to be absolute.
+pub fn worktree(
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    match &options.mode {
+        Mode::Add { path, commit_ish } => {
+            add(path, commit_ish.as_deref(), options, repository, writer)
+        }
+        Mode::List => list(repository, writer),
+        Mode::Remove { path } => remove(path, options.force, repository),
+    }
+}
+
+/// List the main worktree and all linked worktrees of the repository.
+pub fn list_worktrees(repository: &Repository) -> crate::Result<Vec<WorktreeInfo>> {
+    let common_dir = repository.common_dir();
+    let main_path = if repository.git_dir() == common_dir {
+        repository.worktree().root().to_owned()
+    } else {
+        common_dir.parent().unwrap_or(&common_dir).to_owned()
+    };
+    let mut worktrees = vec![WorktreeInfo {
+        path: main_path,
+        git_dir: common_dir.clone(),
+        is_main: true,
+    }];
+
+    let worktrees_dir = common_dir.join("worktrees");
+    if !worktrees_dir.is_dir() {
+        return Ok(worktrees);
+    }
+
+    let mut entries = fs::read_dir(worktrees_dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        // the gitdir file points at the .git file in the root of the linked worktree
+        let Ok(gitdir_content) = fs::read_to_string(entry.path().join("gitdir")) else {
+            continue;
+        };
+        let dot_git = PathBuf::from(gitdir_content.trim_end());
+        worktrees.push(WorktreeInfo {
+            path: dot_git.parent().unwrap_or(&dot_git).to_owned(),
+            git_dir: entry.path(),
+            is_main: false,
+        });
+    }
+
+    Ok(worktrees)
+}
+
+/// Find the worktree, if any, that has the branch checked out.
+pub fn find_checkout(branch: &str, repository: &Repository) -> crate::Result<Option<PathBuf>> {
+    for worktree in list_worktrees(repository)? {
+        let current_branch = RefHandler::new(&worktree.open()?).current_branch().ok();
+        if current_branch.flatten().as_deref() == Some(branch) {
+            return Ok(Some(worktree.path));
+        }
+    }
+    Ok(None)
+}
+
+fn add(
+    path: &Path,
+    commit_ish: Option<&str>,
+    options: &Options,
+    repository: &Repository,
+    writer: &mut dyn OutputWriter,
+) -> crate::Result<()> {
+    if path.exists() && fs::read_dir(path)?.next().is_some() {
+        let message = format!("'{}' already exists", path.display());
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    let refs = RefHandler::new(repository);
+    let path_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| {
+            let message = format!("invalid worktree path: {}", path.display());
+            crate::Error::Fatal(None, message)
+        })?;
+    let existing_branch = match commit_ish {
+        Some(commit_ish) if options.create.is_none() && !options.detach => {
+            refs.find_branch(commit_ish)?.map(|_| commit_ish)
+        }
+        _ => None,
+    };
+
+    let commit_id = Revision::parse(commit_ish.unwrap_or("HEAD"))?.resolve(repository)?;
+    let branch = if let Some(branch) = existing_branch {
+        if let Some(checkout_path) = find_checkout(branch, repository)? {
+            let message = format!(
+                "'{}' is already checked out at '{}'",
+                branch,
+                checkout_path.display()
+            );
+            return Err(crate::Error::Fatal(None, message));
+        }
+        writer.writeln(format!("Preparing worktree (checking out '{}')", branch))?;
+        Some(branch.to_owned())
+    } else if options.detach || commit_ish.is_some() && options.create.is_none() {
+        writer.writeln(format!(
+            "Preparing worktree (detached HEAD {})",
+            commit_id.to_short_string()
+        ))?;
+        None
+    } else {
+        let branch = options.create.clone().unwrap_or_else(|| path_name.clone());
+        refs.create_ref(&branch, &commit_id)?;
+        writer.writeln(format!("Preparing worktree (new branch '{}')", branch))?;
+        Some(branch)
+    };
+
+    let git_dir = create_worktree_git_dir(&path_name, path, repository)?;
+    let linked_repository = Repository::new(&git_dir, path)?;
+
+    // check out the commit before writing HEAD, as the checkout starts from the HEAD commit
+    let mut index_lockfile = linked_repository.load_index()?;
+    let index = index_lockfile.as_mut();
+    switch::checkout_commit(&commit_id, &linked_repository, index, &mut NoProgress)?;
+    index_lockfile.write()?;
+    let linked_refs = RefHandler::new(&linked_repository);
+    match &branch {
+        Some(branch) => linked_refs.attach_head(branch)?,
+        None => linked_refs.detach_head(&commit_id)?,
+    }
+
+    let commit = repository.database.load_commit(&commit_id)?;
+    let subject = commit.message.lines().next().unwrap_or_default();
+    writer.writeln(format!(
+        "HEAD is now at {} {}",
+        commit.short_id_as_string(),
+        subject
+    ))?;
+    Ok(())
+}
+
+/// Create the git directory of a linked worktree in `.git/worktrees/<name>`, with a number
+/// appended to the name if it's taken, and link it with the worktree in both directions.
+fn create_worktree_git_dir(
+    name: &str,
+    worktree_path: &Path,
+    repository: &Repository,
+) -> crate::Result<PathBuf> {
+    let worktrees_dir = repository.common_dir().join("worktrees");
+    let mut git_dir = worktrees_dir.join(name);
+    let mut suffix = 1;
+    while git_dir.exists() {
+        git_dir = worktrees_dir.join(format!("{}{}", name, suffix));
+        suffix += 1;
+    }
+
+    fs::create_dir_all(&git_dir)?;
+    fs::create_dir_all(worktree_path)?;
+    fs::write(git_dir.join("commondir"), "../..\n")?;
+    let dot_git = worktree_path.join(".git");
+    fs::write(git_dir.join("gitdir"), format!("{}\n", dot_git.display()))?;
+    fs::write(dot_git, format!("gitdir: {}\n", git_dir.display()))?;
+    Ok(git_dir)
+}
+
+fn list(repository: &Repository, writer: &mut dyn OutputWriter) -> crate::Result<()> {
+    let worktrees = list_worktrees(repository)?;
+    let width = worktrees
+        .iter()
+        .map(|worktree| worktree.path.to_string_lossy().len())
+        .max()
+        .unwrap_or_default();
+
+    for worktree in worktrees {
+        let worktree_repository = worktree.open()?;
+        let refs = RefHandler::new(&worktree_repository);
+        let head = refs
+            .head()
+            .map(|head_id| head_id.to_short_string())
+            .unwrap_or_else(|_| "0".repeat(14));
+        let branch = match refs.current_branch()? {
+            Some(branch) => format!("[{}]", branch),
+            None => String::from("(detached HEAD)"),
+        };
+        writer.writeln(format!(
+            "{:width$} {} {}",
+            worktree.path.display().to_string(),
+            head,
+            branch,
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn remove(path: &Path, force: bool, repository: &Repository) -> crate::Result<()> {
+    let worktree = list_worktrees(repository)?
+        .into_iter()
+        .find(|worktree| worktree.path == path)
+        .ok_or_else(|| {
+            let message = format!("'{}' is not a working tree", path.display());
+            crate::Error::Fatal(None, message)
+        })?;
+    if worktree.is_main {
+        let message = format!("'{}' is a main working tree", path.display());
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    if !force && path.is_dir() && has_local_changes(&worktree.open()?)? {
+        let message = format!(
+            "'{}' contains modified or untracked files, use --force to delete it",
+            path.display()
+        );
+        return Err(crate::Error::Fatal(None, message));
+    }
+
+    if path.exists() {
+        fs::remove_dir_all(path)?;
+    }
+    Ok(fs::remove_dir_all(worktree.git_dir)?)
+}
+
+fn has_local_changes(repository: &Repository) -> crate::Result<bool> {
+    let mut index = repository.load_index_unlocked()?;
+    let path_to_committed_id = status::resolve_committed_paths_and_ids(repository)?;
+    let has_staged_deletions = path_to_committed_id
+        .keys()
+        .any(|path| !index.has_entry(path));
+
+    let has_staged_changes =
+        !status::resolve_files_with_staged_changes(&path_to_committed_id, repository, &index)?
+            .is_empty();
+    let scan = status::scan_worktree(repository, &mut index);
+
+    Ok(has_staged_deletions
+        || has_staged_changes
+        || !scan.unstaged_changes.is_empty()
+        || !scan.untracked_paths.is_empty())
+}
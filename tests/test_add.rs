@@ -1,6 +1,9 @@
 use std::{fs, path::PathBuf};
 
+use rut::add;
 use rut::index::Index;
+use rut::objects::{Blob, GitObject};
+use rut::output::Progress;
 
 #[test]
 fn test_add_directory() -> rut::Result<()> {
@@ -40,6 +43,43 @@ fn test_add_directory() -> rut::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_add_directory_with_many_files_stores_each_blob() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let mut expected_entries = vec![];
+    for dir in ["a", "b", "c"] {
+        fs::create_dir(workdir.join(dir))?;
+        for i in 0..20 {
+            // files with equal content share a blob that is stored concurrently
+            let content = format!("Content {}\n", i % 5);
+            let path = PathBuf::from(dir).join(format!("file{:02}.txt", i));
+            fs::write(workdir.join(&path), &content)?;
+            let blob = Blob::with_hash_algorithm(content.into_bytes(), repository.hash_algorithm());
+            expected_entries.push((path, blob.id().clone()));
+        }
+    }
+
+    // act
+    rut_testhelpers::run_command_string("add .", &repository)?;
+
+    // assert
+    let index = repository.load_index_unlocked()?;
+    let entries = index
+        .get_entries()
+        .iter()
+        .map(|entry| (entry.path.clone(), entry.object_id.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(entries, expected_entries);
+    for (_, object_id) in &entries {
+        repository.database.load_blob(object_id)?;
+    }
+    rut_testhelpers::assert_healthy_repo(&repository.git_dir());
+
+    Ok(())
+}
+
 #[test]
 fn test_adding_file_when_index_is_locked() -> rut::Result<()> {
     // arrange
@@ -110,10 +150,204 @@ fn test_add_nonexisting_file() -> rut::Result<()> {
         Err(error) => {
             let message = error.to_string();
             let expected_message =
-                "fatal: pathspec \"file/that/does/not/exist\" did not match any files";
+                "fatal: pathspec 'file/that/does/not/exist' did not match any files";
             assert_eq!(message, expected_message);
         }
     };
 
     Ok(())
 }
+
+#[test]
+fn test_add_patch_stages_only_selected_hunks() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let original_content = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    rut_testhelpers::commit_content(&repository, &file, original_content, "Add file")?;
+    fs::write(&file, "one\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n")?;
+
+    // act
+    let output = rut_testhelpers::rut_add_patch("s\nn\ny\n", &repository)?;
+
+    // assert
+    assert!(output.contains("Stage this hunk [y,n,q,s]? Split into 2 hunks."));
+    let staged_content = read_staged_content(&repository, "file.txt")?;
+    assert_eq!(staged_content, "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n");
+    rut_testhelpers::assert_file_contains(&file, "one\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_add_patch_quit_leaves_index_unchanged() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "a\nb\n", "Add file")?;
+    fs::write(&file, "a\nB\n")?;
+
+    // act
+    let output = rut_testhelpers::rut_add_patch("x\nq\n", &repository)?;
+
+    // assert
+    assert!(output.contains("Stage this hunk [y,n,q]? y - stage this hunk"));
+    assert_eq!(read_staged_content(&repository, "file.txt")?, "a\nb\n");
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        " M file.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_add_patch_skips_file_that_is_not_utf8() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "caf\u{e9}\n", "Add file")?;
+    fs::write(&file, b"caf\xe9\n")?;
+
+    // act
+    let output = rut_testhelpers::rut_add_patch("y\n", &repository)?;
+
+    // assert
+    assert!(output.contains("Skipping file.txt, as it is not UTF-8 text"));
+    assert_eq!(read_staged_content(&repository, "file.txt")?, "caf\u{e9}\n");
+
+    Ok(())
+}
+
+fn read_staged_content(repository: &rut::workspace::Repository, path: &str) -> rut::Result<String> {
+    let index = Index::from_file(repository.git_dir().join("index"))?;
+    let entry = index.get(PathBuf::from(path)).unwrap();
+    let blob = repository.database.load_blob(&entry.object_id)?;
+    Ok(String::from_utf8(blob.content().to_vec()).unwrap())
+}
+
+#[test]
+fn test_add_glob_pathspec() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    let nested_dir = workdir.join("docs");
+    fs::create_dir(&nested_dir)?;
+    fs::write(workdir.join("README.md"), "A README.")?;
+    fs::write(workdir.join("file.txt"), "A file.")?;
+    fs::write(nested_dir.join("guide.md"), "A guide.")?;
+
+    // act
+    rut_testhelpers::run_command_string("add '*.md'", &repository)?;
+
+    // assert
+    let index = Index::from_file(repository.index_file())?;
+    let paths_in_index: Vec<&PathBuf> = index
+        .get_entries()
+        .iter()
+        .map(|entry| &entry.path)
+        .collect();
+
+    assert_eq!(
+        paths_in_index,
+        vec![&PathBuf::from("README.md"), &PathBuf::from("docs/guide.md")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_add_from_subdirectory_resolves_paths_relative_to_it() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    let nested_dir = workdir.join("nested");
+    fs::create_dir(&nested_dir)?;
+    fs::write(nested_dir.join("file.txt"), "A file.")?;
+    fs::write(workdir.join("file.txt"), "Another file.")?;
+
+    // act
+    rut_testhelpers::run_command_string_in_directory("add file.txt", &nested_dir)?;
+
+    // assert
+    let index = Index::from_file(repository.index_file())?;
+    let paths_in_index: Vec<&PathBuf> = index
+        .get_entries()
+        .iter()
+        .map(|entry| &entry.path)
+        .collect();
+
+    assert_eq!(paths_in_index, vec![&PathBuf::from("nested/file.txt")]);
+
+    Ok(())
+}
+
+/// Records the calls to a Progress as lines of text.
+#[derive(Default)]
+struct RecordingProgress {
+    calls: Vec<String>,
+}
+
+impl Progress for RecordingProgress {
+    fn start(&mut self, title: &str, total: Option<u64>) {
+        self.calls.push(format!("start {} {:?}", title, total));
+    }
+
+    fn update(&mut self, done: u64) {
+        self.calls.push(format!("update {}", done));
+    }
+
+    fn finish(&mut self) {
+        self.calls.push(String::from("finish"));
+    }
+}
+
+#[test]
+fn test_add_reports_progress() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("first.txt"), "first")?;
+    fs::write(workdir.join("second.txt"), "second")?;
+    let mut progress = RecordingProgress::default();
+
+    // act
+    add::add(&[workdir], &repository, &mut progress)?;
+
+    // assert
+    assert_eq!(
+        progress.calls,
+        vec!["start Adding files Some(2)", "update 2", "finish"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_add_converts_crlf_to_lf_with_autocrlf() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::run_command_string("config core.autocrlf true", &repository)?;
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("file.txt"), "first\r\nsecond\r\n")?;
+
+    // act
+    rut_testhelpers::run_command_string("add file.txt", &repository)?;
+
+    // assert
+    let index = repository.load_index_unlocked()?;
+    let stored_id = &index.get("file.txt").unwrap().object_id;
+    let expected_blob =
+        Blob::with_hash_algorithm(b"first\nsecond\n".to_vec(), repository.hash_algorithm());
+    assert_eq!(stored_id, expected_blob.id());
+    // rewriting the file makes status hash it, which must give the id of the converted content
+    fs::write(workdir.join("file.txt"), "first\r\nsecond\r\n")?;
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "A  file.txt\n"
+    );
+
+    Ok(())
+}
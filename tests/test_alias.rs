@@ -0,0 +1,61 @@
+use std::fs;
+
+#[test]
+fn test_alias_expands_to_command_with_arguments() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Content\n", "Initial commit")?;
+    rut_testhelpers::run_command_string("config alias.last 'log -n 1'", &repository)?;
+    rut_testhelpers::run_command_string(
+        "config alias.subject 'last --pretty=format:%s'",
+        &repository,
+    )?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("subject", &repository)?;
+
+    // assert
+    assert_eq!(output, "Initial commit\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_shell_alias_runs_in_worktree_root_with_arguments() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir(workdir.join("dir"))?;
+    rut_testhelpers::run_command_string(
+        "config alias.where '!echo \"$GIT_PREFIX\" && ls'",
+        &repository,
+    )?;
+    fs::write(workdir.join("file.txt"), "Content\n")?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string_in_directory("where file.txt", &workdir.join("dir"))?;
+
+    // assert
+    assert_eq!(output, "dir/\nfile.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_alias_loop_is_detected() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::run_command_string("config alias.first second", &repository)?;
+    rut_testhelpers::run_command_string("config alias.second first", &repository)?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("first", &repository);
+
+    // assert
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("alias loop detected: expansion of 'first' does not terminate"));
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::fs;
+
+use rut::workspace::Repository;
+
+const MBOX: &str = "\
+From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001
+From: Patch Author <patch@rut.com>
+Date: Tue, 14 Nov 2023 23:13:20 +0100
+Subject: [PATCH] Replace the second
+ line
+
+The second line was wrong.
+---
+ file.txt | 2 +-
+ 1 file changed, 1 insertion(+), 1 deletion(-)
+
+diff --git a/file.txt b/file.txt
+index 0000000..0000000
+--- a/file.txt
++++ b/file.txt
+@@ -1,3 +1,3 @@
+ First line
+-Second line
++New second line
+ Third line
+-- 
+rut 0.1.0
+
+";
+
+fn commit_file(content: &str, repository: &Repository) -> rut::Result<()> {
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(repository, &file, content, "Initial commit")?;
+    Ok(())
+}
+
+#[test]
+fn test_am_commits_patch_with_its_author_and_message() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    commit_file("First line\nSecond line\nThird line\n", &repository)?;
+    fs::write(workdir.join("patch.mbox"), MBOX)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("am patch.mbox", &repository)?;
+
+    // assert
+    assert_eq!(output, "Applying: Replace the second line\n");
+    let commit = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    assert!(commit.contains("\nauthor Patch Author <patch@rut.com> 1700000000 +0100\n"));
+    assert!(commit.ends_with("\n\nReplace the second line\n\nThe second line was wrong."));
+    rut_testhelpers::assert_file_contains(
+        &workdir.join("file.txt"),
+        "First line\nNew second line\nThird line\n",
+    );
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "?? patch.mbox\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_am_applies_patch_at_offset() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    commit_file(
+        "Zeroth line\nFirst line\nSecond line\nThird line\n",
+        &repository,
+    )?;
+    fs::write(workdir.join("patch.mbox"), MBOX)?;
+
+    // act
+    rut_testhelpers::run_command_string("am patch.mbox", &repository)?;
+
+    // assert
+    rut_testhelpers::assert_file_contains(
+        &workdir.join("file.txt"),
+        "Zeroth line\nFirst line\nNew second line\nThird line\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_am_fails_when_patch_does_not_apply() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    commit_file("First line\nOther line\nThird line\n", &repository)?;
+    let head_before = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    fs::write(workdir.join("patch.mbox"), MBOX)?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("am patch.mbox", &repository);
+
+    // assert
+    match result {
+        Err(rut::Error::Fatal(_, message)) => assert_eq!(
+            message,
+            "patch failed: file.txt\nPatch failed at 0001 Replace the second line"
+        ),
+        other => panic!("expected a failed patch, got {:?}", other),
+    }
+    let head_after = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    assert_eq!(head_after, head_before);
+    rut_testhelpers::assert_file_contains(
+        &workdir.join("file.txt"),
+        "First line\nOther line\nThird line\n",
+    );
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use std::fs;
+
+use rut::workspace::Repository;
+
+fn commit_files(repository: &Repository) -> rut::Result<String> {
+    let workdir = repository.worktree().root();
+    fs::create_dir(workdir.join("nested"))?;
+    fs::write(workdir.join("file.txt"), "Hello\n")?;
+    fs::write(workdir.join("nested").join("other.txt"), "Other\n")?;
+    rut_testhelpers::rut_add(workdir, repository);
+    rut_testhelpers::rut_commit("Initial commit", repository)
+}
+
+/// Read the name, octal mode and content of each entry of a tar archive.
+fn read_tar_entries(archive: &[u8]) -> Vec<(String, String, Vec<u8>)> {
+    let mut entries = vec![];
+    let mut offset = 0;
+    while archive[offset] != 0 {
+        let header = &archive[offset..offset + 512];
+        let field = |start: usize, end: usize| {
+            let field = &header[start..end];
+            let length = field.iter().position(|byte| *byte == 0).unwrap();
+            String::from_utf8(field[..length].to_vec()).unwrap()
+        };
+        let size = usize::from_str_radix(&field(124, 136), 8).unwrap();
+        let content = archive[offset + 512..offset + 512 + size].to_vec();
+        entries.push((field(0, 100), field(100, 108), content));
+        offset += 512 + size.div_ceil(512) * 512;
+    }
+    entries
+}
+
+#[test]
+fn test_archive_writes_tar_with_prefix() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = commit_files(&repository)?;
+    let workdir = repository.worktree().root();
+
+    // act
+    rut_testhelpers::run_command_string("archive HEAD --prefix=project/ -o out.tar", &repository)?;
+
+    // assert
+    let archive = fs::read(workdir.join("out.tar"))?;
+    assert_eq!(archive.len() % 10240, 0);
+    let entries = read_tar_entries(&archive);
+    let summary = entries
+        .iter()
+        .map(|(name, mode, _)| (name.as_str(), mode.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("pax_global_header", "0000666"),
+            ("project/", "0000755"),
+            ("project/file.txt", "0000644"),
+            ("project/nested/", "0000755"),
+            ("project/nested/other.txt", "0000644"),
+        ]
+    );
+    assert_eq!(
+        entries[0].2,
+        format!("52 comment={}\n", commit_id).into_bytes()
+    );
+    assert_eq!(entries[2].2, b"Hello\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_archive_writes_zip_by_extension() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = commit_files(&repository)?;
+    let workdir = repository.worktree().root();
+
+    // act
+    rut_testhelpers::run_command_string("archive HEAD^{tree} -o out.zip", &repository)?;
+    rut_testhelpers::run_command_string("archive HEAD -o commit.zip", &repository)?;
+
+    // assert
+    let archive = fs::read(workdir.join("out.zip"))?;
+    assert_eq!(&archive[..4], b"PK\x03\x04");
+    // the end of the central directory lists the number of entries, and the comment is empty
+    let end_record = &archive[archive.len() - 22..];
+    assert_eq!(&end_record[..4], b"PK\x05\x06");
+    assert_eq!(&end_record[10..12], &[3, 0]);
+    let commit_archive = fs::read(workdir.join("commit.zip"))?;
+    assert!(commit_archive.ends_with(commit_id.as_bytes()));
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::fs;
+
+use rut::objects::ObjectId;
+
+#[test]
+fn test_blame_attributes_lines_to_introducing_commits() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let first_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "a\nb\nc\n", "First commit")?;
+    let second_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "a\nB\nc\nd\n", "Second commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("blame file.txt", &repository)?;
+
+    // assert
+    let first_short_id = ObjectId::from_sha(&first_commit_id)
+        .unwrap()
+        .to_short_string();
+    let second_short_id = ObjectId::from_sha(&second_commit_id)
+        .unwrap()
+        .to_short_string();
+
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with(&first_short_id));
+    assert!(lines[0].ends_with(" 1) a"));
+    assert!(lines[1].starts_with(&second_short_id));
+    assert!(lines[1].ends_with(" 2) B"));
+    assert!(lines[2].starts_with(&first_short_id));
+    assert!(lines[2].ends_with(" 3) c"));
+    assert!(lines[3].starts_with(&second_short_id));
+    assert!(lines[3].ends_with(" 4) d"));
+
+    Ok(())
+}
+
+#[test]
+fn test_blame_with_line_range() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "a\nb\nc\n", "First commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("blame -L 2,3 file.txt", &repository)?;
+
+    // assert
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with(" 2) b"));
+    assert!(lines[1].ends_with(" 3) c"));
+
+    Ok(())
+}
+
+#[test]
+fn test_blame_file_that_was_deleted_and_readded() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "a\n", "First commit")?;
+    fs::remove_file(&file)?;
+    rut_testhelpers::run_command_string("add file.txt", &repository)?;
+    rut_testhelpers::rut_commit("Delete file", &repository)?;
+    let readd_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "a\n", "Re-add file")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("blame file.txt", &repository)?;
+
+    // assert
+    let short_id = ObjectId::from_sha(&readd_commit_id)
+        .unwrap()
+        .to_short_string();
+    assert!(output.starts_with(&short_id));
+
+    Ok(())
+}
+
+#[test]
+fn test_blame_error_on_untracked_path() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("blame missing.txt", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on blaming untracked path"),
+        Err(error) => assert_eq!(error.to_string(), "fatal: no such path missing.txt in HEAD"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_blame_error_on_missing_blob_in_parent() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "a\n", "First commit")?;
+    let blob_id = rut_testhelpers::run_command_string("hash-object file.txt", &repository)?;
+    let blob_id = ObjectId::from_sha(blob_id.trim()).unwrap();
+    rut_testhelpers::commit_content(&repository, &file, "a\nb\n", "Second commit")?;
+    let objects_dir = repository.git_dir().join("objects");
+    fs::remove_file(objects_dir.join(blob_id.dirname()).join(blob_id.filename()))?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("blame file.txt", &repository);
+
+    // assert
+    assert!(result.is_err(), "expected error, got {:?}", result);
+
+    Ok(())
+}
@@ -1,3 +1,6 @@
+use std::fs;
+
+use rut::refs::RefHandler;
 use rut_testhelpers::assert_file_contains;
 
 #[test]
@@ -12,7 +15,7 @@ fn test_create_valid_branch() -> rut::Result<()> {
     // assert
     rut_testhelpers::assert_file_contains(
         &repository.git_dir().join("refs/heads/new-branch"),
-        &commit_oid,
+        &format!("{}\n", commit_oid),
     );
 
     Ok(())
@@ -75,8 +78,231 @@ fn test_branch_off_non_head_commit() -> rut::Result<()> {
     // assert
     assert_file_contains(
         &repository.git_dir().join("refs/heads/new-branch"),
-        &initial_commit_oid,
+        &format!("{}\n", initial_commit_oid),
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_list_branches() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    rut_testhelpers::run_command_string("branch feature", &repository)?;
+    rut_testhelpers::run_command_string("branch bugfix", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("branch", &repository)?;
+
+    // assert
+    assert_eq!(output, "  bugfix\n  feature\n* main\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_list_branches_verbose_includes_packed_refs() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    std::fs::write(
+        repository.git_dir().join("packed-refs"),
+        format!(
+            "# pack-refs with: peeled fully-peeled sorted\n{} refs/heads/old\n",
+            first_commit_id
+        ),
+    )?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("branch -v", &repository)?;
+
+    // assert
+    let expected_output = format!(
+        "* main {} Second commit\n  old  {} First commit\n",
+        &second_commit_id[..7],
+        &first_commit_id[..7]
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_delete_merged_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    rut_testhelpers::run_command_string("branch feature", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("branch -d feature", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!("Deleted branch feature (was {}).\n", &commit_id[..7])
+    );
+    assert!(!repository.git_dir().join("refs/heads/feature").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_deleting_unmerged_branch_without_force() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+    rut_testhelpers::run_command_string("branch feature", &repository)?;
+    std::fs::write(
+        repository.git_dir().join("refs/heads/main"),
+        first_commit_id,
+    )?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("branch -d feature", &repository);
+    let forced_result = rut_testhelpers::run_command_string("branch -D feature", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on deleting unmerged branch"),
+        Err(error) => assert!(error
+            .to_string()
+            .starts_with("fatal: the branch 'feature' is not fully merged.")),
+    }
+    assert!(forced_result.is_ok());
+    assert!(!repository.git_dir().join("refs/heads/feature").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_delete_packed_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let packed_refs = repository.git_dir().join("packed-refs");
+    std::fs::write(
+        &packed_refs,
+        format!("{} refs/heads/old\n{} refs/tags/v1\n", commit_id, commit_id),
+    )?;
+
+    // act
+    rut_testhelpers::run_command_string("branch -d old", &repository)?;
+
+    // assert
+    assert_eq!(
+        std::fs::read_to_string(&packed_refs)?,
+        format!("{} refs/tags/v1\n", commit_id)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rename_current_branch_updates_head() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("branch -m main trunk", &repository)?;
+
+    // assert
+    assert_file_contains(
+        &repository.git_dir().join("refs/heads/trunk"),
+        &format!("{}\n", commit_id),
+    );
+    assert!(!repository.git_dir().join("refs/heads/main").exists());
+    assert_file_contains(&repository.git_dir().join("HEAD"), "ref: refs/heads/trunk");
+
+    Ok(())
+}
+
+#[test]
+fn test_list_branches_as_json() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("branch --format=json", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "{{\"name\":\"main\",\"id\":\"{0}\",\"current\":true,\"upstream\":null}}\n\
+             {{\"name\":\"topic\",\"id\":\"{0}\",\"current\":false,\"upstream\":null}}\n",
+            commit_id
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_branch_set_upstream_to_local_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Initial\n", "Initial commit")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    rut_testhelpers::run_command_string("switch topic", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Topic\n", "Topic commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("branch --set-upstream-to main", &repository)?;
+
+    // assert
+    assert_eq!(output, "branch 'topic' set up to track 'main'.\n");
+    assert_eq!(
+        rut_testhelpers::run_command_string("config branch.topic.remote", &repository)?,
+        ".\n"
+    );
+    assert_eq!(
+        rut_testhelpers::run_command_string("rev-parse @{u}", &repository)?,
+        rut_testhelpers::run_command_string("rev-parse main", &repository)?
+    );
+    let status = rut_testhelpers::run_command_string("status", &repository)?;
+    assert!(status.starts_with("On branch topic\nYour branch is ahead of 'main' by 1 commit.\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_branch_set_upstream_to_remote_tracking_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let commit_id = rut_testhelpers::commit_content(&repository, &file, "Initial\n", "Initial")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    let remote_ref = repository.git_dir().join("refs/remotes/origin/main");
+    fs::create_dir_all(remote_ref.parent().unwrap())?;
+    fs::write(remote_ref, format!("{}\n", commit_id))?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("branch -u origin/main topic", &repository)?;
+    let missing_upstream_result =
+        rut_testhelpers::run_command_string("branch -u origin/gone topic", &repository);
+
+    // assert
+    assert_eq!(output, "branch 'topic' set up to track 'origin/main'.\n");
+    assert_eq!(
+        rut_testhelpers::run_command_string("config branch.topic.remote", &repository)?,
+        "origin\n"
+    );
+    assert_eq!(
+        rut_testhelpers::run_command_string("config branch.topic.merge", &repository)?,
+        "refs/heads/main\n"
     );
+    let upstream = RefHandler::new(&repository).upstream("topic")?.unwrap();
+    assert_eq!(upstream.name, "origin/main");
+    assert_eq!(upstream.object_id.map(|id| id.to_string()), Some(commit_id));
+    assert!(missing_upstream_result.is_err());
 
     Ok(())
 }
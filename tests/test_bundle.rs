@@ -0,0 +1,72 @@
+use std::fs;
+
+#[test]
+fn test_bundle_create_verify_and_unbundle() -> rut::Result<()> {
+    // arrange
+    let source = rut_testhelpers::create_repository();
+    let workdir = source.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&source, &file, "First\n", "Initial commit")?;
+    let head = rut_testhelpers::commit_content(&source, &file, "Second\n", "Second commit")?;
+    let branch = rut_testhelpers::run_command_string("symbolic-ref HEAD", &source)?;
+    let bundle_path = workdir.join("repo.bundle");
+    let destination = rut_testhelpers::create_repository();
+
+    // act
+    rut_testhelpers::run_command_string("bundle create repo.bundle HEAD", &source)?;
+    let verify_output = rut_testhelpers::run_command_string(
+        format!("bundle verify {}", bundle_path.display()),
+        &destination,
+    )?;
+    let unbundle_output = rut_testhelpers::run_command_string(
+        format!("bundle unbundle {}", bundle_path.display()),
+        &destination,
+    )?;
+
+    // assert
+    assert_eq!(
+        verify_output,
+        format!(
+            "The bundle contains this ref:\n{} HEAD\nThe bundle records a complete history.\n{} is okay\n",
+            head,
+            bundle_path.display()
+        )
+    );
+    assert_eq!(unbundle_output, format!("{} HEAD\n", head));
+    rut_testhelpers::run_command_string(
+        format!("update-ref {} {}", branch.trim(), head),
+        &destination,
+    )?;
+    let content = rut_testhelpers::rut_cat_file("HEAD:file.txt", &destination)?;
+    assert_eq!(content, "Second");
+    rut_testhelpers::assert_healthy_repo(&destination.git_dir());
+
+    Ok(())
+}
+
+#[test]
+fn test_bundle_requires_prerequisites() -> rut::Result<()> {
+    // arrange
+    let source = rut_testhelpers::create_repository();
+    let workdir = source.worktree().root();
+    let file = workdir.join("file.txt");
+    let first = rut_testhelpers::commit_content(&source, &file, "First\n", "Initial commit")?;
+    rut_testhelpers::commit_content(&source, &file, "Second\n", "Second commit")?;
+    let bundle_path = workdir.join("repo.bundle");
+    let destination = rut_testhelpers::create_repository();
+
+    // act
+    rut_testhelpers::run_command_string("bundle create repo.bundle HEAD~1..HEAD", &source)?;
+    let header = fs::read(&bundle_path)?;
+    let result = rut_testhelpers::run_command_string(
+        format!("bundle verify {}", bundle_path.display()),
+        &destination,
+    );
+
+    // assert
+    let prerequisite = format!("\n-{} Initial commit\n", first);
+    assert!(String::from_utf8_lossy(&header).contains(&prerequisite));
+    assert!(result.is_err());
+
+    Ok(())
+}
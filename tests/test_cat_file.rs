@@ -0,0 +1,93 @@
+use std::fs;
+
+use rut::objects::{Blob, GitObject};
+
+#[test]
+fn test_cat_file_shows_type_of_commit() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string(format!("cat-file -t {}", commit_id), &repository)?;
+
+    // assert
+    assert_eq!(output, "commit\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_cat_file_shows_size_and_content_of_blob() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "hello\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+
+    // act
+    let size_output =
+        rut_testhelpers::run_command_string(format!("cat-file -s {}", blob.id()), &repository)?;
+    let pretty_output =
+        rut_testhelpers::run_command_string(format!("cat-file -p {}", blob.id()), &repository)?;
+
+    // assert
+    assert_eq!(size_output, "6\n");
+    assert_eq!(pretty_output, "hello\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_cat_file_pretty_prints_tree() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let nested_dir = workdir.join("nested");
+    fs::create_dir(&nested_dir)?;
+    fs::write(workdir.join("file.txt"), "hello\n")?;
+    fs::write(nested_dir.join("other.txt"), "hello\n")?;
+    rut_testhelpers::run_command_string("add .", &repository)?;
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    let commit = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    let tree_id = commit.lines().next().unwrap().trim_start_matches("tree ");
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string(format!("cat-file -p {}", tree_id), &repository)?;
+
+    // assert
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], format!("100644 blob {}\tfile.txt", blob.id()));
+    assert!(lines[1].starts_with("040000 tree "));
+    assert!(lines[1].ends_with("\tnested"));
+
+    Ok(())
+}
+
+#[test]
+fn test_cat_file_batch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "hello\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+
+    // act
+    let output =
+        rut_testhelpers::rut_cat_file_batch(&format!("{}\ndeadbeef\n", blob.id()), &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!("{} blob 6\nhello\n\ndeadbeef missing\n", blob.id())
+    );
+
+    Ok(())
+}
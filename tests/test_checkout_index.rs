@@ -0,0 +1,47 @@
+use std::fs;
+
+#[test]
+fn test_checkout_index_all_restores_deleted_files_and_keeps_modified_ones() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    let nested_file = workdir.join("dir").join("nested.txt");
+    fs::create_dir(workdir.join("dir"))?;
+    rut_testhelpers::commit_content(&repository, &file, "Content\n", "Initial commit")?;
+    rut_testhelpers::commit_content(&repository, &nested_file, "Nested\n", "Add nested file")?;
+    fs::write(&file, "Modified\n")?;
+    fs::remove_dir_all(workdir.join("dir"))?;
+
+    // act
+    let (exit_status, output) =
+        rut_testhelpers::run_command_string_with_exit_status("checkout-index -a", &repository)?;
+
+    // assert
+    assert_eq!(exit_status, 1);
+    assert_eq!(output, "file.txt already exists, no checkout\n");
+    rut_testhelpers::assert_file_contains(&nested_file, "Nested\n");
+    rut_testhelpers::assert_file_contains(&file, "Modified\n");
+    let status = rut_testhelpers::rut_status_porcelain(&repository)?;
+    assert_eq!(status, " M file.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_checkout_index_force_overwrites_files() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Content\n", "Initial commit")?;
+    fs::write(&file, "Modified\n")?;
+
+    // act
+    rut_testhelpers::run_command_string("checkout-index -f file.txt", &repository)?;
+
+    // assert
+    rut_testhelpers::assert_file_contains(&file, "Content\n");
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+
+    Ok(())
+}
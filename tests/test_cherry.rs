@@ -0,0 +1,52 @@
+#[test]
+fn test_cherry_marks_commits_already_applied_upstream() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "1\n2\n3\n4\n5\n", "Base")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    // the same change as the first topic commit, as if it had been cherry-picked
+    rut_testhelpers::commit_content(&repository, &file, "1\ntwo\n3\n4\n5\n", "Picked two")?;
+    rut_testhelpers::commit_content(&repository, &workdir.join("other.txt"), "x\n", "Other")?;
+    rut_testhelpers::run_command_string("switch topic", &repository)?;
+    let picked_id =
+        rut_testhelpers::commit_content(&repository, &file, "1\ntwo\n3\n4\n5\n", "Change two")?;
+    let new_id =
+        rut_testhelpers::commit_content(&repository, &file, "1\ntwo\n3\nfour\n5\n", "Change four")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("cherry main", &repository)?;
+    let verbose_output = rut_testhelpers::run_command_string("cherry -v main", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("- {}\n+ {}\n", picked_id, new_id));
+    assert_eq!(
+        verbose_output,
+        format!("- {} Change two\n+ {} Change four\n", picked_id, new_id)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_cherry_defaults_to_upstream_of_current_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Base\n", "Base")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    rut_testhelpers::run_command_string("switch topic", &repository)?;
+    let topic_id = rut_testhelpers::commit_content(&repository, &file, "Topic\n", "Topic")?;
+    let untracked_result = rut_testhelpers::run_command_string("cherry", &repository);
+    rut_testhelpers::run_command_string("branch --set-upstream-to main", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("cherry", &repository)?;
+
+    // assert
+    assert!(untracked_result.is_err());
+    assert_eq!(output, format!("+ {}\n", topic_id));
+
+    Ok(())
+}
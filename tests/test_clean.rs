@@ -0,0 +1,85 @@
+use std::fs;
+
+use rut::workspace::Repository;
+
+fn create_repository_with_untracked_content() -> rut::Result<Repository> {
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let tracked_file = workdir.join("tracked.txt");
+    rut_testhelpers::commit_content(&repository, &tracked_file, "tracked", "First commit")?;
+
+    let untracked_dir = workdir.join("untracked_dir");
+    fs::create_dir(&untracked_dir)?;
+    fs::write(untracked_dir.join("file.txt"), "untracked")?;
+    fs::write(workdir.join("untracked.txt"), "untracked")?;
+    Ok(repository)
+}
+
+#[test]
+fn test_clean_refuses_without_force_or_dry_run() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_untracked_content()?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("clean", &repository);
+
+    // assert
+    assert!(result.is_err());
+    assert!(repository.worktree().root().join("untracked.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_clean_dry_run_removes_nothing() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_untracked_content()?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("clean -n -d", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "Would remove untracked.txt\nWould remove untracked_dir/\n"
+    );
+    assert!(repository.worktree().root().join("untracked.txt").exists());
+    assert!(repository.worktree().root().join("untracked_dir").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_clean_force_removes_untracked_files_only() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_untracked_content()?;
+    let workdir = repository.worktree().root();
+
+    // act
+    let output = rut_testhelpers::run_command_string("clean -f", &repository)?;
+
+    // assert
+    assert_eq!(output, "Removing untracked.txt\n");
+    assert!(!workdir.join("untracked.txt").exists());
+    assert!(workdir.join("untracked_dir").exists());
+    assert!(workdir.join("tracked.txt").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_clean_force_with_directories() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_untracked_content()?;
+    let workdir = repository.worktree().root();
+
+    // act
+    rut_testhelpers::run_command_string("clean -f -d", &repository)?;
+
+    // assert
+    assert!(!workdir.join("untracked.txt").exists());
+    assert!(!workdir.join("untracked_dir").exists());
+    assert!(workdir.join("tracked.txt").exists());
+
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use std::fs;
 
+use rut::objects::{GitObject, ObjectId, ObjectType, RawObject};
+use rut::refs::RefHandler;
 use rut::workspace::Repository;
 
 #[test]
@@ -73,12 +75,118 @@ fn test_second_commit_gets_proper_parent() -> rut::Result<()> {
 
     rut_testhelpers::assert_file_contains(
         &repository.git_dir().join("refs/heads/main"),
-        &second_commit_sha,
+        &format!("{}\n", second_commit_sha),
     );
 
-    let second_commit_content =
-        rut_testhelpers::git_cat_file(&repository.git_dir(), &second_commit_sha);
-    assert_eq!(second_commit_content.contains(&first_commit_sha), true);
+    let second_commit_content = rut_testhelpers::rut_cat_file(&second_commit_sha, &repository)?;
+    assert!(second_commit_content.contains(&first_commit_sha));
+
+    Ok(())
+}
+
+#[test]
+fn test_amend_replaces_head_commit() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let readme = repository.worktree().root().join("README.md");
+
+    let first_commit_sha =
+        rut_testhelpers::commit_content(&repository, &readme, "First", "First commit")?;
+    let second_commit_sha =
+        rut_testhelpers::commit_content(&repository, &readme, "Second", "Second commit")?;
+    fs::write(&readme, "Amended")?;
+    rut_testhelpers::rut_add(&readme, &repository);
+
+    // act
+    rut_testhelpers::run_command_string("commit --amend --no-edit", &repository)?;
+
+    // assert
+    let amended_commit_sha = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    assert_ne!(amended_commit_sha.trim(), second_commit_sha);
+
+    let amended_commit_content = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    assert!(amended_commit_content.contains(&format!("parent {}", first_commit_sha)));
+    assert!(amended_commit_content.ends_with("Second commit"));
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+
+    Ok(())
+}
+
+#[test]
+fn test_amend_with_new_message() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let readme = repository.worktree().root().join("README.md");
+    rut_testhelpers::commit_content(&repository, &readme, "First", "First commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("commit --amend -m 'Reworded'", &repository)?;
+
+    // assert
+    assert!(output.contains("(root commit)"));
+    assert!(output.ends_with("Reworded\n"));
+    let amended_commit_content = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    assert!(!amended_commit_content.contains("parent"));
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_all_stages_modified_and_deleted_files() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let readme = workdir.join("README.md");
+    let file = workdir.join("file.txt");
+    let untracked = workdir.join("untracked.txt");
+
+    fs::write(&readme, "A README.")?;
+    fs::write(&file, "A file.")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    fs::write(&readme, "A modified README.")?;
+    fs::remove_file(&file)?;
+    fs::write(&untracked, "Untracked")?;
+
+    // act
+    rut_testhelpers::run_command_string("commit -a -m 'Second commit'", &repository)?;
+
+    // assert
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "?? untracked.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_without_message_launches_editor() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let readme = repository.worktree().root().join("README.md");
+    fs::write(&readme, "A README.")?;
+    rut_testhelpers::rut_add(&readme, &repository);
+
+    // act
+    std::env::set_var("GIT_EDITOR", "true");
+    let unedited_result = rut_testhelpers::run_command_string("commit", &repository);
+    std::env::set_var("GIT_EDITOR", "sed -i '1s/^/Edited message/'");
+    let edited_output = rut_testhelpers::run_command_string("commit", &repository)?;
+    std::env::remove_var("GIT_EDITOR");
+
+    // assert
+    match unedited_result {
+        Err(error) => assert_eq!(
+            error.to_string(),
+            "fatal: Aborting commit due to empty commit message."
+        ),
+        Ok(_) => panic!("expected an error"),
+    }
+    assert!(edited_output.ends_with("] Edited message\n"));
+    let commit_content = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    assert!(commit_content.ends_with("\n\nEdited message"));
 
     Ok(())
 }
@@ -91,7 +199,350 @@ fn assert_is_root_tree(repository: &Repository, root_tree_id: &str) {
 
     assert!(root_tree_file.is_file());
 
-    let git_dir = repository.git_dir();
-    let stdout = rut_testhelpers::git_cat_file(&git_dir, "HEAD");
-    assert_eq!(stdout.contains(root_tree_id), true);
+    let stdout = rut_testhelpers::rut_cat_file("HEAD", repository).unwrap();
+    assert!(stdout.contains(root_tree_id));
+}
+
+#[test]
+fn test_commit_without_identity_asks_who_you_are() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let run_commit = || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_rut"))
+            .args(["commit", "-m", "Message"])
+            .current_dir(workdir)
+            .env_remove("GIT_AUTHOR_NAME")
+            .env_remove("GIT_AUTHOR_EMAIL")
+            .env("HOME", workdir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .output()
+            .unwrap()
+    };
+
+    // act
+    let output_without_identity = run_commit();
+    fs::write(
+        repository.git_dir().join("config"),
+        "[user]\n\tname = Local\n\temail = local@rut.com\n",
+    )?;
+    let output_with_local_identity = run_commit();
+
+    // assert
+    let stdout = String::from_utf8_lossy(&output_without_identity.stdout);
+    assert!(!output_without_identity.status.success());
+    assert!(stdout.starts_with("fatal: Author identity unknown"));
+    assert!(stdout.contains("*** Please tell me who you are."));
+    assert!(output_with_local_identity.status.success());
+    assert!(rut_testhelpers::rut_cat_file("HEAD", &repository)?
+        .contains("author Local <local@rut.com>"));
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_with_separate_committer_and_dates() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    // act
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rut"))
+        .args(["commit", "-m", "Message"])
+        .current_dir(workdir)
+        .env("GIT_AUTHOR_NAME", "Author")
+        .env("GIT_AUTHOR_EMAIL", "author@rut.com")
+        .env("GIT_AUTHOR_DATE", "@1700000000 +0100")
+        .env("GIT_COMMITTER_NAME", "Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@rut.com")
+        .env("GIT_COMMITTER_DATE", "Wed, 15 Nov 2023 00:00:00 -0500")
+        .output()
+        .unwrap();
+
+    // assert
+    assert!(output.status.success());
+    let commit = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    assert!(commit.contains("\nauthor Author <author@rut.com> 1700000000 +0100\n"));
+    assert!(commit.contains("\ncommitter Committer <committer@rut.com> 1700024400 -0500\n"));
+    rut_testhelpers::assert_healthy_repo(&repository.git_dir());
+
+    Ok(())
+}
+
+#[test]
+fn test_loaded_commit_keeps_committer_and_time_zones() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rut"))
+        .args(["commit", "-m", "Message"])
+        .current_dir(workdir)
+        .env("GIT_AUTHOR_DATE", "1600000000 +0530")
+        .env("GIT_COMMITTER_NAME", "Other Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@rut.com")
+        .env("GIT_COMMITTER_DATE", "1600000100 -0800")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let head_id = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+
+    // act
+    let commit = repository
+        .database
+        .load_commit(&ObjectId::from_sha(head_id.trim()).unwrap())?;
+
+    // assert
+    assert_eq!(commit.id_as_string(), head_id.trim());
+    assert_eq!(commit.timezone, "+0530");
+    assert_eq!(commit.committer.name, "Other Committer");
+    assert_eq!(commit.committer_timestamp, 1600000100);
+    assert_eq!(commit.committer_timezone, "-0800");
+
+    Ok(())
+}
+
+#[test]
+fn test_loaded_commit_with_unknown_headers_round_trips() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let raw_commit = "\
+tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904
+author Author <author@rut.com> 1600000000 +0200
+committer Committer <committer@rut.com> 1600000000 +0200
+encoding UTF-8
+gpgsig -----BEGIN PGP SIGNATURE-----
+ 
+ iQEzBAABCAAdFiEE
+ =abcd
+ -----END PGP SIGNATURE-----
+mergetag object 4b825dc642cb6eb9a060e54bf8d69288fbee4904
+
+Signed commit
+";
+    let raw_object = RawObject::new(
+        ObjectType::Commit,
+        raw_commit.as_bytes().to_vec(),
+        repository.hash_algorithm(),
+    );
+    repository.database.store_object(&raw_object)?;
+
+    // act
+    let commit = repository.database.load_commit(raw_object.id())?;
+
+    // assert
+    assert_eq!(commit.id(), raw_object.id());
+    assert_eq!(commit.committer.name, "Committer");
+    assert_eq!(commit.message, "Signed commit\n");
+    assert_eq!(
+        commit.to_object_format(),
+        [
+            format!("commit {}\0", raw_commit.len()).as_bytes(),
+            raw_commit.as_bytes()
+        ]
+        .concat()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_loaded_commit_with_latin1_message_and_gpgsig_round_trips() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let raw_commit = [
+        "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n".as_bytes(),
+        b"author Author <author@rut.com> 1600000000 +0200\n",
+        b"committer Committer <committer@rut.com> 1600000000 +0200\n",
+        b"encoding ISO-8859-1\n",
+        b"gpgsig -----BEGIN PGP SIGNATURE-----\n",
+        b" \n",
+        b" iQEzBAABCAAdFiEE\xe9\n",
+        b" -----END PGP SIGNATURE-----\n",
+        b"\n",
+        b"Caf\xe9 au lait\n",
+    ]
+    .concat();
+    let raw_object = RawObject::new(
+        ObjectType::Commit,
+        raw_commit.clone(),
+        repository.hash_algorithm(),
+    );
+    repository.database.store_object(&raw_object)?;
+
+    // act
+    let commit = repository.database.load_commit(raw_object.id())?;
+
+    // assert
+    assert_eq!(commit.id(), raw_object.id());
+    assert_eq!(commit.raw_message(), b"Caf\xe9 au lait\n");
+    assert_eq!(commit.message, "Caf\u{fffd} au lait\n");
+    assert_eq!(commit.to_object_format(), raw_object.to_object_format());
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_loading_commit_with_non_utf8_author() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let raw_commit = [
+        "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n".as_bytes(),
+        b"author Jos\xe9 <jose@rut.com> 1600000000 +0200\n",
+        b"committer Committer <committer@rut.com> 1600000000 +0200\n",
+        b"\n",
+        b"Message\n",
+    ]
+    .concat();
+    let raw_object = RawObject::new(ObjectType::Commit, raw_commit, repository.hash_algorithm());
+    repository.database.store_object(&raw_object)?;
+
+    // act
+    let result = repository.database.load_commit(raw_object.id());
+
+    // assert
+    assert_eq!(
+        result.err().map(|error| error.to_string()),
+        Some(format!(
+            "fatal: object {} is corrupt: author is not valid UTF-8",
+            raw_object.id()
+        ))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_records_cache_tree_of_root_tree() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir(workdir.join("nested"))?;
+    fs::write(workdir.join("README.md"), "A README.")?;
+    fs::write(workdir.join("nested/file.txt"), "A file.")?;
+    rut_testhelpers::run_command_string("add README.md nested", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("commit -m 'Initial commit'", &repository)?;
+
+    // assert
+    let head_commit = repository
+        .database
+        .load_commit(&RefHandler::new(&repository).head()?)?;
+    let index = repository.load_index_unlocked()?;
+    assert_eq!(index.cache_tree().object_id(), Some(&head_commit.tree));
+    assert_eq!(index.cache_tree().entry_count(), Some(2));
+    let nested_tree = index.cache_tree().subtree("nested").unwrap();
+    assert_eq!(nested_tree.entry_count(), Some(1));
+    assert!(
+        rut_testhelpers::run_command_string("ls-tree HEAD", &repository)?.contains(&format!(
+            "040000 tree {}\tnested\n",
+            nested_tree.object_id().unwrap()
+        ))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_rebuilds_trees_invalidated_by_add_and_rm() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir(workdir.join("first"))?;
+    fs::create_dir(workdir.join("second"))?;
+    fs::write(workdir.join("first/file.txt"), "first")?;
+    fs::write(workdir.join("second/file.txt"), "second")?;
+    fs::write(workdir.join("second/other.txt"), "other")?;
+    rut_testhelpers::run_command_string("add first second", &repository)?;
+    rut_testhelpers::run_command_string("commit -m 'Initial commit'", &repository)?;
+    fs::write(workdir.join("first/file.txt"), "modified")?;
+    rut_testhelpers::run_command_string("add first/file.txt", &repository)?;
+    rut_testhelpers::run_command_string("rm second/other.txt", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("commit -m 'Second commit'", &repository)?;
+
+    // assert
+    let head_commit = repository
+        .database
+        .load_commit(&RefHandler::new(&repository).head()?)?;
+    // build the tree from scratch in a fresh repository with the same files
+    let scratch_repository = rut_testhelpers::create_repository();
+    let scratch_workdir = scratch_repository.worktree().root();
+    fs::create_dir(scratch_workdir.join("first"))?;
+    fs::create_dir(scratch_workdir.join("second"))?;
+    fs::write(scratch_workdir.join("first/file.txt"), "modified")?;
+    fs::write(scratch_workdir.join("second/file.txt"), "second")?;
+    rut_testhelpers::run_command_string("add first second", &scratch_repository)?;
+    assert_eq!(
+        rut_testhelpers::run_command_string("write-tree", &scratch_repository)?,
+        format!("{}\n", head_commit.tree)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_concludes_merge_in_progress() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Base\n", "Add file")?;
+    let branch = RefHandler::new(&repository).current_branch()?.unwrap();
+    rut_testhelpers::run_command_string("branch other", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Ours\n", "Change file")?;
+    rut_testhelpers::run_command_string("switch other", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Theirs\n", "Change file")?;
+    rut_testhelpers::run_command_string(format!("switch {}", branch), &repository)?;
+    rut_testhelpers::rut_merge_with_conflicts("other", &repository)?;
+    fs::write(&file, "Resolved\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let ours_id = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    let theirs_id = rut_testhelpers::run_command_string("rev-parse other", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("commit --no-edit", &repository)?;
+
+    // assert
+    let commit = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    assert!(commit.contains(&format!("parent {}parent {}", ours_id, theirs_id)));
+    assert!(commit.ends_with("\n\nMerge branch 'other'\n\n# Conflicts:\n#\tfile.txt"));
+    assert!(!repository.git_dir().join("MERGE_HEAD").exists());
+    assert!(!repository.git_dir().join("MERGE_MSG").exists());
+    let reflog = fs::read_to_string(repository.git_dir().join("logs/HEAD"))?;
+    assert!(reflog.ends_with("\tcommit (merge): Merge branch 'other'\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_with_unmerged_paths_fails() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Base\n", "Add file")?;
+    let branch = RefHandler::new(&repository).current_branch()?.unwrap();
+    rut_testhelpers::run_command_string("branch other", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Ours\n", "Change file")?;
+    rut_testhelpers::run_command_string("switch other", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Theirs\n", "Change file")?;
+    rut_testhelpers::run_command_string(format!("switch {}", branch), &repository)?;
+    rut_testhelpers::rut_merge_with_conflicts("other", &repository)?;
+    let head_before = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("commit -m Merge", &repository);
+
+    // assert
+    match result {
+        Err(rut::Error::Fatal(_, message)) => assert_eq!(
+            message,
+            "Committing is not possible because you have unmerged files."
+        ),
+        other => panic!("expected the commit to fail, got {:?}", other),
+    }
+    let head_after = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    assert_eq!(head_after, head_before);
+
+    Ok(())
 }
@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_commit_graph_write_is_used_by_history_walks() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let base_id = rut_testhelpers::commit_content(&repository, &file, "Base\n", "Base")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Main\n", "Main")?;
+    rut_testhelpers::run_command_string("switch topic", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Topic\n", "Topic")?;
+
+    // act
+    rut_testhelpers::run_command_string("commit-graph write", &repository)?;
+    // commits made after writing the graph aren't in it
+    rut_testhelpers::commit_content(&repository, &file, "Topic 2\n", "Topic 2")?;
+
+    // assert
+    assert!(repository
+        .git_dir()
+        .join("objects/info/commit-graph")
+        .is_file());
+    assert_eq!(
+        rut_testhelpers::run_command_string("merge-base main topic", &repository)?,
+        format!("{}\n", base_id)
+    );
+    assert_eq!(
+        rut_testhelpers::run_command_string("rev-list --count main..topic", &repository)?,
+        "2\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_graph_write_replaces_existing_graph() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "First\n", "First")?;
+    rut_testhelpers::run_command_string("commit-graph write", &repository)?;
+    let graph_path = repository.git_dir().join("objects/info/commit-graph");
+    let first_graph = fs::read(&graph_path)?;
+    rut_testhelpers::commit_content(&repository, &file, "Second\n", "Second")?;
+
+    // act
+    rut_testhelpers::run_command_string("commit-graph write", &repository)?;
+
+    // assert
+    let second_graph = fs::read(&graph_path)?;
+    assert!(second_graph.len() > first_graph.len());
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_list_orders_by_committer_date_with_and_without_graph() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Base\n", "Base")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    // the author dates are in the opposite order of the committer dates
+    fs::write(&file, "Main\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    commit_with_dates("Main", "@1700000100 +0000", "@1700000300 +0000", workdir);
+    rut_testhelpers::run_command_string("switch topic", &repository)?;
+    fs::write(&file, "Topic\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    commit_with_dates("Topic", "@1700000400 +0000", "@1700000200 +0000", workdir);
+    let main_id = rut_testhelpers::run_command_string("rev-parse main", &repository)?;
+    let topic_id = rut_testhelpers::run_command_string("rev-parse topic", &repository)?;
+
+    // act
+    let without_graph = rut_testhelpers::run_command_string("rev-list main...topic", &repository)?;
+    rut_testhelpers::run_command_string("commit-graph write", &repository)?;
+    let with_graph = rut_testhelpers::run_command_string("rev-list main...topic", &repository)?;
+
+    // assert
+    assert_eq!(without_graph, format!("{}{}", main_id, topic_id));
+    assert_eq!(with_graph, without_graph);
+
+    Ok(())
+}
+
+fn commit_with_dates(message: &str, author_date: &str, committer_date: &str, workdir: &Path) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rut"))
+        .args(["commit", "-m", message])
+        .current_dir(workdir)
+        .env("GIT_AUTHOR_NAME", "Author")
+        .env("GIT_AUTHOR_EMAIL", "author@rut.com")
+        .env("GIT_AUTHOR_DATE", author_date)
+        .env("GIT_COMMITTER_NAME", "Committer")
+        .env("GIT_COMMITTER_EMAIL", "committer@rut.com")
+        .env("GIT_COMMITTER_DATE", committer_date)
+        .output()
+        .expect("Failed running rut");
+    assert!(output.status.success());
+}
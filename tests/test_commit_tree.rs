@@ -0,0 +1,51 @@
+use std::fs;
+
+#[test]
+fn test_write_tree_and_commit_tree_build_history_without_moving_refs() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let head = rut_testhelpers::commit_content(&repository, &file, "First\n", "Initial commit")?;
+    fs::write(&file, "Second\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+
+    // act
+    let tree = rut_testhelpers::run_command_string("write-tree", &repository)?;
+    let commit = rut_testhelpers::run_command_string(
+        format!("commit-tree {} -p HEAD -m Subject -m Body", tree.trim()),
+        &repository,
+    )?;
+
+    // assert
+    let commit = commit.trim();
+    let content = rut_testhelpers::rut_cat_file(commit, &repository)?;
+    assert!(content.starts_with(&format!("tree {}\nparent {}\n", tree.trim(), head)));
+    assert!(content.ends_with("\n\nSubject\n\nBody"));
+    let file_content = rut_testhelpers::rut_cat_file(&format!("{}:file.txt", commit), &repository)?;
+    assert_eq!(file_content, "Second");
+    let current_head = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    assert_eq!(current_head.trim(), head);
+    rut_testhelpers::assert_healthy_repo(&repository.git_dir());
+
+    Ok(())
+}
+
+#[test]
+fn test_read_tree_replaces_index_with_tree() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "First\n", "Initial commit")?;
+    let other_file = workdir.join("other.txt");
+    rut_testhelpers::commit_content(&repository, &other_file, "Other\n", "Add other file")?;
+
+    // act
+    rut_testhelpers::run_command_string("read-tree HEAD~1", &repository)?;
+
+    // assert
+    let status = rut_testhelpers::rut_status_porcelain(&repository)?;
+    assert_eq!(status, "D  other.txt\n?? other.txt\n");
+
+    Ok(())
+}
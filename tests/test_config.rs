@@ -1,8 +1,8 @@
-use rut::config;
+use rut::config::{self, Config, ConfigLevel};
 use std::fs;
 
 #[test]
-fn test_parse_gitconfig_with_values() {
+fn test_read_config_file_with_values() {
     // arrange
     let gitconfig_content = "[user]\nname = John Doe\nemail = john@doe.com";
     let tempdir = rut_testhelpers::create_temporary_directory();
@@ -10,9 +10,219 @@ fn test_parse_gitconfig_with_values() {
     fs::write(&gitconfig_path, gitconfig_content).unwrap();
 
     // act
-    let parsed_config = config::parse_gitconfig(&gitconfig_path).unwrap();
+    let parsed_config = Config::from_files(&[(ConfigLevel::Global, &gitconfig_path)]).unwrap();
 
     // assert
-    assert_eq!(parsed_config.name, Some("John Doe".to_string()));
-    assert_eq!(parsed_config.email, Some("john@doe.com".to_string()));
+    assert_eq!(
+        parsed_config.get_string("user.name"),
+        Some("John Doe".to_string())
+    );
+    assert_eq!(
+        parsed_config.get_string("user.email"),
+        Some("john@doe.com".to_string())
+    );
+}
+
+#[test]
+fn test_read_nonexisting_config_file() {
+    // arrange
+    let tempdir = rut_testhelpers::create_temporary_directory();
+    let gitconfig_path = tempdir.join(".gitconfig");
+
+    // act
+    let entries = config::read_config_file(&gitconfig_path, ConfigLevel::Global).unwrap();
+
+    // assert
+    assert!(entries.is_empty());
+}
+
+#[test]
+fn test_later_levels_take_precedence() {
+    // arrange
+    let tempdir = rut_testhelpers::create_temporary_directory();
+    let global_config = tempdir.join("global");
+    let local_config = tempdir.join("local");
+    fs::write(
+        &global_config,
+        "[user]\nname = Global\nemail = global@rut.com",
+    )
+    .unwrap();
+    fs::write(&local_config, "[User]\n\tName = Local").unwrap();
+
+    // act
+    let config = Config::from_files(&[
+        (ConfigLevel::Global, &global_config),
+        (ConfigLevel::Local, &local_config),
+    ])
+    .unwrap();
+
+    // assert
+    assert_eq!(config.get_string("user.name"), Some("Local".to_string()));
+    assert_eq!(
+        config.get_string("user.email"),
+        Some("global@rut.com".to_string())
+    );
+}
+
+#[test]
+fn test_typed_getters() {
+    // arrange
+    let config_content = "\
+# a comment
+[core]
+\tbare = false ; another comment
+\tfilemode
+[pack]
+\twindowMemory = 2k
+[remote \"Origin\"]
+\tfetch = +refs/heads/*:refs/remotes/origin/*
+\tfetch = \"+refs/tags/*:refs/tags/*\"
+[alias]
+\tgreeting = \"hello  \\\"world\\\"\"
+";
+    let tempdir = rut_testhelpers::create_temporary_directory();
+    let config_path = tempdir.join("config");
+    fs::write(&config_path, config_content).unwrap();
+
+    // act
+    let config = Config::from_files(&[(ConfigLevel::Local, &config_path)]).unwrap();
+
+    // assert
+    assert_eq!(config.get_bool("core.bare").unwrap(), Some(false));
+    assert_eq!(config.get_bool("core.filemode").unwrap(), Some(true));
+    assert_eq!(config.get_bool("core.missing").unwrap(), None);
+    assert_eq!(config.get_int("pack.windowmemory").unwrap(), Some(2048));
+    assert_eq!(
+        config.get_all("remote.Origin.fetch"),
+        vec![
+            "+refs/heads/*:refs/remotes/origin/*",
+            "+refs/tags/*:refs/tags/*"
+        ]
+    );
+    assert!(config.get_all("remote.origin.fetch").is_empty());
+    assert_eq!(
+        config.get_string("alias.greeting"),
+        Some("hello  \"world\"".to_string())
+    );
+}
+
+#[test]
+fn test_bad_config_values() {
+    // arrange
+    let tempdir = rut_testhelpers::create_temporary_directory();
+    let config_path = tempdir.join("config");
+    fs::write(&config_path, "[core]\n\tbare = maybe\n").unwrap();
+    let bad_config_path = tempdir.join("bad_config");
+    fs::write(&bad_config_path, "[core\n").unwrap();
+
+    // act
+    let config = Config::from_files(&[(ConfigLevel::Local, &config_path)]).unwrap();
+    let bad_config_result = Config::from_files(&[(ConfigLevel::Local, &bad_config_path)]);
+
+    // assert
+    assert_eq!(
+        config.get_bool("core.bare").unwrap_err().to_string(),
+        "fatal: bad boolean config value 'maybe' for 'core.bare'"
+    );
+    assert_eq!(
+        bad_config_result.unwrap_err().to_string(),
+        format!(
+            "fatal: bad config line 1 in file {}",
+            bad_config_path.display()
+        )
+    );
+}
+
+#[test]
+fn test_config_command_sets_and_gets_values() {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    // act
+    rut_testhelpers::run_command_string("config --local user.name Rut", &repository).unwrap();
+    rut_testhelpers::run_command_string("config remote.Origin.URL there", &repository).unwrap();
+    let name = rut_testhelpers::run_command_string("config --get user.name", &repository).unwrap();
+    let missing = rut_testhelpers::run_command_with_exit_status(
+        vec!["config", "--local", "user.email"],
+        repository.worktree().root(),
+    )
+    .unwrap();
+    let listing = rut_testhelpers::run_command_string("config --local -l", &repository).unwrap();
+
+    // assert
+    assert_eq!(name, "Rut\n");
+    assert_eq!(missing, (1, String::new()));
+    rut_testhelpers::assert_file_contains(
+        &repository.git_dir().join("config"),
+        "[user]\n\tname = Rut\n[remote \"Origin\"]\n\tURL = there\n",
+    );
+    assert_eq!(listing, "user.name=Rut\nremote.Origin.url=there\n");
+}
+
+#[test]
+fn test_config_command_preserves_comments_and_ordering() {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let config_path = repository.git_dir().join("config");
+    let config_content = "\
+# my settings
+[user]
+\tname = Old Name ; replace me
+[core]
+\teditor = vi
+[user]
+\temail = rut@rut.com
+";
+    fs::write(&config_path, config_content).unwrap();
+
+    // act
+    rut_testhelpers::run_command_string("config user.name New", &repository).unwrap();
+    rut_testhelpers::run_command_string("config user.signingKey abc", &repository).unwrap();
+    rut_testhelpers::run_command_string("config --local --unset core.editor", &repository).unwrap();
+
+    // assert
+    rut_testhelpers::assert_file_contains(
+        &config_path,
+        "\
+# my settings
+[user]
+\tname = New
+[core]
+[user]
+\temail = rut@rut.com
+\tsigningKey = abc
+",
+    );
+}
+
+#[test]
+fn test_config_command_errors() {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    fs::write(
+        repository.git_dir().join("config"),
+        "[remote \"origin\"]\n\tfetch = a\n\tfetch = b\n",
+    )
+    .unwrap();
+
+    // act
+    let no_section_result = rut_testhelpers::run_command_string("config name rut", &repository);
+    let multiple_values_result =
+        rut_testhelpers::run_command_string("config remote.origin.fetch c", &repository);
+    let unset_missing_result =
+        rut_testhelpers::run_command_string("config --unset user.name", &repository);
+
+    // assert
+    assert_eq!(
+        no_section_result.unwrap_err().to_string(),
+        "fatal: key does not contain a section: name"
+    );
+    assert_eq!(
+        multiple_values_result.unwrap_err().to_string(),
+        "fatal: cannot overwrite multiple values of 'remote.origin.fetch' with a single value"
+    );
+    assert_eq!(
+        unset_missing_result.unwrap_err().to_string(),
+        "fatal: key 'user.name' is not set"
+    );
 }
@@ -0,0 +1,61 @@
+use std::fs;
+
+#[test]
+fn test_count_objects_counts_loose_objects() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    rut_testhelpers::commit_content(
+        &repository,
+        &workdir.join("file.txt"),
+        "Content\n",
+        "Initial commit",
+    )?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("count-objects", &repository)?;
+
+    // assert
+    assert!(output.starts_with("3 objects, "));
+    assert!(output.ends_with(" kilobytes\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_count_objects_verbose_reports_garbage() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    rut_testhelpers::commit_content(
+        &repository,
+        &workdir.join("file.txt"),
+        "Content\n",
+        "Initial commit",
+    )?;
+    let fanout_dir = repository.git_dir().join("objects").join("ab");
+    fs::create_dir_all(&fanout_dir)?;
+    fs::write(fanout_dir.join("not-an-object"), "Garbage\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("count-objects -v", &repository)?;
+
+    // assert
+    let stats = output
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .filter(|(name, _)| !name.starts_with("size"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stats,
+        vec![
+            ("count", "3"),
+            ("in-pack", "0"),
+            ("packs", "0"),
+            ("prune-packable", "0"),
+            ("garbage", "1"),
+        ]
+    );
+
+    Ok(())
+}
@@ -194,6 +194,34 @@ index 9649cde..0000000
     Ok(())
 }
 
+#[test]
+fn test_diff_stat() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    let other_file = workdir.join("other_file.txt");
+    fs::write(&file, "First line\nSecond line\n")?;
+    fs::write(&other_file, "First line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "First line\nChanged line\nThird line\n")?;
+    fs::remove_file(&other_file)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff --stat", &repository)?;
+
+    // assert
+    let expected_output = " file.txt       | 3 ++-
+ other_file.txt | 1 -
+ 2 files changed, 2 insertions(+), 2 deletions(-)
+";
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
 fn create_committed_file_with_staged_changes(
     repository: &Repository,
     file: &Path,
@@ -240,3 +268,563 @@ fn create_expected_header<P: AsRef<Path>>(filepath: P, old_blob: &Blob, new_blob
         filepath.as_ref().display(),
     )
 }
+
+#[test]
+fn test_diff_limited_by_pathspec() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    let other_file = workdir.join("other.txt");
+    fs::write(&file, "First line")?;
+    fs::write(&other_file, "First line")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "Changed line")?;
+    fs::write(&other_file, "Changed line")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff other.txt", &repository)?;
+
+    // assert
+    assert!(output.contains("diff --git a/other.txt b/other.txt"));
+    assert!(!output.contains("file.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_cached_shows_rename_with_changes() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    fs::write(&old_file, "a\nb\nc\nd\n")?;
+    rut_testhelpers::rut_add(&old_file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    let new_file = workdir.join("new.txt");
+    fs::write(&new_file, "a\nb\nc\nx\n")?;
+    rut_testhelpers::rut_add(&new_file, &repository);
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff --cached", &repository)?;
+
+    // assert
+    let expected_output = "diff --git a/old.txt b/new.txt
+similarity index 75%
+rename from old.txt
+rename to new.txt
+index d68dd40..2155939
+--- a/old.txt
++++ b/new.txt
+@@ -1,4 +1,4 @@
+ a
+ b
+ c
+-d
++x
+";
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_cached_shows_pure_rename_without_content() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    fs::write(&old_file, "First line\n")?;
+    rut_testhelpers::rut_add(&old_file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff --cached", &repository)?;
+
+    // assert
+    let expected_output = "diff --git a/old.txt b/new.txt
+similarity index 100%
+rename from old.txt
+rename to new.txt
+";
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_rename_threshold() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    fs::write(&old_file, "a\nb\nc\nd\n")?;
+    rut_testhelpers::rut_add(&old_file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    let new_file = workdir.join("new.txt");
+    fs::write(&new_file, "a\nb\nc\nx\n")?;
+    rut_testhelpers::rut_add(&new_file, &repository);
+
+    // act
+    let renamed_output =
+        rut_testhelpers::run_command_string("diff --cached --stat -M7", &repository)?;
+    let unrenamed_output =
+        rut_testhelpers::run_command_string("diff --cached --stat -M8", &repository)?;
+
+    // assert
+    assert!(renamed_output.starts_with(" old.txt => new.txt | 2 +-\n"));
+    assert!(unrenamed_output.contains(" new.txt | 4 ++++\n old.txt | 4 ----\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_without_context_lines() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "a\nb\nc\nd\ne\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "a\nb\nnew\nc\ne\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff -U0", &repository)?;
+
+    // assert
+    let hunks = output.lines().skip(4).collect::<Vec<_>>();
+    assert_eq!(hunks, vec!["@@ -2,0 +3 @@", "+new", "@@ -4 +4,0 @@", "-d"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_merges_changes_with_overlapping_context() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "1\n2\n3\n4\n5\n6\n7\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "1\nx\n3\n4\n5\ny\n7\n")?;
+
+    // act
+    let separate_output = rut_testhelpers::run_command_string("diff --unified=1", &repository)?;
+    let merged_output = rut_testhelpers::run_command_string("diff --unified=2", &repository)?;
+
+    // assert
+    let hunk_headers = |output: &str| {
+        output
+            .lines()
+            .filter(|line| line.starts_with("@@"))
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        hunk_headers(&separate_output),
+        vec!["@@ -1,3 +1,3 @@", "@@ -5,3 +5,3 @@"]
+    );
+    assert_eq!(hunk_headers(&merged_output), vec!["@@ -1,7 +1,7 @@"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_addition_to_empty_file() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "First line\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff", &repository)?;
+
+    // assert
+    assert!(output.ends_with("@@ -0,0 +1 @@\n+First line\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_ref() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    fs::write(&file, "First line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "Second line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+
+    // one staged and one unstaged change on top of the second commit
+    let new_file = workdir.join("new.txt");
+    fs::write(&new_file, "New line\n")?;
+    rut_testhelpers::rut_add(&new_file, &repository);
+    fs::write(&file, "Third line\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff HEAD~1", &repository)?;
+
+    // assert
+    let expected_output = "diff --git a/file.txt b/file.txt
+index 9649cde..17354a8
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-First line
++Third line
+diff --git a/new.txt b/new.txt
+index 0000000..5dbf8d7
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++New line
+";
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_ref_limited_by_pathspec() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    let other_file = workdir.join("other.txt");
+    fs::write(&file, "First line\n")?;
+    fs::write(&other_file, "First line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "Changed line\n")?;
+    fs::remove_file(&other_file)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff HEAD other.txt", &repository)?;
+
+    // assert
+    assert!(output.starts_with("diff --git a/other.txt b/other.txt\n"));
+    assert!(output.contains("+++ /dev/null\n"));
+    assert!(!output.contains("file.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_cached_against_ref() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "First line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "Second line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+    fs::write(&file, "Unstaged line\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff --cached HEAD~1", &repository)?;
+
+    // assert
+    assert!(output.ends_with("-First line\n+Second line\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_cached_against_ref_limited_by_pathspec_after_separator() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    let other_file = workdir.join("other.txt");
+    fs::write(&file, "First line\n")?;
+    fs::write(&other_file, "First line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "Second line\n")?;
+    fs::write(&other_file, "Second line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string("diff --cached HEAD~1 -- other.txt", &repository)?;
+
+    // assert
+    assert!(output.starts_with("diff --git a/other.txt b/other.txt\n"));
+    assert!(!output.contains("file.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_path_after_separator_is_not_taken_as_revision() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("HEAD");
+    fs::write(&file, "First line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+    fs::write(&file, "Second line\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff -- HEAD", &repository)?;
+
+    // assert
+    assert!(output.starts_with("diff --git a/HEAD b/HEAD\n"));
+    assert!(output.ends_with("-First line\n+Second line\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_name_only_and_name_status() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let modified_file = workdir.join("modified.txt");
+    let deleted_file = workdir.join("deleted.txt");
+    fs::write(&modified_file, "First line\n")?;
+    fs::write(&deleted_file, "First line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&modified_file, "Second line\n")?;
+    fs::remove_file(&deleted_file)?;
+    let added_file = workdir.join("added.txt");
+    fs::write(&added_file, "First line\nSecond line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+
+    // act
+    let name_only_output =
+        rut_testhelpers::run_command_string("diff --cached --name-only", &repository)?;
+    let name_status_output =
+        rut_testhelpers::run_command_string("diff --cached --name-status", &repository)?;
+
+    // assert
+    assert_eq!(name_only_output, "added.txt\ndeleted.txt\nmodified.txt\n");
+    assert_eq!(
+        name_status_output,
+        "A\tadded.txt\nD\tdeleted.txt\nM\tmodified.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_name_status_of_rename() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    fs::write(&old_file, "a\nb\nc\nd\n")?;
+    rut_testhelpers::rut_add(&old_file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    fs::write(workdir.join("new.txt"), "a\nb\nc\nx\n")?;
+    rut_testhelpers::rut_add(&workdir.join("new.txt"), &repository);
+
+    // act
+    let output = rut_testhelpers::run_command_string("diff --cached --name-status", &repository)?;
+
+    // assert
+    assert_eq!(output, "R075\told.txt\tnew.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_names_terminated_by_nul() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old name.txt");
+    fs::write(&old_file, "a\nb\nc\nd\n")?;
+    rut_testhelpers::rut_add(&old_file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::rename(&old_file, workdir.join("new\tname.txt"))?;
+    rut_testhelpers::rut_add(workdir, &repository);
+
+    // act
+    let name_only_output =
+        rut_testhelpers::run_command_string("diff --cached --name-only -z", &repository)?;
+    let name_status_output =
+        rut_testhelpers::run_command_string("diff --cached --name-status -z", &repository)?;
+
+    // assert
+    assert_eq!(name_only_output, "new\tname.txt\0");
+    assert_eq!(name_status_output, "R100\0old name.txt\0new\tname.txt\0");
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_with_patience_and_histogram_algorithms() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "}\nx\nx\ny\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "y\nx\nx\nz\n")?;
+
+    // act
+    let myers_output = rut_testhelpers::run_command_string("diff", &repository)?;
+    let patience_output =
+        rut_testhelpers::run_command_string("diff --diff-algorithm=patience", &repository)?;
+    let histogram_output =
+        rut_testhelpers::run_command_string("diff --diff-algorithm=histogram", &repository)?;
+
+    // assert
+    let hunks = |output: &str| {
+        output
+            .lines()
+            .skip(4)
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        hunks(&myers_output),
+        vec!["@@ -1,4 +1,4 @@", "-}", "+y", " x", " x", "-y", "+z"]
+    );
+    let expected_hunks = vec!["@@ -1,4 +1,4 @@", "-}", "-x", "-x", " y", "+x", "+x", "+z"];
+    assert_eq!(hunks(&patience_output), expected_hunks);
+    assert_eq!(hunks(&histogram_output), expected_hunks);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_algorithm_from_config() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "}\nx\nx\ny\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "y\nx\nx\nz\n")?;
+    rut_testhelpers::run_command_string("config diff.algorithm histogram", &repository)?;
+
+    // act
+    let config_output = rut_testhelpers::run_command_string("diff", &repository)?;
+    let overridden_output =
+        rut_testhelpers::run_command_string("diff --diff-algorithm=myers", &repository)?;
+
+    // assert
+    assert!(config_output.ends_with(" y\n+x\n+x\n+z\n"));
+    assert!(overridden_output.ends_with(" x\n x\n-y\n+z\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_exit_code() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "First line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    // act
+    let unchanged_result =
+        rut_testhelpers::run_command_string_with_exit_status("diff --exit-code", &repository)?;
+    fs::write(&file, "Second line\n")?;
+    let changed_result =
+        rut_testhelpers::run_command_string_with_exit_status("diff --exit-code", &repository)?;
+    let default_result = rut_testhelpers::run_command_string_with_exit_status("diff", &repository)?;
+
+    // assert
+    assert_eq!(unchanged_result, (0, String::new()));
+    assert_eq!(changed_result.0, 1);
+    assert!(changed_result.1.ends_with("-First line\n+Second line\n"));
+    assert_eq!(default_result.0, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_quiet() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "First line\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    fs::write(&file, "Second line\n")?;
+
+    // act
+    let unstaged_result =
+        rut_testhelpers::run_command_string_with_exit_status("diff --quiet", &repository)?;
+    let staged_result =
+        rut_testhelpers::run_command_string_with_exit_status("diff --quiet --cached", &repository)?;
+
+    // assert
+    assert_eq!(unstaged_result, (1, String::new()));
+    assert_eq!(staged_result, (0, String::new()));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_name_status_as_json() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    fs::write(&old_file, "a\nb\nc\nd\n")?;
+    rut_testhelpers::rut_add(&old_file, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    fs::write(workdir.join("new.txt"), "a\nb\nc\nx\n")?;
+    rut_testhelpers::rut_add(&workdir.join("new.txt"), &repository);
+    let old_id = rut_testhelpers::run_command_string("rev-parse HEAD:old.txt", &repository)?;
+    let new_id = rut_testhelpers::run_command_string("hash-object new.txt", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string(
+        "diff --cached --name-status --format=json",
+        &repository,
+    )?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "{{\"path\":\"new.txt\",\"status\":\"renamed\",\"from\":\"old.txt\",\"similarity\":75,\"old_id\":\"{}\",\"new_id\":\"{}\"}}\n",
+            old_id.trim(),
+            new_id.trim()
+        )
+    );
+
+    Ok(())
+}
@@ -0,0 +1,111 @@
+// external programs are run with sh
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use rut::difftool;
+use rut::workspace::Repository;
+
+fn commit_and_modify_file(repository: &Repository) -> rut::Result<()> {
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "First line\n")?;
+    rut_testhelpers::rut_add(&file, repository);
+    rut_testhelpers::rut_commit("First commit", repository)?;
+    fs::write(&file, "Second line\n")?;
+    Ok(())
+}
+
+#[test]
+fn test_difftool_launches_configured_tool() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    commit_and_modify_file(&repository)?;
+    rut_testhelpers::run_command_string("config diff.tool record", &repository)?;
+    rut_testhelpers::run_command_string(
+        r#"config difftool.record.cmd 'cat "$LOCAL" "$REMOTE" > "$MERGED.recorded"'"#,
+        &repository,
+    )?;
+
+    // act
+    rut_testhelpers::run_command_string("difftool --no-prompt", &repository)?;
+
+    // assert
+    let recorded = fs::read_to_string(repository.worktree().root().join("file.txt.recorded"))?;
+    assert_eq!(recorded, "First line\nSecond line\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_difftool_asks_before_launching_tool() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("file.txt"), "First line\n")?;
+    fs::write(workdir.join("other.txt"), "First line\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+    fs::write(workdir.join("file.txt"), "Second line\n")?;
+    fs::write(workdir.join("other.txt"), "Second line\n")?;
+    let options = difftool::OptionsBuilder::default()
+        .extcmd(Some(String::from("cp")))
+        .build()
+        .unwrap();
+
+    // act
+    let output = rut_testhelpers::rut_difftool("n\ny\n", &options, &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "Viewing (1/2): 'file.txt'\nLaunch 'cp' [Y/n]? Viewing (2/2): 'other.txt'\nLaunch 'cp' [Y/n]? "
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_difftool_without_tool_writes_diff() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    commit_and_modify_file(&repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("difftool", &repository)?;
+
+    // assert
+    assert!(output.ends_with("-First line\n+Second line\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_runs_external_diff() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    commit_and_modify_file(&repository)?;
+    let program = repository.git_dir().join("external-diff");
+    fs::write(
+        &program,
+        "#!/bin/sh\necho \"$1 $3 $4 $6 $7\"\ncat \"$2\" \"$5\"\n",
+    )?;
+    fs::set_permissions(&program, fs::Permissions::from_mode(0o755))?;
+    rut_testhelpers::run_command_string(
+        format!("config diff.external {}", program.display()),
+        &repository,
+    )?;
+
+    // act
+    let external_output = rut_testhelpers::run_command_string("diff", &repository)?;
+    let internal_output = rut_testhelpers::run_command_string("diff --no-ext-diff", &repository)?;
+
+    // assert
+    assert_eq!(
+        external_output,
+        "file.txt 9649cde 100644 bd11d3a 100644\nFirst line\nSecond line\n"
+    );
+    assert!(internal_output.ends_with("-First line\n+Second line\n"));
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use rut::fast_import;
+
+#[test]
+fn test_fast_export_round_trips_through_fast_import() -> rut::Result<()> {
+    // arrange
+    let source = rut_testhelpers::create_repository();
+    let workdir = source.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&source, &file, "First\n", "Initial commit")?;
+    rut_testhelpers::run_command_string("branch side", &source)?;
+    let nested_file = workdir.join("dir").join("nested.txt");
+    std::fs::create_dir(workdir.join("dir"))?;
+    rut_testhelpers::commit_content(&source, &nested_file, "Nested\n", "Add nested file")?;
+    rut_testhelpers::run_command_string("rm file.txt", &source)?;
+    let head = rut_testhelpers::rut_commit("Remove file", &source)?;
+    let destination = rut_testhelpers::create_repository();
+
+    // act
+    let stream = rut_testhelpers::rut_fast_export(&source)?;
+    rut_testhelpers::rut_fast_import(&stream, &Default::default(), &destination)?;
+
+    // assert
+    let imported_head = rut_testhelpers::run_command_string("rev-parse HEAD", &destination)?;
+    assert_eq!(imported_head.trim(), head);
+    let side = rut_testhelpers::run_command_string("rev-parse side", &destination)?;
+    let source_side = rut_testhelpers::run_command_string("rev-parse side", &source)?;
+    assert_eq!(side, source_side);
+    let content = rut_testhelpers::rut_cat_file("HEAD:dir/nested.txt", &destination)?;
+    assert_eq!(content, "Nested");
+    rut_testhelpers::assert_healthy_repo(&destination.git_dir());
+
+    Ok(())
+}
+
+#[test]
+fn test_fast_import_does_not_lose_commits_unless_forced() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let head = rut_testhelpers::commit_content(&repository, &file, "First\n", "Initial commit")?;
+    let branch = rut_testhelpers::run_command_string("symbolic-ref HEAD", &repository)?;
+    // the reset starts the branch over, so the commit has no parent
+    let stream = format!(
+        "reset {0}\ncommit {0}\ncommitter Jane <jane@example.com> 1700000000 +0100\ndata <<EOF\nUnrelated\nEOF\nM 644 inline file.txt\ndata 6\nOther\n\n",
+        branch.trim()
+    );
+    let forced = fast_import::OptionsBuilder::default()
+        .force(true)
+        .build()
+        .unwrap();
+
+    // act
+    let output =
+        rut_testhelpers::rut_fast_import(stream.as_bytes(), &Default::default(), &repository)?;
+    let unchanged_head = rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?;
+    rut_testhelpers::rut_fast_import(stream.as_bytes(), &forced, &repository)?;
+
+    // assert
+    assert!(output.starts_with(&format!("warning: Not updating {}", branch.trim())));
+    assert_eq!(unchanged_head.trim(), head);
+    assert_eq!(
+        rut_testhelpers::rut_cat_file("HEAD:file.txt", &repository)?,
+        "Other"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+#[test]
+fn test_for_each_ref_with_format_and_pattern() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "First\n", "First commit")?;
+    rut_testhelpers::run_command_string("branch first", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Second\n", "Second commit")?;
+    rut_testhelpers::run_command_string("update-ref refs/tags/v1 HEAD", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string(
+        "for-each-ref --format=%(refname:short):%(subject) refs/heads",
+        &repository,
+    )?;
+
+    // assert
+    let branch = rut_testhelpers::run_command_string("symbolic-ref --short HEAD", &repository)?;
+    let mut expected_lines = [
+        String::from("first:First commit"),
+        format!("{}:Second commit", branch.trim()),
+    ];
+    expected_lines.sort();
+    assert_eq!(output, format!("{}\n", expected_lines.join("\n")));
+
+    Ok(())
+}
+
+#[test]
+fn test_for_each_ref_sorts_in_descending_order_and_limits_count() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    for branch in ["aaa", "bbb", "ccc"] {
+        rut_testhelpers::run_command_string(format!("branch {}", branch), &repository)?;
+    }
+
+    // act
+    let output = rut_testhelpers::run_command_string(
+        "for-each-ref --sort=-refname --count=2 --format=%(refname) refs/heads/aaa refs/heads/bbb refs/heads/ccc",
+        &repository,
+    )?;
+
+    // assert
+    assert_eq!(output, "refs/heads/ccc\nrefs/heads/bbb\n");
+
+    Ok(())
+}
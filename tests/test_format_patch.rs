@@ -0,0 +1,89 @@
+use std::fs;
+
+use rut::workspace::Repository;
+
+fn commit_two_changes(repository: &Repository) -> rut::Result<()> {
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(repository, &file, "a\nb\nc\n", "Initial commit")?;
+    fs::create_dir(workdir.join("nested"))?;
+    fs::write(workdir.join("nested").join("new.txt"), "New file\n")?;
+    fs::write(&file, "a\nx\nc\n")?;
+    rut_testhelpers::rut_add(workdir, repository);
+    rut_testhelpers::rut_commit("Change b to x\n\nAlso add a new file.", repository)?;
+    rut_testhelpers::commit_content(repository, &file, "a\nx\n", "Remove c")?;
+    Ok(())
+}
+
+/// Read the tree and author lines of the commit, which applying a patch of it should preserve.
+fn read_tree_and_author(revision: &str, repository: &Repository) -> rut::Result<Vec<String>> {
+    let commit = rut_testhelpers::rut_cat_file(revision, repository)?;
+    Ok(commit
+        .lines()
+        .filter(|line| line.starts_with("tree ") || line.starts_with("author "))
+        .map(str::to_owned)
+        .collect())
+}
+
+#[test]
+fn test_format_patch_writes_patch_file_per_commit() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    commit_two_changes(&repository)?;
+    let workdir = repository.worktree().root();
+
+    // act
+    let output = rut_testhelpers::run_command_string("format-patch HEAD~2 -o out", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "out/0001-Change-b-to-x.patch\nout/0002-Remove-c.patch\n"
+    );
+    let patch = fs::read_to_string(workdir.join("out").join("0001-Change-b-to-x.patch"))?;
+    let mut lines = patch.lines();
+    assert!(lines.next().unwrap().starts_with("From "));
+    assert!(lines.next().unwrap().starts_with("From: "));
+    assert!(lines.next().unwrap().starts_with("Date: "));
+    assert_eq!(lines.next(), Some("Subject: [PATCH 1/2] Change b to x"));
+    assert_eq!(lines.next(), Some(""));
+    assert_eq!(lines.next(), Some("Also add a new file."));
+    assert_eq!(lines.next(), Some("---"));
+    assert!(patch.contains(" 2 files changed, 2 insertions(+), 1 deletion(-)\n"));
+    assert!(patch.contains("\n@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n"));
+    assert!(patch.contains("\n+++ b/nested/new.txt\n@@ -0,0 +1 @@\n+New file\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_format_patch_and_am_round_trip() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    commit_two_changes(&repository)?;
+    let expected_head = read_tree_and_author("HEAD", &repository)?;
+    let expected_parent = read_tree_and_author("HEAD~1", &repository)?;
+    let log_command = "log -n 2 --pretty=format:%s%n%b";
+    let expected_log = rut_testhelpers::run_command_string(log_command, &repository)?;
+    rut_testhelpers::run_command_string("format-patch HEAD~2", &repository)?;
+    rut_testhelpers::run_command_string("switch -c applied HEAD~2", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string(
+        "am 0001-Change-b-to-x.patch 0002-Remove-c.patch",
+        &repository,
+    )?;
+
+    // assert
+    assert_eq!(output, "Applying: Change b to x\nApplying: Remove c\n");
+    assert_eq!(read_tree_and_author("HEAD", &repository)?, expected_head);
+    assert_eq!(
+        read_tree_and_author("HEAD~1", &repository)?,
+        expected_parent
+    );
+    let log = rut_testhelpers::run_command_string(log_command, &repository)?;
+    assert_eq!(log, expected_log);
+    rut_testhelpers::assert_healthy_repo(&repository.git_dir());
+
+    Ok(())
+}
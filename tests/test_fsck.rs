@@ -0,0 +1,66 @@
+use std::fs;
+
+use rut::objects::ObjectId;
+
+#[test]
+fn test_fsck_reports_dangling_blob() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    rut_testhelpers::commit_content(
+        &repository,
+        &workdir.join("file.txt"),
+        "Content\n",
+        "Initial commit",
+    )?;
+    fs::write(workdir.join("dangling.txt"), "Dangling\n")?;
+    let blob_id = rut_testhelpers::run_command_string("hash-object -w dangling.txt", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("fsck", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("dangling blob {}", blob_id));
+
+    Ok(())
+}
+
+#[test]
+fn test_fsck_reports_missing_tree() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir(workdir.join("nested"))?;
+    let commit_id = rut_testhelpers::commit_content(
+        &repository,
+        &workdir.join("nested").join("file.txt"),
+        "Content\n",
+        "Initial commit",
+    )?;
+    let commit_id = ObjectId::from_sha(commit_id.trim()).unwrap();
+    let root_tree_id = repository.database.load_commit(&commit_id)?.tree;
+    let root_tree = repository.database.load_tree(&root_tree_id)?;
+    let nested_tree_id = root_tree.entries()[0].object_id.clone();
+    fs::remove_file(
+        repository
+            .objects_dir()
+            .join(nested_tree_id.dirname())
+            .join(nested_tree_id.filename()),
+    )?;
+
+    // act
+    let (exit_status, output) =
+        rut_testhelpers::run_command_string_with_exit_status("fsck", &repository)?;
+
+    // assert
+    assert_eq!(exit_status, 1);
+    assert_eq!(
+        output,
+        format!(
+            "broken link from    tree {}\n              to    tree {}\nmissing tree {}\n",
+            root_tree_id, nested_tree_id, nested_tree_id
+        )
+    );
+
+    Ok(())
+}
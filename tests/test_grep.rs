@@ -0,0 +1,73 @@
+use std::fs;
+
+#[test]
+fn test_grep_searches_tracked_files_only() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(
+        workdir.join("tracked.txt"),
+        "first line\nneedle here\nlast line\n",
+    )?;
+    fs::write(workdir.join("untracked.txt"), "needle\n")?;
+    rut_testhelpers::run_command_string("add tracked.txt", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("grep needle", &repository)?;
+
+    // assert
+    assert_eq!(output, "tracked.txt:2:needle here\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_grep_searches_worktree_content() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "old needle\n", "First commit")?;
+    fs::write(&file, "new needle\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("grep 'ne+dle'", &repository)?;
+
+    // assert
+    assert_eq!(output, "file.txt:1:new needle\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_grep_in_revision() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "old needle\n", "First commit")?;
+    rut_testhelpers::commit_content(&repository, &file, "new needle\n", "Second commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("grep needle HEAD^", &repository)?;
+
+    // assert
+    assert_eq!(output, "HEAD^:file.txt:1:old needle\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_grep_error_on_invalid_pattern() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    // act
+    let result = rut_testhelpers::run_command_string("grep '('", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on invalid pattern"),
+        Err(error) => assert_eq!(error.to_string(), "fatal: invalid pattern '('"),
+    }
+
+    Ok(())
+}
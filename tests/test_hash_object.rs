@@ -0,0 +1,77 @@
+use std::fs;
+
+use rut::hash_object;
+
+const HELLO_BLOB_ID: &str = "ce013625030ba8dba906f756967f9e9ca394464a";
+
+#[test]
+fn test_hash_object_does_not_write_by_default() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(file, "hello\n")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("hash-object file.txt", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("{}\n", HELLO_BLOB_ID));
+    let object_file = repository
+        .objects_dir()
+        .join(&HELLO_BLOB_ID[..2])
+        .join(&HELLO_BLOB_ID[2..]);
+    assert!(!object_file.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_object_writes_blob() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(file, "hello\n")?;
+
+    // act
+    rut_testhelpers::run_command_string("hash-object -w file.txt", &repository)?;
+
+    // assert
+    let content = rut_testhelpers::rut_cat_file(HELLO_BLOB_ID, &repository)?;
+    assert_eq!(content, "hello");
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_object_from_stdin() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let options = hash_object::OptionsBuilder::default().build().unwrap();
+
+    // act
+    let output = rut_testhelpers::rut_hash_object_stdin("hello\n", &options, &repository)?;
+
+    // assert
+    assert_eq!(output, format!("{}\n", HELLO_BLOB_ID));
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_object_error_on_missing_file() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    // act
+    let result = rut_testhelpers::run_command_string("hash-object missing.txt", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("should have failed to hash missing file"),
+        Err(error) => assert!(error
+            .to_string()
+            .starts_with("fatal: could not open 'missing.txt' for reading")),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+// hooks are shell scripts that must be executable
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use rut::workspace::Repository;
+
+fn install_hook(name: &str, script: &str, repository: &Repository) -> rut::Result<()> {
+    let hooks_dir = repository.git_dir().join("hooks");
+    fs::create_dir_all(&hooks_dir)?;
+    let hook_path = hooks_dir.join(name);
+    fs::write(&hook_path, format!("#!/bin/sh\n{}\n", script))?;
+    fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[test]
+fn test_failing_pre_commit_hook_aborts_commit() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    install_hook("pre-commit", "exit 1", &repository)?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("commit -m 'First commit'", &repository);
+
+    // assert
+    assert!(result.is_err());
+    assert!(!repository.git_dir().join("refs/heads/main").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_no_verify_skips_failing_hooks() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    install_hook("pre-commit", "exit 1", &repository)?;
+    install_hook("commit-msg", "exit 1", &repository)?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string("commit --no-verify -m 'First commit'", &repository)?;
+
+    // assert
+    assert!(output.contains("First commit"));
+    assert!(repository.git_dir().join("refs/heads/main").is_file());
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_msg_hook_can_rewrite_message() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    install_hook("commit-msg", "printf 'Rewritten' > \"$1\"", &repository)?;
+    install_hook("post-commit", "touch post-commit-ran", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("commit -m 'Original'", &repository)?;
+
+    // assert
+    assert!(output.ends_with("Rewritten\n"));
+    assert!(repository
+        .worktree()
+        .root()
+        .join("post-commit-ran")
+        .is_file());
+
+    Ok(())
+}
@@ -0,0 +1,171 @@
+use std::fs;
+
+use rut::refs::RefHandler;
+use rut::workspace::Repository;
+
+#[test]
+fn test_init_with_initial_branch() -> rut::Result<()> {
+    // arrange
+    let workdir = rut_testhelpers::create_temporary_directory();
+
+    // act
+    rut_testhelpers::run_command_string_in_directory("init --initial-branch=trunk", &workdir)?;
+
+    // assert
+    rut_testhelpers::assert_file_contains(&workdir.join(".git/HEAD"), "ref: refs/heads/trunk");
+
+    Ok(())
+}
+
+#[test]
+fn test_init_with_invalid_initial_branch() -> rut::Result<()> {
+    // arrange
+    let workdir = rut_testhelpers::create_temporary_directory();
+
+    // act
+    let result = rut_testhelpers::run_command_string_in_directory("init -b 'a..b'", &workdir);
+
+    // assert
+    match result {
+        Err(error) => assert_eq!(
+            error.to_string(),
+            "fatal: invalid initial branch name: 'a..b'"
+        ),
+        Ok(_) => panic!("init should fail with an invalid branch name"),
+    }
+    assert!(!workdir.join(".git").exists());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn test_init_copies_template_directory() -> rut::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    // arrange
+    let workdir = rut_testhelpers::create_temporary_directory();
+    let template = rut_testhelpers::create_temporary_directory();
+    fs::create_dir_all(template.join("hooks"))?;
+    fs::create_dir_all(template.join("info"))?;
+    let hook = template.join("hooks/pre-commit.sample");
+    fs::write(&hook, "#!/bin/sh\nexit 0\n")?;
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755))?;
+    fs::write(template.join("info/exclude"), "*.log\n")?;
+
+    // act
+    rut_testhelpers::run_command_string_in_directory(
+        format!("init --template {}", template.display()),
+        &workdir,
+    )?;
+
+    // assert
+    let git_dir = workdir.join(".git");
+    rut_testhelpers::assert_file_contains(&git_dir.join("info/exclude"), "*.log\n");
+    let copied_hook = git_dir.join("hooks/pre-commit.sample");
+    assert_eq!(
+        fs::metadata(copied_hook)?.permissions().mode() & 0o777,
+        0o755
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_reinit_keeps_head_and_refs() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let commit_id = rut_testhelpers::commit_content(&repository, &file, "content", "Commit")?;
+    rut_testhelpers::run_command_string("switch -c feature", &repository)?;
+    let git_dir = repository.git_dir();
+
+    // act
+    let output = rut_testhelpers::run_command_string("init -b trunk", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "warning: re-init: ignored --initial-branch=trunk\nReinitialized existing Rut repository in {:#?}",
+            git_dir
+        )
+    );
+    rut_testhelpers::assert_file_contains(&git_dir.join("HEAD"), "ref: refs/heads/feature");
+    let branch_ref = fs::read_to_string(git_dir.join("refs/heads/feature"))?;
+    assert_eq!(branch_ref.trim(), commit_id);
+
+    Ok(())
+}
+
+#[test]
+fn test_init_with_sha256_object_format_records_it_in_config() -> rut::Result<()> {
+    // arrange
+    let workdir = rut_testhelpers::create_temporary_directory();
+
+    // act
+    rut_testhelpers::run_command_string_in_directory("init --object-format=sha256", &workdir)?;
+
+    // assert
+    let config = fs::read_to_string(workdir.join(".git/config"))?;
+    assert!(config.contains("repositoryformatversion = 1"));
+    assert!(config.contains("[extensions]\n\tobjectformat = sha256"));
+
+    Ok(())
+}
+
+#[test]
+fn test_sha256_repository_is_readable() -> rut::Result<()> {
+    // arrange
+    let workdir = rut_testhelpers::create_temporary_directory();
+    rut_testhelpers::run_command_string_in_directory("init --object-format=sha256", &workdir)?;
+    let repository = Repository::from_worktree_root(&workdir)?;
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content\n", "Initial commit")?;
+    fs::write(&file, "modified\n")?;
+
+    // act
+    let reopened = Repository::from_worktree_root(&workdir)?;
+    let status = rut_testhelpers::rut_status_porcelain(&reopened)?;
+    let log = rut_testhelpers::run_command_string("log --oneline", &reopened)?;
+
+    // assert
+    let head_id = RefHandler::new(&reopened).head()?.to_string();
+    assert_eq!(head_id.len(), 64);
+    assert_eq!(status, " M file.txt\n");
+    assert!(log.ends_with("Initial commit\n"));
+    rut_testhelpers::run_command_string("fsck", &reopened)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_unknown_object_format() -> rut::Result<()> {
+    // arrange
+    let workdir = rut_testhelpers::create_temporary_directory();
+    rut_testhelpers::run_command_string_in_directory("init", &workdir)?;
+    fs::write(
+        workdir.join(".git/config"),
+        "[core]\n\trepositoryformatversion = 1\n[extensions]\n\tobjectformat = sha512\n",
+    )?;
+
+    // act
+    let result = Repository::from_worktree_root(&workdir);
+    let command_result = rut_testhelpers::run_command_string_in_directory("status", &workdir);
+
+    // assert
+    let expected = "fatal: unknown repository object format 'sha512'";
+    assert_eq!(
+        result.err().map(|error| error.to_string()).as_deref(),
+        Some(expected)
+    );
+    assert_eq!(
+        command_result
+            .err()
+            .map(|error| error.to_string())
+            .as_deref(),
+        Some(expected)
+    );
+
+    Ok(())
+}
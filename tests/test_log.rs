@@ -1,7 +1,9 @@
+use std::fs;
+
 use rut::log;
 
-use rut::objects::GitObject;
 use rut::objects::ObjectId;
+use rut::objects::{Author, Commit, GitObject};
 
 #[test]
 fn test_log() -> rut::Result<()> {
@@ -113,3 +115,573 @@ fn test_log_two_commits_with_oneline_formatting() -> rut::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_log_with_stat() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    let file = repository.worktree().root().join("file.txt");
+    let commit_id = rut_testhelpers::commit_content(&repository, &file, "content", "First commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline --stat", &repository)?;
+
+    // assert
+    let expected_output = format!(
+        "{} (HEAD -> main) First commit
+ file.txt | 1 +
+ 1 file changed, 1 insertion(+)
+",
+        &commit_id[..7]
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_with_patch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    let file = repository.worktree().root().join("file.txt");
+    let first_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "content", "First commit")?;
+    let second_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "more content", "Second commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline -p", &repository)?;
+
+    // assert
+    let expected_output = format!(
+        "{} (HEAD -> main) Second commit
+diff --git a/file.txt b/file.txt
+index 6b584e8..62dee8b
+--- a/file.txt
++++ b/file.txt
+@@ -1 +1 @@
+-content
++more content
+{} First commit
+diff --git a/file.txt b/file.txt
+index 0000000..6b584e8
+--- /dev/null
++++ b/file.txt
+@@ -0,0 +1 @@
++content
+",
+        &second_commit_id[..7],
+        &first_commit_id[..7]
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_with_path_only_shows_commits_touching_path() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    let file = workdir.join("file.txt");
+    let other_file = workdir.join("other_file.txt");
+    let first_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "content", "First commit")?;
+    rut_testhelpers::commit_content(&repository, &other_file, "content", "Second commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline -- file.txt", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("{} First commit\n", &first_commit_id[..7]));
+
+    Ok(())
+}
+
+#[test]
+fn test_log_with_path_replaced_by_directory() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let unchanged_file = workdir.join("dir").join("unchanged.txt");
+    fs::create_dir(unchanged_file.parent().unwrap())?;
+    fs::write(&unchanged_file, "content")?;
+    rut_testhelpers::rut_add(&unchanged_file, &repository);
+    let path = workdir.join("path");
+    let first_commit_id = rut_testhelpers::commit_content(&repository, &path, "file", "Add file")?;
+    rut_testhelpers::run_command_string("rm path", &repository)?;
+    fs::create_dir(&path)?;
+    let second_commit_id = rut_testhelpers::commit_content(
+        &repository,
+        &path.join("nested.txt"),
+        "nested",
+        "Add dir",
+    )?;
+    let other_file = workdir.join("other.txt");
+    rut_testhelpers::commit_content(&repository, &other_file, "content", "Add other")?;
+
+    // act
+    let path_output = rut_testhelpers::run_command_string("log --oneline -- path", &repository)?;
+    let nested_output =
+        rut_testhelpers::run_command_string("log --oneline -- path/nested.txt", &repository)?;
+    let dir_output = rut_testhelpers::run_command_string("log --oneline -- dir", &repository)?;
+
+    // assert
+    assert_eq!(
+        path_output,
+        format!(
+            "{} Add dir\n{} Add file\n",
+            &second_commit_id[..7],
+            &first_commit_id[..7]
+        )
+    );
+    assert_eq!(
+        nested_output,
+        format!("{} Add dir\n", &second_commit_id[..7])
+    );
+    assert_eq!(dir_output, format!("{} Add file\n", &first_commit_id[..7]));
+
+    Ok(())
+}
+
+#[test]
+fn test_log_follow_continues_across_rename() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    let file = workdir.join("file.txt");
+    let first_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "content", "First commit")?;
+    rut_testhelpers::run_command_string("mv file.txt renamed.txt", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Rename file", &repository)?;
+
+    // act
+    let output_without_follow =
+        rut_testhelpers::run_command_string("log --oneline -- renamed.txt", &repository)?;
+    let output_with_follow =
+        rut_testhelpers::run_command_string("log --oneline --follow -- renamed.txt", &repository)?;
+
+    // assert
+    let second_commit_line = format!("{} (HEAD -> main) Rename file\n", &second_commit_id[..7]);
+    assert_eq!(output_without_follow, second_commit_line);
+    assert_eq!(
+        output_with_follow,
+        format!(
+            "{}{} First commit\n",
+            second_commit_line,
+            &first_commit_id[..7]
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_log_follow_shows_rename_in_diff() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content\n", "First commit")?;
+    rut_testhelpers::run_command_string("mv file.txt renamed.txt", &repository)?;
+    rut_testhelpers::rut_commit("Rename file", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string(
+        "log -n 1 --stat --oneline --follow -- renamed.txt",
+        &repository,
+    )?;
+
+    // assert
+    assert!(
+        output.contains(" file.txt => renamed.txt | 0 \n"),
+        "{}",
+        output
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_log_revision_ranges() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    let third_commit_id = rut_testhelpers::rut_commit("Third commit", &repository)?;
+
+    // act
+    let range_output =
+        rut_testhelpers::run_command_string("log --oneline HEAD~2..HEAD", &repository)?;
+    let symmetric_output =
+        rut_testhelpers::run_command_string("log --oneline HEAD~1...HEAD~2", &repository)?;
+
+    // assert
+    assert_eq!(
+        range_output,
+        format!(
+            "{} (HEAD -> main) Third commit\n{} Second commit\n",
+            &third_commit_id[..7],
+            &second_commit_id[..7]
+        )
+    );
+    assert_eq!(
+        symmetric_output,
+        format!("{} Second commit\n", &second_commit_id[..7])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_log_graph_with_merge_commit() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let root_id = rut_testhelpers::commit_content(&repository, &file, "root", "Root")?;
+    let root_id = ObjectId::from_sha(&root_id).unwrap();
+    let root = repository.database.load_commit(&root_id)?;
+
+    let author = || Author {
+        name: root.author.name.clone(),
+        email: root.author.email.clone(),
+    };
+    let create_commit = |message: &str, parents: Vec<ObjectId>, timestamp: u64| {
+        let commit = Commit::with_parents(
+            root.tree.clone(),
+            author(),
+            message.to_owned(),
+            parents,
+            timestamp + root.timestamp,
+        );
+        repository.database.store_object(&commit).map(|_| commit)
+    };
+    let side = create_commit("Side", vec![root_id.clone()], 1)?;
+    let mainline = create_commit("Mainline", vec![root_id.clone()], 2)?;
+    let merge = create_commit("Merge", vec![mainline.id().clone(), side.id().clone()], 3)?;
+    std::fs::write(
+        repository.git_dir().join("refs/heads/main"),
+        merge.id().to_string(),
+    )?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline --graph", &repository)?;
+
+    // assert
+    let expected_output = format!(
+        "* {} (HEAD -> main) Merge
+|\\
+* | {} Mainline
+| * {} Side
+|/
+* {} Root
+",
+        merge.short_id_as_string(),
+        mainline.short_id_as_string(),
+        side.short_id_as_string(),
+        root.short_id_as_string(),
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_graph_with_octopus_merge_commit() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let root_id = rut_testhelpers::commit_content(&repository, &file, "root", "Root")?;
+    let root_id = ObjectId::from_sha(&root_id).unwrap();
+    let root = repository.database.load_commit(&root_id)?;
+
+    let create_commit = |message: &str, parents: Vec<ObjectId>, timestamp: u64| {
+        let commit = Commit::with_parents(
+            root.tree.clone(),
+            root.author.clone(),
+            message.to_owned(),
+            parents,
+            timestamp + root.timestamp,
+        );
+        repository.database.store_object(&commit).map(|_| commit)
+    };
+    let first_side = create_commit("First side", vec![root_id.clone()], 1)?;
+    let second_side = create_commit("Second side", vec![root_id.clone()], 2)?;
+    let mainline = create_commit("Mainline", vec![root_id.clone()], 3)?;
+    let parents = vec![
+        mainline.id().clone(),
+        first_side.id().clone(),
+        second_side.id().clone(),
+    ];
+    let merge = create_commit("Merge", parents, 4)?;
+    std::fs::write(
+        repository.git_dir().join("refs/heads/main"),
+        merge.id().to_string(),
+    )?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline --graph", &repository)?;
+
+    // assert
+    let expected_output = format!(
+        "* {} (HEAD -> main) Merge
+|\\ \\
+* | | {} Mainline
+| | * {} Second side
+| |/
+| * {} First side
+|/
+* {} Root
+",
+        merge.short_id_as_string(),
+        mainline.short_id_as_string(),
+        second_side.short_id_as_string(),
+        first_side.short_id_as_string(),
+        root.short_id_as_string(),
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_filters() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    let second_commit = repository
+        .database
+        .load_commit(&ObjectId::from_sha(&second_commit_id).unwrap())?;
+
+    // act
+    let grep_output =
+        rut_testhelpers::run_command_string("log --oneline --grep ^Second", &repository)?;
+    let author_output = rut_testhelpers::run_command_string(
+        format!("log --oneline --author '{}'", second_commit.author.name),
+        &repository,
+    )?;
+    let unknown_author_output =
+        rut_testhelpers::run_command_string("log --oneline --author 'Nobody'", &repository)?;
+    let since_output =
+        rut_testhelpers::run_command_string("log --oneline --since '1 hour ago'", &repository)?;
+    let until_output =
+        rut_testhelpers::run_command_string("log --oneline --until 1970-01-02", &repository)?;
+
+    // assert
+    let second_commit_line = format!("{} (HEAD -> main) Second commit\n", &second_commit_id[..7]);
+    assert_eq!(grep_output, second_commit_line);
+    assert_eq!(author_output.lines().count(), 2);
+    assert_eq!(unknown_author_output, "");
+    assert_eq!(since_output.lines().count(), 2);
+    assert_eq!(until_output, "");
+
+    Ok(())
+}
+
+#[test]
+fn test_log_decorates_branches_and_tags() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    rut_testhelpers::run_command_string("branch feature HEAD^", &repository)?;
+    let tags_dir = repository.git_dir().join("refs/tags");
+    std::fs::create_dir_all(&tags_dir)?;
+    std::fs::write(tags_dir.join("v1.0"), &first_commit_id)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline", &repository)?;
+
+    // assert
+    let expected_output = format!(
+        "{} (HEAD -> main) Second commit\n{} (feature, tag: v1.0) First commit\n",
+        &second_commit_id[..7],
+        &first_commit_id[..7]
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_decorates_detached_head() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+    std::fs::write(repository.git_dir().join("HEAD"), &first_commit_id)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --oneline", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!("{} (HEAD) First commit\n", &first_commit_id[..7])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_log_pretty_format() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string("log --pretty=format:%h|%p|%an|%s%d", &repository)?;
+
+    // assert
+    let commit = repository
+        .database
+        .load_commit(&ObjectId::from_sha(&second_commit_id).unwrap())?;
+    let expected_output = format!(
+        "{}|{}|{}|Second commit (HEAD -> main)\n{}||{}|First commit\n",
+        &second_commit_id[..7],
+        &first_commit_id[..7],
+        commit.author.name,
+        &first_commit_id[..7],
+        commit.author.name,
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_pretty_short_omits_date_and_body() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Subject\n\nBody", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --pretty=short", &repository)?;
+
+    // assert
+    let commit = repository
+        .database
+        .load_commit(&ObjectId::from_sha(&commit_id).unwrap())?;
+    let expected_output = format!(
+        "commit {} (HEAD -> main)\nAuthor: {}\n\n    Subject\n",
+        commit_id, commit.author
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_pretty_fuller_shows_committer_and_dates() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Subject\n\nBody", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --pretty=fuller", &repository)?;
+
+    // assert
+    let commit = repository
+        .database
+        .load_commit(&ObjectId::from_sha(&commit_id).unwrap())?;
+    let timestring = log::to_local_timestring(commit.timestamp).unwrap();
+    let expected_output = format!(
+        "commit {} (HEAD -> main)
+Author:     {}
+AuthorDate: {}
+Commit:     {}
+CommitDate: {}
+
+    Subject
+
+    Body
+",
+        commit_id, commit.author, timestring, commit.author, timestring
+    );
+    assert_eq!(output, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_pager_flags_do_not_change_captured_output() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    rut_testhelpers::run_command_string("config core.pager 'sed s/^/paged:/'", &repository)?;
+    let expected_output =
+        rut_testhelpers::run_command_string("log --pretty=format:%s", &repository)?;
+
+    // act
+    let paginated =
+        rut_testhelpers::run_command_string("--paginate log --pretty=format:%s", &repository)?;
+    let unpaged =
+        rut_testhelpers::run_command_string("--no-pager log --pretty=format:%s", &repository)?;
+
+    // assert
+    assert_eq!(expected_output, "Initial commit\n");
+    assert_eq!(paginated, expected_output);
+    assert_eq!(unpaged, expected_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_color_mode_from_flag_and_config() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let run_log = |color_flag: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_rut"))
+            .args(color_flag)
+            .args(["log", "--oneline"])
+            .current_dir(repository.worktree().root())
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // act
+    let default_output = run_log(&[]);
+    let always_output = run_log(&["--color=always"]);
+    rut_testhelpers::run_command_string("config color.ui always", &repository)?;
+    let config_output = run_log(&[]);
+    let never_output = run_log(&["--color=never"]);
+
+    // assert
+    assert!(!default_output.contains('\x1b'));
+    assert!(always_output.contains("\x1b[38;5;130m"));
+    assert_eq!(config_output, always_output);
+    assert_eq!(never_output, default_output);
+
+    Ok(())
+}
+
+#[test]
+fn test_log_as_json() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_id = rut_testhelpers::rut_commit("Second commit\n\nWith a body", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("log --format=json", &repository)?;
+
+    // assert
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with(&format!("{{\"id\":\"{}\",", second_id)));
+    assert!(lines[0].contains(&format!("\"parents\":[\"{}\"]", first_id)));
+    assert!(lines[0].contains("\"refs\":[\"HEAD\",\"refs/heads/main\"]"));
+    assert!(lines[0].contains("\"subject\":\"Second commit\""));
+    assert!(lines[0].contains("\"message\":\"Second commit\\n\\nWith a body"));
+    assert!(lines[1].contains("\"parents\":[]"));
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::fs;
+
+use rut::hashing::HashAlgorithm;
+use rut::objects::{Blob, GitObject};
+use rut::workspace::Repository;
+
+#[test]
+fn test_ls_files_lists_tracked_paths_sorted() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let nested_dir = workdir.join("nested");
+    fs::create_dir(&nested_dir)?;
+    fs::write(workdir.join("b.txt"), "b")?;
+    fs::write(workdir.join("a.txt"), "a")?;
+    fs::write(nested_dir.join("c.txt"), "c")?;
+    fs::write(workdir.join("untracked.txt"), "untracked")?;
+    rut_testhelpers::run_command_string("add a.txt", &repository)?;
+    rut_testhelpers::run_command_string("add b.txt", &repository)?;
+    rut_testhelpers::run_command_string("add nested", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-files", &repository)?;
+
+    // assert
+    assert_eq!(output, "a.txt\nb.txt\nnested/c.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_files_stage() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    fs::write(&file, "hello\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-files --stage", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("100644 {} 0\tfile.txt\n", blob.id()));
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_files_null_terminated() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("with space.txt"), "a")?;
+    fs::write(workdir.join("other.txt"), "b")?;
+    rut_testhelpers::run_command_string("add .", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-files -z", &repository)?;
+
+    // assert
+    assert_eq!(output, "other.txt\0with space.txt\0");
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_files_reads_path_compressed_index_written_by_git() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    // git writes a cache tree extension along with the tree of the index
+    let cache_tree_data = b"\0-1 1\nnested\0-1 1\ndeeper\0-1 0\n";
+    let cache_tree = [
+        b"TREE".as_slice(),
+        &(cache_tree_data.len() as u32).to_be_bytes(),
+        cache_tree_data,
+    ]
+    .concat();
+    write_path_compressed_index(
+        &[
+            ("nested/a.txt", 0),
+            ("nested/deeper/b.txt", 0),
+            ("top.txt", 0),
+        ],
+        &cache_tree,
+        &repository,
+    )?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-files", &repository)?;
+
+    // assert
+    assert_eq!(output, "nested/a.txt\nnested/deeper/b.txt\ntop.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_index_written_by_rut_keeps_version_and_extended_flags() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    write_path_compressed_index(
+        &[("nested/a.txt", SKIP_WORKTREE_FLAG), ("nested/b.txt", 0)],
+        &[],
+        &repository,
+    )?;
+    fs::create_dir(workdir.join("nested"))?;
+    fs::write(workdir.join("nested/c.txt"), "c")?;
+
+    // act
+    rut_testhelpers::run_command_string("add nested/c.txt", &repository)?;
+
+    // assert
+    let listing = rut_testhelpers::run_command_string("ls-files", &repository)?;
+    assert_eq!(listing, "nested/a.txt\nnested/b.txt\nnested/c.txt\n");
+    let index_bytes = fs::read(repository.git_dir().join("index"))?;
+    assert_eq!(index_bytes[4..8], [0, 0, 0, 4]);
+    // the flags of the first entry follow its stat data and object id
+    let flags_start = HEADER_SIZE + STAT_DATA_SIZE + HashAlgorithm::Sha1.raw_size();
+    assert_eq!(
+        index_bytes[flags_start..(flags_start + 4)],
+        [0x40, 12, 0x40, 0]
+    );
+
+    Ok(())
+}
+
+const HEADER_SIZE: usize = 12;
+const STAT_DATA_SIZE: usize = 40;
+const EXTENDED_FLAG: u16 = 0x4000;
+const SKIP_WORKTREE_FLAG: u16 = 0x4000;
+
+/// Write an index of version 4 as git does with `index.version=4`, with the path of each entry
+/// compressed against the previous one. The entries are blobs of their path, given with their
+/// extended flags, and have no stat data.
+fn write_path_compressed_index(
+    entries: &[(&str, u16)],
+    extensions: &[u8],
+    repository: &Repository,
+) -> rut::Result<()> {
+    let mut bytes = b"DIRC".to_vec();
+    bytes.extend_from_slice(&4u32.to_be_bytes());
+    bytes.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    let mut previous_path: &[u8] = b"";
+    for (path, extended_flags) in entries {
+        let path = path.as_bytes();
+        let blob = Blob::new(path.to_vec());
+        bytes.extend_from_slice(&[0; 24]);
+        bytes.extend_from_slice(&0o100644u32.to_be_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(blob.id().bytes());
+        let mut flags = path.len() as u16;
+        if *extended_flags != 0 {
+            flags |= EXTENDED_FLAG;
+        }
+        bytes.extend_from_slice(&flags.to_be_bytes());
+        if *extended_flags != 0 {
+            bytes.extend_from_slice(&extended_flags.to_be_bytes());
+        }
+        let common_length = previous_path
+            .iter()
+            .zip(path)
+            .take_while(|(previous, current)| previous == current)
+            .count();
+        // the number of bytes to strip is a varint, which fits in a single byte for short paths
+        bytes.push((previous_path.len() - common_length) as u8);
+        bytes.extend_from_slice(&path[common_length..]);
+        bytes.push(0);
+        previous_path = path;
+    }
+    bytes.extend_from_slice(extensions);
+    let checksum = HashAlgorithm::Sha1.hash(&bytes);
+    bytes.extend_from_slice(&checksum);
+    fs::write(repository.git_dir().join("index"), bytes)?;
+    Ok(())
+}
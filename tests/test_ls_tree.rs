@@ -0,0 +1,102 @@
+use std::fs;
+
+use rut::objects::{Blob, GitObject};
+use rut::workspace::Repository;
+
+fn create_nested_commit() -> rut::Result<Repository> {
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let nested_dir = workdir.join("nested");
+    fs::create_dir(&nested_dir)?;
+    fs::write(workdir.join("file.txt"), "hello\n")?;
+    fs::write(nested_dir.join("other.txt"), "hello\n")?;
+    rut_testhelpers::run_command_string("add .", &repository)?;
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    Ok(repository)
+}
+
+#[test]
+fn test_ls_tree_lists_top_level_entries() -> rut::Result<()> {
+    // arrange
+    let repository = create_nested_commit()?;
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-tree HEAD", &repository)?;
+
+    // assert
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], format!("100644 blob {}\tfile.txt", blob.id()));
+    assert!(lines[1].starts_with("040000 tree "));
+    assert!(lines[1].ends_with("\tnested"));
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_tree_recursive() -> rut::Result<()> {
+    // arrange
+    let repository = create_nested_commit()?;
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-tree -r HEAD", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "100644 blob {}\tfile.txt\n100644 blob {}\tnested/other.txt\n",
+            blob.id(),
+            blob.id()
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_tree_with_directory_path() -> rut::Result<()> {
+    // arrange
+    let repository = create_nested_commit()?;
+    let blob = Blob::new("hello\n".as_bytes().to_vec());
+
+    // act
+    let entry_output = rut_testhelpers::run_command_string("ls-tree HEAD nested", &repository)?;
+    let contents_output = rut_testhelpers::run_command_string("ls-tree HEAD nested/", &repository)?;
+
+    // assert
+    assert!(entry_output.starts_with("040000 tree "));
+    assert!(entry_output.ends_with("\tnested\n"));
+    assert_eq!(
+        contents_output,
+        format!("100644 blob {}\tnested/other.txt\n", blob.id())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_ls_tree_lists_gitlinks_as_commits() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let submodule_commit = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+    fs::write(repository.worktree().root().join("file.txt"), "hello\n")?;
+    rut_testhelpers::run_command_string("add file.txt", &repository)?;
+    rut_testhelpers::rut_stage_gitlink("submodule", submodule_commit, &repository);
+    rut_testhelpers::rut_commit("Add submodule", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("ls-tree -r HEAD", &repository)?;
+
+    // assert
+    let lines = output.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[1],
+        format!("160000 commit {}\tsubmodule", submodule_commit)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use rut::objects::{Author, Commit, GitObject, ObjectId};
+
+#[test]
+fn test_merge_base_of_ancestor_is_ancestor() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("merge-base HEAD HEAD~1", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("{}\n", first_commit_id));
+
+    Ok(())
+}
+
+#[test]
+fn test_merge_base_of_diverged_histories() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Root", &repository)?;
+    let base_id = rut_testhelpers::rut_commit("Base", &repository)?;
+    let base_id = ObjectId::from_sha(&base_id).unwrap();
+    let base = repository.database.load_commit(&base_id)?;
+
+    let create_commit = |message: &str, parents: Vec<ObjectId>, timestamp: u64| {
+        let author = Author {
+            name: base.author.name.clone(),
+            email: base.author.email.clone(),
+        };
+        let commit = Commit::with_parents(
+            base.tree.clone(),
+            author,
+            message.to_owned(),
+            parents,
+            timestamp + base.timestamp,
+        );
+        repository.database.store_object(&commit).map(|_| commit)
+    };
+    let side = create_commit("Side", vec![base_id.clone()], 1)?;
+    let mainline = create_commit("Mainline", vec![base_id.clone()], 2)?;
+    let side_tip = create_commit("Side tip", vec![side.id().clone()], 3)?;
+    let merge = create_commit("Merge", vec![mainline.id().clone(), side.id().clone()], 4)?;
+
+    // act
+    let base_of_merge_and_side_tip =
+        rut::rev_walk::merge_base(&repository, merge.id(), side_tip.id())?;
+    let base_of_mainline_and_side_tip =
+        rut::rev_walk::merge_base(&repository, mainline.id(), side_tip.id())?;
+
+    // assert
+    assert_eq!(base_of_merge_and_side_tip.as_ref(), Some(side.id()));
+    assert_eq!(base_of_mainline_and_side_tip, Some(base_id));
+
+    Ok(())
+}
@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rut::index::Index;
+
+fn paths_in_index(repository: &rut::workspace::Repository) -> rut::Result<Vec<PathBuf>> {
+    let index = Index::from_file(repository.index_file())?;
+    Ok(index
+        .get_entries()
+        .iter()
+        .map(|entry| entry.path.clone())
+        .collect())
+}
+
+#[test]
+fn test_mv_renames_file() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+    let original_id = Index::from_file(repository.index_file())?
+        .get("file.txt")
+        .unwrap()
+        .object_id
+        .clone();
+
+    // act
+    rut_testhelpers::run_command_string("mv file.txt renamed.txt", &repository)?;
+
+    // assert
+    assert!(!file.exists());
+    assert_eq!(fs::read_to_string(workdir.join("renamed.txt"))?, "content");
+    assert_eq!(
+        paths_in_index(&repository)?,
+        vec![PathBuf::from("renamed.txt")]
+    );
+    let index = Index::from_file(repository.index_file())?;
+    assert_eq!(index.get("renamed.txt").unwrap().object_id, original_id);
+
+    Ok(())
+}
+
+#[test]
+fn test_mv_moves_directory_recursively() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let nested_dir = workdir.join("dir").join("nested");
+    fs::create_dir_all(&nested_dir)?;
+    fs::write(workdir.join("dir").join("a.txt"), "a")?;
+    fs::write(nested_dir.join("b.txt"), "b")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("mv dir moved", &repository)?;
+
+    // assert
+    assert!(!workdir.join("dir").exists());
+    assert!(workdir.join("moved").join("nested").join("b.txt").is_file());
+    assert_eq!(
+        paths_in_index(&repository)?,
+        vec![
+            PathBuf::from("moved/a.txt"),
+            PathBuf::from("moved/nested/b.txt")
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_mv_into_existing_directory() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    fs::create_dir(workdir.join("dir"))?;
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+
+    // act
+    rut_testhelpers::run_command_string("mv file.txt dir", &repository)?;
+
+    // assert
+    assert!(workdir.join("dir").join("file.txt").is_file());
+    assert_eq!(
+        paths_in_index(&repository)?,
+        vec![PathBuf::from("dir/file.txt")]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_mv_errors_on_untracked_source() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("untracked.txt"), "untracked")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("mv untracked.txt other.txt", &repository);
+
+    // assert
+    match result {
+        Err(error) => assert_eq!(
+            error.to_string(),
+            "fatal: not under version control, source=untracked.txt, destination=other.txt"
+        ),
+        Ok(_) => panic!("expected an error"),
+    }
+    assert!(workdir.join("untracked.txt").exists());
+
+    Ok(())
+}
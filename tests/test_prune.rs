@@ -0,0 +1,67 @@
+use std::fs;
+
+use rut::workspace::Repository;
+
+/// Commit a file and write a blob that nothing points to, returning the id of the blob.
+fn commit_and_write_unreachable_blob(repository: &Repository) -> rut::Result<String> {
+    let workdir = repository.worktree().root();
+    rut_testhelpers::commit_content(
+        repository,
+        &workdir.join("file.txt"),
+        "Content\n",
+        "Initial commit",
+    )?;
+    fs::write(workdir.join("unreachable.txt"), "Unreachable\n")?;
+    let blob_id =
+        rut_testhelpers::run_command_string("hash-object -w unreachable.txt", repository)?;
+    Ok(blob_id.trim().to_owned())
+}
+
+#[test]
+fn test_prune_deletes_unreachable_object() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let blob_id = commit_and_write_unreachable_blob(&repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("prune --expire now", &repository)?;
+
+    // assert
+    assert!(rut_testhelpers::rut_cat_file(&blob_id, &repository).is_err());
+    let count = rut_testhelpers::run_command_string("count-objects", &repository)?;
+    assert!(count.starts_with("3 objects, "));
+    rut_testhelpers::assert_healthy_repo(&repository.git_dir());
+
+    Ok(())
+}
+
+#[test]
+fn test_prune_dry_run_lists_unreachable_object_without_deleting_it() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let blob_id = commit_and_write_unreachable_blob(&repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("prune -n --expire now", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("{} blob\n", blob_id));
+    assert!(rut_testhelpers::rut_cat_file(&blob_id, &repository).is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn test_prune_keeps_unreachable_object_within_grace_period() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let blob_id = commit_and_write_unreachable_blob(&repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("prune", &repository)?;
+
+    // assert
+    assert!(rut_testhelpers::rut_cat_file(&blob_id, &repository).is_ok());
+
+    Ok(())
+}
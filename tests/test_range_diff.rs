@@ -0,0 +1,76 @@
+#[test]
+fn test_range_diff_pairs_up_commits_of_two_versions_of_series() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "1\n2\n3\n4\n5\n6\n7\n8\n9\n", "Base")?;
+    rut_testhelpers::run_command_string("branch v1", &repository)?;
+    rut_testhelpers::run_command_string("branch v2", &repository)?;
+
+    rut_testhelpers::run_command_string("switch v1", &repository)?;
+    let v1_two = rut_testhelpers::commit_content(
+        &repository,
+        &file,
+        "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n",
+        "Two",
+    )?;
+    let v1_eight = rut_testhelpers::commit_content(
+        &repository,
+        &file,
+        "1\ntwo\n3\n4\n5\n6\n7\neight\n9\n",
+        "Eight",
+    )?;
+    let v1_dropped =
+        rut_testhelpers::commit_content(&repository, &workdir.join("dropped.txt"), "x\n", "Drop")?;
+
+    rut_testhelpers::run_command_string("switch v2", &repository)?;
+    let v2_two = rut_testhelpers::commit_content(
+        &repository,
+        &file,
+        "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n",
+        "Two",
+    )?;
+    let v2_eight = rut_testhelpers::commit_content(
+        &repository,
+        &file,
+        "1\ntwo\n3\n4\n5\n6\n7\nEIGHT\n9\n",
+        "Eight",
+    )?;
+    let v2_added =
+        rut_testhelpers::commit_content(&repository, &workdir.join("added.txt"), "y\n", "Add")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("range-diff main..v1 main..v2", &repository)?;
+
+    // assert
+    let short = |id: &str| id[..7].to_owned();
+    let expected_output = [
+        format!("1:  {} = 1:  {} Two", short(&v1_two), short(&v2_two)),
+        format!("2:  {} ! 2:  {} Eight", short(&v1_eight), short(&v2_eight)),
+        String::from("    @@ -7,5 +7,5 @@"),
+        String::from("      6"),
+        String::from("      7"),
+        String::from("     -8"),
+        String::from("    -+eight"),
+        String::from("    ++EIGHT"),
+        String::from("      9"),
+        format!("3:  {} < -:  ------- Drop", short(&v1_dropped)),
+        format!("-:  ------- > 3:  {} Add", short(&v2_added)),
+    ];
+    assert_eq!(output, expected_output.join("\n") + "\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_range_diff_requires_ranges() {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    // act
+    let result = rut_testhelpers::run_command_string("range-diff main main..main", &repository);
+
+    // assert
+    assert!(result.is_err());
+}
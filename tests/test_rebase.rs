@@ -0,0 +1,152 @@
+use std::fs;
+
+use rut::workspace::Repository;
+
+/// Create `main` with a commit of `upstream_content` and `topic` with a commit of
+/// `topic_content`, both on top of a commit of `base_content`, and switch to `topic`.
+fn create_diverged_branches(
+    base_content: &str,
+    upstream_content: &str,
+    topic_content: &str,
+    repository: &Repository,
+) -> rut::Result<()> {
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(repository, &file, base_content, "Base")?;
+    rut_testhelpers::run_command_string("branch topic", repository)?;
+    rut_testhelpers::commit_content(repository, &file, upstream_content, "Upstream change")?;
+    rut_testhelpers::run_command_string("switch topic", repository)?;
+    rut_testhelpers::commit_content(repository, &file, topic_content, "Topic change")?;
+    Ok(())
+}
+
+#[test]
+fn test_rebase_replays_commits_on_top_of_upstream() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    create_diverged_branches("1\n2\n3\n", "one\n2\n3\n", "1\n2\nthree\n", &repository)?;
+    let topic_commit = rut_testhelpers::rut_cat_file("topic", &repository)?;
+    let topic_id = rut_testhelpers::run_command_string("rev-parse topic", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("rebase main", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "Successfully rebased and updated refs/heads/topic.\n"
+    );
+    rut_testhelpers::assert_file_contains(&workdir.join("file.txt"), "one\n2\nthree\n");
+    let log = rut_testhelpers::run_command_string("log --format=format:%s", &repository)?;
+    assert_eq!(log, "Topic change\nUpstream change\nBase\n");
+    assert_eq!(
+        fs::read_to_string(repository.git_dir().join("HEAD"))?,
+        "ref: refs/heads/topic"
+    );
+    let rebased_commit = rut_testhelpers::rut_cat_file("topic", &repository)?;
+    let author_line = |commit: &str| {
+        commit
+            .lines()
+            .find(|line| line.starts_with("author "))
+            .map(str::to_owned)
+    };
+    assert_eq!(author_line(&rebased_commit), author_line(&topic_commit));
+    assert!(!repository.git_dir().join("rebase-merge").exists());
+    assert_eq!(
+        rut_testhelpers::run_command_string("rev-parse ORIG_HEAD", &repository)?,
+        topic_id
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rebase_stops_at_conflict_and_continues_after_resolving_it() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    create_diverged_branches("1\n2\n3\n", "1\nzwei\n3\n", "1\nTWO\n3\n", &repository)?;
+
+    // act
+    let rebase_result = rut_testhelpers::run_command_string("rebase main", &repository);
+    let conflicted_content = fs::read_to_string(&file)?;
+    let continue_before_resolving_result =
+        rut_testhelpers::run_command_string("rebase --continue", &repository);
+    fs::write(&file, "1\nresolved\n3\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let continue_output = rut_testhelpers::run_command_string("rebase --continue", &repository)?;
+
+    // assert
+    assert!(rebase_result.is_err());
+    assert!(conflicted_content.starts_with("1\n<<<<<<< HEAD\nzwei\n=======\nTWO\n>>>>>>> "));
+    assert!(conflicted_content.ends_with(" (Topic change)\n3\n"));
+    assert!(continue_before_resolving_result.is_err());
+    assert_eq!(
+        continue_output,
+        "Successfully rebased and updated refs/heads/topic.\n"
+    );
+    let log = rut_testhelpers::run_command_string("log --format=format:%s", &repository)?;
+    assert_eq!(log, "Topic change\nUpstream change\nBase\n");
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+
+    Ok(())
+}
+
+#[test]
+fn test_rebase_abort_returns_to_original_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    create_diverged_branches("1\n2\n3\n", "1\nzwei\n3\n", "1\nTWO\n3\n", &repository)?;
+    let topic_id = rut_testhelpers::run_command_string("rev-parse topic", &repository)?;
+    assert!(rut_testhelpers::run_command_string("rebase main", &repository).is_err());
+
+    // act
+    rut_testhelpers::run_command_string("rebase --abort", &repository)?;
+
+    // assert
+    rut_testhelpers::assert_file_contains(&workdir.join("file.txt"), "1\nTWO\n3\n");
+    assert_eq!(
+        rut_testhelpers::run_command_string("rev-parse HEAD", &repository)?,
+        topic_id
+    );
+    assert_eq!(
+        fs::read_to_string(repository.git_dir().join("HEAD"))?,
+        "ref: refs/heads/topic"
+    );
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+    assert!(!repository.git_dir().join("rebase-merge").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_rebase_reuses_recorded_resolution_of_conflict() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::run_command_string("config rerere.enabled true", &repository)?;
+    create_diverged_branches("1\n2\n3\n", "1\nzwei\n3\n", "1\nTWO\n3\n", &repository)?;
+    rut_testhelpers::run_command_string("branch other-topic", &repository)?;
+    assert!(rut_testhelpers::run_command_string("rebase main", &repository).is_err());
+    fs::write(&file, "1\nresolved\n3\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let continue_output = rut_testhelpers::run_command_string("rebase --continue", &repository)?;
+    rut_testhelpers::run_command_string("switch other-topic", &repository)?;
+
+    // act
+    let rebase_result = rut_testhelpers::run_command_string("rebase main", &repository);
+
+    // assert
+    assert!(continue_output.starts_with("Recorded resolution for 'file.txt'.\n"));
+    assert!(rebase_result.is_err());
+    rut_testhelpers::assert_file_contains(&file, "1\nresolved\n3\n");
+    // the resolution is left for review before it's added
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "UU file.txt\n"
+    );
+
+    Ok(())
+}
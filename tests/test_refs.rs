@@ -0,0 +1,144 @@
+use std::fs;
+
+use rut::objects::ObjectId;
+use rut::refs::{ExpectedValue, RefHandler};
+
+#[test]
+fn test_transaction_fails_when_ref_has_moved() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let first_commit = ObjectId::from_sha(first_commit.trim()).unwrap();
+    let second_commit = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    let second_commit = ObjectId::from_sha(second_commit.trim()).unwrap();
+    let refs = RefHandler::new(&repository);
+    let branch = refs.current_branch()?.unwrap();
+
+    // act
+    let result = refs
+        .transaction()
+        .update(
+            "HEAD",
+            &first_commit,
+            ExpectedValue::Object(first_commit.clone()),
+        )
+        .commit();
+
+    // assert
+    let expected_message = format!(
+        "fatal: cannot lock ref 'refs/heads/{}': is at {} but expected {}",
+        branch, second_commit, first_commit
+    );
+    assert_eq!(result.unwrap_err().to_string(), expected_message);
+    assert_eq!(refs.head()?, second_commit);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_fails_when_branch_is_locked() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let refs = RefHandler::new(&repository);
+    let branch = refs.current_branch()?.unwrap();
+    let lock_path = repository
+        .git_dir()
+        .join(format!("refs/heads/{}.lock", branch));
+    fs::write(&lock_path, "")?;
+
+    // act
+    let result = rut_testhelpers::rut_commit("Second commit", &repository);
+
+    // assert
+    let message = result.unwrap_err().to_string();
+    assert!(message.starts_with(&format!("fatal: cannot lock ref 'refs/heads/{}'", branch)));
+    assert!(message.ends_with("File exists."));
+    assert_eq!(refs.head()?.to_string(), first_commit.trim());
+    assert!(lock_path.is_file());
+
+    Ok(())
+}
+
+#[test]
+fn test_transaction_applies_no_update_when_one_fails() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let commit = ObjectId::from_sha(commit.trim()).unwrap();
+    let refs = RefHandler::new(&repository);
+
+    // act
+    let result = refs
+        .transaction()
+        .update("refs/heads/created", &commit, ExpectedValue::Missing)
+        .update(
+            "refs/heads/moved",
+            &commit,
+            ExpectedValue::Object(commit.clone()),
+        )
+        .commit();
+
+    // assert
+    assert!(result.is_err());
+    assert_eq!(refs.read_ref("refs/heads/created")?, None);
+    let heads_dir = repository.git_dir().join("refs/heads");
+    let lockfiles = fs::read_dir(heads_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".lock"))
+        .count();
+    assert_eq!(lockfiles, 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_transaction_applies_no_update_when_packed_refs_cannot_be_written() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let commit = ObjectId::from_sha(commit.trim()).unwrap();
+    let refs = RefHandler::new(&repository);
+    let git_dir = repository.git_dir();
+    fs::write(
+        git_dir.join("packed-refs"),
+        format!("{} refs/heads/packed\n", commit),
+    )?;
+    fs::write(git_dir.join("packed-refs.lock"), "")?;
+
+    // act
+    let result = refs
+        .transaction()
+        .update("refs/heads/created", &commit, ExpectedValue::Missing)
+        .delete("refs/heads/packed", ExpectedValue::Any)
+        .commit();
+
+    // assert
+    assert!(result.is_err());
+    assert_eq!(refs.read_ref("refs/heads/created")?, None);
+    assert_eq!(refs.read_ref("refs/heads/packed")?, Some(commit));
+    assert!(!git_dir.join("refs/heads/created.lock").exists());
+    assert!(!git_dir.join("refs/heads/packed.lock").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_transaction_writes_ref_with_trailing_newline() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let commit = ObjectId::from_sha(commit.trim()).unwrap();
+    let refs = RefHandler::new(&repository);
+
+    // act
+    refs.transaction()
+        .update("refs/heads/created", &commit, ExpectedValue::Missing)
+        .commit()?;
+
+    // assert
+    let content = fs::read_to_string(repository.git_dir().join("refs/heads/created"))?;
+    assert_eq!(content, format!("{}\n", commit));
+
+    Ok(())
+}
@@ -40,3 +40,107 @@ fn test_restores_file_to_specified_commit() -> rut::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_restores_directory_and_deleted_files() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    let dir = repository.worktree().root().join("dir");
+    let nested_dir = dir.join("nested");
+    fs::create_dir_all(&nested_dir)?;
+    let file = dir.join("file.txt");
+    let nested_file = nested_dir.join("nested.txt");
+    let other_file = repository.worktree().root().join("other.txt");
+    fs::write(&file, "content")?;
+    fs::write(&nested_file, "nested content")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    rut_testhelpers::rut_add(&nested_file, &repository);
+    rut_testhelpers::commit_content(&repository, &other_file, "other", "First commit")?;
+
+    fs::write(&file, "changed")?;
+    fs::remove_dir_all(&nested_dir)?;
+    fs::write(&other_file, "changed")?;
+
+    // act
+    rut_testhelpers::run_command_string("restore dir", &repository)?;
+
+    // assert
+    assert_eq!(fs::read_to_string(&file)?, "content");
+    assert_eq!(fs::read_to_string(&nested_file)?, "nested content");
+    assert_eq!(fs::read_to_string(&other_file)?, "changed");
+
+    Ok(())
+}
+
+#[test]
+fn test_restores_multiple_paths() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    let first_file = repository.worktree().root().join("first.txt");
+    let second_file = repository.worktree().root().join("second.txt");
+    fs::write(&first_file, "first")?;
+    rut_testhelpers::rut_add(&first_file, &repository);
+    rut_testhelpers::commit_content(&repository, &second_file, "second", "First commit")?;
+    fs::remove_file(&first_file)?;
+    fs::write(&second_file, "changed")?;
+
+    // act
+    rut_testhelpers::run_command_string("restore first.txt second.txt", &repository)?;
+
+    // assert
+    assert_eq!(fs::read_to_string(&first_file)?, "first");
+    assert_eq!(fs::read_to_string(&second_file)?, "second");
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_restoring_unknown_path() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "First commit")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("restore nope.txt", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on unknown path"),
+        Err(error) => assert_eq!(
+            error.to_string(),
+            "fatal: pathspec 'nope.txt' did not match any file(s) known to rut"
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_restore_checks_out_crlf_line_endings_with_eol_attribute() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    let workdir = repository.worktree().root();
+    let attributes = workdir.join(".gitattributes");
+    let file = workdir.join("file.txt");
+    fs::write(&attributes, "*.txt eol=crlf\n")?;
+    rut_testhelpers::rut_add(&attributes, &repository);
+    rut_testhelpers::commit_content(&repository, &file, "first\nsecond\n", "First commit")?;
+    fs::remove_file(&file)?;
+
+    // act
+    rut_testhelpers::run_command_string("restore file.txt", &repository)?;
+
+    // assert
+    assert_eq!(fs::read_to_string(&file)?, "first\r\nsecond\r\n");
+    assert_eq!(
+        rut_testhelpers::run_command_string("diff", &repository)?,
+        ""
+    );
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+#[test]
+fn test_rev_list_head() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit_id = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("rev-list HEAD", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!("{}\n{}\n", second_commit_id, first_commit_id)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_list_range_with_max_count() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit_id = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    let third_commit_id = rut_testhelpers::rut_commit("Third commit", &repository)?;
+    rut_testhelpers::rut_commit("Fourth commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("rev-list -n 2 HEAD~3..HEAD^", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!("{}\n{}\n", third_commit_id, second_commit_id)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_list_count() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("First commit", &repository)?;
+    rut_testhelpers::rut_commit("Second commit", &repository)?;
+    rut_testhelpers::rut_commit("Third commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("rev-list --count HEAD^..", &repository)?;
+
+    // assert
+    assert_eq!(output, "1\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_list_left_right_marks_side_of_symmetric_difference() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Base\n", "Base")?;
+    rut_testhelpers::run_command_string("branch topic", &repository)?;
+    let main_id = rut_testhelpers::commit_content(&repository, &file, "Main\n", "Main")?;
+    rut_testhelpers::run_command_string("switch topic", &repository)?;
+    let topic_id = rut_testhelpers::commit_content(&repository, &file, "Topic\n", "Topic")?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string("rev-list --left-right main...topic", &repository)?;
+
+    // assert
+    let mut lines = output.lines().collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(lines, [format!("<{}", main_id), format!(">{}", topic_id)]);
+
+    Ok(())
+}
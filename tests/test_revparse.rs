@@ -1,3 +1,5 @@
+use rut::objects::{Blob, GitObject};
+
 #[test]
 fn test_parse_head() -> rut::Result<()> {
     // arrange
@@ -87,23 +89,188 @@ fn test_error_on_ambiguous_id() -> rut::Result<()> {
     // arrange
     let repository = rut_testhelpers::create_repository();
 
-    // object hashes of a file with just a "b" and a file with just an "f" both start on 6
-    rut_testhelpers::rut_commit("b", &repository)?;
-    rut_testhelpers::rut_commit("f", &repository)?;
+    // the ids of these blobs are 6bb2f98... and 6bb2f4e...
+    for content in ["195\n", "389\n"] {
+        let blob =
+            Blob::with_hash_algorithm(content.as_bytes().to_vec(), repository.hash_algorithm());
+        repository.database.store_object(&blob)?;
+    }
 
     // act
-    let result = rut_testhelpers::run_command_string("rev-parse 6", &repository);
+    let result = rut_testhelpers::run_command_string("rev-parse 6bb2f", &repository);
 
     // assert
     match result {
-        Ok(_) => panic!("expected error on ambiguous id"),
-        Err(error) => {
-            let message = error.to_string();
-            let expected_message =
-                "fatal: ambiguous argument '6': unknown revision or path not in the working tree.";
-            assert_eq!(message, expected_message);
+        Err(rut::Error::AmbiguousObjectId(prefix, candidates)) => {
+            assert_eq!(prefix, "6bb2f");
+            assert_eq!(candidates.len(), 2);
         }
+        other => panic!("expected error on ambiguous id, got {:?}", other),
     }
+    let output = rut_testhelpers::run_command_string("rev-parse 6bb2f9", &repository)?;
+    assert_eq!(output, "6bb2f98fb0227744dff2c9023c2a8d53cc721588\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_range() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_oid = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let second_oid = rut_testhelpers::rut_commit("Second commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("rev-parse HEAD^..", &repository)?;
+
+    // assert
+    assert_eq!(output, format!("{}\n^{}\n", second_oid, first_oid));
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_path_in_commit_and_index() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    std::fs::create_dir(workdir.join("nested"))?;
+    let file = workdir.join("nested").join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Committed\n", "Initial commit")?;
+    let committed_blob =
+        Blob::with_hash_algorithm(b"Committed\n".to_vec(), repository.hash_algorithm());
+    std::fs::write(&file, "Staged\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    let staged_blob = Blob::with_hash_algorithm(b"Staged\n".to_vec(), repository.hash_algorithm());
+
+    // act
+    let committed_output =
+        rut_testhelpers::run_command_string("rev-parse HEAD:nested/file.txt", &repository)?;
+    let staged_output =
+        rut_testhelpers::run_command_string("rev-parse :nested/file.txt", &repository)?;
+
+    // assert
+    assert_eq!(committed_output, format!("{}\n", committed_blob.id()));
+    assert_eq!(staged_output, format!("{}\n", staged_blob.id()));
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_peeled_tree() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let commit = rut_testhelpers::rut_cat_file("HEAD", &repository)?;
+    let tree_id = commit
+        .lines()
+        .next()
+        .unwrap()
+        .strip_prefix("tree ")
+        .unwrap();
+
+    // act
+    let output = rut_testhelpers::run_command_string("rev-parse HEAD^{tree}", &repository)?;
+    let result = rut_testhelpers::run_command_string("rev-parse HEAD^{tree}^{commit}", &repository);
+
+    // assert
+    assert_eq!(output, format!("{}\n", tree_id));
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_previous_checkout_and_upstream() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let main_oid = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let main = rut_testhelpers::run_command_string("symbolic-ref --short HEAD", &repository)?;
+    let main = main.trim();
+    rut_testhelpers::run_command_string("switch -c topic", &repository)?;
+    rut_testhelpers::rut_commit("Topic commit", &repository)?;
+    rut_testhelpers::run_command_string("config branch.topic.remote .", &repository)?;
+    let merge_config = format!("config branch.topic.merge refs/heads/{}", main);
+    rut_testhelpers::run_command_string(merge_config, &repository)?;
+
+    // act
+    let previous_output = rut_testhelpers::run_command_string("rev-parse @{-1}", &repository)?;
+    let upstream_output = rut_testhelpers::run_command_string("rev-parse @{u}", &repository)?;
+
+    // assert
+    assert_eq!(previous_output, format!("{}\n", main_oid));
+    assert_eq!(upstream_output, format!("{}\n", main_oid));
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_short_and_abbrev_ref() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_oid = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    rut_testhelpers::run_command_string("switch -c topic", &repository)?;
+
+    // act
+    let short = rut_testhelpers::run_command_string("rev-parse --short HEAD", &repository)?;
+    let shorter = rut_testhelpers::run_command_string("rev-parse --short=5 HEAD", &repository)?;
+    let branch = rut_testhelpers::run_command_string("rev-parse --abbrev-ref HEAD", &repository)?;
+
+    // assert
+    assert_eq!(short, format!("{}\n", &commit_oid[..7]));
+    assert_eq!(shorter, format!("{}\n", &commit_oid[..5]));
+    assert_eq!(branch, "topic\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_git_dir_and_show_toplevel() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    std::fs::create_dir(workdir.join("nested"))?;
+
+    // act
+    let root_output = rut_testhelpers::run_command_string("rev-parse --git-dir", &repository)?;
+    let nested_output = rut_testhelpers::run_command_string_in_directory(
+        "rev-parse --git-dir --show-toplevel",
+        &workdir.join("nested"),
+    )?;
+
+    // assert
+    assert_eq!(root_output, ".git\n");
+    assert_eq!(
+        nested_output,
+        format!(
+            "{}\n{}\n",
+            repository.git_dir().display(),
+            workdir.display()
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_verify() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_oid = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("rev-parse --verify HEAD", &repository)?;
+    let (quiet_status, quiet_output) = rut_testhelpers::run_command_string_with_exit_status(
+        "rev-parse --verify -q missing",
+        &repository,
+    )?;
+    let result = rut_testhelpers::run_command_string("rev-parse --verify missing", &repository);
+
+    // assert
+    assert_eq!(output, format!("{}\n", commit_oid));
+    assert_eq!(quiet_status, 1);
+    assert_eq!(quiet_output, "");
+    assert!(result.is_err());
 
     Ok(())
 }
@@ -38,3 +38,158 @@ fn test_remove_file() -> rut::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_remove_file_deletes_it_from_worktree() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+
+    // act
+    rut_testhelpers::run_command_string("rm file.txt", &repository)?;
+
+    // assert
+    assert!(!file.exists());
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "D  file.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_cached_keeps_worktree_file() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+    fs::write(&file, "modified content")?;
+
+    // act
+    rut_testhelpers::run_command_string("rm --cached file.txt", &repository)?;
+
+    // assert
+    assert_eq!(fs::read_to_string(&file)?, "modified content");
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "D  file.txt\n?? file.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_directory_requires_recursive_flag() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let dir = repository.worktree().root().join("dir");
+    fs::create_dir(&dir)?;
+    fs::write(dir.join("first.txt"), "first")?;
+    fs::write(dir.join("second.txt"), "second")?;
+    rut_testhelpers::rut_add(&dir, &repository);
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    let non_recursive_result = rut_testhelpers::run_command_string("rm dir", &repository);
+    rut_testhelpers::run_command_string("rm -r dir", &repository)?;
+
+    // assert
+    match non_recursive_result {
+        Ok(_) => panic!("expected error on removing directory without -r"),
+        Err(error) => assert_eq!(
+            error.to_string(),
+            "fatal: not removing 'dir' recursively without -r"
+        ),
+    }
+    assert!(!dir.exists());
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "D  dir/first.txt\nD  dir/second.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_removing_locally_modified_file_without_force() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+    fs::write(&file, "modified content")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("rm file.txt", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on removing modified file"),
+        Err(error) => assert!(error
+            .to_string()
+            .starts_with("fatal: the following file has local modifications:")),
+    }
+    assert!(file.exists());
+
+    rut_testhelpers::run_command_string("rm -f file.txt", &repository)?;
+    assert!(!file.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_removing_cached_file_differing_from_index_and_head() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+    fs::write(&file, "staged content")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    fs::write(&file, "unstaged content")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("rm --cached file.txt", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on removing file with staged and unstaged changes"),
+        Err(error) => assert!(error.to_string().starts_with(
+            "fatal: the following file has staged content different from both the file and the HEAD:"
+        )),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_with_glob_and_exclude_pathspecs() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    for name in ["a.txt", "b.txt", "c.md"] {
+        fs::write(workdir.join(name), name)?;
+    }
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("rm '*.txt' ':!b.txt'", &repository)?;
+
+    // assert
+    let index = Index::from_file(repository.index_file())?;
+    let paths_in_index: Vec<&PathBuf> = index
+        .get_entries()
+        .iter()
+        .map(|entry| &entry.path)
+        .collect();
+
+    assert_eq!(
+        paths_in_index,
+        vec![&PathBuf::from("b.txt"), &PathBuf::from("c.md")]
+    );
+    assert!(!workdir.join("a.txt").exists());
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use std::fs;
+
+fn commit_as(name: &str, email: &str, message: &str, repository: &rut::workspace::Repository) {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_rut"))
+        .args(["commit", "-m", message])
+        .current_dir(repository.worktree().root())
+        .env("GIT_AUTHOR_NAME", name)
+        .env("GIT_AUTHOR_EMAIL", email)
+        .env("GIT_COMMITTER_NAME", name)
+        .env("GIT_COMMITTER_EMAIL", email)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_shortlog_coalesces_authors_with_mailmap() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    commit_as("Jane Doe", "jane@old.example.com", "First", &repository);
+    commit_as("John Smith", "john@example.com", "Second", &repository);
+    commit_as("Jane Doe", "jane@example.com", "Third", &repository);
+    let mailmap = "# Jane changed email\nJane Doe <jane@example.com> <Jane@Old.example.com>\n";
+    fs::write(repository.worktree().root().join(".mailmap"), mailmap)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("shortlog", &repository)?;
+    let summary = rut_testhelpers::run_command_string("shortlog -sne", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "Jane Doe (2):\n      First\n      Third\n\nJohn Smith (1):\n      Second\n\n"
+    );
+    assert_eq!(
+        summary,
+        "     2\tJane Doe <jane@example.com>\n     1\tJohn Smith <john@example.com>\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_log_and_blame_show_mapped_authors() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    fs::write(&file, "Content\n")?;
+    rut_testhelpers::rut_add(&file, &repository);
+    commit_as("jd", "jane@old.example.com", "Initial commit", &repository);
+    let mailmap = "Jane Doe <jane@example.com> jd <jane@old.example.com>\n";
+    fs::write(workdir.join(".mailmap"), mailmap)?;
+
+    // act
+    let mapped = rut_testhelpers::run_command_string("log --pretty=format:%an", &repository)?;
+    let unmapped =
+        rut_testhelpers::run_command_string("log --no-mailmap --pretty=format:%an", &repository)?;
+    let blame = rut_testhelpers::run_command_string("blame file.txt", &repository)?;
+
+    // assert
+    assert_eq!(mapped, "Jane Doe\n");
+    assert_eq!(unmapped, "jd\n");
+    assert!(blame.contains("(Jane Doe "));
+
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use std::fs;
+
+use rut::refs::RefHandler;
+
+#[test]
+fn test_show_ref_lists_loose_and_packed_refs() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let commit_id = commit_id.trim();
+    let branch = RefHandler::new(&repository).current_branch()?.unwrap();
+    let packed_refs = format!(
+        "# pack-refs with: peeled fully-peeled sorted \n{} refs/tags/v1\n",
+        commit_id
+    );
+    fs::write(repository.git_dir().join("packed-refs"), packed_refs)?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("show-ref", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "{} refs/heads/{}\n{} refs/tags/v1\n",
+            commit_id, branch, commit_id
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_show_ref_fails_when_no_ref_matches() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    let (exit_status, output) =
+        rut_testhelpers::run_command_string_with_exit_status("show-ref nonexistent", &repository)?;
+
+    // assert
+    assert_eq!(exit_status, 1);
+    assert_eq!(output, "");
+
+    Ok(())
+}
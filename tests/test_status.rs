@@ -1,6 +1,19 @@
 use std::fs;
-
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use std::process::Command;
+
+use rut::hash_object;
+use rut::objects::ObjectId;
+use rut::refs::RefHandler;
 use rut::status;
+use rut::workspace::Repository;
+
+fn on_branch_line(repository: &Repository) -> rut::Result<String> {
+    let branch = RefHandler::new(repository).current_branch()?.unwrap();
+    Ok(format!("On branch {}\n", branch))
+}
 
 #[test]
 fn test_status_shows_untracked_file() -> rut::Result<()> {
@@ -239,7 +252,7 @@ fn test_human_readable_format() -> rut::Result<()> {
     // act
     let output = rut_testhelpers::rut_status(&repository, &options)?;
 
-    assert_eq!(output, "Changes to be committed:\n\tnew file: staged.txt\n\nChanges not staged for commit:\n\tmodified: modified.txt\n\nUntracked files:\n\tuntracked.txt\n\n");
+    assert_eq!(output, format!("{}Changes to be committed:\n\tnew file: staged.txt\n\nChanges not staged for commit:\n\tmodified: modified.txt\n\nUntracked files:\n\tuntracked.txt\n\n", on_branch_line(&repository)?));
 
     Ok(())
 }
@@ -268,3 +281,645 @@ fn test_status_shows_untracked_file_in_tracked_directory() -> rut::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_status_limited_by_pathspec() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    fs::create_dir(workdir.join("src"))?;
+    let tracked_file = workdir.join("src/tracked.rs");
+    rut_testhelpers::commit_content(&repository, &tracked_file, "", "Initial commit")?;
+    fs::write(workdir.join("src/main.rs"), "fn main() {}")?;
+    fs::write(workdir.join("src/lib.rs"), "")?;
+    fs::write(workdir.join("README.md"), "A README.")?;
+
+    let options = status::Options {
+        output_format: status::OutputFormat::Porcelain,
+        pathspecs: vec![String::from("src"), String::from(":!src/lib.rs")],
+        rename_threshold: None,
+        branch: false,
+        null_terminated: false,
+        untracked_files: status::UntrackedFiles::Normal,
+        ignored: false,
+    };
+
+    // act
+    let output = rut_testhelpers::rut_status(&repository, &options)?;
+
+    // assert
+    assert_eq!(output, "?? src/main.rs\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_from_subdirectory() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    let nested_dir = workdir.join("nested");
+    fs::create_dir(&nested_dir)?;
+    fs::write(nested_dir.join("nested.txt"), "A nested file.")?;
+    fs::write(workdir.join("file.txt"), "A file.")?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string_in_directory("status --porcelain", &nested_dir)?;
+
+    // assert
+    assert_eq!(output, "?? file.txt\n?? nested/\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_outside_repository() -> rut::Result<()> {
+    // arrange
+    let directory = rut_testhelpers::create_temporary_directory();
+
+    // act
+    let result = rut_testhelpers::run_command_string_in_directory("status", &directory);
+
+    // assert
+    match result {
+        Err(error @ rut::Error::NotARepository(_)) => assert_eq!(
+            error.to_string(),
+            "fatal: not a git repository (or any of the parent directories): .git"
+        ),
+        Err(error) => panic!("expected not a repository error, got {:?}", error),
+        Ok(_) => panic!("status should fail outside of a repository"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_status_with_directory_option() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("file.txt"), "A file.")?;
+    let outside_dir = rut_testhelpers::create_temporary_directory();
+
+    // act
+    let output = rut_testhelpers::run_command_string_in_directory(
+        format!("-C {} status --porcelain", workdir.display()),
+        &outside_dir,
+    )?;
+
+    // assert
+    assert_eq!(output, "?? file.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_with_git_dir_and_work_tree_options() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("file.txt"), "A file.")?;
+    let outside_dir = rut_testhelpers::create_temporary_directory();
+
+    // act
+    let output = rut_testhelpers::run_command_string_in_directory(
+        format!(
+            "--git-dir {} --work-tree {} status --porcelain",
+            repository.git_dir().display(),
+            workdir.display()
+        ),
+        &outside_dir,
+    )?;
+
+    // assert
+    assert_eq!(output, "?? file.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_and_diff_skip_submodules() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("file.txt"), "hello\n")?;
+    fs::create_dir(workdir.join("submodule"))?;
+    rut_testhelpers::run_command_string("add file.txt", &repository)?;
+    rut_testhelpers::rut_stage_gitlink(
+        "submodule",
+        "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+        &repository,
+    );
+    rut_testhelpers::rut_commit("Add submodule", &repository)?;
+
+    // act
+    let status_output = rut_testhelpers::rut_status_porcelain(&repository)?;
+    let diff_output = rut_testhelpers::rut_diff_default(&repository)?;
+    let show_output = rut_testhelpers::run_command_string("log -p -n 1", &repository)?;
+
+    // assert
+    assert_eq!(status_output, "");
+    assert_eq!(diff_output, "");
+    assert!(show_output.contains("+++ b/file.txt"));
+    assert!(!show_output.contains("b/submodule"));
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_untracked_directory_nested_in_tracked_directory() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir_all(workdir.join("nested/deeper/untracked"))?;
+    fs::write(workdir.join("nested/deeper/tracked.txt"), "tracked")?;
+    fs::write(
+        workdir.join("nested/deeper/untracked/file.txt"),
+        "untracked",
+    )?;
+    rut_testhelpers::run_command_string("add nested/deeper/tracked.txt", &repository)?;
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    let output = rut_testhelpers::rut_status_porcelain(&repository)?;
+
+    // assert
+    assert_eq!(output, "?? nested/deeper/untracked/\n");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_status_reuses_entries_of_directories_with_unchanged_mtime() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("first.txt"), "first")?;
+    set_old_mtime(workdir);
+    rut_testhelpers::rut_status_porcelain(&repository)?;
+    // a file that appears without changing the mtime of its directory goes unnoticed
+    fs::write(workdir.join("second.txt"), "second")?;
+    set_old_mtime(workdir);
+
+    // act
+    let output = rut_testhelpers::rut_status_porcelain(&repository)?;
+
+    // assert
+    assert_eq!(output, "?? first.txt\n");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_status_rereads_directories_with_changed_mtime() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("first.txt"), "first")?;
+    set_old_mtime(workdir);
+    rut_testhelpers::rut_status_porcelain(&repository)?;
+    fs::write(workdir.join("second.txt"), "second")?;
+
+    // act
+    let output = rut_testhelpers::rut_status_porcelain(&repository)?;
+
+    // assert
+    assert_eq!(output, "?? first.txt\n?? second.txt\n");
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_old_mtime(path: &Path) {
+    let output = Command::new("touch")
+        .args(["-d", "2020-01-01 00:00:00"])
+        .arg(path)
+        .output()
+        .expect("Failed running touch");
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_status_shows_modified_files_of_unchanged_size() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir(workdir.join("nested"))?;
+    let paths = ["a.txt", "b.txt", "nested/c.txt", "nested/d.txt"];
+    for path in paths {
+        fs::write(workdir.join(path), "before")?;
+    }
+    rut_testhelpers::run_command_string("add a.txt b.txt nested", &repository)?;
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    for path in ["a.txt", "nested/d.txt"] {
+        fs::write(workdir.join(path), "after!")?;
+    }
+
+    // act
+    let output = rut_testhelpers::rut_status_porcelain(&repository)?;
+
+    // assert
+    assert_eq!(output, " M a.txt\n M nested/d.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_does_not_show_tracked_hidden_file_as_deleted() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join(".editorconfig"), "root = true")?;
+    rut_testhelpers::run_command_string("add .editorconfig", &repository)?;
+
+    // act
+    let output = rut_testhelpers::rut_status_porcelain(&repository)?;
+
+    // assert
+    assert_eq!(output, "A  .editorconfig\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_staged_rename_of_similar_file() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    let content = "First line\nSecond line\nThird line\n";
+    rut_testhelpers::commit_content(&repository, &old_file, content, "Initial commit")?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    let new_file = workdir.join("new.txt");
+    fs::write(&new_file, "First line\nSecond line\nChanged line\n")?;
+    rut_testhelpers::rut_add(&new_file, &repository);
+
+    // act
+    let porcelain_output = rut_testhelpers::rut_status_porcelain(&repository)?;
+    let human_readable_output = rut_testhelpers::run_command_string("status", &repository)?;
+
+    // assert
+    assert_eq!(porcelain_output, "R  old.txt -> new.txt\n");
+    assert_eq!(
+        human_readable_output,
+        format!(
+            "{}Changes to be committed:\n\trenamed: old.txt -> new.txt\n\n",
+            on_branch_line(&repository)?
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_dissimilar_files_as_deletion_and_addition() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old.txt");
+    let content = "First line\nSecond line\nThird line\n";
+    rut_testhelpers::commit_content(&repository, &old_file, content, "Initial commit")?;
+
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    let new_file = workdir.join("new.txt");
+    fs::write(&new_file, "First line\nSecond line\nChanged line\n")?;
+    rut_testhelpers::rut_add(&new_file, &repository);
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string("status --porcelain --find-renames=90%", &repository)?;
+
+    // assert
+    assert_eq!(output, "A  new.txt\nD  old.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_status_porcelain_v2_shows_entries_with_modes_and_ids() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("old.txt"), "First\nSecond\nThird\n")?;
+    fs::write(workdir.join("modified.txt"), "Content\n")?;
+    fs::write(workdir.join("deleted.txt"), "Content\n")?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let head_id = RefHandler::new(&repository).head()?;
+    let branch = RefHandler::new(&repository).current_branch()?.unwrap();
+    let old_id = hash_file("old.txt", &repository)?;
+    let content_id = hash_file("modified.txt", &repository)?;
+    rut_testhelpers::run_command_string("mv old.txt new.txt", &repository)?;
+    fs::write(workdir.join("modified.txt"), "Staged content\n")?;
+    rut_testhelpers::rut_add(&workdir.join("modified.txt"), &repository);
+    let staged_id = hash_file("modified.txt", &repository)?;
+    fs::write(workdir.join("modified.txt"), "Unstaged content\n")?;
+    fs::remove_file(workdir.join("deleted.txt"))?;
+    fs::write(workdir.join("untracked.txt"), "Content\n")?;
+
+    // act
+    let output =
+        rut_testhelpers::run_command_string("status --porcelain=v2 --branch", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "# branch.oid {head_id}\n\
+             # branch.head {branch}\n\
+             1 .D N... 100644 100644 000000 {content_id} {content_id} deleted.txt\n\
+             1 MM N... 100644 100644 100644 {content_id} {staged_id} modified.txt\n\
+             2 R. N... 100644 100644 100644 {old_id} {old_id} R100 new.txt\told.txt\n\
+             ? untracked.txt\n"
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_how_branch_diverged_from_upstream() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Initial\n", "Initial commit")?;
+    let branch = RefHandler::new(&repository).current_branch()?.unwrap();
+    rut_testhelpers::run_command_string("branch other", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "Local\n", "Local commit")?;
+    rut_testhelpers::run_command_string("switch other", &repository)?;
+    let upstream_id = rut_testhelpers::commit_content(&repository, &file, "Remote\n", "Remote")?;
+    rut_testhelpers::run_command_string(format!("switch {}", branch), &repository)?;
+
+    let remote_ref = repository.git_dir().join("refs/remotes/origin/main");
+    fs::create_dir_all(remote_ref.parent().unwrap())?;
+    fs::write(remote_ref, format!("{}\n", upstream_id))?;
+    let config_command = format!("config branch.{}.remote origin", branch);
+    rut_testhelpers::run_command_string(config_command, &repository)?;
+    let config_command = format!("config branch.{}.merge refs/heads/main", branch);
+    rut_testhelpers::run_command_string(config_command, &repository)?;
+
+    // act
+    let human_readable_output = rut_testhelpers::run_command_string("status", &repository)?;
+    let porcelain_output =
+        rut_testhelpers::run_command_string("status --porcelain --branch", &repository)?;
+    let porcelain_v2_output =
+        rut_testhelpers::run_command_string("status --porcelain=v2 --branch", &repository)?;
+
+    // assert
+    assert_eq!(
+        human_readable_output,
+        format!(
+            "On branch {}\nYour branch and 'origin/main' have diverged,\nand have 1 and 1 different commits each, respectively.\n\n\n",
+            branch
+        )
+    );
+    assert_eq!(
+        porcelain_output,
+        format!("## {}...origin/main [ahead 1, behind 1]\n", branch)
+    );
+    assert!(porcelain_v2_output.ends_with(&format!(
+        "\n# branch.head {}\n# branch.upstream origin/main\n# branch.ab +1 -1\n",
+        branch
+    )));
+
+    Ok(())
+}
+
+#[test]
+fn test_status_untracked_files_modes() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    rut_testhelpers::commit_content(&repository, &workdir.join("tracked.txt"), "", "Initial")?;
+    fs::create_dir_all(workdir.join("dir").join("nested"))?;
+    fs::write(workdir.join("dir").join("file.txt"), "")?;
+    fs::write(workdir.join("dir").join("nested").join("file.txt"), "")?;
+    fs::write(workdir.join("untracked.txt"), "")?;
+
+    // act
+    let normal_output =
+        rut_testhelpers::run_command_string("status --porcelain -unormal", &repository)?;
+    let no_output = rut_testhelpers::run_command_string(
+        "status --porcelain --untracked-files=no",
+        &repository,
+    )?;
+    let all_output = rut_testhelpers::run_command_string("status --porcelain -u", &repository)?;
+
+    // assert
+    assert_eq!(normal_output, "?? dir/\n?? untracked.txt\n");
+    assert_eq!(no_output, "");
+    assert_eq!(
+        all_output,
+        "?? dir/file.txt\n?? dir/nested/file.txt\n?? untracked.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_ignored_paths() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    fs::create_dir_all(workdir.join("target").join("debug"))?;
+    fs::write(workdir.join("target").join("debug").join("rut"), "")?;
+    fs::write(workdir.join("Cargo.lock"), "")?;
+    fs::write(workdir.join("untracked.txt"), "")?;
+
+    // act
+    let default_output = rut_testhelpers::run_command_string("status --porcelain", &repository)?;
+    let ignored_output =
+        rut_testhelpers::run_command_string("status --porcelain --ignored", &repository)?;
+    let all_ignored_output =
+        rut_testhelpers::run_command_string("status --porcelain --ignored -u=all", &repository)?;
+    let human_readable_output =
+        rut_testhelpers::run_command_string("status --ignored", &repository)?;
+
+    // assert
+    assert_eq!(default_output, "?? untracked.txt\n");
+    assert_eq!(
+        ignored_output,
+        "?? untracked.txt\n!! Cargo.lock\n!! target/\n"
+    );
+    assert_eq!(
+        all_ignored_output,
+        "?? untracked.txt\n!! Cargo.lock\n!! target/debug/rut\n"
+    );
+    assert!(human_readable_output.ends_with(
+        "Untracked files:\n\tuntracked.txt\n\nIgnored files:\n\tCargo.lock\n\ttarget/\n\n"
+    ));
+
+    Ok(())
+}
+
+fn hash_file(path: &str, repository: &Repository) -> rut::Result<String> {
+    let output = rut_testhelpers::run_command_string(format!("hash-object {}", path), repository)?;
+    Ok(output.trim_end().to_owned())
+}
+
+fn hash_content(content: &str, repository: &Repository) -> rut::Result<String> {
+    let options = hash_object::Options::default();
+    let output = rut_testhelpers::rut_hash_object_stdin(content, &options, repository)?;
+    Ok(output.trim_end().to_owned())
+}
+
+fn create_merge_conflicts(repository: &Repository) -> rut::Result<()> {
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    let gone_file = workdir.join("gone.txt");
+    rut_testhelpers::commit_content(repository, &file, "Base\n", "Add file")?;
+    rut_testhelpers::commit_content(repository, &gone_file, "Base\n", "Add gone file")?;
+    let branch = RefHandler::new(repository).current_branch()?.unwrap();
+    rut_testhelpers::run_command_string("branch other", repository)?;
+    rut_testhelpers::commit_content(repository, &file, "Ours\n", "Change file")?;
+    rut_testhelpers::commit_content(repository, &gone_file, "Ours\n", "Change gone file")?;
+    rut_testhelpers::run_command_string("switch other", repository)?;
+    rut_testhelpers::commit_content(repository, &file, "Theirs\n", "Change file")?;
+    rut_testhelpers::run_command_string("rm gone.txt", repository)?;
+    rut_testhelpers::rut_commit("Remove gone file", repository)?;
+    rut_testhelpers::run_command_string(format!("switch {}", branch), repository)?;
+    rut_testhelpers::rut_merge_with_conflicts("other", repository)?;
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_unmerged_paths() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    create_merge_conflicts(&repository)?;
+    let base_id = hash_content("Base\n", &repository)?;
+    let ours_id = hash_content("Ours\n", &repository)?;
+    let theirs_id = hash_content("Theirs\n", &repository)?;
+    let null_id = ObjectId::null(repository.hash_algorithm());
+
+    // act
+    let porcelain_output = rut_testhelpers::run_command_string("status --porcelain", &repository)?;
+    let porcelain_v2_output =
+        rut_testhelpers::run_command_string("status --porcelain=v2", &repository)?;
+    let human_readable_output = rut_testhelpers::run_command_string("status", &repository)?;
+
+    // assert
+    assert_eq!(porcelain_output, "UU file.txt\nUD gone.txt\n");
+    assert_eq!(
+        porcelain_v2_output,
+        format!(
+            "u UU N... 100644 100644 100644 100644 {base_id} {ours_id} {theirs_id} file.txt\n\
+             u UD N... 100644 100644 000000 100644 {base_id} {ours_id} {null_id} gone.txt\n"
+        )
+    );
+    assert!(human_readable_output
+        .ends_with("Unmerged paths:\n\tboth modified: file.txt\n\tdeleted by them: gone.txt\n\n"));
+    // writing the index keeps the entries of each stage
+    assert_eq!(
+        rut_testhelpers::run_command_string("ls-files --stage", &repository)?,
+        format!(
+            "100644 {base_id} 1\tfile.txt\n\
+             100644 {ours_id} 2\tfile.txt\n\
+             100644 {theirs_id} 3\tfile.txt\n\
+             100644 {base_id} 1\tgone.txt\n\
+             100644 {ours_id} 2\tgone.txt\n"
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_adding_unmerged_path_resolves_it() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    create_merge_conflicts(&repository)?;
+    let workdir = repository.worktree().root();
+    fs::write(workdir.join("file.txt"), "Resolved\n")?;
+
+    // act
+    rut_testhelpers::rut_add(&workdir.join("file.txt"), &repository);
+
+    // assert
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "M  file.txt\nUD gone.txt\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_merge_in_progress() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    create_merge_conflicts(&repository)?;
+    let workdir = repository.worktree().root();
+    let unmerged_output = rut_testhelpers::run_command_string("status", &repository)?;
+    fs::write(workdir.join("file.txt"), "Resolved\n")?;
+    rut_testhelpers::rut_add(&workdir.join("file.txt"), &repository);
+    rut_testhelpers::rut_add(&workdir.join("gone.txt"), &repository);
+
+    // act
+    let resolved_output = rut_testhelpers::run_command_string("status", &repository)?;
+
+    // assert
+    assert!(unmerged_output.contains("\nYou have unmerged paths.\n\nUnmerged paths:\n"));
+    assert!(resolved_output.contains(
+        "\nAll conflicts fixed but you are still merging.\n\nChanges to be committed:\n"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_status_records_terminated_by_nul() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let old_file = workdir.join("old name.txt");
+    rut_testhelpers::commit_content(&repository, &old_file, "a\nb\nc\nd\n", "Initial commit")?;
+    fs::rename(&old_file, workdir.join("new\nname.txt"))?;
+    rut_testhelpers::rut_add(workdir, &repository);
+    fs::write(workdir.join("untracked file.txt"), "")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("status -z", &repository)?;
+    let porcelain_v2_output =
+        rut_testhelpers::run_command_string("status --porcelain=v2 -z", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "R  new\nname.txt\0old name.txt\0?? untracked file.txt\0"
+    );
+    assert!(
+        porcelain_v2_output.ends_with(" R100 new\nname.txt\0old name.txt\0? untracked file.txt\0")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_status_as_json() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content\n", "Initial commit")?;
+    fs::write(&file, "changed\n")?;
+    fs::write(workdir.join("say \"hi\".txt"), "")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("status --format=json", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        "{\"path\":\"file.txt\",\"place\":\"worktree\",\"change\":\"modified\"}\n\
+         {\"path\":\"say \\\"hi\\\".txt\",\"change\":\"untracked\"}\n"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use std::fs;
+
+use rut::refs::RefHandler;
+use rut::workspace::Repository;
+
+/// Create a repository with a single commit to use as the source of a submodule.
+fn create_submodule_source() -> rut::Result<Repository> {
+    let source = rut_testhelpers::create_repository();
+    let file = source.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&source, &file, "submodule content", "Initial commit")?;
+    Ok(source)
+}
+
+fn head_id(repository: &Repository) -> rut::Result<String> {
+    Ok(RefHandler::new(repository).head()?.to_string())
+}
+
+#[test]
+fn test_add_clones_and_stages_submodule() -> rut::Result<()> {
+    // arrange
+    let source = create_submodule_source()?;
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+
+    // act
+    rut_testhelpers::run_command_string(
+        format!("submodule add {} sub", source.worktree().root().display()),
+        &repository,
+    )?;
+
+    // assert
+    rut_testhelpers::assert_file_contains(&workdir.join("sub/file.txt"), "submodule content");
+    rut_testhelpers::assert_file_contains(
+        &workdir.join(".gitmodules"),
+        &format!(
+            "[submodule \"sub\"]\n\tpath = sub\n\turl = {}\n",
+            source.worktree().root().display()
+        ),
+    );
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&repository)?,
+        "A  .gitmodules\nA  sub\n"
+    );
+    let status = rut_testhelpers::run_command_string("submodule status", &repository)?;
+    assert_eq!(status, format!(" {} sub\n", head_id(&source)?));
+
+    Ok(())
+}
+
+#[test]
+fn test_add_refuses_remote_url() {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    // act
+    let result = rut_testhelpers::run_command_string(
+        "submodule add https://example.com/repo.git",
+        &repository,
+    );
+
+    // assert
+    assert!(result.is_err());
+    assert!(!repository.worktree().root().join("repo").exists());
+}
+
+#[test]
+fn test_update_refuses_names_and_paths_outside_of_repository() -> rut::Result<()> {
+    // arrange
+    let source = create_submodule_source()?;
+    let url = source.worktree().root().display();
+    for (name, path) in [
+        ("../../escaped", "sub"),
+        ("sub", "../sub"),
+        ("sub", ".GIT/hooks"),
+    ] {
+        let repository = rut_testhelpers::create_repository();
+        fs::write(
+            repository.worktree().root().join(".gitmodules"),
+            format!(
+                "[submodule \"{}\"]\n\tpath = {}\n\turl = {}\n",
+                name, path, url
+            ),
+        )?;
+
+        // act
+        let result = rut_testhelpers::run_command_string("submodule update", &repository);
+
+        // assert
+        assert!(result.is_err(), "accepted name {} with path {}", name, path);
+        assert!(!repository.git_dir().join("modules").exists());
+        assert!(!repository.worktree().root().join("escaped").exists());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_status_shows_modified_submodule() -> rut::Result<()> {
+    // arrange
+    let source = create_submodule_source()?;
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    rut_testhelpers::run_command_string(
+        format!("submodule add {} sub", source.worktree().root().display()),
+        &repository,
+    )?;
+    rut_testhelpers::rut_commit("Add submodule", &repository)?;
+
+    let submodule_repository = Repository::discover(workdir.join("sub"))?;
+    let file = workdir.join("sub/other.txt");
+    rut_testhelpers::commit_content(&submodule_repository, &file, "other", "Second commit")?;
+
+    // act
+    let porcelain = rut_testhelpers::rut_status_porcelain(&repository)?;
+    let status = rut_testhelpers::run_command_string("submodule status", &repository)?;
+
+    // assert
+    assert_eq!(porcelain, " M sub\n");
+    assert_eq!(
+        status,
+        format!("+{} sub\n", head_id(&submodule_repository)?)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_init_and_update_restore_missing_submodule() -> rut::Result<()> {
+    // arrange
+    let source = create_submodule_source()?;
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let git_dir = repository.git_dir();
+    rut_testhelpers::run_command_string(
+        format!("submodule add {} sub", source.worktree().root().display()),
+        &repository,
+    )?;
+    rut_testhelpers::rut_commit("Add submodule", &repository)?;
+
+    // make it look like a fresh clone of the superproject
+    fs::remove_dir_all(workdir.join("sub"))?;
+    fs::remove_dir_all(git_dir.join("modules"))?;
+    rut_testhelpers::run_command_string("config --unset submodule.sub.url", &repository)?;
+    let commit_id = head_id(&source)?;
+
+    // act
+    let status_before = rut_testhelpers::run_command_string("submodule status", &repository)?;
+    let init_output = rut_testhelpers::run_command_string("submodule init", &repository)?;
+    let update_output = rut_testhelpers::run_command_string("submodule update", &repository)?;
+
+    // assert
+    assert_eq!(status_before, format!("-{} sub\n", commit_id));
+    assert_eq!(
+        init_output,
+        format!(
+            "Submodule 'sub' ({}) registered for path 'sub'\n",
+            source.worktree().root().display()
+        )
+    );
+    assert!(update_output.ends_with(&format!(
+        "Submodule path 'sub': checked out '{}'\n",
+        commit_id
+    )));
+    rut_testhelpers::assert_file_contains(&workdir.join("sub/file.txt"), "submodule content");
+    let status_after = rut_testhelpers::run_command_string("submodule status", &repository)?;
+    assert_eq!(status_after, format!(" {} sub\n", commit_id));
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use std::fs;
+
+use rut_testhelpers::assert_file_contains;
+
+#[test]
+fn test_switch_to_branch_updates_worktree_and_head() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let new_file = repository.worktree().root().join("dir").join("new.txt");
+    rut_testhelpers::commit_content(&repository, &file, "first", "First commit")?;
+    rut_testhelpers::run_command_string("branch feature", &repository)?;
+    fs::create_dir(new_file.parent().unwrap())?;
+    fs::write(&new_file, "new")?;
+    rut_testhelpers::rut_add(&new_file, &repository);
+    rut_testhelpers::commit_content(&repository, &file, "second", "Second commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("switch feature", &repository)?;
+
+    // assert
+    assert_eq!(output, "Switched to branch 'feature'\n");
+    assert_file_contains(&file, "first");
+    assert!(!new_file.parent().unwrap().exists());
+    assert_file_contains(
+        &repository.git_dir().join("HEAD"),
+        "ref: refs/heads/feature",
+    );
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_on_detached_head_advances_head_only() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let first_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "first", "First commit")?;
+    let second_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "second", "Second commit")?;
+
+    // act
+    let switch_output = rut_testhelpers::run_command_string("switch --detach HEAD^", &repository)?;
+    let detached_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "detached", "Detached commit")?;
+    let status_output = rut_testhelpers::run_command_string("status", &repository)?;
+
+    // assert
+    assert_eq!(
+        switch_output,
+        format!("HEAD is now at {} First commit\n", &first_commit_id[..7])
+    );
+    assert_file_contains(
+        &repository.git_dir().join("HEAD"),
+        &format!("{}\n", detached_commit_id),
+    );
+    assert_file_contains(
+        &repository.git_dir().join("refs/heads/main"),
+        &format!("{}\n", second_commit_id),
+    );
+    assert!(status_output.starts_with(&format!("HEAD detached at {}\n", &detached_commit_id[..7])));
+
+    Ok(())
+}
+
+#[test]
+fn test_error_on_switch_overwriting_local_changes() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "first", "First commit")?;
+    rut_testhelpers::run_command_string("branch feature", &repository)?;
+    rut_testhelpers::commit_content(&repository, &file, "second", "Second commit")?;
+    fs::write(&file, "local change")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("switch feature", &repository);
+
+    // assert
+    match result {
+        Ok(_) => panic!("expected error on overwriting local changes"),
+        Err(error) => assert!(error.to_string().starts_with(
+            "fatal: Your local changes to the following files would be overwritten by checkout:\n\tfile.txt\n"
+        )),
+    }
+    assert_file_contains(&file, "local change");
+    assert_file_contains(&repository.git_dir().join("HEAD"), "ref: refs/heads/main");
+
+    Ok(())
+}
+
+#[test]
+fn test_switch_create_at_start_point() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    let first_commit_id =
+        rut_testhelpers::commit_content(&repository, &file, "first", "First commit")?;
+    rut_testhelpers::commit_content(&repository, &file, "second", "Second commit")?;
+
+    // act
+    let output = rut_testhelpers::run_command_string("switch -c feature HEAD^", &repository)?;
+
+    // assert
+    assert_eq!(output, "Switched to a new branch 'feature'\n");
+    assert_file_contains(&file, "first");
+    assert_file_contains(
+        &repository.git_dir().join("refs/heads/feature"),
+        &format!("{}\n", first_commit_id),
+    );
+    assert_file_contains(
+        &repository.git_dir().join("HEAD"),
+        "ref: refs/heads/feature",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_failed_switch_create_does_not_leave_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "first", "First commit")?;
+    rut_testhelpers::commit_content(&repository, &file, "second", "Second commit")?;
+    fs::write(&file, "local change")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("switch -c feature HEAD^", &repository);
+
+    // assert
+    assert!(result.is_err());
+    assert!(!repository.git_dir().join("refs/heads/feature").exists());
+    assert_file_contains(&file, "local change");
+    assert_file_contains(&repository.git_dir().join("HEAD"), "ref: refs/heads/main");
+
+    Ok(())
+}
+
+#[test]
+fn test_switch_create_with_locked_index_does_not_create_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "first", "First commit")?;
+    fs::write(repository.git_dir().join("index.lock"), "")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("switch -c feature", &repository);
+
+    // assert
+    assert!(result.is_err());
+    assert!(!repository.git_dir().join("refs/heads/feature").exists());
+
+    Ok(())
+}
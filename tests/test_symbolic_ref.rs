@@ -0,0 +1,33 @@
+#[test]
+fn test_symbolic_ref_points_head_to_branch() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    rut_testhelpers::rut_commit("Initial commit", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string("symbolic-ref HEAD refs/heads/unborn", &repository)?;
+    let output = rut_testhelpers::run_command_string("symbolic-ref HEAD", &repository)?;
+    let short_output =
+        rut_testhelpers::run_command_string("symbolic-ref --short HEAD", &repository)?;
+
+    // assert
+    assert_eq!(output, "refs/heads/unborn\n");
+    assert_eq!(short_output, "unborn\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_symbolic_ref_refuses_target_outside_refs() {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+
+    // act
+    let result = rut_testhelpers::run_command_string("symbolic-ref HEAD ORIG_HEAD", &repository);
+
+    // assert
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "fatal: Refusing to point HEAD outside of refs/"
+    );
+}
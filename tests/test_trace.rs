@@ -0,0 +1,33 @@
+#[test]
+fn test_trace_ref_updates_and_locks_to_stderr() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let run_branch = |name: &str, trace: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_rut"))
+            .args(["branch", name])
+            .current_dir(repository.worktree().root())
+            .env("RUT_TRACE", trace)
+            .output()
+            .unwrap()
+    };
+
+    // act
+    let traced = run_branch("traced", "1");
+    let untraced = run_branch("untraced", "0");
+
+    // assert
+    let stderr = String::from_utf8_lossy(&traced.stderr);
+    assert!(traced.status.success());
+    assert!(stderr.contains("trace: command: rut branch traced\n"));
+    assert!(stderr.contains("trace: lock: acquired "));
+    assert!(stderr.contains(&format!(
+        "trace: ref: update refs/heads/traced to {}\n",
+        commit_id
+    )));
+    assert!(stderr.contains("trace: command: finished in "));
+    assert!(untraced.status.success());
+    assert!(untraced.stderr.is_empty());
+
+    Ok(())
+}
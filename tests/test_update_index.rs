@@ -0,0 +1,77 @@
+use std::fs;
+
+#[test]
+fn test_update_index_requires_add_and_remove_flags() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Content\n", "Initial commit")?;
+    fs::write(workdir.join("new.txt"), "New\n")?;
+    fs::remove_file(&file)?;
+
+    // act
+    let add_without_flag = rut_testhelpers::run_command_string("update-index new.txt", &repository);
+    let remove_without_flag =
+        rut_testhelpers::run_command_string("update-index file.txt", &repository);
+    rut_testhelpers::run_command_string(
+        "update-index --add --remove new.txt file.txt",
+        &repository,
+    )?;
+
+    // assert
+    assert!(add_without_flag.is_err());
+    assert!(remove_without_flag.is_err());
+    let status = rut_testhelpers::rut_status_porcelain(&repository)?;
+    assert_eq!(status, "D  file.txt\nA  new.txt\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_update_index_cacheinfo_adds_entry_without_file() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let workdir = repository.worktree().root();
+    let file = workdir.join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Content\n", "Initial commit")?;
+    let blob_id = rut_testhelpers::run_command_string("rev-parse HEAD:file.txt", &repository)?;
+
+    // act
+    rut_testhelpers::run_command_string(
+        format!(
+            "update-index --add --cacheinfo 100755,{},copy.sh",
+            blob_id.trim()
+        ),
+        &repository,
+    )?;
+
+    // assert
+    let staged = rut_testhelpers::run_command_string("ls-files -s", &repository)?;
+    assert!(staged.contains(&format!("100755 {} 0\tcopy.sh\n", blob_id.trim())));
+    rut_testhelpers::run_command_string("checkout-index copy.sh", &repository)?;
+    rut_testhelpers::assert_file_contains(&workdir.join("copy.sh"), "Content\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_update_index_refresh_reports_files_that_need_update() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "Content\n", "Initial commit")?;
+    fs::write(&file, "Modified\n")?;
+
+    // act
+    let (exit_status, output) = rut_testhelpers::run_command_string_with_exit_status(
+        "update-index --refresh",
+        &repository,
+    )?;
+
+    // assert
+    assert_eq!(exit_status, 1);
+    assert_eq!(output, "file.txt: needs update\n");
+
+    Ok(())
+}
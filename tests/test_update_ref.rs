@@ -0,0 +1,43 @@
+use rut::refs::RefHandler;
+
+#[test]
+fn test_update_ref_creates_and_deletes_ref() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let commit_id = rut_testhelpers::rut_commit("Initial commit", &repository)?;
+    let command = format!("update-ref refs/tags/v1 {}", commit_id.trim());
+
+    // act
+    rut_testhelpers::run_command_string(&command, &repository)?;
+    let refs = RefHandler::new(&repository);
+    let tag_id = refs.read_ref("refs/tags/v1")?;
+    rut_testhelpers::run_command_string("update-ref -d refs/tags/v1", &repository)?;
+
+    // assert
+    assert_eq!(tag_id.unwrap().to_string(), commit_id.trim());
+    assert_eq!(refs.read_ref("refs/tags/v1")?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_update_ref_fails_when_old_value_does_not_match() -> rut::Result<()> {
+    // arrange
+    let repository = rut_testhelpers::create_repository();
+    let first_commit = rut_testhelpers::rut_commit("First commit", &repository)?;
+    let second_commit = rut_testhelpers::rut_commit("Second commit", &repository)?;
+    let command = format!("update-ref HEAD {} HEAD~1", first_commit.trim());
+
+    // act
+    let result = rut_testhelpers::run_command_string(&command, &repository);
+
+    // assert
+    let message = result.unwrap_err().to_string();
+    assert!(message.contains(&format!(
+        "is at {} but expected {}",
+        second_commit.trim(),
+        first_commit.trim()
+    )));
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use std::fs;
+use std::path::PathBuf;
+
+use rut::refs::RefHandler;
+use rut::workspace::Repository;
+
+fn create_repository_with_commit() -> rut::Result<Repository> {
+    let repository = rut_testhelpers::create_repository();
+    let file = repository.worktree().root().join("file.txt");
+    rut_testhelpers::commit_content(&repository, &file, "content", "Initial commit")?;
+    Ok(repository)
+}
+
+fn add_worktree(repository: &Repository) -> rut::Result<PathBuf> {
+    let worktree_path = rut_testhelpers::create_temporary_directory().join("linked");
+    rut_testhelpers::run_command_string(
+        format!("worktree add {}", worktree_path.display()),
+        repository,
+    )?;
+    Ok(worktree_path)
+}
+
+#[test]
+fn test_add_checks_out_new_branch_in_linked_worktree() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    let worktree_path = rut_testhelpers::create_temporary_directory().join("linked");
+
+    // act
+    let output = rut_testhelpers::run_command_string(
+        format!("worktree add {}", worktree_path.display()),
+        &repository,
+    )?;
+
+    // assert
+    let head_id = RefHandler::new(&repository).head()?;
+    assert_eq!(
+        output,
+        format!(
+            "Preparing worktree (new branch 'linked')\nHEAD is now at {} Initial commit\n",
+            head_id.to_short_string()
+        )
+    );
+    rut_testhelpers::assert_file_contains(&worktree_path.join("file.txt"), "content");
+
+    let linked_repository = Repository::discover(&worktree_path)?;
+    assert_eq!(linked_repository.common_dir(), repository.git_dir());
+    assert_eq!(linked_repository.head()?, "linked");
+    assert_eq!(
+        rut_testhelpers::rut_status_porcelain(&linked_repository)?,
+        ""
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_in_linked_worktree_updates_shared_branch() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    let worktree_path = add_worktree(&repository)?;
+    let linked_repository = Repository::discover(&worktree_path)?;
+    let file = worktree_path.join("other.txt");
+
+    // act
+    rut_testhelpers::commit_content(&linked_repository, &file, "other", "Linked commit")?;
+
+    // assert
+    let linked_head = RefHandler::new(&linked_repository).head()?;
+    let branch = RefHandler::new(&repository).find_branch("linked")?;
+    assert_eq!(branch, Some(linked_head));
+    assert_eq!(rut_testhelpers::rut_status_porcelain(&repository)?, "");
+
+    Ok(())
+}
+
+#[test]
+fn test_list_shows_main_and_linked_worktrees() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    let worktree_path = add_worktree(&repository)?;
+    let main_path = repository.worktree().root().display().to_string();
+    let linked_path = worktree_path.display().to_string();
+    let width = main_path.len().max(linked_path.len());
+    let head_id = RefHandler::new(&repository).head()?.to_short_string();
+
+    // act
+    let output = rut_testhelpers::run_command_string("worktree list", &repository)?;
+
+    // assert
+    assert_eq!(
+        output,
+        format!(
+            "{:width$} {} [main]\n{:width$} {} [linked]\n",
+            main_path, head_id, linked_path, head_id
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_add_refuses_branch_checked_out_elsewhere() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    let worktree_path = rut_testhelpers::create_temporary_directory().join("linked");
+
+    // act
+    let result = rut_testhelpers::run_command_string(
+        format!("worktree add {} main", worktree_path.display()),
+        &repository,
+    );
+
+    // assert
+    assert!(result.is_err());
+    assert!(!worktree_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_switch_refuses_branch_checked_out_in_linked_worktree() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    add_worktree(&repository)?;
+
+    // act
+    let result = rut_testhelpers::run_command_string("switch linked", &repository);
+
+    // assert
+    assert!(result.is_err());
+    assert_eq!(repository.head()?, "main");
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_refuses_modified_worktree_without_force() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    let worktree_path = add_worktree(&repository)?;
+    fs::write(worktree_path.join("file.txt"), "modified")?;
+
+    // act
+    let result = rut_testhelpers::run_command_string(
+        format!("worktree remove {}", worktree_path.display()),
+        &repository,
+    );
+
+    // assert
+    assert!(result.is_err());
+    assert!(worktree_path.exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_remove_with_force_deletes_worktree_and_metadata() -> rut::Result<()> {
+    // arrange
+    let repository = create_repository_with_commit()?;
+    let worktree_path = add_worktree(&repository)?;
+    fs::write(worktree_path.join("untracked.txt"), "untracked")?;
+
+    // act
+    rut_testhelpers::run_command_string(
+        format!("worktree remove --force {}", worktree_path.display()),
+        &repository,
+    )?;
+
+    // assert
+    assert!(!worktree_path.exists());
+    assert!(!repository.git_dir().join("worktrees/linked").exists());
+    assert!(RefHandler::new(&repository)
+        .find_branch("linked")?
+        .is_some());
+
+    Ok(())
+}